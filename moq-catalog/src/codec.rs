@@ -0,0 +1,340 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Error;
+
+/// A codec string (RFC 6381), parsed for the codecs we understand.
+///
+/// A malformed string for a known codec is rejected, while an unknown codec is kept verbatim.
+/// A known codec without any parameters, such as `avc1`, is also kept verbatim since it can't be matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Codec {
+	H264(H264),
+	H265(H265),
+	AV1(AV1),
+	Mp4a(Mp4a),
+	Opus,
+	Unknown(String),
+}
+
+impl Codec {
+	pub fn is_video(&self) -> bool {
+		matches!(self, Self::H264(_) | Self::H265(_) | Self::AV1(_))
+	}
+
+	pub fn is_audio(&self) -> bool {
+		matches!(self, Self::Mp4a(_) | Self::Opus)
+	}
+
+	/// Returns true if a decoder with this capability can decode the other codec.
+	///
+	/// The capability is the most demanding stream supported, such as the highest level of a profile.
+	pub fn supports(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Self::H264(cap), Self::H264(other)) => cap.profile == other.profile && cap.level >= other.level,
+			(Self::H265(cap), Self::H265(other)) => {
+				cap.profile == other.profile && (cap.high_tier || !other.high_tier) && cap.level >= other.level
+			}
+			(Self::AV1(cap), Self::AV1(other)) => {
+				cap.profile >= other.profile
+					&& cap.level >= other.level
+					&& (cap.high_tier || !other.high_tier)
+					&& cap.bitdepth >= other.bitdepth
+			}
+			(Self::Mp4a(cap), Self::Mp4a(other)) => {
+				cap.object_type == other.object_type
+					&& (cap.audio_object_type.is_none() || cap.audio_object_type == other.audio_object_type)
+			}
+			(Self::Opus, Self::Opus) => true,
+			(Self::Unknown(cap), Self::Unknown(other)) => cap == other,
+			_ => false,
+		}
+	}
+}
+
+impl FromStr for Codec {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || Error::InvalidCodec(s.to_string());
+		let (prefix, params) = s.split_once('.').unwrap_or((s, ""));
+
+		Ok(match prefix {
+			"opus" if params.is_empty() => Self::Opus,
+			_ if params.is_empty() => Self::Unknown(s.to_string()),
+			"avc1" => Self::H264(H264::parse(params).ok_or_else(invalid)?),
+			"hev1" | "hvc1" => Self::H265(H265::parse(prefix == "hvc1", params).ok_or_else(invalid)?),
+			"av01" => Self::AV1(AV1::parse(params).ok_or_else(invalid)?),
+			"mp4a" => Self::Mp4a(Mp4a::parse(params).ok_or_else(invalid)?),
+			_ => Self::Unknown(s.to_string()),
+		})
+	}
+}
+
+impl fmt::Display for Codec {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::H264(codec) => codec.fmt(f),
+			Self::H265(codec) => codec.fmt(f),
+			Self::AV1(codec) => codec.fmt(f),
+			Self::Mp4a(codec) => codec.fmt(f),
+			Self::Opus => write!(f, "opus"),
+			Self::Unknown(codec) => write!(f, "{}", codec),
+		}
+	}
+}
+
+impl Serialize for Codec {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
+impl<'de> Deserialize<'de> for Codec {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let s = String::deserialize(deserializer)?;
+		s.parse().map_err(serde::de::Error::custom)
+	}
+}
+
+/// H.264, from `avc1.PPCCLL`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct H264 {
+	pub profile: u8,
+	pub constraints: u8,
+	pub level: u8,
+}
+
+impl H264 {
+	fn parse(params: &str) -> Option<Self> {
+		if params.len() != 6 || !params.is_ascii() {
+			return None;
+		}
+
+		Some(Self {
+			profile: u8::from_str_radix(&params[0..2], 16).ok()?,
+			constraints: u8::from_str_radix(&params[2..4], 16).ok()?,
+			level: u8::from_str_radix(&params[4..6], 16).ok()?,
+		})
+	}
+}
+
+impl fmt::Display for H264 {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "avc1.{:02x}{:02x}{:02x}", self.profile, self.constraints, self.level)
+	}
+}
+
+/// H.265, from `hev1.P.C.TL.B` or `hvc1.P.C.TL.B`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct H265 {
+	/// True for `hvc1`, where the parameter sets are only in the init segment.
+	pub hvc1: bool,
+
+	/// The profile space, where 0 is omitted and 1-3 are written as A-C.
+	pub profile_space: u8,
+	pub profile: u8,
+	pub compatibility: u32,
+	pub high_tier: bool,
+	pub level: u8,
+	pub constraints: Vec<u8>,
+}
+
+impl H265 {
+	fn parse(hvc1: bool, params: &str) -> Option<Self> {
+		let mut parts = params.split('.');
+
+		let profile = parts.next()?;
+		let (profile_space, profile) = match profile.strip_prefix(['A', 'B', 'C']) {
+			Some(rest) => (profile.as_bytes()[0] - b'A' + 1, rest),
+			None => (0, profile),
+		};
+
+		let compatibility = u32::from_str_radix(parts.next()?, 16).ok()?;
+
+		let level = parts.next()?;
+		let high_tier = match level.get(..1)? {
+			"L" => false,
+			"H" => true,
+			_ => return None,
+		};
+
+		let constraints = parts
+			.map(|constraint| u8::from_str_radix(constraint, 16).ok())
+			.collect::<Option<_>>()?;
+
+		Some(Self {
+			hvc1,
+			profile_space,
+			profile: profile.parse().ok()?,
+			compatibility,
+			high_tier,
+			level: level[1..].parse().ok()?,
+			constraints,
+		})
+	}
+}
+
+impl fmt::Display for H265 {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}.", if self.hvc1 { "hvc1" } else { "hev1" })?;
+		if self.profile_space > 0 {
+			write!(f, "{}", (b'A' + self.profile_space - 1) as char)?;
+		}
+
+		let tier = if self.high_tier { 'H' } else { 'L' };
+		write!(f, "{}.{:X}.{}{}", self.profile, self.compatibility, tier, self.level)?;
+
+		for constraint in &self.constraints {
+			write!(f, ".{:X}", constraint)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// AV1, from `av01.P.LLT.DD` with any optional fields kept verbatim.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AV1 {
+	pub profile: u8,
+	pub level: u8,
+	pub high_tier: bool,
+	pub bitdepth: u8,
+	pub extra: Option<String>,
+}
+
+impl AV1 {
+	fn parse(params: &str) -> Option<Self> {
+		let mut parts = params.splitn(4, '.');
+
+		let profile = parts.next()?.parse().ok()?;
+
+		let level = parts.next()?;
+		if level.len() != 3 || !level.is_ascii() {
+			return None;
+		}
+
+		let high_tier = match &level[2..] {
+			"M" => false,
+			"H" => true,
+			_ => return None,
+		};
+
+		let bitdepth = parts.next()?;
+		if bitdepth.len() != 2 {
+			return None;
+		}
+
+		Some(Self {
+			profile,
+			level: level[..2].parse().ok()?,
+			high_tier,
+			bitdepth: bitdepth.parse().ok()?,
+			extra: parts.next().map(str::to_string),
+		})
+	}
+}
+
+impl fmt::Display for AV1 {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let tier = if self.high_tier { 'H' } else { 'M' };
+		write!(
+			f,
+			"av01.{}.{:02}{}.{:02}",
+			self.profile, self.level, tier, self.bitdepth
+		)?;
+
+		if let Some(extra) = &self.extra {
+			write!(f, ".{}", extra)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// MPEG-4 audio, from `mp4a.OO.A`, such as `mp4a.40.2` for AAC-LC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mp4a {
+	/// The object type indication in hex, such as 0x40 for MPEG-4 audio.
+	pub object_type: u8,
+
+	/// The audio object type in decimal, such as 2 for AAC-LC.
+	pub audio_object_type: Option<u8>,
+}
+
+impl Mp4a {
+	fn parse(params: &str) -> Option<Self> {
+		let (object_type, audio_object_type) = match params.split_once('.') {
+			Some((object_type, audio_object_type)) => (object_type, Some(audio_object_type.parse().ok()?)),
+			None => (params, None),
+		};
+
+		Some(Self {
+			object_type: u8::from_str_radix(object_type, 16).ok()?,
+			audio_object_type,
+		})
+	}
+}
+
+impl fmt::Display for Mp4a {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "mp4a.{:02x}", self.object_type)?;
+
+		if let Some(audio_object_type) = self.audio_object_type {
+			write!(f, ".{}", audio_object_type)?;
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn parse() {
+		for s in [
+			"avc1.64001f",
+			"hev1.1.6.L93.B0",
+			"hvc1.A1.60.H120.90.0",
+			"av01.0.04M.08",
+			"av01.0.04M.10.0.112.09.16.09.0",
+			"mp4a.40.2",
+			"mp4a.6b",
+			"opus",
+			"avc1",
+			"vp09.00.10.08",
+		] {
+			let codec: Codec = s.parse().unwrap();
+			assert_eq!(codec.to_string(), s);
+		}
+
+		assert_eq!(
+			"avc1.64001f".parse::<Codec>().unwrap(),
+			Codec::H264(H264 {
+				profile: 0x64,
+				constraints: 0,
+				level: 0x1f
+			})
+		);
+
+		for s in ["avc1.64001", "avc1.zz001f", "hev1.1.6.X93", "av01.0.4M.08", "mp4a.zz.2"] {
+			assert!(matches!(s.parse::<Codec>(), Err(Error::InvalidCodec(_))), "{}", s);
+		}
+	}
+
+	#[test]
+	fn supports() {
+		let cap: Codec = "avc1.640028".parse().unwrap();
+		assert!(cap.supports(&"avc1.64001f".parse().unwrap()));
+		assert!(!cap.supports(&"avc1.640033".parse().unwrap()));
+		assert!(!cap.supports(&"avc1.42001f".parse().unwrap()));
+		assert!(!cap.supports(&Codec::Opus));
+
+		let cap: Codec = "av01.0.08M.10".parse().unwrap();
+		assert!(cap.supports(&"av01.0.04M.08".parse().unwrap()));
+		assert!(!cap.supports(&"av01.0.04H.08".parse().unwrap()));
+	}
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "e2ee")]
+use {crate::Error, moq_transport::e2ee::TrackKey};
+
+/// How the payloads of a track are encrypted, see [moq_transport::e2ee].
+///
+/// The key is either distributed out of band and identified by `keyId`,
+/// or sealed with a key shared by all authorized subscribers and embedded in the catalog.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct TrackEncryption {
+	pub scheme: String,
+
+	#[serde(rename = "keyId", skip_serializing_if = "Option::is_none")]
+	pub key_id: Option<String>,
+
+	/// The hex encoded track key, sealed with the shared key.
+	#[serde(rename = "sealedKey", skip_serializing_if = "Option::is_none")]
+	pub sealed_key: Option<String>,
+}
+
+impl TrackEncryption {
+	/// The only scheme supported, matching [moq_transport::e2ee].
+	pub const SCHEME: &'static str = "xchacha20poly1305";
+
+	/// The track key is distributed out of band with the given ID.
+	pub fn new(key_id: String) -> Self {
+		Self {
+			scheme: Self::SCHEME.to_string(),
+			key_id: Some(key_id),
+			sealed_key: None,
+		}
+	}
+
+	/// Embed the track key, sealed with the shared key.
+	#[cfg(feature = "e2ee")]
+	pub fn sealed(key: &TrackKey, shared: &TrackKey) -> Self {
+		Self {
+			scheme: Self::SCHEME.to_string(),
+			key_id: None,
+			sealed_key: Some(hex::encode(shared.seal(key))),
+		}
+	}
+
+	/// Recover the track key embedded by [Self::sealed].
+	#[cfg(feature = "e2ee")]
+	pub fn unseal(&self, shared: &TrackKey) -> Result<TrackKey, Error> {
+		if self.scheme != Self::SCHEME {
+			return Err(Error::UnsupportedEncryption(self.scheme.clone()));
+		}
+
+		let sealed = self.sealed_key.as_ref().ok_or(Error::MissingKey)?;
+		let sealed = hex::decode(sealed).map_err(|_| moq_transport::serve::ServeError::Decrypt)?;
+
+		Ok(shared.unseal(&sealed)?)
+	}
+}
@@ -0,0 +1,40 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+	#[error("json error: {0}")]
+	Json(#[from] serde_json::Error),
+
+	#[error("unsupported catalog version: {0}")]
+	UnsupportedVersion(u16),
+
+	#[error("invalid patch: {0}")]
+	Patch(String),
+
+	#[error("patch without a snapshot")]
+	MissingSnapshot,
+
+	#[error("invalid codec: {0}")]
+	InvalidCodec(String),
+
+	#[error("empty catalog group")]
+	Empty,
+
+	#[error("duplicate track: {0}")]
+	Duplicate(String),
+
+	#[error("missing track: {0}")]
+	Missing(String),
+
+	#[error("write before the first keyframe")]
+	MissingKeyframe,
+
+	#[error("unsupported encryption: {0}")]
+	UnsupportedEncryption(String),
+
+	#[error("missing sealed key")]
+	MissingKey,
+
+	#[error("serve error: {0}")]
+	Serve(#[from] moq_transport::serve::ServeError),
+}
@@ -5,6 +5,24 @@
 /// https://www.ietf.org/archive/id/draft-ietf-moq-catalogformat-01.html
 use serde::{Deserialize, Serialize};
 
+mod codec;
+mod encryption;
+mod error;
+mod patch;
+mod reader;
+mod select;
+mod simulcast;
+mod writer;
+
+pub use codec::*;
+pub use encryption::*;
+pub use error::*;
+pub use patch::*;
+pub use reader::*;
+pub use select::*;
+pub use simulcast::*;
+pub use writer::*;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Root {
 	pub version: u16,
@@ -24,7 +42,91 @@ pub struct Root {
 	pub tracks: Vec<Track>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+impl Root {
+	/// The catalog version produced and understood by this crate.
+	pub const VERSION: u16 = 1;
+
+	/// Parse a catalog, rejecting any version we don't understand.
+	///
+	/// Unknown fields are ignored so minor additions remain forward compatible.
+	pub fn from_slice(v: &[u8]) -> Result<Self, Error> {
+		// Peek at the version first so a newer catalog produces a useful error instead of a serde error.
+		#[derive(Deserialize)]
+		struct Version {
+			version: u16,
+		}
+
+		let Version { version } = serde_json::from_slice(v)?;
+		if version != Self::VERSION {
+			return Err(Error::UnsupportedVersion(version));
+		}
+
+		Ok(serde_json::from_slice(v)?)
+	}
+}
+
+impl Root {
+	/// Parse a catalog split across multiple frames of a group.
+	///
+	/// The first frame is the base document and each later frame is a JSON object merged into it:
+	/// objects are merged recursively, arrays (ex. `tracks`) are appended and anything else is replaced.
+	/// A later frame may instead be a JSON array, which is applied as a [Patch].
+	pub fn from_frames<T: AsRef<[u8]>>(frames: &[T]) -> Result<Self, Error> {
+		let (base, extensions) = frames.split_first().ok_or(Error::Empty)?;
+
+		// The common case is a single frame, so skip the intermediate Value.
+		if extensions.is_empty() {
+			return Self::from_slice(base.as_ref());
+		}
+
+		let mut root: serde_json::Value = serde_json::from_slice(base.as_ref())?;
+		for extension in extensions {
+			extend(&mut root, extension.as_ref())?;
+		}
+
+		Self::from_slice(&serde_json::to_vec(&root)?)
+	}
+}
+
+// Apply a later frame of a group, which is either a patch or an object to merge.
+pub(crate) fn extend(root: &mut serde_json::Value, frame: &[u8]) -> Result<(), Error> {
+	match serde_json::from_slice(frame)? {
+		serde_json::Value::Array(ops) => serde_json::from_value::<Patch>(serde_json::Value::Array(ops))?.apply(root),
+		extension => {
+			merge(root, extension);
+			Ok(())
+		}
+	}
+}
+
+fn merge(base: &mut serde_json::Value, extension: serde_json::Value) {
+	use serde_json::Value;
+
+	match (base, extension) {
+		(Value::Object(base), Value::Object(extension)) => {
+			for (key, value) in extension {
+				match base.get_mut(&key) {
+					Some(existing) => merge(existing, value),
+					None => {
+						base.insert(key, value);
+					}
+				}
+			}
+		}
+		(Value::Array(base), Value::Array(extension)) => base.extend(extension),
+		(base, extension) => *base = extension,
+	}
+}
+
+impl std::str::FromStr for Root {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::from_slice(s.as_bytes())
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Track {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub namespace: Option<String>,
@@ -57,6 +159,10 @@ pub struct Track {
 
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub depends: Option<Vec<String>>,
+
+	/// Set when the payloads are encrypted end to end.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub encryption: Option<TrackEncryption>,
 }
 
 impl Track {
@@ -87,9 +193,9 @@ pub enum TrackPackaging {
 	Loc,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct SelectionParam {
-	pub codec: Option<String>,
+	pub codec: Option<Codec>,
 
 	#[serde(rename = "mimeType")]
 	#[serde(skip_serializing_if = "Option::is_none")]
@@ -188,3 +294,92 @@ impl CommonTrackFields {
 		common
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn version() {
+		let catalog = r#"{"version":1,"streamingFormat":1,"streamingFormatVersion":"0.2","supportsDeltaUpdates":true,"commonTrackFields":{},"tracks":[],"unknownField":true}"#;
+		let root: Root = catalog.parse().unwrap();
+		assert_eq!(root.version, Root::VERSION);
+
+		let catalog = catalog.replace(r#""version":1"#, r#""version":2"#);
+		assert!(matches!(
+			Root::from_slice(catalog.as_bytes()),
+			Err(Error::UnsupportedVersion(2))
+		));
+	}
+
+	#[test]
+	fn frames() {
+		let base = r#"{"version":1,"streamingFormat":1,"streamingFormatVersion":"0.2","supportsDeltaUpdates":false,"commonTrackFields":{"packaging":"cmaf"},"tracks":[{"name":"video","selectionParams":{"codec":"avc1"}}]}"#;
+		let audio = r#"{"tracks":[{"name":"audio","selectionParams":{"codec":"opus"}}]}"#;
+		let common = r#"{"commonTrackFields":{"renderGroup":1}}"#;
+
+		let root = Root::from_frames(&[base]).unwrap();
+		assert_eq!(root.tracks.len(), 1);
+
+		let root = Root::from_frames(&[base, audio, common]).unwrap();
+		assert_eq!(root.tracks.len(), 2);
+		assert_eq!(root.tracks[1].name, "audio");
+		assert_eq!(root.common_track_fields.render_group, Some(1));
+		assert!(root.common_track_fields.packaging.is_some());
+
+		let patch = r#"[{"op":"remove","path":"/tracks/0"}]"#;
+		let root = Root::from_frames(&[base, audio, patch]).unwrap();
+		assert_eq!(root.tracks.len(), 1);
+		assert_eq!(root.tracks[0].name, "audio");
+
+		assert!(matches!(Root::from_frames::<&str>(&[]), Err(Error::Empty)));
+	}
+
+	#[test]
+	fn patch() {
+		let mut decoder = Decoder::default();
+
+		let patch = r#"[{"op":"add","path":"/tracks/-","value":{"name":"audio","selectionParams":{"codec":"opus"}}}]"#;
+		assert!(matches!(decoder.decode(patch.as_bytes()), Err(Error::MissingSnapshot)));
+
+		let snapshot = r#"{"version":1,"streamingFormat":1,"streamingFormatVersion":"0.2","supportsDeltaUpdates":true,"commonTrackFields":{},"tracks":[{"name":"video","selectionParams":{"codec":"avc1"}}]}"#;
+		let root = decoder.decode(snapshot.as_bytes()).unwrap();
+		assert_eq!(root.tracks.len(), 1);
+
+		let root = decoder.decode(patch.as_bytes()).unwrap();
+		assert_eq!(root.tracks.len(), 2);
+		assert_eq!(root.tracks[1].name, "audio");
+
+		let patch = r#"[{"op":"replace","path":"/tracks/0/name","value":"video2"},{"op":"remove","path":"/tracks/1"}]"#;
+		let root = decoder.decode(patch.as_bytes()).unwrap();
+		assert_eq!(root.tracks.len(), 1);
+		assert_eq!(root.tracks[0].name, "video2");
+
+		// A failed patch leaves the catalog untouched.
+		let patch = r#"[{"op":"remove","path":"/tracks/0"},{"op":"remove","path":"/tracks/5"}]"#;
+		assert!(matches!(decoder.decode(patch.as_bytes()), Err(Error::Patch(_))));
+		assert_eq!(decoder.decode(b"[]").unwrap().tracks.len(), 1);
+	}
+
+	#[test]
+	fn encryption() {
+		use moq_transport::e2ee::TrackKey;
+
+		let shared = TrackKey::generate();
+		let key = TrackKey::generate();
+
+		let encryption = serde_json::to_string(&TrackEncryption::sealed(&key, &shared)).unwrap();
+		let catalog = format!(
+			r#"{{"version":1,"streamingFormat":1,"streamingFormatVersion":"0.2","supportsDeltaUpdates":false,"commonTrackFields":{{}},"tracks":[{{"name":"video","selectionParams":{{"codec":"avc1"}},"encryption":{}}}]}}"#,
+			encryption
+		);
+
+		let root: Root = catalog.parse().unwrap();
+		let encryption = root.tracks[0].encryption.as_ref().unwrap();
+		assert_eq!(encryption.unseal(&shared).unwrap().to_bytes(), key.to_bytes());
+		assert!(encryption.unseal(&key).is_err());
+
+		let encryption = TrackEncryption::new("abc".to_string());
+		assert!(matches!(encryption.unseal(&shared), Err(Error::MissingKey)));
+	}
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Error, Root};
+
+/// A delta update to a catalog, using the JSON Patch format (RFC 6902).
+///
+/// Only the add, remove, replace and test operations are supported.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(transparent)]
+pub struct Patch(pub Vec<PatchOp>);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+	Add { path: String, value: Value },
+	Remove { path: String },
+	Replace { path: String, value: Value },
+	Test { path: String, value: Value },
+}
+
+impl Patch {
+	/// Apply the patch to a JSON value, leaving it untouched on error.
+	pub fn apply(&self, target: &mut Value) -> Result<(), Error> {
+		let mut patched = target.clone();
+		for op in &self.0 {
+			op.apply(&mut patched)?;
+		}
+
+		*target = patched;
+		Ok(())
+	}
+}
+
+impl PatchOp {
+	fn apply(&self, target: &mut Value) -> Result<(), Error> {
+		match self {
+			Self::Add { path, value } => {
+				let (parent, key) = Self::parent(target, path)?;
+				match parent {
+					Value::Object(map) => {
+						map.insert(key, value.clone());
+					}
+					Value::Array(array) if key == "-" => array.push(value.clone()),
+					Value::Array(array) => {
+						let index = Self::index(&key, array.len() + 1, path)?;
+						array.insert(index, value.clone());
+					}
+					_ => return Err(Error::Patch(path.clone())),
+				}
+			}
+			Self::Remove { path } => {
+				let (parent, key) = Self::parent(target, path)?;
+				match parent {
+					Value::Object(map) => {
+						map.remove(&key).ok_or_else(|| Error::Patch(path.clone()))?;
+					}
+					Value::Array(array) => {
+						let index = Self::index(&key, array.len(), path)?;
+						array.remove(index);
+					}
+					_ => return Err(Error::Patch(path.clone())),
+				}
+			}
+			Self::Replace { path, value } => {
+				let existing = target.pointer_mut(path).ok_or_else(|| Error::Patch(path.clone()))?;
+				*existing = value.clone();
+			}
+			Self::Test { path, value } => {
+				if target.pointer(path) != Some(value) {
+					return Err(Error::Patch(path.clone()));
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	// Returns the parent of the path and the unescaped final key.
+	fn parent<'a>(target: &'a mut Value, path: &str) -> Result<(&'a mut Value, String), Error> {
+		let (parent, key) = path.rsplit_once('/').ok_or_else(|| Error::Patch(path.to_string()))?;
+		let parent = target
+			.pointer_mut(parent)
+			.ok_or_else(|| Error::Patch(path.to_string()))?;
+		let key = key.replace("~1", "/").replace("~0", "~");
+
+		Ok((parent, key))
+	}
+
+	fn index(key: &str, len: usize, path: &str) -> Result<usize, Error> {
+		match key.parse::<usize>() {
+			Ok(index) if index < len => Ok(index),
+			_ => Err(Error::Patch(path.to_string())),
+		}
+	}
+}
+
+/// A single catalog object, which is either a full snapshot or a patch to the previous catalog.
+#[derive(Debug)]
+pub enum Update {
+	Snapshot(Root),
+	Patch(Patch),
+}
+
+impl Update {
+	/// Parse a catalog object: a JSON object is a snapshot, while a JSON array is a patch.
+	pub fn from_slice(v: &[u8]) -> Result<Self, Error> {
+		match serde_json::from_slice::<Value>(v)? {
+			Value::Array(_) => Ok(Self::Patch(serde_json::from_slice(v)?)),
+			_ => Ok(Self::Snapshot(Root::from_slice(v)?)),
+		}
+	}
+}
+
+/// Applies a sequence of snapshots and patches, producing the merged catalog.
+///
+/// The publisher should write a full snapshot as the first object of each group, followed by patches in the same group.
+/// A late joiner receives the latest group from the start, so it always begins with a snapshot.
+/// Publishing only snapshots remains valid, since each one replaces the catalog.
+#[derive(Debug, Default)]
+pub struct Decoder {
+	root: Option<Value>,
+}
+
+impl Decoder {
+	/// Apply the next catalog object, returning the merged catalog.
+	///
+	/// A patch that arrives before any snapshot fails with [Error::MissingSnapshot].
+	pub fn decode(&mut self, v: &[u8]) -> Result<Root, Error> {
+		match Update::from_slice(v)? {
+			Update::Snapshot(_) => {
+				self.root = Some(serde_json::from_slice(v)?);
+			}
+			Update::Patch(patch) => {
+				let root = self.root.as_mut().ok_or(Error::MissingSnapshot)?;
+				patch.apply(root)?;
+			}
+		};
+
+		// Validate the merged result, including the version.
+		let root = self.root.as_ref().ok_or(Error::MissingSnapshot)?;
+		Root::from_slice(&serde_json::to_vec(root)?)
+	}
+
+	/// Forget the current catalog, for example when starting a new group.
+	pub fn reset(&mut self) {
+		self.root = None;
+	}
+}
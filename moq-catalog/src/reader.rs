@@ -0,0 +1,99 @@
+use moq_transport::serve::{GroupReader, GroupsReader};
+use serde::Deserialize;
+
+use crate::{Error, Root};
+
+/// Reads a catalog track, where each group contains a complete catalog.
+///
+/// A catalog may be split across multiple frames of a group; see [Root::from_frames].
+pub struct Reader {
+	groups: GroupsReader,
+}
+
+impl Reader {
+	pub fn new(groups: GroupsReader) -> Self {
+		Self { groups }
+	}
+
+	/// Returns the catalog in the next group, or None when the track has ended.
+	///
+	/// Every frame in the group is read before parsing, so an incomplete group results in an error.
+	/// A publisher using delta updates keeps the group open, so only the first frame is returned;
+	/// use [Self::updates] to receive the later patches.
+	pub async fn read(&mut self) -> Result<Option<Root>, Error> {
+		let mut group = match self.groups.next().await? {
+			Some(group) => group,
+			None => return Ok(None),
+		};
+
+		let mut frames = Vec::new();
+		while let Some(frame) = group.read_next().await? {
+			// Peek at the first frame, as waiting for the rest of a delta group would block until the next catalog.
+			if frames.is_empty() {
+				#[derive(Deserialize)]
+				struct Delta {
+					#[serde(rename = "supportsDeltaUpdates", default)]
+					enabled: bool,
+				}
+
+				let Delta { enabled } = serde_json::from_slice(&frame)?;
+				if enabled {
+					return Ok(Some(Root::from_slice(&frame)?));
+				}
+			}
+
+			frames.push(frame);
+		}
+
+		Ok(Some(Root::from_frames(&frames)?))
+	}
+
+	/// Returns each change to the catalog, including patches within a group.
+	pub fn updates(self) -> Updates {
+		Updates {
+			groups: self.groups,
+			group: None,
+			root: None,
+		}
+	}
+}
+
+/// The catalog after each frame of the track, returned by [Reader::updates].
+///
+/// The first frame of each group is a complete catalog, and each later frame updates it.
+/// The publisher closes a group when it starts the next one, which replaces the catalog.
+pub struct Updates {
+	groups: GroupsReader,
+	group: Option<GroupReader>,
+
+	// The merged catalog for the current group.
+	root: Option<serde_json::Value>,
+}
+
+impl Updates {
+	/// Returns the updated catalog, or None when the track has ended.
+	pub async fn next(&mut self) -> Result<Option<Root>, Error> {
+		loop {
+			if let Some(group) = &mut self.group {
+				if let Some(frame) = group.read_next().await? {
+					let root = match &mut self.root {
+						Some(root) => {
+							crate::extend(root, &frame)?;
+							root
+						}
+						None => self.root.insert(serde_json::from_slice(&frame)?),
+					};
+
+					return Ok(Some(Root::from_slice(&serde_json::to_vec(root)?)?));
+				}
+			}
+
+			// Wait for the next group, which starts with a new catalog.
+			self.group = match self.groups.next().await? {
+				Some(group) => Some(group),
+				None => return Ok(None),
+			};
+			self.root = None;
+		}
+	}
+}
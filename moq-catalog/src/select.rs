@@ -0,0 +1,141 @@
+use std::cmp::Reverse;
+
+use crate::{Codec, Root, Track};
+
+/// What the player can decode and receive, used by [Root::select].
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+	/// The most demanding codecs the decoder supports, see [Codec::supports].
+	pub codecs: Vec<Codec>,
+
+	/// The largest resolution worth receiving, or None for unlimited.
+	pub max_width: Option<u32>,
+	pub max_height: Option<u32>,
+
+	/// The available bandwidth in bits per second, or None for unlimited.
+	pub bandwidth: Option<u32>,
+
+	/// The preferred language, such as `en`.
+	pub language: Option<String>,
+}
+
+impl Capabilities {
+	fn decodable(&self, track: &Track) -> bool {
+		let params = &track.selection_params;
+
+		let codec = match &params.codec {
+			Some(codec) => codec,
+			None => return false,
+		};
+
+		self.codecs.iter().any(|cap| cap.supports(codec))
+			&& within(params.width, self.max_width)
+			&& within(params.height, self.max_height)
+	}
+
+	fn fits(&self, track: &Track) -> bool {
+		within(track.selection_params.bitrate, self.bandwidth)
+	}
+
+	// Prefer the requested language, treating a track without one as a match.
+	fn language(&self, track: &Track) -> bool {
+		match (&self.language, &track.selection_params.language) {
+			(Some(preferred), Some(language)) => preferred == language,
+			_ => true,
+		}
+	}
+}
+
+// Returns true unless both are known and the value exceeds the limit.
+fn within(value: Option<u32>, max: Option<u32>) -> bool {
+	match (value, max) {
+		(Some(value), Some(max)) => value <= max,
+		_ => true,
+	}
+}
+
+impl Root {
+	/// Pick the best track the player can decode, or None if nothing is supported.
+	///
+	/// Only tracks with a codec supported by [Capabilities::codecs] are considered, so select video and audio separately.
+	/// The highest quality track within the bandwidth is preferred, otherwise the lowest bitrate track.
+	pub fn select(&self, caps: &Capabilities) -> Option<&Track> {
+		let decodable = self.tracks.iter().filter(|track| caps.decodable(track));
+
+		let quality = |track: &&Track| {
+			let params = &track.selection_params;
+			let pixels = params.width.unwrap_or(0) as u64 * params.height.unwrap_or(0) as u64;
+			(caps.language(track), params.bitrate.unwrap_or(0), pixels)
+		};
+
+		decodable
+			.clone()
+			.filter(|track| caps.fits(track))
+			.max_by_key(quality)
+			.or_else(|| {
+				decodable.min_by_key(|track| {
+					(
+						Reverse(caps.language(track)),
+						track.selection_params.bitrate.unwrap_or(0),
+					)
+				})
+			})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::SelectionParam;
+
+	fn track(name: &str, codec: &str, height: u32, bitrate: u32) -> Track {
+		Track {
+			name: name.to_string(),
+			selection_params: SelectionParam {
+				codec: Some(codec.parse().unwrap()),
+				width: Some(height * 16 / 9),
+				height: Some(height),
+				bitrate: Some(bitrate),
+				..Default::default()
+			},
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn select() {
+		let root = Root {
+			version: Root::VERSION,
+			streaming_format: 1,
+			streaming_format_version: "0.2".to_string(),
+			streaming_delta_updates: false,
+			common_track_fields: Default::default(),
+			tracks: vec![
+				track("360p", "avc1.42001e", 360, 500_000),
+				track("720p", "avc1.64001f", 720, 2_000_000),
+				track("1080p", "avc1.640028", 1080, 5_000_000),
+				track("av1", "av01.0.08M.08", 1080, 3_000_000),
+			],
+		};
+
+		let mut caps = Capabilities {
+			codecs: vec!["avc1.640028".parse().unwrap(), "avc1.42001e".parse().unwrap()],
+			..Default::default()
+		};
+		assert_eq!(root.select(&caps).unwrap().name, "1080p");
+
+		caps.bandwidth = Some(3_000_000);
+		assert_eq!(root.select(&caps).unwrap().name, "720p");
+
+		caps.max_height = Some(480);
+		assert_eq!(root.select(&caps).unwrap().name, "360p");
+
+		// Nothing fits the bandwidth, so fall back to the lowest bitrate.
+		caps.max_height = None;
+		caps.bandwidth = Some(100_000);
+		assert_eq!(root.select(&caps).unwrap().name, "360p");
+
+		caps.codecs = vec![Codec::Opus];
+		assert!(root.select(&caps).is_none());
+	}
+}
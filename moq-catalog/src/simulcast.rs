@@ -0,0 +1,180 @@
+use bytes::Bytes;
+use moq_transport::data::{ObjectFlags, ObjectMeta};
+use moq_transport::serve::{Group, GroupWriter, GroupsWriter, ServeError, TracksWriter};
+
+use crate::{Error, Track, Writer};
+
+struct Rendition {
+	name: String,
+	groups: GroupsWriter,
+
+	// The group for the current keyframe, or None if the rendition was added since.
+	group: Option<GroupWriter>,
+}
+
+/// Publishes renditions of the same source, linked in the catalog by a shared alternate group.
+///
+/// Every rendition starts a group with the same ID at each [Self::keyframe], and each frame carries the source timestamp.
+/// A relay or player can then switch renditions at any group boundary without a gap or a repeat.
+pub struct Simulcast {
+	alt_group: u16,
+	renditions: Vec<Rendition>,
+
+	// The ID of the next group, shared by every rendition.
+	next: u64,
+}
+
+impl Simulcast {
+	pub fn new(alt_group: u16) -> Self {
+		Self {
+			alt_group,
+			renditions: Vec::new(),
+			next: 0,
+		}
+	}
+
+	/// Create the rendition's track in the broadcast and add it to the catalog in the alternate group.
+	///
+	/// The rendition is written starting at the next keyframe.
+	pub fn add(&mut self, broadcast: &mut TracksWriter, catalog: &mut Writer, mut track: Track) -> Result<(), Error> {
+		if self.renditions.iter().any(|rendition| rendition.name == track.name) {
+			return Err(Error::Duplicate(track.name));
+		}
+
+		// NOTE: This fails if every reader of the broadcast was dropped.
+		let writer = broadcast.create(&track.name).ok_or(ServeError::Cancel)?;
+
+		track.alt_group = Some(self.alt_group);
+
+		self.renditions.push(Rendition {
+			name: track.name.clone(),
+			groups: writer.groups()?,
+			group: None,
+		});
+
+		catalog.add_track(track)
+	}
+
+	/// Remove the rendition from the broadcast and the catalog.
+	pub fn remove(&mut self, broadcast: &mut TracksWriter, catalog: &mut Writer, name: &str) -> Result<(), Error> {
+		self.renditions.retain(|rendition| rendition.name != name);
+		broadcast.remove(name);
+		catalog.remove_track(name)?;
+
+		Ok(())
+	}
+
+	/// Start a new group in every rendition, returning its ID; call this at each keyframe of the source.
+	pub fn keyframe(&mut self) -> Result<u64, Error> {
+		let group_id = self.next;
+
+		for rendition in &mut self.renditions {
+			// NOTE: This closes the previous group.
+			rendition.group = Some(rendition.groups.create(Group { group_id, priority: 0 })?);
+		}
+
+		self.next += 1;
+
+		Ok(group_id)
+	}
+
+	/// Write a frame of the rendition to the current group, with the timestamp of the source frame.
+	///
+	/// Fails with [Error::MissingKeyframe] until [Self::keyframe] is called after the rendition was added.
+	pub fn write(&mut self, name: &str, timestamp: u64, payload: Bytes) -> Result<(), Error> {
+		let rendition = self
+			.renditions
+			.iter_mut()
+			.find(|rendition| rendition.name == name)
+			.ok_or_else(|| Error::Missing(name.to_string()))?;
+
+		let group = rendition.group.as_mut().ok_or(Error::MissingKeyframe)?;
+
+		let meta = ObjectMeta {
+			timestamp,
+			flags: ObjectFlags {
+				keyframe: group.is_empty(),
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		Ok(group.write_with_meta(payload, meta)?)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{CommonTrackFields, Root};
+	use moq_transport::serve::{self, TrackReaderMode};
+
+	#[tokio::test]
+	async fn aligned() {
+		let (mut broadcast, _, mut reader) = serve::Tracks::new("test".to_string()).produce();
+		let catalog_track = broadcast.create(".catalog").unwrap();
+		let mut catalog = Writer::new(catalog_track.groups().unwrap());
+		catalog
+			.write(Root {
+				version: Root::VERSION,
+				streaming_format: 1,
+				streaming_format_version: "0.2".to_string(),
+				streaming_delta_updates: false,
+				common_track_fields: CommonTrackFields::default(),
+				tracks: Vec::new(),
+			})
+			.unwrap();
+
+		let mut simulcast = Simulcast::new(1);
+		for name in ["360p", "720p"] {
+			let track = Track {
+				name: name.to_string(),
+				..Default::default()
+			};
+			simulcast.add(&mut broadcast, &mut catalog, track).unwrap();
+		}
+
+		let duplicate = Track {
+			name: "720p".to_string(),
+			..Default::default()
+		};
+		assert!(matches!(
+			simulcast.add(&mut broadcast, &mut catalog, duplicate),
+			Err(Error::Duplicate(_))
+		));
+
+		let root = catalog.root().unwrap();
+		assert!(root.tracks.iter().all(|track| track.alt_group == Some(1)));
+
+		assert!(matches!(
+			simulcast.write("360p", 0, Bytes::from_static(b"key")),
+			Err(Error::MissingKeyframe)
+		));
+
+		simulcast.keyframe().unwrap();
+		assert_eq!(simulcast.keyframe().unwrap(), 1);
+
+		for name in ["360p", "720p"] {
+			simulcast.write(name, 33, Bytes::from_static(b"key")).unwrap();
+			simulcast.write(name, 66, Bytes::from_static(b"delta")).unwrap();
+		}
+
+		for name in ["360p", "720p"] {
+			let mut groups = match reader.subscribe(name).unwrap().mode().await.unwrap() {
+				TrackReaderMode::Groups(groups) => groups,
+				_ => panic!("expected groups"),
+			};
+
+			let mut group = groups.next().await.unwrap().unwrap();
+			assert_eq!(group.group_id, 1);
+
+			let meta = group.next().await.unwrap().unwrap().meta.clone().unwrap();
+			assert_eq!(meta.timestamp, 33);
+			assert!(meta.flags.keyframe);
+
+			let meta = group.next().await.unwrap().unwrap().meta.clone().unwrap();
+			assert_eq!(meta.timestamp, 66);
+			assert!(!meta.flags.keyframe);
+		}
+	}
+}
@@ -0,0 +1,238 @@
+use moq_transport::serve::{GroupWriter, GroupsWriter};
+
+use crate::{Error, Patch, PatchOp, Root, Track};
+
+/// Writes a catalog track, where each group contains a complete catalog.
+///
+/// Every catalog is published as a new group, so a late joiner only needs the latest group.
+/// If the catalog supports delta updates, track changes are instead sent as a [Patch] in the current group.
+pub struct Writer {
+	groups: GroupsWriter,
+	root: Option<Root>,
+
+	// The current group, kept open for patches when delta updates are supported.
+	group: Option<GroupWriter>,
+}
+
+impl Writer {
+	pub fn new(groups: GroupsWriter) -> Self {
+		Self {
+			groups,
+			root: None,
+			group: None,
+		}
+	}
+
+	/// Publish a complete catalog as a new group, replacing the previous catalog.
+	pub fn write(&mut self, root: Root) -> Result<(), Error> {
+		self.root = Some(root);
+		self.publish()
+	}
+
+	/// Returns the catalog most recently written, if any.
+	pub fn root(&self) -> Option<&Root> {
+		self.root.as_ref()
+	}
+
+	/// Add a track, replacing any track with the same namespace and name, then update the catalog.
+	///
+	/// Fails with [Error::MissingSnapshot] if no catalog was written yet.
+	pub fn add_track(&mut self, track: Track) -> Result<(), Error> {
+		let root = self.root.as_mut().ok_or(Error::MissingSnapshot)?;
+		let value = serde_json::to_value(&track)?;
+
+		let existing = root
+			.tracks
+			.iter()
+			.position(|existing| existing.namespace == track.namespace && existing.name == track.name);
+
+		let op = match existing {
+			Some(index) => {
+				root.tracks[index] = track;
+				PatchOp::Replace {
+					path: format!("/tracks/{}", index),
+					value,
+				}
+			}
+			None => {
+				root.tracks.push(track);
+				PatchOp::Add {
+					path: "/tracks/-".to_string(),
+					value,
+				}
+			}
+		};
+
+		self.update(Patch(vec![op]))
+	}
+
+	/// Remove a track by name, then update the catalog if it was found.
+	pub fn remove_track(&mut self, name: &str) -> Result<Option<Track>, Error> {
+		let root = self.root.as_mut().ok_or(Error::MissingSnapshot)?;
+		let index = match root.tracks.iter().position(|track| track.name == name) {
+			Some(index) => index,
+			None => return Ok(None),
+		};
+
+		let track = root.tracks.remove(index);
+		self.update(Patch(vec![PatchOp::Remove {
+			path: format!("/tracks/{}", index),
+		}]))?;
+
+		Ok(Some(track))
+	}
+
+	// Send the patch in the current group if possible, otherwise republish the whole catalog.
+	fn update(&mut self, patch: Patch) -> Result<(), Error> {
+		match &mut self.group {
+			Some(group) => Ok(group.write(serde_json::to_vec(&patch)?.into())?),
+			None => self.publish(),
+		}
+	}
+
+	fn publish(&mut self) -> Result<(), Error> {
+		let root = self.root.as_ref().ok_or(Error::MissingSnapshot)?;
+		let payload = serde_json::to_vec(root)?;
+
+		// NOTE: This closes the previous group, so readers move on to the new catalog.
+		let mut group = self.groups.append(0)?;
+		group.write(payload.into())?;
+
+		self.group = root.streaming_delta_updates.then_some(group);
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{CommonTrackFields, Reader};
+	use moq_transport::serve;
+
+	#[tokio::test]
+	async fn republish() {
+		let (writer, reader) = serve::Track::new("test".to_string(), ".catalog".to_string()).produce();
+		let mut writer = Writer::new(writer.groups().unwrap());
+
+		assert!(matches!(
+			writer.add_track(Track::default()),
+			Err(Error::MissingSnapshot)
+		));
+
+		writer
+			.write(Root {
+				version: Root::VERSION,
+				streaming_format: 1,
+				streaming_format_version: "0.2".to_string(),
+				streaming_delta_updates: false,
+				common_track_fields: CommonTrackFields::default(),
+				tracks: Vec::new(),
+			})
+			.unwrap();
+
+		let groups = match reader.mode().await.unwrap() {
+			serve::TrackReaderMode::Groups(groups) => groups,
+			_ => panic!("expected groups"),
+		};
+		let mut reader = Reader::new(groups);
+		assert!(reader.read().await.unwrap().unwrap().tracks.is_empty());
+
+		writer
+			.add_track(Track {
+				name: "video".to_string(),
+				..Default::default()
+			})
+			.unwrap();
+
+		let root = reader.read().await.unwrap().unwrap();
+		assert_eq!(root.tracks.len(), 1);
+		assert_eq!(root.tracks[0].name, "video");
+
+		assert!(writer.remove_track("audio").unwrap().is_none());
+		assert!(writer.remove_track("video").unwrap().is_some());
+		assert!(reader.read().await.unwrap().unwrap().tracks.is_empty());
+	}
+
+	#[tokio::test]
+	async fn delta() {
+		let (writer, reader) = serve::Track::new("test".to_string(), ".catalog".to_string()).produce();
+		let mut writer = Writer::new(writer.groups().unwrap());
+
+		let root = Root {
+			version: Root::VERSION,
+			streaming_format: 1,
+			streaming_format_version: "0.2".to_string(),
+			streaming_delta_updates: true,
+			common_track_fields: CommonTrackFields::default(),
+			tracks: Vec::new(),
+		};
+		writer.write(root).unwrap();
+
+		let groups = match reader.mode().await.unwrap() {
+			serve::TrackReaderMode::Groups(groups) => groups,
+			_ => panic!("expected groups"),
+		};
+		let mut updates = Reader::new(groups).updates();
+		assert!(updates.next().await.unwrap().unwrap().tracks.is_empty());
+
+		for name in ["video", "audio"] {
+			writer
+				.add_track(Track {
+					name: name.to_string(),
+					..Default::default()
+				})
+				.unwrap();
+		}
+		writer.remove_track("video").unwrap();
+
+		// Each change is a patch in the same group.
+		assert_eq!(updates.next().await.unwrap().unwrap().tracks.len(), 1);
+		assert_eq!(updates.next().await.unwrap().unwrap().tracks.len(), 2);
+
+		let root = updates.next().await.unwrap().unwrap();
+		assert_eq!(root.tracks.len(), 1);
+		assert_eq!(root.tracks[0].name, "audio");
+	}
+
+	#[tokio::test]
+	async fn delta_read() {
+		let (writer, reader) = serve::Track::new("test".to_string(), ".catalog".to_string()).produce();
+		let mut writer = Writer::new(writer.groups().unwrap());
+
+		let catalog = |tracks| Root {
+			version: Root::VERSION,
+			streaming_format: 1,
+			streaming_format_version: "0.2".to_string(),
+			streaming_delta_updates: true,
+			common_track_fields: CommonTrackFields::default(),
+			tracks,
+		};
+		writer.write(catalog(Vec::new())).unwrap();
+		writer
+			.add_track(Track {
+				name: "video".to_string(),
+				..Default::default()
+			})
+			.unwrap();
+
+		let groups = match reader.mode().await.unwrap() {
+			serve::TrackReaderMode::Groups(groups) => groups,
+			_ => panic!("expected groups"),
+		};
+		let mut reader = Reader::new(groups);
+
+		// The group is still open, so only the snapshot is returned.
+		assert!(reader.read().await.unwrap().unwrap().tracks.is_empty());
+
+		let audio = Track {
+			name: "audio".to_string(),
+			..Default::default()
+		};
+		writer.write(catalog(vec![audio])).unwrap();
+
+		let root = reader.read().await.unwrap().unwrap();
+		assert_eq!(root.tracks.len(), 1);
+		assert_eq!(root.tracks[0].name, "audio");
+	}
+}
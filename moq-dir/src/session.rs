@@ -1,16 +1,17 @@
 use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
 use moq_transport::session::{Announced, Publisher, Subscriber};
+use moq_transport::transport;
 
 use crate::Listings;
 
 #[derive(Clone)]
 pub struct Session {
-	session: web_transport::Session,
+	session: transport::Session,
 	listings: Listings,
 }
 
 impl Session {
-	pub fn new(session: web_transport::Session, listings: Listings) -> Self {
+	pub fn new(session: transport::Session, listings: Listings) -> Self {
 		Self { session, listings }
 	}
 
@@ -0,0 +1,211 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Context;
+use bytes::{Bytes, BytesMut};
+use log::{debug, info, warn};
+use moq_catalog::Codec;
+use moq_transport::{
+	data::ObjectMeta,
+	serve::{GroupReader, TrackReader, TrackReaderMode, Tracks, TracksReader, TracksWriter},
+	session::Subscriber,
+};
+
+use crate::{Segments, SegmentsReader, SegmentsWriter};
+
+// The number of segments kept for each track.
+const MAX_SEGMENTS: usize = 8;
+
+/// A track served as an HLS media playlist.
+pub struct Rendition {
+	pub track: moq_catalog::Track,
+
+	// The init segment referenced by the catalog.
+	pub init: Bytes,
+
+	pub segments: SegmentsReader,
+}
+
+/// The tracks of a broadcast, keyed by name.
+#[derive(Clone)]
+pub struct Broadcast {
+	pub renditions: Arc<HashMap<String, Rendition>>,
+
+	// The names of the selected video and audio tracks.
+	pub video: Option<String>,
+	pub audio: Option<String>,
+}
+
+/// Subscribes to a broadcast and splits each track into segments.
+pub struct Import {
+	subscriber: Subscriber,
+	broadcast: TracksReader,
+	tracks_writer: TracksWriter,
+}
+
+impl Import {
+	pub fn new(subscriber: Subscriber, tracks: Tracks) -> Self {
+		let (tracks_writer, _tracks_request, broadcast) = tracks.produce();
+
+		Self {
+			subscriber,
+			broadcast,
+			tracks_writer,
+		}
+	}
+
+	/// Read the catalog and init segments, then import the first video and audio track in the background.
+	pub async fn load(&mut self) -> anyhow::Result<Broadcast> {
+		let catalog = self.subscribe(".catalog")?;
+		let catalog = match catalog.mode().await? {
+			// NOTE: The publisher may keep the group open for updates, so only wait for the first catalog.
+			TrackReaderMode::Groups(groups) => moq_catalog::Reader::new(groups)
+				.updates()
+				.next()
+				.await?
+				.context("no catalog")?,
+			_ => anyhow::bail!("expected catalog groups"),
+		};
+
+		let find = |kind: fn(&Codec) -> bool| {
+			catalog
+				.tracks
+				.iter()
+				.find(move |track| track.selection_params.codec.as_ref().is_some_and(kind))
+		};
+		let (video, audio) = (find(Codec::is_video), find(Codec::is_audio));
+		anyhow::ensure!(video.is_some() || audio.is_some(), "no supported tracks in catalog");
+
+		let mut inits = HashMap::new();
+		let mut renditions = HashMap::new();
+
+		for track in video.into_iter().chain(audio) {
+			let name = track.init_track.clone().context("missing init track")?;
+
+			// Tracks usually share an init segment, so only download it once.
+			let init = match inits.get(&name) {
+				Some(init) => Bytes::clone(init),
+				None => {
+					let init = Self::recv_init(self.subscribe(&name)?).await?;
+					inits.insert(name, init.clone());
+					init
+				}
+			};
+
+			info!("serving track {}: {:?}", track.name, track.selection_params.codec);
+
+			let (writer, segments) = Segments::produce(MAX_SEGMENTS);
+			let reader = self.subscribe(&track.name)?;
+
+			tokio::spawn(async move {
+				let name = reader.name.clone();
+				if let Err(err) = Self::run_track(reader, writer).await {
+					warn!("failed to import track {name}: {err:?}");
+				}
+			});
+
+			renditions.insert(
+				track.name.clone(),
+				Rendition {
+					track: track.clone(),
+					init,
+					segments,
+				},
+			);
+		}
+
+		Ok(Broadcast {
+			renditions: Arc::new(renditions),
+			video: video.map(|track| track.name.clone()),
+			audio: audio.map(|track| track.name.clone()),
+		})
+	}
+
+	fn subscribe(&mut self, name: &str) -> anyhow::Result<TrackReader> {
+		let track = self.tracks_writer.create(name).context("failed to create track")?;
+
+		let mut subscriber = self.subscriber.clone();
+		tokio::task::spawn(async move {
+			let name = track.name.clone();
+			subscriber.subscribe(track).await.unwrap_or_else(|err| {
+				warn!("failed to subscribe to track {name}: {err:?}");
+			});
+		});
+
+		self.broadcast.subscribe(name).context("no track")
+	}
+
+	async fn recv_init(track: TrackReader) -> anyhow::Result<Bytes> {
+		let mut group = match track.mode().await? {
+			TrackReaderMode::Groups(mut groups) => groups.next().await?.context("no init group")?,
+			_ => anyhow::bail!("expected init segment"),
+		};
+
+		group.read_next().await?.context("no init fragment")
+	}
+
+	// Each group becomes a segment, skipping the rest of a group when the next one arrives.
+	async fn run_track(track: TrackReader, mut segments: SegmentsWriter) -> anyhow::Result<()> {
+		let name = track.name.clone();
+
+		let mut groups = match track.mode().await? {
+			TrackReaderMode::Groups(groups) => groups,
+			_ => anyhow::bail!("expected groups"),
+		};
+
+		let mut current: Option<GroupReader> = None;
+		let mut done = false;
+
+		loop {
+			tokio::select! {
+				res = groups.next(), if !done => match res? {
+					Some(group) => {
+						if let Some(skipped) = current.replace(group) {
+							debug!("track {name}: skipping rest of group={}", skipped.group_id);
+						}
+						segments.finish();
+					}
+					None => done = true,
+				},
+				res = Self::recv_fragment(current.as_mut().unwrap()), if current.is_some() => match res {
+					Ok(Some((meta, fragment))) => segments.part(meta.timestamp, meta.flags.keyframe, fragment),
+					Ok(None) => {
+						segments.finish();
+						current = None;
+					}
+					Err(err) => {
+						warn!("track {name}: dropping group: {err:?}");
+						segments.finish();
+						current = None;
+					}
+				},
+				else => break,
+			}
+		}
+
+		segments.close();
+		Ok(())
+	}
+
+	// Read the next moof and mdat, returning the metadata of the moof, or None when the group is done.
+	async fn recv_fragment(group: &mut GroupReader) -> anyhow::Result<Option<(ObjectMeta, Bytes)>> {
+		let mut meta = None;
+		let mut fragment = BytesMut::new();
+
+		while let Some(mut object) = group.next().await? {
+			if meta.is_none() {
+				meta = Some(object.meta.clone().context("fragment missing timestamp")?);
+			}
+
+			let atom = object.read_all().await?;
+			let mdat = atom.get(4..8) == Some(b"mdat");
+			fragment.extend_from_slice(&atom);
+
+			if mdat {
+				return Ok(meta.map(|meta| (meta, fragment.freeze())));
+			}
+		}
+
+		anyhow::ensure!(fragment.is_empty(), "group ended with a partial fragment");
+		Ok(None)
+	}
+}
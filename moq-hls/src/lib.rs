@@ -0,0 +1,9 @@
+mod import;
+mod playlist;
+mod segment;
+mod server;
+
+pub use import::*;
+pub use playlist::*;
+pub use segment::*;
+pub use server::*;
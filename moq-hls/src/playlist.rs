@@ -0,0 +1,172 @@
+use std::fmt::{self, Write};
+
+use moq_catalog::Track;
+
+use crate::Segments;
+
+// Only list the parts of the most recent segments, as recommended by the LL-HLS spec.
+const PART_SEGMENTS: usize = 3;
+
+/// A media playlist for a single track, with a partial segment for each fragment.
+pub struct MediaPlaylist<'a> {
+	pub segments: &'a Segments,
+}
+
+impl fmt::Display for MediaPlaylist<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let state = self.segments;
+
+		let part_target = state.part_target.as_secs_f64();
+		let target_duration = state.target_duration.as_secs_f64().ceil().max(1.0);
+
+		writeln!(f, "#EXTM3U")?;
+		writeln!(f, "#EXT-X-VERSION:9")?;
+		writeln!(f, "#EXT-X-TARGETDURATION:{}", target_duration)?;
+		writeln!(f, "#EXT-X-PART-INF:PART-TARGET={:.3}", part_target)?;
+		writeln!(
+			f,
+			"#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK={:.3}",
+			3.0 * part_target
+		)?;
+
+		let first = state.segments.front().map(|segment| segment.sequence).unwrap_or(0);
+		writeln!(f, "#EXT-X-MEDIA-SEQUENCE:{}", first)?;
+		writeln!(f, "#EXT-X-MAP:URI=\"init.mp4\"")?;
+
+		let recent = state.segments.len().saturating_sub(PART_SEGMENTS);
+
+		for (index, segment) in state.segments.iter().enumerate() {
+			if index >= recent {
+				for (part, data) in segment.parts[..segment.published()].iter().enumerate() {
+					let duration = data.duration.unwrap_or_default() as f64 / 1000.0;
+					write!(
+						f,
+						"#EXT-X-PART:DURATION={:.3},URI=\"{}.{}.m4s\"",
+						duration, segment.sequence, part
+					)?;
+
+					if data.independent {
+						write!(f, ",INDEPENDENT=YES")?;
+					}
+
+					writeln!(f)?;
+				}
+			}
+
+			if !segment.is_ready() {
+				// NOTE: Only the last segment can be incomplete, since a new group completes the previous one.
+				break;
+			}
+
+			writeln!(f, "#EXTINF:{:.3},", segment.duration() as f64 / 1000.0)?;
+			writeln!(f, "{}.m4s", segment.sequence)?;
+		}
+
+		if state.closed {
+			return writeln!(f, "#EXT-X-ENDLIST");
+		}
+
+		// Tell the player to request the next part before it's published.
+		let (sequence, part) = match state.segments.back() {
+			Some(segment) if !segment.is_ready() => (segment.sequence, segment.published()),
+			_ => (state.next_sequence(), 0),
+		};
+		writeln!(f, "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"{}.{}.m4s\"", sequence, part)
+	}
+}
+
+/// A multivariant playlist, listing a video track with an optional audio rendition.
+pub struct MasterPlaylist<'a> {
+	pub video: Option<&'a Track>,
+	pub audio: Option<&'a Track>,
+}
+
+impl fmt::Display for MasterPlaylist<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "#EXTM3U")?;
+		writeln!(f, "#EXT-X-VERSION:9")?;
+		writeln!(f, "#EXT-X-INDEPENDENT-SEGMENTS")?;
+
+		let mut codecs = Vec::new();
+		let mut bandwidth = 0u64;
+
+		for track in self.video.iter().chain(&self.audio) {
+			let params = &track.selection_params;
+			if let Some(codec) = &params.codec {
+				codecs.push(codec.to_string());
+			}
+			bandwidth += params.bitrate.unwrap_or(0) as u64;
+		}
+
+		let mut attrs = format!("BANDWIDTH={},CODECS=\"{}\"", bandwidth, codecs.join(","));
+
+		let variant = match (self.video, self.audio) {
+			(Some(video), audio) => {
+				let params = &video.selection_params;
+				if let (Some(width), Some(height)) = (params.width, params.height) {
+					write!(attrs, ",RESOLUTION={}x{}", width, height)?;
+				}
+				if let Some(framerate) = params.framerate {
+					write!(attrs, ",FRAME-RATE={}", framerate)?;
+				}
+
+				if let Some(audio) = audio {
+					write!(f, "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"audio\",NAME=\"{}\"", audio.name)?;
+					if let Some(language) = &audio.selection_params.language {
+						write!(f, ",LANGUAGE=\"{}\"", language)?;
+					}
+					writeln!(f, ",DEFAULT=YES,AUTOSELECT=YES,URI=\"{}/playlist.m3u8\"", audio.name)?;
+
+					attrs.push_str(",AUDIO=\"audio\"");
+				}
+
+				video
+			}
+			(None, Some(audio)) => audio,
+			(None, None) => return Ok(()),
+		};
+
+		writeln!(f, "#EXT-X-STREAM-INF:{}", attrs)?;
+		writeln!(f, "{}/playlist.m3u8", variant.name)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	use bytes::Bytes;
+
+	#[test]
+	fn media() {
+		let (mut writer, reader) = Segments::produce(4);
+
+		writer.part(0, true, Bytes::new());
+		writer.part(500, false, Bytes::new());
+		writer.segment();
+		writer.part(1000, true, Bytes::new());
+		writer.part(1500, false, Bytes::new());
+
+		let playlist = MediaPlaylist {
+			segments: &reader.current(),
+		}
+		.to_string();
+
+		assert_eq!(
+			playlist,
+			"#EXTM3U\n\
+			#EXT-X-VERSION:9\n\
+			#EXT-X-TARGETDURATION:1\n\
+			#EXT-X-PART-INF:PART-TARGET=0.500\n\
+			#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1.500\n\
+			#EXT-X-MEDIA-SEQUENCE:0\n\
+			#EXT-X-MAP:URI=\"init.mp4\"\n\
+			#EXT-X-PART:DURATION=0.500,URI=\"0.0.m4s\",INDEPENDENT=YES\n\
+			#EXT-X-PART:DURATION=0.500,URI=\"0.1.m4s\"\n\
+			#EXTINF:1.000,\n\
+			0.m4s\n\
+			#EXT-X-PART:DURATION=0.500,URI=\"1.0.m4s\",INDEPENDENT=YES\n\
+			#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"1.1.m4s\"\n"
+		);
+	}
+}
@@ -0,0 +1,236 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bytes::{Bytes, BytesMut};
+use tokio::sync::watch;
+
+/// A fragment (moof + mdat), served as an LL-HLS partial segment.
+#[derive(Debug, Clone)]
+pub struct Part {
+	// The presentation timestamp in milliseconds, from the object metadata.
+	pub timestamp: u64,
+
+	// The duration in milliseconds, known once the next fragment arrives.
+	pub duration: Option<u64>,
+
+	// True if the fragment starts with a keyframe.
+	pub independent: bool,
+
+	pub data: Bytes,
+}
+
+/// A group of fragments, served as an HLS media segment.
+#[derive(Debug, Clone)]
+pub struct Segment {
+	// The media sequence number, which increases by one for each segment unlike the group ID.
+	pub sequence: u64,
+
+	pub parts: Vec<Part>,
+
+	// True once the group has ended, so no more parts will be added.
+	pub complete: bool,
+}
+
+impl Segment {
+	/// Returns the number of parts with a known duration, which may be listed in a playlist.
+	pub fn published(&self) -> usize {
+		self.parts.iter().take_while(|part| part.duration.is_some()).count()
+	}
+
+	/// Returns true if the segment is complete and its duration is known.
+	pub fn is_ready(&self) -> bool {
+		self.complete && self.published() == self.parts.len()
+	}
+
+	/// The duration of the published parts in milliseconds.
+	pub fn duration(&self) -> u64 {
+		self.parts.iter().filter_map(|part| part.duration).sum()
+	}
+
+	/// The concatenated parts.
+	pub fn data(&self) -> Bytes {
+		let mut data = BytesMut::with_capacity(self.parts.iter().map(|part| part.data.len()).sum());
+		for part in &self.parts {
+			data.extend_from_slice(&part.data);
+		}
+		data.freeze()
+	}
+}
+
+/// The recent segments of a track.
+#[derive(Debug, Clone, Default)]
+pub struct Segments {
+	pub segments: VecDeque<Segment>,
+
+	// The longest segment and part, which are advertised in the playlist.
+	pub target_duration: Duration,
+	pub part_target: Duration,
+
+	// True once the track has ended.
+	pub closed: bool,
+}
+
+impl Segments {
+	/// Create a writer that keeps the given number of segments, and a reader that can be cloned.
+	pub fn produce(max: usize) -> (SegmentsWriter, SegmentsReader) {
+		let (state, reader) = watch::channel(Self::default());
+		(SegmentsWriter { state, max }, SegmentsReader { state: reader })
+	}
+
+	pub fn get(&self, sequence: u64) -> Option<&Segment> {
+		let first = self.segments.front()?.sequence;
+		self.segments.get(sequence.checked_sub(first)? as usize)
+	}
+
+	/// The sequence number of the next segment to be created.
+	pub fn next_sequence(&self) -> u64 {
+		self.segments.back().map(|segment| segment.sequence + 1).unwrap_or(0)
+	}
+}
+
+pub struct SegmentsWriter {
+	state: watch::Sender<Segments>,
+	max: usize,
+}
+
+impl SegmentsWriter {
+	/// Start a new segment, completing the previous one.
+	pub fn segment(&mut self) {
+		let max = self.max;
+
+		self.state.send_modify(|state| {
+			let sequence = state.next_sequence();
+
+			if let Some(last) = state.segments.back_mut() {
+				last.complete = true;
+			}
+
+			state.segments.push_back(Segment {
+				sequence,
+				parts: Vec::new(),
+				complete: false,
+			});
+
+			while state.segments.len() > max.max(1) {
+				state.segments.pop_front();
+			}
+		});
+	}
+
+	/// Append a fragment to the current segment, starting one if needed.
+	pub fn part(&mut self, timestamp: u64, independent: bool, data: Bytes) {
+		if self.state.borrow().segments.back().is_none_or(|last| last.complete) {
+			self.segment();
+		}
+
+		self.state.send_modify(|state| {
+			// The previous part ends where this one starts, even if it's in the previous segment.
+			let previous = state
+				.segments
+				.iter_mut()
+				.rev()
+				.flat_map(|segment| segment.parts.last_mut())
+				.next();
+			if let Some(previous) = previous {
+				let duration = timestamp.saturating_sub(previous.timestamp);
+				previous.duration = Some(duration);
+				state.part_target = state.part_target.max(Duration::from_millis(duration));
+			}
+
+			if let Some(segment) = state.segments.iter().rev().nth(1) {
+				state.target_duration = state.target_duration.max(Duration::from_millis(segment.duration()));
+			}
+
+			let segment = state.segments.back_mut().expect("segment was created");
+			segment.parts.push(Part {
+				timestamp,
+				duration: None,
+				independent,
+				data,
+			});
+		});
+	}
+
+	/// Complete the current segment, such as when the group ends.
+	pub fn finish(&mut self) {
+		self.state.send_modify(|state| {
+			if let Some(last) = state.segments.back_mut() {
+				last.complete = true;
+			}
+		});
+	}
+
+	/// Mark the track as ended, guessing the duration of the last part.
+	pub fn close(self) {
+		self.state.send_modify(|state| {
+			let estimate = state.part_target.as_millis() as u64;
+
+			if let Some(last) = state.segments.back_mut() {
+				last.complete = true;
+				if let Some(part) = last.parts.last_mut() {
+					part.duration.get_or_insert(estimate);
+				}
+			}
+
+			state.closed = true;
+		});
+	}
+}
+
+#[derive(Clone)]
+pub struct SegmentsReader {
+	state: watch::Receiver<Segments>,
+}
+
+impl SegmentsReader {
+	/// Returns a snapshot of the current segments.
+	pub fn current(&self) -> Segments {
+		self.state.borrow().clone()
+	}
+
+	/// Wait until the predicate is true or the track has ended, then return a snapshot.
+	pub async fn wait<F: FnMut(&Segments) -> bool>(&mut self, mut f: F) -> Segments {
+		if let Ok(state) = self.state.wait_for(|state| state.closed || f(state)).await {
+			return state.clone();
+		}
+
+		// The writer was dropped without closing, so return what we have.
+		self.current()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn durations() {
+		let (mut writer, reader) = Segments::produce(2);
+
+		writer.part(0, true, Bytes::from_static(b"a"));
+		writer.part(500, false, Bytes::from_static(b"b"));
+		writer.segment();
+		writer.part(1000, true, Bytes::from_static(b"c"));
+
+		let state = reader.current();
+		let first = state.get(0).unwrap();
+		assert!(first.is_ready());
+		assert_eq!(first.duration(), 1000);
+		assert_eq!(first.data(), Bytes::from_static(b"ab"));
+
+		let second = state.get(1).unwrap();
+		assert!(!second.is_ready());
+		assert_eq!(second.published(), 0);
+		assert_eq!(state.part_target, Duration::from_millis(500));
+
+		// Only the most recent segments are kept.
+		writer.segment();
+		writer.part(2000, true, Bytes::from_static(b"d"));
+		writer.close();
+
+		let state = reader.current();
+		assert!(state.get(0).is_none());
+		assert!(state.get(2).unwrap().is_ready());
+		assert_eq!(state.target_duration, Duration::from_millis(1000));
+		assert!(state.closed);
+	}
+}
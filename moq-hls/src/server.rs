@@ -0,0 +1,181 @@
+use std::{net, time::Duration};
+
+use axum::{
+	extract::{Path, Query, State},
+	http::{header, Method, StatusCode},
+	response::{IntoResponse, Response},
+	routing::get,
+	Router,
+};
+use serde::Deserialize;
+use tower_http::cors::{Any, CorsLayer};
+
+use crate::{Broadcast, MasterPlaylist, MediaPlaylist, Rendition, Segments};
+
+const PLAYLIST: &str = "application/vnd.apple.mpegurl";
+const MEDIA: &str = "video/mp4";
+
+/// Serves a broadcast as Low-Latency HLS.
+///
+/// The multivariant playlist is at `/master.m3u8`, and each track at `/{track}/playlist.m3u8`.
+pub struct Server {
+	bind: net::SocketAddr,
+	broadcast: Broadcast,
+}
+
+impl Server {
+	pub fn new(bind: net::SocketAddr, broadcast: Broadcast) -> Self {
+		Self { bind, broadcast }
+	}
+
+	pub async fn run(self) -> anyhow::Result<()> {
+		let app = Router::new()
+			.route("/master.m3u8", get(get_master))
+			.route("/:track/:file", get(get_file))
+			.layer(CorsLayer::new().allow_origin(Any).allow_methods([Method::GET]))
+			.with_state(self.broadcast);
+
+		log::info!("serving HLS: bind={}", self.bind);
+
+		let listener = tokio::net::TcpListener::bind(&self.bind).await?;
+		axum::serve(listener, app.into_make_service()).await?;
+
+		Ok(())
+	}
+}
+
+// The blocking playlist reload parameters.
+#[derive(Deserialize)]
+struct Reload {
+	#[serde(rename = "_HLS_msn")]
+	msn: Option<u64>,
+
+	#[serde(rename = "_HLS_part")]
+	part: Option<usize>,
+}
+
+async fn get_master(State(broadcast): State<Broadcast>) -> Response {
+	let track = |name: &Option<String>| {
+		name.as_ref()
+			.and_then(|name| broadcast.renditions.get(name))
+			.map(|rendition| &rendition.track)
+	};
+
+	let playlist = MasterPlaylist {
+		video: track(&broadcast.video),
+		audio: track(&broadcast.audio),
+	};
+
+	([(header::CONTENT_TYPE, PLAYLIST)], playlist.to_string()).into_response()
+}
+
+async fn get_file(
+	Path((track, file)): Path<(String, String)>,
+	Query(reload): Query<Reload>,
+	State(broadcast): State<Broadcast>,
+) -> Result<Response, AppError> {
+	let rendition = broadcast.renditions.get(&track).ok_or(AppError::NotFound)?;
+
+	match file.as_str() {
+		"playlist.m3u8" => get_playlist(rendition, reload).await,
+		"init.mp4" => Ok(([(header::CONTENT_TYPE, MEDIA)], rendition.init.clone()).into_response()),
+		_ => get_media(rendition, &file).await,
+	}
+}
+
+async fn get_playlist(rendition: &Rendition, reload: Reload) -> Result<Response, AppError> {
+	let mut segments = rendition.segments.clone();
+
+	let state = match reload.msn {
+		Some(msn) => {
+			let current = segments.current();
+
+			// The spec requires rejecting a request more than two segments in the future.
+			if msn > current.next_sequence() + 1 {
+				return Err(AppError::BadRequest);
+			}
+
+			let wait = segments.wait(|state| contains(state, msn, reload.part));
+			tokio::time::timeout(timeout(&current), wait)
+				.await
+				.map_err(|_| AppError::Unavailable)?
+		}
+		None if reload.part.is_some() => return Err(AppError::BadRequest),
+		None => segments.current(),
+	};
+
+	let playlist = MediaPlaylist { segments: &state };
+	Ok(([(header::CONTENT_TYPE, PLAYLIST)], playlist.to_string()).into_response())
+}
+
+// Serve `{msn}.m4s` or `{msn}.{part}.m4s`, waiting if it's the next one to be published.
+async fn get_media(rendition: &Rendition, file: &str) -> Result<Response, AppError> {
+	let name = file.strip_suffix(".m4s").ok_or(AppError::NotFound)?;
+	let (msn, part) = match name.split_once('.') {
+		Some((msn, part)) => (msn, Some(part.parse::<usize>().map_err(|_| AppError::NotFound)?)),
+		None => (name, None),
+	};
+	let msn: u64 = msn.parse().map_err(|_| AppError::NotFound)?;
+
+	let mut segments = rendition.segments.clone();
+	let current = segments.current();
+
+	// Only block for the preload hint, which is at most the next segment.
+	if msn > current.next_sequence() {
+		return Err(AppError::NotFound);
+	}
+
+	let available = |state: &Segments| match state.get(msn) {
+		Some(segment) => segment.complete || part.is_some_and(|part| part < segment.parts.len()),
+		None => msn < state.next_sequence(),
+	};
+
+	let state = tokio::time::timeout(timeout(&current), segments.wait(available))
+		.await
+		.map_err(|_| AppError::Unavailable)?;
+
+	let segment = state.get(msn).ok_or(AppError::NotFound)?;
+	let data = match part {
+		Some(part) => segment.parts.get(part).ok_or(AppError::NotFound)?.data.clone(),
+		None => segment.data(),
+	};
+
+	Ok(([(header::CONTENT_TYPE, MEDIA)], data).into_response())
+}
+
+// Returns true if the playlist contains the given segment, or part of a segment.
+fn contains(state: &Segments, msn: u64, part: Option<usize>) -> bool {
+	match state.get(msn) {
+		Some(segment) => segment.is_ready() || part.is_some_and(|part| part < segment.published()),
+		// The segment was already removed.
+		None => msn < state.next_sequence(),
+	}
+}
+
+// Give up on a blocking request after three target durations, as recommended by the spec.
+fn timeout(state: &Segments) -> Duration {
+	(3 * state.target_duration).max(Duration::from_secs(3))
+}
+
+#[derive(thiserror::Error, Debug)]
+enum AppError {
+	#[error("not found")]
+	NotFound,
+
+	#[error("bad request")]
+	BadRequest,
+
+	#[error("timed out")]
+	Unavailable,
+}
+
+// Tell axum how to convert `AppError` into a response.
+impl IntoResponse for AppError {
+	fn into_response(self) -> Response {
+		match self {
+			AppError::NotFound => StatusCode::NOT_FOUND.into_response(),
+			AppError::BadRequest => StatusCode::BAD_REQUEST.into_response(),
+			AppError::Unavailable => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+		}
+	}
+}
@@ -1,2 +1,4 @@
 pub mod quic;
+#[cfg(feature = "quiche")]
+pub mod quiche;
 pub mod tls;
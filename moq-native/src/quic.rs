@@ -1,9 +1,18 @@
-use std::{net, sync::Arc, time};
+use std::{
+	collections::HashSet,
+	net, path,
+	sync::{Arc, Mutex},
+	time,
+};
 
 use anyhow::Context;
 use clap::Parser;
 use url::Url;
 
+use moq_transport::session::{Reconnect, SessionError};
+use moq_transport::transport;
+use rustls::pki_types::CertificateDer;
+
 use crate::tls;
 
 use futures::future::BoxFuture;
@@ -48,14 +57,11 @@ pub struct Endpoint {
 
 impl Endpoint {
 	pub fn new(config: Config) -> anyhow::Result<Self> {
-		// Enable BBR congestion control
-		// TODO validate the implementation
-		let mut transport = quinn::TransportConfig::default();
-		transport.max_idle_timeout(Some(time::Duration::from_secs(10).try_into().unwrap()));
-		transport.keep_alive_interval(Some(time::Duration::from_secs(4))); // TODO make this smarter
-		transport.congestion_controller_factory(Arc::new(quinn::congestion::BbrConfig::default()));
-		transport.mtu_discovery_config(None); // Disable MTU discovery
-		let transport = Arc::new(transport);
+		let transport = Arc::new(transport(
+			Congestion::default(),
+			ClientBuilder::IDLE_TIMEOUT,
+			Some(ClientBuilder::KEEP_ALIVE),
+		)?);
 
 		let mut server_config = None;
 
@@ -84,23 +90,56 @@ impl Endpoint {
 			accept: Default::default(),
 		});
 
-		let client = Client {
-			quic,
-			config: config.tls.client,
-			transport,
-		};
+		let mut client = config.tls.client;
+		client.key_log = Arc::new(rustls::KeyLogFile::new());
+
+		let client = Client::new(quic, client, transport)?;
 
 		Ok(Self { client, server })
 	}
 }
 
+// Build the QUIC transport configuration shared by clients and servers.
+fn transport(
+	congestion: Congestion,
+	idle_timeout: time::Duration,
+	keep_alive: Option<time::Duration>,
+) -> anyhow::Result<quinn::TransportConfig> {
+	let mut transport = quinn::TransportConfig::default();
+	transport.max_idle_timeout(Some(idle_timeout.try_into().context("idle timeout too large")?));
+	transport.keep_alive_interval(keep_alive); // TODO make this smarter
+	transport.congestion_controller_factory(congestion.factory());
+	transport.mtu_discovery_config(None); // Disable MTU discovery
+	Ok(transport)
+}
+
+/// The congestion controller used for each connection.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Congestion {
+	// TODO validate the implementation
+	#[default]
+	Bbr,
+	Cubic,
+	NewReno,
+}
+
+impl Congestion {
+	fn factory(self) -> Arc<dyn quinn::congestion::ControllerFactory + Send + Sync> {
+		match self {
+			Self::Bbr => Arc::new(quinn::congestion::BbrConfig::default()),
+			Self::Cubic => Arc::new(quinn::congestion::CubicConfig::default()),
+			Self::NewReno => Arc::new(quinn::congestion::NewRenoConfig::default()),
+		}
+	}
+}
+
 pub struct Server {
 	quic: quinn::Endpoint,
-	accept: FuturesUnordered<BoxFuture<'static, anyhow::Result<web_transport::Session>>>,
+	accept: FuturesUnordered<BoxFuture<'static, anyhow::Result<transport::Session>>>,
 }
 
 impl Server {
-	pub async fn accept(&mut self) -> Option<web_transport::Session> {
+	pub async fn accept(&mut self) -> Option<transport::Session> {
 		loop {
 			tokio::select! {
 				res = self.quic.accept() => {
@@ -117,7 +156,7 @@ impl Server {
 		}
 	}
 
-	async fn accept_session(conn: quinn::Incoming) -> anyhow::Result<web_transport::Session> {
+	async fn accept_session(conn: quinn::Incoming) -> anyhow::Result<transport::Session> {
 		let mut conn = conn.accept()?;
 
 		let handshake = conn
@@ -148,7 +187,7 @@ impl Server {
 			server_name,
 		);
 
-		let session = match alpn.as_bytes() {
+		match alpn.as_bytes() {
 			web_transport_quinn::ALPN => {
 				// Wait for the CONNECT request.
 				let request = web_transport_quinn::accept(conn)
@@ -156,17 +195,17 @@ impl Server {
 					.context("failed to receive WebTransport request")?;
 
 				// Accept the CONNECT request.
-				request
+				let session = request
 					.ok()
 					.await
-					.context("failed to respond to WebTransport request")?
+					.context("failed to respond to WebTransport request")?;
+
+				Ok(session.into())
 			}
-			// A bit of a hack to pretend like we're a WebTransport session
-			moq_transport::setup::ALPN => conn.into(),
+			// Raw QUIC, used between servers to skip the HTTP/3 handshake.
+			moq_transport::setup::ALPN => Ok(conn.into()),
 			_ => anyhow::bail!("unsupported ALPN: {}", alpn),
-		};
-
-		Ok(session.into())
+		}
 	}
 
 	pub fn local_addr(&self) -> anyhow::Result<net::SocketAddr> {
@@ -174,29 +213,183 @@ impl Server {
 	}
 }
 
+/// Configures a [Client] without assembling the quinn and rustls configuration by hand.
+///
+/// The ALPN is chosen for each connection by the URL scheme: `https` for WebTransport and `moqt` for raw QUIC.
+pub struct ClientBuilder {
+	bind: net::SocketAddr,
+	roots: Vec<CertificateDer<'static>>,
+	root_files: Vec<path::PathBuf>,
+	disable_verify: bool,
+	congestion: Congestion,
+	idle_timeout: time::Duration,
+	keep_alive: Option<time::Duration>,
+	keylog: Option<path::PathBuf>,
+	zero_rtt: bool,
+}
+
+impl ClientBuilder {
+	pub const IDLE_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+	pub const KEEP_ALIVE: time::Duration = time::Duration::from_secs(4);
+
+	/// Listen for UDP packets on the given address, by default any port.
+	pub fn bind(mut self, bind: net::SocketAddr) -> Self {
+		self.bind = bind;
+		self
+	}
+
+	/// Trust this root certificate instead of the platform's roots.
+	pub fn root(mut self, root: CertificateDer<'static>) -> Self {
+		self.roots.push(root);
+		self
+	}
+
+	/// Trust the root certificate in this PEM file instead of the platform's roots.
+	pub fn root_file(mut self, path: impl Into<path::PathBuf>) -> Self {
+		self.root_files.push(path.into());
+		self
+	}
+
+	/// Danger: Disable TLS certificate verification, for local development only.
+	pub fn disable_verify(mut self, disable: bool) -> Self {
+		self.disable_verify = disable;
+		self
+	}
+
+	pub fn congestion(mut self, congestion: Congestion) -> Self {
+		self.congestion = congestion;
+		self
+	}
+
+	/// Close the connection after this long without hearing from the server.
+	pub fn idle_timeout(mut self, timeout: time::Duration) -> Self {
+		self.idle_timeout = timeout;
+		self
+	}
+
+	/// Send a keep-alive at this interval, which should be less than the idle timeout, or None to disable.
+	pub fn keep_alive(mut self, interval: Option<time::Duration>) -> Self {
+		self.keep_alive = interval;
+		self
+	}
+
+	/// Append TLS secrets to this file, instead of the file named by `SSLKEYLOGFILE`.
+	pub fn keylog(mut self, path: impl Into<path::PathBuf>) -> Self {
+		self.keylog = Some(path.into());
+		self
+	}
+
+	/// Send the session setup as 0-RTT when resuming a connection to the same server.
+	///
+	/// This saves a round trip on reconnect, but the early data could be replayed by an attacker.
+	/// A rejected WebTransport request is sent again, but a raw QUIC session fails and should be dialed again.
+	/// The server is then dialed with a full handshake, so a caller like [Client::reconnect] recovers.
+	pub fn zero_rtt(mut self, enabled: bool) -> Self {
+		self.zero_rtt = enabled;
+		self
+	}
+
+	pub fn build(self) -> anyhow::Result<Client> {
+		let mut roots = self.roots;
+		for path in &self.root_files {
+			roots.push(tls::load_root(path)?);
+		}
+
+		let mut config = tls::client(roots, self.disable_verify)?;
+		config.enable_early_data = self.zero_rtt;
+		config.key_log = match &self.keylog {
+			Some(path) => Arc::new(tls::KeyLogPath::new(path)?),
+			None => Arc::new(rustls::KeyLogFile::new()),
+		};
+
+		let transport = transport(self.congestion, self.idle_timeout, self.keep_alive)?;
+
+		let runtime = quinn::default_runtime().context("no async runtime")?;
+		let socket = std::net::UdpSocket::bind(self.bind).context("failed to bind UDP socket")?;
+		let quic = quinn::Endpoint::new(quinn::EndpointConfig::default(), None, socket, runtime)
+			.context("failed to create QUIC endpoint")?;
+
+		Client::new(quic, config, Arc::new(transport))
+	}
+}
+
+impl Default for ClientBuilder {
+	fn default() -> Self {
+		Self {
+			bind: "[::]:0".parse().unwrap(),
+			roots: Vec::new(),
+			root_files: Vec::new(),
+			disable_verify: false,
+			congestion: Congestion::default(),
+			idle_timeout: Self::IDLE_TIMEOUT,
+			keep_alive: Some(Self::KEEP_ALIVE),
+			keylog: None,
+			zero_rtt: false,
+		}
+	}
+}
+
 #[derive(Clone)]
 pub struct Client {
 	quic: quinn::Endpoint,
-	config: rustls::ClientConfig,
-	transport: Arc<quinn::TransportConfig>,
+
+	// A configuration per ALPN, each with its own session store, since 0-RTT requires resuming the same ALPN.
+	webtransport: quinn::ClientConfig,
+	raw: quinn::ClientConfig,
+
+	zero_rtt: bool,
+
+	// Servers that rejected 0-RTT, which are dialed with a full handshake from then on.
+	rejected: Arc<Mutex<HashSet<net::SocketAddr>>>,
 }
 
 impl Client {
-	pub async fn connect(&self, url: &Url) -> anyhow::Result<web_transport::Session> {
-		let mut config = self.config.clone();
-
-		// TODO support connecting to both ALPNs at the same time
-		config.alpn_protocols = vec![match url.scheme() {
-			"https" => web_transport_quinn::ALPN.to_vec(),
-			"moqt" => moq_transport::setup::ALPN.to_vec(),
-			_ => anyhow::bail!("url scheme must be 'https' or 'moqt'"),
-		}];
+	fn new(
+		quic: quinn::Endpoint,
+		config: rustls::ClientConfig,
+		transport: Arc<quinn::TransportConfig>,
+	) -> anyhow::Result<Self> {
+		Ok(Self {
+			quic,
+			zero_rtt: config.enable_early_data,
+			rejected: Default::default(),
+			webtransport: Self::config(config.clone(), web_transport_quinn::ALPN, transport.clone())?,
+			raw: Self::config(config, moq_transport::setup::ALPN, transport)?,
+		})
+	}
 
-		config.key_log = Arc::new(rustls::KeyLogFile::new());
+	fn config(
+		mut config: rustls::ClientConfig,
+		alpn: &[u8],
+		transport: Arc<quinn::TransportConfig>,
+	) -> anyhow::Result<quinn::ClientConfig> {
+		config.alpn_protocols = vec![alpn.to_vec()];
+		config.resumption = rustls::client::Resumption::default();
 
 		let config: quinn::crypto::rustls::QuicClientConfig = config.try_into()?;
 		let mut config = quinn::ClientConfig::new(Arc::new(config));
-		config.transport_config(self.transport.clone());
+		config.transport_config(transport);
+
+		Ok(config)
+	}
+
+	pub fn builder() -> ClientBuilder {
+		ClientBuilder::default()
+	}
+
+	/// Parse the URL and connect to it, see [Self::connect].
+	pub async fn connect_str(&self, url: &str) -> anyhow::Result<transport::Session> {
+		let url = Url::parse(url).context("invalid URL")?;
+		self.connect(&url).await
+	}
+
+	pub async fn connect(&self, url: &Url) -> anyhow::Result<transport::Session> {
+		// TODO support connecting to both ALPNs at the same time
+		let config = match url.scheme() {
+			"https" => self.webtransport.clone(),
+			"moqt" => self.raw.clone(),
+			_ => anyhow::bail!("url scheme must be 'https' or 'moqt'"),
+		};
 
 		let host = url.host().context("invalid DNS name")?.to_string();
 		let port = url.port().unwrap_or(443);
@@ -208,14 +401,77 @@ impl Client {
 			.next()
 			.context("no DNS entries")?;
 
-		let connection = self.quic.connect_with(config, addr, &host)?.await?;
+		let connecting = self.quic.connect_with(config, addr, &host)?;
 
-		let session = match url.scheme() {
-			"https" => web_transport_quinn::connect_with(connection, url).await?,
-			"moqt" => connection.into(),
-			_ => unreachable!(),
+		let zero_rtt = self.zero_rtt && !self.rejected.lock().unwrap().contains(&addr);
+		let (connection, accepted) = match zero_rtt {
+			true => match connecting.into_0rtt() {
+				Ok((connection, accepted)) => (connection, Some(accepted)),
+				// We don't have a session ticket for this server yet.
+				Err(connecting) => (connecting.await?, None),
+			},
+			false => (connecting.await?, None),
 		};
 
-		Ok(session.into())
+		match url.scheme() {
+			"https" => {
+				let session = match accepted {
+					// The CONNECT request is sent as early data, and sent again if the server rejects it.
+					Some(accepted) => {
+						let connect = web_transport_quinn::connect_with(connection.clone(), url);
+						tokio::pin!(connect);
+
+						tokio::select! {
+							biased;
+							false = accepted => {
+								log::debug!("server rejected 0-RTT, retrying: url={}", url);
+								web_transport_quinn::connect_with(connection, url).await?
+							}
+							res = &mut connect => res?,
+						}
+					}
+					None => web_transport_quinn::connect_with(connection, url).await?,
+				};
+
+				Ok(session.into())
+			}
+			"moqt" => {
+				// The SETUP is sent by the caller and lost if the server rejects 0-RTT, so the session can't recover.
+				// Close the connection so the session fails, and use a full handshake when the caller dials again.
+				if let Some(accepted) = accepted {
+					let (connection, rejected) = (connection.clone(), self.rejected.clone());
+					tokio::spawn(async move {
+						if !accepted.await {
+							log::warn!("server rejected 0-RTT, closing: addr={}", addr);
+							rejected.lock().unwrap().insert(addr);
+							connection.close(quinn::VarInt::from_u32(0), b"0-RTT rejected");
+						}
+					});
+				}
+
+				Ok(connection.into())
+			}
+			_ => unreachable!(),
+		}
+	}
+
+	/// Move every connection to a new local socket, such as after switching from Wi-Fi to cellular.
+	///
+	/// Sessions migrate to the new path without interruption, since the server allows migration by default.
+	/// See [moq_transport::session::SessionPath] to react on the server.
+	pub fn rebind(&self, bind: net::SocketAddr) -> anyhow::Result<()> {
+		let socket = net::UdpSocket::bind(bind).context("failed to bind UDP socket")?;
+		self.quic.rebind(socket).context("failed to rebind")
+	}
+
+	/// Connect to the URL, dialing it again with backoff whenever the session fails; see [Reconnect].
+	pub async fn reconnect(&self, url: &Url, reconnect: Reconnect) -> Result<(), SessionError> {
+		reconnect
+			.run(move || async move {
+				self.connect(url)
+					.await
+					.map_err(|err| transport::TransportError::Failed(err.to_string()).into())
+			})
+			.await
 	}
 }
@@ -0,0 +1,775 @@
+//! An alternate QUIC backend using [quiche](https://github.com/cloudflare/quiche), enabled with the `quiche` feature.
+//!
+//! Useful for quiche's congestion controllers, or a FIPS build of BoringSSL.
+//! Only raw QUIC is supported, using `moqt://` URLs and the [moq_transport::setup::ALPN];
+//! it interoperates with the same mode of [crate::quic], but not with WebTransport clients.
+use std::{
+	collections::HashMap,
+	net,
+	sync::{Arc, Mutex},
+	time,
+};
+
+use anyhow::Context;
+use bytes::Bytes;
+use futures::{future::BoxFuture, FutureExt};
+use moq_transport::{setup, transport};
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::sync::{mpsc, Notify};
+use url::Url;
+
+use crate::tls;
+
+// The maximum size of a UDP payload, sent or received.
+const MAX_PAYLOAD: usize = 1350;
+
+pub struct Config {
+	pub bind: net::SocketAddr,
+
+	/// Only the paths are used, since quiche loads the certificates itself.
+	pub tls: tls::Args,
+
+	pub congestion: ::quiche::CongestionControlAlgorithm,
+}
+
+pub struct Endpoint {
+	pub client: Client,
+	pub server: Option<Server>,
+}
+
+impl Endpoint {
+	pub fn new(config: Config) -> anyhow::Result<Self> {
+		let client = quiche_config(&config, false)?;
+		let server = match config.tls.cert.is_empty() {
+			true => None,
+			false => Some(quiche_config(&config, true)?),
+		};
+
+		let socket = std::net::UdpSocket::bind(config.bind).context("failed to bind UDP socket")?;
+		socket.set_nonblocking(true)?;
+		let socket = Arc::new(tokio::net::UdpSocket::from_std(socket)?);
+
+		let (accept, incoming) = mpsc::unbounded_channel();
+		let has_server = server.is_some();
+
+		let router = Arc::new(Router {
+			socket: socket.clone(),
+			conns: Default::default(),
+			server: server.map(|config| (Mutex::new(config), accept)),
+		});
+
+		tokio::spawn(router.clone().run());
+
+		Ok(Self {
+			client: Client {
+				router,
+				config: Arc::new(Mutex::new(client)),
+			},
+			server: has_server.then_some(Server { socket, incoming }),
+		})
+	}
+}
+
+fn quiche_config(config: &Config, server: bool) -> anyhow::Result<::quiche::Config> {
+	let mut quiche = ::quiche::Config::new(::quiche::PROTOCOL_VERSION)?;
+	quiche.set_application_protos(&[setup::ALPN])?;
+	quiche.set_cc_algorithm(config.congestion);
+
+	// Match the limits used by quinn, which are generous enough for media.
+	quiche.set_max_idle_timeout(10_000);
+	quiche.set_max_recv_udp_payload_size(MAX_PAYLOAD);
+	quiche.set_max_send_udp_payload_size(MAX_PAYLOAD);
+	quiche.set_initial_max_data(10_000_000);
+	quiche.set_initial_max_stream_data_bidi_local(1_000_000);
+	quiche.set_initial_max_stream_data_bidi_remote(1_000_000);
+	quiche.set_initial_max_stream_data_uni(1_000_000);
+	quiche.set_initial_max_streams_bidi(100);
+	quiche.set_initial_max_streams_uni(1_000);
+	quiche.enable_dgram(true, 1_000, 1_000);
+
+	if server {
+		// TODO support multiple certificates, selected by SNI like the quinn server.
+		let cert = config.tls.cert.first().context("missing certificate")?;
+		let key = config.tls.key.first().context("missing key")?;
+
+		quiche.load_cert_chain_from_pem_file(cert.to_str().context("invalid path")?)?;
+		quiche.load_priv_key_from_pem_file(key.to_str().context("invalid path")?)?;
+	} else {
+		// The system roots are used unless specific roots are provided.
+		for root in &config.tls.root {
+			quiche.load_verify_locations_from_file(root.to_str().context("invalid path")?)?;
+		}
+
+		quiche.verify_peer(!config.tls.disable_verify);
+	}
+
+	Ok(quiche)
+}
+
+#[derive(Clone)]
+pub struct Client {
+	router: Arc<Router>,
+	config: Arc<Mutex<::quiche::Config>>,
+}
+
+impl Client {
+	pub async fn connect(&self, url: &Url) -> anyhow::Result<transport::Session> {
+		anyhow::ensure!(url.scheme() == "moqt", "url scheme must be 'moqt'");
+
+		let host = url.host().context("invalid DNS name")?.to_string();
+		let port = url.port().unwrap_or(443);
+
+		let addr = tokio::net::lookup_host((host.clone(), port))
+			.await
+			.context("failed DNS lookup")?
+			.next()
+			.context("no DNS entries")?;
+
+		let scid = connection_id();
+		let local = self.router.socket.local_addr()?;
+
+		let conn = {
+			let mut config = self.config.lock().unwrap();
+			::quiche::connect(Some(&host), &scid, local, addr, &mut config)?
+		};
+
+		let conn = self.router.spawn(conn, vec![scid]);
+		conn.established().await?;
+
+		Ok(transport::Session::new(conn))
+	}
+}
+
+pub struct Server {
+	socket: Arc<tokio::net::UdpSocket>,
+	incoming: mpsc::UnboundedReceiver<Connection>,
+}
+
+impl Server {
+	pub async fn accept(&mut self) -> Option<transport::Session> {
+		let conn = self.incoming.recv().await?;
+		Some(transport::Session::new(conn))
+	}
+
+	pub fn local_addr(&self) -> anyhow::Result<net::SocketAddr> {
+		self.socket.local_addr().context("failed to get local address")
+	}
+}
+
+fn connection_id() -> ::quiche::ConnectionId<'static> {
+	let mut id = vec![0; ::quiche::MAX_CONN_ID_LEN];
+	SystemRandom::new()
+		.fill(&mut id)
+		.expect("failed to generate connection ID");
+	::quiche::ConnectionId::from_vec(id)
+}
+
+struct Packet {
+	data: Vec<u8>,
+	info: ::quiche::RecvInfo,
+}
+
+// Reads from the socket and routes packets to connections by their ID, accepting new connections if a server.
+struct Router {
+	socket: Arc<tokio::net::UdpSocket>,
+	conns: Mutex<HashMap<::quiche::ConnectionId<'static>, mpsc::UnboundedSender<Packet>>>,
+	server: Option<(Mutex<::quiche::Config>, mpsc::UnboundedSender<Connection>)>,
+}
+
+impl Router {
+	async fn run(self: Arc<Self>) {
+		let mut buf = vec![0; 65535];
+
+		loop {
+			let (size, from) = match self.socket.recv_from(&mut buf).await {
+				Ok(res) => res,
+				Err(err) => {
+					log::warn!("failed to receive UDP packet: {}", err);
+					continue;
+				}
+			};
+
+			if let Err(err) = self.route(&mut buf[..size], from).await {
+				log::debug!("dropped packet: from={} err={}", from, err);
+			}
+		}
+	}
+
+	async fn route(self: &Arc<Self>, packet: &mut [u8], from: net::SocketAddr) -> anyhow::Result<()> {
+		let header = ::quiche::Header::from_slice(packet, ::quiche::MAX_CONN_ID_LEN)?;
+		let (ty, version) = (header.ty, header.version);
+
+		// Copy the IDs so the packet can be borrowed again.
+		let dcid = header.dcid.into_owned();
+		let scid = header.scid.into_owned();
+
+		let info = ::quiche::RecvInfo {
+			from,
+			to: self.socket.local_addr()?,
+		};
+
+		if let Some(conn) = self.conns.lock().unwrap().get(&dcid) {
+			conn.send(Packet {
+				data: packet.to_vec(),
+				info,
+			})
+			.ok();
+			return Ok(());
+		}
+
+		let (config, accept) = self.server.as_ref().context("not a server")?;
+		anyhow::ensure!(ty == ::quiche::Type::Initial, "unknown connection");
+
+		if !::quiche::version_is_supported(version) {
+			let mut out = vec![0; MAX_PAYLOAD];
+			let size = ::quiche::negotiate_version(&scid, &dcid, &mut out)?;
+			self.socket.send_to(&out[..size], from).await?;
+			return Ok(());
+		}
+
+		// TODO send a RETRY to validate the address first.
+		let id = connection_id();
+		let conn = ::quiche::accept(&id, None, info.to, from, &mut config.lock().unwrap())?;
+
+		// Route by the original ID too, since the client uses it until it receives our reply.
+		let conn = self.spawn(conn, vec![id, dcid]);
+
+		self.conns.lock().unwrap()[&conn.0.ids[0]]
+			.send(Packet {
+				data: packet.to_vec(),
+				info,
+			})
+			.ok();
+
+		let accept = accept.clone();
+		tokio::spawn(async move {
+			match conn.established().await {
+				Ok(()) => accept.send(conn).ok(),
+				Err(err) => {
+					log::warn!("failed to accept QUIC connection: {}", err);
+					None
+				}
+			};
+		});
+
+		Ok(())
+	}
+
+	// Run the connection, routing packets for any of the IDs to it.
+	fn spawn(self: &Arc<Self>, conn: ::quiche::Connection, ids: Vec<::quiche::ConnectionId<'static>>) -> Connection {
+		let (sender, packets) = mpsc::unbounded_channel();
+
+		let mut conns = self.conns.lock().unwrap();
+		for id in &ids {
+			conns.insert(id.clone(), sender.clone());
+		}
+
+		let (accept_uni, uni) = mpsc::unbounded_channel();
+		let (accept_bi, bi) = mpsc::unbounded_channel();
+
+		let inner = Arc::new(Inner {
+			state: Mutex::new(State {
+				conn,
+				next: [0, 0],
+				remote: [0, 0],
+				fins: Vec::new(),
+				accept_uni,
+				accept_bi,
+			}),
+			changed: Notify::new(),
+			wake: Notify::new(),
+			ids,
+			accept_uni: tokio::sync::Mutex::new(uni),
+			accept_bi: tokio::sync::Mutex::new(bi),
+		});
+
+		tokio::spawn(drive(inner.clone(), self.clone(), packets));
+
+		Connection(inner)
+	}
+}
+
+struct Inner {
+	state: Mutex<State>,
+
+	// Notified after the driver processes any packets or timers, so blocked streams can retry.
+	changed: Notify,
+
+	// Notified when there's something new to send.
+	wake: Notify,
+
+	// The connection IDs routed to this connection.
+	ids: Vec<::quiche::ConnectionId<'static>>,
+
+	accept_uni: tokio::sync::Mutex<mpsc::UnboundedReceiver<transport::RecvStream>>,
+	accept_bi: tokio::sync::Mutex<mpsc::UnboundedReceiver<(transport::SendStream, transport::RecvStream)>>,
+}
+
+impl Inner {
+	// Retry the function each time the connection changes, until it returns a result.
+	async fn wait<T, F>(&self, mut f: F) -> T
+	where
+		F: FnMut(&mut State) -> Option<T>,
+	{
+		loop {
+			let changed = self.changed.notified();
+			tokio::pin!(changed);
+			changed.as_mut().enable();
+
+			if let Some(res) = f(&mut *self.state.lock().unwrap()) {
+				// Any change may have queued packets, so wake up the driver.
+				self.wake.notify_one();
+				return res;
+			}
+
+			changed.await;
+		}
+	}
+
+	fn update<T, F>(&self, f: F) -> T
+	where
+		F: FnOnce(&mut State) -> T,
+	{
+		let res = f(&mut *self.state.lock().unwrap());
+		self.wake.notify_one();
+		res
+	}
+}
+
+struct State {
+	conn: ::quiche::Connection,
+
+	// The index of the next stream to open, by whether it's unidirectional.
+	next: [u64; 2],
+
+	// The index of the next stream the peer will open, by whether it's unidirectional.
+	remote: [u64; 2],
+
+	// Streams that were dropped but couldn't be finished yet due to flow control.
+	fins: Vec<u64>,
+
+	accept_uni: mpsc::UnboundedSender<transport::RecvStream>,
+	accept_bi: mpsc::UnboundedSender<(transport::SendStream, transport::RecvStream)>,
+}
+
+impl State {
+	fn error(&self) -> Option<transport::TransportError> {
+		if !self.conn.is_closed() && !self.conn.is_draining() {
+			return None;
+		}
+
+		Some(match self.conn.peer_error() {
+			Some(err) if err.is_app => transport::TransportError::Closed {
+				code: err.error_code.try_into().unwrap_or(u32::MAX),
+				reason: String::from_utf8_lossy(&err.reason).to_string(),
+			},
+			Some(err) => transport::TransportError::Failed(format!("connection error: {:?}", err)),
+			None if self.conn.is_timed_out() => transport::TransportError::Failed("idle timeout".to_string()),
+			None => transport::TransportError::Failed("closed locally".to_string()),
+		})
+	}
+
+	fn server(&self) -> bool {
+		self.conn.is_server()
+	}
+
+	// Accept any new streams opened by the peer.
+	fn accept(&mut self, inner: &Arc<Inner>) {
+		let server = !self.server();
+		let readable: Vec<u64> = self.conn.readable().collect();
+
+		for id in readable {
+			if id & 1 != server as u64 {
+				continue;
+			}
+
+			// Like quinn, streams are accepted in order, so a new stream accepts any lower streams too.
+			let uni = id & 2 == 2;
+			while self.remote[uni as usize] <= id >> 2 {
+				let id = stream_id(self.remote[uni as usize], uni, server);
+				self.remote[uni as usize] += 1;
+
+				let recv = transport::RecvStream::new(QuicheRecv::new(inner, id));
+				if uni {
+					self.accept_uni.send(recv).ok();
+				} else {
+					let send = transport::SendStream::new(QuicheSend::new(inner, id));
+					self.accept_bi.send((send, recv)).ok();
+				}
+			}
+		}
+	}
+
+	// Retry finishing any dropped streams.
+	fn finish(&mut self) {
+		let conn = &mut self.conn;
+		self.fins
+			.retain(|id| matches!(conn.stream_send(*id, &[], true), Err(::quiche::Error::Done)));
+	}
+}
+
+// The lowest bit is set for streams opened by the server, and the next bit for unidirectional streams.
+fn stream_id(index: u64, uni: bool, server: bool) -> u64 {
+	(index << 2) | ((uni as u64) << 1) | (server as u64)
+}
+
+// Send and receive packets for the connection until it's closed.
+async fn drive(inner: Arc<Inner>, router: Arc<Router>, mut packets: mpsc::UnboundedReceiver<Packet>) {
+	let mut out = vec![0; MAX_PAYLOAD];
+
+	loop {
+		let (send, timeout, closed) = {
+			let mut state = inner.state.lock().unwrap();
+			state.finish();
+			state.accept(&inner);
+
+			// NOTE: Packets are sent immediately, ignoring quiche's pacing hints.
+			let mut send = Vec::new();
+			loop {
+				match state.conn.send(&mut out) {
+					Ok((size, info)) => send.push((out[..size].to_vec(), info.to)),
+					Err(::quiche::Error::Done) => break,
+					Err(err) => {
+						log::warn!("failed to send QUIC packet: {}", err);
+						state.conn.close(false, 0x1, b"internal error").ok();
+						break;
+					}
+				}
+			}
+
+			(send, state.conn.timeout(), state.conn.is_closed())
+		};
+
+		for (packet, to) in send {
+			if let Err(err) = router.socket.send_to(&packet, to).await {
+				log::warn!("failed to send UDP packet: {}", err);
+			}
+		}
+
+		inner.changed.notify_waiters();
+
+		if closed {
+			break;
+		}
+
+		let timeout = timeout.unwrap_or(time::Duration::from_secs(60));
+
+		tokio::select! {
+			packet = packets.recv() => {
+				// NOTE: The router holds a sender until we remove our IDs.
+				let mut packet = packet.expect("router closed");
+				let mut state = inner.state.lock().unwrap();
+				if let Err(err) = state.conn.recv(&mut packet.data, packet.info) {
+					log::debug!("failed to process QUIC packet: {}", err);
+				}
+			},
+			_ = tokio::time::sleep(timeout) => inner.state.lock().unwrap().conn.on_timeout(),
+			_ = inner.wake.notified() => {},
+		}
+	}
+
+	let mut conns = router.conns.lock().unwrap();
+	for id in &inner.ids {
+		conns.remove(id);
+	}
+}
+
+#[derive(Clone)]
+struct Connection(Arc<Inner>);
+
+impl Connection {
+	async fn established(&self) -> Result<(), transport::TransportError> {
+		self.0
+			.wait(|state| match state.error() {
+				Some(err) => Some(Err(err)),
+				None => state.conn.is_established().then_some(Ok(())),
+			})
+			.await
+	}
+
+	fn open(&self, uni: bool) -> BoxFuture<'_, Result<u64, transport::TransportError>> {
+		async move {
+			self.0
+				.wait(|state| {
+					if let Some(err) = state.error() {
+						return Some(Err(err));
+					}
+
+					let left = match uni {
+						true => state.conn.peer_streams_left_uni(),
+						false => state.conn.peer_streams_left_bidi(),
+					};
+
+					// NOTE: This is only an estimate, since streams are created on the first write.
+					if left == 0 {
+						return None;
+					}
+
+					let index = state.next[uni as usize];
+					state.next[uni as usize] += 1;
+
+					Some(Ok(stream_id(index, uni, state.server())))
+				})
+				.await
+		}
+		.boxed()
+	}
+}
+
+impl transport::Transport for Connection {
+	fn open_uni(&self) -> BoxFuture<'_, Result<transport::SendStream, transport::TransportError>> {
+		async move {
+			let id = self.open(true).await?;
+			Ok(transport::SendStream::new(QuicheSend::new(&self.0, id)))
+		}
+		.boxed()
+	}
+
+	fn open_bi(
+		&self,
+	) -> BoxFuture<'_, Result<(transport::SendStream, transport::RecvStream), transport::TransportError>> {
+		async move {
+			let id = self.open(false).await?;
+			let send = transport::SendStream::new(QuicheSend::new(&self.0, id));
+			let recv = transport::RecvStream::new(QuicheRecv::new(&self.0, id));
+			Ok((send, recv))
+		}
+		.boxed()
+	}
+
+	fn accept_uni(&self) -> BoxFuture<'_, Result<transport::RecvStream, transport::TransportError>> {
+		async move {
+			let mut accept = self.0.accept_uni.lock().await;
+			tokio::select! {
+				Some(recv) = accept.recv() => Ok(recv),
+				err = self.closed() => Err(err),
+			}
+		}
+		.boxed()
+	}
+
+	fn accept_bi(
+		&self,
+	) -> BoxFuture<'_, Result<(transport::SendStream, transport::RecvStream), transport::TransportError>> {
+		async move {
+			let mut accept = self.0.accept_bi.lock().await;
+			tokio::select! {
+				Some(streams) = accept.recv() => Ok(streams),
+				err = self.closed() => Err(err),
+			}
+		}
+		.boxed()
+	}
+
+	fn send_datagram(&self, payload: Bytes) -> BoxFuture<'_, Result<(), transport::TransportError>> {
+		let res = self.0.update(|state| {
+			if let Some(err) = state.error() {
+				return Err(err);
+			}
+
+			match state.conn.dgram_send(&payload) {
+				// The queue is full, so drop the datagram like the network would.
+				Ok(()) | Err(::quiche::Error::Done) => Ok(()),
+				Err(err) => Err(transport::TransportError::Failed(err.to_string())),
+			}
+		});
+
+		async move { res }.boxed()
+	}
+
+	fn recv_datagram(&self) -> BoxFuture<'_, Result<Bytes, transport::TransportError>> {
+		async move {
+			self.0
+				.wait(|state| match state.conn.dgram_recv_vec() {
+					Ok(datagram) => Some(Ok(datagram.into())),
+					Err(::quiche::Error::Done) => state.error().map(Err),
+					Err(err) => Some(Err(transport::TransportError::Failed(err.to_string()))),
+				})
+				.await
+		}
+		.boxed()
+	}
+
+	fn max_datagram_size(&self) -> BoxFuture<'_, usize> {
+		let size = self.0.update(|state| state.conn.dgram_max_writable_len().unwrap_or(0));
+		async move { size }.boxed()
+	}
+
+	fn close(&self, code: u32, reason: &str) {
+		self.0
+			.update(|state| state.conn.close(true, code.into(), reason.as_bytes()).ok());
+	}
+
+	fn closed(&self) -> BoxFuture<'_, transport::TransportError> {
+		self.0.wait(|state| state.error()).boxed()
+	}
+}
+
+struct QuicheSend {
+	inner: Arc<Inner>,
+	id: u64,
+
+	// Set once the stream is reset.
+	done: bool,
+}
+
+impl QuicheSend {
+	fn new(inner: &Arc<Inner>, id: u64) -> Self {
+		Self {
+			inner: inner.clone(),
+			id,
+			done: false,
+		}
+	}
+}
+
+impl transport::SendTransport for QuicheSend {
+	fn write<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<usize, transport::TransportError>> {
+		async move {
+			if buf.is_empty() {
+				return Ok(0);
+			}
+
+			self.inner
+				.wait(|state| {
+					if let Some(err) = state.error() {
+						return Some(Err(err));
+					}
+
+					match state.conn.stream_send(self.id, buf, false) {
+						Ok(0) | Err(::quiche::Error::Done) => None,
+						Ok(size) => Some(Ok(size)),
+						Err(::quiche::Error::StreamStopped(code)) => Some(Err(transport::TransportError::Stopped(
+							code.try_into().unwrap_or(u32::MAX),
+						))),
+						Err(err) => Some(Err(transport::TransportError::Failed(err.to_string()))),
+					}
+				})
+				.await
+		}
+		.boxed()
+	}
+
+	fn write_chunk(&mut self, mut chunk: Bytes) -> BoxFuture<'_, Result<(), transport::TransportError>> {
+		async move {
+			while !chunk.is_empty() {
+				let size = self.write(&chunk).await?;
+				chunk = chunk.slice(size..);
+			}
+
+			Ok(())
+		}
+		.boxed()
+	}
+
+	fn set_priority(&mut self, order: i32) {
+		// quiche uses the 8 urgency levels from RFC 9218, where lower is sent first.
+		let urgency = (3 - order.clamp(-4, 3)) as u8;
+		self.inner
+			.update(|state| state.conn.stream_priority(self.id, urgency, false).ok());
+	}
+
+	fn reset(&mut self, code: u32) {
+		self.done = true;
+		self.inner.update(|state| {
+			state
+				.conn
+				.stream_shutdown(self.id, ::quiche::Shutdown::Write, code.into())
+				.ok()
+		});
+	}
+}
+
+impl Drop for QuicheSend {
+	fn drop(&mut self) {
+		// Like quinn, dropping the stream finishes it.
+		if !self.done {
+			self.inner.update(|state| state.fins.push(self.id));
+		}
+	}
+}
+
+struct QuicheRecv {
+	inner: Arc<Inner>,
+	id: u64,
+
+	// Set once the stream is finished, stopped or reset.
+	done: bool,
+}
+
+impl QuicheRecv {
+	fn new(inner: &Arc<Inner>, id: u64) -> Self {
+		Self {
+			inner: inner.clone(),
+			id,
+			done: false,
+		}
+	}
+}
+
+impl transport::RecvTransport for QuicheRecv {
+	fn read_chunk(&mut self, max: usize) -> BoxFuture<'_, Result<Option<Bytes>, transport::TransportError>> {
+		async move {
+			if self.done {
+				return Ok(None);
+			}
+
+			let res = self
+				.inner
+				.wait(|state| {
+					if !state.conn.stream_readable(self.id) {
+						return state.error().map(Err);
+					}
+
+					let mut buf = vec![0; max.min(65535)];
+					match state.conn.stream_recv(self.id, &mut buf) {
+						Ok((size, fin)) => {
+							buf.truncate(size);
+							Some(Ok((buf, fin)))
+						}
+						Err(::quiche::Error::Done) => None,
+						Err(::quiche::Error::StreamReset(code)) => Some(Err(transport::TransportError::Reset(
+							code.try_into().unwrap_or(u32::MAX),
+						))),
+						Err(err) => Some(Err(transport::TransportError::Failed(err.to_string()))),
+					}
+				})
+				.await;
+
+			let (chunk, fin) = match res {
+				Ok(res) => res,
+				Err(err) => {
+					self.done = true;
+					return Err(err);
+				}
+			};
+
+			self.done = fin;
+
+			match chunk.is_empty() && fin {
+				true => Ok(None),
+				false => Ok(Some(chunk.into())),
+			}
+		}
+		.boxed()
+	}
+
+	fn stop(&mut self, code: u32) {
+		if !self.done {
+			self.done = true;
+			self.inner.update(|state| {
+				state
+					.conn
+					.stream_shutdown(self.id, ::quiche::Shutdown::Read, code.into())
+					.ok()
+			});
+		}
+	}
+}
+
+impl Drop for QuicheRecv {
+	fn drop(&mut self) {
+		// Like quinn, dropping an unfinished stream asks the peer to stop sending.
+		transport::RecvTransport::stop(self, 0);
+	}
+}
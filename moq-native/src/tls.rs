@@ -1,14 +1,15 @@
 use anyhow::Context;
 use clap::Parser;
 use ring::digest::{digest, SHA256};
-use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
 use rustls::server::{ClientHello, ResolvesServerCert};
 use rustls::sign::CertifiedKey;
 use rustls::RootCertStore;
 use std::fs;
-use std::io::{self, Cursor, Read};
+use std::io::{self, Cursor, Read, Write};
 use std::path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Parser, Clone, Default)]
 #[group(id = "tls")]
@@ -27,6 +28,14 @@ pub struct Args {
 	#[arg(long = "tls-key")]
 	pub key: Vec<path::PathBuf>,
 
+	/// Generate a self-signed certificate for this hostname, for local development.
+	///
+	/// Browsers accept it via `serverCertificateHashes`, which requires a validity of two weeks or less,
+	/// so it's rotated automatically and the current fingerprint should be fetched before connecting.
+	/// You can use this option multiple times for multiple hostnames.
+	#[arg(long = "tls-self-sign")]
+	pub self_sign: Vec<String>,
+
 	/// Use the TLS root at this path, encoded as PEM.
 	///
 	/// This value can be provided multiple times for multiple roots.
@@ -39,13 +48,36 @@ pub struct Args {
 	/// Fine for local development and between relays, but should be used in caution in production.
 	#[arg(long = "tls-disable-verify")]
 	pub disable_verify: bool,
+
+	/// Accept 0-RTT from clients resuming a session, saving them a round trip.
+	///
+	/// Danger: Early data can be replayed by an attacker, and a replayed SUBSCRIBE or ANNOUNCE creates real state.
+	#[arg(long = "tls-zero-rtt")]
+	pub zero_rtt: bool,
 }
 
 #[derive(Clone)]
 pub struct Config {
 	pub client: rustls::ClientConfig,
 	pub server: Option<rustls::ServerConfig>,
-	pub fingerprints: Vec<String>,
+	pub fingerprints: Fingerprints,
+}
+
+impl Config {
+	/// Returns true if we serve a generated certificate, which browsers only accept by fingerprint.
+	pub fn self_signed(&self) -> bool {
+		self.fingerprints.0.generated.is_some()
+	}
+}
+
+/// The SHA256 fingerprints of our certificates, which change when a self-signed certificate is rotated.
+#[derive(Clone)]
+pub struct Fingerprints(Arc<ServeCerts>);
+
+impl Fingerprints {
+	pub fn get(&self) -> Vec<String> {
+		self.0.fingerprints()
+	}
 }
 
 impl Args {
@@ -62,51 +94,34 @@ impl Args {
 			serve.load(chain, key)?;
 		}
 
-		// Create a list of acceptable root certificates.
-		let mut roots = RootCertStore::empty();
-
-		if self.root.is_empty() {
-			// Add the platform's native root certificates.
-			for cert in rustls_native_certs::load_native_certs().context("could not load platform certs")? {
-				roots.add(cert).context("failed to add root cert")?;
-			}
-		} else {
-			// Add the specified root certificates.
-			for root in &self.root {
-				let root = fs::File::open(root).context("failed to open root cert file")?;
-				let mut root = io::BufReader::new(root);
-
-				let root = rustls_pemfile::certs(&mut root)
-					.next()
-					.context("no roots found")?
-					.context("failed to read root cert")?;
-
-				roots.add(root).context("failed to add root cert")?;
-			}
+		if !self.self_sign.is_empty() {
+			serve.generated = Some(SelfSigned::new(self.self_sign.clone())?);
 		}
 
 		// Create the TLS configuration we'll use as a client (relay -> relay)
-		let mut client = rustls::ClientConfig::builder_with_provider(provider.clone())
-			.with_protocol_versions(&[&rustls::version::TLS13])?
-			.with_root_certificates(roots)
-			.with_no_client_auth();
-
-		// Allow disabling TLS verification altogether.
-		if self.disable_verify {
-			let noop = NoCertificateVerification(provider.clone());
-			client.dangerous().set_certificate_verifier(Arc::new(noop));
-		}
+		let roots = self
+			.root
+			.iter()
+			.map(|root| load_root(root))
+			.collect::<anyhow::Result<_>>()?;
+		let client = client(roots, self.disable_verify)?;
 
-		let fingerprints = serve.fingerprints();
+		let serve = Arc::new(serve);
+		let fingerprints = Fingerprints(serve.clone());
 
 		// Create the TLS configuration we'll use as a server (relay <- browser)
-		let server = if !self.key.is_empty() {
-			Some(
-				rustls::ServerConfig::builder_with_provider(provider)
-					.with_protocol_versions(&[&rustls::version::TLS13])?
-					.with_no_client_auth()
-					.with_cert_resolver(Arc::new(serve)),
-			)
+		let server = if !self.key.is_empty() || !self.self_sign.is_empty() {
+			let mut server = rustls::ServerConfig::builder_with_provider(provider)
+				.with_protocol_versions(&[&rustls::version::TLS13])?
+				.with_no_client_auth()
+				.with_cert_resolver(serve);
+
+			// QUIC requires either no early data or an unlimited amount.
+			if self.zero_rtt {
+				server.max_early_data_size = u32::MAX;
+			}
+
+			Some(server)
 		} else {
 			None
 		};
@@ -119,9 +134,84 @@ impl Args {
 	}
 }
 
+/// Create the TLS configuration used as a client, trusting the given roots or the platform's roots if empty.
+pub fn client(roots: Vec<CertificateDer<'static>>, disable_verify: bool) -> anyhow::Result<rustls::ClientConfig> {
+	let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+	// Create a list of acceptable root certificates.
+	let mut store = RootCertStore::empty();
+
+	if roots.is_empty() {
+		// Add the platform's native root certificates.
+		for cert in rustls_native_certs::load_native_certs().context("could not load platform certs")? {
+			store.add(cert).context("failed to add root cert")?;
+		}
+	} else {
+		for root in roots {
+			store.add(root).context("failed to add root cert")?;
+		}
+	}
+
+	let mut client = rustls::ClientConfig::builder_with_provider(provider.clone())
+		.with_protocol_versions(&[&rustls::version::TLS13])?
+		.with_root_certificates(store)
+		.with_no_client_auth();
+
+	// Allow disabling TLS verification altogether.
+	if disable_verify {
+		let noop = NoCertificateVerification(provider);
+		client.dangerous().set_certificate_verifier(Arc::new(noop));
+	}
+
+	Ok(client)
+}
+
+/// Read the first certificate from a PEM file, used as a root.
+pub fn load_root(path: &path::Path) -> anyhow::Result<CertificateDer<'static>> {
+	let root = fs::File::open(path).context("failed to open root cert file")?;
+	let mut root = io::BufReader::new(root);
+
+	let root = rustls_pemfile::certs(&mut root)
+		.next()
+		.context("no roots found")?
+		.context("failed to read root cert")?;
+
+	Ok(root)
+}
+
+/// Appends TLS secrets to a file in the NSS key log format, so packet captures can be decrypted.
+///
+/// Unlike [rustls::KeyLogFile], the path is chosen by the application rather than `SSLKEYLOGFILE`.
+#[derive(Debug)]
+pub struct KeyLogPath(Mutex<fs::File>);
+
+impl KeyLogPath {
+	pub fn new(path: &path::Path) -> anyhow::Result<Self> {
+		let file = fs::OpenOptions::new()
+			.append(true)
+			.create(true)
+			.open(path)
+			.context("failed to open keylog file")?;
+
+		Ok(Self(Mutex::new(file)))
+	}
+}
+
+impl rustls::KeyLog for KeyLogPath {
+	fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+		let mut file = self.0.lock().unwrap();
+		let res = writeln!(file, "{} {} {}", label, hex::encode(client_random), hex::encode(secret));
+
+		if let Err(err) = res {
+			log::warn!("failed to write keylog: {}", err);
+		}
+	}
+}
+
 #[derive(Default, Debug)]
 struct ServeCerts {
 	list: Vec<Arc<CertifiedKey>>,
+	generated: Option<SelfSigned>,
 }
 
 impl ServeCerts {
@@ -157,6 +247,8 @@ impl ServeCerts {
 	pub fn fingerprints(&self) -> Vec<String> {
 		self.list
 			.iter()
+			.cloned()
+			.chain(self.generated.as_ref().map(SelfSigned::current))
 			.map(|ck| {
 				let fingerprint = digest(&SHA256, ck.cert[0].as_ref());
 				let fingerprint = hex::encode(fingerprint.as_ref());
@@ -166,6 +258,65 @@ impl ServeCerts {
 	}
 }
 
+/// A self-signed certificate that is regenerated once it's halfway to expiring.
+///
+/// A fingerprint fetched just before rotation remains valid for a while, and existing connections are unaffected.
+#[derive(Debug)]
+struct SelfSigned {
+	hostnames: Vec<String>,
+	current: Mutex<(Arc<CertifiedKey>, Instant)>,
+}
+
+impl SelfSigned {
+	// Browsers reject a certificate hash for anything valid longer than two weeks.
+	const VALIDITY: Duration = Duration::from_secs(10 * 24 * 60 * 60);
+	const ROTATE: Duration = Duration::from_secs(5 * 24 * 60 * 60);
+
+	fn new(hostnames: Vec<String>) -> anyhow::Result<Self> {
+		let cert = Self::generate(&hostnames)?;
+
+		Ok(Self {
+			hostnames,
+			current: Mutex::new((cert, Instant::now())),
+		})
+	}
+
+	fn generate(hostnames: &[String]) -> anyhow::Result<Arc<CertifiedKey>> {
+		let mut params = rcgen::CertificateParams::new(hostnames).context("invalid hostname")?;
+
+		// Backdate slightly to allow for clock skew, while keeping the total validity in bounds.
+		let now = time::OffsetDateTime::now_utc();
+		params.not_before = now - Duration::from_secs(60);
+		params.not_after = now + Self::VALIDITY - Duration::from_secs(60);
+
+		// WebTransport requires ECDSA for certificate hashes, which is the default.
+		let key = rcgen::KeyPair::generate().context("failed to generate key")?;
+		let cert = params.self_signed(&key).context("failed to sign certificate")?;
+
+		let key = PrivatePkcs8KeyDer::from(key.serialize_der()).into();
+		let key = rustls::crypto::ring::sign::any_supported_type(&key)?;
+
+		Ok(Arc::new(CertifiedKey::new(vec![cert.der().clone()], key)))
+	}
+
+	// Return the current certificate, rotating it first if needed.
+	fn current(&self) -> Arc<CertifiedKey> {
+		let mut current = self.current.lock().unwrap();
+
+		if current.1.elapsed() >= Self::ROTATE {
+			match Self::generate(&self.hostnames) {
+				Ok(cert) => {
+					log::info!("rotated self-signed certificate: hostnames={:?}", self.hostnames);
+					*current = (cert, Instant::now());
+				}
+				Err(err) => log::warn!("failed to rotate self-signed certificate: {:?}", err),
+			}
+		}
+
+		current.0.clone()
+	}
+}
+
 impl ResolvesServerCert for ServeCerts {
 	fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
 		if let Some(name) = client_hello.server_name() {
@@ -183,8 +334,11 @@ impl ResolvesServerCert for ServeCerts {
 			}
 		}
 
-		// Default to the last certificate if we couldn't find one.
-		self.list.last().cloned()
+		// Default to the generated certificate, or the last certificate if we couldn't find one.
+		match &self.generated {
+			Some(generated) => Some(generated.current()),
+			None => self.list.last().cloned(),
+		}
 	}
 }
 
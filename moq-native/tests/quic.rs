@@ -0,0 +1,110 @@
+use std::path;
+
+use moq_native::{quic, tls};
+use moq_transport::session::{Publisher, Subscriber};
+
+fn server(zero_rtt: bool) -> quic::Endpoint {
+	let dir = path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+	let args = quic::Args {
+		bind: "127.0.0.1:0".parse().unwrap(),
+		tls: tls::Args {
+			cert: vec![dir.join("cert.pem")],
+			key: vec![dir.join("key.pem")],
+			zero_rtt,
+			..Default::default()
+		},
+	};
+
+	quic::Endpoint::new(args.load().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn builder() {
+	let keylog = std::env::temp_dir().join(format!("moq-keylog-{}", std::process::id()));
+
+	// A self-signed certificate for localhost, so verification is disabled.
+	let client = quic::Client::builder()
+		.bind("127.0.0.1:0".parse().unwrap())
+		.disable_verify(true)
+		.congestion(quic::Congestion::Cubic)
+		.keylog(&keylog)
+		.zero_rtt(true)
+		.build()
+		.unwrap();
+
+	let mut server = server(true);
+	let server = server.server.as_mut().unwrap();
+	let addr = server.local_addr().unwrap();
+
+	// The second connection of each scheme resumes the session, using 0-RTT.
+	for url in [
+		format!("moqt://{}", addr),
+		format!("https://{}", addr),
+		format!("moqt://{}", addr),
+		format!("https://{}", addr),
+	] {
+		let (client, server) = tokio::join!(client.connect_str(&url), server.accept());
+
+		let (publish, subscribe) =
+			tokio::join!(Publisher::accept(server.unwrap()), Subscriber::connect(client.unwrap()));
+		let (_publish, _) = publish.unwrap();
+		let (_subscribe, _) = subscribe.unwrap();
+	}
+
+	let secrets = std::fs::read_to_string(&keylog).unwrap();
+	std::fs::remove_file(&keylog).ok();
+	assert!(secrets.contains("CLIENT_HANDSHAKE_TRAFFIC_SECRET"));
+	assert!(secrets.contains("CLIENT_EARLY_TRAFFIC_SECRET"));
+
+	assert!(quic::Client::builder()
+		.build()
+		.unwrap()
+		.connect_str("http://localhost")
+		.await
+		.is_err());
+}
+
+#[tokio::test]
+async fn zero_rtt_rejected() {
+	let client = quic::Client::builder()
+		.bind("127.0.0.1:0".parse().unwrap())
+		.disable_verify(true)
+		.zero_rtt(true)
+		.build()
+		.unwrap();
+
+	// Get a session ticket from one server, which another server can't resume.
+	let mut first = server(true);
+	let first = first.server.as_mut().unwrap();
+	let url = format!("moqt://{}", first.local_addr().unwrap());
+
+	let (client_session, server_session) = tokio::join!(client.connect_str(&url), first.accept());
+	let (publish, subscribe) = tokio::join!(
+		Publisher::accept(server_session.unwrap()),
+		Subscriber::connect(client_session.unwrap())
+	);
+	let (_publish, _) = publish.unwrap();
+	let (_subscribe, _) = subscribe.unwrap();
+
+	let mut second = server(true);
+	let second = second.server.as_mut().unwrap();
+	let url = format!("moqt://{}", second.local_addr().unwrap());
+
+	// The SETUP sent as early data is lost, so the session fails instead of hanging.
+	let (client_session, server_session) = tokio::join!(client.connect_str(&url), second.accept());
+	let (_, subscribe) = tokio::join!(
+		Publisher::accept(server_session.unwrap()),
+		Subscriber::connect(client_session.unwrap())
+	);
+	assert!(subscribe.is_err());
+
+	// Dialing again uses a full handshake.
+	let (client_session, server_session) = tokio::join!(client.connect_str(&url), second.accept());
+	let (publish, subscribe) = tokio::join!(
+		Publisher::accept(server_session.unwrap()),
+		Subscriber::connect(client_session.unwrap())
+	);
+	let (_publish, _) = publish.unwrap();
+	let (_subscribe, _) = subscribe.unwrap();
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "quiche")]
+
+use std::path;
+
+use bytes::Bytes;
+use moq_native::{quic, quiche, tls};
+use moq_transport::{
+	serve::{self, ServeError, TrackReaderMode},
+	session::{Publisher, Subscriber},
+	transport,
+};
+use url::Url;
+
+// A self-signed certificate for localhost, so verification is disabled.
+fn tls() -> tls::Args {
+	let dir = path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+	tls::Args {
+		cert: vec![dir.join("cert.pem")],
+		key: vec![dir.join("key.pem")],
+		self_sign: Vec::new(),
+		root: Vec::new(),
+		disable_verify: true,
+	}
+}
+
+fn quinn() -> quic::Endpoint {
+	let args = quic::Args {
+		bind: "127.0.0.1:0".parse().unwrap(),
+		tls: tls(),
+	};
+
+	quic::Endpoint::new(args.load().unwrap()).unwrap()
+}
+
+fn quiche() -> quiche::Endpoint {
+	quiche::Endpoint::new(quiche::Config {
+		bind: "127.0.0.1:0".parse().unwrap(),
+		tls: tls(),
+		congestion: ::quiche::CongestionControlAlgorithm::CUBIC,
+	})
+	.unwrap()
+}
+
+// Publish a group from the server to the client, then reset it.
+async fn exchange(client: transport::Session, server: transport::Session) {
+	let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+	let (publish, mut publisher) = publish.unwrap();
+	let (subscribe, mut subscriber) = subscribe.unwrap();
+
+	tokio::spawn(publish.run());
+	tokio::spawn(subscribe.run());
+
+	let (mut writer, _, reader) = serve::Tracks::new("test".to_string()).produce();
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+
+	let mut group = groups.append(0).unwrap();
+	group.write(Bytes::from_static(b"hello")).unwrap();
+	group.write(Bytes::from(vec![7; 100_000])).unwrap();
+
+	tokio::spawn(async move { publisher.announce(reader).await });
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = match track_reader.mode().await.unwrap() {
+		TrackReaderMode::Groups(groups) => groups,
+		_ => panic!("wrong mode"),
+	};
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from(vec![7; 100_000])));
+
+	group.close(ServeError::Timeout).unwrap();
+	assert_eq!(reader.read_next().await, Err(ServeError::Timeout));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn quinn_to_quiche() {
+	let client = quinn();
+	let mut server = quiche();
+
+	let server = server.server.as_mut().unwrap();
+	let url = Url::parse(&format!("moqt://{}", server.local_addr().unwrap())).unwrap();
+
+	let (client, server) = tokio::join!(client.client.connect(&url), server.accept());
+	exchange(client.unwrap(), server.unwrap()).await;
+}
+
+#[tokio::test]
+async fn quiche_to_quinn() {
+	let client = quiche();
+	let mut server = quinn();
+
+	let server = server.server.as_mut().unwrap();
+	let url = Url::parse(&format!("moqt://{}", server.local_addr().unwrap())).unwrap();
+
+	let (client, server) = tokio::join!(client.client.connect(&url), server.accept());
+	exchange(client.unwrap(), server.unwrap()).await;
+}
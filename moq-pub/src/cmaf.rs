@@ -0,0 +1,477 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+const VIDEO_TRACK: u32 = 1;
+const AUDIO_TRACK: u32 = 2;
+
+// Video timestamps are in milliseconds, including the composition time offset.
+const VIDEO_TIMESCALE: u32 = 1000;
+
+const KEYFRAME_FLAGS: u32 = 0x0200_0000;
+const DELTA_FLAGS: u32 = 0x0101_0000;
+
+/// An AudioSpecificConfig, which becomes part of the esds box.
+#[derive(Clone)]
+pub struct AacConfig {
+	pub sample_rate: u32,
+	pub channels: u8,
+
+	// Bounded by MAX_SIZE, so it fits in the esds box.
+	raw: Bytes,
+}
+
+impl AacConfig {
+	const SAMPLE_RATES: [u32; 13] = [
+		96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+	];
+
+	// The largest config where every esds descriptor size still fits in a single byte.
+	const MAX_SIZE: usize = 0x7f - (3 + 2 + 13 + 2 + 3);
+
+	pub fn parse(raw: Bytes) -> anyhow::Result<Self> {
+		anyhow::ensure!(raw.len() >= 2, "short AudioSpecificConfig");
+		anyhow::ensure!(
+			raw.len() <= Self::MAX_SIZE,
+			"AudioSpecificConfig too large: {}",
+			raw.len()
+		);
+
+		// 5 bits of object type, 4 bits of frequency index, 4 bits of channel configuration.
+		let bits = u16::from_be_bytes([raw[0], raw[1]]);
+		let index = ((bits >> 7) & 0xf) as usize;
+		let channels = ((bits >> 3) & 0xf) as u8;
+
+		let sample_rate = *Self::SAMPLE_RATES
+			.get(index)
+			.ok_or_else(|| anyhow::anyhow!("unsupported AAC frequency index: {}", index))?;
+
+		Ok(Self {
+			sample_rate,
+			channels,
+			raw,
+		})
+	}
+}
+
+#[derive(Clone)]
+pub enum AudioConfig {
+	Aac(AacConfig),
+	Opus { sample_rate: u32, channels: u8 },
+}
+
+impl AudioConfig {
+	pub fn sample_rate(&self) -> u32 {
+		match self {
+			Self::Aac(aac) => aac.sample_rate,
+			Self::Opus { sample_rate, .. } => *sample_rate,
+		}
+	}
+
+	pub fn channels(&self) -> u8 {
+		match self {
+			Self::Aac(aac) => aac.channels,
+			Self::Opus { channels, .. } => *channels,
+		}
+	}
+}
+
+struct Sample {
+	// The decode time in timescale units.
+	time: u64,
+	cts: i32,
+	keyframe: bool,
+	data: Bytes,
+}
+
+/// Muxes H.264 and AAC or Opus frames into a fragmented MP4, with one sample per fragment.
+///
+/// The init segment is written when the first frame arrives, so every codec must be configured before then.
+#[derive(Default)]
+pub struct Muxer {
+	avcc: Option<Bytes>,
+	audio: Option<AudioConfig>,
+
+	// The resolution provided by the caller, since we don't parse the SPS.
+	width: u16,
+	height: u16,
+
+	started: bool,
+	sequence: u32,
+
+	// The previous video frame, written once the next frame gives us its duration.
+	pending: Option<Sample>,
+
+	// The muxed atoms waiting to be read.
+	out: BytesMut,
+}
+
+impl Muxer {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The atoms written so far, which should be consumed by the caller.
+	pub fn output(&mut self) -> &mut BytesMut {
+		&mut self.out
+	}
+
+	/// Returns true once the init segment has been written.
+	pub fn started(&self) -> bool {
+		self.started
+	}
+
+	pub fn metadata(&mut self, width: u16, height: u16) {
+		self.width = width;
+		self.height = height;
+	}
+
+	/// Set the AVCDecoderConfigurationRecord, which becomes the avcC box.
+	pub fn video_config(&mut self, avcc: Bytes) {
+		match self.started {
+			true => log::warn!("ignoring video config after start"),
+			false => self.avcc = Some(avcc),
+		}
+	}
+
+	pub fn audio_config(&mut self, config: AudioConfig) {
+		match self.started {
+			true => log::warn!("ignoring audio config after start"),
+			false => self.audio = Some(config),
+		}
+	}
+
+	/// The timescale used for audio samples, if audio is configured.
+	pub fn audio_rate(&self) -> Option<u32> {
+		self.audio.as_ref().map(AudioConfig::sample_rate)
+	}
+
+	/// Write a frame of length-prefixed NAL units, with the timestamp and composition offset in milliseconds.
+	pub fn video(&mut self, timestamp: u64, keyframe: bool, cts: i32, data: Bytes) {
+		if self.avcc.is_none() || (!self.started && !keyframe) {
+			return;
+		}
+
+		self.start();
+
+		let sample = Sample {
+			time: timestamp,
+			cts,
+			keyframe,
+			data,
+		};
+
+		if let Some(prev) = self.pending.replace(sample) {
+			let duration = timestamp.saturating_sub(prev.time) as u32;
+			self.fragment(VIDEO_TRACK, prev, duration);
+		}
+	}
+
+	/// Write an audio frame, with the time and duration in units of the sample rate.
+	pub fn audio(&mut self, time: u64, duration: u32, data: Bytes) {
+		if self.audio.is_none() {
+			return;
+		}
+
+		// Wait for a video keyframe so the broadcast starts with a complete group.
+		if !self.started && self.avcc.is_some() {
+			return;
+		}
+
+		self.start();
+
+		let sample = Sample {
+			time,
+			cts: 0,
+			keyframe: true,
+			data,
+		};
+		self.fragment(AUDIO_TRACK, sample, duration);
+	}
+
+	/// Write the last video frame, which has an unknown duration.
+	pub fn finish(&mut self) {
+		if let Some(prev) = self.pending.take() {
+			self.fragment(VIDEO_TRACK, prev, 0);
+		}
+	}
+
+	// Write the ftyp and moov atoms if we haven't already.
+	fn start(&mut self) {
+		if self.started {
+			return;
+		}
+		self.started = true;
+
+		let out = &mut self.out;
+
+		write_box(out, b"ftyp", |buf| {
+			buf.put_slice(b"iso6");
+			buf.put_u32(0);
+			for brand in [b"iso6", b"cmfc", b"isom", b"mp41"] {
+				buf.put_slice(brand);
+			}
+		});
+
+		let avcc = self.avcc.clone();
+		let audio = self.audio.clone();
+		let (width, height) = (self.width, self.height);
+
+		write_box(out, b"moov", |buf| {
+			write_full_box(buf, b"mvhd", 0, 0, |buf| {
+				buf.put_u32(0); // creation time
+				buf.put_u32(0); // modification time
+				buf.put_u32(1000); // timescale
+				buf.put_u32(0); // duration
+				buf.put_u32(0x0001_0000); // rate
+				buf.put_u16(0x0100); // volume
+				buf.put_bytes(0, 10); // reserved
+				put_matrix(buf);
+				buf.put_bytes(0, 24); // pre-defined
+				buf.put_u32(AUDIO_TRACK + 1); // next track ID
+			});
+
+			if let Some(avcc) = &avcc {
+				write_trak(buf, VIDEO_TRACK, VIDEO_TIMESCALE, (width, height), |buf| {
+					write_box(buf, b"avc1", |buf| {
+						put_sample_entry(buf);
+						buf.put_bytes(0, 16); // pre-defined and reserved
+						buf.put_u16(width);
+						buf.put_u16(height);
+						buf.put_u32(0x0048_0000); // horizontal resolution
+						buf.put_u32(0x0048_0000); // vertical resolution
+						buf.put_u32(0); // reserved
+						buf.put_u16(1); // frame count
+						buf.put_bytes(0, 32); // compressor name
+						buf.put_u16(0x0018); // depth
+						buf.put_i16(-1); // pre-defined
+
+						write_box(buf, b"avcC", |buf| buf.put_slice(avcc));
+					});
+				});
+			}
+
+			if let Some(audio) = &audio {
+				let rate = audio.sample_rate();
+				let kind = match audio {
+					AudioConfig::Aac(_) => b"mp4a",
+					AudioConfig::Opus { .. } => b"Opus",
+				};
+
+				write_trak(buf, AUDIO_TRACK, rate, (0, 0), |buf| {
+					write_box(buf, kind, |buf| {
+						put_sample_entry(buf);
+						buf.put_bytes(0, 8); // reserved
+						buf.put_u16(audio.channels().into());
+						buf.put_u16(16); // sample size
+						buf.put_u32(0); // pre-defined and reserved
+						buf.put_u32(rate << 16);
+
+						match audio {
+							AudioConfig::Aac(aac) => {
+								write_full_box(buf, b"esds", 0, 0, |buf| put_es_descriptor(buf, aac))
+							}
+							AudioConfig::Opus { channels, .. } => write_box(buf, b"dOps", |buf| {
+								buf.put_u8(0); // version
+								buf.put_u8(*channels);
+								buf.put_u16(0); // pre-skip
+								buf.put_u32(rate);
+								buf.put_i16(0); // output gain
+								buf.put_u8(0); // channel mapping family
+							}),
+						}
+					});
+				});
+			}
+
+			write_box(buf, b"mvex", |buf| {
+				let tracks = [
+					avcc.is_some().then_some(VIDEO_TRACK),
+					audio.is_some().then_some(AUDIO_TRACK),
+				];
+				for track in tracks.into_iter().flatten() {
+					write_full_box(buf, b"trex", 0, 0, |buf| {
+						buf.put_u32(track);
+						buf.put_u32(1); // sample description index
+						buf.put_u32(0); // duration
+						buf.put_u32(0); // size
+						buf.put_u32(0); // flags
+					});
+				}
+			});
+		});
+	}
+
+	// Write a moof and mdat containing a single sample.
+	fn fragment(&mut self, track: u32, sample: Sample, duration: u32) {
+		self.sequence += 1;
+
+		let out = &mut self.out;
+		let start = out.len();
+		let mut offset = 0;
+
+		write_box(out, b"moof", |buf| {
+			write_full_box(buf, b"mfhd", 0, 0, |buf| buf.put_u32(self.sequence));
+
+			write_box(buf, b"traf", |buf| {
+				// The data offset is relative to the start of the moof.
+				write_full_box(buf, b"tfhd", 0, 0x02_0000, |buf| buf.put_u32(track));
+				write_full_box(buf, b"tfdt", 1, 0, |buf| buf.put_u64(sample.time));
+
+				// Data offset, duration, size, flags, and composition time offset.
+				write_full_box(buf, b"trun", 1, 0x0f01, |buf| {
+					buf.put_u32(1);
+					offset = buf.len();
+					buf.put_i32(0);
+					buf.put_u32(duration);
+					buf.put_u32(sample.data.len() as u32);
+					buf.put_u32(if sample.keyframe { KEYFRAME_FLAGS } else { DELTA_FLAGS });
+					buf.put_i32(sample.cts);
+				});
+			});
+		});
+
+		// Skip over the moof and the mdat header.
+		let data_offset = (out.len() - start + 8) as i32;
+		out[offset..offset + 4].copy_from_slice(&data_offset.to_be_bytes());
+
+		write_box(out, b"mdat", |buf| buf.put_slice(&sample.data));
+	}
+}
+
+// Write a box, filling in the size once the contents are written.
+fn write_box<F: FnOnce(&mut BytesMut)>(buf: &mut BytesMut, kind: &[u8; 4], contents: F) {
+	let start = buf.len();
+	buf.put_u32(0);
+	buf.put_slice(kind);
+
+	contents(buf);
+
+	let size = (buf.len() - start) as u32;
+	buf[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn write_full_box<F: FnOnce(&mut BytesMut)>(buf: &mut BytesMut, kind: &[u8; 4], version: u8, flags: u32, contents: F) {
+	write_box(buf, kind, |buf| {
+		buf.put_u32((version as u32) << 24 | flags);
+		contents(buf);
+	});
+}
+
+// Write a trak with an empty sample table, since every sample is in a fragment.
+fn write_trak<F: FnOnce(&mut BytesMut)>(
+	buf: &mut BytesMut,
+	track: u32,
+	timescale: u32,
+	(width, height): (u16, u16),
+	entry: F,
+) {
+	let video = track == VIDEO_TRACK;
+
+	write_box(buf, b"trak", |buf| {
+		// Enabled and in the movie.
+		write_full_box(buf, b"tkhd", 0, 0x3, |buf| {
+			buf.put_u32(0); // creation time
+			buf.put_u32(0); // modification time
+			buf.put_u32(track);
+			buf.put_u32(0); // reserved
+			buf.put_u32(0); // duration
+			buf.put_bytes(0, 8); // reserved
+			buf.put_u16(0); // layer
+			buf.put_u16(0); // alternate group
+			buf.put_u16(if video { 0 } else { 0x0100 }); // volume
+			buf.put_u16(0); // reserved
+			put_matrix(buf);
+			buf.put_u32((width as u32) << 16);
+			buf.put_u32((height as u32) << 16);
+		});
+
+		write_box(buf, b"mdia", |buf| {
+			write_full_box(buf, b"mdhd", 0, 0, |buf| {
+				buf.put_u32(0); // creation time
+				buf.put_u32(0); // modification time
+				buf.put_u32(timescale);
+				buf.put_u32(0); // duration
+				buf.put_u16(0x55c4); // und
+				buf.put_u16(0); // pre-defined
+			});
+
+			write_full_box(buf, b"hdlr", 0, 0, |buf| {
+				buf.put_u32(0); // pre-defined
+				buf.put_slice(if video { b"vide" } else { b"soun" });
+				buf.put_bytes(0, 12); // reserved
+				buf.put_slice(if video { b"VideoHandler\0" } else { b"SoundHandler\0" });
+			});
+
+			write_box(buf, b"minf", |buf| {
+				if video {
+					write_full_box(buf, b"vmhd", 0, 1, |buf| buf.put_bytes(0, 8));
+				} else {
+					write_full_box(buf, b"smhd", 0, 0, |buf| buf.put_u32(0));
+				}
+
+				write_box(buf, b"dinf", |buf| {
+					write_full_box(buf, b"dref", 0, 0, |buf| {
+						buf.put_u32(1);
+						// The media is in the same file.
+						write_full_box(buf, b"url ", 0, 1, |_| {});
+					});
+				});
+
+				write_box(buf, b"stbl", |buf| {
+					write_full_box(buf, b"stsd", 0, 0, |buf| {
+						buf.put_u32(1);
+						entry(buf);
+					});
+					write_full_box(buf, b"stts", 0, 0, |buf| buf.put_u32(0));
+					write_full_box(buf, b"stsc", 0, 0, |buf| buf.put_u32(0));
+					write_full_box(buf, b"stsz", 0, 0, |buf| buf.put_u64(0));
+					write_full_box(buf, b"stco", 0, 0, |buf| buf.put_u32(0));
+				});
+			});
+		});
+	});
+}
+
+fn put_matrix(buf: &mut BytesMut) {
+	for value in [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000] {
+		buf.put_u32(value);
+	}
+}
+
+// The fields shared by every sample entry.
+fn put_sample_entry(buf: &mut BytesMut) {
+	buf.put_bytes(0, 6); // reserved
+	buf.put_u16(1); // data reference index
+}
+
+fn put_es_descriptor(buf: &mut BytesMut, aac: &AacConfig) {
+	// NOTE: AacConfig::parse limits the size, so this can't fail.
+	let config = u8::try_from(aac.raw.len())
+		.ok()
+		.filter(|&size| size as usize <= AacConfig::MAX_SIZE)
+		.expect("AudioSpecificConfig too large");
+
+	// ES_Descriptor
+	buf.put_u8(0x03);
+	buf.put_u8(3 + 2 + 13 + 2 + config + 3);
+	buf.put_u16(AUDIO_TRACK as u16);
+	buf.put_u8(0); // flags
+
+	// DecoderConfigDescriptor
+	buf.put_u8(0x04);
+	buf.put_u8(13 + 2 + config);
+	buf.put_u8(0x40); // MPEG-4 audio
+	buf.put_u8(0x15); // audio stream
+	buf.put_bytes(0, 3); // buffer size
+	buf.put_u32(0); // max bitrate
+	buf.put_u32(0); // average bitrate
+
+	// DecoderSpecificInfo
+	buf.put_u8(0x05);
+	buf.put_u8(config);
+	buf.put_slice(&aac.raw);
+
+	// SLConfigDescriptor
+	buf.put_u8(0x06);
+	buf.put_u8(1);
+	buf.put_u8(0x02);
+}
@@ -0,0 +1,321 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+const IDR: u8 = 5;
+const SPS: u8 = 7;
+const PPS: u8 = 8;
+
+// Profiles with the chroma format and scaling matrices in the SPS.
+const HIGH_PROFILES: [u8; 13] = [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135];
+
+/// The decoder configuration, built from the parameter sets sent in-band.
+pub struct Config {
+	/// An AVCDecoderConfigurationRecord, which becomes the avcC box.
+	pub avcc: Bytes,
+	pub width: u16,
+	pub height: u16,
+}
+
+impl Config {
+	/// Returns the configuration if the frame contains both an SPS and PPS.
+	pub fn parse(frame: &[u8]) -> anyhow::Result<Option<Self>> {
+		let sps = nal_units(frame).find(|nal| nal_type(nal) == SPS);
+		let pps = nal_units(frame).find(|nal| nal_type(nal) == PPS);
+
+		let (sps, pps) = match (sps, pps) {
+			(Some(sps), Some(pps)) => (sps, pps),
+			_ => return Ok(None),
+		};
+
+		anyhow::ensure!(sps.len() >= 4, "short SPS");
+		let (width, height) = resolution(sps)?;
+
+		let mut avcc = BytesMut::new();
+		avcc.put_u8(1); // version
+		avcc.put_slice(&sps[1..4]); // profile, compatibility, and level
+		avcc.put_u8(0xff); // 4 byte NAL lengths
+		avcc.put_u8(0xe1); // 1 SPS
+		avcc.put_u16(sps.len() as u16);
+		avcc.put_slice(sps);
+		avcc.put_u8(1); // 1 PPS
+		avcc.put_u16(pps.len() as u16);
+		avcc.put_slice(pps);
+
+		Ok(Some(Self {
+			avcc: avcc.freeze(),
+			width,
+			height,
+		}))
+	}
+}
+
+/// Returns true if the frame contains an IDR slice.
+pub fn is_keyframe(frame: &[u8]) -> bool {
+	nal_units(frame).any(|nal| nal_type(nal) == IDR)
+}
+
+/// Convert an Annex B frame, with start codes, into NAL units with a 4 byte length prefix.
+pub fn from_annexb(frame: &[u8]) -> Bytes {
+	let mut out = BytesMut::with_capacity(frame.len() + 16);
+
+	for nal in annexb_units(frame) {
+		out.put_u32(nal.len() as u32);
+		out.put_slice(nal);
+	}
+
+	out.freeze()
+}
+
+// Split on the 3 or 4 byte start codes, trimming the trailing zeros of each NAL unit.
+fn annexb_units(frame: &[u8]) -> Vec<&[u8]> {
+	// The offset after each start code.
+	let starts: Vec<usize> = (2..frame.len())
+		.filter(|&i| frame[i] == 1 && frame[i - 1] == 0 && frame[i - 2] == 0)
+		.map(|i| i + 1)
+		.collect();
+
+	let ends = starts.iter().skip(1).map(|start| start - 3).chain([frame.len()]);
+
+	starts
+		.iter()
+		.zip(ends)
+		.map(|(&start, end)| {
+			let nal = &frame[start..end];
+			let len = nal.iter().rposition(|&byte| byte != 0).map_or(0, |i| i + 1);
+			&nal[..len]
+		})
+		.filter(|nal| !nal.is_empty())
+		.collect()
+}
+
+// Iterate over NAL units with a 4 byte length prefix.
+fn nal_units(mut frame: &[u8]) -> impl Iterator<Item = &[u8]> {
+	std::iter::from_fn(move || {
+		let size = u32::from_be_bytes(frame.get(..4)?.try_into().ok()?) as usize;
+		let nal = frame.get(4..4 + size)?;
+		frame = &frame[4 + size..];
+		Some(nal)
+	})
+}
+
+fn nal_type(nal: &[u8]) -> u8 {
+	nal.first().map(|header| header & 0x1f).unwrap_or_default()
+}
+
+// Parse the SPS until the frame size and cropping.
+fn resolution(sps: &[u8]) -> anyhow::Result<(u16, u16)> {
+	let profile = sps[1];
+	let mut r = BitReader::new(unescape(&sps[4..]));
+
+	r.ue()?; // seq_parameter_set_id
+
+	let mut chroma_format = 1;
+	if HIGH_PROFILES.contains(&profile) {
+		chroma_format = r.ue()?;
+		if chroma_format == 3 && r.bit()? {
+			// Each colour plane is coded separately, like monochrome.
+			chroma_format = 0;
+		}
+
+		r.ue()?; // bit_depth_luma_minus8
+		r.ue()?; // bit_depth_chroma_minus8
+		r.bit()?; // qpprime_y_zero_transform_bypass_flag
+
+		if r.bit()? {
+			let count = if chroma_format == 3 { 12 } else { 8 };
+			for i in 0..count {
+				if r.bit()? {
+					r.scaling_list(if i < 6 { 16 } else { 64 })?;
+				}
+			}
+		}
+	}
+
+	r.ue()?; // log2_max_frame_num_minus4
+
+	match r.ue()? {
+		0 => {
+			r.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+		}
+		1 => {
+			r.bit()?; // delta_pic_order_always_zero_flag
+			r.se()?; // offset_for_non_ref_pic
+			r.se()?; // offset_for_top_to_bottom_field
+			for _ in 0..r.ue()? {
+				r.se()?; // offset_for_ref_frame
+			}
+		}
+		_ => {}
+	}
+
+	r.ue()?; // max_num_ref_frames
+	r.bit()?; // gaps_in_frame_num_value_allowed_flag
+
+	let width_mbs = r.ue()? + 1;
+	let height_units = r.ue()? + 1;
+	let frame_mbs_only = r.bit()? as u32;
+	if frame_mbs_only == 0 {
+		r.bit()?; // mb_adaptive_frame_field_flag
+	}
+	r.bit()?; // direct_8x8_inference_flag
+
+	// A hostile SPS could overflow any of the following.
+	let invalid = || anyhow::anyhow!("invalid SPS frame size");
+
+	let mut width = width_mbs.checked_mul(16).ok_or_else(invalid)?;
+	let mut height = height_units
+		.checked_mul(16 * (2 - frame_mbs_only))
+		.ok_or_else(invalid)?;
+
+	if r.bit()? {
+		let (left, right, top, bottom) = (r.ue()?, r.ue()?, r.ue()?, r.ue()?);
+
+		// The crop units depend on the chroma subsampling.
+		let (crop_x, crop_y) = match chroma_format {
+			0 => (1, 2 - frame_mbs_only),
+			1 => (2, 2 * (2 - frame_mbs_only)),
+			2 => (2, 2 - frame_mbs_only),
+			_ => (1, 2 - frame_mbs_only),
+		};
+
+		let crop = |start: u32, end: u32, units: u32| {
+			start
+				.checked_add(end)
+				.and_then(|crop| crop.checked_mul(units))
+				.ok_or_else(invalid)
+		};
+
+		width = width.saturating_sub(crop(left, right, crop_x)?);
+		height = height.saturating_sub(crop(top, bottom, crop_y)?);
+	}
+
+	Ok((width.try_into()?, height.try_into()?))
+}
+
+// Remove the emulation prevention bytes.
+fn unescape(data: &[u8]) -> Vec<u8> {
+	let mut out = Vec::with_capacity(data.len());
+	let mut zeros = 0;
+
+	for &byte in data {
+		if zeros >= 2 && byte == 3 {
+			zeros = 0;
+			continue;
+		}
+
+		zeros = if byte == 0 { zeros + 1 } else { 0 };
+		out.push(byte);
+	}
+
+	out
+}
+
+struct BitReader {
+	data: Vec<u8>,
+	offset: usize,
+}
+
+impl BitReader {
+	fn new(data: Vec<u8>) -> Self {
+		Self { data, offset: 0 }
+	}
+
+	fn bit(&mut self) -> anyhow::Result<bool> {
+		let byte = self
+			.data
+			.get(self.offset / 8)
+			.ok_or_else(|| anyhow::anyhow!("short SPS"))?;
+		let bit = byte >> (7 - self.offset % 8) & 1;
+		self.offset += 1;
+		Ok(bit == 1)
+	}
+
+	// An unsigned Exp-Golomb code.
+	fn ue(&mut self) -> anyhow::Result<u32> {
+		let mut zeros = 0;
+		while !self.bit()? {
+			zeros += 1;
+			anyhow::ensure!(zeros < 32, "invalid Exp-Golomb code");
+		}
+
+		let mut value = 0;
+		for _ in 0..zeros {
+			value = value << 1 | self.bit()? as u32;
+		}
+
+		Ok((1 << zeros) - 1 + value)
+	}
+
+	// A signed Exp-Golomb code.
+	fn se(&mut self) -> anyhow::Result<i32> {
+		let value = self.ue()?;
+		Ok(match value % 2 {
+			0 => -((value / 2) as i32),
+			_ => value.div_ceil(2) as i32,
+		})
+	}
+
+	fn scaling_list(&mut self, size: usize) -> anyhow::Result<()> {
+		let mut last = 8;
+		let mut next = 8;
+
+		for _ in 0..size {
+			if next != 0 {
+				let delta = self.se()?;
+				anyhow::ensure!((-128..=127).contains(&delta), "invalid scaling list delta: {}", delta);
+				next = (last + delta + 256) % 256;
+			}
+			if next != 0 {
+				last = next;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// Build an SPS with the given profile, followed by the fields as a string of bits.
+	fn build(profile: u8, bits: &str) -> Vec<u8> {
+		let bits: Vec<u8> = bits
+			.bytes()
+			.filter(|bit| !bit.is_ascii_whitespace())
+			.map(|bit| bit - b'0')
+			.collect();
+
+		let mut sps = vec![0x67, profile, 0, 30];
+		for byte in bits.chunks(8) {
+			let byte = byte.iter().chain(std::iter::repeat(&0)).take(8);
+			sps.push(byte.fold(0, |acc, bit| acc << 1 | bit));
+		}
+
+		sps
+	}
+
+	#[test]
+	fn resolution_720p() {
+		// id, frame num, poc type 2, ref frames, gaps, 80 wide, 45 high, frame only, 8x8, no cropping
+		let sps = build(66, "1 1 011 1 0 0000001010000 00000101101 1 1 0");
+		assert_eq!(resolution(&sps).unwrap(), (1280, 720));
+	}
+
+	#[test]
+	fn hostile() {
+		// The largest width in macroblocks, which overflows when converted to pixels.
+		let width = format!("{}1{}", "0".repeat(31), "1".repeat(31));
+		let sps = build(66, &format!("1 1 011 1 0 {width} 1 1 1 0"));
+		assert!(resolution(&sps).is_err());
+
+		// The largest cropping, which overflows when converted to pixels.
+		let crop = format!("{}1{}", "0".repeat(31), "1".repeat(31));
+		let sps = build(66, &format!("1 1 011 1 0 1 1 1 1 1 {crop} {crop} 1 1"));
+		assert!(resolution(&sps).is_err());
+
+		// A scaling list delta that overflows, in a high profile with 4:2:0 chroma.
+		let delta = format!("{}1{}0", "0".repeat(31), "1".repeat(30));
+		let sps = build(100, &format!("1 010 1 1 0 1 1 {delta}"));
+		assert!(resolution(&sps).is_err());
+	}
+}
@@ -1,2 +1,8 @@
+mod cmaf;
+mod h264;
 mod media;
+pub mod rtmp;
+pub mod srt;
+pub mod whip;
+
 pub use media::*;
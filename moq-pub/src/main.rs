@@ -1,13 +1,11 @@
-use bytes::BytesMut;
 use std::net;
 use url::Url;
 
 use anyhow::Context;
 use clap::Parser;
-use tokio::io::AsyncReadExt;
 
 use moq_native::quic;
-use moq_pub::Media;
+use moq_pub::{rtmp, srt, whip, Media};
 use moq_transport::{serve, session::Publisher};
 
 #[derive(Parser, Clone)]
@@ -34,6 +32,18 @@ pub struct Cli {
 	#[arg(long)]
 	pub name: String,
 
+	/// Accept a single RTMP publisher on the given address, instead of reading fMP4 from stdin.
+	#[arg(long)]
+	pub rtmp: Option<net::SocketAddr>,
+
+	/// Accept a single WHIP publisher on the given address, instead of reading fMP4 from stdin.
+	#[arg(long, conflicts_with = "rtmp")]
+	pub whip: Option<net::SocketAddr>,
+
+	/// Accept a single SRT caller sending MPEG-TS on the given address, instead of reading fMP4 from stdin.
+	#[arg(long, conflicts_with_all = ["rtmp", "whip"])]
+	pub srt: Option<net::SocketAddr>,
+
 	/// The TLS configuration.
 	#[command(flatten)]
 	pub tls: moq_native::tls::Args,
@@ -51,7 +61,7 @@ async fn main() -> anyhow::Result<()> {
 
 	let cli = Cli::parse();
 
-	let (writer, _, reader) = serve::Tracks::new(cli.name).produce();
+	let (writer, _, reader) = serve::Tracks::new(cli.name.clone()).produce();
 	let media = Media::new(writer)?;
 
 	let tls = cli.tls.load()?;
@@ -70,19 +80,32 @@ async fn main() -> anyhow::Result<()> {
 
 	tokio::select! {
 		res = session.run() => res.context("session error")?,
-		res = run_media(media) => res.context("media error")?,
+		res = run_media(media, &cli) => res.context("media error")?,
 		res = publisher.announce(reader) => res.context("publisher error")?,
 	}
 
 	Ok(())
 }
 
-async fn run_media(mut media: Media) -> anyhow::Result<()> {
-	let mut input = tokio::io::stdin();
-	let mut buf = BytesMut::new();
+async fn run_media(mut media: Media, cli: &Cli) -> anyhow::Result<()> {
+	if let Some(addr) = cli.whip {
+		return whip::ingest(addr, &mut media).await;
+	}
 
-	loop {
-		input.read_buf(&mut buf).await.context("failed to read from stdin")?;
-		media.parse(&mut buf).context("failed to parse media")?;
+	if let Some(addr) = cli.srt {
+		return srt::ingest(addr, &mut media).await;
 	}
+
+	let addr = match cli.rtmp {
+		Some(addr) => addr,
+		None => return media.read_from(tokio::io::stdin()).await,
+	};
+
+	let listener = tokio::net::TcpListener::bind(addr).await?;
+	log::info!("waiting for RTMP publisher: bind={}", addr);
+
+	let (stream, peer) = listener.accept().await?;
+	log::info!("accepted RTMP publisher: addr={}", peer);
+
+	rtmp::ingest(stream, &mut media).await
 }
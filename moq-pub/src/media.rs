@@ -1,11 +1,13 @@
 use anyhow::{self, Context};
-use bytes::{Buf, Bytes};
+use bytes::{Buf, Bytes, BytesMut};
+use moq_transport::data::{ObjectFlags, ObjectMeta};
 use moq_transport::serve::{GroupWriter, GroupsWriter, TrackWriter, TracksWriter};
 use mp4::{self, ReadBox, TrackType};
 use std::cmp::max;
 use std::collections::HashMap;
 use std::io::Cursor;
 use std::time;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 pub struct Media {
 	// Tracks based on their track ID.
@@ -16,7 +18,7 @@ pub struct Media {
 
 	// The init and catalog tracks
 	init: GroupsWriter,
-	catalog: GroupsWriter,
+	catalog: moq_catalog::Writer,
 
 	// The ftyp and moov atoms at the start of the file.
 	ftyp: Option<Bytes>,
@@ -29,6 +31,7 @@ pub struct Media {
 impl Media {
 	pub fn new(mut broadcast: TracksWriter) -> anyhow::Result<Self> {
 		let catalog = broadcast.create(".catalog").context("broadcast closed")?.groups()?;
+		let catalog = moq_catalog::Writer::new(catalog);
 		let init = broadcast.create("0.mp4").context("broadcast closed")?.groups()?;
 
 		Ok(Media {
@@ -42,6 +45,19 @@ impl Media {
 		})
 	}
 
+	/// Read a fragmented MP4 stream until it ends, such as the output of ffmpeg.
+	pub async fn read_from<R: AsyncRead + Unpin>(&mut self, mut input: R) -> anyhow::Result<()> {
+		let mut buf = BytesMut::new();
+
+		while input.read_buf(&mut buf).await.context("failed to read input")? > 0 {
+			self.parse(&mut buf)?;
+		}
+
+		anyhow::ensure!(buf.is_empty(), "input ended with a partial atom");
+
+		Ok(())
+	}
+
 	// Parse the input buffer, reading any full atoms we can find.
 	// Keep appending more data and calling parse.
 	pub fn parse<B: Buf>(&mut self, buf: &mut B) -> anyhow::Result<()> {
@@ -173,7 +189,7 @@ impl Media {
 				let codec = rfc6381_codec::Codec::avc1(profile, constraints, level);
 				let codec_str = codec.to_string();
 
-				selection_params.codec = Some(codec_str);
+				selection_params.codec = Some(codec_str.parse()?);
 				selection_params.width = Some(width.into());
 				selection_params.height = Some(height.into());
 			} else if let Some(_hev1) = &stsd.hev1 {
@@ -188,7 +204,7 @@ impl Media {
 					.dec_config;
 				let codec_str = format!("mp4a.{:02x}.{}", desc.object_type_indication, desc.dec_specific.profile);
 
-				selection_params.codec = Some(codec_str);
+				selection_params.codec = Some(codec_str.parse()?);
 				selection_params.channel_config = Some(mp4a.channelcount.to_string());
 				selection_params.samplerate = Some(mp4a.samplerate.value().into());
 
@@ -201,12 +217,16 @@ impl Media {
 				let vpcc = &vp09.vpcc;
 				let codec_str = format!("vp09.0.{:02x}.{:02x}.{:02x}", vpcc.profile, vpcc.level, vpcc.bit_depth);
 
-				selection_params.codec = Some(codec_str);
+				selection_params.codec = Some(codec_str.parse()?);
 				selection_params.width = Some(vp09.width.into());
 				selection_params.height = Some(vp09.height.into());
 
 				// TODO Test if this actually works; I'm just guessing based on mp4box.js
 				anyhow::bail!("VP9 not yet supported")
+			} else if let Some(opus) = opus_entry(&raw, id) {
+				selection_params.codec = Some(moq_catalog::Codec::Opus);
+				selection_params.channel_config = Some(opus.channels.to_string());
+				selection_params.samplerate = Some(opus.sample_rate);
 			} else {
 				// TODO add av01 support: https://github.com/gpac/mp4box.js/blob/325741b592d910297bf609bc7c400fc76101077b/src/box-codecs.js#L251
 				anyhow::bail!("unknown codec for track: {}", trak.tkhd.track_id);
@@ -223,7 +243,7 @@ impl Media {
 		}
 
 		let catalog = moq_catalog::Root {
-			version: 1,
+			version: moq_catalog::Root::VERSION,
 			streaming_format: 1,
 			streaming_format_version: "0.2".to_string(),
 			streaming_delta_updates: true,
@@ -231,12 +251,9 @@ impl Media {
 			tracks,
 		};
 
-		let catalog_str = serde_json::to_string_pretty(&catalog)?;
+		log::info!("catalog: {}", serde_json::to_string_pretty(&catalog)?);
 
-		log::info!("catalog: {}", catalog_str);
-
-		// Create a single fragment for the segment.
-		self.catalog.append(0)?.write(catalog_str.into())?;
+		self.catalog.write(catalog)?;
 
 		Ok(())
 	}
@@ -287,6 +304,59 @@ fn next_atom<B: Buf>(buf: &mut B) -> anyhow::Result<Option<Bytes>> {
 	Ok(Some(atom))
 }
 
+struct OpusEntry {
+	channels: u16,
+	sample_rate: u32,
+}
+
+// The mp4 crate doesn't support Opus, so find the sample entry in the raw moov atom.
+fn opus_entry(moov: &[u8], track_id: u32) -> Option<OpusEntry> {
+	let trak = children(moov.get(8..)?)
+		.filter(|(name, _)| name == b"trak")
+		.map(|(_, trak)| trak)
+		.find(|trak| {
+			let tkhd = children(trak).find(|(name, _)| name == b"tkhd").map(|(_, tkhd)| tkhd);
+			let id = tkhd.and_then(|tkhd| {
+				// The track ID follows the creation and modification times, which are 64-bit in version 1.
+				let offset = if tkhd.first() == Some(&1) { 20 } else { 12 };
+				Some(u32::from_be_bytes(tkhd.get(offset..offset + 4)?.try_into().ok()?))
+			});
+			id == Some(track_id)
+		})?;
+
+	let stsd = [b"mdia", b"minf", b"stbl", b"stsd"]
+		.into_iter()
+		.try_fold(trak, |parent, kind| {
+			children(parent).find(|(name, _)| name == kind).map(|(_, child)| child)
+		})?;
+
+	// Skip the version, flags, and entry count.
+	let (name, entry) = children(stsd.get(8..)?).next()?;
+	if name != b"Opus" {
+		return None;
+	}
+
+	Some(OpusEntry {
+		channels: u16::from_be_bytes(entry.get(16..18)?.try_into().ok()?),
+		sample_rate: u32::from_be_bytes(entry.get(24..28)?.try_into().ok()?) >> 16,
+	})
+}
+
+// Iterate over the boxes in the buffer, returning the type and contents of each.
+fn children(mut buf: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+	std::iter::from_fn(move || {
+		let size = u32::from_be_bytes(buf.get(..4)?.try_into().ok()?) as usize;
+		if size < 8 || size > buf.len() {
+			return None;
+		}
+
+		let (atom, rest) = buf.split_at(size);
+		buf = rest;
+
+		Some((&atom[4..8], &atom[8..]))
+	})
+}
+
 struct Track {
 	// The track we're producing
 	track: GroupsWriter,
@@ -312,14 +382,6 @@ impl Track {
 	}
 
 	pub fn header(&mut self, raw: Bytes, fragment: Fragment) -> anyhow::Result<()> {
-		if let Some(current) = self.current.as_mut() {
-			// Use the existing segment
-			current.write(raw)?;
-			return Ok(());
-		}
-
-		// Otherwise make a new segment
-
 		// Compute the timestamp in milliseconds.
 		// Overflows after 583 million years, so we're fine.
 		let timestamp: u32 = fragment
@@ -328,13 +390,31 @@ impl Track {
 			.try_into()
 			.context("timestamp too large")?;
 
+		// Let relays and players see the timing without parsing the moof.
+		// The mdat that follows has no metadata, since it shares the same timestamp.
+		let meta = ObjectMeta {
+			timestamp: timestamp.into(),
+			flags: ObjectFlags {
+				keyframe: fragment.keyframe,
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		if let Some(current) = self.current.as_mut() {
+			// Use the existing segment
+			current.write_with_meta(raw, meta)?;
+			return Ok(());
+		}
+
+		// Otherwise make a new segment
 		let priority = u32::MAX.checked_sub(timestamp).context("priority too large")?.into();
 
 		// Create a new segment.
 		let mut segment = self.track.append(priority)?;
 
 		// Write the fragment in it's own object.
-		segment.write(raw)?;
+		segment.write_with_meta(raw, meta)?;
 
 		// Save for the next iteration
 		self.current = Some(segment);
@@ -372,7 +452,7 @@ impl Fragment {
 		let track = moof.trafs[0].tfhd.track_id;
 
 		// Parse the moof to get some timing information to sleep.
-		let timestamp = sample_timestamp(&moof).expect("couldn't find timestamp");
+		let timestamp = sample_timestamp(&moof).context("missing tfdt timestamp")?;
 
 		// Detect if we should start a new segment.
 		let keyframe = sample_keyframe(&moof);
@@ -0,0 +1,183 @@
+use anyhow::Context;
+use bytes::{Buf, BufMut, BytesMut};
+
+/// An AMF0 value, used by RTMP commands and metadata.
+///
+/// Only the types sent by common encoders are supported.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Amf {
+	Number(f64),
+	Boolean(bool),
+	String(String),
+	Object(Vec<(String, Amf)>),
+	Null,
+	Undefined,
+	Array(Vec<Amf>),
+}
+
+impl Amf {
+	const NUMBER: u8 = 0x00;
+	const BOOLEAN: u8 = 0x01;
+	const STRING: u8 = 0x02;
+	const OBJECT: u8 = 0x03;
+	const NULL: u8 = 0x05;
+	const UNDEFINED: u8 = 0x06;
+	const ECMA_ARRAY: u8 = 0x08;
+	const OBJECT_END: u8 = 0x09;
+	const STRICT_ARRAY: u8 = 0x0a;
+
+	/// The deepest nesting of objects and arrays we'll decode, to avoid a stack overflow.
+	pub const MAX_DEPTH: usize = 32;
+
+	/// Decode every value in the buffer.
+	pub fn decode_all<B: Buf>(buf: &mut B) -> anyhow::Result<Vec<Self>> {
+		let mut values = Vec::new();
+		while buf.has_remaining() {
+			values.push(Self::decode(buf)?);
+		}
+		Ok(values)
+	}
+
+	pub fn decode<B: Buf>(buf: &mut B) -> anyhow::Result<Self> {
+		Self::decode_depth(buf, 0)
+	}
+
+	fn decode_depth<B: Buf>(buf: &mut B, depth: usize) -> anyhow::Result<Self> {
+		anyhow::ensure!(buf.has_remaining(), "missing AMF marker");
+		anyhow::ensure!(depth <= Self::MAX_DEPTH, "AMF value nested too deeply");
+
+		Ok(match buf.get_u8() {
+			Self::NUMBER => {
+				anyhow::ensure!(buf.remaining() >= 8, "short AMF number");
+				Self::Number(buf.get_f64())
+			}
+			Self::BOOLEAN => {
+				anyhow::ensure!(buf.has_remaining(), "short AMF boolean");
+				Self::Boolean(buf.get_u8() != 0)
+			}
+			Self::STRING => Self::String(decode_string(buf)?),
+			Self::OBJECT => Self::Object(decode_properties(buf, depth + 1)?),
+			Self::NULL => Self::Null,
+			Self::UNDEFINED => Self::Undefined,
+			Self::ECMA_ARRAY => {
+				// The count is only a hint, since the array is terminated like an object.
+				anyhow::ensure!(buf.remaining() >= 4, "short AMF array");
+				buf.advance(4);
+				Self::Object(decode_properties(buf, depth + 1)?)
+			}
+			Self::STRICT_ARRAY => {
+				anyhow::ensure!(buf.remaining() >= 4, "short AMF array");
+				let count = buf.get_u32();
+				let values = (0..count)
+					.map(|_| Self::decode_depth(buf, depth + 1))
+					.collect::<anyhow::Result<_>>()?;
+				Self::Array(values)
+			}
+			marker => anyhow::bail!("unsupported AMF marker: {}", marker),
+		})
+	}
+
+	pub fn encode(&self, buf: &mut BytesMut) {
+		match self {
+			Self::Number(value) => {
+				buf.put_u8(Self::NUMBER);
+				buf.put_f64(*value);
+			}
+			Self::Boolean(value) => {
+				buf.put_u8(Self::BOOLEAN);
+				buf.put_u8(*value as u8);
+			}
+			Self::String(value) => {
+				buf.put_u8(Self::STRING);
+				encode_string(value, buf);
+			}
+			Self::Object(properties) => {
+				buf.put_u8(Self::OBJECT);
+				for (key, value) in properties {
+					encode_string(key, buf);
+					value.encode(buf);
+				}
+				encode_string("", buf);
+				buf.put_u8(Self::OBJECT_END);
+			}
+			Self::Null => buf.put_u8(Self::NULL),
+			Self::Undefined => buf.put_u8(Self::UNDEFINED),
+			Self::Array(values) => {
+				buf.put_u8(Self::STRICT_ARRAY);
+				buf.put_u32(values.len() as u32);
+				for value in values {
+					value.encode(buf);
+				}
+			}
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Self::String(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	pub fn as_number(&self) -> Option<f64> {
+		match self {
+			Self::Number(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	/// Returns the property of an object with the given key.
+	pub fn get(&self, key: &str) -> Option<&Amf> {
+		match self {
+			Self::Object(properties) => properties.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+			_ => None,
+		}
+	}
+
+	/// Create an object from a list of properties.
+	pub fn object<'a, I: IntoIterator<Item = (&'a str, Amf)>>(properties: I) -> Self {
+		Self::Object(properties.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+	}
+}
+
+impl From<&str> for Amf {
+	fn from(value: &str) -> Self {
+		Self::String(value.to_string())
+	}
+}
+
+impl From<f64> for Amf {
+	fn from(value: f64) -> Self {
+		Self::Number(value)
+	}
+}
+
+fn decode_string<B: Buf>(buf: &mut B) -> anyhow::Result<String> {
+	anyhow::ensure!(buf.remaining() >= 2, "short AMF string");
+	let size = buf.get_u16() as usize;
+	anyhow::ensure!(buf.remaining() >= size, "short AMF string");
+
+	let mut value = vec![0; size];
+	buf.copy_to_slice(&mut value);
+	String::from_utf8(value).context("invalid AMF string")
+}
+
+fn encode_string(value: &str, buf: &mut BytesMut) {
+	buf.put_u16(value.len() as u16);
+	buf.put_slice(value.as_bytes());
+}
+
+// Decode properties until the empty key and object end marker.
+fn decode_properties<B: Buf>(buf: &mut B, depth: usize) -> anyhow::Result<Vec<(String, Amf)>> {
+	let mut properties = Vec::new();
+
+	loop {
+		let key = decode_string(buf)?;
+		if key.is_empty() && buf.chunk().first() == Some(&Amf::OBJECT_END) {
+			buf.advance(1);
+			return Ok(properties);
+		}
+
+		properties.push((key, Amf::decode_depth(buf, depth)?));
+	}
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// A complete RTMP message, reassembled from chunks.
+#[derive(Debug)]
+pub struct Message {
+	pub kind: u8,
+	pub stream_id: u32,
+	pub timestamp: u32,
+	pub payload: Bytes,
+}
+
+impl Message {
+	pub const SET_CHUNK_SIZE: u8 = 1;
+	pub const ABORT: u8 = 2;
+	pub const USER_CONTROL: u8 = 4;
+	pub const WINDOW_ACK_SIZE: u8 = 5;
+	pub const SET_PEER_BANDWIDTH: u8 = 6;
+	pub const AUDIO: u8 = 8;
+	pub const VIDEO: u8 = 9;
+	pub const DATA: u8 = 18;
+	pub const COMMAND: u8 = 20;
+}
+
+// The chunk size until changed by the peer.
+const DEFAULT_CHUNK_SIZE: usize = 128;
+
+// The largest chunk size we'll accept, to avoid abuse.
+const MAX_CHUNK_SIZE: usize = 1 << 24;
+
+// The timestamp value that signals an extended timestamp.
+const EXTENDED_TIMESTAMP: u32 = 0xffffff;
+
+// The last header of a chunk stream, used to decompress the next header.
+#[derive(Default)]
+struct ChunkStream {
+	timestamp: u32,
+	delta: u32,
+	length: usize,
+	kind: u8,
+	stream_id: u32,
+	extended: bool,
+
+	// The message being reassembled.
+	payload: BytesMut,
+}
+
+/// Reads chunks and reassembles them into messages.
+pub struct ChunkReader {
+	chunk_size: usize,
+	streams: HashMap<u32, ChunkStream>,
+}
+
+impl ChunkReader {
+	pub fn new() -> Self {
+		Self {
+			chunk_size: DEFAULT_CHUNK_SIZE,
+			streams: HashMap::new(),
+		}
+	}
+
+	pub fn set_chunk_size(&mut self, size: u32) -> anyhow::Result<()> {
+		let size = size as usize;
+		anyhow::ensure!((1..=MAX_CHUNK_SIZE).contains(&size), "invalid chunk size: {}", size);
+		self.chunk_size = size;
+		Ok(())
+	}
+
+	/// Abort the partial message on the given chunk stream.
+	pub fn abort(&mut self, csid: u32) {
+		if let Some(stream) = self.streams.get_mut(&csid) {
+			stream.payload.clear();
+		}
+	}
+
+	/// Returns the next complete message, or None if the connection was closed between chunks.
+	pub async fn read<R: AsyncRead + Unpin>(&mut self, r: &mut R) -> anyhow::Result<Option<Message>> {
+		loop {
+			let first = match r.read_u8().await {
+				Ok(first) => first,
+				Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+				Err(err) => return Err(err.into()),
+			};
+
+			let format = first >> 6;
+			let csid = match first & 0x3f {
+				0 => 64 + r.read_u8().await? as u32,
+				1 => 64 + r.read_u16_le().await? as u32,
+				csid => csid as u32,
+			};
+
+			let stream = self.streams.entry(csid).or_default();
+			let start = stream.payload.is_empty();
+
+			// A compressed header reuses fields from the previous chunk.
+			anyhow::ensure!(
+				format == 0 || stream.length > 0 || stream.kind > 0,
+				"compressed header without a previous chunk"
+			);
+
+			if format <= 2 {
+				let timestamp = read_u24(r).await?;
+				stream.extended = timestamp == EXTENDED_TIMESTAMP;
+
+				if format <= 1 {
+					// Otherwise the new length could be shorter than what we've already received.
+					anyhow::ensure!(start, "new message header before the previous message finished");
+
+					stream.length = read_u24(r).await? as usize;
+					stream.kind = r.read_u8().await?;
+				}
+
+				if format == 0 {
+					stream.stream_id = r.read_u32_le().await?;
+				}
+
+				let timestamp = match stream.extended {
+					true => r.read_u32().await?,
+					false => timestamp,
+				};
+
+				if format == 0 {
+					stream.timestamp = timestamp;
+					stream.delta = 0;
+				} else {
+					stream.delta = timestamp;
+					stream.timestamp = stream.timestamp.wrapping_add(timestamp);
+				}
+			} else {
+				if stream.extended {
+					// The extended timestamp is repeated, which we ignore for continuation chunks.
+					let timestamp = r.read_u32().await?;
+					if start {
+						stream.delta = timestamp;
+					}
+				}
+
+				// A new message with a type 3 header uses the previous delta.
+				if start {
+					stream.timestamp = stream.timestamp.wrapping_add(stream.delta);
+				}
+			}
+
+			anyhow::ensure!(stream.length <= MAX_CHUNK_SIZE, "message too large");
+
+			let remaining = stream
+				.length
+				.checked_sub(stream.payload.len())
+				.context("chunk exceeds the message length")?;
+			let size = self.chunk_size.min(remaining);
+			let offset = stream.payload.len();
+			stream.payload.resize(offset + size, 0);
+			r.read_exact(&mut stream.payload[offset..]).await?;
+
+			if stream.payload.len() == stream.length {
+				return Ok(Some(Message {
+					kind: stream.kind,
+					stream_id: stream.stream_id,
+					timestamp: stream.timestamp,
+					payload: stream.payload.split().freeze(),
+				}));
+			}
+		}
+	}
+}
+
+/// Splits messages into chunks, always using a full header.
+pub struct ChunkWriter {
+	chunk_size: usize,
+}
+
+impl ChunkWriter {
+	pub fn new() -> Self {
+		Self {
+			chunk_size: DEFAULT_CHUNK_SIZE,
+		}
+	}
+
+	/// Write a Set Chunk Size message and use the new size for the following messages.
+	pub async fn set_chunk_size<W: AsyncWrite + Unpin>(&mut self, w: &mut W, size: u32) -> anyhow::Result<()> {
+		self.write(w, 2, Message::SET_CHUNK_SIZE, 0, &size.to_be_bytes())
+			.await?;
+		self.chunk_size = size as usize;
+		Ok(())
+	}
+
+	pub async fn write<W: AsyncWrite + Unpin>(
+		&self,
+		w: &mut W,
+		csid: u8,
+		kind: u8,
+		stream_id: u32,
+		payload: &[u8],
+	) -> anyhow::Result<()> {
+		let mut buf = BytesMut::new();
+
+		// NOTE: We only send control messages, so the timestamp is always zero.
+		buf.put_u8(csid & 0x3f);
+		put_u24(&mut buf, 0);
+		put_u24(&mut buf, payload.len() as u32);
+		buf.put_u8(kind);
+		buf.put_u32_le(stream_id);
+
+		for (index, chunk) in payload.chunks(self.chunk_size).enumerate() {
+			if index > 0 {
+				buf.put_u8(0xc0 | (csid & 0x3f));
+			}
+			buf.put_slice(chunk);
+		}
+
+		w.write_all(&buf).await?;
+		Ok(())
+	}
+}
+
+async fn read_u24<R: AsyncRead + Unpin>(r: &mut R) -> anyhow::Result<u32> {
+	let mut buf = [0; 4];
+	r.read_exact(&mut buf[1..]).await?;
+	Ok(u32::from_be_bytes(buf))
+}
+
+fn put_u24(buf: &mut BytesMut, value: u32) {
+	buf.put_slice(&value.to_be_bytes()[1..]);
+}
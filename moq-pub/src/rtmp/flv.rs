@@ -0,0 +1,76 @@
+use bytes::{Buf, Bytes};
+
+use crate::cmaf::AacConfig;
+
+/// The payload of an RTMP video message, which is an FLV video tag without the header.
+pub enum VideoTag {
+	/// An AVCDecoderConfigurationRecord, which becomes the avcC box.
+	Config(Bytes),
+
+	/// Length-prefixed NAL units for a single frame.
+	Frame { keyframe: bool, cts: i32, data: Bytes },
+}
+
+impl VideoTag {
+	const KEYFRAME: u8 = 1;
+	const AVC: u8 = 7;
+
+	/// Parse the tag, returning None for tags that don't carry media, such as the end of sequence.
+	pub fn parse(mut payload: Bytes) -> anyhow::Result<Option<Self>> {
+		anyhow::ensure!(payload.remaining() >= 5, "short video tag");
+
+		let header = payload.get_u8();
+		anyhow::ensure!(header & 0x80 == 0, "enhanced RTMP is not supported");
+
+		let codec = header & 0x0f;
+		anyhow::ensure!(codec == Self::AVC, "unsupported video codec: {}", codec);
+
+		let keyframe = header >> 4 == Self::KEYFRAME;
+		let kind = payload.get_u8();
+
+		// The composition time offset is a signed 24-bit integer.
+		let cts = ((payload.get_uint(3) as i32) << 8) >> 8;
+
+		Ok(match kind {
+			0 => {
+				anyhow::ensure!(payload.len() >= 7, "short AVC decoder configuration");
+
+				// NOTE: The NAL units are copied as-is, so they must use the same length prefix as MP4.
+				let length_size = (payload[4] & 0x3) + 1;
+				anyhow::ensure!(length_size == 4, "unsupported NAL length size: {}", length_size);
+
+				Some(Self::Config(payload))
+			}
+			1 => Some(Self::Frame {
+				keyframe,
+				cts,
+				data: payload,
+			}),
+			_ => None,
+		})
+	}
+}
+
+/// The payload of an RTMP audio message, which is an FLV audio tag without the header.
+pub enum AudioTag {
+	Config(AacConfig),
+
+	/// A single raw AAC frame.
+	Frame(Bytes),
+}
+
+impl AudioTag {
+	const AAC: u8 = 10;
+
+	pub fn parse(mut payload: Bytes) -> anyhow::Result<Self> {
+		anyhow::ensure!(payload.remaining() >= 2, "short audio tag");
+
+		let format = payload.get_u8() >> 4;
+		anyhow::ensure!(format == Self::AAC, "unsupported audio codec: {}", format);
+
+		Ok(match payload.get_u8() {
+			0 => Self::Config(AacConfig::parse(payload)?),
+			_ => Self::Frame(payload),
+		})
+	}
+}
@@ -0,0 +1,233 @@
+//! Accepts an RTMP publisher, such as OBS or ffmpeg, and remuxes H.264 and AAC into CMAF for [Media].
+mod amf;
+mod chunk;
+mod flv;
+
+use amf::Amf;
+use bytes::{BufMut, BytesMut};
+use chunk::{ChunkReader, ChunkWriter, Message};
+use flv::{AudioTag, VideoTag};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::cmaf::{AudioConfig, Muxer};
+use crate::Media;
+
+// The size of the random data in the handshake.
+const HANDSHAKE_SIZE: usize = 1536;
+
+// The chunk stream used for commands.
+const COMMAND_CSID: u8 = 3;
+
+// The message stream created for the publisher.
+const STREAM_ID: u32 = 1;
+
+// Our acknowledgement window and chunk size.
+const WINDOW_SIZE: u32 = 2_500_000;
+const CHUNK_SIZE: u32 = 4096;
+
+// Each AAC frame contains 1024 samples.
+const AAC_FRAME: u32 = 1024;
+
+/// Accept a single RTMP publisher and import its media until it stops publishing or disconnects.
+///
+/// Only H.264 video and AAC audio are supported; the stream key is ignored.
+pub async fn ingest<S: AsyncRead + AsyncWrite + Unpin>(stream: S, media: &mut Media) -> anyhow::Result<()> {
+	let mut session = Session {
+		stream,
+		reader: ChunkReader::new(),
+		writer: ChunkWriter::new(),
+		muxer: Muxer::new(),
+	};
+
+	session.handshake().await?;
+	session.run(media).await
+}
+
+struct Session<S> {
+	stream: S,
+	reader: ChunkReader,
+	writer: ChunkWriter,
+	muxer: Muxer,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Session<S> {
+	// The simple handshake, which echoes the client's random data.
+	async fn handshake(&mut self) -> anyhow::Result<()> {
+		let version = self.stream.read_u8().await?;
+		anyhow::ensure!(version == 3, "unsupported RTMP version: {}", version);
+
+		let mut c1 = vec![0; HANDSHAKE_SIZE];
+		self.stream.read_exact(&mut c1).await?;
+
+		let mut reply = BytesMut::with_capacity(1 + 2 * HANDSHAKE_SIZE);
+		reply.put_u8(3);
+		reply.put_bytes(0, HANDSHAKE_SIZE);
+		reply.put_slice(&c1);
+		self.stream.write_all(&reply).await?;
+
+		let mut c2 = vec![0; HANDSHAKE_SIZE];
+		self.stream.read_exact(&mut c2).await?;
+
+		Ok(())
+	}
+
+	async fn run(&mut self, media: &mut Media) -> anyhow::Result<()> {
+		while let Some(message) = self.reader.read(&mut self.stream).await? {
+			let done = self.handle(message).await?;
+
+			// Import any complete atoms.
+			media.parse(self.muxer.output())?;
+
+			if done {
+				break;
+			}
+		}
+
+		self.muxer.finish();
+		media.parse(self.muxer.output())?;
+
+		Ok(())
+	}
+
+	// Returns true when the publisher is done.
+	async fn handle(&mut self, message: Message) -> anyhow::Result<bool> {
+		match message.kind {
+			Message::SET_CHUNK_SIZE => {
+				anyhow::ensure!(message.payload.len() >= 4, "short chunk size");
+				let size = u32::from_be_bytes(message.payload[..4].try_into().unwrap());
+				self.reader.set_chunk_size(size & 0x7fff_ffff)?;
+			}
+			Message::ABORT => {
+				anyhow::ensure!(message.payload.len() >= 4, "short abort");
+				self.reader
+					.abort(u32::from_be_bytes(message.payload[..4].try_into().unwrap()));
+			}
+			Message::COMMAND => {
+				let values = Amf::decode_all(&mut message.payload.clone())?;
+				return self.command(message.stream_id, values).await;
+			}
+			Message::DATA => {
+				let values = Amf::decode_all(&mut message.payload.clone())?;
+				self.data(values);
+			}
+			Message::VIDEO => match VideoTag::parse(message.payload)? {
+				Some(VideoTag::Config(avcc)) => self.muxer.video_config(avcc),
+				Some(VideoTag::Frame { keyframe, cts, data }) => {
+					self.muxer.video(message.timestamp.into(), keyframe, cts, data)
+				}
+				None => {}
+			},
+			Message::AUDIO => match AudioTag::parse(message.payload)? {
+				AudioTag::Config(aac) => self.muxer.audio_config(AudioConfig::Aac(aac)),
+				AudioTag::Frame(data) => {
+					if let Some(rate) = self.muxer.audio_rate() {
+						let time = message.timestamp as u64 * rate as u64 / 1000;
+						self.muxer.audio(time, AAC_FRAME, data);
+					}
+				}
+			},
+			kind => log::trace!("ignoring RTMP message: kind={}", kind),
+		}
+
+		Ok(false)
+	}
+
+	async fn command(&mut self, stream_id: u32, values: Vec<Amf>) -> anyhow::Result<bool> {
+		let mut values = values.into_iter();
+		let name = values.next().and_then(|name| name.as_str().map(str::to_string));
+		let transaction = values.next().and_then(|id| id.as_number()).unwrap_or(0.0);
+		let args: Vec<_> = values.collect();
+
+		log::debug!("RTMP command: name={:?} args={:?}", name, args);
+
+		match name.as_deref() {
+			Some("connect") => {
+				self.control(Message::WINDOW_ACK_SIZE, &WINDOW_SIZE.to_be_bytes())
+					.await?;
+
+				// The window size and a dynamic limit type.
+				let mut bandwidth = WINDOW_SIZE.to_be_bytes().to_vec();
+				bandwidth.push(2);
+				self.control(Message::SET_PEER_BANDWIDTH, &bandwidth).await?;
+
+				self.writer.set_chunk_size(&mut self.stream, CHUNK_SIZE).await?;
+
+				let properties = Amf::object([("fmsVer", "FMS/3,0,1,123".into()), ("capabilities", 31.0.into())]);
+				let info = Amf::object([
+					("level", "status".into()),
+					("code", "NetConnection.Connect.Success".into()),
+					("description", "Connection succeeded.".into()),
+					("objectEncoding", 0.0.into()),
+				]);
+				self.reply(0, "_result", transaction, [properties, info]).await?;
+			}
+			Some("createStream") => {
+				self.reply(0, "_result", transaction, [Amf::Null, (STREAM_ID as f64).into()])
+					.await?;
+			}
+			Some("publish") => {
+				let name = args.get(1).and_then(Amf::as_str).unwrap_or_default();
+				log::info!("RTMP publish: name={}", name);
+
+				// Stream Begin
+				let mut begin = vec![0, 0];
+				begin.extend_from_slice(&stream_id.to_be_bytes());
+				self.control(Message::USER_CONTROL, &begin).await?;
+
+				let info = Amf::object([
+					("level", "status".into()),
+					("code", "NetStream.Publish.Start".into()),
+					("description", "Start publishing.".into()),
+				]);
+				self.reply(stream_id, "onStatus", 0.0, [Amf::Null, info]).await?;
+			}
+			Some("deleteStream") | Some("FCUnpublish") => return Ok(true),
+			// Reply to anything else with an empty result, such as releaseStream and FCPublish.
+			_ if transaction > 0.0 => {
+				self.reply(0, "_result", transaction, [Amf::Null, Amf::Undefined])
+					.await?;
+			}
+			_ => {}
+		}
+
+		Ok(false)
+	}
+
+	// Handle @setDataFrame or onMetaData, which contains the resolution.
+	fn data(&mut self, values: Vec<Amf>) {
+		let metadata = values.iter().find(|value| matches!(value, Amf::Object(_)));
+		let dimension = |key| {
+			metadata
+				.and_then(|metadata| metadata.get(key))
+				.and_then(Amf::as_number)
+				.map(|value| value as u16)
+		};
+
+		if let (Some(width), Some(height)) = (dimension("width"), dimension("height")) {
+			self.muxer.metadata(width, height);
+		}
+	}
+
+	async fn control(&mut self, kind: u8, payload: &[u8]) -> anyhow::Result<()> {
+		self.writer.write(&mut self.stream, 2, kind, 0, payload).await
+	}
+
+	async fn reply<I: IntoIterator<Item = Amf>>(
+		&mut self,
+		stream_id: u32,
+		name: &str,
+		transaction: f64,
+		args: I,
+	) -> anyhow::Result<()> {
+		let mut payload = BytesMut::new();
+		Amf::from(name).encode(&mut payload);
+		Amf::Number(transaction).encode(&mut payload);
+		for arg in args {
+			arg.encode(&mut payload);
+		}
+
+		self.writer
+			.write(&mut self.stream, COMMAND_CSID, Message::COMMAND, stream_id, &payload)
+			.await
+	}
+}
@@ -0,0 +1,195 @@
+//! Accepts an SRT caller sending MPEG-TS, such as OBS or ffmpeg, and remuxes H.264 and AAC into CMAF for [Media].
+mod ts;
+
+use std::net;
+
+use bytes::{BufMut, BytesMut};
+use futures::TryStreamExt;
+use srt_tokio::SrtSocket;
+use ts::{Demuxer, Kind, Pes};
+
+use crate::cmaf::{AacConfig, AudioConfig, Muxer};
+use crate::{h264, Media};
+
+// MPEG-TS timestamps use a 90kHz clock and wrap after 33 bits.
+const TS_CLOCK: u64 = 90_000;
+const TS_MASK: u64 = (1 << 33) - 1;
+
+// Each AAC frame contains 1024 samples.
+const AAC_FRAME: u32 = 1024;
+
+/// Accept a single SRT caller on the given address and import its media until it disconnects.
+///
+/// Only H.264 video and AAC audio in the first program are supported; the stream ID is ignored.
+pub async fn ingest(bind: net::SocketAddr, media: &mut Media) -> anyhow::Result<()> {
+	log::info!("waiting for SRT publisher: bind={}", bind);
+
+	let mut socket = SrtSocket::builder().listen_on(bind).await?;
+	log::info!("accepted SRT publisher");
+
+	let mut remux = Remux::new();
+
+	while let Some((_, data)) = socket.try_next().await? {
+		remux.push(&data)?;
+		media.parse(remux.output())?;
+	}
+
+	remux.finish()?;
+	media.parse(remux.output())?;
+
+	Ok(())
+}
+
+/// Remuxes an MPEG transport stream into a fragmented MP4.
+struct Remux {
+	demuxer: Demuxer,
+	muxer: Muxer,
+
+	// The first timestamp, so the broadcast starts at zero.
+	base: Option<u64>,
+}
+
+impl Remux {
+	fn new() -> Self {
+		Self {
+			demuxer: Demuxer::new(),
+			muxer: Muxer::new(),
+			base: None,
+		}
+	}
+
+	fn output(&mut self) -> &mut BytesMut {
+		self.muxer.output()
+	}
+
+	fn push(&mut self, data: &[u8]) -> anyhow::Result<()> {
+		for pes in self.demuxer.push(data)? {
+			self.pes(pes)?;
+		}
+
+		Ok(())
+	}
+
+	fn finish(&mut self) -> anyhow::Result<()> {
+		for pes in self.demuxer.flush()? {
+			self.pes(pes)?;
+		}
+
+		self.muxer.finish();
+
+		Ok(())
+	}
+
+	fn pes(&mut self, pes: Pes) -> anyhow::Result<()> {
+		let base = *self.base.get_or_insert(pes.dts);
+		let dts = relative(pes.dts, base);
+
+		match pes.kind {
+			Kind::Video => {
+				let data = h264::from_annexb(&pes.data);
+
+				if !self.muxer.started() {
+					// Wait for the parameter sets, which are sent in-band before each keyframe.
+					match h264::Config::parse(&data)? {
+						Some(config) => {
+							self.muxer.metadata(config.width, config.height);
+							self.muxer.video_config(config.avcc);
+						}
+						None => return Ok(()),
+					}
+				}
+
+				let keyframe = h264::is_keyframe(&data);
+				let cts = relative(pes.pts, pes.dts) * 1000 / TS_CLOCK;
+
+				self.muxer.video(dts * 1000 / TS_CLOCK, keyframe, cts as i32, data);
+			}
+			Kind::Audio => {
+				let video = self.demuxer.kinds().any(|kind| kind == Kind::Video);
+				let mut data = pes.data;
+				let mut time = None;
+
+				while let Some(frame) = Adts::parse(&data)? {
+					let payload = data.split_to(frame.size).split_off(frame.header);
+
+					if !self.muxer.started() {
+						if self.muxer.audio_rate().is_none() {
+							self.muxer.audio_config(AudioConfig::Aac(frame.config()?));
+						}
+
+						// Wait for a video keyframe so the broadcast starts with a complete group.
+						if video {
+							continue;
+						}
+					}
+
+					let Some(rate) = self.muxer.audio_rate() else {
+						continue;
+					};
+
+					// Any frames after the first in the PES follow it immediately.
+					let time = time.get_or_insert(dts * rate as u64 / TS_CLOCK);
+					self.muxer.audio(*time, AAC_FRAME, payload);
+					*time += AAC_FRAME as u64;
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+// Returns the time since the base, treating timestamps before the base as zero.
+fn relative(timestamp: u64, base: u64) -> u64 {
+	let delta = timestamp.wrapping_sub(base) & TS_MASK;
+	if delta > TS_MASK / 2 {
+		0
+	} else {
+		delta
+	}
+}
+
+// The header of an ADTS frame, which wraps each raw AAC frame.
+struct Adts {
+	profile: u8,
+	frequency: u8,
+	channels: u8,
+
+	// The size of the header and the entire frame.
+	header: usize,
+	size: usize,
+}
+
+impl Adts {
+	// Parse the next frame header, returning None at the end of the buffer.
+	fn parse(buf: &[u8]) -> anyhow::Result<Option<Self>> {
+		if buf.is_empty() {
+			return Ok(None);
+		}
+
+		anyhow::ensure!(buf.len() >= 7, "short ADTS header");
+		anyhow::ensure!(buf[0] == 0xff && buf[1] & 0xf0 == 0xf0, "invalid ADTS sync word");
+
+		let protection_absent = buf[1] & 0x1 == 1;
+		let header = if protection_absent { 7 } else { 9 };
+		let size = ((buf[3] as usize & 0x3) << 11) | (buf[4] as usize) << 3 | (buf[5] as usize) >> 5;
+		anyhow::ensure!(header <= size && size <= buf.len(), "invalid ADTS frame size");
+
+		Ok(Some(Self {
+			profile: buf[2] >> 6,
+			frequency: (buf[2] >> 2) & 0xf,
+			channels: (buf[2] & 0x1) << 2 | buf[3] >> 6,
+			header,
+			size,
+		}))
+	}
+
+	// Build the AudioSpecificConfig from the header.
+	fn config(&self) -> anyhow::Result<AacConfig> {
+		let mut raw = BytesMut::with_capacity(2);
+		let object = self.profile as u16 + 1;
+		raw.put_u16(object << 11 | (self.frequency as u16) << 7 | (self.channels as u16) << 3);
+
+		AacConfig::parse(raw.freeze())
+	}
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use bytes::{Buf, Bytes, BytesMut};
+
+const PACKET_SIZE: usize = 188;
+const SYNC_BYTE: u8 = 0x47;
+const PAT_PID: u16 = 0;
+
+// The stream types we can remux.
+const STREAM_AAC: u8 = 0x0f;
+const STREAM_H264: u8 = 0x1b;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+	/// H.264 in Annex B format.
+	Video,
+
+	/// One or more ADTS frames.
+	Audio,
+}
+
+/// A complete PES packet, with the timestamps in 90kHz units.
+pub struct Pes {
+	pub kind: Kind,
+	pub pts: u64,
+	pub dts: u64,
+	pub data: Bytes,
+}
+
+struct Stream {
+	kind: Kind,
+
+	// The PES packet being reassembled, including the header.
+	pes: BytesMut,
+}
+
+/// Splits an MPEG transport stream into PES packets for the first program.
+///
+/// Sections that span multiple packets are not supported, which is fine for the small PAT and PMT.
+#[derive(Default)]
+pub struct Demuxer {
+	pmt: Option<u16>,
+	streams: HashMap<u16, Stream>,
+
+	// A partial packet from the previous call.
+	partial: BytesMut,
+}
+
+impl Demuxer {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Returns the kinds of media in the program, once the PMT has been parsed.
+	pub fn kinds(&self) -> impl Iterator<Item = Kind> + '_ {
+		self.streams.values().map(|stream| stream.kind)
+	}
+
+	/// Demux the data, which need not be aligned to packets, and return any complete PES packets.
+	pub fn push(&mut self, mut data: &[u8]) -> anyhow::Result<Vec<Pes>> {
+		let mut out = Vec::new();
+
+		if !self.partial.is_empty() {
+			let needed = (PACKET_SIZE - self.partial.len()).min(data.len());
+			self.partial.extend_from_slice(&data[..needed]);
+			data = &data[needed..];
+
+			if self.partial.len() < PACKET_SIZE {
+				return Ok(out);
+			}
+
+			let packet = self.partial.split().freeze();
+			self.packet(&packet, &mut out)?;
+		}
+
+		while !data.is_empty() {
+			// Resynchronize if we're not at the start of a packet.
+			if data[0] != SYNC_BYTE {
+				let skip = data.iter().position(|&byte| byte == SYNC_BYTE).unwrap_or(data.len());
+				log::warn!("skipping unsynchronized data: size={}", skip);
+				data = &data[skip..];
+				continue;
+			}
+
+			if data.len() < PACKET_SIZE {
+				self.partial.extend_from_slice(data);
+				break;
+			}
+
+			self.packet(&data[..PACKET_SIZE], &mut out)?;
+			data = &data[PACKET_SIZE..];
+		}
+
+		Ok(out)
+	}
+
+	/// Return the PES packets that are still buffered, since they end at the next packet.
+	pub fn flush(&mut self) -> anyhow::Result<Vec<Pes>> {
+		let mut out = Vec::new();
+		for stream in self.streams.values_mut() {
+			if let Some(pes) = parse_pes(stream.kind, stream.pes.split().freeze())? {
+				out.push(pes);
+			}
+		}
+		Ok(out)
+	}
+
+	fn packet(&mut self, packet: &[u8], out: &mut Vec<Pes>) -> anyhow::Result<()> {
+		let start = packet[1] & 0x40 != 0;
+		let pid = u16::from_be_bytes([packet[1], packet[2]]) & 0x1fff;
+		let control = (packet[3] >> 4) & 0x3;
+
+		// Skip the adaptation field, if any.
+		let mut payload = &packet[4..];
+		if control & 0x2 != 0 {
+			let size = payload[0] as usize + 1;
+			anyhow::ensure!(size <= payload.len(), "invalid adaptation field");
+			payload = &payload[size..];
+		}
+
+		if control & 0x1 == 0 || payload.is_empty() {
+			return Ok(());
+		}
+
+		if pid == PAT_PID {
+			if start {
+				self.pat(payload)?;
+			}
+		} else if Some(pid) == self.pmt {
+			if start {
+				self.program(payload)?;
+			}
+		} else if let Some(stream) = self.streams.get_mut(&pid) {
+			if start && !stream.pes.is_empty() {
+				if let Some(pes) = parse_pes(stream.kind, stream.pes.split().freeze())? {
+					out.push(pes);
+				}
+			}
+
+			// Drop the payload until we find the start of a PES packet.
+			if start || !stream.pes.is_empty() {
+				stream.pes.extend_from_slice(payload);
+			}
+		}
+
+		Ok(())
+	}
+
+	fn pat(&mut self, payload: &[u8]) -> anyhow::Result<()> {
+		let mut entries = section(payload, 0x00)?;
+
+		while entries.remaining() >= 4 {
+			let program = entries.get_u16();
+			let pid = entries.get_u16() & 0x1fff;
+
+			// Program 0 is the network information table.
+			if program != 0 {
+				self.pmt = Some(pid);
+				break;
+			}
+		}
+
+		Ok(())
+	}
+
+	fn program(&mut self, payload: &[u8]) -> anyhow::Result<()> {
+		let mut pmt = section(payload, 0x02)?;
+		anyhow::ensure!(pmt.remaining() >= 4, "short PMT");
+
+		pmt.advance(2); // PCR PID
+		let info = (pmt.get_u16() & 0x0fff) as usize;
+		anyhow::ensure!(pmt.remaining() >= info, "short PMT");
+		pmt.advance(info);
+
+		while pmt.remaining() >= 5 {
+			let stream_type = pmt.get_u8();
+			let pid = pmt.get_u16() & 0x1fff;
+			let info = (pmt.get_u16() & 0x0fff) as usize;
+			pmt.advance(info.min(pmt.remaining()));
+
+			let kind = match stream_type {
+				STREAM_H264 => Kind::Video,
+				STREAM_AAC => Kind::Audio,
+				_ => {
+					log::warn!("ignoring unsupported stream: type={:#x} pid={}", stream_type, pid);
+					continue;
+				}
+			};
+
+			// Keep any partial PES if the PMT is repeated.
+			self.streams.entry(pid).or_insert_with(|| Stream {
+				kind,
+				pes: BytesMut::new(),
+			});
+		}
+
+		Ok(())
+	}
+}
+
+// Returns the contents of a PSI section after the common header, without the CRC.
+fn section(payload: &[u8], table: u8) -> anyhow::Result<&[u8]> {
+	let pointer = *payload.first().unwrap_or(&0) as usize;
+	let section = payload.get(1 + pointer..).unwrap_or_default();
+	anyhow::ensure!(section.len() >= 8, "short section");
+	anyhow::ensure!(section[0] == table, "unexpected table: {}", section[0]);
+
+	let length = (u16::from_be_bytes([section[1], section[2]]) & 0x0fff) as usize;
+	anyhow::ensure!(
+		length >= 9 && 3 + length <= section.len(),
+		"section spans multiple packets"
+	);
+
+	// Skip the ID, version, and section numbers.
+	Ok(&section[8..3 + length - 4])
+}
+
+// Parse a PES packet, returning None if it has no timestamp.
+fn parse_pes(kind: Kind, mut pes: Bytes) -> anyhow::Result<Option<Pes>> {
+	if pes.is_empty() {
+		return Ok(None);
+	}
+
+	anyhow::ensure!(pes.len() >= 9 && pes[..3] == [0, 0, 1], "invalid PES header");
+
+	let flags = pes[7];
+	let header = pes[8] as usize;
+	anyhow::ensure!(pes.len() >= 9 + header, "short PES header");
+
+	let timestamps = &pes[9..9 + header];
+	let pts = match flags >> 6 {
+		0b10 | 0b11 if timestamps.len() >= 5 => timestamp(&timestamps[..5]),
+		_ => return Ok(None),
+	};
+	let dts = match flags >> 6 {
+		0b11 if timestamps.len() >= 10 => timestamp(&timestamps[5..10]),
+		_ => pts,
+	};
+
+	pes.advance(9 + header);
+
+	Ok(Some(Pes {
+		kind,
+		pts,
+		dts,
+		data: pes,
+	}))
+}
+
+// A 33-bit timestamp, split up by marker bits.
+fn timestamp(buf: &[u8]) -> u64 {
+	(((buf[0] as u64) >> 1) & 0x7) << 30
+		| (buf[1] as u64) << 22
+		| ((buf[2] as u64) >> 1) << 15
+		| (buf[3] as u64) << 7
+		| (buf[4] as u64) >> 1
+}
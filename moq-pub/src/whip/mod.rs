@@ -0,0 +1,390 @@
+//! Accepts a WebRTC publisher using WHIP, such as OBS or a browser, and remuxes H.264 and Opus into CMAF for [Media].
+use std::{net, sync::Arc, time::Instant};
+
+use axum::{
+	extract::State,
+	http::{header, Method, StatusCode},
+	response::{IntoResponse, Response},
+	routing::{delete, post},
+	Router,
+};
+use bytes::Bytes;
+use tokio::sync::{mpsc, Mutex};
+use tower_http::cors::{Any, CorsLayer};
+use webrtc::{
+	api::{
+		interceptor_registry::register_default_interceptors,
+		media_engine::{MediaEngine, MIME_TYPE_H264, MIME_TYPE_OPUS},
+		APIBuilder,
+	},
+	interceptor::registry::Registry,
+	media::io::sample_builder::SampleBuilder,
+	peer_connection::{
+		configuration::RTCConfiguration, peer_connection_state::RTCPeerConnectionState,
+		sdp::session_description::RTCSessionDescription, RTCPeerConnection,
+	},
+	rtp::{
+		codecs::{h264::H264Packet, opus::OpusPacket},
+		packetizer::Depacketizer,
+	},
+	rtp_transceiver::rtp_codec::{RTCRtpCodecCapability, RTCRtpCodecParameters, RTPCodecType},
+	track::track_remote::TrackRemote,
+};
+
+use crate::cmaf::{AudioConfig, Muxer};
+use crate::{h264, Media};
+
+// The RTP clock rates, fixed by the payload formats.
+const VIDEO_CLOCK: u32 = 90_000;
+const OPUS_CLOCK: u32 = 48_000;
+
+// The number of packets to buffer while waiting for a complete frame.
+const MAX_LATE: u16 = 512;
+
+// The resource created for the publisher, which is deleted to stop publishing.
+const RESOURCE: &str = "/session";
+
+/// Accept a single WHIP publisher on the given address and import its media until it stops publishing or disconnects.
+///
+/// The offer is sent to `POST /` and the session is stopped with `DELETE /session`.
+/// Only H.264 video and Opus audio are negotiated, and only host ICE candidates are gathered.
+pub async fn ingest(bind: net::SocketAddr, media: &mut Media) -> anyhow::Result<()> {
+	let (events, mut rx) = mpsc::unbounded_channel();
+
+	let whip = Whip {
+		session: Default::default(),
+		events,
+	};
+
+	let app = Router::new()
+		.route("/", post(publish))
+		.route(RESOURCE, delete(unpublish))
+		.layer(
+			CorsLayer::new()
+				.allow_origin(Any)
+				.allow_methods([Method::POST, Method::DELETE])
+				.allow_headers([header::CONTENT_TYPE])
+				.expose_headers([header::LOCATION]),
+		)
+		.with_state(whip.clone());
+
+	let listener = tokio::net::TcpListener::bind(bind).await?;
+	log::info!("waiting for WHIP publisher: bind={}", bind);
+
+	let server = axum::serve(listener, app.into_make_service());
+
+	let res = tokio::select! {
+		res = server => res.map_err(Into::into),
+		res = import(&mut rx, media) => res,
+	};
+
+	// Hang up if we stopped early.
+	if let Some(session) = whip.session.lock().await.take() {
+		session.close().await?;
+	}
+
+	res
+}
+
+enum Event {
+	// The kinds of media in the offer.
+	Start { video: bool, audio: bool },
+
+	// Length-prefixed NAL units, with the timestamp in milliseconds.
+	Video { timestamp: u64, data: Bytes },
+
+	// An Opus packet, with the time and duration in samples.
+	Audio { time: u64, duration: u32, data: Bytes },
+
+	Closed,
+}
+
+#[derive(Clone)]
+struct Whip {
+	// The active publisher, as we only accept one.
+	session: Arc<Mutex<Option<Arc<RTCPeerConnection>>>>,
+	events: mpsc::UnboundedSender<Event>,
+}
+
+// Mux the events from the peer connection until it's closed.
+async fn import(events: &mut mpsc::UnboundedReceiver<Event>, media: &mut Media) -> anyhow::Result<()> {
+	let mut muxer = Muxer::new();
+	let mut video = false;
+
+	while let Some(event) = events.recv().await {
+		match event {
+			Event::Start { video: v, audio } => {
+				video = v;
+				if audio {
+					muxer.audio_config(AudioConfig::Opus {
+						sample_rate: OPUS_CLOCK,
+						channels: 2,
+					});
+				}
+			}
+			Event::Video { timestamp, data } => {
+				if !muxer.started() {
+					// Wait for the parameter sets, which are sent in-band before each keyframe.
+					match h264::Config::parse(&data)? {
+						Some(config) => {
+							muxer.metadata(config.width, config.height);
+							muxer.video_config(config.avcc);
+						}
+						None => continue,
+					}
+				}
+
+				let keyframe = h264::is_keyframe(&data);
+				muxer.video(timestamp, keyframe, 0, data);
+			}
+			Event::Audio { time, duration, data } => {
+				// Wait for a video keyframe so the broadcast starts with a complete group.
+				if video && !muxer.started() {
+					continue;
+				}
+
+				muxer.audio(time, duration, data);
+			}
+			Event::Closed => break,
+		}
+
+		media.parse(muxer.output())?;
+	}
+
+	muxer.finish();
+	media.parse(muxer.output())?;
+
+	Ok(())
+}
+
+async fn publish(State(whip): State<Whip>, offer: String) -> Result<Response, AppError> {
+	let mut session = whip.session.lock().await;
+	if session.is_some() {
+		return Err(AppError::Conflict);
+	}
+
+	let offer = RTCSessionDescription::offer(offer).map_err(|_| AppError::BadRequest)?;
+	let parsed = offer.unmarshal().map_err(|_| AppError::BadRequest)?;
+	let has = |kind: &str| parsed.media_descriptions.iter().any(|m| m.media_name.media == kind);
+
+	let pc = peer_connection().await?;
+	let start = Instant::now();
+
+	let events = whip.events.clone();
+	pc.on_track(Box::new(move |track, _receiver, _transceiver| {
+		let events = events.clone();
+		tokio::spawn(async move {
+			if let Err(err) = read_track(track, start, &events).await {
+				log::warn!("failed to read track: {:?}", err);
+			}
+		});
+		Box::pin(async {})
+	}));
+
+	let events = whip.events.clone();
+	pc.on_peer_connection_state_change(Box::new(move |state| {
+		log::info!("WHIP connection state: {}", state);
+		if matches!(state, RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed) {
+			events.send(Event::Closed).ok();
+		}
+		Box::pin(async {})
+	}));
+
+	pc.set_remote_description(offer).await?;
+
+	// We don't support trickle ICE, so wait until all of the candidates are in the answer.
+	let answer = pc.create_answer(None).await?;
+	let mut gathered = pc.gathering_complete_promise().await;
+	pc.set_local_description(answer).await?;
+	gathered.recv().await;
+
+	let answer = pc.local_description().await.ok_or(AppError::BadRequest)?;
+
+	whip.events
+		.send(Event::Start {
+			video: has("video"),
+			audio: has("audio"),
+		})
+		.ok();
+
+	session.replace(pc);
+	log::info!("accepted WHIP publisher");
+
+	Ok((
+		StatusCode::CREATED,
+		[(header::CONTENT_TYPE, "application/sdp"), (header::LOCATION, RESOURCE)],
+		answer.sdp,
+	)
+		.into_response())
+}
+
+async fn unpublish(State(whip): State<Whip>) -> Result<Response, AppError> {
+	let session = whip.session.lock().await.take().ok_or(AppError::NotFound)?;
+	session.close().await?;
+
+	whip.events.send(Event::Closed).ok();
+
+	Ok(StatusCode::OK.into_response())
+}
+
+// Create a peer connection that only negotiates the codecs we can remux.
+async fn peer_connection() -> anyhow::Result<Arc<RTCPeerConnection>> {
+	let mut engine = MediaEngine::default();
+
+	engine.register_codec(
+		RTCRtpCodecParameters {
+			capability: RTCRtpCodecCapability {
+				mime_type: MIME_TYPE_H264.to_string(),
+				clock_rate: VIDEO_CLOCK,
+				sdp_fmtp_line: "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f".to_string(),
+				..Default::default()
+			},
+			payload_type: 102,
+			..Default::default()
+		},
+		RTPCodecType::Video,
+	)?;
+
+	engine.register_codec(
+		RTCRtpCodecParameters {
+			capability: RTCRtpCodecCapability {
+				mime_type: MIME_TYPE_OPUS.to_string(),
+				clock_rate: OPUS_CLOCK,
+				channels: 2,
+				sdp_fmtp_line: "minptime=10;useinbandfec=1".to_string(),
+				..Default::default()
+			},
+			payload_type: 111,
+			..Default::default()
+		},
+		RTPCodecType::Audio,
+	)?;
+
+	let registry = register_default_interceptors(Registry::new(), &mut engine)?;
+
+	let api = APIBuilder::new()
+		.with_media_engine(engine)
+		.with_interceptor_registry(registry)
+		.build();
+
+	Ok(Arc::new(api.new_peer_connection(RTCConfiguration::default()).await?))
+}
+
+// Depacketize the RTP packets into frames until the track ends.
+async fn read_track(
+	track: Arc<TrackRemote>,
+	start: Instant,
+	events: &mpsc::UnboundedSender<Event>,
+) -> anyhow::Result<()> {
+	log::info!("WHIP track: kind={}", track.kind());
+
+	match track.kind() {
+		RTPCodecType::Video => {
+			let mut depacketizer = H264Packet::default();
+			depacketizer.is_avc = true;
+
+			read_samples(
+				&track,
+				depacketizer,
+				VIDEO_CLOCK,
+				start,
+				|time, _, data| Event::Video {
+					timestamp: time * 1000 / VIDEO_CLOCK as u64,
+					data,
+				},
+				events,
+			)
+			.await
+		}
+		RTPCodecType::Audio => {
+			read_samples(
+				&track,
+				OpusPacket,
+				OPUS_CLOCK,
+				start,
+				|time, duration, data| Event::Audio { time, duration, data },
+				events,
+			)
+			.await
+		}
+		kind => anyhow::bail!("unsupported track kind: {}", kind),
+	}
+}
+
+// Build samples from the RTP packets, converting the timestamps to a time in clock units since the start.
+async fn read_samples<D, F>(
+	track: &TrackRemote,
+	depacketizer: D,
+	clock: u32,
+	start: Instant,
+	event: F,
+	events: &mpsc::UnboundedSender<Event>,
+) -> anyhow::Result<()>
+where
+	D: Depacketizer,
+	F: Fn(u64, u32, Bytes) -> Event,
+{
+	let mut builder = SampleBuilder::new(MAX_LATE, depacketizer, clock);
+
+	// Each track has a random RTP timestamp offset, so the first packet is aligned to its arrival time.
+	// TODO use RTCP sender reports for lip sync
+	let mut last: Option<(u32, u64)> = None;
+
+	loop {
+		let (packet, _) = match track.read_rtp().await {
+			Ok(packet) => packet,
+			Err(err) => {
+				log::debug!("WHIP track ended: {}", err);
+				return Ok(());
+			}
+		};
+
+		builder.push(packet);
+
+		while let Some(sample) = builder.pop() {
+			let time = match last {
+				// Unwrap the 32-bit timestamp, which can go backwards slightly.
+				Some((timestamp, time)) => {
+					let delta = sample.packet_timestamp.wrapping_sub(timestamp) as i32;
+					time.saturating_add_signed(delta.into())
+				}
+				None => start.elapsed().as_micros() as u64 * clock as u64 / 1_000_000,
+			};
+			last = Some((sample.packet_timestamp, time));
+
+			let duration = (sample.duration.as_micros() as u64 * clock as u64 / 1_000_000) as u32;
+
+			if events.send(event(time, duration, sample.data)).is_err() {
+				return Ok(());
+			}
+		}
+	}
+}
+
+enum AppError {
+	BadRequest,
+	NotFound,
+	Conflict,
+	Internal(anyhow::Error),
+}
+
+impl<E: Into<anyhow::Error>> From<E> for AppError {
+	fn from(err: E) -> Self {
+		Self::Internal(err.into())
+	}
+}
+
+// Tell axum how to convert `AppError` into a response.
+impl IntoResponse for AppError {
+	fn into_response(self) -> Response {
+		match self {
+			AppError::BadRequest => StatusCode::BAD_REQUEST.into_response(),
+			AppError::NotFound => StatusCode::NOT_FOUND.into_response(),
+			AppError::Conflict => StatusCode::CONFLICT.into_response(),
+			AppError::Internal(err) => {
+				log::warn!("WHIP error: {:?}", err);
+				StatusCode::INTERNAL_SERVER_ERROR.into_response()
+			}
+		}
+	}
+}
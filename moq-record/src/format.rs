@@ -0,0 +1,309 @@
+//! A recording is a header followed by length-prefixed records, in the order they were received.
+//!
+//! Each record is stamped with the time since the recording started, so it can be replayed with the original pacing.
+//! Everything is encoded with the same varints as MoQ Transport, except the 32-bit length prefix of each record.
+use std::time;
+
+use anyhow::Context;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use moq_transport::{
+	coding::{Decode, Encode},
+	data::ObjectMeta,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const MAGIC: &[u8; 8] = b"MOQ-REC\0";
+const VERSION: u64 = 1;
+
+// Avoid allocating an absurd buffer for a corrupt length.
+const MAX_RECORD: usize = 64 * 1024 * 1024;
+
+/// A single event in a recording.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+	/// A track was subscribed, assigning an ID used by the records that follow.
+	Track { id: u64, name: String },
+
+	/// A group was received.
+	Group { track: u64, group_id: u64, priority: u64 },
+
+	/// A complete object was received, with the publisher's metadata if any.
+	Object {
+		track: u64,
+		group_id: u64,
+		meta: Option<ObjectMeta>,
+		payload: Bytes,
+	},
+
+	/// A group was finished, so no more objects will be received.
+	GroupEnd { track: u64, group_id: u64 },
+}
+
+impl Record {
+	const TRACK: u64 = 0;
+	const GROUP: u64 = 1;
+	const OBJECT: u64 = 2;
+	const GROUP_END: u64 = 3;
+
+	fn encode<W: BufMut>(&self, time: time::Duration, w: &mut W) -> anyhow::Result<()> {
+		match self {
+			Self::Track { id, name } => {
+				Self::TRACK.encode(w)?;
+				(time.as_micros() as u64).encode(w)?;
+				id.encode(w)?;
+				name.encode(w)?;
+			}
+			Self::Group {
+				track,
+				group_id,
+				priority,
+			} => {
+				Self::GROUP.encode(w)?;
+				(time.as_micros() as u64).encode(w)?;
+				track.encode(w)?;
+				group_id.encode(w)?;
+				priority.encode(w)?;
+			}
+			Self::Object {
+				track,
+				group_id,
+				meta,
+				payload,
+			} => {
+				Self::OBJECT.encode(w)?;
+				(time.as_micros() as u64).encode(w)?;
+				track.encode(w)?;
+				group_id.encode(w)?;
+
+				match meta {
+					Some(meta) => {
+						1u64.encode(w)?;
+						meta.encode(w)?;
+					}
+					None => 0u64.encode(w)?,
+				}
+
+				payload.len().encode(w)?;
+				w.put_slice(payload);
+			}
+			Self::GroupEnd { track, group_id } => {
+				Self::GROUP_END.encode(w)?;
+				(time.as_micros() as u64).encode(w)?;
+				track.encode(w)?;
+				group_id.encode(w)?;
+			}
+		}
+
+		Ok(())
+	}
+
+	fn decode(mut r: Bytes) -> anyhow::Result<(time::Duration, Self)> {
+		let kind = u64::decode(&mut r)?;
+		let time = time::Duration::from_micros(u64::decode(&mut r)?);
+
+		let record = match kind {
+			Self::TRACK => Self::Track {
+				id: u64::decode(&mut r)?,
+				name: String::decode(&mut r)?,
+			},
+			Self::GROUP => Self::Group {
+				track: u64::decode(&mut r)?,
+				group_id: u64::decode(&mut r)?,
+				priority: u64::decode(&mut r)?,
+			},
+			Self::OBJECT => {
+				let track = u64::decode(&mut r)?;
+				let group_id = u64::decode(&mut r)?;
+
+				let meta = match u64::decode(&mut r)? {
+					0 => None,
+					1 => Some(ObjectMeta::decode(&mut r)?),
+					flag => anyhow::bail!("invalid object meta flag: {}", flag),
+				};
+
+				let size = usize::decode(&mut r)?;
+				anyhow::ensure!(size <= r.remaining(), "truncated object payload");
+
+				Self::Object {
+					track,
+					group_id,
+					meta,
+					payload: r.split_to(size),
+				}
+			}
+			Self::GROUP_END => Self::GroupEnd {
+				track: u64::decode(&mut r)?,
+				group_id: u64::decode(&mut r)?,
+			},
+			kind => anyhow::bail!("unknown record type: {}", kind),
+		};
+
+		Ok((time, record))
+	}
+}
+
+/// Writes a recording, starting with a header containing the broadcast namespace.
+pub struct RecordWriter<W: AsyncWrite + Unpin> {
+	output: W,
+	buffer: BytesMut,
+}
+
+impl<W: AsyncWrite + Unpin> RecordWriter<W> {
+	pub async fn new(mut output: W, namespace: &str) -> anyhow::Result<Self> {
+		let mut buffer = BytesMut::new();
+		buffer.put_slice(MAGIC);
+		VERSION.encode(&mut buffer)?;
+		namespace.to_string().encode(&mut buffer)?;
+
+		output.write_all(&buffer).await?;
+		buffer.clear();
+
+		Ok(Self { output, buffer })
+	}
+
+	/// Append a record received at the given time since the recording started.
+	pub async fn write(&mut self, time: time::Duration, record: &Record) -> anyhow::Result<()> {
+		self.buffer.clear();
+		self.buffer.put_u32(0); // Filled in below
+		record.encode(time, &mut self.buffer)?;
+
+		let size = self.buffer.len() - 4;
+		anyhow::ensure!(size <= MAX_RECORD, "record too large: {}", size);
+		self.buffer[..4].copy_from_slice(&(size as u32).to_be_bytes());
+
+		self.output.write_all(&self.buffer).await?;
+
+		Ok(())
+	}
+
+	/// Flush any buffered output, returning the underlying writer.
+	pub async fn finish(mut self) -> anyhow::Result<W> {
+		self.output.flush().await?;
+		Ok(self.output)
+	}
+}
+
+/// Reads a recording produced by [RecordWriter].
+pub struct RecordReader<R: AsyncRead + Unpin> {
+	input: R,
+	namespace: String,
+}
+
+impl<R: AsyncRead + Unpin> RecordReader<R> {
+	/// Read and validate the header.
+	pub async fn new(mut input: R) -> anyhow::Result<Self> {
+		let mut magic = [0u8; 8];
+		input.read_exact(&mut magic).await.context("failed to read header")?;
+		anyhow::ensure!(&magic == MAGIC, "not a MoQ recording");
+
+		let version = read_varint(&mut input).await?;
+		anyhow::ensure!(version == VERSION, "unsupported recording version: {}", version);
+
+		let size = read_varint(&mut input).await? as usize;
+		anyhow::ensure!(size <= MAX_RECORD, "namespace too large: {}", size);
+
+		let mut namespace = vec![0u8; size];
+		input.read_exact(&mut namespace).await?;
+		let namespace = String::from_utf8(namespace).context("invalid namespace")?;
+
+		Ok(Self { input, namespace })
+	}
+
+	/// The namespace of the recorded broadcast.
+	pub fn namespace(&self) -> &str {
+		&self.namespace
+	}
+
+	/// Returns the next record and the time it was received, or None at the end of the recording.
+	pub async fn next(&mut self) -> anyhow::Result<Option<(time::Duration, Record)>> {
+		let size = match self.input.read_u32().await {
+			Ok(size) => size as usize,
+			Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+			Err(err) => return Err(err.into()),
+		};
+		anyhow::ensure!(size <= MAX_RECORD, "record too large: {}", size);
+
+		let mut buffer = vec![0u8; size];
+		self.input.read_exact(&mut buffer).await.context("truncated record")?;
+
+		Record::decode(buffer.into()).map(Some)
+	}
+}
+
+// Read a QUIC-style varint, where the top two bits of the first byte are the length.
+async fn read_varint<R: AsyncRead + Unpin>(input: &mut R) -> anyhow::Result<u64> {
+	let mut buffer = [0u8; 8];
+	input.read_exact(&mut buffer[..1]).await?;
+
+	let size = 1 << (buffer[0] >> 6);
+	input.read_exact(&mut buffer[1..size]).await?;
+
+	Ok(u64::decode(&mut &buffer[..size])?)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	use moq_transport::data::ObjectFlags;
+
+	#[tokio::test]
+	async fn round_trip() {
+		let records = [
+			Record::Track {
+				id: 0,
+				name: ".catalog".to_string(),
+			},
+			Record::Group {
+				track: 0,
+				group_id: 7,
+				priority: 2,
+			},
+			Record::Object {
+				track: 0,
+				group_id: 7,
+				meta: None,
+				payload: Bytes::from_static(b"{}"),
+			},
+			Record::Object {
+				track: 0,
+				group_id: 7,
+				meta: Some(ObjectMeta {
+					timestamp: 90_000,
+					flags: ObjectFlags {
+						keyframe: true,
+						discardable: false,
+					},
+					tag: Bytes::from_static(b"tag"),
+				}),
+				payload: Bytes::from_static(b"moof"),
+			},
+			Record::GroupEnd { track: 0, group_id: 7 },
+		];
+
+		let mut writer = RecordWriter::new(Vec::new(), "dev").await.unwrap();
+		for (i, record) in records.iter().enumerate() {
+			writer
+				.write(time::Duration::from_millis(i as u64 * 10), record)
+				.await
+				.unwrap();
+		}
+		let output = writer.finish().await.unwrap();
+
+		let mut reader = RecordReader::new(output.as_slice()).await.unwrap();
+		assert_eq!(reader.namespace(), "dev");
+
+		for (i, expected) in records.iter().enumerate() {
+			let (time, record) = reader.next().await.unwrap().unwrap();
+			assert_eq!(time, time::Duration::from_millis(i as u64 * 10));
+			assert_eq!(&record, expected);
+		}
+
+		assert!(reader.next().await.unwrap().is_none());
+	}
+
+	#[tokio::test]
+	async fn invalid_magic() {
+		assert!(RecordReader::new(&b"NOT-MOQ\0\x01"[..]).await.is_err());
+	}
+}
@@ -0,0 +1,7 @@
+mod format;
+mod record;
+mod replay;
+
+pub use format::*;
+pub use record::*;
+pub use replay::*;
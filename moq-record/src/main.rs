@@ -0,0 +1,136 @@
+use std::{net, path::PathBuf};
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use tokio::{fs, io};
+use url::Url;
+
+use moq_native::quic;
+use moq_record::{Pacing, RecordReader, Recorder, Replayer};
+use moq_transport::serve::Tracks;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+	env_logger::init();
+
+	// Disable tracing so we don't get a bunch of Quinn spam.
+	let tracer = tracing_subscriber::FmtSubscriber::builder()
+		.with_max_level(tracing::Level::WARN)
+		.finish();
+	tracing::subscriber::set_global_default(tracer).unwrap();
+
+	let config = Config::parse();
+	let tls = config.tls.load()?;
+	let quic = quic::Endpoint::new(quic::Config { bind: config.bind, tls })?;
+
+	let session = quic.client.connect(&config.url).await?;
+
+	match config.command {
+		Command::Record { name, output } => {
+			let (session, subscriber) = moq_transport::session::Subscriber::connect(session)
+				.await
+				.context("failed to create MoQ Transport session")?;
+
+			let output = io::BufWriter::new(fs::File::create(&output).await.context("failed to create output")?);
+			let recorder = Recorder::new(subscriber, Tracks::new(name));
+
+			tokio::select! {
+				res = session.run() => res.context("session error")?,
+				res = recorder.run(output) => res.context("recorder error")?,
+			}
+		}
+		Command::Replay {
+			name,
+			input,
+			fast,
+			on_demand,
+		} => {
+			let (session, mut publisher) = moq_transport::session::Publisher::connect(session)
+				.await
+				.context("failed to create MoQ Transport session")?;
+
+			let input = io::BufReader::new(fs::File::open(&input).await.context("failed to open input")?);
+			let input = RecordReader::new(input).await?;
+
+			// Default to the namespace of the original broadcast.
+			let name = name.unwrap_or_else(|| input.namespace().to_string());
+			let (writer, _, reader) = Tracks::new(name).produce();
+
+			let pacing = match fast {
+				true => Pacing::Fast,
+				false => Pacing::Original,
+			};
+			let replayer = Replayer::new(writer).pacing(pacing).on_demand(on_demand);
+
+			tokio::select! {
+				res = session.run() => res.context("session error")?,
+				res = replayer.run(input) => res.context("replay error")?,
+				res = publisher.announce(reader) => res.context("failed to serve tracks")?,
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[derive(Parser, Clone)]
+pub struct Config {
+	/// Listen for UDP packets on the given address.
+	#[arg(long, default_value = "[::]:0")]
+	pub bind: net::SocketAddr,
+
+	/// Connect to the given URL starting with https://
+	#[arg(value_parser = moq_url)]
+	pub url: Url,
+
+	/// The TLS configuration.
+	#[command(flatten)]
+	pub tls: moq_native::tls::Args,
+
+	#[command(subcommand)]
+	pub command: Command,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+	/// Subscribe to a broadcast and record it to a file until it ends.
+	Record {
+		/// The name of the broadcast
+		#[arg(long)]
+		name: String,
+
+		/// The file to write the recording to.
+		#[arg(long)]
+		output: PathBuf,
+	},
+
+	/// Publish a recording as a broadcast.
+	Replay {
+		/// The name of the broadcast, defaulting to the recorded name.
+		#[arg(long)]
+		name: Option<String>,
+
+		/// The file to read the recording from.
+		#[arg(long)]
+		input: PathBuf,
+
+		/// Publish as fast as possible instead of with the original timing.
+		#[arg(long)]
+		fast: bool,
+
+		/// Retain every group so subscribers can watch from the beginning, and keep serving after the end.
+		#[arg(long)]
+		on_demand: bool,
+	},
+}
+
+fn moq_url(s: &str) -> Result<Url, String> {
+	let url = Url::try_from(s).map_err(|e| e.to_string())?;
+
+	// Make sure the scheme is moq
+	if url.scheme() != "https" {
+		return Err("url scheme must be https:// for WebTransport".to_string());
+	}
+
+	Ok(url)
+}
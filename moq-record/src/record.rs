@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use log::{info, warn};
+use moq_transport::{
+	serve::{GroupReader, TrackReader, TrackReaderMode, Tracks, TracksReader, TracksWriter},
+	session::Subscriber,
+};
+use tokio::{io::AsyncWrite, sync::mpsc, time::Instant};
+
+use crate::{Record, RecordWriter};
+
+const CATALOG: &str = ".catalog";
+
+/// Subscribes to every track in a broadcast's catalog and writes everything received to a recording.
+pub struct Recorder {
+	subscriber: Subscriber,
+	broadcast: TracksReader,
+	tracks_writer: TracksWriter,
+}
+
+impl Recorder {
+	pub fn new(subscriber: Subscriber, tracks: Tracks) -> Self {
+		let (tracks_writer, _tracks_request, broadcast) = tracks.produce();
+
+		Self {
+			subscriber,
+			broadcast,
+			tracks_writer,
+		}
+	}
+
+	/// Record the catalog, then each track and init track it lists, until they have all ended.
+	///
+	/// NOTE: Only the first catalog is used to choose tracks; tracks added by later updates are not recorded.
+	pub async fn run<W: AsyncWrite + Unpin>(mut self, output: W) -> anyhow::Result<()> {
+		let mut output = RecordWriter::new(output, &self.broadcast.namespace).await?;
+		let start = Instant::now();
+
+		let (tx, mut rx) = mpsc::unbounded_channel();
+
+		// Dropped once every track has been subscribed, so the channel closes when they all end.
+		let mut tx = Some(tx);
+		let mut names = HashSet::new();
+
+		let record = Record::Track {
+			id: 0,
+			name: CATALOG.to_string(),
+		};
+		output.write(start.elapsed(), &record).await?;
+		self.record(0, CATALOG, tx.as_ref().unwrap())?;
+		names.insert(CATALOG.to_string());
+
+		while let Some(record) = rx.recv().await {
+			output.write(start.elapsed(), &record).await?;
+
+			let catalog = match (&tx, &record) {
+				(Some(_), Record::Object { track: 0, payload, .. }) => {
+					moq_catalog::Root::from_slice(payload).context("failed to parse catalog")?
+				}
+				_ => continue,
+			};
+
+			let tx = tx.take().unwrap();
+
+			let tracks = catalog
+				.tracks
+				.iter()
+				.flat_map(|track| track.init_track.iter().chain([&track.name]));
+
+			for name in tracks {
+				if !names.insert(name.clone()) {
+					continue;
+				}
+
+				let id = names.len() as u64 - 1;
+				info!("recording track {name}: id={id}");
+
+				let record = Record::Track { id, name: name.clone() };
+				output.write(start.elapsed(), &record).await?;
+
+				self.record(id, name, &tx)?;
+			}
+		}
+
+		anyhow::ensure!(tx.is_none(), "no catalog received");
+
+		output.finish().await?;
+
+		Ok(())
+	}
+
+	// Subscribe to the track and forward everything received to the writer.
+	fn record(&mut self, id: u64, name: &str, tx: &mpsc::UnboundedSender<Record>) -> anyhow::Result<()> {
+		let track = self.subscribe(name)?;
+		let tx = tx.clone();
+
+		tokio::spawn(async move {
+			let name = track.name.clone();
+			if let Err(err) = Self::run_track(id, track, tx).await {
+				warn!("failed to record track {name}: {err:?}");
+			}
+		});
+
+		Ok(())
+	}
+
+	fn subscribe(&mut self, name: &str) -> anyhow::Result<TrackReader> {
+		let track = self.tracks_writer.create(name).context("failed to create track")?;
+
+		let mut subscriber = self.subscriber.clone();
+		tokio::task::spawn(async move {
+			let name = track.name.clone();
+			subscriber.subscribe(track).await.unwrap_or_else(|err| {
+				warn!("failed to subscribe to track {name}: {err:?}");
+			});
+		});
+
+		self.broadcast.subscribe(name).context("no track")
+	}
+
+	// Groups are recorded in parallel, since they may arrive out of order.
+	async fn run_track(id: u64, track: TrackReader, tx: mpsc::UnboundedSender<Record>) -> anyhow::Result<()> {
+		let mut groups = match track.mode().await? {
+			TrackReaderMode::Groups(groups) => groups,
+			_ => anyhow::bail!("expected groups"),
+		};
+
+		while let Some(group) = groups.next().await? {
+			let record = Record::Group {
+				track: id,
+				group_id: group.group_id,
+				priority: group.priority,
+			};
+
+			if tx.send(record).is_err() {
+				break;
+			}
+
+			let tx = tx.clone();
+			tokio::spawn(async move {
+				let group_id = group.group_id;
+				if let Err(err) = Self::run_group(id, group, tx).await {
+					warn!("failed to record group: track={id} group={group_id} err={err:?}");
+				}
+			});
+		}
+
+		Ok(())
+	}
+
+	async fn run_group(id: u64, mut group: GroupReader, tx: mpsc::UnboundedSender<Record>) -> anyhow::Result<()> {
+		let group_id = group.group_id;
+
+		while let Some(mut object) = group.next().await? {
+			let meta = object.meta.clone();
+			let payload = object.read_all().await?;
+
+			let record = Record::Object {
+				track: id,
+				group_id,
+				meta,
+				payload,
+			};
+
+			if tx.send(record).is_err() {
+				return Ok(());
+			}
+		}
+
+		tx.send(Record::GroupEnd { track: id, group_id }).ok();
+
+		Ok(())
+	}
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use log::{debug, info};
+use moq_transport::serve::{Group, GroupOrder, GroupWriter, GroupsWriter, Track, TracksWriter};
+use tokio::{io::AsyncRead, time::Instant};
+
+use crate::{Record, RecordReader};
+
+/// How quickly a recording is republished.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Pacing {
+	/// Wait until each record's original time, as if the broadcast were live.
+	#[default]
+	Original,
+
+	/// Publish every record as soon as it's read.
+	Fast,
+}
+
+/// Republishes a recording produced by [crate::Recorder].
+pub struct Replayer {
+	broadcast: TracksWriter,
+	pacing: Pacing,
+	on_demand: bool,
+
+	// Keyed by the track ID in the recording.
+	tracks: HashMap<u64, GroupsWriter>,
+	groups: HashMap<(u64, u64), GroupWriter>,
+}
+
+impl Replayer {
+	pub fn new(broadcast: TracksWriter) -> Self {
+		Self {
+			broadcast,
+			pacing: Pacing::default(),
+			on_demand: false,
+			tracks: HashMap::new(),
+			groups: HashMap::new(),
+		}
+	}
+
+	pub fn pacing(mut self, pacing: Pacing) -> Self {
+		self.pacing = pacing;
+		self
+	}
+
+	/// Retain every group and deliver them in order, so subscribers can watch from the beginning.
+	///
+	/// Otherwise only the latest group is retained, like a live broadcast.
+	pub fn on_demand(mut self, on_demand: bool) -> Self {
+		self.on_demand = on_demand;
+		self
+	}
+
+	/// Publish each record in the recording.
+	///
+	/// When on demand, this never returns after reaching the end, so the tracks remain available to new subscribers.
+	pub async fn run<R: AsyncRead + Unpin>(mut self, mut input: RecordReader<R>) -> anyhow::Result<()> {
+		info!(
+			"replaying broadcast: namespace={} pacing={:?}",
+			input.namespace(),
+			self.pacing
+		);
+
+		let start = Instant::now();
+
+		while let Some((time, record)) = input.next().await? {
+			if self.pacing == Pacing::Original {
+				tokio::time::sleep_until(start + time).await;
+			}
+
+			self.replay(record)?;
+		}
+
+		info!("finished replaying broadcast");
+
+		if self.on_demand {
+			std::future::pending::<()>().await;
+		}
+
+		Ok(())
+	}
+
+	fn replay(&mut self, record: Record) -> anyhow::Result<()> {
+		match record {
+			Record::Track { id, name } => {
+				let mut track = Track::new(self.broadcast.namespace.clone(), name);
+				if self.on_demand {
+					track = track.cache(usize::MAX).order(GroupOrder::Ascending);
+				}
+
+				let track = self.broadcast.insert(track).context("broadcast closed")?;
+				debug!("replaying track {}: id={}", track.name, id);
+
+				self.tracks.insert(id, track.groups()?);
+			}
+			Record::Group {
+				track,
+				group_id,
+				priority,
+			} => {
+				let groups = self.tracks.get_mut(&track).context("unknown track")?;
+				let group = groups.create(Group { group_id, priority })?;
+				self.groups.insert((track, group_id), group);
+			}
+			Record::Object {
+				track,
+				group_id,
+				meta,
+				payload,
+			} => {
+				let group = self.groups.get_mut(&(track, group_id)).context("unknown group")?;
+				match meta {
+					Some(meta) => group.write_with_meta(payload, meta)?,
+					None => group.write(payload)?,
+				}
+			}
+			Record::GroupEnd { track, group_id } => {
+				// Dropping the writer finishes the group.
+				self.groups.remove(&(track, group_id));
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	use bytes::Bytes;
+	use moq_transport::serve::{TrackReaderMode, Tracks};
+
+	use crate::RecordWriter;
+
+	#[tokio::test]
+	async fn replay_on_demand() {
+		let mut writer = RecordWriter::new(Vec::new(), "dev").await.unwrap();
+		let records = [
+			Record::Track {
+				id: 0,
+				name: "video".to_string(),
+			},
+			Record::Group {
+				track: 0,
+				group_id: 0,
+				priority: 0,
+			},
+			Record::Object {
+				track: 0,
+				group_id: 0,
+				meta: None,
+				payload: Bytes::from_static(b"a"),
+			},
+			Record::Group {
+				track: 0,
+				group_id: 1,
+				priority: 0,
+			},
+			Record::GroupEnd { track: 0, group_id: 0 },
+			Record::Object {
+				track: 0,
+				group_id: 1,
+				meta: None,
+				payload: Bytes::from_static(b"b"),
+			},
+			Record::GroupEnd { track: 0, group_id: 1 },
+		];
+		for record in &records {
+			writer.write(Default::default(), record).await.unwrap();
+		}
+		let recording = writer.finish().await.unwrap();
+
+		let (writer, _request, mut reader) = Tracks::new("dev".to_string()).produce();
+		let replay = Replayer::new(writer).pacing(Pacing::Fast).on_demand(true);
+		let input = RecordReader::new(std::io::Cursor::new(recording)).await.unwrap();
+
+		// The recording is in memory, so yielding once lets the replay reach the end before we subscribe, like a late viewer.
+		let handle = tokio::spawn(replay.run(input));
+		tokio::task::yield_now().await;
+
+		let track = reader.subscribe("video").unwrap();
+		let mut groups = match track.mode().await.unwrap() {
+			TrackReaderMode::Groups(groups) => groups,
+			_ => panic!("expected groups"),
+		};
+
+		for expected in [&b"a"[..], b"b"] {
+			let mut group = groups.next().await.unwrap().unwrap();
+			assert_eq!(group.read_next().await.unwrap().unwrap(), expected);
+			assert!(group.read_next().await.unwrap().is_none());
+		}
+
+		handle.abort();
+	}
+}
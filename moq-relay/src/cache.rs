@@ -0,0 +1,201 @@
+use std::{
+	collections::HashMap,
+	ops,
+	sync::{Arc, Mutex},
+	time,
+};
+
+use moq_transport::serve::{Track, TrackReader, TrackWriter};
+use tokio::sync::oneshot;
+
+/// How tracks fetched from other origins are cached.
+#[derive(Clone, Debug)]
+pub struct CacheConfig {
+	/// The maximum number of groups retained for each track.
+	pub groups: usize,
+
+	/// Evict groups this long after they were created, regardless of the count.
+	pub expires: Option<time::Duration>,
+
+	/// Keep a track subscribed this long after the last subscriber leaves, so a late joiner is served from the cache.
+	pub linger: time::Duration,
+}
+
+impl Default for CacheConfig {
+	fn default() -> Self {
+		Self {
+			groups: Track::DEFAULT_CACHE,
+			expires: None,
+			linger: time::Duration::ZERO,
+		}
+	}
+}
+
+type CacheKey = (String, String);
+
+struct CacheEntry {
+	reader: TrackReader,
+
+	// The number of subscribers currently holding the track.
+	active: usize,
+
+	// Incremented each time the track is used, so a stale linger timer doesn't evict it.
+	epoch: u64,
+
+	// Stops the task watching for the track to close when the entry is evicted.
+	_evicted: oneshot::Sender<()>,
+}
+
+/// Shares a single upstream subscription for each track between every downstream subscriber.
+///
+/// Groups are retained according to the [CacheConfig], so subscribers that join later start from the cache.
+#[derive(Clone)]
+pub struct Cache {
+	config: Arc<CacheConfig>,
+	entries: Arc<Mutex<HashMap<CacheKey, CacheEntry>>>,
+}
+
+impl Cache {
+	pub fn new(config: CacheConfig) -> Self {
+		Self {
+			config: Arc::new(config),
+			entries: Default::default(),
+		}
+	}
+
+	/// Return the cached track, along with a writer if it's new and must be subscribed upstream.
+	///
+	/// The track is evicted once it's closed, or after lingering without any subscribers.
+	pub fn subscribe(&self, namespace: &str, name: &str) -> (CachedTrack, Option<TrackWriter>) {
+		let key = (namespace.to_string(), name.to_string());
+		let mut entries = self.entries.lock().unwrap();
+
+		if let Some(entry) = entries.get_mut(&key) {
+			entry.active += 1;
+			entry.epoch += 1;
+
+			log::debug!("serving from cache: namespace={} name={}", namespace, name);
+			return (self.track(key, entry.reader.clone()), None);
+		}
+
+		let mut track = Track::new(key.0.clone(), key.1.clone()).cache(self.config.groups);
+		if let Some(expires) = self.config.expires {
+			track = track.expires(expires);
+		}
+
+		let (writer, reader) = track.produce();
+		let (evicted, stop) = oneshot::channel();
+
+		entries.insert(
+			key.clone(),
+			CacheEntry {
+				reader: reader.clone(),
+				active: 1,
+				epoch: 0,
+				_evicted: evicted,
+			},
+		);
+
+		// Evict the track once it's closed, so the next subscriber fetches it again.
+		let cache = self.clone();
+		let watch = reader.clone();
+		let watch_key = key.clone();
+		tokio::spawn(async move {
+			tokio::select! {
+				_ = watch.closed() => cache.evict(&watch_key, &watch.info),
+				_ = stop => {},
+			}
+		});
+
+		(self.track(key, reader), Some(writer))
+	}
+
+	fn track(&self, key: CacheKey, reader: TrackReader) -> CachedTrack {
+		CachedTrack {
+			_drop: Arc::new(CachedTrackDrop {
+				cache: self.clone(),
+				key,
+				info: reader.info.clone(),
+			}),
+			reader,
+		}
+	}
+
+	// Called when a subscriber is done with the track, ignoring it if the track was already evicted.
+	fn release(&self, key: &CacheKey, info: &Arc<Track>) {
+		let mut entries = self.entries.lock().unwrap();
+		let Some(entry) = entries
+			.get_mut(key)
+			.filter(|entry| Arc::ptr_eq(&entry.reader.info, info))
+		else {
+			return;
+		};
+
+		entry.active -= 1;
+		if entry.active > 0 {
+			return;
+		}
+
+		if self.config.linger.is_zero() {
+			entries.remove(key);
+			return;
+		}
+
+		let epoch = entry.epoch;
+		let cache = self.clone();
+		let key = key.clone();
+
+		tokio::spawn(async move {
+			tokio::time::sleep(cache.config.linger).await;
+
+			let mut entries = cache.entries.lock().unwrap();
+			if entries
+				.get(&key)
+				.is_some_and(|entry| entry.active == 0 && entry.epoch == epoch)
+			{
+				log::debug!("evicting idle track: namespace={} name={}", key.0, key.1);
+				entries.remove(&key);
+			}
+		});
+	}
+
+	// Remove the entry, unless it was already replaced by a newer track.
+	fn evict(&self, key: &CacheKey, info: &Arc<Track>) {
+		let mut entries = self.entries.lock().unwrap();
+		if entries
+			.get(key)
+			.is_some_and(|entry| Arc::ptr_eq(&entry.reader.info, info))
+		{
+			entries.remove(key);
+		}
+	}
+}
+
+/// A track served from the [Cache], which is released when dropped.
+#[derive(Clone)]
+pub struct CachedTrack {
+	pub reader: TrackReader,
+
+	// Releases the track once every clone is dropped.
+	_drop: Arc<CachedTrackDrop>,
+}
+
+impl ops::Deref for CachedTrack {
+	type Target = TrackReader;
+
+	fn deref(&self) -> &Self::Target {
+		&self.reader
+	}
+}
+
+struct CachedTrackDrop {
+	cache: Cache,
+	key: CacheKey,
+	info: Arc<Track>,
+}
+
+impl Drop for CachedTrackDrop {
+	fn drop(&mut self) {
+		self.cache.release(&self.key, &self.info);
+	}
+}
@@ -0,0 +1,152 @@
+use std::{
+	collections::{HashMap, HashSet},
+	sync::{Arc, Mutex},
+	time,
+};
+
+use anyhow::Context;
+use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
+use moq_native::quic;
+use moq_transport::{
+	serve::{Track, TrackReader, TrackReaderMode, Tracks},
+	session::Subscriber,
+};
+use url::Url;
+
+use crate::Locals;
+
+/// The broadcast each relay uses to gossip the namespaces it's the origin for.
+pub const CLUSTER_NAMESPACE: &str = ".cluster";
+
+// A single track where each group is a snapshot of the origins, one namespace per line.
+const ORIGINS_TRACK: &str = "origins";
+
+// How long to wait before reconnecting to a peer.
+const RECONNECT_DELAY: time::Duration = time::Duration::from_secs(1);
+
+pub struct ClusterConfig {
+	/// The other relays in the cluster, which are all connected to.
+	pub peers: Vec<Url>,
+}
+
+/// Exchanges which relay is the origin for each broadcast, so a broadcast published to any relay is reachable from all.
+///
+/// Every relay publishes its local namespaces as [CLUSTER_NAMESPACE], and subscribes to the same broadcast from each peer.
+#[derive(Clone)]
+pub struct Cluster {
+	locals: Locals,
+	quic: quic::Client,
+	peers: Arc<Vec<Url>>,
+
+	// The namespaces each peer is the origin for.
+	origins: Arc<Mutex<HashMap<Url, HashSet<String>>>>,
+}
+
+impl Cluster {
+	pub fn new(config: ClusterConfig, locals: Locals, quic: quic::Client) -> Self {
+		Self {
+			locals,
+			quic,
+			peers: Arc::new(config.peers),
+			origins: Default::default(),
+		}
+	}
+
+	/// Returns the peer that is the origin for the namespace, if any.
+	pub fn route(&self, namespace: &str) -> Option<Url> {
+		let origins = self.origins.lock().unwrap();
+		origins
+			.iter()
+			.find(|(_, namespaces)| namespaces.contains(namespace))
+			.map(|(peer, _)| peer.clone())
+	}
+
+	pub async fn run(self) -> anyhow::Result<()> {
+		let mut tasks = FuturesUnordered::new();
+		tasks.push(self.clone().publish().boxed());
+
+		for peer in self.peers.iter() {
+			tasks.push(self.clone().follow(peer.clone()).boxed());
+		}
+
+		// Every task runs forever unless publishing fails.
+		tasks.select_next_some().await
+	}
+
+	// Publish a snapshot of our local namespaces each time they change.
+	async fn publish(self) -> anyhow::Result<()> {
+		let (mut writer, _, reader) = Tracks::new(CLUSTER_NAMESPACE.to_string()).produce();
+
+		// Peers subscribe to the broadcast like any other local broadcast.
+		let _registration = self.locals.clone().register(reader);
+
+		let mut groups = writer
+			.create(ORIGINS_TRACK)
+			.context("failed to create origins track")?
+			.groups()?;
+
+		let mut published = None;
+
+		loop {
+			let changed = self.locals.changed();
+
+			let mut origins = self.locals.namespaces();
+			origins.retain(|namespace| namespace != CLUSTER_NAMESPACE);
+			origins.sort();
+
+			if published.as_ref() != Some(&origins) {
+				log::debug!("publishing cluster origins: {:?}", origins);
+				groups.append(0)?.write(origins.join("\n").into())?;
+				published = Some(origins);
+			}
+
+			changed.await;
+		}
+	}
+
+	// Subscribe to the peer's origins, reconnecting until the relay shuts down.
+	async fn follow(self, peer: Url) -> anyhow::Result<()> {
+		loop {
+			if let Err(err) = self.subscribe(&peer).await {
+				log::warn!("failed following cluster peer: peer={} err={:?}", peer, err);
+			}
+
+			// Forget the peer's origins until we reconnect.
+			self.origins.lock().unwrap().remove(&peer);
+
+			tokio::time::sleep(RECONNECT_DELAY).await;
+		}
+	}
+
+	async fn subscribe(&self, peer: &Url) -> anyhow::Result<()> {
+		let session = self.quic.connect(peer).await?;
+		let (session, mut subscriber) = Subscriber::connect(session).await?;
+
+		let (writer, reader) = Track::new(CLUSTER_NAMESPACE.to_string(), ORIGINS_TRACK.to_string()).produce();
+
+		tokio::select! {
+			res = session.run() => res?,
+			res = subscriber.subscribe(writer) => res?,
+			res = self.recv(peer, reader) => res?,
+		}
+
+		Ok(())
+	}
+
+	async fn recv(&self, peer: &Url, track: TrackReader) -> anyhow::Result<()> {
+		let mut groups = match track.mode().await? {
+			TrackReaderMode::Groups(groups) => groups,
+			_ => anyhow::bail!("expected origins groups"),
+		};
+
+		while let Some(mut group) = groups.next().await? {
+			let payload = group.read_next().await?.unwrap_or_default();
+			let origins: HashSet<String> = std::str::from_utf8(&payload)?.lines().map(String::from).collect();
+
+			log::info!("cluster origins: peer={} origins={:?}", peer, origins);
+			self.origins.lock().unwrap().insert(peer.clone(), origins);
+		}
+
+		Ok(())
+	}
+}
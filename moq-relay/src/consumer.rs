@@ -1,7 +1,7 @@
 use anyhow::Context;
 use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
 use moq_transport::{
-	serve::Tracks,
+	serve::{Tracks, TracksReader},
 	session::{Announced, SessionError, Subscriber},
 };
 
@@ -51,16 +51,24 @@ impl Consumer {
 	async fn serve(mut self, mut announce: Announced) -> Result<(), anyhow::Error> {
 		let mut tasks = FuturesUnordered::new();
 
+		// Fast path: skip producing the tracks and updating the origin if the namespace is already served.
+		if let Some(existing) = self.locals.route(&announce.namespace) {
+			return Self::serve_existing(announce, existing).await;
+		}
+
 		let (_, mut request, reader) = Tracks::new(announce.namespace.to_string()).produce();
 
+		// Register the local tracks before any await, so concurrent announces can't race; unregister on drop.
+		let register = self.locals.register(reader.clone());
+		if !register.is_owner() {
+			return Self::serve_existing(announce, register.tracks.clone()).await;
+		}
+
 		if let Some(api) = self.api.as_ref() {
 			let mut refresh = api.set_origin(reader.namespace.clone()).await?;
 			tasks.push(async move { refresh.run().await.context("failed refreshing origin") }.boxed());
 		}
 
-		// Register the local tracks, unregister on drop
-		let _register = self.locals.register(reader.clone()).await?;
-
 		announce.ok()?;
 
 		if let Some(mut forward) = self.forward {
@@ -98,4 +106,17 @@ impl Consumer {
 			}
 		}
 	}
+
+	// Accept an announce for a namespace that's already served, sharing the existing tracks until either is closed.
+	async fn serve_existing(mut announce: Announced, existing: TracksReader) -> Result<(), anyhow::Error> {
+		log::info!("sharing existing tracks: {:?}", existing.info);
+		announce.ok()?;
+
+		tokio::select! {
+			res = announce.closed() => res?,
+			res = existing.closed() => res?,
+		}
+
+		Ok(())
+	}
 }
@@ -2,12 +2,16 @@ use std::collections::hash_map;
 use std::collections::HashMap;
 
 use std::sync::{Arc, Mutex};
+use std::time;
 
-use moq_transport::serve::{ServeError, TracksReader};
+use moq_transport::serve::TracksReader;
 
 #[derive(Clone)]
 pub struct Locals {
 	lookup: Arc<Mutex<HashMap<String, TracksReader>>>,
+
+	// Wakes any tasks waiting for a namespace to be registered or removed.
+	changed: Arc<tokio::sync::Notify>,
 }
 
 impl Default for Locals {
@@ -20,36 +24,95 @@ impl Locals {
 	pub fn new() -> Self {
 		Self {
 			lookup: Default::default(),
+			changed: Default::default(),
 		}
 	}
 
-	pub async fn register(&mut self, tracks: TracksReader) -> anyhow::Result<Registration> {
+	/// Register the tracks for their namespace, until the [Registration] is dropped.
+	///
+	/// If the namespace is already registered, the registration shares the existing tracks instead,
+	/// so requests for the namespace are still routed to a single place.
+	pub fn register(&mut self, tracks: TracksReader) -> Registration {
 		let namespace = tracks.namespace.clone();
-		match self.lookup.lock().unwrap().entry(namespace.clone()) {
-			hash_map::Entry::Vacant(entry) => entry.insert(tracks),
-			hash_map::Entry::Occupied(_) => return Err(ServeError::Duplicate.into()),
+		let existing = match self.lookup.lock().unwrap().entry(namespace.clone()) {
+			hash_map::Entry::Vacant(entry) => {
+				entry.insert(tracks.clone());
+				None
+			}
+			hash_map::Entry::Occupied(entry) => Some(entry.get().clone()),
 		};
 
-		let registration = Registration {
+		let owner = existing.is_none();
+		if owner {
+			self.changed.notify_waiters();
+		}
+
+		Registration {
 			locals: self.clone(),
 			namespace,
-		};
-
-		Ok(registration)
+			tracks: existing.unwrap_or(tracks),
+			owner,
+		}
 	}
 
 	pub fn route(&self, namespace: &str) -> Option<TracksReader> {
 		self.lookup.lock().unwrap().get(namespace).cloned()
 	}
+
+	/// Returns every namespace currently registered.
+	pub fn namespaces(&self) -> Vec<String> {
+		self.lookup.lock().unwrap().keys().cloned().collect()
+	}
+
+	/// Block until a namespace is registered or removed.
+	///
+	/// Create the future before checking [Self::namespaces], so a change in between isn't missed.
+	pub fn changed(&self) -> tokio::sync::futures::Notified<'_> {
+		self.changed.notified()
+	}
+
+	/// Route the namespace, waiting up to the timeout for a publisher to register it.
+	///
+	/// This smooths over the startup race where a subscriber arrives just before the publisher.
+	pub async fn route_wait(&self, namespace: &str, timeout: time::Duration) -> Option<TracksReader> {
+		let deadline = tokio::time::Instant::now() + timeout;
+
+		loop {
+			// Create the future before checking, so a registration in between isn't missed.
+			let registered = self.changed();
+
+			if let Some(tracks) = self.route(namespace) {
+				return Some(tracks);
+			}
+
+			tokio::time::timeout_at(deadline, registered).await.ok()?;
+		}
+	}
 }
 
 pub struct Registration {
 	locals: Locals,
 	namespace: String,
+
+	/// The tracks routed for the namespace, which belong to another registration unless [Self::is_owner].
+	pub tracks: TracksReader,
+
+	// Only the first registration removes the namespace when dropped.
+	owner: bool,
+}
+
+impl Registration {
+	/// Returns true if these tracks were registered, rather than sharing ones that already were.
+	pub fn is_owner(&self) -> bool {
+		self.owner
+	}
 }
 
 impl Drop for Registration {
 	fn drop(&mut self) {
-		self.locals.lookup.lock().unwrap().remove(&self.namespace);
+		if self.owner {
+			self.locals.lookup.lock().unwrap().remove(&self.namespace);
+			self.locals.changed.notify_waiters();
+		}
 	}
 }
@@ -1,6 +1,8 @@
 use clap::Parser;
 
 mod api;
+mod cache;
+mod cluster;
 mod consumer;
 mod local;
 mod producer;
@@ -10,6 +12,8 @@ mod session;
 mod web;
 
 pub use api::*;
+pub use cache::*;
+pub use cluster::*;
 pub use consumer::*;
 pub use local::*;
 pub use producer::*;
@@ -18,7 +22,8 @@ pub use remote::*;
 pub use session::*;
 pub use web::*;
 
-use std::net;
+use moq_transport::session::SessionLimits;
+use std::{net, time};
 use url::Url;
 
 #[derive(Parser, Clone)]
@@ -46,8 +51,52 @@ pub struct Cli {
 	#[arg(long)]
 	pub node: Option<Url>,
 
+	/// Join a cluster by gossiping origins with these relays, so broadcasts published to any of them are reachable.
+	/// This can be repeated, and is an alternative to --api that doesn't need a database.
+	#[arg(long)]
+	pub cluster_peer: Vec<Url>,
+
+	/// The number of groups cached for each track fetched from another origin.
+	#[arg(long, default_value = "8")]
+	pub cache_groups: usize,
+
+	/// Evict cached groups after this many seconds.
+	#[arg(long)]
+	pub cache_expires: Option<u64>,
+
+	/// Keep fetching a track for this many seconds after the last subscriber leaves, so late joiners hit the cache.
+	#[arg(long, default_value = "10")]
+	pub cache_linger: u64,
+
+	/// Redirect subscribers to the origin of a broadcast, instead of fetching it on their behalf.
+	/// Only clients that follow redirects can watch broadcasts published to other relays.
+	#[arg(long)]
+	pub redirect: bool,
+
+	/// The maximum number of subscriptions served to each client at once.
+	#[arg(long)]
+	pub max_subscriptions: Option<usize>,
+
+	/// The maximum number of broadcasts announced by each client at once.
+	#[arg(long)]
+	pub max_announces: Option<usize>,
+
+	/// The maximum number of streams received from each client at once.
+	#[arg(long)]
+	pub max_streams: Option<usize>,
+
+	/// The maximum rate that media is received from each client, in bytes per second.
+	#[arg(long)]
+	pub max_bitrate: Option<u64>,
+
+	/// Hold subscriptions for broadcasts that haven't been announced yet for this many milliseconds.
+	/// This lets viewers join before the streamer goes live.
+	#[arg(long, default_value = "500")]
+	pub subscribe_wait: u64,
+
 	/// Enable development mode.
 	/// This hosts a HTTPS web server via TCP to serve the fingerprint of the certificate.
+	/// The server uses HTTP instead when the certificate is generated with `--tls-self-sign`.
 	#[arg(long)]
 	pub dev: bool,
 }
@@ -76,6 +125,22 @@ async fn main() -> anyhow::Result<()> {
 		node: cli.node,
 		api: cli.api,
 		announce: cli.announce,
+		cluster: ClusterConfig {
+			peers: cli.cluster_peer,
+		},
+		cache: CacheConfig {
+			groups: cli.cache_groups,
+			expires: cli.cache_expires.map(time::Duration::from_secs),
+			linger: time::Duration::from_secs(cli.cache_linger),
+		},
+		redirect: cli.redirect,
+		limits: SessionLimits {
+			max_subscriptions: cli.max_subscriptions,
+			max_announces: cli.max_announces,
+			max_streams: cli.max_streams,
+			max_bitrate: cli.max_bitrate,
+		},
+		wait: time::Duration::from_millis(cli.subscribe_wait),
 	})?;
 
 	if cli.dev {
@@ -1,3 +1,5 @@
+use std::time;
+
 use futures::{stream::FuturesUnordered, StreamExt};
 use moq_transport::{
 	serve::{ServeError, TracksReader},
@@ -11,14 +13,18 @@ pub struct Producer {
 	remote: Publisher,
 	locals: Locals,
 	remotes: Option<RemotesConsumer>,
+
+	// How long to wait for a publisher to register a namespace before rejecting a subscription.
+	wait: time::Duration,
 }
 
 impl Producer {
-	pub fn new(remote: Publisher, locals: Locals, remotes: Option<RemotesConsumer>) -> Self {
+	pub fn new(remote: Publisher, locals: Locals, remotes: Option<RemotesConsumer>, wait: time::Duration) -> Self {
 		Self {
 			remote,
 			locals,
 			remotes,
+			wait,
 		}
 	}
 
@@ -58,6 +64,13 @@ impl Producer {
 		}
 
 		if let Some(remotes) = &self.remotes {
+			if remotes.redirect {
+				if let Some(url) = remotes.origin(&subscribe.namespace).await? {
+					log::info!("redirecting to origin: {:?} url={}", subscribe.info, url);
+					return Ok(subscribe.redirect(url.to_string())?);
+				}
+			}
+
 			if let Some(remote) = remotes.route(&subscribe.namespace).await? {
 				if let Some(track) = remote.subscribe(subscribe.namespace.clone(), subscribe.name.clone())? {
 					log::info!("serving from remote: {:?} {:?}", remote.info, track.info);
@@ -68,6 +81,14 @@ impl Producer {
 			}
 		}
 
+		// The publisher may not have gone live yet, so hold the subscription before giving up.
+		if let Some(mut local) = self.locals.route_wait(&subscribe.namespace, self.wait).await {
+			if let Some(track) = local.subscribe(&subscribe.name) {
+				log::info!("serving from local after waiting: {:?}", track.info);
+				return Ok(subscribe.serve(track).await?);
+			}
+		}
+
 		Err(ServeError::NotFound.into())
 	}
 }
@@ -1,12 +1,16 @@
-use std::net;
+use std::{net, time};
 
 use anyhow::Context;
 
 use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
 use moq_native::quic;
+use moq_transport::session::{SessionConfig, SessionLimits};
 use url::Url;
 
-use crate::{Api, Consumer, Locals, Producer, Remotes, RemotesConsumer, RemotesProducer, Session};
+use crate::{
+	Api, Cache, CacheConfig, Cluster, ClusterConfig, Consumer, Locals, Producer, Remotes, RemotesConsumer,
+	RemotesProducer, Session,
+};
 
 pub struct RelayConfig {
 	/// Listen on this address
@@ -24,6 +28,21 @@ pub struct RelayConfig {
 	/// Our hostname which we advertise to other origins.
 	/// We use QUIC, so the certificate must be valid for this address.
 	pub node: Option<Url>,
+
+	/// Gossip origins with these other relays.
+	pub cluster: ClusterConfig,
+
+	/// How tracks fetched from other origins are cached.
+	pub cache: CacheConfig,
+
+	/// Redirect subscribers to the origin instead of fetching from it.
+	pub redirect: bool,
+
+	/// Caps the requests and data accepted from each client.
+	pub limits: SessionLimits,
+
+	/// How long to hold a subscription for a broadcast that hasn't been announced yet.
+	pub wait: time::Duration,
 }
 
 pub struct Relay {
@@ -31,7 +50,10 @@ pub struct Relay {
 	announce: Option<Url>,
 	locals: Locals,
 	api: Option<Api>,
+	cluster: Option<Cluster>,
 	remotes: Option<(RemotesProducer, RemotesConsumer)>,
+	limits: SessionLimits,
+	wait: time::Duration,
 }
 
 impl Relay {
@@ -51,10 +73,22 @@ impl Relay {
 
 		let locals = Locals::new();
 
-		let remotes = api.clone().map(|api| {
+		let cluster = match config.cluster.peers.is_empty() {
+			true => None,
+			false => {
+				log::info!("joining cluster: peers={:?}", config.cluster.peers);
+				Some(Cluster::new(config.cluster, locals.clone(), quic.client.clone()))
+			}
+		};
+
+		// We only need to fetch from other origins when we know where they are.
+		let remotes = (api.is_some() || cluster.is_some()).then(|| {
 			Remotes {
-				api,
+				api: api.clone(),
+				cluster: cluster.clone(),
 				quic: quic.client.clone(),
+				cache: Cache::new(config.cache),
+				redirect: config.redirect,
 			}
 			.produce()
 		});
@@ -63,8 +97,11 @@ impl Relay {
 			quic,
 			announce: config.announce,
 			api,
+			cluster,
 			locals,
 			remotes,
+			limits: config.limits,
+			wait: config.wait,
 		})
 	}
 
@@ -76,6 +113,10 @@ impl Relay {
 			consumer
 		});
 
+		if let Some(cluster) = self.cluster {
+			tasks.push(async move { cluster.run().await.context("cluster failed") }.boxed());
+		}
+
 		let forward = if let Some(url) = &self.announce {
 			log::info!("forwarding announces to {}", url);
 			let session = self
@@ -91,7 +132,12 @@ impl Relay {
 			// Create a normal looking session, except we never forward or register announces.
 			let session = Session {
 				session,
-				producer: Some(Producer::new(publisher, self.locals.clone(), remotes.clone())),
+				producer: Some(Producer::new(
+					publisher,
+					self.locals.clone(),
+					remotes.clone(),
+					self.wait,
+				)),
 				consumer: Some(Consumer::new(subscriber, self.locals.clone(), None, None)),
 			};
 
@@ -116,9 +162,11 @@ impl Relay {
 					let remotes = remotes.clone();
 					let forward = forward.clone();
 					let api = self.api.clone();
+					let wait = self.wait;
+					let config = SessionConfig::default().with_limits(self.limits.clone());
 
 					tasks.push(async move {
-						let (session, publisher, subscriber) = match moq_transport::session::Session::accept(conn).await {
+						let (session, publisher, subscriber) = match moq_transport::session::Session::accept_with(conn, config).await {
 							Ok(session) => session,
 							Err(err) => {
 								log::warn!("failed to accept MoQ session: {}", err);
@@ -126,9 +174,11 @@ impl Relay {
 							}
 						};
 
+						log::debug!("accepted MoQ session: role={:?}", session.role());
+
 						let session = Session {
 							session,
-							producer: publisher.map(|publisher| Producer::new(publisher, locals.clone(), remotes)),
+							producer: publisher.map(|publisher| Producer::new(publisher, locals.clone(), remotes, wait)),
 							consumer: subscriber.map(|subscriber| Consumer::new(subscriber, locals, api, forward)),
 						};
 
@@ -4,24 +4,35 @@ use std::collections::VecDeque;
 use std::fmt;
 use std::ops;
 use std::sync::Arc;
-use std::sync::Weak;
 
 use futures::stream::FuturesUnordered;
 use futures::FutureExt;
 use futures::StreamExt;
 use moq_native::quic;
-use moq_transport::serve::{Track, TrackReader, TrackWriter};
+use moq_transport::serve::{ServeError, TrackWriter};
 use moq_transport::watch::State;
 use url::Url;
 
-use crate::Api;
+use crate::{Api, Cache, CachedTrack, Cluster};
+
+// The number of redirects followed for each track before giving up.
+const MAX_REDIRECTS: usize = 4;
 
 pub struct Remotes {
 	/// The client we use to fetch/store origin information.
-	pub api: Api,
+	pub api: Option<Api>,
+
+	/// The cluster, which gossips origin information between relays.
+	pub cluster: Option<Cluster>,
 
 	// A QUIC endpoint we'll use to fetch from other origins.
 	pub quic: quic::Client,
+
+	// Shares tracks fetched from other origins between subscribers.
+	pub cache: Cache,
+
+	/// Redirect subscribers to the origin, instead of fetching on their behalf.
+	pub redirect: bool,
 }
 
 impl Remotes {
@@ -120,14 +131,13 @@ impl RemotesConsumer {
 	}
 
 	pub async fn route(&self, namespace: &str) -> anyhow::Result<Option<RemoteConsumer>> {
-		// Always fetch the origin instead of using the (potentially invalid) cache.
-		let origin = match self.api.get_origin(namespace).await? {
+		let url = match self.origin(namespace).await? {
 			None => return Ok(None),
-			Some(origin) => origin,
+			Some(url) => url,
 		};
 
 		let state = self.state.lock();
-		if let Some(remote) = state.lookup.get(&origin.url).cloned() {
+		if let Some(remote) = state.lookup.get(&url).cloned() {
 			return Ok(Some(remote));
 		}
 
@@ -137,17 +147,30 @@ impl RemotesConsumer {
 		};
 
 		let remote = Remote {
-			url: origin.url.clone(),
+			url: url.clone(),
 			remotes: self.info.clone(),
 		};
 
 		let (writer, reader) = remote.produce();
 		state.requested.push_back(writer);
 
-		state.lookup.insert(origin.url, reader.clone());
+		state.lookup.insert(url, reader.clone());
 
 		Ok(Some(reader))
 	}
+
+	/// Find the URL of the origin, preferring the cluster since it's already known locally.
+	pub async fn origin(&self, namespace: &str) -> anyhow::Result<Option<Url>> {
+		if let Some(url) = self.cluster.as_ref().and_then(|cluster| cluster.route(namespace)) {
+			return Ok(Some(url));
+		}
+
+		// Always fetch the origin instead of using the (potentially invalid) cache.
+		match &self.api {
+			Some(api) => Ok(api.get_origin(namespace).await?.map(|origin| origin.url)),
+			None => Ok(None),
+		}
+	}
 }
 
 impl ops::Deref for RemotesConsumer {
@@ -192,7 +215,6 @@ impl Remote {
 
 #[derive(Default)]
 struct RemoteState {
-	tracks: HashMap<(String, String), RemoteTrackWeak>,
 	requested: VecDeque<TrackWriter>,
 }
 
@@ -209,7 +231,21 @@ impl RemoteProducer {
 	pub async fn run(&mut self) -> anyhow::Result<()> {
 		// TODO reuse QUIC and MoQ sessions
 		let session = self.quic.connect(&self.url).await?;
-		let (session, subscriber) = moq_transport::session::Subscriber::connect(session).await?;
+		let (session, mut subscriber) = moq_transport::session::Subscriber::connect(session).await?;
+
+		// The origin may be a relay that redirects us elsewhere.
+		let quic = self.quic.clone();
+		subscriber.follow_redirects(MAX_REDIRECTS, move |url| {
+			let quic = quic.clone();
+			async move {
+				let url = Url::parse(&url).map_err(|err| ServeError::Internal(err.to_string()))?;
+				let session = quic
+					.connect(&url)
+					.await
+					.map_err(|err| ServeError::Internal(err.to_string()))?;
+				Ok(session)
+			}
+		});
 
 		// Run the session
 		let mut session = session.run().boxed();
@@ -284,29 +320,21 @@ impl RemoteConsumer {
 		Self { info, state }
 	}
 
-	/// Request a track from the broadcast.
-	pub fn subscribe(&self, namespace: String, name: String) -> anyhow::Result<Option<RemoteTrackReader>> {
-		let key = (namespace.clone(), name.clone());
-		let state = self.state.lock();
-		if let Some(track) = state.tracks.get(&key) {
-			if let Some(track) = track.upgrade() {
-				return Ok(Some(track));
-			}
-		}
-
-		let mut state = match state.into_mut() {
-			Some(state) => state,
-			None => return Ok(None),
-		};
+	/// Request a track from the broadcast, sharing a single upstream subscription through the cache.
+	pub fn subscribe(&self, namespace: String, name: String) -> anyhow::Result<Option<CachedTrack>> {
+		let (track, writer) = self.cache.subscribe(&namespace, &name);
 
-		let (writer, reader) = Track::new(namespace, name).produce();
-		let reader = RemoteTrackReader::new(reader, self.state.clone());
+		if let Some(writer) = writer {
+			// NOTE: The writer is dropped if the remote is gone, which evicts the track from the cache.
+			let mut state = match self.state.lock_mut() {
+				Some(state) => state,
+				None => return Ok(None),
+			};
 
-		// Insert the track into our Map so we deduplicate future requests.
-		state.tracks.insert(key, reader.downgrade());
-		state.requested.push_back(writer);
+			state.requested.push_back(writer);
+		}
 
-		Ok(Some(reader))
+		Ok(Some(track))
 	}
 }
 
@@ -317,68 +345,3 @@ impl ops::Deref for RemoteConsumer {
 		&self.info
 	}
 }
-
-#[derive(Clone)]
-pub struct RemoteTrackReader {
-	pub reader: TrackReader,
-	drop: Arc<RemoteTrackDrop>,
-}
-
-impl RemoteTrackReader {
-	fn new(reader: TrackReader, parent: State<RemoteState>) -> Self {
-		let drop = Arc::new(RemoteTrackDrop {
-			parent,
-			key: (reader.namespace.clone(), reader.name.clone()),
-		});
-
-		Self { reader, drop }
-	}
-
-	fn downgrade(&self) -> RemoteTrackWeak {
-		RemoteTrackWeak {
-			reader: self.reader.clone(),
-			drop: Arc::downgrade(&self.drop),
-		}
-	}
-}
-
-impl ops::Deref for RemoteTrackReader {
-	type Target = TrackReader;
-
-	fn deref(&self) -> &Self::Target {
-		&self.reader
-	}
-}
-
-impl ops::DerefMut for RemoteTrackReader {
-	fn deref_mut(&mut self) -> &mut Self::Target {
-		&mut self.reader
-	}
-}
-
-struct RemoteTrackWeak {
-	reader: TrackReader,
-	drop: Weak<RemoteTrackDrop>,
-}
-
-impl RemoteTrackWeak {
-	fn upgrade(&self) -> Option<RemoteTrackReader> {
-		Some(RemoteTrackReader {
-			reader: self.reader.clone(),
-			drop: self.drop.upgrade()?,
-		})
-	}
-}
-
-struct RemoteTrackDrop {
-	parent: State<RemoteState>,
-	key: (String, String),
-}
-
-impl Drop for RemoteTrackDrop {
-	fn drop(&mut self) {
-		if let Some(mut parent) = self.parent.lock_mut() {
-			parent.tracks.remove(&self.key);
-		}
-	}
-}
@@ -1,7 +1,8 @@
 use std::{net, sync::Arc};
 
 use axum::{extract::State, http::Method, response::IntoResponse, routing::get, Router};
-use hyper_serve::tls_rustls::RustlsAcceptor;
+use axum_server::tls_rustls::RustlsConfig;
+use moq_native::tls::Fingerprints;
 use tower_http::cors::{Any, CorsLayer};
 
 pub struct WebConfig {
@@ -13,35 +14,49 @@ pub struct WebConfig {
 // TODO remove this when Chrome adds support for self-signed certificates using WebTransport
 pub struct Web {
 	app: Router,
-	server: hyper_serve::Server<RustlsAcceptor>,
+	bind: net::SocketAddr,
+
+	// A browser won't trust a self-signed certificate over HTTPS, so it's served over HTTP instead.
+	tls: Option<RustlsConfig>,
 }
 
 impl Web {
 	pub fn new(config: WebConfig) -> Self {
-		// Get the first certificate's fingerprint.
-		// TODO serve all of them so we can support multiple signature algorithms.
-		let fingerprint = config.tls.fingerprints.first().expect("missing certificate").clone();
-
-		let mut tls = config.tls.server.expect("missing server configuration");
-		tls.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
-		let tls = hyper_serve::tls_rustls::RustlsConfig::from_config(Arc::new(tls));
+		let tls = match config.tls.self_signed() {
+			true => None,
+			false => {
+				let mut tls = config.tls.server.expect("missing server configuration");
+				tls.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+				Some(RustlsConfig::from_config(Arc::new(tls)))
+			}
+		};
 
 		let app = Router::new()
 			.route("/fingerprint", get(serve_fingerprint))
 			.layer(CorsLayer::new().allow_origin(Any).allow_methods([Method::GET]))
-			.with_state(fingerprint);
-
-		let server = hyper_serve::bind_rustls(config.bind, tls);
+			.with_state(config.tls.fingerprints);
 
-		Self { app, server }
+		Self {
+			app,
+			bind: config.bind,
+			tls,
+		}
 	}
 
 	pub async fn run(self) -> anyhow::Result<()> {
-		self.server.serve(self.app.into_make_service()).await?;
+		let app = self.app.into_make_service();
+
+		match self.tls {
+			Some(tls) => axum_server::bind_rustls(self.bind, tls).serve(app).await?,
+			None => axum_server::bind(self.bind).serve(app).await?,
+		}
+
 		Ok(())
 	}
 }
 
-async fn serve_fingerprint(State(fingerprint): State<String>) -> impl IntoResponse {
-	fingerprint
+// Fetched again before each connection, since a self-signed certificate is rotated.
+// TODO serve all of them so we can support multiple signature algorithms.
+async fn serve_fingerprint(State(fingerprints): State<Fingerprints>) -> impl IntoResponse {
+	fingerprints.get().into_iter().next().expect("missing certificate")
 }
@@ -0,0 +1,383 @@
+use std::{future::Future, pin::Pin, time::Duration};
+
+use anyhow::Context;
+use log::{debug, info};
+use moq_catalog::Track;
+use moq_transport::serve::{self, GroupReader, GroupsReader, TrackReaderMode};
+use moq_transport::session::{SessionStats, SessionStatsReader, Subscribe, Subscriber};
+use tokio::sync::watch;
+
+/// What an [AbrAlgorithm] knows when choosing a rendition.
+pub struct AbrInput<'a> {
+	/// The renditions, ordered by ascending bitrate.
+	pub renditions: &'a [Track],
+
+	/// The index of the rendition currently playing, if any.
+	pub current: Option<usize>,
+
+	/// The smoothed throughput of the session in bits per second.
+	pub bandwidth: u64,
+
+	/// The latest statistics for the session.
+	pub stats: &'a SessionStats,
+}
+
+/// Chooses which rendition to play each time the bandwidth estimate is updated.
+pub trait AbrAlgorithm: Send {
+	/// Return the index of the rendition to play, or None to keep the current one.
+	fn choose(&mut self, input: &AbrInput) -> Option<usize>;
+}
+
+/// The default [AbrAlgorithm], switching down as soon as the throughput drops and up once it's stable.
+///
+/// The throughput is bounded by the rendition being played, so switching up is a probe:
+/// after `upswitch` consecutive estimates where the current rendition fits, try the next one.
+pub struct ThroughputRule {
+	/// The fraction of the bandwidth a rendition may use.
+	pub safety: f64,
+
+	/// The number of consecutive estimates before switching up.
+	pub upswitch: u32,
+
+	stable: u32,
+}
+
+impl Default for ThroughputRule {
+	fn default() -> Self {
+		Self {
+			safety: 0.8,
+			upswitch: 5,
+			stable: 0,
+		}
+	}
+}
+
+impl AbrAlgorithm for ThroughputRule {
+	fn choose(&mut self, input: &AbrInput) -> Option<usize> {
+		let bitrate = |index: usize| input.renditions[index].selection_params.bitrate.unwrap_or(0) as f64;
+		let budget = input.bandwidth as f64 * self.safety;
+
+		let current = match input.current {
+			Some(current) => current,
+			// Start with the best rendition that fits, or the lowest if none do.
+			None => {
+				let fits = (0..input.renditions.len())
+					.rev()
+					.find(|&index| bitrate(index) <= budget);
+				return Some(fits.unwrap_or(0));
+			}
+		};
+
+		// The current rendition no longer fits, so switch down immediately.
+		if bitrate(current) > budget {
+			self.stable = 0;
+			let fits = (0..current).rev().find(|&index| bitrate(index) <= budget);
+			return Some(fits.unwrap_or(0));
+		}
+
+		self.stable += 1;
+		if self.stable >= self.upswitch && current + 1 < input.renditions.len() {
+			self.stable = 0;
+			return Some(current + 1);
+		}
+
+		None
+	}
+}
+
+// The groups of a subscribed rendition, unsubscribed when dropped.
+struct Rendition {
+	index: usize,
+	groups: GroupsReader,
+	_subscribe: Subscribe,
+}
+
+type Pending = Pin<Box<dyn Future<Output = anyhow::Result<Rendition>> + Send>>;
+
+/// Switches between renditions of a video track to match the available throughput.
+///
+/// The bandwidth is estimated from the session's statistics, and each estimate is given to the [AbrAlgorithm].
+/// A switch subscribes to the new rendition and keeps playing the old one until the new one delivers a group.
+/// Each group starts with a keyframe, so playback switches cleanly at a group boundary,
+/// provided the renditions share an init segment and number their groups alike.
+pub struct Abr {
+	subscriber: Subscriber,
+	namespace: String,
+	renditions: Vec<Track>,
+	algorithm: Box<dyn AbrAlgorithm>,
+	stats: watch::Receiver<SessionStats>,
+
+	// The smoothed throughput in bits per second, once estimated.
+	bandwidth: Option<u64>,
+
+	current: Option<Rendition>,
+	pending: Option<(usize, Pending)>,
+
+	// The latest group returned, so a rendition doesn't repeat a group after switching.
+	latest: Option<u64>,
+}
+
+impl Abr {
+	/// The interval between bandwidth estimates.
+	pub const INTERVAL: Duration = Duration::from_millis(500);
+
+	/// Play the renditions of the broadcast, which are sorted by bitrate.
+	pub fn new(
+		subscriber: Subscriber,
+		namespace: &str,
+		mut renditions: Vec<Track>,
+		stats: &SessionStatsReader,
+	) -> Self {
+		renditions.sort_by_key(|track| track.selection_params.bitrate.unwrap_or(0));
+
+		Self {
+			subscriber,
+			namespace: namespace.to_string(),
+			renditions,
+			algorithm: Box::<ThroughputRule>::default(),
+			stats: stats.watch(Self::INTERVAL),
+			bandwidth: None,
+			current: None,
+			pending: None,
+			latest: None,
+		}
+	}
+
+	/// Choose renditions with a custom algorithm instead of [ThroughputRule].
+	pub fn with_algorithm<A: AbrAlgorithm + 'static>(mut self, algorithm: A) -> Self {
+		self.algorithm = Box::new(algorithm);
+		self
+	}
+
+	/// Returns the rendition currently playing.
+	pub fn current(&self) -> Option<&Track> {
+		self.current.as_ref().map(|current| &self.renditions[current.index])
+	}
+
+	/// Returns the next group to play, from whichever rendition is current.
+	pub async fn next(&mut self) -> anyhow::Result<Option<GroupReader>> {
+		anyhow::ensure!(!self.renditions.is_empty(), "no renditions");
+
+		if self.current.is_none() && self.pending.is_none() {
+			// Start with the lowest rendition until the bandwidth is known.
+			self.switch(0);
+		}
+
+		loop {
+			let (switching, playing) = (self.pending.is_some(), self.current.is_some());
+			let pending = self.pending.as_mut().map(|pending| &mut pending.1);
+			let current = self.current.as_mut().map(|current| &mut current.groups);
+
+			tokio::select! {
+				Ok(()) = self.stats.changed() => self.estimate(),
+				res = async { pending.unwrap().await }, if switching => {
+					self.pending = None;
+					if let Some(group) = self.start(res?).await? {
+						return Ok(Some(group));
+					}
+				},
+				res = async { current.unwrap().next().await }, if playing => {
+					let group = res?;
+					if let Some(group) = &group {
+						self.latest = Some(self.latest.map_or(group.group_id, |latest| latest.max(group.group_id)));
+					}
+					return Ok(group);
+				},
+			}
+		}
+	}
+
+	// Replace the current rendition, skipping any groups that were already played.
+	async fn start(&mut self, mut rendition: Rendition) -> anyhow::Result<Option<GroupReader>> {
+		let group = loop {
+			match rendition.groups.next().await? {
+				Some(group) if self.latest.is_some_and(|latest| group.group_id <= latest) => continue,
+				group => break group,
+			}
+		};
+
+		info!("switched to rendition: {}", self.renditions[rendition.index].name);
+		self.current = Some(rendition);
+
+		if let Some(group) = &group {
+			self.latest = Some(group.group_id);
+		}
+
+		Ok(group)
+	}
+
+	// Update the bandwidth estimate and ask the algorithm which rendition to play.
+	fn estimate(&mut self) {
+		let stats = self.stats.borrow_and_update().clone();
+		let Some(bitrate) = stats.recv_bitrate else {
+			return;
+		};
+
+		// An exponentially weighted moving average, so a single slow interval doesn't cause a switch.
+		let sample = bitrate * 8;
+		let bandwidth = match self.bandwidth {
+			Some(bandwidth) => (bandwidth * 7 + sample * 3) / 10,
+			None => sample,
+		};
+		self.bandwidth = Some(bandwidth);
+
+		// Treat a switch in progress as current, so it isn't restarted by the next estimate.
+		let current = self.pending.as_ref().map(|pending| pending.0);
+		let current = current.or(self.current.as_ref().map(|current| current.index));
+
+		let input = AbrInput {
+			renditions: &self.renditions,
+			current,
+			bandwidth,
+			stats: &stats,
+		};
+
+		if let Some(index) = self.algorithm.choose(&input) {
+			if Some(index) != current && index < self.renditions.len() {
+				debug!("switching rendition: bandwidth={} index={}", bandwidth, index);
+				self.switch(index);
+			}
+		}
+	}
+
+	// Subscribe to the rendition, replacing any switch in progress.
+	fn switch(&mut self, index: usize) {
+		if self.current.as_ref().is_some_and(|current| current.index == index) {
+			self.pending = None;
+			return;
+		}
+
+		let name = self.renditions[index].name.clone();
+		let (writer, reader) = serve::Track::new(self.namespace.clone(), name).produce();
+		let subscribe = self.subscriber.subscribe_handle(writer);
+
+		let pending = async move {
+			let groups = match reader.mode().await? {
+				TrackReaderMode::Groups(groups) => groups,
+				_ => anyhow::bail!("expected groups"),
+			};
+
+			Ok(Rendition {
+				index,
+				groups,
+				_subscribe: subscribe,
+			})
+		};
+
+		self.pending = Some((index, Box::pin(pending)));
+	}
+
+	/// Returns the smoothed throughput in bits per second, once estimated.
+	pub fn bandwidth(&self) -> Option<u64> {
+		self.bandwidth
+	}
+
+	/// Returns the renditions that can replace the track: the same kind of codec in the same alternate group.
+	pub fn alternates(tracks: &[Track], track: &Track) -> anyhow::Result<Vec<Track>> {
+		let is_video = track
+			.selection_params
+			.codec
+			.as_ref()
+			.context("missing codec")?
+			.is_video();
+
+		let alternates = tracks.iter().filter(|other| {
+			other.alt_group.is_some()
+				&& other.alt_group == track.alt_group
+				&& other.init_track == track.init_track
+				&& other
+					.selection_params
+					.codec
+					.as_ref()
+					.is_some_and(|codec| codec.is_video() == is_video)
+		});
+
+		let mut alternates: Vec<_> = alternates.cloned().collect();
+		if alternates.is_empty() {
+			alternates.push(track.clone());
+		}
+
+		Ok(alternates)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use moq_catalog::SelectionParam;
+
+	fn renditions(bitrates: &[u32]) -> Vec<Track> {
+		bitrates
+			.iter()
+			.map(|&bitrate| Track {
+				name: format!("{bitrate}"),
+				selection_params: SelectionParam {
+					bitrate: Some(bitrate),
+					..Default::default()
+				},
+				..Default::default()
+			})
+			.collect()
+	}
+
+	fn choose(
+		rule: &mut ThroughputRule,
+		renditions: &[Track],
+		current: Option<usize>,
+		bandwidth: u64,
+	) -> Option<usize> {
+		rule.choose(&AbrInput {
+			renditions,
+			current,
+			bandwidth,
+			stats: &SessionStats::default(),
+		})
+	}
+
+	#[test]
+	fn start() {
+		let renditions = renditions(&[1_000, 2_000, 4_000]);
+		let mut rule = ThroughputRule::default();
+
+		// The best rendition within 80% of the bandwidth, or the lowest if none fit.
+		assert_eq!(choose(&mut rule, &renditions, None, 3_000), Some(1));
+		assert_eq!(choose(&mut rule, &renditions, None, 10_000), Some(2));
+		assert_eq!(choose(&mut rule, &renditions, None, 100), Some(0));
+	}
+
+	#[test]
+	fn switch_down() {
+		let renditions = renditions(&[1_000, 2_000, 4_000]);
+		let mut rule = ThroughputRule::default();
+
+		// The current rendition still fits, so keep it.
+		assert_eq!(choose(&mut rule, &renditions, Some(2), 5_000), None);
+
+		// Switch straight to the best rendition that fits, skipping any in between.
+		assert_eq!(choose(&mut rule, &renditions, Some(2), 1_500), Some(0));
+	}
+
+	#[test]
+	fn switch_up() {
+		let renditions = renditions(&[1_000, 2_000, 4_000]);
+		let mut rule = ThroughputRule {
+			upswitch: 3,
+			..Default::default()
+		};
+
+		// Only probe the next rendition after enough consecutive estimates.
+		assert_eq!(choose(&mut rule, &renditions, Some(0), 10_000), None);
+		assert_eq!(choose(&mut rule, &renditions, Some(0), 10_000), None);
+		assert_eq!(choose(&mut rule, &renditions, Some(0), 10_000), Some(1));
+
+		// A drop in bandwidth restarts the count.
+		assert_eq!(choose(&mut rule, &renditions, Some(1), 10_000), None);
+		assert_eq!(choose(&mut rule, &renditions, Some(1), 2_000), Some(0));
+		assert_eq!(choose(&mut rule, &renditions, Some(0), 10_000), None);
+
+		// There's nothing above the highest rendition.
+		for _ in 0..3 {
+			assert_eq!(choose(&mut rule, &renditions, Some(2), 10_000), None);
+		}
+	}
+}
@@ -0,0 +1,274 @@
+use std::{
+	cmp::{Ordering, Reverse},
+	collections::BinaryHeap,
+	time::Duration,
+};
+
+use bytes::Bytes;
+use log::{debug, warn};
+use moq_transport::serve::{GroupReader, TrackReader, TrackReaderMode};
+use tokio::{sync::mpsc, time::Instant};
+
+/// A frame released by the [JitterBuffer] at its playout time.
+#[derive(Debug, Clone)]
+pub struct Frame {
+	pub group_id: u64,
+	pub object_id: u64,
+
+	// The presentation timestamp from the object metadata, in the buffer's timescale.
+	pub timestamp: u64,
+
+	pub payload: Bytes,
+}
+
+// Orders the pending frames by timestamp, breaking ties by their position in the track.
+struct Queued(Frame);
+
+impl Queued {
+	fn key(&self) -> (u64, u64, u64) {
+		(self.0.timestamp, self.0.group_id, self.0.object_id)
+	}
+}
+
+impl PartialEq for Queued {
+	fn eq(&self, other: &Self) -> bool {
+		self.key() == other.key()
+	}
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Queued {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.key().cmp(&other.key())
+	}
+}
+
+/// Reorders the frames of a track by timestamp and releases them at a steady pace.
+///
+/// Each frame is released `latency` after its timestamp, relative to the first frame received.
+/// Frames that arrive after a later frame was already released are dropped.
+/// Objects without a timestamp use the timestamp of the previous object in the group.
+pub struct JitterBuffer {
+	frames: mpsc::UnboundedReceiver<Frame>,
+	closed: bool,
+
+	pending: BinaryHeap<Reverse<Queued>>,
+
+	latency: Duration,
+
+	// The number of timestamp units per second.
+	timescale: u64,
+
+	// The time the first frame was received, and its timestamp.
+	clock: Option<(Instant, u64)>,
+
+	// The timestamp of the last frame released.
+	released: Option<u64>,
+
+	dropped: u64,
+}
+
+impl JitterBuffer {
+	/// Buffer the track with the given target latency.
+	///
+	/// The track is read by a background task, so this must be called within a tokio runtime.
+	pub fn new(track: TrackReader, latency: Duration, timescale: u64) -> Self {
+		let (sender, frames) = mpsc::unbounded_channel();
+
+		tokio::spawn(async move {
+			let name = track.name.clone();
+			if let Err(err) = Self::run_track(track, sender).await {
+				warn!("failed to buffer track {name}: {err:?}");
+			}
+		});
+
+		Self {
+			frames,
+			closed: false,
+			pending: BinaryHeap::new(),
+			latency,
+			timescale,
+			clock: None,
+			released: None,
+			dropped: 0,
+		}
+	}
+
+	/// Returns the next frame once it's due, or None when the track has ended and every frame was released.
+	pub async fn next(&mut self) -> Option<Frame> {
+		loop {
+			let deadline = self
+				.pending
+				.peek()
+				.map(|Reverse(queued)| self.deadline(queued.0.timestamp));
+
+			tokio::select! {
+				frame = self.frames.recv(), if !self.closed => match frame {
+					Some(frame) => self.insert(frame),
+					None => self.closed = true,
+				},
+				_ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+					let Reverse(Queued(frame)) = self.pending.pop()?;
+					self.released = Some(frame.timestamp);
+					return Some(frame);
+				},
+				else => return None,
+			}
+		}
+	}
+
+	/// The number of frames dropped because they arrived too late.
+	pub fn dropped(&self) -> u64 {
+		self.dropped
+	}
+
+	fn insert(&mut self, frame: Frame) {
+		// Start the clock with the first frame received.
+		self.clock.get_or_insert_with(|| (Instant::now(), frame.timestamp));
+
+		if self.released.is_some_and(|released| frame.timestamp < released) {
+			debug!(
+				"dropping late frame: group={} object={} timestamp={}",
+				frame.group_id, frame.object_id, frame.timestamp
+			);
+			self.dropped += 1;
+			return;
+		}
+
+		self.pending.push(Reverse(Queued(frame)));
+	}
+
+	// Returns when a frame with the given timestamp should be released.
+	fn deadline(&self, timestamp: u64) -> Instant {
+		let (start, base) = self.clock.expect("clock starts with the first frame");
+
+		// Frames older than the first frame are released as soon as possible.
+		let elapsed = timestamp.saturating_sub(base) as u128 * 1_000_000_000 / self.timescale.max(1) as u128;
+
+		start + self.latency + Duration::from_nanos(elapsed.try_into().unwrap_or(u64::MAX))
+	}
+
+	async fn run_track(track: TrackReader, sender: mpsc::UnboundedSender<Frame>) -> anyhow::Result<()> {
+		let mut groups = match track.mode().await? {
+			TrackReaderMode::Groups(groups) => groups,
+			_ => anyhow::bail!("expected groups"),
+		};
+
+		loop {
+			tokio::select! {
+				group = groups.next() => match group? {
+					Some(group) => {
+						let sender = sender.clone();
+						tokio::spawn(async move {
+							if let Err(err) = Self::run_group(group, sender).await {
+								warn!("failed to buffer group: {err:?}");
+							}
+						});
+					}
+					None => return Ok(()),
+				},
+				// Stop reading once the buffer is dropped.
+				_ = sender.closed() => return Ok(()),
+			}
+		}
+	}
+
+	async fn run_group(mut group: GroupReader, sender: mpsc::UnboundedSender<Frame>) -> anyhow::Result<()> {
+		let mut timestamp = 0;
+
+		while let Some(mut object) = group.next().await? {
+			if let Some(meta) = &object.meta {
+				timestamp = meta.timestamp;
+			}
+
+			let frame = Frame {
+				group_id: group.group_id,
+				object_id: object.object_id,
+				timestamp,
+				payload: object.read_all().await?,
+			};
+
+			if sender.send(frame).is_err() {
+				break;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use moq_transport::{data::ObjectMeta, serve};
+
+	fn meta(timestamp: u64) -> ObjectMeta {
+		ObjectMeta {
+			timestamp,
+			..Default::default()
+		}
+	}
+
+	#[tokio::test]
+	async fn reorder() {
+		let (writer, reader) = serve::Track::new("test".to_string(), "video".to_string())
+			.order(serve::GroupOrder::Ascending)
+			.cache(2)
+			.produce();
+		let mut groups = writer.groups().unwrap();
+		let mut buffer = JitterBuffer::new(reader, Duration::from_millis(100), 1000);
+
+		let mut early = groups.append(0).unwrap();
+		let mut late = groups.append(0).unwrap();
+
+		// The later frame arrives first, but each frame is released in timestamp order.
+		late.write_with_meta(Bytes::from_static(b"two"), meta(20)).unwrap();
+		tokio::time::sleep(Duration::from_millis(10)).await;
+		early.write_with_meta(Bytes::from_static(b"one"), meta(10)).unwrap();
+		drop((early, late, groups));
+
+		let frame = buffer.next().await.unwrap();
+		assert_eq!((frame.group_id, frame.timestamp), (0, 10));
+		assert_eq!(frame.payload, Bytes::from_static(b"one"));
+
+		let frame = buffer.next().await.unwrap();
+		assert_eq!((frame.group_id, frame.timestamp), (1, 20));
+
+		assert!(buffer.next().await.is_none());
+		assert_eq!(buffer.dropped(), 0);
+	}
+
+	#[tokio::test]
+	async fn late() {
+		let (writer, reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+		let mut groups = writer.groups().unwrap();
+		let mut buffer = JitterBuffer::new(reader, Duration::from_millis(10), 1000);
+
+		groups
+			.append(0)
+			.unwrap()
+			.write_with_meta(Bytes::from_static(b"one"), meta(20))
+			.unwrap();
+		assert_eq!(buffer.next().await.unwrap().timestamp, 20);
+
+		// A frame older than one already released is dropped, while later frames continue.
+		let mut group = groups.append(0).unwrap();
+		group.write_with_meta(Bytes::from_static(b"late"), meta(15)).unwrap();
+		group.write_with_meta(Bytes::from_static(b"two"), meta(30)).unwrap();
+		drop((group, groups));
+
+		let frame = buffer.next().await.unwrap();
+		assert_eq!(frame.timestamp, 30);
+		assert_eq!(frame.payload, Bytes::from_static(b"two"));
+
+		assert!(buffer.next().await.is_none());
+		assert_eq!(buffer.dropped(), 1);
+	}
+}
@@ -1 +1,3 @@
+pub mod abr;
+pub mod jitter;
 pub mod media;
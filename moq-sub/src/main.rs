@@ -34,6 +34,9 @@ async fn main() -> anyhow::Result<()> {
 	let tracks = Tracks::new(config.name);
 
 	let mut media = Media::new(subscriber, tracks, out).await?;
+	if config.abr {
+		media = media.with_abr(session.stats());
+	}
 
 	tokio::select! {
 		res = session.run() => res.context("session error")?,
@@ -57,6 +60,10 @@ pub struct Config {
 	#[arg(long)]
 	pub name: String,
 
+	/// Switch between video renditions to match the available throughput.
+	#[arg(long)]
+	pub abr: bool,
+
 	/// The TLS configuration.
 	#[command(flatten)]
 	pub tls: moq_native::tls::Args,
@@ -1,14 +1,16 @@
-use std::{io::Cursor, sync::Arc};
+use std::sync::Arc;
 
 use anyhow::Context;
 use log::{debug, info, trace, warn};
+use moq_catalog::Codec;
+
+use crate::abr::Abr;
 use moq_transport::serve::{
-	GroupObjectReader, GroupReader, TrackReader, TrackReaderMode, Tracks, TracksReader, TracksWriter,
+	GroupObjectReader, GroupReader, GroupsReader, TrackReader, TrackReaderMode, Tracks, TracksReader, TracksWriter,
 };
-use moq_transport::session::Subscriber;
-use mp4::ReadBox;
+use moq_transport::session::{SessionStatsReader, Subscriber};
 use tokio::{
-	io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
+	io::{AsyncWrite, AsyncWriteExt},
 	sync::Mutex,
 	task::JoinSet,
 };
@@ -18,6 +20,39 @@ pub struct Media<O> {
 	broadcast: TracksReader,
 	tracks_writer: TracksWriter,
 	output: Arc<Mutex<O>>,
+
+	// Switch between video renditions based on the session's throughput, see [Self::with_abr].
+	abr: Option<SessionStatsReader>,
+}
+
+// A track to play, or the renditions of a track chosen by ABR.
+enum Source {
+	Track(TrackReader),
+	Abr(Box<Abr>),
+}
+
+enum Groups {
+	Track(GroupsReader),
+	Abr(Box<Abr>),
+}
+
+impl Groups {
+	async fn new(source: Source) -> anyhow::Result<Self> {
+		match source {
+			Source::Track(track) => match track.mode().await? {
+				TrackReaderMode::Groups(groups) => Ok(Self::Track(groups)),
+				_ => anyhow::bail!("expected groups"),
+			},
+			Source::Abr(abr) => Ok(Self::Abr(abr)),
+		}
+	}
+
+	async fn next(&mut self) -> anyhow::Result<Option<GroupReader>> {
+		match self {
+			Self::Track(groups) => Ok(groups.next().await?),
+			Self::Abr(abr) => abr.next().await,
+		}
+	}
 }
 
 impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
@@ -29,85 +64,81 @@ impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
 			broadcast,
 			tracks_writer,
 			output: Arc::new(Mutex::new(output)),
+			abr: None,
 		})
 	}
 
-	pub async fn run(&mut self) -> anyhow::Result<()> {
-		let moov = {
-			let init_track_name = "0.mp4";
-			let track = self
-				.tracks_writer
-				.create(init_track_name)
-				.context("failed to create init track")?;
-
-			let mut subscriber = self.subscriber.clone();
-			tokio::task::spawn(async move {
-				subscriber.subscribe(track).await.unwrap_or_else(|err| {
-					warn!("failed to subscribe to init track: {err:?}");
-				});
-			});
-
-			let track = self.broadcast.subscribe(init_track_name).context("no init track")?;
-			let mut group = match track.mode().await? {
-				TrackReaderMode::Groups(mut groups) => groups.next().await?.context("no init group")?,
-				_ => anyhow::bail!("expected init segment"),
-			};
-
-			let object = group.next().await?.context("no init fragment")?;
-			let buf = Self::recv_object(object).await?;
-			self.output.lock().await.write_all(&buf).await?;
-			let mut reader = Cursor::new(&buf);
-
-			let ftyp = read_atom(&mut reader).await?;
-			anyhow::ensure!(&ftyp[4..8] == b"ftyp", "expected ftyp atom");
+	/// Switch between the video renditions in the catalog's alternate group, based on the session's throughput.
+	pub fn with_abr(mut self, stats: SessionStatsReader) -> Self {
+		self.abr = Some(stats);
+		self
+	}
 
-			let moov = read_atom(&mut reader).await?;
-			anyhow::ensure!(&moov[4..8] == b"moov", "expected moov atom");
-			let mut moov_reader = Cursor::new(&moov);
-			let moov_header = mp4::BoxHeader::read(&mut moov_reader)?;
+	pub async fn run(&mut self) -> anyhow::Result<()> {
+		let catalog = self.subscribe(".catalog")?;
+		let catalog = match catalog.mode().await? {
+			// NOTE: The publisher may keep the group open for updates, so only wait for the first catalog.
+			TrackReaderMode::Groups(groups) => moq_catalog::Reader::new(groups)
+				.updates()
+				.next()
+				.await?
+				.context("no catalog")?,
+			_ => anyhow::bail!("expected catalog groups"),
+		};
 
-			mp4::MoovBox::read_box(&mut moov_reader, moov_header.size)?
+		// Play the first video and audio track we understand.
+		let find = |kind: fn(&Codec) -> bool| {
+			catalog
+				.tracks
+				.iter()
+				.find(move |track| track.selection_params.codec.as_ref().is_some_and(kind))
+		};
+		let (video, audio) = (find(Codec::is_video), find(Codec::is_audio));
+		let selected: Vec<_> = video.into_iter().chain(audio).collect();
+		anyhow::ensure!(!selected.is_empty(), "no supported tracks in catalog");
+
+		// A fragmented MP4 has a single init segment, so every track must share it.
+		let init = selected[0].init_track.clone().context("missing init track")?;
+		anyhow::ensure!(
+			selected.iter().all(|track| track.init_track.as_ref() == Some(&init)),
+			"tracks use different init segments"
+		);
+
+		let track = self.subscribe(&init)?;
+		let mut group = match track.mode().await? {
+			TrackReaderMode::Groups(mut groups) => groups.next().await?.context("no init group")?,
+			_ => anyhow::bail!("expected init segment"),
 		};
 
-		let mut has_video = false;
-		let mut has_audio = false;
-		let mut tracks = vec![];
-		for trak in &moov.traks {
-			let id = trak.tkhd.track_id;
-			let name = format!("{}.m4s", id);
-			info!("found track {name}");
-			let mut active = false;
-			if !has_video && trak.mdia.minf.stbl.stsd.avc1.is_some() {
-				active = true;
-				has_video = true;
-				info!("using {name} for video");
-			}
-			if !has_audio && trak.mdia.minf.stbl.stsd.mp4a.is_some() {
-				active = true;
-				has_audio = true;
-				info!("using {name} for audio");
-			}
-			if active {
-				let track = self.tracks_writer.create(&name).context("failed to create track")?;
+		let object = group.next().await?.context("no init fragment")?;
+		let buf = Self::recv_object(object).await?;
+		self.output.lock().await.write_all(&buf).await?;
 
-				let mut subscriber = self.subscriber.clone();
-				tokio::task::spawn(async move {
-					subscriber.subscribe(track).await.unwrap_or_else(|err| {
-						warn!("failed to subscribe to track: {err:?}");
-					});
-				});
+		let mut tracks = Vec::new();
+		for track in selected {
+			let source = match (&self.abr, video) {
+				(Some(stats), Some(video)) if video.name == track.name => {
+					let renditions = Abr::alternates(&catalog.tracks, video)?;
+					let names: Vec<_> = renditions.iter().map(|track| &track.name).collect();
+					info!("playing renditions: {:?}", names);
 
-				tracks.push(self.broadcast.subscribe(&name).context("no track")?);
-			}
+					let abr = Abr::new(self.subscriber.clone(), &self.broadcast.namespace, renditions, stats);
+					Source::Abr(Box::new(abr))
+				}
+				_ => {
+					info!("playing track {}: {:?}", track.name, track.selection_params.codec);
+					Source::Track(self.subscribe(&track.name)?)
+				}
+			};
+
+			tracks.push((track.name.clone(), source));
 		}
 
-		info!("playing {} tracks", tracks.len());
 		let mut tasks = JoinSet::new();
-		for track in tracks {
+		for (name, source) in tracks {
 			let out = self.output.clone();
 			tasks.spawn(async move {
-				let name = track.name.clone();
-				if let Err(err) = Self::recv_track(track, out).await {
+				if let Err(err) = Self::recv_track(&name, source, out).await {
 					warn!("failed to play track {name}: {err:?}");
 				}
 			});
@@ -116,35 +147,76 @@ impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
 		Ok(())
 	}
 
-	async fn recv_track(track: TrackReader, out: Arc<Mutex<O>>) -> anyhow::Result<()> {
-		let name = track.name.clone();
+	fn subscribe(&mut self, name: &str) -> anyhow::Result<TrackReader> {
+		let track = self.tracks_writer.create(name).context("failed to create track")?;
+
+		let mut subscriber = self.subscriber.clone();
+		tokio::task::spawn(async move {
+			let name = track.name.clone();
+			subscriber.subscribe(track).await.unwrap_or_else(|err| {
+				warn!("failed to subscribe to track {name}: {err:?}");
+			});
+		});
+
+		self.broadcast.subscribe(name).context("no track")
+	}
+
+	// Write each fragment of the track in order, skipping to the next group when one arrives.
+	// Each group starts with a keyframe, so abandoning a stalled or broken group doesn't corrupt the output.
+	async fn recv_track(name: &str, source: Source, out: Arc<Mutex<O>>) -> anyhow::Result<()> {
 		debug!("track {name}: start");
-		if let TrackReaderMode::Groups(mut groups) = track.mode().await? {
-			while let Some(group) = groups.next().await? {
-				let out = out.clone();
-				tokio::task::spawn(async move {
-					if let Err(err) = Self::recv_group(group, out).await {
-						warn!("failed to receive group: {err:?}");
+
+		let mut groups = Groups::new(source).await?;
+
+		let mut current: Option<GroupReader> = None;
+		let mut done = false;
+
+		loop {
+			tokio::select! {
+				res = groups.next(), if !done => match res? {
+					Some(group) => {
+						if let Some(skipped) = current.replace(group) {
+							debug!("track {name}: skipping rest of group={}", skipped.group_id);
+						}
 					}
-				});
+					None => done = true,
+				},
+				// NOTE: Only reading is cancelled by a new group, so a fragment is never partially written.
+				res = Self::recv_fragment(current.as_mut().unwrap()), if current.is_some() => match res {
+					Ok(Some(fragment)) => out.lock().await.write_all(&fragment).await?,
+					Ok(None) => current = None,
+					Err(err) => {
+						warn!("track {name}: dropping group: {err:?}");
+						current = None;
+					}
+				},
+				else => break,
 			}
 		}
+
 		debug!("track {name}: finish");
 		Ok(())
 	}
 
-	async fn recv_group(mut group: GroupReader, out: Arc<Mutex<O>>) -> anyhow::Result<()> {
-		trace!("group={} start", group.group_id);
+	// Read the next moof and mdat, or None when the group is done.
+	// Each object is a single atom, so the fragment is complete once we read an mdat.
+	async fn recv_fragment(group: &mut GroupReader) -> anyhow::Result<Option<Vec<u8>>> {
+		let mut fragment = Vec::new();
+
 		while let Some(object) = group.next().await? {
 			trace!("group={} fragment={} start", group.group_id, object.object_id);
-			let out = out.clone();
-			let buf = Self::recv_object(object).await?;
 
-			// TODO: avoid interleaving out of order fragments
-			out.lock().await.write_all(&buf).await?;
+			let atom = Self::recv_object(object).await?;
+			let mdat = atom.get(4..8) == Some(b"mdat");
+			fragment.extend_from_slice(&atom);
+
+			if mdat {
+				return Ok(Some(fragment));
+			}
 		}
 
-		Ok(())
+		anyhow::ensure!(fragment.is_empty(), "group ended with a partial fragment");
+		Ok(None)
 	}
 
 	async fn recv_object(mut object: GroupObjectReader) -> anyhow::Result<Vec<u8>> {
@@ -155,40 +227,3 @@ impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
 		Ok(buf)
 	}
 }
-
-// Read a full MP4 atom into a vector.
-async fn read_atom<R: AsyncReadExt + Unpin>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
-	// Read the 8 bytes for the size + type
-	let mut buf = [0u8; 8];
-	reader.read_exact(&mut buf).await?;
-
-	// Convert the first 4 bytes into the size.
-	let size = u32::from_be_bytes(buf[0..4].try_into()?) as u64;
-
-	let mut raw = buf.to_vec();
-
-	let mut limit = match size {
-		// Runs until the end of the file.
-		0 => reader.take(u64::MAX),
-
-		// The next 8 bytes are the extended size to be used instead.
-		1 => {
-			reader.read_exact(&mut buf).await?;
-			let size_large = u64::from_be_bytes(buf);
-			anyhow::ensure!(size_large >= 16, "impossible extended box size: {}", size_large);
-
-			reader.take(size_large - 16)
-		}
-
-		2..=7 => {
-			anyhow::bail!("impossible box size: {}", size)
-		}
-
-		size => reader.take(size - 8),
-	};
-
-	// Append to the vector and return it.
-	let _read_bytes = limit.read_to_end(&mut raw).await?;
-
-	Ok(raw)
-}
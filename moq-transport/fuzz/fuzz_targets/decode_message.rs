@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use moq_transport::{coding::Decode, message::Message};
+
+// Decoding arbitrary bytes must return an error rather than panic.
+fuzz_target!(|data: &[u8]| {
+	let mut buf = data;
+	let _ = Message::decode(&mut buf);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use moq_transport::{coding::Decode, setup};
+
+// Decoding arbitrary bytes must return an error rather than panic.
+fuzz_target!(|data: &[u8]| {
+	let _ = setup::Client::decode(&mut &data[..]);
+	let _ = setup::Server::decode(&mut &data[..]);
+});
@@ -0,0 +1,31 @@
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use moq_transport::{
+	coding::{Decode, Encode},
+	message::Message,
+};
+
+// Any message that survives a round trip must survive every subsequent round trip unchanged.
+//
+// NOTE: The first round trip may normalize the message, such as dropping the start of a LatestGroup subscription,
+// and the encoder accepts some messages the decoder rejects, such as a start object without a start group.
+fuzz_target!(|msg: Message| {
+	let mut buf = BytesMut::new();
+	if msg.encode(&mut buf).is_err() {
+		return;
+	}
+
+	let first = match Message::decode(&mut buf) {
+		Ok(first) => first,
+		Err(_) => return,
+	};
+	assert!(buf.is_empty(), "message not fully consumed: {:?}", first);
+
+	first.encode(&mut buf).expect("failed to re-encode message");
+	let second = Message::decode(&mut buf).expect("failed to decode re-encoded message");
+	assert!(buf.is_empty(), "message not fully consumed: {:?}", second);
+
+	assert_eq!(first, second);
+});
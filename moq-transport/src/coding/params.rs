@@ -3,7 +3,8 @@ use std::io::Cursor;
 
 use crate::coding::{Decode, DecodeError, Encode, EncodeError};
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Params(pub HashMap<u64, Vec<u8>>);
 
 impl Decode for Params {
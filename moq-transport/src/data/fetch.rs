@@ -0,0 +1,76 @@
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+// The stream type for a fetch, distinct from the unidirectional data streams.
+const FETCH_STREAM: u64 = 0x52;
+
+/// Sent by the subscriber on a new bidirectional stream to request a single cached group.
+///
+/// Unlike a subscription, a fetch is bounded: the publisher serves the one group and closes the stream.
+#[derive(Clone, Debug)]
+pub struct FetchHeader {
+	// The track namespace.
+	pub namespace: String,
+
+	// The track name.
+	pub name: String,
+
+	// The group sequence number.
+	pub group_id: u64,
+}
+
+impl Decode for FetchHeader {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		let t = u64::decode(r)?;
+		if t != FETCH_STREAM {
+			return Err(DecodeError::InvalidMessage(t));
+		}
+
+		Ok(Self {
+			namespace: String::decode(r)?,
+			name: String::decode(r)?,
+			group_id: u64::decode(r)?,
+		})
+	}
+}
+
+impl Encode for FetchHeader {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		FETCH_STREAM.encode(w)?;
+		self.namespace.encode(w)?;
+		self.name.encode(w)?;
+		self.group_id.encode(w)?;
+
+		Ok(())
+	}
+}
+
+/// Sent by the publisher in response to a [FetchHeader].
+///
+/// On success, the code is 0 and the group's objects follow, encoded as [super::GroupObject].
+/// Otherwise the code is the error and the stream is closed.
+#[derive(Clone, Debug)]
+pub struct FetchResponse {
+	// 0 on success, otherwise an error code.
+	pub code: u64,
+
+	// The priority, where **smaller** values are sent first.
+	pub send_order: u64,
+}
+
+impl Decode for FetchResponse {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self {
+			code: u64::decode(r)?,
+			send_order: u64::decode(r)?,
+		})
+	}
+}
+
+impl Encode for FetchResponse {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.code.encode(w)?;
+		self.send_order.encode(w)?;
+
+		Ok(())
+	}
+}
@@ -1,5 +1,9 @@
 use crate::coding::{Decode, DecodeError, Encode, EncodeError};
-use crate::data::ObjectStatus;
+use crate::data::{ObjectMeta, ObjectStatus};
+
+// A reserved status indicating the object is prefixed with metadata.
+// Objects without metadata are encoded exactly as before, so older peers are unaffected.
+const METADATA_STATUS: u64 = 0x80;
 
 #[derive(Clone, Debug)]
 pub struct GroupHeader {
@@ -43,17 +47,30 @@ pub struct GroupObject {
 	pub object_id: u64,
 	pub size: usize,
 	pub status: ObjectStatus,
+
+	// Optional metadata, such as a presentation timestamp.
+	pub meta: Option<ObjectMeta>,
 }
 
 impl Decode for GroupObject {
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
 		let object_id = u64::decode(r)?;
-		let size = usize::decode(r)?;
+		let mut size = usize::decode(r)?;
+		let mut meta = None;
 
 		// If the size is 0, then the status is sent explicitly.
 		// Otherwise, the status is assumed to be 0x0 (Object).
 		let status = if size == 0 {
-			ObjectStatus::decode(r)?
+			let mut status = u64::decode(r)?;
+
+			// The metadata is followed by the actual size and status.
+			if status == METADATA_STATUS {
+				meta = Some(ObjectMeta::decode(r)?);
+				size = usize::decode(r)?;
+				status = if size == 0 { u64::decode(r)? } else { 0 };
+			}
+
+			ObjectStatus::try_from(status)?
 		} else {
 			ObjectStatus::Object
 		};
@@ -62,6 +79,7 @@ impl Decode for GroupObject {
 			object_id,
 			size,
 			status,
+			meta,
 		})
 	}
 }
@@ -69,6 +87,13 @@ impl Decode for GroupObject {
 impl Encode for GroupObject {
 	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
 		self.object_id.encode(w)?;
+
+		if let Some(meta) = &self.meta {
+			0usize.encode(w)?;
+			METADATA_STATUS.encode(w)?;
+			meta.encode(w)?;
+		}
+
 		self.size.encode(w)?;
 
 		// If the size is 0, then the status is sent explicitly.
@@ -80,3 +105,41 @@ impl Encode for GroupObject {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::data::ObjectFlags;
+	use bytes::BytesMut;
+
+	#[test]
+	fn meta() {
+		let mut buf = BytesMut::new();
+
+		let object = GroupObject {
+			object_id: 3,
+			size: 5,
+			status: ObjectStatus::Object,
+			meta: Some(ObjectMeta {
+				timestamp: 90_000,
+				flags: ObjectFlags {
+					keyframe: true,
+					discardable: false,
+				},
+				tag: bytes::Bytes::from_static(b"key"),
+			}),
+		};
+		object.encode(&mut buf).unwrap();
+
+		let decoded = GroupObject::decode(&mut buf).unwrap();
+		assert_eq!(decoded.object_id, 3);
+		assert_eq!(decoded.size, 5);
+		assert_eq!(decoded.status, ObjectStatus::Object);
+		assert_eq!(decoded.meta, object.meta);
+
+		// Objects without metadata are unchanged on the wire.
+		let object = GroupObject { meta: None, ..object };
+		object.encode(&mut buf).unwrap();
+		assert_eq!(buf.as_ref(), &[3, 5]);
+	}
+}
@@ -0,0 +1,96 @@
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+/// Flags describing how an object may be treated by relays and players.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ObjectFlags {
+	/// The object can be decoded on its own, such as a video keyframe.
+	pub keyframe: bool,
+
+	/// The object can be dropped without breaking later objects, such as a non-reference frame.
+	pub discardable: bool,
+}
+
+impl ObjectFlags {
+	const KEYFRAME: u64 = 0x1;
+	const DISCARDABLE: u64 = 0x2;
+}
+
+impl Decode for ObjectFlags {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		// NOTE: Unknown flags are ignored so more can be added later.
+		let flags = u64::decode(r)?;
+
+		Ok(Self {
+			keyframe: flags & Self::KEYFRAME != 0,
+			discardable: flags & Self::DISCARDABLE != 0,
+		})
+	}
+}
+
+impl Encode for ObjectFlags {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		let mut flags = 0;
+		if self.keyframe {
+			flags |= Self::KEYFRAME;
+		}
+		if self.discardable {
+			flags |= Self::DISCARDABLE;
+		}
+
+		flags.encode(w)
+	}
+}
+
+/// Optional metadata attached to an object, such as a presentation timestamp.
+///
+/// This lets relays drop late or discardable objects, and players synchronize tracks, without parsing the payload.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectMeta {
+	/// The presentation timestamp, in application defined units.
+	pub timestamp: u64,
+
+	/// Hints for relays and players, such as whether the object is a keyframe.
+	pub flags: ObjectFlags,
+
+	/// A small opaque tag for the application, bounded by [Self::MAX_TAG].
+	pub tag: bytes::Bytes,
+}
+
+impl ObjectMeta {
+	/// The maximum size of the tag, to avoid abuse.
+	pub const MAX_TAG: usize = 64;
+}
+
+impl Decode for ObjectMeta {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		let timestamp = u64::decode(r)?;
+		let flags = ObjectFlags::decode(r)?;
+
+		let size = usize::decode(r)?;
+		if size > Self::MAX_TAG {
+			return Err(DecodeError::InvalidValue);
+		}
+
+		Self::decode_remaining(r, size)?;
+		let tag = r.copy_to_bytes(size);
+
+		Ok(Self { timestamp, flags, tag })
+	}
+}
+
+impl Encode for ObjectMeta {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		if self.tag.len() > Self::MAX_TAG {
+			return Err(EncodeError::InvalidValue);
+		}
+
+		self.timestamp.encode(w)?;
+		self.flags.encode(w)?;
+		self.tag.len().encode(w)?;
+
+		Self::encode_remaining(w, self.tag.len())?;
+		w.put_slice(&self.tag);
+
+		Ok(())
+	}
+}
@@ -1,11 +1,15 @@
 mod datagram;
+mod fetch;
 mod group;
 mod header;
+mod meta;
 mod object;
 mod track;
 
 pub use datagram::*;
+pub use fetch::*;
 pub use group::*;
 pub use header::*;
+pub use meta::*;
 pub use object::*;
 pub use track::*;
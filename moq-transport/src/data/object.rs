@@ -9,9 +9,11 @@ pub enum ObjectStatus {
 	EndOfTrack = 0x4,
 }
 
-impl Decode for ObjectStatus {
-	fn decode<B: bytes::Buf>(r: &mut B) -> Result<Self, DecodeError> {
-		match u64::decode(r)? {
+impl TryFrom<u64> for ObjectStatus {
+	type Error = DecodeError;
+
+	fn try_from(status: u64) -> Result<Self, Self::Error> {
+		match status {
 			0x0 => Ok(Self::Object),
 			0x1 => Ok(Self::ObjectDoesNotExist),
 			0x2 => Ok(Self::GroupDoesNotExist),
@@ -22,6 +24,12 @@ impl Decode for ObjectStatus {
 	}
 }
 
+impl Decode for ObjectStatus {
+	fn decode<B: bytes::Buf>(r: &mut B) -> Result<Self, DecodeError> {
+		u64::decode(r)?.try_into()
+	}
+}
+
 impl Encode for ObjectStatus {
 	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
 		match self {
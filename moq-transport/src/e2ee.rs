@@ -0,0 +1,566 @@
+//! End-to-end encryption of object payloads, so relays only ever see ciphertext.
+//!
+//! Each track is encrypted with its own [TrackKey] using XChaCha20-Poly1305.
+//! A random nonce is prepended to every payload, and the group and object IDs are authenticated,
+//! so a relay can't reorder objects without the subscriber noticing.
+//! Headers, priorities and [crate::data::ObjectMeta] are still sent in the clear since relays need them.
+//!
+//! Keys are distributed out of band, or sealed with a shared key via [TrackKey::seal] and published in the catalog.
+//!
+//! The [EncryptingTrackWriter] and [DecryptingTrackReader] wrap the usual track handles.
+//! Only whole objects are supported, since the tag can't be verified until the payload is complete.
+//! Stream mode is not supported.
+use std::{fmt, ops::Deref};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use chacha20poly1305::{
+	aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+	XChaCha20Poly1305, XNonce,
+};
+
+use crate::{
+	data::ObjectMeta,
+	serve::{
+		Datagram, DatagramsReader, DatagramsWriter, Group, GroupInfo, GroupReader, GroupWriter, GroupsReader,
+		GroupsWriter, Object, ObjectInfo, ObjectReader, ObjectsReader, ObjectsWriter, ServeError, Track, TrackReader,
+		TrackReaderMode, TrackWriter,
+	},
+};
+
+const NONCE_SIZE: usize = 24;
+const TAG_SIZE: usize = 16;
+
+// Authenticated when sealing a key, so a sealed key can't be mistaken for a payload.
+const SEAL_CONTEXT: &[u8] = b"moq-track-key";
+
+/// A symmetric key used to encrypt the payloads of a single track.
+#[derive(Clone)]
+pub struct TrackKey {
+	bytes: [u8; Self::SIZE],
+	cipher: XChaCha20Poly1305,
+}
+
+impl TrackKey {
+	/// The size of the key in bytes.
+	pub const SIZE: usize = 32;
+
+	/// The number of bytes each encrypted payload grows by.
+	pub const OVERHEAD: usize = NONCE_SIZE + TAG_SIZE;
+
+	/// Generate a random key.
+	pub fn generate() -> Self {
+		Self::new(XChaCha20Poly1305::generate_key(&mut OsRng).into())
+	}
+
+	pub fn new(bytes: [u8; Self::SIZE]) -> Self {
+		let cipher = XChaCha20Poly1305::new(&bytes.into());
+		Self { bytes, cipher }
+	}
+
+	/// The raw key, used to distribute it out of band.
+	pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+		self.bytes
+	}
+
+	/// Encrypt the payload of the given object.
+	pub fn encrypt(&self, group_id: u64, object_id: u64, payload: &[u8]) -> Result<Bytes, ServeError> {
+		let aad = Self::aad(group_id, object_id);
+		self.encrypt_with(payload, &aad)
+	}
+
+	/// Decrypt the payload of the given object, failing if it was modified or moved.
+	pub fn decrypt(&self, group_id: u64, object_id: u64, payload: &[u8]) -> Result<Bytes, ServeError> {
+		let aad = Self::aad(group_id, object_id);
+		self.decrypt_with(payload, &aad)
+	}
+
+	/// Encrypt another key with this one, so it can be published alongside the track.
+	pub fn seal(&self, key: &TrackKey) -> Bytes {
+		// Only fails if the plaintext is enormous.
+		self.encrypt_with(&key.bytes, SEAL_CONTEXT).expect("failed to seal key")
+	}
+
+	/// Decrypt a key produced by [Self::seal].
+	pub fn unseal(&self, sealed: &[u8]) -> Result<TrackKey, ServeError> {
+		let bytes = self.decrypt_with(sealed, SEAL_CONTEXT)?;
+		let bytes = bytes.as_ref().try_into().map_err(|_| ServeError::Decrypt)?;
+		Ok(Self::new(bytes))
+	}
+
+	fn encrypt_with(&self, payload: &[u8], aad: &[u8]) -> Result<Bytes, ServeError> {
+		let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+		let ciphertext = self
+			.cipher
+			.encrypt(&nonce, Payload { msg: payload, aad })
+			.map_err(|_| ServeError::Size)?;
+
+		let mut buf = BytesMut::with_capacity(NONCE_SIZE + ciphertext.len());
+		buf.put_slice(&nonce);
+		buf.put_slice(&ciphertext);
+
+		Ok(buf.freeze())
+	}
+
+	fn decrypt_with(&self, payload: &[u8], aad: &[u8]) -> Result<Bytes, ServeError> {
+		if payload.len() < Self::OVERHEAD {
+			return Err(ServeError::Decrypt);
+		}
+
+		let (nonce, ciphertext) = payload.split_at(NONCE_SIZE);
+		let plaintext = self
+			.cipher
+			.decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+			.map_err(|_| ServeError::Decrypt)?;
+
+		Ok(plaintext.into())
+	}
+
+	fn aad(group_id: u64, object_id: u64) -> [u8; 16] {
+		let mut aad = [0; 16];
+		aad[..8].copy_from_slice(&group_id.to_be_bytes());
+		aad[8..].copy_from_slice(&object_id.to_be_bytes());
+		aad
+	}
+}
+
+impl fmt::Debug for TrackKey {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("TrackKey(..)")
+	}
+}
+
+/// Encrypts every payload written to a track.
+pub struct EncryptingTrackWriter {
+	track: TrackWriter,
+	key: TrackKey,
+}
+
+impl EncryptingTrackWriter {
+	pub fn new(track: TrackWriter, key: TrackKey) -> Self {
+		Self { track, key }
+	}
+
+	pub fn groups(self) -> Result<EncryptingGroupsWriter, ServeError> {
+		Ok(EncryptingGroupsWriter {
+			groups: self.track.groups()?,
+			key: self.key,
+		})
+	}
+
+	pub fn objects(self) -> Result<EncryptingObjectsWriter, ServeError> {
+		Ok(EncryptingObjectsWriter {
+			objects: self.track.objects()?,
+			key: self.key,
+		})
+	}
+
+	pub fn datagrams(self) -> Result<EncryptingDatagramsWriter, ServeError> {
+		Ok(EncryptingDatagramsWriter {
+			datagrams: self.track.datagrams()?,
+			key: self.key,
+		})
+	}
+
+	/// Block until all readers have been dropped, see [TrackWriter::unused].
+	pub async fn unused(&self) {
+		self.track.unused().await
+	}
+
+	pub fn close(self, err: ServeError) -> Result<(), ServeError> {
+		self.track.close(err)
+	}
+}
+
+impl Deref for EncryptingTrackWriter {
+	type Target = Track;
+
+	fn deref(&self) -> &Self::Target {
+		&self.track
+	}
+}
+
+pub struct EncryptingGroupsWriter {
+	groups: GroupsWriter,
+	key: TrackKey,
+}
+
+impl EncryptingGroupsWriter {
+	pub fn append(&mut self, priority: u64) -> Result<EncryptingGroupWriter, ServeError> {
+		let group = self.groups.append(priority)?;
+		Ok(EncryptingGroupWriter::new(group, self.key.clone()))
+	}
+
+	pub fn create(&mut self, group: Group) -> Result<EncryptingGroupWriter, ServeError> {
+		let group = self.groups.create(group)?;
+		Ok(EncryptingGroupWriter::new(group, self.key.clone()))
+	}
+
+	pub fn close(self, err: ServeError) -> Result<(), ServeError> {
+		self.groups.close(err)
+	}
+}
+
+impl Deref for EncryptingGroupsWriter {
+	type Target = Track;
+
+	fn deref(&self) -> &Self::Target {
+		&self.groups
+	}
+}
+
+pub struct EncryptingGroupWriter {
+	group: GroupWriter,
+	key: TrackKey,
+
+	// The next object ID, which is authenticated so it must be known before writing.
+	next: u64,
+}
+
+impl EncryptingGroupWriter {
+	fn new(group: GroupWriter, key: TrackKey) -> Self {
+		Self { group, key, next: 0 }
+	}
+
+	/// Encrypt and write the next object.
+	pub fn write(&mut self, payload: Bytes) -> Result<(), ServeError> {
+		self.write_inner(payload, None)
+	}
+
+	/// Encrypt and write the next object, with metadata that is sent in the clear.
+	pub fn write_with_meta(&mut self, payload: Bytes, meta: ObjectMeta) -> Result<(), ServeError> {
+		self.write_inner(payload, Some(meta))
+	}
+
+	fn write_inner(&mut self, payload: Bytes, meta: Option<ObjectMeta>) -> Result<(), ServeError> {
+		let payload = self.key.encrypt(self.group.group_id, self.next, &payload)?;
+
+		let mut object = self.group.create_at(self.next, payload.len(), meta)?;
+		object.write(payload)?;
+
+		self.next += 1;
+
+		Ok(())
+	}
+
+	pub fn close(self, err: ServeError) -> Result<(), ServeError> {
+		self.group.close(err)
+	}
+}
+
+impl Deref for EncryptingGroupWriter {
+	type Target = GroupInfo;
+
+	fn deref(&self) -> &Self::Target {
+		&self.group
+	}
+}
+
+pub struct EncryptingObjectsWriter {
+	objects: ObjectsWriter,
+	key: TrackKey,
+}
+
+impl EncryptingObjectsWriter {
+	pub fn write(&mut self, object: Object, payload: Bytes) -> Result<(), ServeError> {
+		let payload = self.key.encrypt(object.group_id, object.object_id, &payload)?;
+		self.objects.write(object, payload)
+	}
+
+	pub fn close(self, err: ServeError) -> Result<(), ServeError> {
+		self.objects.close(err)
+	}
+}
+
+impl Deref for EncryptingObjectsWriter {
+	type Target = Track;
+
+	fn deref(&self) -> &Self::Target {
+		&self.objects
+	}
+}
+
+pub struct EncryptingDatagramsWriter {
+	datagrams: DatagramsWriter,
+	key: TrackKey,
+}
+
+impl EncryptingDatagramsWriter {
+	pub fn write(&mut self, mut datagram: Datagram) -> Result<(), ServeError> {
+		datagram.payload = self
+			.key
+			.encrypt(datagram.group_id, datagram.object_id, &datagram.payload)?;
+		self.datagrams.write(datagram)
+	}
+
+	pub fn close(self, err: ServeError) -> Result<(), ServeError> {
+		self.datagrams.close(err)
+	}
+}
+
+/// Decrypts every payload read from a track.
+///
+/// A payload that fails to decrypt returns [ServeError::Decrypt], which usually means the key is wrong.
+#[derive(Clone)]
+pub struct DecryptingTrackReader {
+	track: TrackReader,
+	key: TrackKey,
+}
+
+impl DecryptingTrackReader {
+	pub fn new(track: TrackReader, key: TrackKey) -> Self {
+		Self { track, key }
+	}
+
+	/// Block until the publisher picks a mode, returning [ServeError::Mode] for stream mode.
+	pub async fn mode(&self) -> Result<DecryptingTrackReaderMode, ServeError> {
+		let key = self.key.clone();
+
+		Ok(match self.track.mode().await? {
+			TrackReaderMode::Groups(groups) => DecryptingGroupsReader { groups, key }.into(),
+			TrackReaderMode::Objects(objects) => DecryptingObjectsReader { objects, key }.into(),
+			TrackReaderMode::Datagrams(datagrams) => DecryptingDatagramsReader { datagrams, key }.into(),
+			TrackReaderMode::Stream(_) => return Err(ServeError::Mode),
+		})
+	}
+
+	pub async fn closed(&self) -> Result<(), ServeError> {
+		self.track.closed().await
+	}
+}
+
+impl Deref for DecryptingTrackReader {
+	type Target = Track;
+
+	fn deref(&self) -> &Self::Target {
+		&self.track
+	}
+}
+
+pub enum DecryptingTrackReaderMode {
+	Groups(DecryptingGroupsReader),
+	Objects(DecryptingObjectsReader),
+	Datagrams(DecryptingDatagramsReader),
+}
+
+impl From<DecryptingGroupsReader> for DecryptingTrackReaderMode {
+	fn from(reader: DecryptingGroupsReader) -> Self {
+		Self::Groups(reader)
+	}
+}
+
+impl From<DecryptingObjectsReader> for DecryptingTrackReaderMode {
+	fn from(reader: DecryptingObjectsReader) -> Self {
+		Self::Objects(reader)
+	}
+}
+
+impl From<DecryptingDatagramsReader> for DecryptingTrackReaderMode {
+	fn from(reader: DecryptingDatagramsReader) -> Self {
+		Self::Datagrams(reader)
+	}
+}
+
+#[derive(Clone)]
+pub struct DecryptingGroupsReader {
+	groups: GroupsReader,
+	key: TrackKey,
+}
+
+impl DecryptingGroupsReader {
+	pub async fn next(&mut self) -> Result<Option<DecryptingGroupReader>, ServeError> {
+		Ok(self.groups.next().await?.map(|group| DecryptingGroupReader {
+			group,
+			key: self.key.clone(),
+		}))
+	}
+}
+
+impl Deref for DecryptingGroupsReader {
+	type Target = Track;
+
+	fn deref(&self) -> &Self::Target {
+		&self.groups
+	}
+}
+
+#[derive(Clone)]
+pub struct DecryptingGroupReader {
+	group: GroupReader,
+	key: TrackKey,
+}
+
+impl DecryptingGroupReader {
+	/// Read and decrypt the next object.
+	pub async fn read_next(&mut self) -> Result<Option<Bytes>, ServeError> {
+		let mut object = match self.group.next().await? {
+			Some(object) => object,
+			None => return Ok(None),
+		};
+
+		let payload = object.read_all().await?;
+		let payload = self.key.decrypt(self.group.group_id, object.object_id, &payload)?;
+
+		Ok(Some(payload))
+	}
+}
+
+impl Deref for DecryptingGroupReader {
+	type Target = GroupInfo;
+
+	fn deref(&self) -> &Self::Target {
+		&self.group
+	}
+}
+
+#[derive(Clone)]
+pub struct DecryptingObjectsReader {
+	objects: ObjectsReader,
+	key: TrackKey,
+}
+
+impl DecryptingObjectsReader {
+	pub async fn next(&mut self) -> Result<Option<DecryptingObjectReader>, ServeError> {
+		Ok(self.objects.next().await?.map(|object| DecryptingObjectReader {
+			object,
+			key: self.key.clone(),
+		}))
+	}
+}
+
+impl Deref for DecryptingObjectsReader {
+	type Target = Track;
+
+	fn deref(&self) -> &Self::Target {
+		&self.objects
+	}
+}
+
+#[derive(Clone)]
+pub struct DecryptingObjectReader {
+	object: ObjectReader,
+	key: TrackKey,
+}
+
+impl DecryptingObjectReader {
+	/// Read and decrypt the whole object.
+	pub async fn read_all(&mut self) -> Result<Bytes, ServeError> {
+		let payload = self.object.read_all().await?;
+		self.key.decrypt(self.object.group_id, self.object.object_id, &payload)
+	}
+}
+
+impl Deref for DecryptingObjectReader {
+	type Target = ObjectInfo;
+
+	fn deref(&self) -> &Self::Target {
+		&self.object
+	}
+}
+
+#[derive(Clone)]
+pub struct DecryptingDatagramsReader {
+	datagrams: DatagramsReader,
+	key: TrackKey,
+}
+
+impl DecryptingDatagramsReader {
+	pub async fn read(&mut self) -> Result<Option<Datagram>, ServeError> {
+		let mut datagram = match self.datagrams.read().await? {
+			Some(datagram) => datagram,
+			None => return Ok(None),
+		};
+
+		datagram.payload = self
+			.key
+			.decrypt(datagram.group_id, datagram.object_id, &datagram.payload)?;
+
+		Ok(Some(datagram))
+	}
+}
+
+impl Deref for DecryptingDatagramsReader {
+	type Target = Track;
+
+	fn deref(&self) -> &Self::Target {
+		&self.datagrams.track
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[tokio::test]
+	async fn groups() {
+		let key = TrackKey::generate();
+
+		let (writer, reader) = Track::new("test".to_string(), "video".to_string()).produce();
+		let mut groups = EncryptingTrackWriter::new(writer, key.clone()).groups().unwrap();
+
+		let mut group = groups.append(0).unwrap();
+		group.write(Bytes::from_static(b"hello")).unwrap();
+		group.write(Bytes::from_static(b"world")).unwrap();
+		drop(group);
+
+		// Anything reading the raw track, such as a relay, only sees ciphertext.
+		let mut raw = match reader.mode().await.unwrap() {
+			TrackReaderMode::Groups(groups) => groups,
+			_ => panic!("wrong mode"),
+		};
+		let payload = raw.next().await.unwrap().unwrap().read_next().await.unwrap().unwrap();
+		assert_eq!(payload.len(), 5 + TrackKey::OVERHEAD);
+		assert!(!payload.windows(5).any(|w| w == b"hello"));
+
+		let mut groups = match DecryptingTrackReader::new(reader.clone(), key).mode().await.unwrap() {
+			DecryptingTrackReaderMode::Groups(groups) => groups,
+			_ => panic!("wrong mode"),
+		};
+
+		let mut group = groups.next().await.unwrap().unwrap();
+		assert_eq!(group.read_next().await.unwrap().unwrap(), "hello");
+		assert_eq!(group.read_next().await.unwrap().unwrap(), "world");
+
+		// The wrong key fails to decrypt.
+		let mut groups = match DecryptingTrackReader::new(reader, TrackKey::generate())
+			.mode()
+			.await
+			.unwrap()
+		{
+			DecryptingTrackReaderMode::Groups(groups) => groups,
+			_ => panic!("wrong mode"),
+		};
+
+		let mut group = groups.next().await.unwrap().unwrap();
+		assert_eq!(group.read_next().await, Err(ServeError::Decrypt));
+	}
+
+	#[test]
+	fn authenticated() {
+		let key = TrackKey::generate();
+		let payload = key.encrypt(1, 2, b"payload").unwrap();
+
+		assert_eq!(key.decrypt(1, 2, &payload).unwrap(), "payload");
+
+		// Moving the payload to another object is detected.
+		assert_eq!(key.decrypt(1, 3, &payload), Err(ServeError::Decrypt));
+		assert_eq!(key.decrypt(2, 2, &payload), Err(ServeError::Decrypt));
+
+		let mut tampered = payload.to_vec();
+		*tampered.last_mut().unwrap() ^= 1;
+		assert_eq!(key.decrypt(1, 2, &tampered), Err(ServeError::Decrypt));
+		assert_eq!(key.decrypt(1, 2, &payload[..10]), Err(ServeError::Decrypt));
+	}
+
+	#[test]
+	fn seal() {
+		let shared = TrackKey::generate();
+		let key = TrackKey::generate();
+
+		let sealed = shared.seal(&key);
+		assert_eq!(shared.unseal(&sealed).unwrap().to_bytes(), key.to_bytes());
+		assert_eq!(key.unseal(&sealed).err(), Some(ServeError::Decrypt));
+
+		// A sealed key isn't a valid payload, and vice versa.
+		assert_eq!(shared.decrypt(0, 0, &sealed), Err(ServeError::Decrypt));
+	}
+}
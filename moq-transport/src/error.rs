@@ -1,3 +1,17 @@
+/// An error that can be sent to the peer as an integer code.
+///
+/// The same code is used for control messages (ex. SUBSCRIBE_DONE) and stream resets,
+/// so the peer sees the same error regardless of how it was signalled.
+pub trait ErrorCode {
+	/// An integer code that is sent over the wire.
+	fn code(&self) -> u64;
+
+	/// The code used to reset a stream, which is limited to 32 bits.
+	fn reset_code(&self) -> u32 {
+		self.code().try_into().unwrap_or(u32::MAX)
+	}
+}
+
 /*
 /// An error that causes the session to close.
 #[derive(thiserror::Error, Debug)]
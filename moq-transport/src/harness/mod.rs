@@ -0,0 +1,192 @@
+//! A loopback transport for testing sessions end to end, enabled with the `harness` feature.
+//!
+//! [pair] returns two connected [transport::Session]s over a [transport::Memory] link,
+//! so tests run deterministically without sockets or certificates.
+//! [pair_memory] does the same with simulated network conditions, and [netsim] builds on it for soak tests.
+//!
+//! Resets can be injected from either side with [transport::SendStream::reset].
+//!
+//! The other transports can be tested too:
+//! [pair_webtransport] and [pair_quic] connect over localhost, using a certificate generated on the fly,
+//! and [pair_websocket] tunnels over an in-process channel, in place of a WebSocket library.
+mod netsim;
+
+pub use netsim::*;
+
+use std::{convert::Infallible, net, sync::Arc};
+
+use bytes::Bytes;
+use futures::StreamExt;
+
+use crate::{setup, transport};
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+
+#[derive(thiserror::Error, Debug)]
+pub enum HarnessError {
+	#[error("io error: {0}")]
+	Io(#[from] std::io::Error),
+
+	#[error("certificate error: {0}")]
+	Certificate(#[from] rcgen::Error),
+
+	#[error("tls error: {0}")]
+	Tls(#[from] rustls::Error),
+
+	#[error("no initial cipher suite")]
+	CipherSuite(#[from] quinn::crypto::rustls::NoInitialCipherSuite),
+
+	#[error("connect error: {0}")]
+	Connect(#[from] quinn::ConnectError),
+
+	#[error("connection error: {0}")]
+	Connection(#[from] quinn::ConnectionError),
+
+	#[error("client error: {0}")]
+	Client(#[from] web_transport_quinn::ClientError),
+
+	#[error("server error: {0}")]
+	Server(#[from] web_transport_quinn::ServerError),
+
+	#[error("write error: {0}")]
+	Write(#[from] quinn::WriteError),
+
+	#[error("session error: {0}")]
+	Session(#[from] crate::session::SessionError),
+
+	#[error("serve error: {0}")]
+	Serve(#[from] crate::serve::ServeError),
+
+	#[error("endpoint closed")]
+	Closed,
+}
+
+/// Returns a connected (client, server) pair of sessions over a perfect in-memory link.
+pub fn pair() -> (transport::Session, transport::Session) {
+	pair_memory(Default::default())
+}
+
+/// Returns a connected (client, server) pair of WebTransport sessions over localhost.
+pub async fn pair_webtransport() -> Result<(transport::Session, transport::Session), HarnessError> {
+	let endpoint = endpoint(web_transport_quinn::ALPN)?;
+	let addr = endpoint.local_addr()?;
+	let url = url::Url::parse(&format!("https://localhost:{}", addr.port())).expect("invalid url");
+
+	let connect = async {
+		let conn = endpoint.connect(addr, "localhost")?.await?;
+		Ok::<_, HarnessError>(web_transport_quinn::connect_with(conn, &url).await?)
+	};
+
+	let accept = async {
+		let conn = endpoint.accept().await.ok_or(HarnessError::Closed)?.await?;
+		let request = web_transport_quinn::accept(conn).await?;
+		Ok::<_, HarnessError>(request.ok().await?)
+	};
+
+	let (client, server) = tokio::try_join!(connect, accept)?;
+
+	Ok((client.into(), server.into()))
+}
+
+/// Returns a connected (client, server) pair of raw QUIC sessions over localhost, negotiated with [setup::ALPN].
+pub async fn pair_quic() -> Result<(transport::Session, transport::Session), HarnessError> {
+	let endpoint = endpoint(setup::ALPN)?;
+	let addr = endpoint.local_addr()?;
+
+	let connect = async { Ok::<_, HarnessError>(endpoint.connect(addr, "localhost")?.await?) };
+	let accept = async { Ok::<_, HarnessError>(endpoint.accept().await.ok_or(HarnessError::Closed)?.await?) };
+
+	let (client, server) = tokio::try_join!(connect, accept)?;
+
+	Ok((client.into(), server.into()))
+}
+
+/// Returns a connected (client, server) pair of [transport::WebSocket] sessions, joined by in-process channels.
+pub fn pair_websocket() -> (transport::Session, transport::Session) {
+	let (client_tx, server_rx) = futures::channel::mpsc::unbounded::<Bytes>();
+	let (server_tx, client_rx) = futures::channel::mpsc::unbounded::<Bytes>();
+
+	let client = transport::WebSocket::client(client_tx, client_rx.map(Ok::<_, Infallible>));
+	let server = transport::WebSocket::server(server_tx, server_rx.map(Ok::<_, Infallible>));
+
+	(transport::Session::new(client), transport::Session::new(server))
+}
+
+/// Return a (client, server) pair connected in memory, simulating the network conditions.
+pub fn pair_memory(config: transport::MemoryConfig) -> (transport::Session, transport::Session) {
+	let (client, server) = transport::Memory::pair(config);
+	(transport::Session::new(client), transport::Session::new(server))
+}
+
+// A single endpoint that connects to itself using the ALPN.
+fn endpoint(alpn: &[u8]) -> Result<quinn::Endpoint, HarnessError> {
+	let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+	// A throwaway certificate for localhost; the client skips verification anyway.
+	let cert = rcgen::generate_simple_self_signed(["localhost".to_string()])?;
+	let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der()));
+	let cert = cert.cert.der().clone();
+
+	let mut server = rustls::ServerConfig::builder_with_provider(provider.clone())
+		.with_protocol_versions(&[&rustls::version::TLS13])?
+		.with_no_client_auth()
+		.with_single_cert(vec![cert], key)?;
+	server.alpn_protocols = vec![alpn.to_vec()];
+
+	let server: quinn::crypto::rustls::QuicServerConfig = server.try_into()?;
+	let server = quinn::ServerConfig::with_crypto(Arc::new(server));
+
+	let mut client = rustls::ClientConfig::builder_with_provider(provider.clone())
+		.with_protocol_versions(&[&rustls::version::TLS13])?
+		.dangerous()
+		.with_custom_certificate_verifier(Arc::new(NoVerify(provider)))
+		.with_no_client_auth();
+	client.alpn_protocols = vec![alpn.to_vec()];
+
+	let client: quinn::crypto::rustls::QuicClientConfig = client.try_into()?;
+	let client = quinn::ClientConfig::new(Arc::new(client));
+
+	let mut endpoint = quinn::Endpoint::server(server, (net::Ipv4Addr::LOCALHOST, 0).into())?;
+	endpoint.set_default_client_config(client);
+
+	Ok(endpoint)
+}
+
+// Accept any certificate, since we're only talking to ourselves.
+#[derive(Debug)]
+struct NoVerify(Arc<rustls::crypto::CryptoProvider>);
+
+impl rustls::client::danger::ServerCertVerifier for NoVerify {
+	fn verify_server_cert(
+		&self,
+		_end_entity: &CertificateDer<'_>,
+		_intermediates: &[CertificateDer<'_>],
+		_server_name: &ServerName<'_>,
+		_ocsp: &[u8],
+		_now: UnixTime,
+	) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+		Ok(rustls::client::danger::ServerCertVerified::assertion())
+	}
+
+	fn verify_tls12_signature(
+		&self,
+		message: &[u8],
+		cert: &CertificateDer<'_>,
+		dss: &rustls::DigitallySignedStruct,
+	) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+		rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+	}
+
+	fn verify_tls13_signature(
+		&self,
+		message: &[u8],
+		cert: &CertificateDer<'_>,
+		dss: &rustls::DigitallySignedStruct,
+	) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+		rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+	}
+
+	fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+		self.0.signature_verification_algorithms.supported_schemes()
+	}
+}
@@ -0,0 +1,192 @@
+//! A network simulator for soak testing, publishing a live track over a [transport::Memory] pair.
+//!
+//! The [transport::MemoryConfig] for each direction injects latency, jitter, reordering, loss bursts and bandwidth caps.
+//! The resulting [NetsimReport] lists which groups the subscriber delivered, so tests can assert
+//! that prioritization and expiry behave as intended under stress.
+use std::{
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use bytes::Bytes;
+
+use crate::{
+	serve::{self, ServeError, TrackReaderMode},
+	session::{Publisher, Subscriber},
+	transport,
+};
+
+use super::HarnessError;
+
+/// Publishes groups at a fixed interval from a publisher to a subscriber, over simulated network conditions.
+#[derive(Clone, Debug)]
+pub struct Netsim {
+	/// The conditions for packets sent by the publisher.
+	pub forward: transport::MemoryConfig,
+
+	/// The conditions for packets sent by the subscriber.
+	pub reverse: transport::MemoryConfig,
+
+	/// The published track, which configures the cache, expiry and group order.
+	pub track: serve::Track,
+
+	/// The number of groups to publish.
+	pub groups: u64,
+
+	/// The delay between each group.
+	pub interval: Duration,
+
+	/// The size of each group, written as a single object.
+	pub size: usize,
+
+	/// How long to wait after the last group before reporting, so any stragglers can arrive.
+	pub linger: Duration,
+}
+
+impl Default for Netsim {
+	fn default() -> Self {
+		Self {
+			forward: Default::default(),
+			reverse: Default::default(),
+			track: serve::Track::new("netsim".to_string(), "video".to_string()),
+			groups: 100,
+			interval: Duration::from_millis(33),
+			size: 10_000,
+			linger: Duration::from_secs(1),
+		}
+	}
+}
+
+impl Netsim {
+	/// Publish every group and return what the subscriber delivered.
+	pub async fn run(self) -> Result<NetsimReport, HarnessError> {
+		let (client, server) = transport::Memory::pair_with(self.reverse, self.forward);
+		let (client, server) = (transport::Session::new(client), transport::Session::new(server));
+
+		let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+		let (publish, mut publisher) = publish?;
+		let (subscribe, mut subscriber) = subscribe?;
+
+		tokio::spawn(publish.run());
+		tokio::spawn(subscribe.run());
+
+		let namespace = self.track.namespace.clone();
+		let name = self.track.name.clone();
+
+		let (mut writer, _, reader) = serve::Tracks::new(namespace.clone()).produce();
+		let mut groups = writer.insert(self.track).ok_or(ServeError::Duplicate)?.groups()?;
+
+		tokio::spawn(async move { publisher.announce(reader).await });
+
+		let (track, track_reader) = serve::Track::new(namespace, name).produce();
+		let _subscribe = subscriber.subscribe_handle(track);
+
+		let report = Arc::new(Mutex::new(NetsimReport {
+			published: self.groups,
+			..Default::default()
+		}));
+
+		// Read every group like a player, which skips any group older than the latest.
+		let results = report.clone();
+		tokio::spawn(async move {
+			let mut groups = match track_reader.mode().await {
+				Ok(TrackReaderMode::Groups(groups)) => groups,
+				_ => return,
+			};
+
+			while let Ok(Some(mut group)) = groups.next().await {
+				let results = results.clone();
+
+				tokio::spawn(async move {
+					let res = loop {
+						match group.read_next().await {
+							Ok(Some(_)) => continue,
+							Ok(None) => break Ok(()),
+							Err(err) => break Err(err),
+						}
+					};
+
+					let mut report = results.lock().unwrap();
+					match res {
+						Ok(()) => report.delivered.push(group.group_id),
+						Err(err) => report.reset.push((group.group_id, err)),
+					}
+				});
+			}
+		});
+
+		let payload = Bytes::from(vec![0; self.size]);
+		let mut interval = tokio::time::interval(self.interval);
+
+		for _ in 0..self.groups {
+			interval.tick().await;
+
+			let mut group = groups.append(0)?;
+			group.write(payload.clone())?;
+		}
+
+		tokio::time::sleep(self.linger).await;
+
+		let mut report = report.lock().unwrap().clone();
+		report.delivered.sort_unstable();
+		report.reset.sort_unstable_by_key(|(group_id, _)| *group_id);
+
+		report.dropped = (0..report.published)
+			.filter(|group_id| {
+				report.delivered.binary_search(group_id).is_err()
+					&& !report.reset.iter().any(|(reset, _)| reset == group_id)
+			})
+			.collect();
+
+		Ok(report)
+	}
+}
+
+/// The groups received by the subscriber during a [Netsim] run.
+#[derive(Clone, Debug, Default)]
+pub struct NetsimReport {
+	/// The number of groups published.
+	pub published: u64,
+
+	/// The groups fully received, in order.
+	pub delivered: Vec<u64>,
+
+	/// The groups that started but were reset, in order.
+	pub reset: Vec<(u64, ServeError)>,
+
+	/// The groups never received, or skipped because a newer group arrived first.
+	///
+	/// This includes any groups published before the subscription started, beyond the track's cache.
+	pub dropped: Vec<u64>,
+}
+
+impl NetsimReport {
+	/// The fraction of published groups that were fully received.
+	pub fn delivery_ratio(&self) -> f64 {
+		match self.published {
+			0 => 1.0,
+			published => self.delivered.len() as f64 / published as f64,
+		}
+	}
+
+	/// Panic unless at least this fraction of groups was fully received.
+	pub fn assert_delivered(&self, ratio: f64) {
+		assert!(
+			self.delivery_ratio() >= ratio,
+			"delivered {:.2} of groups, expected at least {:.2}: {:?}",
+			self.delivery_ratio(),
+			ratio,
+			self
+		);
+	}
+
+	/// Panic unless the newest group was fully received, as a live track should prioritize it.
+	pub fn assert_latest_delivered(&self) {
+		let latest = self.published.checked_sub(1);
+		assert!(
+			latest.is_some() && self.delivered.last() == latest.as_ref(),
+			"latest group not delivered: {:?}",
+			self
+		);
+	}
+}
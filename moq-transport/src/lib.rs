@@ -6,9 +6,15 @@
 //! See the [specification](https://datatracker.ietf.org/doc/draft-ietf-moq-transport/) and [github](https://github.com/moq-wg/moq-transport) for any updates.
 pub mod coding;
 pub mod data;
+#[cfg(feature = "e2ee")]
+pub mod e2ee;
 pub mod error;
+#[cfg(all(feature = "harness", not(target_arch = "wasm32")))]
+pub mod harness;
 pub mod message;
+pub mod metrics;
 pub mod serve;
 pub mod session;
 pub mod setup;
+pub mod transport;
 pub mod watch;
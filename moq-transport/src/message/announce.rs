@@ -1,7 +1,8 @@
 use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params};
 
 /// Sent by the publisher to announce the availability of a group of tracks.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Announce {
 	/// The track namespace
 	pub namespace: String,
@@ -1,7 +1,8 @@
 use crate::coding::{Decode, DecodeError, Encode, EncodeError};
 
 /// Sent by the subscriber to reject an Announce after ANNOUNCE_OK
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct AnnounceCancel {
 	// Echo back the namespace that was reset
 	pub namespace: String,
@@ -1,7 +1,8 @@
 use crate::coding::{Decode, DecodeError, Encode, EncodeError};
 
 /// Sent by the subscriber to accept an Announce.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct AnnounceOk {
 	// Echo back the namespace that was announced.
 	// TODO Propose using an ID to save bytes.
@@ -0,0 +1,154 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+use crate::setup::{Version, Versions};
+
+use super::Message;
+
+/// Encodes and decodes control messages for a specific protocol version.
+///
+/// NOTE: Only the control stream is versioned; data streams always use the draft-04 format.
+pub trait Codec: Send + Sync {
+	fn decode(&self, r: &mut dyn bytes::Buf) -> Result<Message, DecodeError>;
+	fn encode(&self, msg: &Message, w: &mut dyn bytes::BufMut) -> Result<(), EncodeError>;
+}
+
+/// The control messages defined by [Version::DRAFT_04].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Draft04;
+
+impl Codec for Draft04 {
+	fn decode(&self, mut r: &mut dyn bytes::Buf) -> Result<Message, DecodeError> {
+		Message::decode(&mut r)
+	}
+
+	fn encode(&self, msg: &Message, mut w: &mut dyn bytes::BufMut) -> Result<(), EncodeError> {
+		msg.encode(&mut w)
+	}
+}
+
+/// Extends another codec by appending [super::SubscribeOk::start] to SUBSCRIBE_OK.
+///
+/// Control messages aren't length prefixed, so this is only used once both peers advertised [super::SUBSCRIBE_START_PARAM].
+pub struct SubscribeStartCodec(pub Arc<dyn Codec>);
+
+impl Codec for SubscribeStartCodec {
+	fn decode(&self, mut r: &mut dyn bytes::Buf) -> Result<Message, DecodeError> {
+		let mut msg = self.0.decode(r)?;
+		if let Message::SubscribeOk(ok) = &mut msg {
+			ok.decode_start(&mut r)?;
+		}
+
+		Ok(msg)
+	}
+
+	fn encode(&self, msg: &Message, mut w: &mut dyn bytes::BufMut) -> Result<(), EncodeError> {
+		self.0.encode(msg, w)?;
+		if let Message::SubscribeOk(ok) = msg {
+			ok.encode_start(&mut w)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// The protocol versions we support, each with a [Codec], in preferred order.
+///
+/// The default only supports [Version::DRAFT_04].
+#[derive(Clone)]
+pub struct Codecs {
+	codecs: HashMap<Version, Arc<dyn Codec>>,
+	order: Vec<Version>,
+}
+
+impl Codecs {
+	/// Returns an empty table, which can't negotiate anything until a version is registered.
+	pub fn new() -> Self {
+		Self {
+			codecs: HashMap::new(),
+			order: Vec::new(),
+		}
+	}
+
+	/// Support a version, preferred less than any version already registered.
+	///
+	/// Registering an existing version replaces its codec without changing the preference.
+	pub fn register<C: Codec + 'static>(&mut self, version: Version, codec: C) {
+		if self.codecs.insert(version, Arc::new(codec)).is_none() {
+			self.order.push(version);
+		}
+	}
+
+	pub fn get(&self, version: Version) -> Option<Arc<dyn Codec>> {
+		self.codecs.get(&version).cloned()
+	}
+
+	/// The supported versions in preferred order, as sent in the client SETUP.
+	pub fn versions(&self) -> Versions {
+		self.order.clone().into()
+	}
+
+	/// Choose the version used by the session, as the server.
+	///
+	/// The client's preference wins, since it's the one offering the versions.
+	pub fn negotiate(&self, offered: &Versions) -> Option<Version> {
+		offered
+			.iter()
+			.find(|version| self.codecs.contains_key(version))
+			.copied()
+	}
+}
+
+impl Default for Codecs {
+	fn default() -> Self {
+		let mut codecs = Self::new();
+		codecs.register(Version::DRAFT_04, Draft04);
+		codecs
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::message::SubscribeOk;
+
+	#[test]
+	fn negotiate() {
+		let custom = Version(0xff0000aa);
+
+		let mut codecs = Codecs::default();
+		codecs.register(custom, Draft04);
+		assert_eq!(codecs.versions(), [Version::DRAFT_04, custom].into());
+
+		// The client's order wins.
+		assert_eq!(codecs.negotiate(&[custom, Version::DRAFT_04].into()), Some(custom));
+		assert_eq!(
+			codecs.negotiate(&[Version::DRAFT_03, Version::DRAFT_04].into()),
+			Some(Version::DRAFT_04)
+		);
+		assert_eq!(codecs.negotiate(&[Version::DRAFT_03].into()), None);
+	}
+
+	#[test]
+	fn subscribe_start() {
+		let ok = Message::SubscribeOk(SubscribeOk {
+			id: 1,
+			expires: None,
+			latest: Some((5, 0)),
+			start: Some(3),
+		});
+
+		// Draft-04 drops the start, so the message is the same as before it existed.
+		let mut draft = Vec::new();
+		Draft04.encode(&ok, &mut draft).unwrap();
+		assert_eq!(draft, [0x04, 0x01, 0x00, 0x01, 0x05, 0x00]);
+
+		let codec = SubscribeStartCodec(Arc::new(Draft04));
+		let mut buf = Vec::new();
+		codec.encode(&ok, &mut buf).unwrap();
+		assert_eq!(buf[..draft.len()], draft[..]);
+
+		let decoded = codec.decode(&mut buf.as_slice()).unwrap();
+		assert_eq!(decoded, ok);
+	}
+}
@@ -3,6 +3,7 @@ use crate::coding::{Decode, DecodeError, Encode, EncodeError};
 /// Filter Types
 /// https://www.ietf.org/archive/id/draft-ietf-moq-transport-04.html#name-filter-types
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum FilterType {
 	LatestGroup = 0x1,
 	LatestObject = 0x2,
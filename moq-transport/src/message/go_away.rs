@@ -1,7 +1,8 @@
 use crate::coding::{Decode, DecodeError, Encode, EncodeError};
 
 /// Sent by the server to indicate that the client should connect to a different server.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct GoAway {
 	pub url: String,
 }
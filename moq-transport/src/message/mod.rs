@@ -11,6 +11,7 @@
 //! - [SubscribeOk]
 //! - [SubscribeError]
 //! - [SubscribeReset]
+//! - [Redirect]
 //! - [Object]
 //!
 //! Messages sent by the subscriber:
@@ -18,6 +19,12 @@
 //! - [Unsubscribe]
 //! - [AnnounceOk]
 //! - [AnnounceError]
+//! - [SubscribeNamespace]
+//! - [UnsubscribeNamespace]
+//!
+//! Messages sent by either endpoint:
+//! - [Ping]
+//! - [Pong]
 //!
 //! Example flow:
 //! ```test
@@ -35,12 +42,17 @@ mod announce;
 mod announce_cancel;
 mod announce_error;
 mod announce_ok;
+mod codec;
 mod filter_type;
 mod go_away;
+mod ping;
+mod pong;
 mod publisher;
+mod redirect;
 mod subscribe;
 mod subscribe_done;
 mod subscribe_error;
+mod subscribe_namespace;
 mod subscribe_ok;
 mod subscribe_update;
 mod subscriber;
@@ -48,17 +60,23 @@ mod track_status;
 mod track_status_request;
 mod unannounce;
 mod unsubscribe;
+mod unsubscribe_namespace;
 
 pub use announce::*;
 pub use announce_cancel::*;
 pub use announce_error::*;
 pub use announce_ok::*;
+pub use codec::*;
 pub use filter_type::*;
 pub use go_away::*;
+pub use ping::*;
+pub use pong::*;
 pub use publisher::*;
+pub use redirect::*;
 pub use subscribe::*;
 pub use subscribe_done::*;
 pub use subscribe_error::*;
+pub use subscribe_namespace::*;
 pub use subscribe_ok::*;
 pub use subscribe_update::*;
 pub use subscriber::*;
@@ -66,6 +84,7 @@ pub use track_status::*;
 pub use track_status_request::*;
 pub use unannounce::*;
 pub use unsubscribe::*;
+pub use unsubscribe_namespace::*;
 
 use crate::coding::{Decode, DecodeError, Encode, EncodeError};
 use std::fmt;
@@ -75,7 +94,8 @@ use std::fmt;
 macro_rules! message_types {
     {$($name:ident = $val:expr,)*} => {
 		/// All supported message types.
-		#[derive(Clone)]
+		#[derive(Clone, PartialEq)]
+		#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 		pub enum Message {
 			$($name($name)),*
 		}
@@ -89,6 +109,7 @@ macro_rules! message_types {
 						let msg = $name::decode(r)?;
 						Ok(Self::$name(msg))
 					})*
+					// There's no length prefix, so an unknown message can't be skipped.
 					_ => Err(DecodeError::InvalidMessage(t)),
 				}
 			}
@@ -166,6 +187,8 @@ message_types! {
 	AnnounceOk = 0x7,
 	AnnounceError = 0x8,
 	AnnounceCancel = 0xc,
+	SubscribeNamespace = 0x11,
+	UnsubscribeNamespace = 0x14,
 
 	// TRACK_STATUS_REQUEST, sent by subscriber
 	TrackStatusRequest = 0xd,
@@ -175,11 +198,20 @@ message_types! {
 
 	// Misc
 	GoAway = 0x10,
+
+	// Keepalive, sent by either endpoint when negotiated during setup.
+	// NOTE: These aren't in the draft, so they use a value unlikely to be assigned.
+	Ping = 0x3f00,
+	Pong = 0x3f01,
+
+	// Sent by the publisher in response to SUBSCRIBE, also not in the draft.
+	Redirect = 0x3f02,
 }
 
 /// Track Status Codes
 /// https://www.ietf.org/archive/id/draft-ietf-moq-transport-04.html#name-track_status
 #[derive(Clone, Debug, PartialEq, Copy)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum TrackStatusCode {
 	// 0x00: The track is in progress, and subsequent fields contain the highest group and object ID for that track.
 	InProgress = 0x00,
@@ -217,3 +249,325 @@ impl Encode for TrackStatusCode {
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::coding::Params;
+	use crate::setup;
+	use bytes::BytesMut;
+
+	// A xorshift generator, so any failure can be reproduced from the seed.
+	struct Random(u64);
+
+	impl Random {
+		fn u64(&mut self) -> u64 {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 7;
+			self.0 ^= self.0 << 17;
+			self.0
+		}
+
+		fn below(&mut self, n: u64) -> u64 {
+			self.u64() % n
+		}
+
+		fn bool(&mut self) -> bool {
+			self.below(2) == 1
+		}
+
+		// A varint, biased towards the boundaries between encoded sizes.
+		fn varint(&mut self) -> u64 {
+			const EDGES: [u64; 8] = [0, 63, 64, 16383, 16384, (1 << 30) - 1, 1 << 30, (1 << 62) - 1];
+
+			match self.below(4) {
+				0 => EDGES[self.below(EDGES.len() as u64) as usize],
+				_ => self.u64() >> (2 + self.below(62)),
+			}
+		}
+
+		fn option<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> Option<T> {
+			self.bool().then(|| f(self))
+		}
+
+		fn string(&mut self) -> String {
+			const CHARS: [&str; 6] = ["a", "/", " ", "\0", "é", "🦀"];
+			(0..self.below(16))
+				.map(|_| CHARS[self.below(CHARS.len() as u64) as usize])
+				.collect()
+		}
+
+		// Parameters with any key except those the message decodes itself.
+		fn params(&mut self, reserved: &[u64]) -> Params {
+			let mut params = Params::new();
+			for _ in 0..self.below(4) {
+				let key = self.varint();
+				if !reserved.contains(&key) {
+					let value = (0..self.below(8)).map(|_| self.u64() as u8).collect();
+					params.0.insert(key, value);
+				}
+			}
+			params
+		}
+
+		fn location(&mut self) -> SubscribeLocation {
+			match self.below(4) {
+				0 => SubscribeLocation::None,
+				1 => SubscribeLocation::Absolute(self.varint()),
+				2 => SubscribeLocation::Latest(self.varint()),
+				_ => SubscribeLocation::Future(self.varint()),
+			}
+		}
+
+		fn pair(&mut self) -> SubscribePair {
+			let group = self.location();
+
+			// You can't have an object without a group.
+			let object = match group {
+				SubscribeLocation::None => SubscribeLocation::None,
+				_ => self.location(),
+			};
+
+			SubscribePair { group, object }
+		}
+
+		// A filter with only the start and end it encodes.
+		fn filter(&mut self) -> (FilterType, Option<SubscribePair>, Option<SubscribePair>) {
+			match self.below(4) {
+				0 => (FilterType::LatestGroup, None, None),
+				1 => (FilterType::LatestObject, None, None),
+				2 => (FilterType::AbsoluteStart, Some(self.pair()), None),
+				_ => (FilterType::AbsoluteRange, Some(self.pair()), Some(self.pair())),
+			}
+		}
+
+		fn status(&mut self) -> TrackStatusCode {
+			match self.below(5) {
+				0 => TrackStatusCode::InProgress,
+				1 => TrackStatusCode::DoesNotExist,
+				2 => TrackStatusCode::NotYetBegun,
+				3 => TrackStatusCode::Finished,
+				_ => TrackStatusCode::Relay,
+			}
+		}
+
+		fn role(&mut self) -> setup::Role {
+			match self.below(3) {
+				0 => setup::Role::Publisher,
+				1 => setup::Role::Subscriber,
+				_ => setup::Role::Both,
+			}
+		}
+
+		// A canonical message of every type, which must survive a round trip unchanged.
+		fn messages(&mut self) -> Vec<Message> {
+			let (filter_type, start, end) = self.filter();
+			let subscribe = Subscribe {
+				id: self.varint(),
+				track_alias: self.varint(),
+				track_namespace: self.string(),
+				track_name: self.string(),
+				filter_type,
+				start,
+				end,
+				params: self.params(&[]),
+			};
+
+			let (filter_type, start, end) = self.filter();
+			let subscribe_update = SubscribeUpdate {
+				id: self.varint(),
+				track_alias: self.varint(),
+				track_namespace: self.string(),
+				track_name: self.string(),
+				filter_type,
+				start,
+				end,
+				priority: self.option(Self::varint),
+				paused: self.option(Self::bool),
+				params: self.params(&[SUBSCRIBE_PRIORITY_PARAM, SUBSCRIBE_PAUSED_PARAM]),
+			};
+
+			vec![
+				subscribe_update.into(),
+				subscribe.into(),
+				Unsubscribe { id: self.varint() }.into(),
+				SubscribeOk {
+					id: self.varint(),
+					// Zero means no expiry.
+					expires: self.option(|r| r.varint().max(1)),
+					latest: self.option(|r| (r.varint(), r.varint())),
+					// Only encoded by SubscribeStartCodec.
+					start: None,
+				}
+				.into(),
+				SubscribeError {
+					id: self.varint(),
+					code: self.varint(),
+					reason: self.string(),
+					alias: self.varint(),
+				}
+				.into(),
+				SubscribeDone {
+					id: self.varint(),
+					code: self.varint(),
+					reason: self.string(),
+					last: self.option(|r| (r.varint(), r.varint())),
+				}
+				.into(),
+				Announce {
+					namespace: self.string(),
+					params: self.params(&[]),
+				}
+				.into(),
+				Unannounce {
+					namespace: self.string(),
+				}
+				.into(),
+				AnnounceOk {
+					namespace: self.string(),
+				}
+				.into(),
+				AnnounceError {
+					namespace: self.string(),
+					code: self.varint(),
+					reason: self.string(),
+				}
+				.into(),
+				AnnounceCancel {
+					namespace: self.string(),
+				}
+				.into(),
+				SubscribeNamespace {
+					namespace_prefix: self.string(),
+					params: self.params(&[]),
+				}
+				.into(),
+				UnsubscribeNamespace {
+					namespace_prefix: self.string(),
+				}
+				.into(),
+				TrackStatusRequest {
+					track_namespace: self.string(),
+					track_name: self.string(),
+				}
+				.into(),
+				TrackStatus {
+					track_namespace: self.string(),
+					track_name: self.string(),
+					status_code: self.status(),
+					last_group_id: self.varint(),
+					last_object_id: self.varint(),
+				}
+				.into(),
+				GoAway { url: self.string() }.into(),
+				Ping {
+					sequence: self.varint(),
+				}
+				.into(),
+				Pong {
+					sequence: self.varint(),
+				}
+				.into(),
+				Redirect {
+					id: self.varint(),
+					url: self.string(),
+				}
+				.into(),
+			]
+		}
+
+		fn client(&mut self) -> setup::Client {
+			setup::Client {
+				versions: (0..self.below(4))
+					.map(|_| setup::Version(self.varint()))
+					.collect::<Vec<_>>()
+					.into(),
+				role: self.role(),
+				// The role and path are reserved.
+				params: self.params(&[0, 1]),
+			}
+		}
+
+		fn server(&mut self) -> setup::Server {
+			setup::Server {
+				version: setup::Version(self.varint()),
+				role: self.role(),
+				params: self.params(&[0, 1]),
+			}
+		}
+	}
+
+	fn encode<T: Encode>(msg: &T) -> BytesMut {
+		let mut buf = BytesMut::new();
+		msg.encode(&mut buf).unwrap();
+		buf
+	}
+
+	fn roundtrip<T: Encode + Decode + PartialEq + fmt::Debug>(msg: T) {
+		let mut buf = encode(&msg);
+		let decoded = T::decode(&mut buf).unwrap();
+
+		assert!(buf.is_empty(), "not fully consumed: {:?}", msg);
+		assert_eq!(decoded, msg);
+	}
+
+	#[test]
+	fn roundtrip_messages() {
+		let mut rng = Random(0x9e3779b97f4a7c15);
+
+		// Make sure a new message type isn't forgotten.
+		assert_eq!(rng.messages().len(), 19);
+
+		for _ in 0..1000 {
+			for msg in rng.messages() {
+				roundtrip(msg);
+			}
+		}
+	}
+
+	#[test]
+	fn roundtrip_setup() {
+		let mut rng = Random(0x9e3779b97f4a7c15);
+
+		for _ in 0..1000 {
+			roundtrip(rng.client());
+			roundtrip(rng.server());
+		}
+	}
+
+	// Malformed input must return an error rather than panic.
+	#[test]
+	fn malformed() {
+		let mut rng = Random(0x2545f4914f6cdd1d);
+
+		for _ in 0..1000 {
+			let mut inputs: Vec<_> = rng.messages().iter().map(encode).collect();
+			inputs.push(encode(&rng.client()));
+			inputs.push(encode(&rng.server()));
+
+			for mut buf in inputs {
+				// Corrupt a few bytes, then truncate, which covers every missing field.
+				for _ in 0..rng.below(3) {
+					let index = rng.below(buf.len() as u64) as usize;
+					buf[index] = rng.u64() as u8;
+				}
+
+				let size = rng.below(buf.len() as u64 + 1) as usize;
+				let buf = &buf[..size];
+
+				Message::decode(&mut &buf[..]).ok();
+				setup::Client::decode(&mut &buf[..]).ok();
+				setup::Server::decode(&mut &buf[..]).ok();
+			}
+		}
+
+		// Random bytes, most of which will be an unknown message type.
+		for _ in 0..10000 {
+			let buf: Vec<u8> = (0..rng.below(64)).map(|_| rng.u64() as u8).collect();
+
+			Message::decode(&mut &buf[..]).ok();
+			setup::Client::decode(&mut &buf[..]).ok();
+			setup::Server::decode(&mut &buf[..]).ok();
+		}
+	}
+}
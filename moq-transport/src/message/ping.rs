@@ -0,0 +1,25 @@
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+/// Sent by either endpoint to check the peer is alive, answered with a [super::Pong].
+///
+/// NOTE: This isn't part of the draft, so it's only sent when the peer advertised support during setup.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Ping {
+	// Echoed in the PONG, so the round trip can be measured.
+	pub sequence: u64,
+}
+
+impl Decode for Ping {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		let sequence = u64::decode(r)?;
+		Ok(Self { sequence })
+	}
+}
+
+impl Encode for Ping {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.sequence.encode(w)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,23 @@
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+/// Sent in response to a [super::Ping].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Pong {
+	// The sequence of the PING being answered.
+	pub sequence: u64,
+}
+
+impl Decode for Pong {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		let sequence = u64::decode(r)?;
+		Ok(Self { sequence })
+	}
+}
+
+impl Encode for Pong {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.sequence.encode(w)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,30 @@
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+/// Sent by the publisher instead of SubscribeOk, telling the subscriber the track is available from another origin.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Redirect {
+	// The ID for this subscription.
+	pub id: u64,
+
+	// The URL of the origin to subscribe to instead.
+	pub url: String,
+}
+
+impl Decode for Redirect {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = u64::decode(r)?;
+		let url = String::decode(r)?;
+
+		Ok(Self { id, url })
+	}
+}
+
+impl Encode for Redirect {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w)?;
+		self.url.encode(w)?;
+
+		Ok(())
+	}
+}
@@ -4,7 +4,8 @@ use crate::message::FilterType;
 /// Sent by the subscriber to request all future objects for the given track.
 ///
 /// Objects will use the provided ID instead of the full track name, to save bytes.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Subscribe {
 	/// The subscription ID
 	pub id: u64,
@@ -96,16 +97,16 @@ impl Encode for Subscribe {
 
 		self.filter_type.encode(w)?;
 
-		if self.filter_type == FilterType::AbsoluteStart || self.filter_type == FilterType::AbsoluteRange {
-			if self.start.is_none() || self.end.is_none() {
-				return Err(EncodeError::MissingField);
-			}
-			if let Some(start) = &self.start {
-				start.encode(w)?;
+		// NOTE: This must match the decoder, which only expects an end for AbsoluteRange.
+		match self.filter_type {
+			FilterType::AbsoluteStart => {
+				self.start.as_ref().ok_or(EncodeError::MissingField)?.encode(w)?;
 			}
-			if let Some(end) = &self.end {
-				end.encode(w)?;
+			FilterType::AbsoluteRange => {
+				self.start.as_ref().ok_or(EncodeError::MissingField)?.encode(w)?;
+				self.end.as_ref().ok_or(EncodeError::MissingField)?.encode(w)?;
 			}
+			_ => {}
 		}
 
 		self.params.encode(w)?;
@@ -115,6 +116,7 @@ impl Encode for Subscribe {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct SubscribePair {
 	pub group: SubscribeLocation,
 	pub object: SubscribeLocation,
@@ -139,6 +141,7 @@ impl Encode for SubscribePair {
 
 /// Signal where the subscription should begin, relative to the current cache.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum SubscribeLocation {
 	None,
 	Absolute(u64),
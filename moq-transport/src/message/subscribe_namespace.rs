@@ -0,0 +1,33 @@
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params};
+
+/// Sent by the subscriber to only receive announcements for namespaces starting with the prefix.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SubscribeNamespace {
+	/// The namespace prefix, such as "room/123/"
+	pub namespace_prefix: String,
+
+	/// Optional parameters
+	pub params: Params,
+}
+
+impl Decode for SubscribeNamespace {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		let namespace_prefix = String::decode(r)?;
+		let params = Params::decode(r)?;
+
+		Ok(Self {
+			namespace_prefix,
+			params,
+		})
+	}
+}
+
+impl Encode for SubscribeNamespace {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.namespace_prefix.encode(w)?;
+		self.params.encode(w)?;
+
+		Ok(())
+	}
+}
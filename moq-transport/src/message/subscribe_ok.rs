@@ -1,7 +1,11 @@
 use crate::coding::{Decode, DecodeError, Encode, EncodeError};
 
+/// The setup parameter advertising support for [SubscribeOk::start], see [super::SubscribeStartCodec].
+pub const SUBSCRIBE_START_PARAM: u64 = 0x3f01;
+
 /// Sent by the publisher to accept a Subscribe.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct SubscribeOk {
 	/// The ID for this subscription.
 	pub id: u64,
@@ -11,6 +15,12 @@ pub struct SubscribeOk {
 
 	/// The latest group and object for the track.
 	pub latest: Option<(u64, u64)>,
+
+	/// The first group that will be delivered, when the subscription starts from the cache.
+	///
+	/// Not part of draft-04, so it's only sent when both peers advertised [SUBSCRIBE_START_PARAM].
+	#[cfg_attr(feature = "arbitrary", arbitrary(value = None))]
+	pub start: Option<u64>,
 }
 
 impl Decode for SubscribeOk {
@@ -29,7 +39,12 @@ impl Decode for SubscribeOk {
 			_ => return Err(DecodeError::InvalidValue),
 		};
 
-		Ok(Self { id, expires, latest })
+		Ok(Self {
+			id,
+			expires,
+			latest,
+			start: None,
+		})
 	}
 }
 
@@ -54,3 +69,35 @@ impl Encode for SubscribeOk {
 		Ok(())
 	}
 }
+
+impl SubscribeOk {
+	// Decode the start appended by [super::SubscribeStartCodec].
+	pub(super) fn decode_start<R: bytes::Buf>(&mut self, r: &mut R) -> Result<(), DecodeError> {
+		Self::decode_remaining(r, 1)?;
+
+		self.start = match r.get_u8() {
+			0 => None,
+			1 => Some(u64::decode(r)?),
+			_ => return Err(DecodeError::InvalidValue),
+		};
+
+		Ok(())
+	}
+
+	// Encode the start appended by [super::SubscribeStartCodec].
+	pub(super) fn encode_start<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		Self::encode_remaining(w, 1)?;
+
+		match self.start {
+			Some(start) => {
+				w.put_u8(1);
+				start.encode(w)?;
+			}
+			None => {
+				w.put_u8(0);
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -2,10 +2,19 @@ use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params};
 use crate::message::subscribe::{SubscribeLocation, SubscribePair};
 use crate::message::FilterType;
 
-/// Sent by the subscriber to request all future objects for the given track.
+/// The parameter used to carry the subscriber priority.
+/// NOTE: Draft-04 has no priority field, so we smuggle it in as a parameter.
+pub const SUBSCRIBE_PRIORITY_PARAM: u64 = 0x20;
+
+/// The parameter used to pause (1) or resume (0) the subscription.
+/// NOTE: This isn't part of draft-04 either, so a publisher that doesn't understand it keeps sending.
+pub const SUBSCRIBE_PAUSED_PARAM: u64 = 0x21;
+
+/// Sent by the subscriber to modify an existing Subscribe.
 ///
 /// Objects will use the provided ID instead of the full track name, to save bytes.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct SubscribeUpdate {
 	/// The subscription ID
 	pub id: u64,
@@ -22,6 +31,12 @@ pub struct SubscribeUpdate {
 	pub start: Option<SubscribePair>, // TODO: Make optional
 	pub end: Option<SubscribePair>, // TODO: Make optional
 
+	/// The new priority for the subscription, applied to subsequent groups.
+	pub priority: Option<u64>,
+
+	/// Pause or resume the delivery of new groups, if set.
+	pub paused: Option<bool>,
+
 	/// Optional parameters
 	pub params: Params,
 }
@@ -73,7 +88,9 @@ impl Decode for SubscribeUpdate {
 
 		// NOTE: There's some more location restrictions in the draft, but they're enforced at a higher level.
 
-		let params = Params::decode(r)?;
+		let mut params = Params::decode(r)?;
+		let priority = params.get::<u64>(SUBSCRIBE_PRIORITY_PARAM)?;
+		let paused = params.get::<u64>(SUBSCRIBE_PAUSED_PARAM)?.map(|paused| paused != 0);
 
 		Ok(Self {
 			id,
@@ -83,6 +100,8 @@ impl Decode for SubscribeUpdate {
 			filter_type,
 			start,
 			end,
+			priority,
+			paused,
 			params,
 		})
 	}
@@ -97,19 +116,25 @@ impl Encode for SubscribeUpdate {
 
 		self.filter_type.encode(w)?;
 
+		// Only an absolute range has an end, matching decode.
 		if self.filter_type == FilterType::AbsoluteStart || self.filter_type == FilterType::AbsoluteRange {
-			if self.start.is_none() || self.end.is_none() {
-				return Err(EncodeError::MissingField);
-			}
-			if let Some(start) = &self.start {
-				start.encode(w)?;
-			}
-			if let Some(end) = &self.end {
-				end.encode(w)?;
-			}
+			self.start.as_ref().ok_or(EncodeError::MissingField)?.encode(w)?;
+		}
+
+		if self.filter_type == FilterType::AbsoluteRange {
+			self.end.as_ref().ok_or(EncodeError::MissingField)?.encode(w)?;
+		}
+
+		let mut params = self.params.clone();
+		if let Some(priority) = self.priority {
+			params.set(SUBSCRIBE_PRIORITY_PARAM, priority)?;
+		}
+
+		if let Some(paused) = self.paused {
+			params.set(SUBSCRIBE_PAUSED_PARAM, paused as u64)?;
 		}
 
-		self.params.encode(w)?;
+		params.encode(w)?;
 
 		Ok(())
 	}
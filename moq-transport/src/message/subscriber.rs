@@ -48,6 +48,8 @@ subscriber_msgs! {
 	AnnounceOk,
 	AnnounceError,
 	AnnounceCancel,
+	SubscribeNamespace,
+	UnsubscribeNamespace,
 	Subscribe,
 	Unsubscribe,
 	SubscribeUpdate,
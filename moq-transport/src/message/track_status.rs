@@ -1,7 +1,8 @@
 use super::TrackStatusCode;
 use crate::coding::{Decode, DecodeError, Encode, EncodeError};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct TrackStatus {
 	/// Track Namespace
 	pub track_namespace: String,
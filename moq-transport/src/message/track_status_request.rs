@@ -1,6 +1,7 @@
 use crate::coding::{Decode, DecodeError, Encode, EncodeError};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct TrackStatusRequest {
 	/// Track Namespace
 	pub track_namespace: String,
@@ -0,0 +1,25 @@
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+/// Sent by the subscriber to remove a prefix added by [super::SubscribeNamespace].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct UnsubscribeNamespace {
+	// Echo back the prefix that was subscribed
+	pub namespace_prefix: String,
+}
+
+impl Decode for UnsubscribeNamespace {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		let namespace_prefix = String::decode(r)?;
+
+		Ok(Self { namespace_prefix })
+	}
+}
+
+impl Encode for UnsubscribeNamespace {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.namespace_prefix.encode(w)?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,152 @@
+//! Metrics reported via the [metrics](https://docs.rs/metrics) facade, when the `metrics` feature is enabled.
+//!
+//! Install any recorder, such as `metrics-exporter-prometheus`, to collect them.
+//! Everything except [SESSIONS] is labelled with the `broadcast` and `track` names.
+//! Each metric is a no-op when the feature is disabled.
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
+use std::time;
+
+/// A gauge of the sessions currently running.
+pub const SESSIONS: &str = "moq_sessions";
+
+/// A gauge of the subscriptions currently being served.
+pub const SUBSCRIPTIONS: &str = "moq_subscriptions";
+
+/// A counter of the groups sent in full.
+pub const GROUPS_SENT: &str = "moq_groups_sent_total";
+
+/// A counter of the groups reset before they were sent in full.
+pub const GROUPS_DROPPED: &str = "moq_groups_dropped_total";
+
+/// A counter of the payload bytes sent.
+pub const BYTES_SENT: &str = "moq_bytes_sent_total";
+
+/// A counter of the bytes received on data streams.
+pub const BYTES_RECEIVED: &str = "moq_bytes_received_total";
+
+/// A histogram of the seconds from opening a group's stream until it's finished.
+pub const GROUP_DURATION: &str = "moq_group_duration_seconds";
+
+/// A histogram of the seconds spent waiting to open a stream.
+pub const STREAM_OPEN: &str = "moq_stream_open_seconds";
+
+/// Register a description of each metric with the installed recorder.
+#[cfg(feature = "metrics")]
+pub fn describe() {
+	use metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+
+	describe_gauge!(SESSIONS, "The sessions currently running.");
+	describe_gauge!(SUBSCRIPTIONS, "The subscriptions currently being served.");
+	describe_counter!(GROUPS_SENT, "The groups sent in full.");
+	describe_counter!(GROUPS_DROPPED, "The groups reset before they were sent in full.");
+	describe_counter!(BYTES_SENT, Unit::Bytes, "The payload bytes sent.");
+	describe_counter!(BYTES_RECEIVED, Unit::Bytes, "The bytes received on data streams.");
+	describe_histogram!(GROUP_DURATION, Unit::Seconds, "The time to send each group.");
+	describe_histogram!(STREAM_OPEN, Unit::Seconds, "The time spent waiting to open a stream.");
+}
+
+// Decrements the gauge when dropped.
+pub(crate) struct Active {
+	#[cfg(feature = "metrics")]
+	gauge: metrics::Gauge,
+}
+
+impl Active {
+	pub fn session() -> Self {
+		Self {
+			#[cfg(feature = "metrics")]
+			gauge: Self::increment(metrics::gauge!(SESSIONS)),
+		}
+	}
+
+	#[cfg(feature = "metrics")]
+	fn increment(gauge: metrics::Gauge) -> metrics::Gauge {
+		gauge.increment(1.0);
+		gauge
+	}
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for Active {
+	fn drop(&mut self) {
+		self.gauge.decrement(1.0);
+	}
+}
+
+// The handles for a single track, created once so each update is cheap.
+#[cfg(feature = "metrics")]
+struct TrackHandles {
+	subscriptions: metrics::Gauge,
+	groups_sent: metrics::Counter,
+	groups_dropped: metrics::Counter,
+	bytes_sent: metrics::Counter,
+	bytes_received: metrics::Counter,
+	group_duration: metrics::Histogram,
+	stream_open: metrics::Histogram,
+}
+
+/// The metrics for a single track.
+#[derive(Clone)]
+pub(crate) struct TrackMetrics {
+	#[cfg(feature = "metrics")]
+	handles: Arc<TrackHandles>,
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+impl TrackMetrics {
+	pub fn new(broadcast: &str, track: &str) -> Self {
+		#[cfg(feature = "metrics")]
+		let handles = {
+			let labels = [("broadcast", broadcast.to_string()), ("track", track.to_string())];
+
+			Arc::new(TrackHandles {
+				subscriptions: metrics::gauge!(SUBSCRIPTIONS, &labels),
+				groups_sent: metrics::counter!(GROUPS_SENT, &labels),
+				groups_dropped: metrics::counter!(GROUPS_DROPPED, &labels),
+				bytes_sent: metrics::counter!(BYTES_SENT, &labels),
+				bytes_received: metrics::counter!(BYTES_RECEIVED, &labels),
+				group_duration: metrics::histogram!(GROUP_DURATION, &labels),
+				stream_open: metrics::histogram!(STREAM_OPEN, &labels),
+			})
+		};
+
+		Self {
+			#[cfg(feature = "metrics")]
+			handles,
+		}
+	}
+
+	pub fn subscribed(&self) -> Active {
+		Active {
+			#[cfg(feature = "metrics")]
+			gauge: Active::increment(self.handles.subscriptions.clone()),
+		}
+	}
+
+	pub fn stream_opened(&self, elapsed: time::Duration) {
+		#[cfg(feature = "metrics")]
+		self.handles.stream_open.record(elapsed.as_secs_f64());
+	}
+
+	pub fn group_finished(&self, elapsed: time::Duration, reset: bool) {
+		#[cfg(feature = "metrics")]
+		match reset {
+			true => self.handles.groups_dropped.increment(1),
+			false => {
+				self.handles.groups_sent.increment(1);
+				self.handles.group_duration.record(elapsed.as_secs_f64());
+			}
+		}
+	}
+
+	pub fn sent(&self, size: usize) {
+		#[cfg(feature = "metrics")]
+		self.handles.bytes_sent.increment(size as u64);
+	}
+
+	pub fn received(&self, size: usize) {
+		#[cfg(feature = "metrics")]
+		self.handles.bytes_received.increment(size as u64);
+	}
+}
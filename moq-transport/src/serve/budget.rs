@@ -0,0 +1,249 @@
+//! A memory budget shared between tracks, split into a [Budget] and per-group handles.
+//!
+//! Each cached group is registered with the [Budget] and the size of each object is added as it's created.
+//! When the total exceeds the limit, the least important groups are evicted across all tracks:
+//! the largest priority value first (sent last), then the oldest.
+//! A group that's still being written is never evicted, so the limit may be exceeded until it's finished.
+//!
+//! Evicted groups are removed from the cache and closed with [ServeError::Evicted] so readers are notified.
+use std::{
+	fmt,
+	sync::{Arc, Mutex},
+};
+
+use super::ServeError;
+
+type Evict = Box<dyn FnOnce(ServeError) + Send>;
+
+struct BudgetEntry {
+	id: u64,
+	priority: u64,
+	size: usize,
+	evict: Evict,
+
+	// Set while the group's writer exists, so it can't be evicted mid-write.
+	writing: bool,
+}
+
+struct BudgetState {
+	limit: usize,
+	used: usize,
+	entries: Vec<BudgetEntry>,
+	next: u64,
+}
+
+/// A limit on the number of bytes cached, shared between tracks.
+///
+/// This can be cloned to share the same budget between multiple tracks or sessions.
+#[derive(Clone)]
+pub struct Budget {
+	state: Arc<Mutex<BudgetState>>,
+}
+
+impl Budget {
+	/// Create a budget that evicts cached groups when more than `limit` bytes are used.
+	pub fn new(limit: usize) -> Self {
+		let state = BudgetState {
+			limit,
+			used: 0,
+			entries: Vec::new(),
+			next: 0,
+		};
+
+		Self {
+			state: Arc::new(Mutex::new(state)),
+		}
+	}
+
+	/// The maximum number of bytes that may be cached.
+	pub fn limit(&self) -> usize {
+		self.state.lock().unwrap().limit
+	}
+
+	/// The number of bytes currently cached.
+	pub fn used(&self) -> usize {
+		self.state.lock().unwrap().used
+	}
+
+	// Register a cached group, calling evict if it's chosen to free memory.
+	pub(super) fn register<F: FnOnce(ServeError) + Send + 'static>(
+		&self,
+		priority: u64,
+		evict: F,
+	) -> (BudgetGuard, BudgetHandle) {
+		let mut state = self.state.lock().unwrap();
+
+		let id = state.next;
+		state.next += 1;
+
+		state.entries.push(BudgetEntry {
+			id,
+			priority,
+			size: 0,
+			evict: Box::new(evict),
+			writing: true,
+		});
+
+		let guard = BudgetGuard {
+			budget: self.clone(),
+			id,
+		};
+		let handle = BudgetHandle {
+			budget: self.clone(),
+			id,
+		};
+
+		(guard, handle)
+	}
+
+	fn add(&self, id: u64, size: usize) {
+		let evicted = {
+			let mut state = self.state.lock().unwrap();
+
+			match state.entries.iter_mut().find(|entry| entry.id == id) {
+				Some(entry) => entry.size += size,
+				None => return, // Already evicted or removed.
+			}
+
+			state.used += size;
+
+			let mut evicted = Vec::new();
+			while state.used > state.limit {
+				// Evict the least important group: the largest priority value, then the oldest.
+				let index = match state
+					.entries
+					.iter()
+					.enumerate()
+					.filter(|(_, entry)| !entry.writing)
+					.max_by_key(|(_, entry)| (entry.priority, std::cmp::Reverse(entry.id)))
+				{
+					Some((index, _)) => index,
+					None => break,
+				};
+
+				let entry = state.entries.swap_remove(index);
+				state.used -= entry.size;
+				evicted.push(entry.evict);
+			}
+
+			evicted
+		};
+
+		// NOTE: Evict after releasing the lock, since it will acquire the cache locks.
+		for evict in evicted {
+			evict(ServeError::Evicted);
+		}
+	}
+
+	fn finish(&self, id: u64) {
+		let mut state = self.state.lock().unwrap();
+		if let Some(entry) = state.entries.iter_mut().find(|entry| entry.id == id) {
+			entry.writing = false;
+		}
+	}
+
+	fn remove(&self, id: u64) {
+		let mut state = self.state.lock().unwrap();
+		if let Some(index) = state.entries.iter().position(|entry| entry.id == id) {
+			let entry = state.entries.swap_remove(index);
+			state.used -= entry.size;
+		}
+	}
+}
+
+impl fmt::Debug for Budget {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let state = self.state.lock().unwrap();
+		f.debug_struct("Budget")
+			.field("limit", &state.limit)
+			.field("used", &state.used)
+			.finish()
+	}
+}
+
+impl PartialEq for Budget {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.state, &other.state)
+	}
+}
+
+// Held by the cache; releases the group's memory when dropped.
+pub(super) struct BudgetGuard {
+	budget: Budget,
+	id: u64,
+}
+
+impl Drop for BudgetGuard {
+	fn drop(&mut self) {
+		self.budget.remove(self.id);
+	}
+}
+
+// Held by the writer to account for each object as it's created.
+pub(super) struct BudgetHandle {
+	budget: Budget,
+	id: u64,
+}
+
+impl BudgetHandle {
+	pub fn add(&self, size: usize) {
+		self.budget.add(self.id, size);
+	}
+}
+
+impl Drop for BudgetHandle {
+	fn drop(&mut self) {
+		self.budget.finish(self.id);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	#[test]
+	fn evict() {
+		let budget = Budget::new(100);
+
+		let evicted = [(); 3].map(|_| Arc::new(AtomicBool::new(false)));
+		let mut entries: Vec<_> = evicted
+			.iter()
+			.map(|evicted| {
+				let evicted = evicted.clone();
+				let (guard, handle) = budget.register(1, move |_| evicted.store(true, Ordering::Relaxed));
+				(guard, Some(handle))
+			})
+			.collect();
+
+		for entry in &mut entries[..2] {
+			entry.1.take().unwrap().add(40);
+		}
+		entries[2].1.as_ref().unwrap().add(40);
+
+		// The oldest finished group with the same priority is evicted first.
+		assert!(evicted[0].load(Ordering::Relaxed));
+		assert!(!evicted[1].load(Ordering::Relaxed));
+		assert_eq!(budget.used(), 80);
+
+		drop(entries);
+		assert_eq!(budget.used(), 0);
+	}
+
+	#[test]
+	fn writing() {
+		let budget = Budget::new(100);
+
+		let evicted = Arc::new(AtomicBool::new(false));
+		let (_guard, handle) = {
+			let evicted = evicted.clone();
+			budget.register(1, move |_| evicted.store(true, Ordering::Relaxed))
+		};
+
+		// The group being written is kept, even when it exceeds the limit on its own.
+		handle.add(60);
+		handle.add(60);
+		assert!(!evicted.load(Ordering::Relaxed));
+		assert_eq!(budget.used(), 120);
+	}
+}
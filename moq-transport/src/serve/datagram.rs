@@ -77,11 +77,19 @@ pub struct DatagramsReader {
 	pub track: Arc<Track>,
 
 	epoch: u64,
+
+	// The latest group returned by latest_changed.
+	latest: Option<u64>,
 }
 
 impl DatagramsReader {
 	fn new(state: State<DatagramsState>, track: Arc<Track>) -> Self {
-		Self { state, track, epoch: 0 }
+		Self {
+			state,
+			track,
+			epoch: 0,
+			latest: None,
+		}
 	}
 
 	pub async fn read(&mut self) -> Result<Option<Datagram>, ServeError> {
@@ -111,6 +119,26 @@ impl DatagramsReader {
 			.as_ref()
 			.map(|datagram| (datagram.group_id, datagram.object_id))
 	}
+
+	/// Block until the latest group sequence increases, without consuming any datagrams.
+	/// None is returned when the track is closed.
+	pub async fn latest_changed(&mut self) -> Option<u64> {
+		loop {
+			{
+				let state = self.state.lock();
+
+				let latest = state.latest.as_ref().map(|datagram| datagram.group_id);
+				if latest > self.latest {
+					self.latest = latest;
+					return latest;
+				}
+
+				state.closed.clone().ok()?;
+				state.modified()?
+			}
+			.await;
+		}
+	}
 }
 
 /// Static information about the datagram.
@@ -1,3 +1,5 @@
+use crate::error::ErrorCode;
+
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum ServeError {
 	// TODO stop using?
@@ -22,12 +24,84 @@ pub enum ServeError {
 	#[error("wrong size")]
 	Size,
 
+	#[error("evicted")]
+	Evicted,
+
+	#[error("missing group: {0}")]
+	Missing(u64),
+
+	#[error("out of order")]
+	OutOfOrder,
+
+	#[error("timeout")]
+	Timeout,
+
+	#[error("going away")]
+	GoingAway,
+
+	/// The readers are too far behind, see [crate::serve::Track::backpressure].
+	#[error("overflow")]
+	Overflow,
+
+	/// Over one of the peer's [crate::session::SessionLimits].
+	#[error("quota exceeded")]
+	Quota,
+
+	/// Rejected by the peer's [crate::session::Authorizer].
+	#[error("unauthorized")]
+	Unauthorized,
+
+	/// The publisher redirected the subscription to another origin.
+	#[error("redirected: {0}")]
+	Redirect(String),
+
+	/// A payload failed to decrypt, see [crate::e2ee].
+	#[error("decrypt failed")]
+	Decrypt,
+
 	#[error("internal error: {0}")]
 	Internal(String),
 }
 
 impl ServeError {
-	pub fn code(&self) -> u64 {
+	/// Returns true if the error is transient and the request may succeed if retried.
+	///
+	/// Only a missing track is retryable, since the publisher may not be routing it yet.
+	pub fn is_retryable(&self) -> bool {
+		match self {
+			Self::NotFound => true,
+			// Errors from the peer are only sent as a code.
+			Self::Closed(code) => *code == Self::NotFound.code(),
+			_ => false,
+		}
+	}
+
+	/// Reconstruct an error from a code sent by the peer.
+	///
+	/// Unknown codes, and errors that carry more than a code, are returned as [ServeError::Closed].
+	pub fn from_code(code: u64) -> Self {
+		match code {
+			0 => Self::Done,
+			1 => Self::Cancel,
+			404 => Self::NotFound,
+			409 => Self::Duplicate,
+			400 => Self::Mode,
+			413 => Self::Size,
+			507 => Self::Evicted,
+			408 => Self::Timeout,
+			416 => Self::OutOfOrder,
+			503 => Self::GoingAway,
+			401 => Self::Unauthorized,
+			429 => Self::Overflow,
+			509 => Self::Quota,
+			422 => Self::Decrypt,
+			code => Self::Closed(code),
+		}
+	}
+}
+
+impl ErrorCode for ServeError {
+	fn code(&self) -> u64 {
 		match self {
 			Self::Done => 0,
 			Self::Cancel => 1,
@@ -36,6 +110,16 @@ impl ServeError {
 			Self::Duplicate => 409,
 			Self::Mode => 400,
 			Self::Size => 413,
+			Self::Evicted => 507,
+			Self::Missing(_) => 410,
+			Self::OutOfOrder => 416,
+			Self::Timeout => 408,
+			Self::GoingAway => 503,
+			Self::Unauthorized => 401,
+			Self::Overflow => 429,
+			Self::Redirect(_) => 301,
+			Self::Quota => 509,
+			Self::Decrypt => 422,
 			Self::Internal(_) => 500,
 		}
 	}
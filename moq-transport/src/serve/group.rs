@@ -8,12 +8,18 @@
 //!
 //! The stream is closed with [ServeError::Closed] when all writers or readers are dropped.
 use bytes::Bytes;
-use std::{cmp, ops::Deref, sync::Arc};
+use std::{
+	cmp,
+	collections::{HashMap, VecDeque},
+	ops::{self, Deref},
+	sync::Arc,
+	time,
+};
 
-use crate::data::ObjectStatus;
-use crate::watch::State;
+use crate::data::{ObjectMeta, ObjectStatus};
+use crate::watch::{State, StateRef, StateWeak};
 
-use super::{ServeError, Track};
+use super::{BudgetGuard, BudgetHandle, GroupOrder, Overflow, ServeError, Track};
 
 pub struct Groups {
 	pub track: Arc<Track>,
@@ -23,11 +29,62 @@ impl Groups {
 	pub fn produce(self) -> (GroupsWriter, GroupsReader) {
 		let (writer, reader) = State::default().split();
 
-		let writer = GroupsWriter::new(writer, self.track.clone());
+		let mut writer = GroupsWriter::new(writer, self.track.clone());
 		let reader = GroupsReader::new(reader, self.track);
 
+		// Proactively evict expired groups, otherwise a track nobody reads would never free memory.
+		if writer.info.expires.is_some() {
+			writer.spawn_expires();
+		}
+
 		(writer, reader)
 	}
+
+	// Sleep until the oldest group expires, then evict it.
+	// Returns when the writer or all readers are dropped, since the cache can no longer change.
+	async fn run_expires(state: StateWeak<GroupsState>) {
+		loop {
+			let state = match state.upgrade() {
+				Some(state) => state,
+				None => return,
+			};
+
+			// Only modify the state when a group expired, since that wakes every reader.
+			let now = tokio::time::Instant::now();
+			if state.lock().cache.iter().any(|cached| cached.expired(now)) {
+				match state.lock_mut() {
+					Some(mut state) => state.expire(now),
+					None => return,
+				};
+			}
+
+			let (deadline, changed) = {
+				let state = state.lock();
+
+				// Wake up when the next group expires, or when a group is added with a sooner expiry.
+				let deadline = state
+					.cache
+					.iter()
+					.filter_map(|cached| Some(cached.created + cached.expires?))
+					.min();
+
+				match state.modified() {
+					Some(changed) => (deadline, changed),
+					None => return,
+				}
+			};
+
+			drop(state);
+
+			match deadline {
+				Some(deadline) => tokio::select! {
+					_ = tokio::time::sleep_until(deadline) => {},
+					_ = changed => {},
+				},
+				None => changed.await,
+			}
+		}
+	}
 }
 
 impl Deref for Groups {
@@ -38,27 +95,133 @@ impl Deref for Groups {
 	}
 }
 
+// A group retained in the cache.
+struct GroupsCached {
+	reader: GroupReader,
+	created: tokio::time::Instant,
+
+	// How long the group is retained, from the group or else the track.
+	expires: Option<time::Duration>,
+
+	// Releases the group's memory from the budget when evicted from the cache.
+	_budget: Option<BudgetGuard>,
+}
+
+impl GroupsCached {
+	fn expired(&self, now: tokio::time::Instant) -> bool {
+		self.expires.is_some_and(|expires| self.created + expires <= now)
+	}
+}
+
+impl Deref for GroupsCached {
+	type Target = GroupReader;
+
+	fn deref(&self) -> &Self::Target {
+		&self.reader
+	}
+}
+
 // State shared between the writer and reader.
 struct GroupsState {
-	latest: Option<GroupReader>,
+	// The most recent groups in ascending order, bounded by the track's cache size and expiration.
+	// The latest group is always retained until it expires so it can be delivered live.
+	cache: VecDeque<GroupsCached>,
 	epoch: u64, // Updated each time latest changes
 	closed: Result<(), ServeError>,
+
+	// The largest group evicted because it expired or the cache was full, which ascending readers skip past.
+	expired: Option<u64>,
+
+	// The most recently dropped groups, and how many older entries were discarded.
+	dropped: VecDeque<GroupsDropped>,
+	dropped_offset: u64,
+
+	// The next group expected by each ascending reader, used for backpressure.
+	cursors: HashMap<u64, u64>,
+	next_cursor: u64,
+}
+
+impl GroupsState {
+	fn latest(&self) -> Option<&GroupReader> {
+		self.cache.back().map(|cached| &cached.reader)
+	}
+
+	// Returns the index of the oldest cached group the slowest reader has yet to receive.
+	fn pending_index(&self) -> usize {
+		match self.cursors.values().min() {
+			Some(expected) => self.cache.partition_point(|cached| cached.group_id < *expected),
+			None => self.cache.len(),
+		}
+	}
+
+	// Returns the number of cached groups the slowest reader has yet to receive.
+	fn pending(&self) -> usize {
+		self.cache.len() - self.pending_index()
+	}
+
+	// Remove the oldest cached group, so ascending readers skip past it.
+	fn evict_oldest(&mut self) {
+		if let Some(evicted) = self.cache.pop_front() {
+			self.expired = cmp::max(self.expired, Some(evicted.group_id));
+		}
+	}
+
+	// Remove any groups that were created more than their expiry ago.
+	fn expire(&mut self, now: tokio::time::Instant) {
+		let mut expired = self.expired;
+
+		self.cache.retain(|cached| {
+			if cached.expired(now) {
+				expired = cmp::max(expired, Some(cached.group_id));
+				return false;
+			}
+			true
+		});
+
+		self.expired = expired;
+	}
 }
 
 impl Default for GroupsState {
 	fn default() -> Self {
 		Self {
-			latest: None,
+			cache: VecDeque::new(),
 			epoch: 0,
 			closed: Ok(()),
+			expired: None,
+			dropped: VecDeque::new(),
+			dropped_offset: 0,
+			cursors: HashMap::new(),
+			next_cursor: 0,
 		}
 	}
 }
 
+/// A range of groups that will never be delivered, returned by [GroupsReader::dropped].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupsDropped {
+	/// The first dropped group.
+	pub start: u64,
+
+	/// The number of consecutive groups dropped.
+	pub count: u64,
+
+	/// The publisher's error if it reset the groups, or None if they never arrived.
+	pub error: Option<ServeError>,
+}
+
+impl GroupsDropped {
+	// The number of drops retained for slow readers.
+	const CAPACITY: usize = 32;
+}
+
 pub struct GroupsWriter {
 	pub info: Arc<Track>,
 	state: State<GroupsState>,
 	next: u64, // Not in the state to avoid a lock
+
+	// Set once a task is evicting expired groups.
+	expiring: bool,
 }
 
 impl GroupsWriter {
@@ -67,6 +230,15 @@ impl GroupsWriter {
 			info: track,
 			state,
 			next: 0,
+			expiring: false,
+		}
+	}
+
+	fn spawn_expires(&mut self) {
+		// NOTE: We can't spawn outside of a runtime; groups will still expire when the next one is created.
+		if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+			runtime.spawn(Groups::run_expires(self.state.downgrade()));
+			self.expiring = true;
 		}
 	}
 
@@ -78,32 +250,186 @@ impl GroupsWriter {
 		})
 	}
 
+	/// Like [Self::append], but the group expires after the provided duration instead of [Track::expires].
+	///
+	/// For example, an init segment can outlive the media groups around it for late joiners.
+	pub fn append_with_expiry(&mut self, priority: u64, expires: time::Duration) -> Result<GroupWriter, ServeError> {
+		self.create_with_expiry(
+			Group {
+				group_id: self.next,
+				priority,
+			},
+			expires,
+		)
+	}
+
+	/// Like [Self::create], but the group expires after the provided duration instead of [Track::expires].
+	pub fn create_with_expiry(&mut self, group: Group, expires: time::Duration) -> Result<GroupWriter, ServeError> {
+		if !self.expiring {
+			self.spawn_expires();
+		}
+
+		self.insert(group, Some(expires))
+	}
+
+	/// Like [Self::append], but waits for readers to catch up instead of failing with [ServeError::Overflow].
+	pub async fn append_async(&mut self, priority: u64) -> Result<GroupWriter, ServeError> {
+		self.ready().await?;
+		self.append(priority)
+	}
+
+	/// Like [Self::create], but waits for readers to catch up instead of failing with [ServeError::Overflow].
+	pub async fn create_async(&mut self, group: Group) -> Result<GroupWriter, ServeError> {
+		self.ready().await?;
+		self.create(group)
+	}
+
+	// Block until the slowest reader is within the backpressure limit.
+	async fn ready(&self) -> Result<(), ServeError> {
+		let max_groups = match self.info.backpressure {
+			Some(backpressure) if backpressure.overflow == Overflow::Reject => backpressure.max_groups,
+			_ => return Ok(()),
+		};
+
+		loop {
+			{
+				let state = self.state.lock();
+				state.closed.clone()?;
+
+				if state.pending() < max_groups {
+					return Ok(());
+				}
+
+				state.modified().ok_or(ServeError::Cancel)?
+			}
+			.await;
+		}
+	}
+
+	/// Create a group with an explicit ID, which may be older than the latest group.
+	///
+	/// An older group backfills the cache, but fails with [ServeError::OutOfOrder] if it's older than every cached group.
+	/// A group that already exists fails with [ServeError::Duplicate].
 	pub fn create(&mut self, group: Group) -> Result<GroupWriter, ServeError> {
+		self.insert(group, None)
+	}
+
+	fn insert(&mut self, group: Group, expires: Option<time::Duration>) -> Result<GroupWriter, ServeError> {
 		let group = GroupInfo {
 			track: self.info.clone(),
 			group_id: group.group_id,
 			priority: group.priority,
+			expires,
 		};
-		let (writer, reader) = group.produce();
+		let expires = group.expires();
+		let (mut writer, reader) = group.produce();
+
+		// Register the group with the memory budget, if any.
+		let budget = self.info.budget.as_ref().map(|budget| {
+			let (guard, handle) = budget.register(writer.priority, writer.evict(self.state.downgrade()));
+			writer.budget = Some(handle);
+			guard
+		});
 
 		let mut state = self.state.lock_mut().ok_or(ServeError::Cancel)?;
 
-		if let Some(latest) = &state.latest {
-			match writer.group_id.cmp(&latest.group_id) {
-				cmp::Ordering::Less => return Ok(writer), // dropped immediately, lul
-				cmp::Ordering::Equal => return Err(ServeError::Duplicate),
-				cmp::Ordering::Greater => state.latest = Some(reader),
+		let now = tokio::time::Instant::now();
+		state.expire(now);
+
+		if let Some(backpressure) = self.info.backpressure {
+			if state.pending() >= backpressure.max_groups {
+				match backpressure.overflow {
+					Overflow::Reject => return Err(ServeError::Overflow),
+					Overflow::DropOldest => {
+						// Readers skip past evicted groups, just like expired groups.
+						let index = state.pending_index();
+						if let Some(evicted) = state.cache.remove(index) {
+							state.expired = cmp::max(state.expired, Some(evicted.group_id));
+						}
+					}
+				}
+			}
+		}
+
+		let reader = GroupsCached {
+			reader,
+			created: now,
+			expires,
+			_budget: budget,
+		};
+
+		// Always retain the latest group, even when the cache is disabled.
+		let capacity = cmp::max(self.info.cache, 1);
+
+		// Find where the group belongs in the cache, which is sorted by group_id.
+		let index = state.cache.partition_point(|cached| cached.group_id < reader.group_id);
+		if let Some(cached) = state.cache.get(index) {
+			if cached.group_id == reader.group_id {
+				return Err(ServeError::Duplicate);
+			}
+		}
+
+		if index < state.cache.len() {
+			// An old group arrived late; retain it only if it's recent enough to be cached.
+			if state.cache.len() >= capacity {
+				if index == 0 {
+					// Older than anything cached, so no reader would ever see it.
+					return Err(ServeError::OutOfOrder);
+				}
+
+				state.evict_oldest();
+				state.cache.insert(index - 1, reader);
+			} else {
+				state.cache.insert(index, reader);
 			}
-		} else {
-			state.latest = Some(reader);
+
+			// NOTE: The latest group didn't change, so don't notify next()
+			return Ok(writer);
+		}
+
+		state.cache.push_back(reader);
+		while state.cache.len() > capacity {
+			state.evict_oldest();
 		}
 
-		self.next = state.latest.as_ref().unwrap().group_id + 1;
+		self.next = writer.group_id + 1;
 		state.epoch += 1;
 
 		Ok(writer)
 	}
 
+	/// Block until all readers have been dropped, signaling that the track no longer needs to be produced.
+	pub async fn unused(&self) {
+		loop {
+			{
+				let state = self.state.lock();
+				match state.modified() {
+					Some(notify) => notify,
+					None => return,
+				}
+			}
+			.await;
+		}
+	}
+
+	/// Notify readers that a range of groups will never be delivered.
+	pub fn dropped(&mut self, dropped: GroupsDropped) -> Result<(), ServeError> {
+		let mut state = self.state.lock_mut().ok_or(ServeError::Cancel)?;
+
+		state.dropped.push_back(dropped);
+		if state.dropped.len() > GroupsDropped::CAPACITY {
+			state.dropped.pop_front();
+			state.dropped_offset += 1;
+		}
+
+		Ok(())
+	}
+
+	/// Returns the group ID after the latest group, used to resume a subscription.
+	pub fn next_group_id(&self) -> u64 {
+		self.next
+	}
+
 	/// Close the segment with an error.
 	pub fn close(self, err: ServeError) -> Result<(), ServeError> {
 		let state = self.state.lock();
@@ -124,11 +450,29 @@ impl Deref for GroupsWriter {
 	}
 }
 
-#[derive(Clone)]
 pub struct GroupsReader {
 	pub info: Arc<Track>,
 	state: State<GroupsState>,
 	epoch: u64,
+
+	// The latest group returned by latest_changed.
+	latest: Option<u64>,
+
+	// The next group to return when delivering in ascending order.
+	expected: Option<u64>,
+
+	// The next cached group to return before resuming live delivery, set by start_at.
+	replay: Option<u64>,
+
+	// The first group not delivered, set by range.
+	end: Option<u64>,
+	finished: bool,
+
+	// The index of the next drop to return.
+	dropped: u64,
+
+	// Identifies our position for backpressure, assigned once we start reading.
+	cursor: Option<u64>,
 }
 
 impl GroupsReader {
@@ -137,17 +481,89 @@ impl GroupsReader {
 			info: track,
 			state,
 			epoch: 0,
+			latest: None,
+			expected: None,
+			replay: None,
+			end: None,
+			finished: false,
+			dropped: 0,
+			cursor: None,
+		}
+	}
+
+	// Record the next group we expect, which may unblock the writer.
+	// Takes the fields separately since the state is borrowed from the reader.
+	fn advance(info: &Track, cursor: &mut Option<u64>, expected: u64, state: StateRef<GroupsState>) {
+		if info.backpressure.is_none() {
+			return;
+		}
+
+		if let Some(mut state) = state.into_mut() {
+			let cursor = *cursor.get_or_insert_with(|| {
+				state.next_cursor += 1;
+				state.next_cursor
+			});
+
+			state.cursors.insert(cursor, expected);
 		}
 	}
 
+	/// Returns the next group, based on the track's [GroupOrder].
 	pub async fn next(&mut self) -> Result<Option<GroupReader>, ServeError> {
+		if self.finished {
+			return Ok(None);
+		}
+
+		let group = match self.info.order {
+			GroupOrder::Ascending => self.next_ascending().await?,
+			GroupOrder::Descending => self.next_descending().await?,
+		};
+
+		let Some(end) = self.end else {
+			return Ok(group);
+		};
+
+		// Finish at the end of the range, rather than waiting for the next group.
+		match group {
+			Some(group) if group.group_id < end => {
+				self.finished = group.group_id + 1 >= end;
+				Ok(Some(group))
+			}
+			_ => {
+				self.finished = true;
+				Ok(None)
+			}
+		}
+	}
+
+	// Returns the latest group, skipping any older groups.
+	async fn next_descending(&mut self) -> Result<Option<GroupReader>, ServeError> {
 		loop {
 			{
 				let state = self.state.lock();
 
+				// Deliver the requested backlog in order before jumping to the latest group.
+				if let Some(replay) = self.replay {
+					let index = state.cache.partition_point(|cached| cached.group_id < replay);
+					if let Some(cached) = state.cache.get(index) {
+						self.replay = Some(cached.group_id + 1);
+
+						// Avoid returning the latest group twice once the backlog catches up.
+						self.epoch = state.epoch;
+
+						return Ok(Some(cached.reader.clone()));
+					}
+
+					self.replay = None;
+				}
+
 				if self.epoch != state.epoch {
 					self.epoch = state.epoch;
-					return Ok(state.latest.clone());
+
+					// NOTE: The latest group may have expired, in which case we wait for the next one.
+					if let Some(latest) = state.latest() {
+						return Ok(Some(latest.clone()));
+					}
 				}
 
 				state.closed.clone()?;
@@ -160,10 +576,199 @@ impl GroupsReader {
 		}
 	}
 
+	// Returns each group in ascending order, using the cache to buffer groups that arrive early.
+	async fn next_ascending(&mut self) -> Result<Option<GroupReader>, ServeError> {
+		loop {
+			{
+				let state = self.state.lock();
+
+				// Start with the oldest cached group, skipping any that expired before we read them.
+				let expected = self
+					.expected
+					.map(|expected| match state.expired {
+						Some(expired) if expired >= expected => expired + 1,
+						_ => expected,
+					})
+					.or_else(|| state.cache.front().map(|cached| cached.group_id));
+
+				let index = expected.map(|expected| state.cache.partition_point(|cached| cached.group_id < expected));
+				if let (Some(expected), Some(cached)) = (expected, index.and_then(|index| state.cache.get(index))) {
+					if cached.group_id == expected {
+						let reader = cached.reader.clone();
+						self.expected = Some(expected + 1);
+						Self::advance(&self.info, &mut self.cursor, expected + 1, state);
+						return Ok(Some(reader));
+					}
+
+					// The expected group is missing but a newer group is buffered.
+					// Nothing more will arrive once closed, so skip the gap.
+					if state.closed.is_err() {
+						let reader = cached.reader.clone();
+						let expected = cached.group_id + 1;
+						self.expected = Some(expected);
+						Self::advance(&self.info, &mut self.cursor, expected, state);
+						return Ok(Some(reader));
+					}
+
+					// The cache is full, so the expected group would be dropped on arrival.
+					// Report the gap once, then resume from the oldest cached group.
+					if state.cache.len() >= cmp::max(self.info.cache, 1) {
+						let resume = cached.group_id;
+						self.expected = Some(resume);
+						Self::advance(&self.info, &mut self.cursor, resume, state);
+						return Err(ServeError::Missing(expected));
+					}
+				}
+
+				state.closed.clone()?;
+				match state.modified() {
+					Some(notify) => notify,
+					None => return Ok(None),
+				}
+			}
+			.await; // Try again when the state changes
+		}
+	}
+
+	/// Start delivery from the given group, replaying any cached groups from that point.
+	///
+	/// Returns the first group that will be delivered, clamped to the oldest group still cached,
+	/// or None if nothing at or after that group is cached.
+	pub fn start_at(&mut self, group_id: u64) -> Option<u64> {
+		let state = self.state.lock();
+		let start = state
+			.cache
+			.iter()
+			.map(|cached| cached.group_id)
+			.find(|cached| *cached >= group_id)?;
+
+		match self.info.order {
+			GroupOrder::Ascending => {
+				self.expected = Some(start);
+				Self::advance(&self.info, &mut self.cursor, start, state);
+			}
+			GroupOrder::Descending => self.replay = Some(start),
+		}
+
+		Some(start)
+	}
+
+	/// Only deliver the groups in the range, starting with any that are cached, for time-shifted playback.
+	///
+	/// Returns the first group that will be delivered like [Self::start_at], or None if it hasn't arrived yet.
+	/// [Self::next] returns None once the end of the range is reached, without waiting for the next group.
+	/// Groups evicted from the cache can't be delivered, so the track should retain enough of them; see [Track::cache].
+	pub fn range(&mut self, range: ops::Range<u64>) -> Option<u64> {
+		self.end = Some(range.end);
+		self.finished = range.is_empty();
+
+		let start = self.start_at(range.start);
+		if start.is_none() && self.info.order == GroupOrder::Ascending {
+			// Wait for the start of the range rather than delivering older cached groups.
+			self.expected = Some(range.start);
+			Self::advance(&self.info, &mut self.cursor, range.start, self.state.lock());
+		}
+
+		start.filter(|start| *start < range.end)
+	}
+
 	// Returns the largest group/sequence
 	pub fn latest(&self) -> Option<(u64, u64)> {
 		let state = self.state.lock();
-		state.latest.as_ref().map(|group| (group.group_id, group.latest()))
+		state.latest().map(|group| (group.group_id, group.latest()))
+	}
+
+	/// Returns a cached group by sequence number, if it's still retained.
+	pub fn get(&self, group_id: u64) -> Option<GroupReader> {
+		let state = self.state.lock();
+		state
+			.cache
+			.iter()
+			.find(|cached| cached.group_id == group_id)
+			.map(|cached| cached.reader.clone())
+	}
+
+	/// Returns all cached groups in ascending order.
+	pub fn cached(&self) -> Vec<GroupReader> {
+		let state = self.state.lock();
+		state.cache.iter().map(|cached| cached.reader.clone()).collect()
+	}
+
+	/// Block until the latest group sequence increases, without consuming any groups.
+	/// None is returned when the track is closed.
+	pub async fn latest_changed(&mut self) -> Option<u64> {
+		loop {
+			{
+				let state = self.state.lock();
+
+				let latest = state.latest().map(|group| group.group_id);
+				if latest > self.latest {
+					self.latest = latest;
+					return latest;
+				}
+
+				state.closed.clone().ok()?;
+				state.modified()?
+			}
+			.await; // Try again when the state changes
+		}
+	}
+
+	/// Returns the next range of groups that will never be delivered, such as to request a keyframe or show a gap.
+	///
+	/// Only the most recent drops are retained, so a slow reader may miss some.
+	/// None is returned when the track is closed.
+	pub async fn dropped(&mut self) -> Option<GroupsDropped> {
+		loop {
+			{
+				let state = self.state.lock();
+
+				self.dropped = cmp::max(self.dropped, state.dropped_offset);
+				if let Some(dropped) = state.dropped.get((self.dropped - state.dropped_offset) as usize) {
+					self.dropped += 1;
+					return Some(dropped.clone());
+				}
+
+				state.closed.clone().ok()?;
+				state.modified()?
+			}
+			.await;
+		}
+	}
+}
+
+impl Clone for GroupsReader {
+	fn clone(&self) -> Self {
+		let mut reader = Self {
+			info: self.info.clone(),
+			state: self.state.clone(),
+			epoch: self.epoch,
+			latest: self.latest,
+			expected: self.expected,
+			replay: self.replay,
+			end: self.end,
+			finished: self.finished,
+			dropped: self.dropped,
+			cursor: None,
+		};
+
+		// The clone starts at the same position, so it holds back the writer too.
+		if let (Some(_), Some(expected)) = (self.cursor, self.expected) {
+			let state = reader.state.lock();
+			Self::advance(&reader.info, &mut reader.cursor, expected, state);
+		}
+
+		reader
+	}
+}
+
+impl Drop for GroupsReader {
+	fn drop(&mut self) {
+		if let Some(cursor) = self.cursor {
+			if let Some(mut state) = self.state.lock_mut() {
+				state.cursors.remove(&cursor);
+			}
+		}
 	}
 }
 
@@ -197,9 +802,17 @@ pub struct GroupInfo {
 
 	// The priority of the group within the track.
 	pub priority: u64,
+
+	// Overrides the track's expiry for this group.
+	pub expires: Option<time::Duration>,
 }
 
 impl GroupInfo {
+	/// How long the group is cached, using the track's expiry unless overridden.
+	pub fn expires(&self) -> Option<time::Duration> {
+		self.expires.or(self.track.expires)
+	}
+
 	pub fn produce(self) -> (GroupWriter, GroupReader) {
 		let (writer, reader) = State::default().split();
 		let info = Arc::new(self);
@@ -246,6 +859,9 @@ pub struct GroupWriter {
 
 	// The next object sequence number to use.
 	next: u64,
+
+	// Used to account for the size of each object.
+	budget: Option<BudgetHandle>,
 }
 
 impl GroupWriter {
@@ -254,6 +870,23 @@ impl GroupWriter {
 			state,
 			info: group,
 			next: 0,
+			budget: None,
+		}
+	}
+
+	// Returns a callback that removes the group from the cache and closes it with an error.
+	fn evict(&self, groups: StateWeak<GroupsState>) -> impl FnOnce(ServeError) + Send + 'static {
+		let group = self.state.downgrade();
+		let group_id = self.group_id;
+
+		move |err| {
+			if let Some(mut groups) = groups.upgrade().as_ref().and_then(|groups| groups.lock_mut()) {
+				groups.cache.retain(|cached| cached.group_id != group_id);
+			}
+
+			if let Some(mut group) = group.upgrade().as_ref().and_then(|group| group.lock_mut()) {
+				group.closed = Err(err);
+			}
 		}
 	}
 
@@ -264,19 +897,66 @@ impl GroupWriter {
 		Ok(())
 	}
 
+	/// Create the next object ID with the given payload and metadata, such as a timestamp.
+	pub fn write_with_meta(&mut self, payload: bytes::Bytes, meta: ObjectMeta) -> Result<(), ServeError> {
+		let mut object = self.create_with_meta(payload.len(), meta)?;
+		object.write(payload)?;
+		Ok(())
+	}
+
 	/// Write an object over multiple writes.
 	///
 	/// BAD STUFF will happen if the size is wrong; this is an advanced feature.
 	pub fn create(&mut self, size: usize) -> Result<GroupObjectWriter, ServeError> {
+		self.create_inner(self.next, size, None)
+	}
+
+	/// Write an object with metadata over multiple writes.
+	///
+	/// The metadata tag is bounded by [ObjectMeta::MAX_TAG].
+	pub fn create_with_meta(&mut self, size: usize, meta: ObjectMeta) -> Result<GroupObjectWriter, ServeError> {
+		if meta.tag.len() > ObjectMeta::MAX_TAG {
+			return Err(ServeError::Size);
+		}
+
+		self.create_inner(self.next, size, Some(meta))
+	}
+
+	/// Write an object with an explicit ID, used when objects arrive out of order.
+	pub fn create_at(
+		&mut self,
+		object_id: u64,
+		size: usize,
+		meta: Option<ObjectMeta>,
+	) -> Result<GroupObjectWriter, ServeError> {
+		if meta.as_ref().is_some_and(|meta| meta.tag.len() > ObjectMeta::MAX_TAG) {
+			return Err(ServeError::Size);
+		}
+
+		self.create_inner(object_id, size, meta)
+	}
+
+	fn create_inner(
+		&mut self,
+		object_id: u64,
+		size: usize,
+		meta: Option<ObjectMeta>,
+	) -> Result<GroupObjectWriter, ServeError> {
+		// NOTE: This may evict groups, so it must be called before acquiring any locks.
+		if let Some(budget) = &self.budget {
+			budget.add(size);
+		}
+
 		let (writer, reader) = GroupObject {
 			group: self.info.clone(),
-			object_id: self.next,
+			object_id,
 			status: ObjectStatus::Object,
 			size,
+			meta,
 		}
 		.produce();
 
-		self.next += 1;
+		self.next = cmp::max(self.next, object_id + 1);
 
 		let mut state = self.state.lock_mut().ok_or(ServeError::Cancel)?;
 		state.objects.push(reader);
@@ -347,6 +1027,27 @@ impl GroupReader {
 		}
 	}
 
+	/// Like [Self::read_next], but returns [GroupRead::Pending] if no object is complete before the timeout.
+	///
+	/// This lets a real-time consumer decide whether to conceal or keep waiting on a stalled publisher.
+	/// An object that's only partially written when the timeout fires is not consumed, so it's returned in full by a later read.
+	pub async fn read_next_timeout(&mut self, timeout: time::Duration) -> Result<GroupRead, ServeError> {
+		// Read using a copy, only advancing once a whole object has been read.
+		let mut reader = self.clone();
+
+		let res = match tokio::time::timeout(timeout, reader.read_next()).await {
+			Ok(res) => res?,
+			Err(_) => return Ok(GroupRead::Pending),
+		};
+
+		self.index = reader.index;
+
+		Ok(match res {
+			Some(object) => GroupRead::Object(object),
+			None => GroupRead::Done,
+		})
+	}
+
 	pub async fn next(&mut self) -> Result<Option<GroupObjectReader>, ServeError> {
 		loop {
 			{
@@ -372,6 +1073,24 @@ impl GroupReader {
 		self.index
 	}
 
+	/// Skip the objects produced so far, so only new objects are returned.
+	///
+	/// Nothing is skipped if the group is already complete, since no new objects would follow.
+	/// Returns the number of objects skipped.
+	pub fn skip_produced(&mut self) -> usize {
+		let state = self.state.lock();
+		let len = state.objects.len();
+
+		if state.closed.is_err() || state.modified().is_none() {
+			return 0;
+		}
+
+		let skipped = len.saturating_sub(self.index);
+		self.index = len;
+
+		skipped
+	}
+
 	pub fn len(&self) -> usize {
 		self.state.lock().objects.len()
 	}
@@ -381,6 +1100,19 @@ impl GroupReader {
 	}
 }
 
+/// The result of [GroupReader::read_next_timeout].
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupRead {
+	/// The next object in the group.
+	Object(Bytes),
+
+	/// No object was complete before the timeout.
+	Pending,
+
+	/// The group has finished.
+	Done,
+}
+
 impl Deref for GroupReader {
 	type Target = GroupInfo;
 
@@ -401,6 +1133,9 @@ pub struct GroupObject {
 
 	// Object status
 	pub status: ObjectStatus,
+
+	// Optional metadata, such as a presentation timestamp.
+	pub meta: Option<ObjectMeta>,
 }
 
 impl GroupObject {
@@ -573,3 +1308,238 @@ impl Deref for GroupObjectReader {
 		&self.info
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::serve::Budget;
+
+	#[tokio::test]
+	async fn out_of_order() {
+		let (mut writer, _reader) = Groups {
+			track: Arc::new(Track::new("test".to_string(), "video".to_string()).cache(2)),
+		}
+		.produce();
+
+		writer
+			.create(Group {
+				group_id: 5,
+				priority: 0,
+			})
+			.unwrap();
+		writer
+			.create(Group {
+				group_id: 7,
+				priority: 0,
+			})
+			.unwrap();
+
+		// Backfilled into the cache, evicting the oldest group.
+		writer
+			.create(Group {
+				group_id: 6,
+				priority: 0,
+			})
+			.unwrap();
+
+		assert_eq!(
+			writer
+				.create(Group {
+					group_id: 6,
+					priority: 0
+				})
+				.err(),
+			Some(ServeError::Duplicate)
+		);
+		assert_eq!(
+			writer
+				.create(Group {
+					group_id: 4,
+					priority: 0
+				})
+				.err(),
+			Some(ServeError::OutOfOrder)
+		);
+	}
+
+	fn cached(reader: &GroupsReader) -> Vec<u64> {
+		reader.cached().iter().map(|group| group.group_id).collect()
+	}
+
+	#[tokio::test]
+	async fn expiry_override() {
+		let track = Track::new("test".to_string(), "video".to_string())
+			.cache(4)
+			.expires(time::Duration::from_millis(50));
+
+		let (mut writer, reader) = Groups { track: Arc::new(track) }.produce();
+
+		// The first group outlives the rest, like an init segment.
+		writer.append_with_expiry(0, time::Duration::from_secs(10)).unwrap();
+		writer.append(0).unwrap();
+		tokio::time::sleep(time::Duration::from_millis(100)).await;
+		writer.append(0).unwrap();
+
+		assert_eq!(cached(&reader), vec![0, 2]);
+		assert_eq!(reader.get(0).unwrap().expires(), Some(time::Duration::from_secs(10)));
+
+		// Without a track expiry, only the overridden group is evicted, even if no group follows.
+		let track = Track::new("test".to_string(), "video".to_string()).cache(4);
+		let (mut writer, reader) = Groups { track: Arc::new(track) }.produce();
+
+		writer.append_with_expiry(0, time::Duration::from_millis(50)).unwrap();
+		writer.append(0).unwrap();
+		tokio::time::sleep(time::Duration::from_millis(100)).await;
+
+		assert_eq!(cached(&reader), vec![1]);
+		assert_eq!(reader.get(1).unwrap().expires(), None);
+	}
+
+	#[tokio::test]
+	async fn read_timeout() {
+		let (mut writer, mut reader) = GroupInfo {
+			track: Arc::new(Track::new("test".to_string(), "video".to_string())),
+			group_id: 0,
+			priority: 0,
+			expires: None,
+		}
+		.produce();
+
+		let timeout = time::Duration::from_millis(10);
+		assert_eq!(reader.read_next_timeout(timeout).await, Ok(GroupRead::Pending));
+
+		// A partial object isn't lost when the timeout fires.
+		let mut object = writer.create(4).unwrap();
+		object.write(Bytes::from_static(b"he")).unwrap();
+		assert_eq!(reader.read_next_timeout(timeout).await, Ok(GroupRead::Pending));
+
+		object.write(Bytes::from_static(b"ll")).unwrap();
+		drop(object);
+		assert_eq!(
+			reader.read_next_timeout(timeout).await,
+			Ok(GroupRead::Object(Bytes::from_static(b"hell")))
+		);
+
+		drop(writer);
+		assert_eq!(reader.read_next_timeout(timeout).await, Ok(GroupRead::Done));
+	}
+
+	#[tokio::test]
+	async fn ascending_evicted() {
+		let track = Track::new("test".to_string(), "video".to_string())
+			.cache(2)
+			.order(GroupOrder::Ascending);
+
+		let (mut writer, mut reader) = Groups { track: Arc::new(track) }.produce();
+
+		writer.append(0).unwrap();
+		assert_eq!(reader.next().await.unwrap().unwrap().group_id, 0);
+
+		// Group 1 is evicted by the cache size before it's read, so it's skipped like an expired group.
+		writer.append(0).unwrap();
+		writer.append(0).unwrap();
+		writer.append(0).unwrap();
+		assert_eq!(reader.next().await.unwrap().unwrap().group_id, 2);
+		assert_eq!(reader.next().await.unwrap().unwrap().group_id, 3);
+
+		// A group that never arrives is reported once, then delivery resumes.
+		writer
+			.create(Group {
+				group_id: 5,
+				priority: 0,
+			})
+			.unwrap();
+		writer
+			.create(Group {
+				group_id: 6,
+				priority: 0,
+			})
+			.unwrap();
+		assert_eq!(reader.next().await.err(), Some(ServeError::Missing(4)));
+		assert_eq!(reader.next().await.unwrap().unwrap().group_id, 5);
+		assert_eq!(reader.next().await.unwrap().unwrap().group_id, 6);
+	}
+
+	#[tokio::test]
+	async fn budget_writing() {
+		let budget = Budget::new(100);
+		let track = Track::new("test".to_string(), "video".to_string())
+			.cache(4)
+			.budget(budget.clone());
+
+		let (mut writer, reader) = Groups { track: Arc::new(track) }.produce();
+
+		writer.append(0).unwrap().write(Bytes::from(vec![0u8; 60])).unwrap();
+
+		// The group being written is kept, even over the limit, while the finished group is evicted.
+		let mut group = writer.append(0).unwrap();
+		group.write(Bytes::from(vec![1u8; 60])).unwrap();
+		group.write(Bytes::from(vec![2u8; 60])).unwrap();
+		assert_eq!(cached(&reader), vec![1]);
+		assert_eq!(budget.used(), 120);
+	}
+
+	#[tokio::test]
+	async fn ascending_expired() {
+		let track = Track::new("test".to_string(), "video".to_string())
+			.cache(4)
+			.expires(time::Duration::from_millis(50))
+			.order(GroupOrder::Ascending);
+
+		let (mut writer, mut reader) = Groups { track: Arc::new(track) }.produce();
+
+		writer.append(0).unwrap();
+		writer.append(0).unwrap();
+		assert_eq!(reader.next().await.unwrap().unwrap().group_id, 0);
+
+		// Group 1 expires before it's read, so it's skipped rather than waited on.
+		tokio::time::sleep(time::Duration::from_millis(100)).await;
+		writer.append(0).unwrap();
+
+		let next = tokio::time::timeout(time::Duration::from_secs(1), reader.next()).await;
+		assert_eq!(next.unwrap().unwrap().unwrap().group_id, 2);
+	}
+
+	#[tokio::test]
+	async fn backpressure() {
+		let track = Track::new("test".to_string(), "video".to_string())
+			.cache(4)
+			.order(GroupOrder::Ascending)
+			.backpressure(2, Overflow::Reject);
+
+		let (mut writer, mut reader) = Groups { track: Arc::new(track) }.produce();
+
+		writer.append(0).unwrap();
+		writer.append(0).unwrap();
+		assert_eq!(reader.next().await.unwrap().unwrap().group_id, 0);
+
+		// Group 1 hasn't been read yet, so only one more group fits.
+		writer.append(0).unwrap();
+		assert_eq!(writer.append(0).err(), Some(ServeError::Overflow));
+
+		// The async variant waits for the reader to catch up instead.
+		let (group, next) = tokio::join!(writer.append_async(0), reader.next());
+		assert_eq!(next.unwrap().unwrap().group_id, 1);
+		assert_eq!(group.unwrap().group_id, 3);
+	}
+
+	#[tokio::test]
+	async fn backpressure_drop() {
+		let track = Track::new("test".to_string(), "video".to_string())
+			.cache(4)
+			.order(GroupOrder::Ascending)
+			.backpressure(2, Overflow::DropOldest);
+
+		let (mut writer, mut reader) = Groups { track: Arc::new(track) }.produce();
+
+		writer.append(0).unwrap();
+		writer.append(0).unwrap();
+		assert_eq!(reader.next().await.unwrap().unwrap().group_id, 0);
+
+		// Group 1 is dropped to make room, so the reader skips it.
+		writer.append(0).unwrap();
+		writer.append(0).unwrap();
+		assert_eq!(reader.next().await.unwrap().unwrap().group_id, 2);
+		assert_eq!(reader.next().await.unwrap().unwrap().group_id, 3);
+	}
+}
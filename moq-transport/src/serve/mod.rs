@@ -1,3 +1,4 @@
+mod budget;
 mod datagram;
 mod error;
 mod group;
@@ -6,6 +7,7 @@ mod stream;
 mod track;
 mod tracks;
 
+pub use budget::*;
 pub use datagram::*;
 pub use error::*;
 pub use group::*;
@@ -122,6 +122,9 @@ pub struct ObjectsReader {
 
 	// The objects ready to be returned
 	pending: BinaryHeap<ObjectReader>,
+
+	// The latest group returned by latest_changed.
+	latest: Option<u64>,
 }
 
 impl ObjectsReader {
@@ -131,6 +134,7 @@ impl ObjectsReader {
 			info,
 			epoch: 0,
 			pending: BinaryHeap::new(),
+			latest: None,
 		}
 	}
 
@@ -171,6 +175,27 @@ impl ObjectsReader {
 			.max_by_key(|a| (a.group_id, a.object_id))
 			.map(|a| (a.group_id, a.object_id))
 	}
+
+	/// Block until the latest group sequence increases, without consuming any objects.
+	/// None is returned when the track is closed.
+	pub async fn latest_changed(&mut self) -> Option<u64> {
+		loop {
+			{
+				let state = self.state.lock();
+
+				// All objects are from the same group.
+				let latest = state.objects.first().map(|object| object.group_id);
+				if latest > self.latest {
+					self.latest = latest;
+					return latest;
+				}
+
+				state.closed.clone().ok()?;
+				state.modified()?
+			}
+			.await;
+		}
+	}
 }
 
 impl Deref for ObjectsReader {
@@ -75,7 +75,7 @@ impl StreamWriter {
 
 		if let Some(latest) = &state.latest {
 			if latest.group_id > group_id {
-				return Err(ServeError::Duplicate);
+				return Err(ServeError::OutOfOrder);
 			}
 		}
 
@@ -138,11 +138,19 @@ pub struct StreamReader {
 	// The number of chunks that we've read.
 	// NOTE: Cloned readers inherit this index, but then run in parallel.
 	epoch: usize,
+
+	// The latest group returned by latest_changed.
+	latest: Option<u64>,
 }
 
 impl StreamReader {
 	fn new(state: State<StreamState>, info: Arc<Stream>) -> Self {
-		Self { state, info, epoch: 0 }
+		Self {
+			state,
+			info,
+			epoch: 0,
+			latest: None,
+		}
 	}
 
 	/// Block until the next group is available.
@@ -171,6 +179,26 @@ impl StreamReader {
 		let state = self.state.lock();
 		state.latest.as_ref().map(|group| (group.group_id, group.latest()))
 	}
+
+	/// Block until the latest group sequence increases, without consuming any groups.
+	/// None is returned when the stream is closed.
+	pub async fn latest_changed(&mut self) -> Option<u64> {
+		loop {
+			{
+				let state = self.state.lock();
+
+				let latest = state.latest.as_ref().map(|group| group.group_id);
+				if latest > self.latest {
+					self.latest = latest;
+					return latest;
+				}
+
+				state.closed.clone().ok()?;
+				state.modified()?
+			}
+			.await; // Try again when the state changes
+		}
+	}
 }
 
 impl Deref for StreamReader {
@@ -239,6 +267,8 @@ impl StreamGroupWriter {
 		}
 		.produce();
 
+		self.next += 1;
+
 		state.objects.push(reader);
 
 		Ok(writer)
@@ -289,8 +319,9 @@ impl StreamGroupReader {
 			{
 				let state = self.state.lock();
 				if self.index < state.objects.len() {
+					let object = state.objects[self.index].clone();
 					self.index += 1;
-					return Ok(Some(state.objects[self.index].clone()));
+					return Ok(Some(object));
 				}
 
 				state.closed.clone()?;
@@ -12,25 +12,96 @@
 //!
 //! The track is closed with [ServeError::Closed] when all writers or readers are dropped.
 
-use crate::watch::State;
+use crate::watch::{State, StateWeak};
 
 use super::{
-	Datagrams, DatagramsReader, DatagramsWriter, Groups, GroupsReader, GroupsWriter, Objects, ObjectsReader,
-	ObjectsWriter, ServeError, Stream, StreamReader, StreamWriter,
+	Budget, Datagrams, DatagramsReader, DatagramsWriter, GroupReader, Groups, GroupsDropped, GroupsReader,
+	GroupsWriter, Objects, ObjectsReader, ObjectsWriter, ServeError, Stream, StreamReader, StreamWriter,
 };
 use paste::paste;
-use std::{ops::Deref, sync::Arc};
+use std::{ops::Deref, sync::Arc, time};
 
 /// Static information about a track.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Track {
 	pub namespace: String,
 	pub name: String,
+
+	/// The maximum number of groups retained for late-joining readers.
+	pub cache: usize,
+
+	/// The maximum duration a group is retained, or None to retain until evicted by the cache size.
+	pub expires: Option<time::Duration>,
+
+	/// A memory budget shared with other tracks, or None for unlimited.
+	pub budget: Option<Budget>,
+
+	/// The order in which groups are delivered to readers.
+	pub order: GroupOrder,
+
+	/// Limits how far readers may fall behind the writer, or None for unlimited.
+	pub backpressure: Option<Backpressure>,
 }
 
 impl Track {
+	/// The number of groups retained by default, matching the old "latest group only" behavior.
+	pub const DEFAULT_CACHE: usize = 1;
+
 	pub fn new(namespace: String, name: String) -> Self {
-		Self { namespace, name }
+		Self {
+			namespace,
+			name,
+			cache: Self::DEFAULT_CACHE,
+			expires: None,
+			budget: None,
+			order: GroupOrder::default(),
+			backpressure: None,
+		}
+	}
+
+	/// Set the maximum number of groups retained by the track.
+	///
+	/// A catalog only needs the latest group, while a video track may want several for concealment.
+	/// Zero means only live delivery: the group currently being written is still delivered, but nothing older.
+	///
+	/// This interacts with [Self::expires]; a group is evicted by whichever limit is hit first.
+	pub fn cache(mut self, count: usize) -> Self {
+		self.cache = count;
+		self
+	}
+
+	/// Set the maximum duration a group is retained after it was created.
+	///
+	/// Expired groups are evicted by a background task, so this requires a tokio runtime with the timer enabled.
+	/// This interacts with [Self::cache]; a group is evicted by whichever limit is hit first.
+	pub fn expires(mut self, expires: time::Duration) -> Self {
+		self.expires = Some(expires);
+		self
+	}
+
+	/// Share a memory budget with other tracks.
+	///
+	/// When the total cached bytes exceed the budget, the least important groups are evicted across all tracks.
+	pub fn budget(mut self, budget: Budget) -> Self {
+		self.budget = Some(budget);
+		self
+	}
+
+	/// Set the order in which groups are delivered to readers.
+	///
+	/// [GroupOrder::Ascending] buffers out-of-order groups, up to the cache size, so it should be paired with [Self::cache].
+	pub fn order(mut self, order: GroupOrder) -> Self {
+		self.order = order;
+		self
+	}
+
+	/// Limit the number of cached groups the slowest reader has yet to receive.
+	///
+	/// Only [GroupOrder::Ascending] readers can fall behind, so this has no effect otherwise.
+	/// The limit should not exceed [Self::cache], since the cache evicts groups first.
+	pub fn backpressure(mut self, max_groups: usize, overflow: Overflow) -> Self {
+		self.backpressure = Some(Backpressure { max_groups, overflow });
+		self
 	}
 
 	pub fn produce(self) -> (TrackWriter, TrackReader) {
@@ -44,6 +115,38 @@ impl Track {
 	}
 }
 
+/// The order in which a reader receives groups.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupOrder {
+	/// Deliver every group in ascending order, waiting for any gaps to be filled. (VOD, catch-up)
+	Ascending,
+
+	/// Deliver only the latest group, skipping any older groups. (live)
+	#[default]
+	Descending,
+}
+
+/// The write backpressure for a track, set with [Track::backpressure].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backpressure {
+	/// The maximum number of cached groups the slowest reader has yet to receive.
+	pub max_groups: usize,
+
+	/// What happens when a new group would exceed the limit.
+	pub overflow: Overflow,
+}
+
+/// What happens when the writer gets too far ahead of the slowest reader.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+	/// Fail with [ServeError::Overflow], or wait for readers with [super::GroupsWriter::create_async].
+	#[default]
+	Reject,
+
+	/// Drop the oldest group the slowest reader has yet to receive, which readers then skip.
+	DropOldest,
+}
+
 struct TrackState {
 	mode: Option<TrackReaderMode>,
 	closed: Result<(), ServeError>,
@@ -115,6 +218,33 @@ impl TrackWriter {
 		Ok(writer)
 	}
 
+	/// Serve the track using the readers of another track, such as one relayed from elsewhere.
+	///
+	/// The writer is kept, so [Self::unused] still reports when our own readers are dropped.
+	pub fn forward(&mut self, mode: TrackReaderMode) -> Result<(), ServeError> {
+		let mut state = self.state.lock_mut().ok_or(ServeError::Cancel)?;
+		if state.mode.is_some() {
+			return Err(ServeError::Duplicate);
+		}
+
+		state.mode = Some(mode);
+		Ok(())
+	}
+
+	/// Block until all readers have been dropped, signaling that the track no longer needs to be produced.
+	pub async fn unused(&self) {
+		loop {
+			{
+				let state = self.state.lock();
+				match state.modified() {
+					Some(notify) => notify,
+					None => return,
+				}
+			}
+			.await;
+		}
+	}
+
 	/// Close the track with an error.
 	pub fn close(self, err: ServeError) -> Result<(), ServeError> {
 		let state = self.state.lock();
@@ -139,11 +269,26 @@ impl Deref for TrackWriter {
 pub struct TrackReader {
 	state: State<TrackState>,
 	pub info: Arc<Track>,
+
+	// Our own copy of the mode, used to track progress in latest_changed and dropped.
+	mode: Option<TrackReaderMode>,
 }
 
 impl TrackReader {
 	fn new(state: State<TrackState>, info: Arc<Track>) -> Self {
-		Self { state, info }
+		Self {
+			state,
+			info,
+			mode: None,
+		}
+	}
+
+	/// Returns a reference that doesn't keep the track alive.
+	pub fn downgrade(&self) -> TrackReaderWeak {
+		TrackReaderWeak {
+			state: self.state.downgrade(),
+			info: self.info.clone(),
+		}
 	}
 
 	pub async fn mode(&self) -> Result<TrackReaderMode, ServeError> {
@@ -166,9 +311,53 @@ impl TrackReader {
 
 	// Returns the largest group/sequence
 	pub fn latest(&self) -> Option<(u64, u64)> {
-		// We don't even know the mode yet.
+		// NOTE: This is None until we know the mode.
 		// TODO populate from SUBSCRIBE_OK
-		None
+		self.state.lock().mode.as_ref()?.latest()
+	}
+
+	/// Returns a cached group by sequence number, so a late reader can fetch recent history.
+	///
+	/// None is returned if the group isn't cached, or the track isn't delivered as groups; see [Track::cache].
+	pub fn get(&self, group_id: u64) -> Option<GroupReader> {
+		match self.state.lock().mode.as_ref()? {
+			TrackReaderMode::Groups(groups) => groups.get(group_id),
+			_ => None,
+		}
+	}
+
+	/// Returns all cached groups in ascending order, or nothing if the track isn't delivered as groups.
+	pub fn cached(&self) -> Vec<GroupReader> {
+		match self.state.lock().mode.as_ref() {
+			Some(TrackReaderMode::Groups(groups)) => groups.cached(),
+			_ => Vec::new(),
+		}
+	}
+
+	/// Block until the latest group sequence increases, returning the new value.
+	///
+	/// This is useful to monitor the progress of a track without consuming it.
+	/// None is returned when the track is closed.
+	pub async fn latest_changed(&mut self) -> Option<u64> {
+		if self.mode.is_none() {
+			self.mode = Some(self.mode().await.ok()?);
+		}
+
+		self.mode.as_mut()?.latest_changed().await
+	}
+
+	/// Returns the next range of groups that will never be delivered, see [GroupsReader::dropped].
+	///
+	/// Drops are only reported when the track is delivered as groups; None is returned otherwise or when the track is closed.
+	pub async fn dropped(&mut self) -> Option<GroupsDropped> {
+		if self.mode.is_none() {
+			self.mode = Some(self.mode().await.ok()?);
+		}
+
+		match self.mode.as_mut()? {
+			TrackReaderMode::Groups(groups) => groups.dropped().await,
+			_ => None,
+		}
 	}
 
 	pub async fn closed(&self) -> Result<(), ServeError> {
@@ -195,6 +384,19 @@ impl Deref for TrackReader {
 	}
 }
 
+/// A weak reference to a [TrackReader], which doesn't prevent the writer from noticing it's unused.
+#[derive(Clone)]
+pub struct TrackReaderWeak {
+	state: StateWeak<TrackState>,
+	info: Arc<Track>,
+}
+
+impl TrackReaderWeak {
+	pub fn upgrade(&self) -> Option<TrackReader> {
+		Some(TrackReader::new(self.state.upgrade()?, self.info.clone()))
+	}
+}
+
 macro_rules! track_readers {
     {$($name:ident,)*} => {
 		paste! {
@@ -215,6 +417,12 @@ macro_rules! track_readers {
 						$(Self::$name(reader) => reader.latest(),)*
 					}
 				}
+
+				pub async fn latest_changed(&mut self) -> Option<u64> {
+					match self {
+						$(Self::$name(reader) => reader.latest_changed().await,)*
+					}
+				}
 			}
 		}
 	}
@@ -6,13 +6,18 @@
 //!
 //! A [Reader] can request tracks by name.
 //! If the track already exists, it will be returned.
-//! If the track doesn't exist, it will be sent to [Unknown] to be handled.
+//! If the track doesn't exist, it will be sent to the first [TracksWriter::route] matching the name,
+//! falling back to [TracksRequest] to be handled.
 //! A [Reader] can be cloned to create multiple subscriptions.
 //!
 //! The broadcast is automatically closed with [ServeError::Done] when [Writer] is dropped, or all [Reader]s are dropped.
-use std::{collections::HashMap, ops::Deref, sync::Arc};
+use std::{
+	collections::HashMap,
+	ops::{self, Deref},
+	sync::Arc,
+};
 
-use super::{ServeError, Track, TrackReader, TrackWriter};
+use super::{GroupsReader, ServeError, Track, TrackReader, TrackReaderMode, TrackReaderWeak, TrackWriter};
 use crate::watch::{Queue, State};
 
 /// Static information about a broadcast.
@@ -39,9 +44,65 @@ impl Tracks {
 	}
 }
 
-#[derive(Default)]
+/// Matches track names for [TracksWriter::route].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackPattern {
+	/// Only the track with this name.
+	Exact(String),
+
+	/// Any track starting with the prefix.
+	Prefix(String),
+
+	/// Any track matching the glob, where `*` matches any sequence of characters, such as "video/*/hd".
+	Glob(String),
+}
+
+impl TrackPattern {
+	pub fn matches(&self, name: &str) -> bool {
+		match self {
+			Self::Exact(exact) => name == exact,
+			Self::Prefix(prefix) => name.starts_with(prefix.as_str()),
+			Self::Glob(glob) => Self::glob(glob.as_bytes(), name.as_bytes()),
+		}
+	}
+
+	fn glob(glob: &[u8], name: &[u8]) -> bool {
+		match glob.split_first() {
+			// Try every possible length for the wildcard.
+			Some((b'*', rest)) => (0..=name.len()).any(|skip| Self::glob(rest, &name[skip..])),
+			Some((c, rest)) => name.first() == Some(c) && Self::glob(rest, &name[1..]),
+			None => name.is_empty(),
+		}
+	}
+}
+
+// A producer registered with TracksWriter::route.
+struct TrackRoute {
+	pattern: TrackPattern,
+	queue: Queue<TrackWriter>,
+}
+
 pub struct TracksState {
 	tracks: HashMap<String, TrackReader>,
+
+	// Checked in order before the default TracksRequest, with exact routes first.
+	routes: Vec<TrackRoute>,
+
+	// Tracks produced on demand, held weakly so the producer can stop when the last reader leaves.
+	requested: HashMap<String, TrackReaderWeak>,
+
+	closed: Result<(), ServeError>,
+}
+
+impl Default for TracksState {
+	fn default() -> Self {
+		Self {
+			tracks: HashMap::new(),
+			routes: Vec::new(),
+			requested: HashMap::new(),
+			closed: Ok(()),
+		}
+	}
 }
 
 /// Publish new tracks for a broadcast by name.
@@ -58,20 +119,62 @@ impl TracksWriter {
 	/// Create a new track with the given name, inserting it into the broadcast.
 	/// None is returned if all [TracksReader]s have been dropped.
 	pub fn create(&mut self, track: &str) -> Option<TrackWriter> {
-		let (writer, reader) = Track {
-			namespace: self.namespace.clone(),
-			name: track.to_owned(),
-		}
-		.produce();
+		self.insert(Track::new(self.namespace.clone(), track.to_owned()))
+	}
+
+	/// Insert a track with custom parameters, such as the cache size, into the broadcast.
+	/// None is returned if all [TracksReader]s have been dropped.
+	pub fn insert(&mut self, track: Track) -> Option<TrackWriter> {
+		let name = track.name.clone();
+		let (writer, reader) = track.produce();
 
 		// NOTE: We overwrite the track if it already exists.
-		self.state.lock_mut()?.tracks.insert(track.to_owned(), reader);
+		self.state.lock_mut()?.tracks.insert(name, reader);
 
 		Some(writer)
 	}
 
+	/// Handle requests for unknown tracks matching the pattern, instead of the default [TracksRequest].
+	///
+	/// Tracks created by [Self::create] or [Self::insert] always take precedence.
+	/// Exact routes are checked first, then the other routes in the order they were registered.
+	/// Drop the returned handle to unregister the route; later requests fall back to the next match.
+	/// None is returned if all [TracksReader]s have been dropped.
+	pub fn route(&mut self, pattern: TrackPattern) -> Option<TracksRequest> {
+		let mut state = self.state.lock_mut()?;
+		let (send, recv) = Queue::default().split();
+
+		let index = match pattern {
+			TrackPattern::Exact(_) => state
+				.routes
+				.iter()
+				.take_while(|route| matches!(route.pattern, TrackPattern::Exact(_)))
+				.count(),
+			_ => state.routes.len(),
+		};
+		state.routes.insert(index, TrackRoute { pattern, queue: send });
+		drop(state);
+
+		Some(TracksRequest::new(self.state.clone(), recv, self.info.clone()))
+	}
+
 	pub fn remove(&mut self, track: &str) -> Option<TrackReader> {
-		self.state.lock_mut()?.tracks.remove(track)
+		let mut state = self.state.lock_mut()?;
+		let requested = state.requested.remove(track).and_then(|track| track.upgrade());
+		state.tracks.remove(track).or(requested)
+	}
+
+	/// Withdraw the broadcast, so it's unannounced and no more tracks can be requested.
+	///
+	/// Tracks already subscribed are unaffected; close them individually to end them too.
+	pub fn close(self, err: ServeError) -> Result<(), ServeError> {
+		let state = self.state.lock();
+		state.closed.clone()?;
+
+		let mut state = state.into_mut().ok_or(ServeError::Cancel)?;
+		state.closed = Err(err);
+
+		Ok(())
 	}
 }
 
@@ -101,6 +204,9 @@ impl TracksRequest {
 
 	/// Wait for a request to create a new track.
 	/// None is returned if all [TracksReader]s have been dropped.
+	///
+	/// Use [TrackWriter::unused] to stop producing the track once every subscriber has left.
+	/// A later request for the same track will be returned here again.
 	pub async fn next(&mut self) -> Option<TrackWriter> {
 		self.incoming.as_mut()?.pop().await
 	}
@@ -117,7 +223,7 @@ impl Deref for TracksRequest {
 impl Drop for TracksRequest {
 	fn drop(&mut self) {
 		// Close any tracks still in the Queue
-		for track in self.incoming.take().unwrap().close() {
+		for track in self.incoming.take().unwrap().drain() {
 			let _ = track.close(ServeError::NotFound);
 		}
 	}
@@ -142,26 +248,81 @@ impl TracksReader {
 	/// None is returned if [TracksWriter] or [TracksRequest] cannot fufill the request.
 	pub fn subscribe(&mut self, name: &str) -> Option<TrackReader> {
 		let state = self.state.lock();
+		state.closed.clone().ok()?;
 
 		if let Some(track) = state.tracks.get(name) {
 			return Some(track.clone());
 		}
 
-		let mut state = state.into_mut()?;
-		let track = Track {
-			namespace: self.namespace.clone(),
-			name: name.to_owned(),
+		if let Some(track) = state.requested.get(name).and_then(|track| track.upgrade()) {
+			return Some(track);
 		}
-		.produce();
 
-		if self.queue.push(track.0).is_err() {
-			return None;
+		let mut state = state.into_mut()?;
+		let (writer, reader) = Track::new(self.namespace.clone(), name.to_owned()).produce();
+
+		// Give the track to the first matching route, forgetting any that were dropped.
+		let mut writer = Some(writer);
+		state.routes.retain_mut(|route| {
+			if !route.pattern.matches(name) {
+				return true;
+			}
+
+			let Some(track) = writer.take() else {
+				return true;
+			};
+
+			match route.queue.push(track) {
+				Ok(()) => true,
+				Err(track) => {
+					writer = Some(track);
+					false
+				}
+			}
+		});
+
+		if let Some(writer) = writer {
+			if self.queue.push(writer).is_err() {
+				return None;
+			}
 		}
 
 		// We requested the track sucessfully so we can deduplicate it.
-		state.tracks.insert(name.to_owned(), track.1.clone());
+		state.requested.insert(name.to_owned(), reader.downgrade());
+
+		Some(reader)
+	}
 
-		Some(track.1.clone())
+	/// Get or request a track like [Self::subscribe], but only read the groups in the range, for DVR-style rewind.
+	///
+	/// Older groups are served from the track's cache, such as a relay's cache or a recording replayed on demand.
+	/// See [GroupsReader::range] for how the range is delivered.
+	pub async fn subscribe_range(&mut self, name: &str, range: ops::Range<u64>) -> Result<GroupsReader, ServeError> {
+		let track = self.subscribe(name).ok_or(ServeError::NotFound)?;
+
+		match track.mode().await? {
+			TrackReaderMode::Groups(mut groups) => {
+				groups.range(range);
+				Ok(groups)
+			}
+			_ => Err(ServeError::Mode),
+		}
+	}
+
+	/// Block until the broadcast is closed with an error, or every [TracksWriter] and [TracksRequest] is dropped.
+	pub async fn closed(&self) -> Result<(), ServeError> {
+		loop {
+			{
+				let state = self.state.lock();
+				state.closed.clone()?;
+
+				match state.modified() {
+					Some(notify) => notify,
+					None => return Ok(()),
+				}
+			}
+			.await;
+		}
 	}
 }
 
@@ -172,3 +333,79 @@ impl Deref for TracksReader {
 		&self.info
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	use crate::serve::GroupOrder;
+
+	#[test]
+	fn pattern() {
+		assert!(TrackPattern::Exact("video".into()).matches("video"));
+		assert!(!TrackPattern::Exact("video".into()).matches("video/hd"));
+		assert!(TrackPattern::Prefix("video/".into()).matches("video/hd"));
+		assert!(!TrackPattern::Prefix("video/".into()).matches("audio/hd"));
+
+		let glob = TrackPattern::Glob("video/*/hd".into());
+		assert!(glob.matches("video/1/hd"));
+		assert!(glob.matches("video/a/b/hd"));
+		assert!(!glob.matches("video/1/sd"));
+		assert!(TrackPattern::Glob("*".into()).matches(""));
+	}
+
+	#[tokio::test]
+	async fn route() {
+		let (mut writer, mut fallback, mut reader) = Tracks::new("test".into()).produce();
+		let _cached = writer.create("video/cached").unwrap();
+
+		let mut prefix = writer.route(TrackPattern::Prefix("video/".into())).unwrap();
+		let mut exact = writer.route(TrackPattern::Exact("video/exact".into())).unwrap();
+
+		// Static tracks win over any route.
+		reader.subscribe("video/cached").unwrap();
+
+		// Exact routes win over patterns, even if registered later.
+		reader.subscribe("video/exact").unwrap();
+		assert_eq!(exact.next().await.unwrap().name, "video/exact");
+
+		reader.subscribe("video/other").unwrap();
+		assert_eq!(prefix.next().await.unwrap().name, "video/other");
+
+		// Unregistering a route falls back to the default handler.
+		drop(prefix);
+		reader.subscribe("video/later").unwrap();
+		assert_eq!(fallback.next().await.unwrap().name, "video/later");
+	}
+
+	#[tokio::test]
+	async fn subscribe_range() {
+		let (mut writer, _, mut reader) = Tracks::new("test".into()).produce();
+
+		let track = Track::new("test".into(), "video".into())
+			.cache(8)
+			.order(GroupOrder::Ascending);
+		let mut groups = writer.insert(track).unwrap().groups().unwrap();
+		for _ in 0..5 {
+			groups.append(0).unwrap();
+		}
+
+		// Rewind into the cache, finishing at the end of the range without waiting for newer groups.
+		let mut range = reader.subscribe_range("video", 1..3).await.unwrap();
+		assert_eq!(range.next().await.unwrap().unwrap().group_id, 1);
+		assert_eq!(range.next().await.unwrap().unwrap().group_id, 2);
+		assert!(range.next().await.unwrap().is_none());
+
+		// A range that hasn't been produced yet waits for its first group.
+		let mut range = reader.subscribe_range("video", 6..7).await.unwrap();
+		groups.append(0).unwrap();
+		groups.append(0).unwrap();
+		assert_eq!(range.next().await.unwrap().unwrap().group_id, 6);
+		assert!(range.next().await.unwrap().is_none());
+
+		assert_eq!(
+			reader.subscribe_range("missing", 0..1).await.err(),
+			Some(ServeError::NotFound)
+		);
+	}
+}
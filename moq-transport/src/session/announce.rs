@@ -1,9 +1,9 @@
 use std::{collections::VecDeque, ops};
 
+use crate::serve::ServeError;
 use crate::watch::State;
-use crate::{message, serve::ServeError};
 
-use super::{Publisher, Subscribed, TrackStatusRequested};
+use super::{Fetched, Publisher, Subscribed, TrackStatusRequested};
 
 #[derive(Debug, Clone)]
 pub struct AnnounceInfo {
@@ -13,6 +13,7 @@ pub struct AnnounceInfo {
 struct AnnounceState {
 	subscribers: VecDeque<Subscribed>,
 	track_statuses_requested: VecDeque<TrackStatusRequested>,
+	fetches: VecDeque<Fetched>,
 	ok: bool,
 	closed: Result<(), ServeError>,
 }
@@ -22,6 +23,7 @@ impl Default for AnnounceState {
 		Self {
 			subscribers: Default::default(),
 			track_statuses_requested: Default::default(),
+			fetches: Default::default(),
 			ok: false,
 			closed: Ok(()),
 		}
@@ -50,10 +52,7 @@ impl Announce {
 			namespace: namespace.clone(),
 		};
 
-		publisher.send_message(message::Announce {
-			namespace,
-			params: Default::default(),
-		});
+		publisher.send_announce(namespace);
 
 		let (send, recv) = State::default().split();
 
@@ -121,6 +120,24 @@ impl Announce {
 		}
 	}
 
+	pub async fn fetched(&self) -> Result<Option<Fetched>, ServeError> {
+		loop {
+			{
+				let state = self.state.lock();
+				if !state.fetches.is_empty() {
+					return Ok(state.into_mut().and_then(|mut state| state.fetches.pop_front()));
+				}
+
+				state.closed.clone()?;
+				match state.modified() {
+					Some(notified) => notified,
+					None => return Ok(None),
+				}
+			}
+			.await;
+		}
+	}
+
 	// Wait until an OK is received
 	pub async fn ok(&self) -> Result<(), ServeError> {
 		loop {
@@ -147,9 +164,7 @@ impl Drop for Announce {
 			return;
 		}
 
-		self.publisher.send_message(message::Unannounce {
-			namespace: self.namespace.to_string(),
-		});
+		self.publisher.send_unannounce(self.namespace.to_string());
 	}
 }
 
@@ -203,4 +218,10 @@ impl AnnounceRecv {
 		state.track_statuses_requested.push_back(track_status_requested);
 		Ok(())
 	}
+
+	pub fn recv_fetch(&mut self, fetched: Fetched) -> Result<(), ServeError> {
+		let mut state = self.state.lock_mut().ok_or(ServeError::Done)?;
+		state.fetches.push_back(fetched);
+		Ok(())
+	}
 }
@@ -1,14 +1,18 @@
 use std::ops;
 
-use crate::watch::State;
+use crate::error::ErrorCode;
+use crate::watch::{Queue, State};
 use crate::{message, serve::ServeError};
 
 use super::{AnnounceInfo, Subscriber};
 
-// There's currently no feedback from the peer, so the shared state is empty.
+// There's currently no feedback from the peer, so the shared state only records our response.
 // If Unannounce contained an error code then we'd be talking.
 #[derive(Default)]
-struct AnnouncedState {}
+struct AnnouncedState {
+	// Set once we've sent ANNOUNCE_OK, so it can be sent again when the peer announces after a reconnect.
+	ok: bool,
+}
 
 pub struct Announced {
 	session: Subscriber,
@@ -32,7 +36,10 @@ impl Announced {
 			error: None,
 			state: send,
 		};
-		let recv = AnnouncedRecv { _state: recv };
+		let recv = AnnouncedRecv {
+			state: recv,
+			resumed: None,
+		};
 
 		(send, recv)
 	}
@@ -48,6 +55,9 @@ impl Announced {
 		});
 
 		self.ok = true;
+		if let Some(mut state) = self.state.lock_mut() {
+			state.ok = true;
+		}
 
 		Ok(())
 	}
@@ -92,11 +102,60 @@ impl Drop for Announced {
 	}
 }
 
+/// An event returned by [super::Subscriber::announced_event], or by [Announcements::next] with only the [AnnounceInfo].
+#[derive(Debug, Clone)]
+pub enum AnnouncedEvent<T = Announced> {
+	/// The peer announced a new namespace.
+	Announced(T),
+
+	/// The peer withdrew a previously announced namespace, we rejected it, or the session moved on.
+	Unannounced(AnnounceInfo),
+}
+
+/// Observes the namespaces announced by the peer, created by [super::Subscriber::announcements] or [super::Subscriber::announced_prefix].
+///
+/// Unlike [super::Subscriber::announced], every handle receives every event, starting with the namespaces already announced.
+/// Handles only observe; the announcements are still accepted or rejected via [Announced].
+/// A namespace announced and withdrawn before the handle reads it is skipped, so at most two events are queued per namespace.
+pub struct Announcements {
+	queue: Queue<AnnouncedEvent<AnnounceInfo>>,
+
+	// The prefix sent with SUBSCRIBE_NAMESPACE, removed when the last handle is dropped.
+	prefix: Option<(Subscriber, String)>,
+}
+
+impl Announcements {
+	pub(super) fn new(queue: Queue<AnnouncedEvent<AnnounceInfo>>, prefix: Option<(Subscriber, String)>) -> Self {
+		Self { queue, prefix }
+	}
+
+	/// Returns the next event, or None once the subscriber has shut down.
+	pub async fn next(&mut self) -> Option<AnnouncedEvent<AnnounceInfo>> {
+		self.queue.pop().await
+	}
+}
+
+impl Drop for Announcements {
+	fn drop(&mut self) {
+		if let Some((mut subscriber, prefix)) = self.prefix.take() {
+			subscriber.drop_announce_prefix(&prefix);
+		}
+	}
+}
+
 pub(super) struct AnnouncedRecv {
-	_state: State<AnnouncedState>,
+	state: State<AnnouncedState>,
+
+	// The reconnect that carried this over from a previous session, until the peer announces it again.
+	pub resumed: Option<u64>,
 }
 
 impl AnnouncedRecv {
+	/// Returns true if we've sent ANNOUNCE_OK.
+	pub fn is_ok(&self) -> bool {
+		self.state.lock().ok
+	}
+
 	pub fn recv_unannounce(self) -> Result<(), ServeError> {
 		// Will cause the state to be dropped
 		Ok(())
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use crate::serve::ServeError;
+
+use super::{Limits, Observer, SessionError, SessionLimits, SessionObserver};
+
+/// The peer authenticated during setup, see [super::Session::identity].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+	/// The name chosen by the [Authenticator], such as the subject of a JWT.
+	pub name: String,
+}
+
+impl Identity {
+	pub fn new<T: Into<String>>(name: T) -> Self {
+		Self { name: name.into() }
+	}
+}
+
+/// Validates the [crate::setup::AuthToken] sent by the client, before the session starts.
+///
+/// Return [SessionError::Unauthorized] to reject the session; the error code is sent to the client.
+pub trait Authenticator: Send + Sync {
+	/// The token is None if the client didn't send one.
+	fn authenticate(&self, token: Option<&str>) -> Result<Identity, SessionError>;
+}
+
+impl<F> Authenticator for F
+where
+	F: Fn(Option<&str>) -> Result<Identity, SessionError> + Send + Sync,
+{
+	fn authenticate(&self, token: Option<&str>) -> Result<Identity, SessionError> {
+		self(token)
+	}
+}
+
+/// A request from the peer, checked by an [Authorizer] before it's accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthRequest<'a> {
+	/// The peer wants to publish the broadcast.
+	Announce { namespace: &'a str },
+
+	/// The peer wants to receive a track from the broadcast.
+	Subscribe { namespace: &'a str, name: &'a str },
+}
+
+/// Decides whether the peer may announce or subscribe, which is needed to run a multi-tenant relay.
+///
+/// The identity is None unless the session was accepted with an [Authenticator].
+/// Return an error to reject the request; its code is sent to the peer.
+pub trait Authorizer: Send + Sync {
+	fn authorize(&self, identity: Option<&Identity>, request: &AuthRequest) -> Result<(), ServeError>;
+}
+
+impl<F> Authorizer for F
+where
+	F: Fn(Option<&Identity>, &AuthRequest) -> Result<(), ServeError> + Send + Sync,
+{
+	fn authorize(&self, identity: Option<&Identity>, request: &AuthRequest) -> Result<(), ServeError> {
+		self(identity, request)
+	}
+}
+
+// The peer's identity, the authorizer for its requests and their limits, shared by the publisher and subscriber.
+// It also carries the session's observer, since it's replaced along with the session on reconnect.
+#[derive(Clone, Default)]
+pub(super) struct Access {
+	identity: Option<Identity>,
+	authorizer: Option<Arc<dyn Authorizer>>,
+	limits: Limits,
+	observer: Observer,
+}
+
+impl Access {
+	pub fn new(
+		identity: Option<Identity>,
+		authorizer: Option<Arc<dyn Authorizer>>,
+		limits: SessionLimits,
+		observer: Option<Arc<dyn SessionObserver>>,
+	) -> Self {
+		Self {
+			identity,
+			authorizer,
+			limits: Limits::new(limits),
+			observer: Observer::new(observer),
+		}
+	}
+
+	pub fn identity(&self) -> Option<&Identity> {
+		self.identity.as_ref()
+	}
+
+	pub fn limits(&self) -> &Limits {
+		&self.limits
+	}
+
+	pub fn observer(&self) -> &Observer {
+		&self.observer
+	}
+
+	pub fn check(&self, request: AuthRequest) -> Result<(), ServeError> {
+		match &self.authorizer {
+			Some(authorizer) => authorizer.authorize(self.identity.as_ref(), &request),
+			None => Ok(()),
+		}
+	}
+}
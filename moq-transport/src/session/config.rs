@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use crate::coding::EncodeError;
+use crate::message::Codecs;
+use crate::setup;
+
+use crate::serve::ServeError;
+
+use super::{AuthRequest, Authenticator, Authorizer, Identity, SessionError, SessionLimits, SessionObserver};
+
+/// Options for the SETUP handshake, see [super::Session::connect_with] and [super::Session::accept_with].
+#[derive(Clone)]
+pub struct SessionConfig {
+	/// The requested role, which may be narrowed by the peer.
+	pub role: setup::Role,
+
+	/// The supported versions, each with the codec used for the control stream.
+	pub codecs: Codecs,
+
+	/// Our extensions, sent to the peer.
+	pub extensions: setup::Extensions,
+
+	/// Validates the client's token before accepting a session, as the server.
+	pub auth: Option<Arc<dyn Authenticator>>,
+
+	/// Checks each ANNOUNCE and SUBSCRIBE from the peer.
+	pub authorizer: Option<Arc<dyn Authorizer>>,
+
+	/// Caps the requests and data accepted from the peer.
+	pub limits: SessionLimits,
+
+	/// Receives each event, for debugging the protocol.
+	pub observer: Option<Arc<dyn SessionObserver>>,
+
+	/// Send requests before the server's SETUP arrives, as the client; see [Self::with_early].
+	pub early: bool,
+}
+
+impl SessionConfig {
+	pub fn new(role: setup::Role) -> Self {
+		Self {
+			role,
+			codecs: Codecs::default(),
+			extensions: setup::Extensions::default(),
+			auth: None,
+			authorizer: None,
+			limits: SessionLimits::default(),
+			observer: None,
+			early: false,
+		}
+	}
+
+	/// Send a token to authenticate the session, as the client.
+	pub fn with_token<T: Into<String>>(mut self, token: T) -> Result<Self, EncodeError> {
+		self.extensions.set(setup::AuthToken(token.into()))?;
+		Ok(self)
+	}
+
+	/// Authenticate each client before accepting the session, as the server.
+	pub fn with_auth<F>(mut self, auth: F) -> Self
+	where
+		F: Fn(Option<&str>) -> Result<Identity, SessionError> + Send + Sync + 'static,
+	{
+		self.auth = Some(Arc::new(auth));
+		self
+	}
+
+	/// Check each ANNOUNCE and SUBSCRIBE from the peer, rejecting it with the returned error.
+	pub fn with_authorizer<F>(mut self, authorizer: F) -> Self
+	where
+		F: Fn(Option<&Identity>, &AuthRequest) -> Result<(), ServeError> + Send + Sync + 'static,
+	{
+		self.authorizer = Some(Arc::new(authorizer));
+		self
+	}
+
+	/// Refuse requests from the peer over the limits, see [SessionLimits].
+	pub fn with_limits(mut self, limits: SessionLimits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	/// Report each control message, data stream and dropped group, such as to a [super::QlogWriter].
+	pub fn with_observer<O: SessionObserver + 'static>(mut self, observer: O) -> Self {
+		self.observer = Some(Arc::new(observer));
+		self
+	}
+}
+
+impl SessionConfig {
+	/// Return from connect without waiting for the server's SETUP, so the first requests are sent alongside ours.
+	///
+	/// Over a resumed QUIC connection with 0-RTT, this puts the SETUP and the first SUBSCRIBE in early data,
+	/// saving a round trip before the first frame. Early data can be replayed by an attacker, so this is opt-in.
+	///
+	/// Requests are encoded before the server picks a version, so only the first version in [Self::codecs] is offered.
+	/// A server that doesn't support it fails the session instead of negotiating another, so dial again without this.
+	/// For the same reason, [crate::message::SUBSCRIBE_START_PARAM] isn't advertised.
+	/// The server's SETUP is verified by [super::Session::run], which fails if the version or role is rejected.
+	/// Until then, the session reports the requested role and no extensions.
+	pub fn with_early(mut self) -> Self {
+		self.early = true;
+		self
+	}
+}
+
+impl Default for SessionConfig {
+	fn default() -> Self {
+		Self::new(setup::Role::Both)
+	}
+}
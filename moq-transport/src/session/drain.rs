@@ -0,0 +1,170 @@
+use std::{sync::Arc, time};
+
+use crate::message::{self, Message};
+use crate::transport;
+use crate::watch::Queue;
+
+use super::{Publisher, SessionError};
+
+#[derive(Clone, Copy, Default)]
+struct DrainState {
+	// We sent GOAWAY, so new requests from the peer are refused.
+	local: bool,
+
+	// The peer sent GOAWAY.
+	remote: bool,
+
+	// We closed the session after draining.
+	closed: bool,
+}
+
+// Signals that a session is draining after sending or receiving GOAWAY.
+#[derive(Clone)]
+pub(super) struct Drain {
+	state: Arc<tokio::sync::watch::Sender<DrainState>>,
+}
+
+impl Drain {
+	pub fn new() -> Self {
+		Self {
+			state: Arc::new(tokio::sync::watch::channel(DrainState::default()).0),
+		}
+	}
+
+	// Returns true if we sent GOAWAY, so new requests should be refused.
+	pub fn is_closing(&self) -> bool {
+		self.state.borrow().local
+	}
+
+	pub fn close(&self) {
+		self.state.send_modify(|state| state.local = true);
+	}
+
+	pub fn finish(&self) {
+		self.state.send_modify(|state| state.closed = true);
+	}
+
+	// Returns true if we closed the session after draining, so any errors are expected.
+	pub fn is_closed(&self) -> bool {
+		self.state.borrow().closed
+	}
+
+	pub fn recv_go_away(&self) {
+		self.state.send_modify(|state| state.remote = true);
+	}
+
+	// Block until we send GOAWAY.
+	pub async fn closing(&self) {
+		let mut state = self.state.subscribe();
+
+		// NOTE: This can't fail because we hold the sender.
+		state.wait_for(|state| state.local).await.ok();
+	}
+
+	// Block until either side sends GOAWAY.
+	pub async fn draining(&self) {
+		let mut state = self.state.subscribe();
+
+		// NOTE: This can't fail because we hold the sender.
+		state.wait_for(|state| state.local || state.remote).await.ok();
+	}
+}
+
+// Counts the subscriptions being served, so draining waits until each has sent SUBSCRIBE_DONE.
+#[derive(Clone)]
+pub(super) struct Serving {
+	count: Arc<tokio::sync::watch::Sender<usize>>,
+}
+
+impl Serving {
+	pub fn new() -> Self {
+		Self {
+			count: Arc::new(tokio::sync::watch::channel(0).0),
+		}
+	}
+
+	// Returns a guard that should be dropped after SUBSCRIBE_DONE is queued.
+	pub fn start(&self) -> ServingGuard {
+		self.count.send_modify(|count| *count += 1);
+		ServingGuard {
+			count: self.count.clone(),
+		}
+	}
+
+	// Block until no subscriptions are being served.
+	pub async fn idle(&self) {
+		let mut count = self.count.subscribe();
+
+		// NOTE: This can't fail because we hold the sender.
+		count.wait_for(|count| *count == 0).await.ok();
+	}
+}
+
+pub(super) struct ServingGuard {
+	count: Arc<tokio::sync::watch::Sender<usize>>,
+}
+
+impl Drop for ServingGuard {
+	fn drop(&mut self) {
+		self.count.send_modify(|count| *count -= 1);
+	}
+}
+
+/// Gracefully closes a running [super::Session], returned by [super::Session::closer].
+#[derive(Clone)]
+pub struct SessionCloser {
+	transport: transport::Session,
+	outgoing: Queue<Message>,
+	drain: Drain,
+	publisher: Option<Publisher>,
+}
+
+impl SessionCloser {
+	pub(super) fn new(
+		transport: transport::Session,
+		outgoing: Queue<Message>,
+		drain: Drain,
+		publisher: Option<Publisher>,
+	) -> Self {
+		Self {
+			transport,
+			outgoing,
+			drain,
+			publisher,
+		}
+	}
+
+	/// Send GOAWAY and close the session once every data stream has finished, or the timeout expires.
+	///
+	/// New subscriptions and announcements from the peer are refused with [crate::serve::ServeError::GoingAway],
+	/// while existing subscriptions continue to be served so the peer has a chance to migrate.
+	/// Each subscription finishes the groups in flight but won't start another,
+	/// then ends with SUBSCRIBE_DONE and [crate::serve::ServeError::GoingAway] so the session can drain.
+	pub async fn close_gracefully(mut self, timeout: time::Duration) -> Result<(), SessionError> {
+		self.drain.close();
+
+		// NOTE: An empty URL means the peer should reconnect to the same server.
+		let go_away = message::GoAway { url: String::new() };
+		self.outgoing.push(go_away.into()).map_err(|_| SessionError::Internal)?;
+
+		let mut outgoing = self.outgoing.clone();
+		let drained = async {
+			if let Some(publisher) = &self.publisher {
+				publisher.drained().await;
+			}
+
+			// Flush any remaining control messages, like the final SUBSCRIBE_DONE.
+			outgoing.close();
+			outgoing.drained().await;
+		};
+
+		if tokio::time::timeout(timeout, drained).await.is_err() {
+			log::debug!("timed out waiting for the session to drain");
+		}
+
+		self.drain.finish();
+		self.transport.close(0, "going away");
+
+		Ok(())
+	}
+}
@@ -1,15 +1,9 @@
-use crate::{coding, serve, setup};
+use crate::{coding, error::ErrorCode, serve, setup, transport};
 
 #[derive(thiserror::Error, Debug, Clone)]
 pub enum SessionError {
-	#[error("webtransport session: {0}")]
-	Session(#[from] web_transport::SessionError),
-
-	#[error("webtransport write: {0}")]
-	Write(#[from] web_transport::WriteError),
-
-	#[error("webtransport read: {0}")]
-	Read(#[from] web_transport::ReadError),
+	#[error("transport error: {0}")]
+	Transport(#[from] transport::TransportError),
 
 	#[error("encode error: {0}")]
 	Encode(#[from] coding::EncodeError),
@@ -45,17 +39,22 @@ pub enum SessionError {
 
 	#[error("wrong size")]
 	WrongSize,
+
+	/// The client's token was missing or rejected by the [super::Authenticator].
+	#[error("unauthorized")]
+	Unauthorized,
+
+	/// The peer didn't answer a PING before the keepalive timeout.
+	#[error("keepalive timeout")]
+	Timeout,
 }
 
-impl SessionError {
-	/// An integer code that is sent over the wire.
-	pub fn code(&self) -> u64 {
+impl ErrorCode for SessionError {
+	fn code(&self) -> u64 {
 		match self {
 			Self::RoleIncompatible(..) => 406,
 			Self::RoleViolation => 405,
-			Self::Session(_) => 503,
-			Self::Read(_) => 500,
-			Self::Write(_) => 500,
+			Self::Transport(err) => err.code(),
 			Self::Version(..) => 406,
 			Self::Decode(_) => 400,
 			Self::Encode(_) => 500,
@@ -63,11 +62,57 @@ impl SessionError {
 			Self::Duplicate => 409,
 			Self::Internal => 500,
 			Self::WrongSize => 400,
+			Self::Timeout => 504,
+			Self::Unauthorized => 401,
 			Self::Serve(err) => err.code(),
 		}
 	}
 }
 
+impl SessionError {
+	/// Returns the error the peer sent when it reset a stream.
+	pub fn reset(&self) -> Option<serve::ServeError> {
+		match self {
+			Self::Transport(transport::TransportError::Reset(code)) => {
+				Some(serve::ServeError::from_code((*code).into()))
+			}
+			_ => None,
+		}
+	}
+
+	/// Returns true if the error may go away by reconnecting, such as the network failing.
+	///
+	/// False if the peer rejected the session during setup, since it would only reject it again.
+	pub fn is_retryable(&self) -> bool {
+		match self {
+			Self::Version(..) | Self::RoleIncompatible(..) | Self::Unauthorized => false,
+			// The server closes the session with the code when it rejects the handshake.
+			Self::Transport(transport::TransportError::Closed { code, .. }) => !matches!(code, 401 | 406),
+			_ => true,
+		}
+	}
+
+	/// Returns the code and reason if the peer closed the session explicitly.
+	///
+	/// None means the session failed for another reason, such as the network dying.
+	pub fn close(&self) -> Option<SessionClose> {
+		match self {
+			Self::Transport(transport::TransportError::Closed { code, reason }) => Some(SessionClose {
+				code: *code,
+				reason: reason.clone(),
+			}),
+			_ => None,
+		}
+	}
+}
+
+/// The code and reason sent by the peer when it closed the session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionClose {
+	pub code: u32,
+	pub reason: String,
+}
+
 impl From<SessionError> for serve::ServeError {
 	fn from(err: SessionError) -> Self {
 		match err {
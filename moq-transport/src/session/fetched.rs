@@ -0,0 +1,97 @@
+use std::ops;
+
+use crate::error::ErrorCode;
+use crate::serve::{ServeError, TrackReader, TrackReaderMode};
+use crate::{data, serve};
+
+use super::{SessionError, Writer};
+
+#[derive(Debug, Clone)]
+pub struct FetchInfo {
+	pub namespace: String,
+	pub name: String,
+	pub group_id: u64,
+}
+
+/// A request for a single cached group, received by the publisher.
+pub struct Fetched {
+	writer: Writer,
+
+	pub info: FetchInfo,
+}
+
+impl Fetched {
+	pub(super) fn new(writer: Writer, header: data::FetchHeader) -> Self {
+		let info = FetchInfo {
+			namespace: header.namespace,
+			name: header.name,
+			group_id: header.group_id,
+		};
+
+		Self { writer, info }
+	}
+
+	/// Serve the requested group from the track's cache, then close the stream.
+	pub async fn serve(self, track: TrackReader) -> Result<(), SessionError> {
+		let group = match track.mode().await? {
+			TrackReaderMode::Groups(groups) => groups.get(self.group_id),
+			_ => None,
+		};
+
+		match group {
+			Some(group) => self.serve_group(group).await,
+			// The group was never cached or has expired.
+			None => self.close(ServeError::NotFound).await,
+		}
+	}
+
+	async fn serve_group(mut self, mut group: serve::GroupReader) -> Result<(), SessionError> {
+		let response = data::FetchResponse {
+			code: 0,
+			send_order: group.priority,
+		};
+
+		self.writer.encode(&response).await?;
+
+		log::trace!("sent fetch: {:?}", self.info);
+
+		while let Some(mut object) = group.next().await? {
+			let header = data::GroupObject {
+				object_id: object.object_id,
+				size: object.size,
+				status: object.status,
+				meta: object.meta.clone(),
+			};
+
+			self.writer.encode(&header).await?;
+
+			while let Some(chunk) = object.read().await? {
+				self.writer.write_chunk(chunk).await?;
+			}
+		}
+
+		log::trace!("sent fetch done");
+
+		Ok(())
+	}
+
+	/// Reject the fetch with an error.
+	pub async fn close(mut self, err: ServeError) -> Result<(), SessionError> {
+		let response = data::FetchResponse {
+			code: err.code(),
+			send_order: 0,
+		};
+
+		self.writer.encode(&response).await?;
+
+		Ok(())
+	}
+}
+
+impl ops::Deref for Fetched {
+	type Target = FetchInfo;
+
+	fn deref(&self) -> &Self::Target {
+		&self.info
+	}
+}
@@ -0,0 +1,109 @@
+use std::{
+	sync::{Arc, Mutex},
+	time,
+};
+
+use crate::message::{self, Message};
+use crate::watch::Queue;
+
+use super::SessionError;
+
+/// The setup parameter advertising support for PING and PONG.
+pub const KEEPALIVE_PARAM: u64 = 0x3f00;
+
+/// The latest round trip time measured by the keepalive, see [super::Session::set_keepalive].
+#[derive(Clone, Default)]
+pub struct SessionRtt {
+	rtt: Arc<Mutex<Option<time::Duration>>>,
+}
+
+impl SessionRtt {
+	/// Returns None until the first PONG is received.
+	pub fn get(&self) -> Option<time::Duration> {
+		*self.rtt.lock().unwrap()
+	}
+
+	fn set(&self, rtt: time::Duration) {
+		*self.rtt.lock().unwrap() = Some(rtt);
+	}
+
+	fn clear(&self) {
+		*self.rtt.lock().unwrap() = None;
+	}
+}
+
+// Sends PING and answers PONG on the control stream.
+#[derive(Clone)]
+pub(super) struct Keepalive {
+	outgoing: Queue<Message>,
+
+	// The sequence of the latest PONG received.
+	pong: Arc<tokio::sync::watch::Sender<u64>>,
+
+	// Wakes up the loop to send a PING immediately.
+	probe: Arc<tokio::sync::Notify>,
+
+	rtt: SessionRtt,
+}
+
+impl Keepalive {
+	pub fn new(outgoing: Queue<Message>) -> Self {
+		Self {
+			outgoing,
+			pong: Arc::new(tokio::sync::watch::channel(0).0),
+			probe: Default::default(),
+			rtt: SessionRtt::default(),
+		}
+	}
+
+	pub fn outgoing(&self) -> Queue<Message> {
+		self.outgoing.clone()
+	}
+
+	pub fn rtt(&self) -> SessionRtt {
+		self.rtt.clone()
+	}
+
+	// Forget the round trip time and measure it again now, after moving to a new network path.
+	pub fn probe(&self) {
+		self.rtt.clear();
+		self.probe.notify_one();
+	}
+
+	pub fn recv_ping(&mut self, msg: message::Ping) {
+		let pong = message::Pong { sequence: msg.sequence };
+		self.outgoing.push(pong.into()).ok();
+	}
+
+	pub fn recv_pong(&mut self, msg: message::Pong) {
+		self.pong.send_replace(msg.sequence);
+	}
+
+	// Send a PING every interval, failing if the PONG doesn't arrive before the timeout.
+	pub async fn run(mut self, interval: time::Duration, timeout: time::Duration) -> Result<(), SessionError> {
+		let mut pong = self.pong.subscribe();
+
+		// The first PING is 1, since the initial value means no PONG has been received.
+		let mut sequence = 0;
+
+		loop {
+			tokio::select! {
+				_ = tokio::time::sleep(interval) => {},
+				_ = self.probe.notified() => {},
+			}
+
+			sequence += 1;
+			let sent = tokio::time::Instant::now();
+
+			self.outgoing
+				.push(message::Ping { sequence }.into())
+				.map_err(|_| SessionError::Internal)?;
+
+			match tokio::time::timeout(timeout, pong.wait_for(|pong| *pong >= sequence)).await {
+				Ok(Ok(_)) => self.rtt.set(sent.elapsed()),
+				Ok(Err(_)) => return Err(SessionError::Internal),
+				Err(_) => return Err(SessionError::Timeout),
+			}
+		}
+	}
+}
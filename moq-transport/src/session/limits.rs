@@ -0,0 +1,90 @@
+use std::{
+	sync::{Arc, Mutex},
+	time,
+};
+
+/// Caps on the requests and data accepted from the peer, so a server can defend against abusive clients.
+///
+/// Requests over a limit are refused with [crate::serve::ServeError::Quota]. Every limit is disabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct SessionLimits {
+	/// The maximum number of subscriptions from the peer served at once.
+	pub max_subscriptions: Option<usize>,
+
+	/// The maximum number of namespaces announced by the peer at once.
+	pub max_announces: Option<usize>,
+
+	/// The maximum number of data streams from the peer read at once, stopping any others.
+	pub max_streams: Option<usize>,
+
+	/// The maximum rate that data is received from the peer, in bytes per second.
+	///
+	/// Reading is paused while over the limit, so QUIC flow control slows the peer down.
+	pub max_bitrate: Option<u64>,
+}
+
+// The limits for a session, along with the state used to enforce them.
+#[derive(Clone, Default)]
+pub(super) struct Limits {
+	config: SessionLimits,
+	bucket: Option<Arc<Mutex<TokenBucket>>>,
+}
+
+impl Limits {
+	pub fn new(config: SessionLimits) -> Self {
+		let bucket = config
+			.max_bitrate
+			.map(|rate| Arc::new(Mutex::new(TokenBucket::new(rate))));
+
+		Self { config, bucket }
+	}
+
+	pub fn config(&self) -> &SessionLimits {
+		&self.config
+	}
+
+	/// Wait until `size` more bytes can be received without exceeding the bitrate.
+	pub async fn throttle(&self, size: usize) {
+		let delay = match &self.bucket {
+			Some(bucket) => bucket.lock().unwrap().consume(size),
+			None => return,
+		};
+
+		if !delay.is_zero() {
+			tokio::time::sleep(delay).await;
+		}
+	}
+}
+
+// Refills at the bitrate, allowing a burst of up to a second's worth of data.
+struct TokenBucket {
+	rate: f64,
+	tokens: f64,
+	updated: time::Instant,
+}
+
+impl TokenBucket {
+	fn new(rate: u64) -> Self {
+		let rate = rate.max(1) as f64;
+
+		Self {
+			rate,
+			tokens: rate,
+			updated: time::Instant::now(),
+		}
+	}
+
+	// Take the tokens, returning how long to wait until the debt is repaid.
+	fn consume(&mut self, size: usize) -> time::Duration {
+		let now = time::Instant::now();
+		let elapsed = now.duration_since(self.updated).as_secs_f64();
+		self.updated = now;
+
+		self.tokens = (self.tokens + elapsed * self.rate).min(self.rate) - size as f64;
+
+		match self.tokens < 0.0 {
+			true => time::Duration::from_secs_f64(-self.tokens / self.rate),
+			false => time::Duration::ZERO,
+		}
+	}
+}
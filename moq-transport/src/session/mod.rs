@@ -1,8 +1,26 @@
 mod announce;
 mod announced;
+mod auth;
+mod config;
+mod drain;
 mod error;
+mod fetched;
+mod keepalive;
+mod limits;
+mod observer;
+mod path;
+mod pool;
+mod priority;
+mod progress;
 mod publisher;
+mod qlog;
 mod reader;
+mod reconnect;
+mod redirect;
+mod relay;
+mod scheduler;
+mod sequence;
+mod stats;
 mod subscribe;
 mod subscribed;
 mod subscriber;
@@ -11,116 +29,301 @@ mod writer;
 
 pub use announce::*;
 pub use announced::*;
+pub use auth::*;
+pub use config::*;
+pub use drain::*;
 pub use error::*;
+pub use fetched::*;
+pub use keepalive::*;
+pub use limits::*;
+pub use observer::*;
+pub use path::*;
+pub use priority::*;
+pub use progress::*;
 pub use publisher::*;
+pub use qlog::*;
+pub use reconnect::*;
+pub use relay::*;
+pub use scheduler::*;
+pub use sequence::*;
+pub use stats::*;
 pub use subscribe::*;
 pub use subscribed::*;
 pub use subscriber::*;
 pub use track_status_requested::*;
 
+use pool::*;
 use reader::*;
+use redirect::*;
 use writer::*;
 
 use futures::{stream::FuturesUnordered, StreamExt};
 
-use crate::message::Message;
-use crate::watch::Queue;
-use crate::{message, setup};
+use std::{sync::Arc, time};
 
-#[must_use = "run() must be called"]
-pub struct Session {
-	webtransport: web_transport::Session,
+use crate::coding::{Encode, EncodeError, Params};
+use crate::error::ErrorCode;
+use crate::message::{Codec, Codecs, Message, SubscribeStartCodec, SUBSCRIBE_START_PARAM};
+use crate::serve::ServeError;
+use crate::watch::Queue;
+use crate::{message, setup, transport};
 
+// The control stream, using the codec for the negotiated version.
+struct Control {
 	sender: Writer,
 	recver: Reader,
+	version: setup::Version,
+	codec: Arc<dyn Codec>,
+}
+
+// Encodes a control message with the negotiated codec.
+struct Versioned<'a> {
+	codec: &'a dyn Codec,
+	msg: &'a Message,
+}
+
+impl Encode for Versioned<'_> {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.codec.encode(self.msg, w)
+	}
+}
+
+#[must_use = "run() must be called"]
+pub struct Session {
+	transport: transport::Session,
+	control: Control,
 
 	publisher: Option<Publisher>,
 	subscriber: Option<Subscriber>,
 
+	// The role after negotiating with the peer.
+	role: setup::Role,
+
+	// The extensions sent by the peer.
+	extensions: setup::Extensions,
+
+	// The client's identity, if we authenticated it as the server, and the authorizer for the peer's requests.
+	access: Access,
+
 	outgoing: Queue<Message>,
+
+	// Answers PING, and sends them if configured and the peer supports it.
+	keepalive: Keepalive,
+	keepalive_config: Option<(time::Duration, time::Duration)>,
+	keepalive_supported: bool,
+
+	// Watches for the transport migrating to a new network path.
+	path: SessionPath,
+
+	// Set after sending or receiving GOAWAY.
+	drain: Drain,
+
+	// Set when the server's SETUP has yet to be received, see [SessionConfig::with_early].
+	early: bool,
 }
 
 impl Session {
 	fn new(
-		webtransport: web_transport::Session,
-		sender: Writer,
-		recver: Reader,
+		transport: transport::Session,
+		control: Control,
 		role: setup::Role,
+		extensions: setup::Extensions,
+		access: Access,
+		keepalive_supported: bool,
+		resume: Option<Subscriber>,
 	) -> (Self, Option<Publisher>, Option<Subscriber>) {
 		let outgoing = Queue::default().split();
+		let keepalive = Keepalive::new(outgoing.0.clone());
+		let drain = Drain::new();
 		let publisher = role
 			.is_publisher()
-			.then(|| Publisher::new(outgoing.0.clone(), webtransport.clone()));
-		let subscriber = role.is_subscriber().then(|| Subscriber::new(outgoing.0));
+			.then(|| Publisher::new(outgoing.0.clone(), transport.clone(), drain.clone(), access.clone()));
+		let subscriber = role.is_subscriber().then(|| match resume {
+			Some(mut subscriber) => {
+				subscriber.resume(outgoing.0, transport.clone(), drain.clone(), access.clone());
+				subscriber
+			}
+			None => Subscriber::new(outgoing.0, Some(transport.clone()), drain.clone(), access.clone()),
+		});
 
 		let session = Self {
-			webtransport,
-			sender,
-			recver,
+			path: SessionPath::new(transport.clone()),
+			transport,
+			control,
 			publisher: publisher.clone(),
 			subscriber: subscriber.clone(),
+			role,
+			extensions,
+			access,
 			outgoing: outgoing.1,
+			keepalive,
+			keepalive_config: None,
+			keepalive_supported,
+			drain,
+			early: false,
 		};
 
 		(session, publisher, subscriber)
 	}
 
-	pub async fn connect(session: web_transport::Session) -> Result<(Session, Publisher, Subscriber), SessionError> {
+	pub async fn connect(
+		session: impl Into<transport::Session>,
+	) -> Result<(Session, Publisher, Subscriber), SessionError> {
 		Self::connect_role(session, setup::Role::Both)
 			.await
 			.map(|(session, publisher, subscriber)| (session, publisher.unwrap(), subscriber.unwrap()))
 	}
 
 	pub async fn connect_role(
-		mut session: web_transport::Session,
+		session: impl Into<transport::Session>,
+		role: setup::Role,
+	) -> Result<(Session, Option<Publisher>, Option<Subscriber>), SessionError> {
+		Self::connect_with(session, SessionConfig::new(role)).await
+	}
+
+	/// Connect offering each version in the config, in preferred order, and use the one chosen by the server.
+	pub async fn connect_with(
+		session: impl Into<transport::Session>,
+		config: SessionConfig,
+	) -> Result<(Session, Option<Publisher>, Option<Subscriber>), SessionError> {
+		Self::connect_inner(session.into(), config, None).await
+	}
+
+	// Connect with the given role, reusing an existing subscriber from a previous session.
+	pub(super) async fn connect_resume(
+		session: transport::Session,
 		role: setup::Role,
+		subscriber: Subscriber,
+	) -> Result<(Session, Option<Publisher>), SessionError> {
+		let (session, publisher, _) = Self::connect_inner(session, SessionConfig::new(role), Some(subscriber)).await?;
+		Ok((session, publisher))
+	}
+
+	async fn connect_inner(
+		session: transport::Session,
+		config: SessionConfig,
+		resume: Option<Subscriber>,
 	) -> Result<(Session, Option<Publisher>, Option<Subscriber>), SessionError> {
 		let control = session.open_bi().await?;
 		let mut sender = Writer::new(control.0);
 		let mut recver = Reader::new(control.1);
 
-		let versions: setup::Versions = [setup::Version::DRAFT_04].into();
+		let SessionConfig {
+			role,
+			codecs,
+			extensions,
+			authorizer,
+			limits,
+			observer,
+			early,
+			..
+		} = config;
+
+		let mut versions = codecs.versions();
+		if early {
+			// Requests are encoded before the server picks a version, so only offer the one we'll use.
+			let preferred = *versions.first().ok_or(SessionError::Internal)?;
+			versions = [preferred].into();
+		}
+
+		let mut params = Params::from(extensions);
+		params.set(KEEPALIVE_PARAM, 1u64)?;
+
+		// The codec is chosen before the server's SETUP when sending early requests, so don't offer anything it changes.
+		if !early {
+			params.set(SUBSCRIBE_START_PARAM, 1u64)?;
+		}
 
 		let client = setup::Client {
 			role,
 			versions: versions.clone(),
-			params: Default::default(),
+			params,
 		};
 
 		log::debug!("sending client SETUP: {:?}", client);
 		sender.encode(&client).await?;
 
+		if early {
+			let version = versions[0];
+			let control = Control {
+				sender,
+				recver,
+				version,
+				codec: Self::codec(&codecs, version, false)?,
+			};
+
+			// The server's SETUP is received when running, so assume the requested role until then.
+			let access = Access::new(None, authorizer, limits, observer);
+			let (mut session, publisher, subscriber) =
+				Session::new(session, control, role, Default::default(), access, false, resume);
+			session.early = true;
+
+			return Ok((session, publisher, subscriber));
+		}
+
 		let server: setup::Server = recver.decode().await?;
 		log::debug!("received server SETUP: {:?}", server);
 
+		// The server must choose one of the versions we offered.
+		if codecs.get(server.version).is_none() {
+			return Err(SessionError::Version(versions, [server.version].into()));
+		}
+
+		let start = server.params.has(SUBSCRIBE_START_PARAM);
+		let codec = Self::codec(&codecs, server.version, start)?;
+
 		// Downgrade our role based on the server's role.
-		let role = match server.role {
-			setup::Role::Both => role,
-			setup::Role::Publisher => match role {
-				// Both sides are publishers only
-				setup::Role::Publisher => return Err(SessionError::RoleIncompatible(server.role, role)),
-				_ => setup::Role::Subscriber,
-			},
-			setup::Role::Subscriber => match role {
-				// Both sides are subscribers only
-				setup::Role::Subscriber => return Err(SessionError::RoleIncompatible(server.role, role)),
-				_ => setup::Role::Publisher,
-			},
+		let role = Self::downgrade(role, server.role)?;
+
+		let keepalive = server.params.has(KEEPALIVE_PARAM);
+		let control = Control {
+			sender,
+			recver,
+			version: server.version,
+			codec,
 		};
 
-		Ok(Session::new(session, sender, recver, role))
+		Ok(Session::new(
+			session,
+			control,
+			role,
+			server.params.into(),
+			Access::new(None, authorizer, limits, observer),
+			keepalive,
+			resume,
+		))
 	}
 
 	pub async fn accept(
-		session: web_transport::Session,
+		session: impl Into<transport::Session>,
 	) -> Result<(Session, Option<Publisher>, Option<Subscriber>), SessionError> {
 		Self::accept_role(session, setup::Role::Both).await
 	}
 
 	pub async fn accept_role(
-		mut session: web_transport::Session,
+		session: impl Into<transport::Session>,
 		role: setup::Role,
 	) -> Result<(Session, Option<Publisher>, Option<Subscriber>), SessionError> {
+		Self::accept_with(session, SessionConfig::new(role)).await
+	}
+
+	/// Accept a session using the first version offered by the client that's in the config.
+	pub async fn accept_with(
+		session: impl Into<transport::Session>,
+		config: SessionConfig,
+	) -> Result<(Session, Option<Publisher>, Option<Subscriber>), SessionError> {
+		let SessionConfig {
+			role,
+			codecs,
+			extensions,
+			auth,
+			authorizer,
+			limits,
+			observer,
+			..
+		} = config;
+
+		let session = session.into();
 		let control = session.accept_bi().await?;
 		let mut sender = Writer::new(control.0);
 		let mut recver = Reader::new(control.1);
@@ -128,53 +331,251 @@ impl Session {
 		let client: setup::Client = recver.decode().await?;
 		log::debug!("received client SETUP: {:?}", client);
 
-		if !client.versions.contains(&setup::Version::DRAFT_04) {
-			return Err(SessionError::Version(
-				client.versions,
-				[setup::Version::DRAFT_04].into(),
-			));
-		}
+		// Reject the client before the session starts, telling it why.
+		let identity = match auth {
+			Some(auth) => match Self::authenticate(auth.as_ref(), &client) {
+				Ok(identity) => Some(identity),
+				Err(err) => {
+					session.close(err.code() as u32, &err.to_string());
+					return Err(err);
+				}
+			},
+			None => None,
+		};
+
+		let version = codecs
+			.negotiate(&client.versions)
+			.ok_or_else(|| SessionError::Version(client.versions.clone(), codecs.versions()))?;
 
 		// Downgrade our role based on the client's role.
-		let role = match client.role {
+		let role = Self::downgrade(role, client.role)?;
+
+		// Only advertise PING support to clients that understand it.
+		let keepalive = client.params.has(KEEPALIVE_PARAM);
+
+		let mut params = Params::from(extensions);
+		if keepalive {
+			params.set(KEEPALIVE_PARAM, 1u64)?;
+		}
+
+		let start = client.params.has(SUBSCRIBE_START_PARAM);
+		if start {
+			params.set(SUBSCRIBE_START_PARAM, 1u64)?;
+		}
+
+		let server = setup::Server { role, version, params };
+
+		log::debug!("sending server SETUP: {:?}", server);
+		sender.encode(&server).await?;
+
+		let control = Control {
+			sender,
+			recver,
+			version,
+			codec: Self::codec(&codecs, version, start)?,
+		};
+
+		Ok(Session::new(
+			session,
+			control,
+			role,
+			client.params.into(),
+			Access::new(identity, authorizer, limits, observer),
+			keepalive,
+			None,
+		))
+	}
+
+	// The codec for the negotiated version, appending the subscription start if both peers support it.
+	fn codec(codecs: &Codecs, version: setup::Version, start: bool) -> Result<Arc<dyn Codec>, SessionError> {
+		let codec = codecs.get(version).ok_or(SessionError::Internal)?;
+
+		Ok(match start {
+			true => Arc::new(SubscribeStartCodec(codec)),
+			false => codec,
+		})
+	}
+
+	// Narrow our role based on the peer's role, failing if both only publish or only subscribe.
+	fn downgrade(role: setup::Role, peer: setup::Role) -> Result<setup::Role, SessionError> {
+		Ok(match peer {
 			setup::Role::Both => role,
 			setup::Role::Publisher => match role {
 				// Both sides are publishers only
-				setup::Role::Publisher => return Err(SessionError::RoleIncompatible(client.role, role)),
+				setup::Role::Publisher => return Err(SessionError::RoleIncompatible(peer, role)),
 				_ => setup::Role::Subscriber,
 			},
 			setup::Role::Subscriber => match role {
 				// Both sides are subscribers only
-				setup::Role::Subscriber => return Err(SessionError::RoleIncompatible(client.role, role)),
+				setup::Role::Subscriber => return Err(SessionError::RoleIncompatible(peer, role)),
 				_ => setup::Role::Publisher,
 			},
+		})
+	}
+
+	fn authenticate(auth: &dyn Authenticator, client: &setup::Client) -> Result<Identity, SessionError> {
+		let extensions = setup::Extensions::from(client.params.clone());
+		let token = extensions.get::<setup::AuthToken>()?;
+		auth.authenticate(token.as_ref().map(|token| token.0.as_str()))
+	}
+
+	/// Returns the version negotiated during setup.
+	pub fn version(&self) -> setup::Version {
+		self.control.version
+	}
+
+	/// Returns the extensions sent by the peer during setup.
+	pub fn extensions(&self) -> &setup::Extensions {
+		&self.extensions
+	}
+
+	/// Returns the client's identity, if the session was accepted with an [Authenticator].
+	pub fn identity(&self) -> Option<&Identity> {
+		self.access.identity()
+	}
+
+	/// Returns our role negotiated during setup, which may be narrower than the role requested.
+	///
+	/// For example, requesting [setup::Role::Both] results in [setup::Role::Subscriber] if the peer only publishes.
+	pub fn role(&self) -> setup::Role {
+		self.role
+	}
+
+	/// Send a PING every interval, closing the session if the PONG doesn't arrive before the timeout.
+	///
+	/// This detects a dead peer sooner than the QUIC idle timeout, and measures the round trip; see [Self::rtt].
+	/// It's ignored if the peer doesn't support PING, which is negotiated during setup.
+	pub fn set_keepalive(&mut self, interval: time::Duration, timeout: time::Duration) {
+		self.keepalive_config = Some((interval, timeout));
+	}
+
+	/// Returns a handle reporting the round trip time measured by the keepalive, which remains valid while running.
+	pub fn rtt(&self) -> SessionRtt {
+		self.keepalive.rtt()
+	}
+
+	/// Returns a handle reporting the session's statistics, which remains valid while running.
+	pub fn stats(&self) -> SessionStatsReader {
+		SessionStatsReader::new(
+			self.access.observer().traffic().clone(),
+			self.keepalive.rtt(),
+			self.path.clone(),
+			self.subscriber.clone(),
+		)
+	}
+
+	/// Returns a handle reporting the network path, and when the session migrates to a new one.
+	///
+	/// After a migration, the round trip is measured again immediately if the keepalive is enabled.
+	pub fn path(&self) -> SessionPath {
+		self.path.clone()
+	}
+
+	/// Returns a handle used to gracefully close the session while it's running; see [SessionCloser::close_gracefully].
+	pub fn closer(&self) -> SessionCloser {
+		SessionCloser::new(
+			self.transport.clone(),
+			self.keepalive.outgoing(),
+			self.drain.clone(),
+			self.publisher.clone(),
+		)
+	}
+
+	pub async fn run(self) -> Result<(), SessionError> {
+		let _active = crate::metrics::Active::session();
+
+		// Learns if the server supports PING from its SETUP, when it wasn't received before running.
+		let (setup_tx, setup_rx) = tokio::sync::oneshot::channel();
+
+		let keepalive = async {
+			let supported = match self.early {
+				true => setup_rx.await.unwrap_or(false),
+				false => self.keepalive_supported,
+			};
+
+			match self.keepalive_config {
+				Some((interval, timeout)) if supported => self.keepalive.clone().run(interval, timeout).await,
+				_ => std::future::pending().await,
+			}
 		};
 
-		let server = setup::Server {
-			role,
-			version: setup::Version::DRAFT_04,
-			params: Default::default(),
+		let path = self.path.clone().run(self.keepalive.clone());
+
+		let observer = self.access.observer().clone();
+		let mut recver = self.control.recver.with_traffic(observer.traffic().clone());
+		let sender = self.control.sender.with_traffic(observer.traffic().clone());
+
+		// Requests are sent while waiting for the server's SETUP, which must agree with what we assumed.
+		let early = self.early.then_some((self.control.version, self.role));
+		let recv = {
+			let (codec, publisher, subscriber) = (
+				self.control.codec.clone(),
+				self.publisher.clone(),
+				self.subscriber.clone(),
+			);
+			let (keepalive, drain, observer) = (self.keepalive.clone(), self.drain.clone(), observer.clone());
+
+			async move {
+				if let Some((version, role)) = early {
+					let server = Self::recv_setup(&mut recver, version, role).await?;
+					setup_tx.send(server.params.has(KEEPALIVE_PARAM)).ok();
+				}
+
+				Self::run_recv(recver, codec, publisher, subscriber, keepalive, drain, observer).await
+			}
 		};
 
-		log::debug!("sending server SETUP: {:?}", server);
-		sender.encode(&server).await?;
+		let res = tokio::select! {
+			res = keepalive => res,
+			res = path => res,
+			res = Self::run_fetches(self.transport.clone(), self.publisher) => res,
+			res = recv => res,
+			res = Self::run_send(sender, self.control.codec, self.outgoing, observer.clone()) => res,
+			res = Self::run_streams(self.transport.clone(), self.subscriber.clone(), self.access.limits().clone()) => res,
+			res = Self::run_datagrams(self.transport, self.subscriber, self.access.limits().clone(), observer.traffic().clone()) => res,
+		};
 
-		Ok(Session::new(session, sender, recver, role))
+		// We closed the session after GOAWAY, so any error is from tearing it down.
+		match res {
+			Err(_) if self.drain.is_closed() => Ok(()),
+			res => res,
+		}
 	}
 
-	pub async fn run(self) -> Result<(), SessionError> {
-		tokio::select! {
-			res = Self::run_recv(self.recver, self.publisher, self.subscriber.clone()) => res,
-			res = Self::run_send(self.sender, self.outgoing) => res,
-			res = Self::run_streams(self.webtransport.clone(), self.subscriber.clone()) => res,
-			res = Self::run_datagrams(self.webtransport, self.subscriber) => res,
+	// Receive the server's SETUP after sending early requests, see [SessionConfig::with_early].
+	async fn recv_setup(
+		recver: &mut Reader,
+		version: setup::Version,
+		role: setup::Role,
+	) -> Result<setup::Server, SessionError> {
+		let server: setup::Server = recver.decode().await?;
+		log::debug!("received server SETUP: {:?}", server);
+
+		if server.version != version {
+			return Err(SessionError::Version([version].into(), [server.version].into()));
 		}
+
+		Self::downgrade(role, server.role)?;
+
+		Ok(server)
 	}
 
-	async fn run_send(mut sender: Writer, mut outgoing: Queue<message::Message>) -> Result<(), SessionError> {
+	async fn run_send(
+		mut sender: Writer,
+		codec: Arc<dyn Codec>,
+		mut outgoing: Queue<message::Message>,
+		observer: Observer,
+	) -> Result<(), SessionError> {
 		while let Some(msg) = outgoing.pop().await {
 			log::debug!("sending message: {:?}", msg);
-			sender.encode(&msg).await?;
+
+			let versioned = Versioned {
+				codec: codec.as_ref(),
+				msg: &msg,
+			};
+			sender.encode(&versioned).await?;
+
+			observer.emit(SessionEvent::MessageSent(&msg));
 		}
 
 		Ok(())
@@ -182,13 +583,19 @@ impl Session {
 
 	async fn run_recv(
 		mut recver: Reader,
+		codec: Arc<dyn Codec>,
 		mut publisher: Option<Publisher>,
 		mut subscriber: Option<Subscriber>,
+		mut keepalive: Keepalive,
+		drain: Drain,
+		observer: Observer,
 	) -> Result<(), SessionError> {
 		loop {
-			let msg: message::Message = recver.decode().await?;
+			let msg = recver.decode_with(|r| codec.decode(r)).await?;
 			log::debug!("received message: {:?}", msg);
 
+			observer.emit(SessionEvent::MessageReceived(&msg));
+
 			let msg = match TryInto::<message::Publisher>::try_into(msg) {
 				Ok(msg) => {
 					subscriber
@@ -211,23 +618,49 @@ impl Session {
 				Err(msg) => msg,
 			};
 
-			// TODO GOAWAY
-			unimplemented!("unknown message context: {:?}", msg)
+			let msg = match msg {
+				Message::Ping(msg) => {
+					keepalive.recv_ping(msg);
+					continue;
+				}
+				Message::Pong(msg) => {
+					keepalive.recv_pong(msg);
+					continue;
+				}
+				Message::GoAway(msg) => {
+					// TODO expose the URL so the application can migrate elsewhere.
+					log::info!("peer is going away: {:?}", msg);
+					drain.recv_go_away();
+					continue;
+				}
+				msg => msg,
+			};
+
+			// Skip messages we can decode but don't handle yet, rather than closing the session.
+			// NOTE: Unknown message types are still an error, since control messages aren't length prefixed.
+			log::warn!("ignoring unsupported message: {:?}", msg);
 		}
 	}
 
 	async fn run_streams(
-		mut webtransport: web_transport::Session,
+		transport: transport::Session,
 		subscriber: Option<Subscriber>,
+		limits: Limits,
 	) -> Result<(), SessionError> {
 		let mut tasks = FuturesUnordered::new();
 
 		loop {
 			tokio::select! {
-				res = webtransport.accept_uni() => {
+				res = transport.accept_uni() => {
 					let stream = res?;
 					let subscriber = subscriber.clone().ok_or(SessionError::RoleViolation)?;
 
+					if limits.config().max_streams.is_some_and(|max| tasks.len() >= max) {
+						log::warn!("too many streams, stopping: max={:?}", limits.config().max_streams);
+						Reader::new(stream).stop(&ServeError::Quota);
+						continue;
+					}
+
 					tasks.push(async move {
 						if let Err(err) = Subscriber::recv_stream(subscriber, stream).await {
 							log::warn!("failed to serve stream: {}", err);
@@ -239,12 +672,45 @@ impl Session {
 		}
 	}
 
+	// Fetches use a bidirectional stream, so they're routed to the publisher unlike other data streams.
+	async fn run_fetches(transport: transport::Session, publisher: Option<Publisher>) -> Result<(), SessionError> {
+		let mut tasks = FuturesUnordered::new();
+
+		loop {
+			tokio::select! {
+				res = transport.accept_bi() => {
+					let (send, recv) = res?;
+
+					// Refuse the stream rather than the session, since we don't publish.
+					let Some(publisher) = publisher.clone() else {
+						log::warn!("refusing fetch without a publisher");
+						Writer::new(send).reset(&SessionError::RoleViolation);
+						Reader::new(recv).stop(&SessionError::RoleViolation);
+						continue;
+					};
+
+					tasks.push(async move {
+						if let Err(err) = Publisher::recv_fetch(publisher, send, recv).await {
+							log::warn!("failed to serve fetch: {}", err);
+						};
+					});
+				},
+				_ = tasks.next(), if !tasks.is_empty() => {},
+			};
+		}
+	}
+
 	async fn run_datagrams(
-		mut webtransport: web_transport::Session,
+		transport: transport::Session,
 		mut subscriber: Option<Subscriber>,
+		limits: Limits,
+		traffic: Traffic,
 	) -> Result<(), SessionError> {
 		loop {
-			let datagram = webtransport.recv_datagram().await?;
+			let datagram = transport.recv_datagram().await?;
+			traffic.received(datagram.len());
+			limits.throttle(datagram.len()).await;
+
 			subscriber
 				.as_mut()
 				.ok_or(SessionError::RoleViolation)?
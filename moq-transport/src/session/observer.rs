@@ -0,0 +1,127 @@
+use std::{sync::Arc, time};
+
+use crate::{data, message::Message};
+
+use super::Traffic;
+
+/// Whether a data stream was opened by us or the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamDirection {
+	Send,
+	Recv,
+}
+
+/// Something that happened during a session, reported to a [SessionObserver].
+#[derive(Debug, Clone, Copy)]
+pub enum SessionEvent<'a> {
+	/// A control message was encoded and sent.
+	MessageSent(&'a Message),
+
+	/// A control message was received and decoded.
+	MessageReceived(&'a Message),
+
+	/// A data stream was opened, once its header was sent or received.
+	StreamOpened {
+		direction: StreamDirection,
+		header: &'a data::Header,
+	},
+
+	/// A data stream was finished, reset or abandoned.
+	StreamClosed {
+		direction: StreamDirection,
+		subscribe_id: u64,
+		group_id: Option<u64>,
+	},
+
+	/// A group was reset or never sent, so the subscriber won't receive it in full.
+	GroupDropped { subscribe_id: u64, group_id: u64 },
+}
+
+/// Receives each [SessionEvent], for debugging the protocol; see [super::QlogWriter].
+///
+/// Events are reported inline, so the observer must be quick.
+pub trait SessionObserver: Send + Sync {
+	/// The time is relative to the end of the SETUP handshake.
+	fn event(&self, time: time::Duration, event: &SessionEvent);
+}
+
+impl<F> SessionObserver for F
+where
+	F: Fn(time::Duration, &SessionEvent) + Send + Sync,
+{
+	fn event(&self, time: time::Duration, event: &SessionEvent) {
+		self(time, event)
+	}
+}
+
+// Reports events to the observer, if any, with the time since the session started.
+// It also counts the traffic for the session's statistics, since it sees every stream.
+#[derive(Clone)]
+pub(super) struct Observer {
+	observer: Option<Arc<dyn SessionObserver>>,
+	start: time::Instant,
+	traffic: Traffic,
+}
+
+impl Observer {
+	pub fn new(observer: Option<Arc<dyn SessionObserver>>) -> Self {
+		Self {
+			observer,
+			start: time::Instant::now(),
+			traffic: Traffic::default(),
+		}
+	}
+
+	pub fn traffic(&self) -> &Traffic {
+		&self.traffic
+	}
+
+	pub fn emit(&self, event: SessionEvent) {
+		if let Some(observer) = &self.observer {
+			observer.event(self.start.elapsed(), &event);
+		}
+	}
+
+	/// Report the stream as opened, and as closed once the returned guard is dropped.
+	pub fn stream(&self, direction: StreamDirection, header: &data::Header) -> ObservedStream {
+		self.emit(SessionEvent::StreamOpened { direction, header });
+		self.traffic.stream_opened(direction);
+
+		let group_id = match header {
+			data::Header::Group(group) => Some(group.group_id),
+			data::Header::Object(object) => Some(object.group_id),
+			_ => None,
+		};
+
+		ObservedStream {
+			observer: self.clone(),
+			direction,
+			subscribe_id: header.subscribe_id(),
+			group_id,
+		}
+	}
+}
+
+impl Default for Observer {
+	fn default() -> Self {
+		Self::new(None)
+	}
+}
+
+pub(super) struct ObservedStream {
+	observer: Observer,
+	direction: StreamDirection,
+	subscribe_id: u64,
+	group_id: Option<u64>,
+}
+
+impl Drop for ObservedStream {
+	fn drop(&mut self) {
+		self.observer.traffic.stream_closed();
+		self.observer.emit(SessionEvent::StreamClosed {
+			direction: self.direction,
+			subscribe_id: self.subscribe_id,
+			group_id: self.group_id,
+		});
+	}
+}
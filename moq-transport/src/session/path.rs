@@ -0,0 +1,72 @@
+use std::{net, sync::Arc, time};
+
+use crate::transport;
+
+use super::{Keepalive, SessionError};
+
+// How often to check if the transport migrated, since it doesn't report it.
+const PATH_INTERVAL: time::Duration = time::Duration::from_millis(250);
+
+/// The network path of a session, which remains valid while running; see [super::Session::path].
+///
+/// A QUIC connection survives the client changing networks, such as from Wi-Fi to cellular.
+/// Use [Self::changed] to react to the new path, for example to adapt the bitrate once the new [transport::Path::bandwidth] is known.
+#[derive(Clone)]
+pub struct SessionPath {
+	transport: transport::Session,
+
+	// The peer's address, updated when the session migrates.
+	remote: Arc<tokio::sync::watch::Sender<Option<net::SocketAddr>>>,
+}
+
+impl SessionPath {
+	pub(super) fn new(transport: transport::Session) -> Self {
+		let remote = transport.path().map(|path| path.remote);
+
+		Self {
+			transport,
+			remote: Arc::new(tokio::sync::watch::channel(remote).0),
+		}
+	}
+
+	/// Returns the current path, or None if the transport doesn't expose it.
+	pub fn get(&self) -> Option<transport::Path> {
+		self.transport.path()
+	}
+
+	/// Block until the session migrates to a new path, returning it, or None if the session is closed first.
+	pub async fn changed(&self) -> Option<transport::Path> {
+		let mut remote = self.remote.subscribe();
+
+		tokio::select! {
+			res = remote.changed() => res.ok()?,
+			_ = self.transport.closed() => return None,
+		};
+
+		self.get()
+	}
+
+	// Poll the transport for a migration, measuring the round trip again once it does.
+	pub(super) async fn run(self, keepalive: Keepalive) -> Result<(), SessionError> {
+		let Some(mut remote) = *self.remote.borrow() else {
+			// The transport doesn't expose the path, so there's nothing to watch.
+			return std::future::pending().await;
+		};
+
+		loop {
+			tokio::time::sleep(PATH_INTERVAL).await;
+
+			let Some(path) = self.get() else {
+				continue;
+			};
+
+			if path.remote != remote {
+				log::info!("session migrated: from={} to={}", remote, path.remote);
+				remote = path.remote;
+
+				keepalive.probe();
+				self.remote.send_replace(Some(remote));
+			}
+		}
+	}
+}
@@ -0,0 +1,96 @@
+use std::{
+	ops,
+	sync::{Arc, Mutex},
+};
+
+use bytes::BytesMut;
+
+// Reuses the buffers used to encode stream headers, so each stream doesn't allocate its own.
+#[derive(Clone, Default)]
+pub(super) struct BufferPool {
+	buffers: Arc<Mutex<Vec<BytesMut>>>,
+}
+
+impl BufferPool {
+	// Bound the memory held by idle buffers.
+	const MAX_BUFFERS: usize = 256;
+	const MAX_CAPACITY: usize = 64 * 1024;
+
+	pub fn get(&self) -> PooledBuffer {
+		let buffer = self.buffers.lock().unwrap().pop().unwrap_or_default();
+
+		PooledBuffer {
+			buffer,
+			pool: Some(self.clone()),
+		}
+	}
+
+	fn put(&self, mut buffer: BytesMut) {
+		// Don't hold on to unusually large buffers.
+		if buffer.capacity() > Self::MAX_CAPACITY {
+			return;
+		}
+
+		buffer.clear();
+
+		let mut buffers = self.buffers.lock().unwrap();
+		if buffers.len() < Self::MAX_BUFFERS {
+			buffers.push(buffer);
+		}
+	}
+}
+
+// A buffer that's returned to its pool when dropped.
+#[derive(Default)]
+pub(super) struct PooledBuffer {
+	buffer: BytesMut,
+	pool: Option<BufferPool>,
+}
+
+impl ops::Deref for PooledBuffer {
+	type Target = BytesMut;
+
+	fn deref(&self) -> &Self::Target {
+		&self.buffer
+	}
+}
+
+impl ops::DerefMut for PooledBuffer {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.buffer
+	}
+}
+
+impl Drop for PooledBuffer {
+	fn drop(&mut self) {
+		if let Some(pool) = self.pool.take() {
+			pool.put(std::mem::take(&mut self.buffer));
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn reuse() {
+		let pool = BufferPool::default();
+
+		let mut buffer = pool.get();
+		buffer.extend_from_slice(b"hello");
+		let ptr = buffer.as_ptr();
+		drop(buffer);
+
+		// The allocation is reused, but the contents are not.
+		let buffer = pool.get();
+		assert!(buffer.is_empty());
+		assert_eq!(buffer.as_ptr(), ptr);
+
+		// Large buffers are freed instead.
+		let mut large = pool.get();
+		large.reserve(BufferPool::MAX_CAPACITY + 1);
+		drop(large);
+		assert!(pool.buffers.lock().unwrap().is_empty());
+	}
+}
@@ -0,0 +1,76 @@
+use std::cmp;
+
+use crate::serve::GroupOrder;
+
+/// A group about to be sent on its own stream, passed to a [Prioritizer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupPriority {
+	/// The priority of the group, or the priority set by the subscriber; a lower value is more important.
+	pub priority: u64,
+
+	/// The order the track delivers groups in.
+	pub order: GroupOrder,
+
+	pub group_id: u64,
+}
+
+/// Maps each group to the priority of its stream, used by the transport to decide which stream to send first.
+///
+/// This only affects streams that are already open; see [super::StreamPolicy] for how streams are opened.
+pub trait Prioritizer: Send + Sync {
+	/// Returns the stream priority, where a larger value is sent first.
+	fn priority(&self, group: &GroupPriority) -> i32;
+}
+
+/// The default [Prioritizer], which prefers important groups and then the next group in the track's order.
+///
+/// A group with a lower priority value always wins, so an audio track can take precedence over video.
+/// Within the same priority, a live (descending) track sends the newest group first,
+/// while an ascending track sends the oldest group first so playback isn't stalled.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultPrioritizer;
+
+impl Prioritizer for DefaultPrioritizer {
+	fn priority(&self, group: &GroupPriority) -> i32 {
+		// The upper 16 bits come from the priority, inverted so a lower value is sent first.
+		let priority = cmp::min(group.priority, i16::MAX as u64) as i32;
+		let priority = (i16::MAX as i32 - priority) << 16;
+
+		// NOTE: The lower 16 bits come from the group sequence, which wraps every 65536 groups.
+		let sequence = (group.group_id & 0xffff) as i32;
+		let sequence = match group.order {
+			GroupOrder::Descending => sequence,
+			GroupOrder::Ascending => 0xffff - sequence,
+		};
+
+		priority | sequence
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn priority(priority: u64, order: GroupOrder, group_id: u64) -> i32 {
+		DefaultPrioritizer.priority(&GroupPriority {
+			priority,
+			order,
+			group_id,
+		})
+	}
+
+	#[test]
+	fn default() {
+		// Newer groups win for live tracks, older groups for ascending tracks.
+		assert!(priority(0, GroupOrder::Descending, 2) > priority(0, GroupOrder::Descending, 1));
+		assert!(priority(0, GroupOrder::Ascending, 1) > priority(0, GroupOrder::Ascending, 2));
+
+		// A more important track always wins, regardless of the group.
+		assert!(priority(0, GroupOrder::Descending, 0) > priority(1, GroupOrder::Descending, 1000));
+		assert!(priority(0, GroupOrder::Ascending, 1000) > priority(1, GroupOrder::Ascending, 0));
+
+		// Huge priorities are clamped rather than overflowing.
+		assert!(priority(u64::MAX, GroupOrder::Descending, 0) >= 0);
+		assert!(priority(u64::MAX, GroupOrder::Descending, 0) < priority(0, GroupOrder::Descending, 0));
+	}
+}
@@ -0,0 +1,116 @@
+use std::{
+	collections::VecDeque,
+	sync::{atomic, Arc, Mutex},
+};
+
+// The number of finished groups to remember, so a stalled or reset group can still be inspected.
+const HISTORY: usize = 16;
+
+/// The transmission status of a group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupStatus {
+	Sending,
+	Finished,
+	Reset,
+}
+
+/// A snapshot of a group being transmitted to the subscriber.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupProgress {
+	pub group_id: u64,
+
+	/// The number of payload bytes written to the stream.
+	pub sent: u64,
+
+	/// The total size of the objects produced so far, including bytes not yet sent.
+	pub size: u64,
+
+	pub status: GroupStatus,
+}
+
+// Updated by the group task without taking a lock.
+pub(super) struct GroupCounter {
+	group_id: u64,
+	sent: atomic::AtomicU64,
+	size: atomic::AtomicU64,
+	status: atomic::AtomicU8,
+}
+
+impl GroupCounter {
+	const SENDING: u8 = 0;
+	const FINISHED: u8 = 1;
+	const RESET: u8 = 2;
+
+	pub fn produced(&self, size: usize) {
+		self.size.fetch_add(size as u64, atomic::Ordering::Relaxed);
+	}
+
+	pub fn sent(&self, size: usize) {
+		self.sent.fetch_add(size as u64, atomic::Ordering::Relaxed);
+	}
+
+	pub fn finish(&self, reset: bool) {
+		let status = if reset { Self::RESET } else { Self::FINISHED };
+		self.status.store(status, atomic::Ordering::Relaxed);
+	}
+
+	fn snapshot(&self) -> GroupProgress {
+		let status = match self.status.load(atomic::Ordering::Relaxed) {
+			Self::SENDING => GroupStatus::Sending,
+			Self::FINISHED => GroupStatus::Finished,
+			_ => GroupStatus::Reset,
+		};
+
+		GroupProgress {
+			group_id: self.group_id,
+			sent: self.sent.load(atomic::Ordering::Relaxed),
+			size: self.size.load(atomic::Ordering::Relaxed),
+			status,
+		}
+	}
+}
+
+/// Reports the progress of each group sent for a subscription, for debugging stalls.
+///
+/// This can be cloned and remains valid after the subscription is served.
+#[derive(Clone, Default)]
+pub struct SubscribedProgress {
+	groups: Arc<Mutex<VecDeque<Arc<GroupCounter>>>>,
+}
+
+impl SubscribedProgress {
+	/// Returns the groups in flight and the most recently finished groups, in the order they were started.
+	pub fn groups(&self) -> Vec<GroupProgress> {
+		let groups = self.groups.lock().unwrap();
+		groups.iter().map(|group| group.snapshot()).collect()
+	}
+
+	pub(super) fn start(&self, group_id: u64) -> Arc<GroupCounter> {
+		let counter = Arc::new(GroupCounter {
+			group_id,
+			sent: Default::default(),
+			size: Default::default(),
+			status: atomic::AtomicU8::new(GroupCounter::SENDING),
+		});
+
+		let mut groups = self.groups.lock().unwrap();
+		groups.push_back(counter.clone());
+
+		// Forget the oldest finished groups, but never one that's still sending.
+		let mut finished = groups
+			.iter()
+			.filter(|group| group.status.load(atomic::Ordering::Relaxed) != GroupCounter::SENDING)
+			.count();
+
+		groups.retain(|group| {
+			if finished <= HISTORY || group.status.load(atomic::Ordering::Relaxed) == GroupCounter::SENDING {
+				return true;
+			}
+
+			finished -= 1;
+			false
+		});
+
+		counter
+	}
+}
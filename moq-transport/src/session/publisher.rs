@@ -1,49 +1,156 @@
 use std::{
-	collections::{hash_map, HashMap},
+	collections::{hash_map, HashMap, HashSet},
 	sync::{Arc, Mutex},
+	time,
 };
 
 use futures::{stream::FuturesUnordered, StreamExt};
 
 use crate::{
+	data,
+	error::ErrorCode,
 	message::{self, Message},
 	serve::{ServeError, TracksReader},
-	setup,
+	setup, transport,
 };
 
 use crate::watch::Queue;
 
-use super::{Announce, AnnounceRecv, Session, SessionError, Subscribed, SubscribedRecv, TrackStatusRequested};
+use super::{
+	Access, Announce, AnnounceRecv, AuthRequest, BufferPool, DefaultPrioritizer, Drain, Fetched, Observer, Prioritizer,
+	Reader, Serving, ServingGuard, Session, SessionError, StreamPermit, StreamPolicy, StreamScheduler, Subscribed,
+	SubscribedRecv, SubscribedStatus, TrackStatusRequested, Writer,
+};
+
+// The namespace prefixes requested by the peer with SUBSCRIBE_NAMESPACE.
+#[derive(Default)]
+struct AnnounceFilter {
+	// None until the peer sends SUBSCRIBE_NAMESPACE, so every namespace is announced.
+	prefixes: Option<Vec<String>>,
+
+	// The namespaces we sent ANNOUNCE for.
+	sent: HashSet<String>,
+}
+
+impl AnnounceFilter {
+	fn matches(&self, namespace: &str) -> bool {
+		match &self.prefixes {
+			Some(prefixes) => prefixes.iter().any(|prefix| namespace.starts_with(prefix.as_str())),
+			None => true,
+		}
+	}
+}
+
+// Subscriptions held until their namespace is announced, see [Publisher::set_pending].
+#[derive(Default)]
+struct Pending {
+	// None unless enabled, so subscriptions go straight to the unknown queue.
+	timeout: Option<time::Duration>,
+
+	subscribes: HashMap<u64, Subscribed>,
+}
+
+impl Pending {
+	// Remove every subscription for the namespace, in the order received.
+	fn take(&mut self, namespace: &str) -> Vec<Subscribed> {
+		let mut ids: Vec<u64> = self
+			.subscribes
+			.iter()
+			.filter(|(_, subscribed)| subscribed.namespace == namespace)
+			.map(|(id, _)| *id)
+			.collect();
+		ids.sort_unstable();
+
+		ids.into_iter().filter_map(|id| self.subscribes.remove(&id)).collect()
+	}
+}
 
 // TODO remove Clone.
 #[derive(Clone)]
 pub struct Publisher {
-	webtransport: web_transport::Session,
+	transport: transport::Session,
 
 	announces: Arc<Mutex<HashMap<String, AnnounceRecv>>>,
+	announce_filter: Arc<Mutex<AnnounceFilter>>,
 	subscribed: Arc<Mutex<HashMap<u64, SubscribedRecv>>>,
 	unknown: Queue<Subscribed>,
+	pending: Arc<Mutex<Pending>>,
+
+	// Shares the concurrent streams between subscriptions.
+	scheduler: StreamScheduler,
+
+	// Orders the group streams that are already open.
+	prioritizer: Arc<Mutex<Arc<dyn Prioritizer>>>,
+
+	// Set after sending or receiving GOAWAY.
+	drain: Drain,
+
+	// The subscriptions being served, which must end before the session has drained.
+	serving: Serving,
+
+	// Checks each SUBSCRIBE from the peer.
+	access: Access,
+
+	// Shares the encode buffers between data streams.
+	pool: BufferPool,
 
 	outgoing: Queue<Message>,
 }
 
 impl Publisher {
-	pub(crate) fn new(outgoing: Queue<Message>, webtransport: web_transport::Session) -> Self {
+	pub(super) fn new(outgoing: Queue<Message>, transport: transport::Session, drain: Drain, access: Access) -> Self {
 		Self {
-			webtransport,
+			transport,
 			announces: Default::default(),
+			announce_filter: Default::default(),
 			subscribed: Default::default(),
 			unknown: Default::default(),
+			pending: Default::default(),
+			scheduler: StreamScheduler::new(),
+			prioritizer: Arc::new(Mutex::new(Arc::new(DefaultPrioritizer))),
+			drain,
+			serving: Serving::new(),
+			access,
+			pool: Default::default(),
 			outgoing,
 		}
 	}
 
-	pub async fn accept(session: web_transport::Session) -> Result<(Session, Publisher), SessionError> {
+	/// Choose how streams are shared between subscriptions once [Self::set_max_streams] is reached.
+	pub fn set_stream_policy(&self, policy: StreamPolicy) {
+		self.scheduler.set_policy(policy);
+	}
+
+	/// Set the maximum number of data streams open at once, shared by every subscription.
+	///
+	/// Streams beyond the limit wait to be opened according to the [StreamPolicy].
+	/// Defaults to 100.
+	pub fn set_max_streams(&self, count: usize) {
+		self.scheduler.set_capacity(count);
+	}
+
+	/// Choose the priority of each group stream, deciding which is sent first under congestion.
+	///
+	/// Defaults to [DefaultPrioritizer], and applies to groups sent afterwards.
+	pub fn set_prioritizer<P: Prioritizer + 'static>(&self, prioritizer: P) {
+		*self.prioritizer.lock().unwrap() = Arc::new(prioritizer);
+	}
+
+	/// Hold a SUBSCRIBE for a namespace that hasn't been announced yet, serving it once [Self::announce] is called.
+	///
+	/// This lets a viewer join before the broadcast goes live.
+	/// Once the timeout expires, the subscription is returned by [Self::subscribed] as before.
+	/// Disabled by default.
+	pub fn set_pending(&self, timeout: time::Duration) {
+		self.pending.lock().unwrap().timeout = Some(timeout);
+	}
+
+	pub async fn accept(session: impl Into<transport::Session>) -> Result<(Session, Publisher), SessionError> {
 		let (session, publisher, _) = Session::accept_role(session, setup::Role::Publisher).await?;
 		Ok((session, publisher.unwrap()))
 	}
 
-	pub async fn connect(session: web_transport::Session) -> Result<(Session, Publisher), SessionError> {
+	pub async fn connect(session: impl Into<transport::Session>) -> Result<(Session, Publisher), SessionError> {
 		let (session, publisher, _) = Session::connect_role(session, setup::Role::Publisher).await?;
 		Ok((session, publisher.unwrap()))
 	}
@@ -54,7 +161,13 @@ impl Publisher {
 		let announce = match self.announces.lock().unwrap().entry(tracks.namespace.clone()) {
 			hash_map::Entry::Occupied(_) => return Err(ServeError::Duplicate.into()),
 			hash_map::Entry::Vacant(entry) => {
-				let (send, recv) = Announce::new(self.clone(), tracks.namespace.clone());
+				let (send, mut recv) = Announce::new(self.clone(), tracks.namespace.clone());
+
+				// Serve any subscriptions that arrived before the announce.
+				for subscribed in self.pending.lock().unwrap().take(&tracks.namespace) {
+					recv.recv_subscribe(subscribed)?;
+				}
+
 				entry.insert(recv);
 				send
 			}
@@ -62,8 +175,10 @@ impl Publisher {
 
 		let mut subscribe_tasks = FuturesUnordered::new();
 		let mut status_tasks = FuturesUnordered::new();
+		let mut fetch_tasks = FuturesUnordered::new();
 		let mut subscribe_done = false;
 		let mut status_done = false;
+		let mut fetch_done = false;
 
 		loop {
 			tokio::select! {
@@ -98,8 +213,26 @@ impl Publisher {
 						None => status_done = true,
 					}
 				},
+				res = announce.fetched(), if !fetch_done => {
+					match res? {
+						Some(fetched) => {
+							let tracks = tracks.clone();
+
+							fetch_tasks.push(async move {
+								let info = fetched.info.clone();
+								if let Err(err) = Self::serve_fetch(fetched, tracks).await {
+									log::warn!("failed serving fetch: {:?}, error: {}", info, err)
+								}
+							});
+						},
+						None => fetch_done = true,
+					}
+				},
+				// Withdrawing the broadcast unannounces it when we return.
+				res = tracks.closed() => return Ok(res?),
 				Some(res) = subscribe_tasks.next() => res,
 				Some(res) = status_tasks.next() => res,
+				Some(res) = fetch_tasks.next() => res,
 				else => return Ok(())
 			}
 		}
@@ -115,6 +248,13 @@ impl Publisher {
 		Ok(())
 	}
 
+	pub async fn serve_fetch(fetched: Fetched, mut tracks: TracksReader) -> Result<(), SessionError> {
+		match tracks.subscribe(&fetched.name) {
+			Some(track) => fetched.serve(track).await,
+			None => fetched.close(ServeError::NotFound).await,
+		}
+	}
+
 	pub async fn serve_track_status(
 		mut track_status_request: TrackStatusRequested,
 		mut tracks: TracksReader,
@@ -148,16 +288,41 @@ impl Publisher {
 		Ok(())
 	}
 
+	/// Returns a snapshot of every subscription currently being served, ordered by subscribe ID.
+	///
+	/// A subscription is removed once it's done, for diagnostics such as an admin endpoint.
+	pub fn active_subscriptions(&self) -> Vec<SubscribedStatus> {
+		let mut res: Vec<_> = self
+			.subscribed
+			.lock()
+			.unwrap()
+			.values()
+			.map(|subscribed| subscribed.status())
+			.collect();
+
+		res.sort_by_key(|status| status.id);
+		res
+	}
+
 	// Returns subscriptions that do not map to an active announce.
 	pub async fn subscribed(&mut self) -> Option<Subscribed> {
 		self.unknown.pop().await
 	}
 
+	/// Block until the session starts draining, after either side sent GOAWAY.
+	///
+	/// Existing subscriptions are still served, but the application should migrate to another session.
+	pub async fn draining(&self) {
+		self.drain.draining().await
+	}
+
 	pub(crate) fn recv_message(&mut self, msg: message::Subscriber) -> Result<(), SessionError> {
 		let res = match msg {
 			message::Subscriber::AnnounceOk(msg) => self.recv_announce_ok(msg),
 			message::Subscriber::AnnounceError(msg) => self.recv_announce_error(msg),
 			message::Subscriber::AnnounceCancel(msg) => self.recv_announce_cancel(msg),
+			message::Subscriber::SubscribeNamespace(msg) => self.recv_subscribe_namespace(msg),
+			message::Subscriber::UnsubscribeNamespace(msg) => self.recv_unsubscribe_namespace(msg),
 			message::Subscriber::Subscribe(msg) => self.recv_subscribe(msg),
 			message::Subscriber::Unsubscribe(msg) => self.recv_unsubscribe(msg),
 			message::Subscriber::SubscribeUpdate(msg) => self.recv_subscribe_update(msg),
@@ -180,14 +345,18 @@ impl Publisher {
 	}
 
 	fn recv_announce_error(&mut self, msg: message::AnnounceError) -> Result<(), SessionError> {
+		self.announce_filter.lock().unwrap().sent.remove(&msg.namespace);
+
 		if let Some(announce) = self.announces.lock().unwrap().remove(&msg.namespace) {
-			announce.recv_error(ServeError::Closed(msg.code))?;
+			announce.recv_error(ServeError::from_code(msg.code))?;
 		}
 
 		Ok(())
 	}
 
 	fn recv_announce_cancel(&mut self, msg: message::AnnounceCancel) -> Result<(), SessionError> {
+		self.announce_filter.lock().unwrap().sent.remove(&msg.namespace);
+
 		if let Some(announce) = self.announces.lock().unwrap().remove(&msg.namespace) {
 			announce.recv_error(ServeError::Cancel)?;
 		}
@@ -197,13 +366,27 @@ impl Publisher {
 
 	fn recv_subscribe(&mut self, msg: message::Subscribe) -> Result<(), SessionError> {
 		let namespace = msg.track_namespace.clone();
+		let id = msg.id;
 
 		let subscribe = {
 			let mut subscribes = self.subscribed.lock().unwrap();
 
 			// Insert the abort handle into the lookup table.
 			let entry = match subscribes.entry(msg.id) {
-				hash_map::Entry::Occupied(_) => return Err(SessionError::Duplicate),
+				hash_map::Entry::Occupied(_) => {
+					// Refuse the reused ID, leaving the existing subscription untouched.
+					// NOTE: This skips send_message, which would remove the existing subscription.
+					let err = ServeError::Duplicate;
+					let reply = message::Publisher::from(message::SubscribeError {
+						id: msg.id,
+						alias: msg.track_alias,
+						code: err.code(),
+						reason: "duplicate subscribe id".to_string(),
+					});
+					self.outgoing.push(reply.into()).ok();
+
+					return Err(SessionError::Duplicate);
+				}
 				hash_map::Entry::Vacant(entry) => entry,
 			};
 
@@ -213,12 +396,64 @@ impl Publisher {
 			send
 		};
 
-		// If we have an announce, route the subscribe to it.
-		if let Some(announce) = self.announces.lock().unwrap().get_mut(&namespace) {
-			return announce.recv_subscribe(subscribe).map_err(Into::into);
+		// We sent GOAWAY, so refuse any new subscriptions.
+		if self.drain.is_closing() {
+			return subscribe.close(ServeError::GoingAway).map_err(Into::into);
+		}
+
+		let request = AuthRequest::Subscribe {
+			namespace: &subscribe.info.namespace,
+			name: &subscribe.info.name,
+		};
+		if let Err(err) = self.access.check(request) {
+			return subscribe.close(err).map_err(Into::into);
+		}
+
+		// Refuse the subscription if it puts us over the limit.
+		let max = self.access.limits().config().max_subscriptions;
+		if max.is_some_and(|max| self.subscribed.lock().unwrap().len() > max) {
+			return subscribe.close(ServeError::Quota).map_err(Into::into);
 		}
 
-		// Otherwise, put it in the unknown queue.
+		{
+			// Hold the lock until the subscribe is pending, so a concurrent announce can't miss it.
+			let mut announces = self.announces.lock().unwrap();
+
+			// If we have an announce, route the subscribe to it.
+			if let Some(announce) = announces.get_mut(&namespace) {
+				return announce.recv_subscribe(subscribe).map_err(Into::into);
+			}
+
+			// Otherwise, wait for the announce if enabled.
+			let mut pending = self.pending.lock().unwrap();
+			if let Some(timeout) = pending.timeout {
+				pending.subscribes.insert(id, subscribe);
+
+				let mut this = self.clone();
+				tokio::spawn(async move {
+					tokio::time::sleep(timeout).await;
+					this.expire_pending(id);
+				});
+
+				return Ok(());
+			}
+		}
+
+		self.recv_unknown(subscribe)
+	}
+
+	// Give up waiting for the announce, treating the subscribe as unknown.
+	fn expire_pending(&mut self, id: u64) {
+		let subscribed = self.pending.lock().unwrap().subscribes.remove(&id);
+
+		if let Some(subscribed) = subscribed {
+			if let Err(err) = self.recv_unknown(subscribed) {
+				log::warn!("failed to expire pending subscribe: {}", err);
+			}
+		}
+	}
+
+	fn recv_unknown(&mut self, subscribe: Subscribed) -> Result<(), SessionError> {
 		// TODO Have some way to detect if the application is not reading from the unknown queue.
 		if let Err(err) = self.unknown.push(subscribe) {
 			// Default to closing with a not found error I guess.
@@ -228,9 +463,17 @@ impl Publisher {
 		Ok(())
 	}
 
-	fn recv_subscribe_update(&mut self, _msg: message::SubscribeUpdate) -> Result<(), SessionError> {
-		// TODO: Implement updating subscriptions.
-		Err(SessionError::Internal)
+	fn recv_subscribe_update(&mut self, msg: message::SubscribeUpdate) -> Result<(), SessionError> {
+		// TODO: Support updating the start/end range.
+		let id = msg.id;
+		if let Some(subscribed) = self.subscribed.lock().unwrap().get_mut(&id) {
+			// The subscription may finish before the update arrives, which isn't an error.
+			if let Err(err) = subscribed.recv_update(msg) {
+				log::debug!("ignoring late subscribe update: id={} error={}", id, err);
+			}
+		}
+
+		Ok(())
 	}
 
 	fn recv_track_status_request(&mut self, msg: message::TrackStatusRequest) -> Result<(), SessionError> {
@@ -259,6 +502,7 @@ impl Publisher {
 		match &msg {
 			message::Publisher::SubscribeDone(msg) => self.drop_subscribe(msg.id),
 			message::Publisher::SubscribeError(msg) => self.drop_subscribe(msg.id),
+			message::Publisher::Redirect(msg) => self.drop_subscribe(msg.id),
 			message::Publisher::Unannounce(msg) => self.drop_announce(msg.namespace.as_str()),
 			_ => (),
 		};
@@ -270,15 +514,150 @@ impl Publisher {
 		self.subscribed.lock().unwrap().remove(&id);
 	}
 
+	fn recv_subscribe_namespace(&mut self, msg: message::SubscribeNamespace) -> Result<(), SessionError> {
+		let announces = self.announces.lock().unwrap();
+		let mut filter = self.announce_filter.lock().unwrap();
+		filter
+			.prefixes
+			.get_or_insert_with(Vec::new)
+			.push(msg.namespace_prefix.clone());
+
+		// Announce any namespaces that were held back until now.
+		for namespace in announces.keys() {
+			if namespace.starts_with(msg.namespace_prefix.as_str()) && filter.sent.insert(namespace.clone()) {
+				self.outgoing
+					.push(
+						message::Announce {
+							namespace: namespace.clone(),
+							params: Default::default(),
+						}
+						.into(),
+					)
+					.ok();
+			}
+		}
+
+		Ok(())
+	}
+
+	fn recv_unsubscribe_namespace(&mut self, msg: message::UnsubscribeNamespace) -> Result<(), SessionError> {
+		// NOTE: Namespaces already announced stay announced.
+		let mut filter = self.announce_filter.lock().unwrap();
+		if let Some(prefixes) = filter.prefixes.as_mut() {
+			if let Some(index) = prefixes.iter().position(|prefix| *prefix == msg.namespace_prefix) {
+				prefixes.remove(index);
+			}
+		}
+
+		Ok(())
+	}
+
+	// Send ANNOUNCE, unless the peer filtered the namespace with SUBSCRIBE_NAMESPACE.
+	pub(super) fn send_announce(&mut self, namespace: String) {
+		let mut filter = self.announce_filter.lock().unwrap();
+		if !filter.matches(&namespace) {
+			return;
+		}
+
+		filter.sent.insert(namespace.clone());
+		drop(filter);
+
+		self.send_message(message::Announce {
+			namespace,
+			params: Default::default(),
+		});
+	}
+
+	// Send UNANNOUNCE, only if the namespace was announced to the peer.
+	pub(super) fn send_unannounce(&mut self, namespace: String) {
+		if self.announce_filter.lock().unwrap().sent.remove(&namespace) {
+			self.send_message(message::Unannounce { namespace });
+		} else {
+			self.drop_announce(&namespace);
+		}
+	}
+
 	fn drop_announce(&mut self, namespace: &str) {
 		self.announces.lock().unwrap().remove(namespace);
 	}
 
-	pub(super) async fn open_uni(&mut self) -> Result<web_transport::SendStream, SessionError> {
-		Ok(self.webtransport.open_uni().await?)
+	pub(super) async fn recv_fetch(
+		self,
+		send: transport::SendStream,
+		recv: transport::RecvStream,
+	) -> Result<(), SessionError> {
+		let mut reader = Reader::new(recv);
+		let header: data::FetchHeader = reader.decode().await?;
+
+		log::trace!("received fetch: {:?}", header);
+
+		let namespace = header.namespace.clone();
+		let fetched = Fetched::new(self.writer(send), header);
+
+		// If we have an announce, route the fetch to it.
+		let fetched = match self.announces.lock().unwrap().get_mut(&namespace) {
+			Some(announce) => return announce.recv_fetch(fetched).map_err(Into::into),
+			None => fetched,
+		};
+
+		fetched.close(ServeError::NotFound).await
+	}
+
+	// Open a data stream once the scheduler allows it; the permit must be held until the stream is done.
+	pub(super) async fn open_uni(
+		&mut self,
+		subscribe_id: u64,
+		priority: u64,
+	) -> Result<(transport::SendStream, StreamPermit), SessionError> {
+		let permit = self.scheduler.acquire(subscribe_id, priority).await?;
+		let stream = self.transport.open_uni().await?;
+
+		Ok((stream, permit))
+	}
+
+	pub(super) fn observer(&self) -> &Observer {
+		self.access.observer()
+	}
+
+	// Create a writer for a data stream, sharing the encode buffers and counting the bytes sent.
+	pub(super) fn writer(&self, stream: transport::SendStream) -> Writer {
+		Writer::new(stream)
+			.with_pool(&self.pool)
+			.with_traffic(self.observer().traffic().clone())
+	}
+
+	// Block until the session is closed, returning the reason.
+	pub(super) async fn closed(&self) -> SessionError {
+		self.transport.closed().await.into()
+	}
+
+	// Block until we send GOAWAY, after which subscriptions stop at the next group.
+	pub(super) async fn closing(&self) {
+		self.drain.closing().await
+	}
+
+	// Returns a guard held by a subscription while it's served.
+	pub(super) fn serving(&self) -> ServingGuard {
+		self.serving.start()
+	}
+
+	// Block until every subscription being served has ended and every data stream has finished.
+	pub(super) async fn drained(&self) {
+		self.serving.idle().await;
+		self.scheduler.idle().await
+	}
+
+	pub(super) fn prioritizer(&self) -> Arc<dyn Prioritizer> {
+		self.prioritizer.lock().unwrap().clone()
 	}
 
 	pub(super) async fn send_datagram(&mut self, data: bytes::Bytes) -> Result<(), SessionError> {
-		Ok(self.webtransport.send_datagram(data).await?)
+		self.observer().traffic().sent(data.len());
+		Ok(self.transport.send_datagram(data).await?)
+	}
+
+	// The largest datagram the transport will currently accept.
+	pub(super) async fn max_datagram_size(&self) -> usize {
+		self.transport.max_datagram_size().await
 	}
 }
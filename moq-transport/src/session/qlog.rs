@@ -0,0 +1,160 @@
+use std::{fmt::Write as _, io, sync::Mutex, time};
+
+use super::{SessionEvent, SessionObserver, StreamDirection};
+
+/// A [SessionObserver] that writes each event as qlog, so a session can be visualized alongside its QUIC trace.
+///
+/// The output uses the JSON-SEQ serialization, with a record per event and times in milliseconds.
+/// Messages and stream headers are included using their debug representation.
+pub struct QlogWriter<W: io::Write + Send> {
+	output: Mutex<W>,
+}
+
+impl<W: io::Write + Send> QlogWriter<W> {
+	/// Write the qlog header, using the title to identify the session.
+	pub fn new(mut output: W, title: &str) -> io::Result<Self> {
+		let header = format!(
+			r#"{{"qlog_version":"0.3","qlog_format":"JSON-SEQ","title":{},"trace":{{"common_fields":{{"time_format":"relative","protocol_type":["MOQT"]}}}}}}"#,
+			quote(title)
+		);
+		write_record(&mut output, &header)?;
+
+		Ok(Self {
+			output: Mutex::new(output),
+		})
+	}
+
+	/// Return the output, such as to flush it once the session is done.
+	pub fn into_inner(self) -> W {
+		self.output.into_inner().unwrap()
+	}
+}
+
+impl<W: io::Write + Send> SessionObserver for QlogWriter<W> {
+	fn event(&self, time: time::Duration, event: &SessionEvent) {
+		let (name, data) = match event {
+			SessionEvent::MessageSent(msg) => (
+				"control_message_created",
+				format!(
+					r#"{{"message_type":{},"message":{}}}"#,
+					quote(msg.name()),
+					quote(&format!("{:?}", msg))
+				),
+			),
+			SessionEvent::MessageReceived(msg) => (
+				"control_message_parsed",
+				format!(
+					r#"{{"message_type":{},"message":{}}}"#,
+					quote(msg.name()),
+					quote(&format!("{:?}", msg))
+				),
+			),
+			SessionEvent::StreamOpened { direction, header } => (
+				"stream_opened",
+				format!(
+					r#"{{"direction":"{}","subscribe_id":{},"header":{}}}"#,
+					direction_name(*direction),
+					header.subscribe_id(),
+					quote(&format!("{:?}", header))
+				),
+			),
+			SessionEvent::StreamClosed {
+				direction,
+				subscribe_id,
+				group_id,
+			} => (
+				"stream_closed",
+				format!(
+					r#"{{"direction":"{}","subscribe_id":{},"group_id":{}}}"#,
+					direction_name(*direction),
+					subscribe_id,
+					group_id.map_or("null".to_string(), |id| id.to_string())
+				),
+			),
+			SessionEvent::GroupDropped { subscribe_id, group_id } => (
+				"group_dropped",
+				format!(r#"{{"subscribe_id":{},"group_id":{}}}"#, subscribe_id, group_id),
+			),
+		};
+
+		let record = format!(
+			r#"{{"time":{:.3},"name":"moqt:{}","data":{}}}"#,
+			time.as_secs_f64() * 1000.0,
+			name,
+			data
+		);
+
+		if let Err(err) = write_record(&mut *self.output.lock().unwrap(), &record) {
+			log::warn!("failed to write qlog: {}", err);
+		}
+	}
+}
+
+// Each JSON-SEQ record starts with a record separator and ends with a newline.
+fn write_record<W: io::Write>(output: &mut W, record: &str) -> io::Result<()> {
+	output.write_all(b"\x1e")?;
+	output.write_all(record.as_bytes())?;
+	output.write_all(b"\n")
+}
+
+fn direction_name(direction: StreamDirection) -> &'static str {
+	match direction {
+		StreamDirection::Send => "send",
+		StreamDirection::Recv => "recv",
+	}
+}
+
+// Encode the string as a JSON string literal.
+fn quote(s: &str) -> String {
+	let mut out = String::with_capacity(s.len() + 2);
+	out.push('"');
+
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+			c => out.push(c),
+		}
+	}
+
+	out.push('"');
+	out
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::message;
+
+	#[test]
+	fn records() {
+		let qlog = QlogWriter::new(Vec::new(), "test \"session\"").unwrap();
+
+		let msg = message::Message::from(message::Unsubscribe { id: 7 });
+		qlog.event(time::Duration::from_millis(1500), &SessionEvent::MessageSent(&msg));
+		qlog.event(
+			time::Duration::ZERO,
+			&SessionEvent::GroupDropped {
+				subscribe_id: 7,
+				group_id: 3,
+			},
+		);
+
+		let output = String::from_utf8(qlog.into_inner()).unwrap();
+		let records: Vec<_> = output.split_terminator('\n').collect();
+
+		assert_eq!(records.len(), 3);
+		assert!(records.iter().all(|record| record.starts_with('\x1e')));
+		assert!(records[0].contains(r#""title":"test \"session\"""#));
+		assert!(records[1].starts_with("\x1e{\"time\":1500.000,\"name\":\"moqt:control_message_created\""));
+		assert!(records[1].contains(r#""message_type":"Unsubscribe""#));
+		assert_eq!(
+			records[2],
+			"\x1e{\"time\":0.000,\"name\":\"moqt:group_dropped\",\"data\":{\"subscribe_id\":7,\"group_id\":3}}"
+		);
+	}
+}
@@ -3,28 +3,76 @@ use std::{cmp, io};
 use bytes::{Buf, Bytes, BytesMut};
 
 use crate::coding::{Decode, DecodeError};
+use crate::error::ErrorCode;
+use crate::metrics::TrackMetrics;
+use crate::transport;
 
-use super::SessionError;
+use super::{Limits, SessionError, Traffic};
 
 pub struct Reader {
-	stream: web_transport::RecvStream,
+	stream: transport::RecvStream,
 	buffer: BytesMut,
+
+	// The maximum size of each chunk returned by read_chunk.
+	max_chunk: usize,
+
+	// Throttles reading to the session's bitrate.
+	limits: Limits,
+
+	// Counts the bytes received, once we know which track the stream is for.
+	metrics: Option<TrackMetrics>,
+
+	// Counts the bytes received for the session's statistics.
+	traffic: Option<Traffic>,
 }
 
 impl Reader {
-	pub fn new(stream: web_transport::RecvStream) -> Self {
+	pub fn new(stream: transport::RecvStream) -> Self {
 		Self {
 			stream,
 			buffer: Default::default(),
+			max_chunk: usize::MAX,
+			limits: Limits::default(),
+			metrics: None,
+			traffic: None,
 		}
 	}
 
+	/// Cap the size of each chunk, so a single read can't allocate an enormous buffer.
+	pub fn with_max_chunk(mut self, size: usize) -> Self {
+		self.max_chunk = cmp::max(size, 1);
+		self
+	}
+
+	/// Pause reading while the session is over its bitrate limit.
+	pub fn with_limits(mut self, limits: Limits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	pub(super) fn with_traffic(mut self, traffic: Traffic) -> Self {
+		self.traffic = Some(traffic);
+		self
+	}
+
+	pub fn set_metrics(&mut self, metrics: TrackMetrics) {
+		self.metrics = Some(metrics);
+	}
+
 	pub async fn decode<T: Decode>(&mut self) -> Result<T, SessionError> {
+		self.decode_with(|r| T::decode(r)).await
+	}
+
+	/// Decode using the provided function, such as a versioned [crate::message::Codec].
+	pub async fn decode_with<T, F>(&mut self, decode: F) -> Result<T, SessionError>
+	where
+		F: Fn(&mut io::Cursor<&BytesMut>) -> Result<T, DecodeError>,
+	{
 		loop {
 			let mut cursor = io::Cursor::new(&self.buffer);
 
 			// Try to decode with the current buffer.
-			let required = match T::decode(&mut cursor) {
+			let required = match decode(&mut cursor) {
 				Ok(msg) => {
 					self.buffer.advance(cursor.position() as usize);
 					return Ok(msg);
@@ -36,9 +84,11 @@ impl Reader {
 			// Read in more data until we reach the requested amount.
 			// We always read at least once to avoid an infinite loop if some dingus puts remain=0
 			loop {
+				let size = self.buffer.len();
 				if !self.stream.read_buf(&mut self.buffer).await? {
 					return Err(DecodeError::More(required - self.buffer.len()).into());
 				};
+				self.received(self.buffer.len() - size).await;
 
 				if self.buffer.len() >= required {
 					break;
@@ -47,14 +97,22 @@ impl Reader {
 		}
 	}
 
+	/// Read up to `max` bytes, further limited by [Self::with_max_chunk].
 	pub async fn read_chunk(&mut self, max: usize) -> Result<Option<Bytes>, SessionError> {
+		let max = cmp::min(max, self.max_chunk);
+
 		if !self.buffer.is_empty() {
 			let size = cmp::min(max, self.buffer.len());
 			let data = self.buffer.split_to(size).freeze();
 			return Ok(Some(data));
 		}
 
-		Ok(self.stream.read_chunk(max).await?)
+		let chunk = self.stream.read_chunk(max).await?;
+		if let Some(chunk) = &chunk {
+			self.received(chunk.len()).await;
+		}
+
+		Ok(chunk)
 	}
 
 	pub async fn done(&mut self) -> Result<bool, SessionError> {
@@ -62,6 +120,26 @@ impl Reader {
 			return Ok(false);
 		}
 
-		Ok(!self.stream.read_buf(&mut self.buffer).await?)
+		let done = !self.stream.read_buf(&mut self.buffer).await?;
+		self.received(self.buffer.len()).await;
+
+		Ok(done)
+	}
+
+	async fn received(&self, size: usize) {
+		if let Some(traffic) = &self.traffic {
+			traffic.received(size);
+		}
+
+		if let Some(metrics) = &self.metrics {
+			metrics.received(size);
+		}
+
+		self.limits.throttle(size).await;
+	}
+
+	/// Stop reading, asking the peer to abandon the stream with the error.
+	pub fn stop<E: ErrorCode>(self, err: &E) {
+		self.stream.stop(err.reset_code());
 	}
 }
@@ -0,0 +1,273 @@
+use std::{
+	cmp,
+	collections::{HashMap, HashSet},
+	future::Future,
+	time,
+};
+
+use futures::{future, stream::FuturesUnordered, FutureExt, StreamExt};
+
+use crate::serve::{ServeError, TracksReader};
+use crate::watch::{Queue, State};
+use crate::{setup, transport};
+
+use super::{Access, Drain, Publisher, Session, SessionError, Subscriber};
+
+/// Configures how a [Reconnect] session retries.
+#[derive(Clone, Debug)]
+pub struct ReconnectConfig {
+	/// The maximum number of consecutive failed attempts before giving up, or None to retry forever.
+	pub max_retries: Option<usize>,
+
+	/// The delay before the first retry, doubled after each failed attempt.
+	pub backoff_min: time::Duration,
+
+	/// The maximum delay between attempts.
+	pub backoff_max: time::Duration,
+
+	/// How long the peer has to announce a namespace again after a reconnect, before it's reported as withdrawn.
+	pub announce_wait: time::Duration,
+}
+
+impl Default for ReconnectConfig {
+	fn default() -> Self {
+		Self {
+			max_retries: None,
+			backoff_min: time::Duration::from_millis(100),
+			backoff_max: time::Duration::from_secs(10),
+			announce_wait: time::Duration::from_secs(2),
+		}
+	}
+}
+
+/// The connection state of a [Reconnect] session.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReconnectStatus {
+	/// Establishing the first connection.
+	Connecting,
+
+	/// The session is established.
+	Connected,
+
+	/// The session was lost and we're trying to establish a new one.
+	Reconnecting { attempt: usize },
+
+	/// We gave up after too many failed attempts.
+	Failed,
+}
+
+/// A session that reconnects when the transport fails with a retryable error; see [SessionError::is_retryable].
+///
+/// Active subscriptions are replayed on the new session, resuming after the last group received,
+/// so any [crate::serve::TrackReader] keeps working across the reconnect, skipping any groups missed in between.
+/// Tracks delivered as a single stream can't be resumed and are closed instead.
+/// Announcements from the peer keep their [super::Announced] handle if the peer announces them again on the new session,
+/// otherwise they're reported as withdrawn via [Subscriber::announced_event] after [ReconnectConfig::announce_wait].
+/// Our own announcements made via [Self::publisher] are replayed.
+pub struct Reconnect {
+	subscriber: Subscriber,
+	publisher: ReconnectPublisher,
+	config: ReconnectConfig,
+	status: State<ReconnectStatus>,
+}
+
+impl Reconnect {
+	/// Returns the session and a subscriber that outlives any individual connection.
+	pub fn new(config: ReconnectConfig) -> (Self, Subscriber) {
+		// The queue is replaced on the first connect.
+		let subscriber = Subscriber::new(Queue::default(), None, Drain::new(), Access::default());
+
+		let this = Self {
+			subscriber: subscriber.clone(),
+			publisher: ReconnectPublisher {
+				announces: Default::default(),
+			},
+			config,
+			status: State::new(ReconnectStatus::Connecting),
+		};
+
+		(this, subscriber)
+	}
+
+	/// Returns a handle that can be used to monitor the connection state.
+	pub fn status(&self) -> ReconnectMonitor {
+		ReconnectMonitor {
+			state: self.status.clone(),
+		}
+	}
+
+	/// Returns a handle used to announce broadcasts on every session.
+	pub fn publisher(&self) -> ReconnectPublisher {
+		self.publisher.clone()
+	}
+
+	/// Connect using the provided function, reconnecting with exponential backoff until the session is closed cleanly.
+	///
+	/// Returns an error if the session fails with an error that isn't retryable, or after too many failed attempts.
+	pub async fn run<F, Fut>(self, mut connect: F) -> Result<(), SessionError>
+	where
+		F: FnMut() -> Fut,
+		Fut: Future<Output = Result<transport::Session, SessionError>>,
+	{
+		let mut attempt = 0;
+		let mut backoff = self.config.backoff_min;
+
+		loop {
+			// The server downgrades the role if it only publishes or subscribes.
+			let res = match connect().await {
+				Ok(session) => Session::connect_resume(session, setup::Role::Both, self.subscriber.clone()).await,
+				Err(err) => Err(err),
+			};
+
+			match res {
+				Ok((session, publisher)) => {
+					attempt = 0;
+					backoff = self.config.backoff_min;
+					self.set_status(ReconnectStatus::Connected);
+					self.subscriber.expire_resumed(self.config.announce_wait);
+
+					let res = match publisher {
+						Some(publisher) => self.publisher.run(session, publisher).await,
+						None => session.run().await,
+					};
+
+					match res {
+						Ok(()) => return Ok(()),
+						Err(err) if !err.is_retryable() => {
+							self.set_status(ReconnectStatus::Failed);
+							return Err(err);
+						}
+						Err(err) => log::warn!("session failed, reconnecting: {}", err),
+					}
+				}
+				Err(err) => {
+					attempt += 1;
+
+					if !err.is_retryable() || self.config.max_retries.is_some_and(|max| attempt > max) {
+						self.set_status(ReconnectStatus::Failed);
+						return Err(err);
+					}
+
+					log::warn!("failed to connect: attempt={} error={}", attempt, err);
+				}
+			}
+
+			self.set_status(ReconnectStatus::Reconnecting { attempt: attempt + 1 });
+
+			tokio::time::sleep(backoff).await;
+			backoff = cmp::min(backoff * 2, self.config.backoff_max);
+		}
+	}
+
+	fn set_status(&self, status: ReconnectStatus) {
+		if let Some(mut state) = self.status.lock_mut() {
+			*state = status;
+		}
+	}
+}
+
+/// Used to monitor the connection state of a [Reconnect] session.
+#[derive(Clone)]
+pub struct ReconnectMonitor {
+	state: State<ReconnectStatus>,
+}
+
+impl ReconnectMonitor {
+	/// Returns the current connection state.
+	pub fn get(&self) -> ReconnectStatus {
+		self.state.lock().clone()
+	}
+
+	/// Block until the connection state changes, returning the new state.
+	pub async fn changed(&self) -> ReconnectStatus {
+		let notify = self.state.lock().modified();
+		if let Some(notify) = notify {
+			notify.await;
+		}
+
+		self.get()
+	}
+}
+
+/// Announces broadcasts on every session established by a [Reconnect], until they're closed.
+#[derive(Clone)]
+pub struct ReconnectPublisher {
+	announces: State<HashMap<String, TracksReader>>,
+}
+
+impl ReconnectPublisher {
+	/// Announce the broadcast on the current session, and again after every reconnect.
+	///
+	/// The broadcast is withdrawn once it's closed, or every [crate::serve::TracksWriter] is dropped.
+	pub fn announce(&mut self, tracks: TracksReader) -> Result<(), ServeError> {
+		let mut announces = self.announces.lock_mut().ok_or(ServeError::Cancel)?;
+
+		// Replace a previous broadcast with the same namespace, but only if it was closed.
+		if let Some(existing) = announces.get(&tracks.namespace) {
+			if existing.closed().now_or_never().is_none() {
+				return Err(ServeError::Duplicate);
+			}
+		}
+
+		announces.insert(tracks.namespace.clone(), tracks);
+
+		Ok(())
+	}
+
+	// Run the session, announcing every broadcast until the session ends.
+	async fn run(&self, session: Session, publisher: Publisher) -> Result<(), SessionError> {
+		let session = session.run();
+		tokio::pin!(session);
+
+		let mut announced = HashSet::new();
+		let mut tasks = FuturesUnordered::new();
+
+		loop {
+			let changed = {
+				let announces = self.announces.lock();
+
+				for (namespace, tracks) in announces.iter() {
+					if announced.insert(namespace.clone()) {
+						let mut publisher = publisher.clone();
+						let tracks = tracks.clone();
+
+						tasks.push(async move {
+							if let Err(err) = publisher.announce(tracks.clone()).await {
+								log::warn!("failed to announce: namespace={} error={}", tracks.namespace, err);
+							}
+							tracks
+						});
+					}
+				}
+
+				announces.modified()
+			};
+
+			// NOTE: Never None, since we hold a reference.
+			let changed = match changed {
+				Some(changed) => changed.left_future(),
+				None => future::pending().right_future(),
+			};
+
+			tokio::select! {
+				res = &mut session => return res,
+				_ = changed => {},
+				Some(tracks) = tasks.next() => {
+					// Otherwise the peer rejected the announce, so don't try again until the next session.
+					if tracks.closed().now_or_never().is_none() {
+						continue;
+					}
+
+					// Forget the broadcast once it's closed, unless it was replaced, so it isn't announced after a reconnect.
+					announced.remove(&tracks.namespace);
+
+					if let Some(mut announces) = self.announces.lock_mut() {
+						if announces.get(&tracks.namespace).is_some_and(|tracks| tracks.closed().now_or_never().is_some()) {
+							announces.remove(&tracks.namespace);
+						}
+					}
+				},
+			}
+		}
+	}
+}
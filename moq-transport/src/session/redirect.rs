@@ -0,0 +1,95 @@
+use std::{collections::HashSet, future::Future, sync::Arc};
+
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::serve::{ServeError, TrackWriter};
+use crate::transport;
+
+use super::{SessionError, SubscribeStart, Subscriber};
+
+type Connect = dyn Fn(String) -> BoxFuture<'static, Result<transport::Session, SessionError>> + Send + Sync;
+
+/// Follows a [crate::message::Redirect] by subscribing to the track on the indicated origin.
+pub(super) struct Redirector {
+	connect: Box<Connect>,
+	max_hops: usize,
+}
+
+impl Redirector {
+	pub fn new<F, Fut>(max_hops: usize, connect: F) -> Self
+	where
+		F: Fn(String) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<transport::Session, SessionError>> + Send + 'static,
+	{
+		Self {
+			connect: Box::new(move |url| connect(url).boxed()),
+			max_hops,
+		}
+	}
+
+	/// Subscribe to the track at each origin in turn, until it's served or we give up.
+	///
+	/// Each URL is visited at most once, so origins redirecting to each other fail instead of looping.
+	pub async fn follow(self: Arc<Self>, mut track: TrackWriter, mut url: String) -> Result<(), ServeError> {
+		let mut visited = HashSet::new();
+
+		loop {
+			if visited.len() >= self.max_hops || !visited.insert(url.clone()) {
+				log::warn!(
+					"too many redirects: namespace={} name={} url={}",
+					track.namespace,
+					track.name,
+					url
+				);
+
+				let err = ServeError::Redirect(url);
+				track.close(err.clone())?;
+				return Err(err);
+			}
+
+			log::info!(
+				"following redirect: namespace={} name={} url={}",
+				track.namespace,
+				track.name,
+				url
+			);
+
+			let (session, mut subscriber) = match self.connect(&url).await {
+				Ok(session) => session,
+				Err(err) => {
+					let err = ServeError::Internal(format!("failed to follow redirect: {}", err));
+					track.close(err.clone())?;
+					return Err(err);
+				}
+			};
+
+			// Any further redirect returns the track to us, so we can detect loops.
+			*subscriber.redirect.lock().unwrap() = Some(self.clone());
+
+			let mut subscribe = subscriber.subscribe_inner(track, SubscribeStart::Latest, None, false);
+
+			// NOTE: The session is closed once the track is done.
+			let res = tokio::select! {
+				Err(err) = session.run() => Err(ServeError::Internal(err.to_string())),
+				res = subscribe.closed() => res,
+			};
+
+			let next = match &res {
+				Err(ServeError::Redirect(next)) => next.clone(),
+				_ => return res,
+			};
+
+			track = match subscribe.take_retry() {
+				Some(track) => track,
+				None => return res,
+			};
+
+			url = next;
+		}
+	}
+
+	async fn connect(&self, url: &str) -> Result<(super::Session, Subscriber), SessionError> {
+		let session = (self.connect)(url.to_string()).await?;
+		Subscriber::connect(session).await
+	}
+}
@@ -0,0 +1,104 @@
+use futures::{stream::FuturesUnordered, StreamExt};
+
+use crate::serve::{ServeError, Track, TrackWriter, Tracks};
+
+use super::{Announced, Publisher, SessionError, Subscriber};
+
+/// Forwards every namespace announced by an upstream [Subscriber] to a downstream [Publisher].
+///
+/// Tracks are subscribed upstream on demand: the first downstream subscription for a track subscribes upstream,
+/// any concurrent subscriptions share it, and it's unsubscribed once the last downstream subscriber leaves.
+/// Downstream subscriptions for a namespace that isn't announced upstream are rejected with [ServeError::NotFound].
+pub struct Relay {
+	upstream: Subscriber,
+	downstream: Publisher,
+}
+
+impl Relay {
+	pub fn new(upstream: Subscriber, downstream: Publisher) -> Self {
+		Self { upstream, downstream }
+	}
+
+	/// Run until the upstream session stops announcing.
+	pub async fn run(mut self) -> Result<(), SessionError> {
+		let mut tasks = FuturesUnordered::new();
+
+		loop {
+			tokio::select! {
+				Some(announced) = self.upstream.announced() => {
+					let upstream = self.upstream.clone();
+					let downstream = self.downstream.clone();
+
+					tasks.push(async move {
+						let info = announced.info.clone();
+						log::info!("relaying announce: {:?}", info);
+
+						if let Err(err) = Self::serve_announce(upstream, downstream, announced).await {
+							log::warn!("failed relaying announce: {:?}, error: {}", info, err);
+						}
+					});
+				},
+				Some(subscribed) = self.downstream.subscribed() => {
+					log::debug!("rejecting subscribe for unknown namespace: {:?}", subscribed.info);
+					subscribed.close(ServeError::NotFound).ok();
+				},
+				_ = tasks.next(), if !tasks.is_empty() => {},
+				else => return Ok(()),
+			}
+		}
+	}
+
+	async fn serve_announce(
+		upstream: Subscriber,
+		mut downstream: Publisher,
+		mut announced: Announced,
+	) -> Result<(), SessionError> {
+		// Requests for the same track are coalesced by the TracksReader.
+		let (_, mut request, reader) = Tracks::new(announced.namespace.clone()).produce();
+
+		announced.ok()?;
+
+		let mut tasks = FuturesUnordered::new();
+
+		let announce = downstream.announce(reader);
+		tokio::pin!(announce);
+
+		loop {
+			tokio::select! {
+				res = &mut announce => return res,
+				res = announced.closed() => return Ok(res?),
+				Some(track) = request.next() => tasks.push(Self::serve_track(upstream.clone(), track)),
+				_ = tasks.next(), if !tasks.is_empty() => {},
+			}
+		}
+	}
+
+	async fn serve_track(mut upstream: Subscriber, mut track: TrackWriter) {
+		log::info!("relaying subscribe: {:?}", track.info);
+
+		// Subscribe upstream with a separate track, so we keep our writer and know when it's unused.
+		let (writer, reader) = Track::new(track.namespace.clone(), track.name.clone()).produce();
+		let subscribe = upstream.subscribe_handle(writer);
+
+		let res = tokio::select! {
+			res = reader.mode() => res.and_then(|mode| track.forward(mode)),
+			_ = track.unused() => return,
+		};
+
+		if let Err(err) = res {
+			log::warn!("failed relaying subscribe: {:?}, error: {}", track.info, err);
+			track.close(err).ok();
+			return;
+		}
+
+		// Dropping the subscription unsubscribes upstream.
+		tokio::select! {
+			res = subscribe.closed() => {
+				let err = res.err().unwrap_or(ServeError::Done);
+				log::debug!("relayed subscribe closed: {:?}, error: {}", track.info, err);
+				track.close(err).ok();
+			},
+			_ = track.unused() => log::debug!("no more downstream subscribers: {:?}", track.info),
+		}
+	}
+}
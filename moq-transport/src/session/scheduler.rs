@@ -0,0 +1,278 @@
+use std::{
+	cmp,
+	collections::HashMap,
+	sync::{Arc, Mutex},
+};
+
+use super::SessionError;
+
+/// How streams are shared between subscriptions once the limit of concurrent streams is reached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StreamPolicy {
+	/// Take turns between subscriptions, giving more turns to higher priority (lower value) tracks.
+	///
+	/// A subscription with priority `p` receives streams at a rate proportional to `1 / (p + 1)`,
+	/// so a busy low priority track still makes progress.
+	#[default]
+	Weighted,
+
+	/// Always open the stream with the highest priority (lowest value) next, in arrival order for ties.
+	///
+	/// A busy high priority track can starve every other track.
+	Strict,
+}
+
+struct Waiter {
+	subscribe_id: u64,
+	priority: u64,
+
+	// Breaks ties in arrival order.
+	sequence: u64,
+
+	grant: tokio::sync::oneshot::Sender<StreamPermit>,
+}
+
+struct SchedulerState {
+	policy: StreamPolicy,
+	capacity: usize,
+	active: usize,
+
+	waiters: Vec<Waiter>,
+	sequence: u64,
+
+	// The virtual time of each subscription that's ahead of the clock, used by the weighted policy.
+	finish: HashMap<u64, u64>,
+	clock: u64,
+}
+
+impl SchedulerState {
+	fn start(&self, waiter: &Waiter) -> u64 {
+		let finish = self.finish.get(&waiter.subscribe_id).copied().unwrap_or_default();
+		cmp::max(finish, self.clock)
+	}
+
+	// Remove the waiter that should be granted a stream next.
+	fn next(&mut self) -> Option<Waiter> {
+		let index = match self.policy {
+			StreamPolicy::Strict => self
+				.waiters
+				.iter()
+				.enumerate()
+				.min_by_key(|(_, waiter)| (waiter.priority, waiter.sequence)),
+			StreamPolicy::Weighted => self
+				.waiters
+				.iter()
+				.enumerate()
+				.min_by_key(|(_, waiter)| (self.start(waiter), waiter.sequence)),
+		}?
+		.0;
+
+		let waiter = self.waiters.swap_remove(index);
+
+		if self.policy == StreamPolicy::Weighted {
+			let start = self.start(&waiter);
+			let finish = start.saturating_add(waiter.priority.saturating_add(1));
+
+			self.clock = start;
+			self.finish.insert(waiter.subscribe_id, finish);
+
+			// Subscriptions that have fallen behind the clock start from it anyway.
+			let clock = self.clock;
+			self.finish.retain(|_, finish| *finish > clock);
+		}
+
+		Some(waiter)
+	}
+}
+
+/// Arbitrates the data streams opened by every subscription served by a [super::Publisher].
+///
+/// Each stream holds a [StreamPermit] until it's finished or reset.
+/// Once the limit is reached, new streams wait here to be granted according to the [StreamPolicy].
+#[derive(Clone)]
+pub(super) struct StreamScheduler {
+	state: Arc<Mutex<SchedulerState>>,
+
+	// Notified when the last permit is released.
+	idle: Arc<tokio::sync::Notify>,
+}
+
+impl StreamScheduler {
+	/// The default maximum number of concurrent streams.
+	///
+	/// This matches the default stream limit of common QUIC implementations, so streams wait here,
+	/// where they can be prioritized, rather than inside the transport.
+	pub const CAPACITY: usize = 100;
+
+	pub fn new() -> Self {
+		let state = SchedulerState {
+			policy: StreamPolicy::default(),
+			capacity: Self::CAPACITY,
+			active: 0,
+			waiters: Vec::new(),
+			sequence: 0,
+			finish: HashMap::new(),
+			clock: 0,
+		};
+
+		Self {
+			state: Arc::new(Mutex::new(state)),
+			idle: Default::default(),
+		}
+	}
+
+	pub fn set_policy(&self, policy: StreamPolicy) {
+		self.state.lock().unwrap().policy = policy;
+	}
+
+	pub fn set_capacity(&self, capacity: usize) {
+		self.state.lock().unwrap().capacity = cmp::max(capacity, 1);
+		self.grant();
+	}
+
+	/// Wait until the subscription may open another stream.
+	pub async fn acquire(&self, subscribe_id: u64, priority: u64) -> Result<StreamPermit, SessionError> {
+		let recv = {
+			let mut state = self.state.lock().unwrap();
+
+			// Skip the queue if there's room and nobody else is waiting.
+			if state.active < state.capacity && state.waiters.is_empty() {
+				state.active += 1;
+				return Ok(StreamPermit {
+					scheduler: Some(self.clone()),
+				});
+			}
+
+			let (grant, recv) = tokio::sync::oneshot::channel();
+			let sequence = state.sequence;
+			state.sequence += 1;
+
+			state.waiters.push(Waiter {
+				subscribe_id,
+				priority,
+				sequence,
+				grant,
+			});
+
+			recv
+		};
+
+		// NOTE: If we're cancelled after being granted, dropping the permit passes it on.
+		recv.await.map_err(|_| SessionError::Internal)
+	}
+
+	/// Wait until every permit has been released and nobody is waiting for one.
+	pub async fn idle(&self) {
+		loop {
+			let notified = self.idle.notified();
+
+			{
+				let state = self.state.lock().unwrap();
+				if state.active == 0 && state.waiters.is_empty() {
+					return;
+				}
+			}
+
+			notified.await;
+		}
+	}
+
+	fn release(&self) {
+		self.state.lock().unwrap().active -= 1;
+		self.grant();
+
+		if self.state.lock().unwrap().active == 0 {
+			self.idle.notify_waiters();
+		}
+	}
+
+	fn grant(&self) {
+		let mut state = self.state.lock().unwrap();
+
+		while state.active < state.capacity {
+			let waiter = match state.next() {
+				Some(waiter) => waiter,
+				None => return,
+			};
+
+			// Count the permit before it exists, since dropping it releases the slot.
+			state.active += 1;
+
+			let permit = StreamPermit {
+				scheduler: Some(self.clone()),
+			};
+
+			if let Err(mut permit) = waiter.grant.send(permit) {
+				// The waiter was cancelled; release the slot here since we're holding the lock.
+				permit.scheduler = None;
+				state.active -= 1;
+			}
+		}
+	}
+}
+
+/// Reserves one of the [super::Publisher]'s concurrent streams, released on drop.
+pub(super) struct StreamPermit {
+	scheduler: Option<StreamScheduler>,
+}
+
+impl Drop for StreamPermit {
+	fn drop(&mut self) {
+		if let Some(scheduler) = self.scheduler.take() {
+			scheduler.release();
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// Queue streams for two subscriptions in alternating order, returning the order they're granted.
+	async fn order(policy: StreamPolicy) -> Vec<u64> {
+		let scheduler = StreamScheduler::new();
+		scheduler.set_policy(policy);
+		scheduler.set_capacity(1);
+
+		let held = scheduler.acquire(0, 0).await.unwrap();
+
+		// Subscription 1 has a higher priority than subscription 2.
+		let mut pending: Vec<_> = (0..8)
+			.map(|i| {
+				let (id, priority) = if i % 2 == 0 { (1, 0) } else { (2, 2) };
+				let scheduler = scheduler.clone();
+				Box::pin(async move { (id, scheduler.acquire(id, priority).await.unwrap()) })
+			})
+			.collect();
+
+		for acquire in pending.iter_mut() {
+			assert!(futures::poll!(acquire).is_pending());
+		}
+
+		drop(held);
+
+		let mut order = Vec::new();
+		while !pending.is_empty() {
+			let mut granted = None;
+			for (index, acquire) in pending.iter_mut().enumerate() {
+				if let std::task::Poll::Ready(res) = futures::poll!(acquire) {
+					granted = Some((index, res));
+					break;
+				}
+			}
+
+			// Dropping the permit grants the next stream.
+			let (index, (id, _permit)) = granted.expect("nothing granted");
+			order.push(id);
+			drop(pending.remove(index));
+		}
+
+		order
+	}
+
+	#[tokio::test]
+	async fn policy() {
+		assert_eq!(order(StreamPolicy::Strict).await, [1, 1, 1, 1, 2, 2, 2, 2]);
+		assert_eq!(order(StreamPolicy::Weighted).await, [1, 2, 1, 1, 2, 1, 2, 2]);
+	}
+}
@@ -0,0 +1,240 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::serve::ServeError;
+use crate::watch::{State, StateWeak};
+
+// The number of groups that may arrive after a gap before the gap is declared dropped.
+const GAP_WINDOW: usize = 8;
+
+/// The fate of a group, reported in group order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupEvent {
+	/// The group was fully received.
+	Delivered(u64),
+
+	/// The publisher reset the group with this error.
+	Reset(u64, ServeError),
+
+	/// The group never arrived or failed for another reason.
+	Dropped(u64),
+}
+
+impl GroupEvent {
+	pub fn group_id(&self) -> u64 {
+		match self {
+			Self::Delivered(id) | Self::Reset(id, _) | Self::Dropped(id) => *id,
+		}
+	}
+}
+
+#[derive(Default)]
+struct GroupEventsState {
+	queue: VecDeque<GroupEvent>,
+	closed: bool,
+}
+
+/// An ordered stream of group events for a subscription.
+pub struct GroupEvents {
+	// The only strong reference, so queued events survive the subscription closing.
+	state: State<GroupEventsState>,
+}
+
+impl GroupEvents {
+	/// Returns the next event, or None once the subscription has closed and every event was returned.
+	pub async fn next(&mut self) -> Option<GroupEvent> {
+		loop {
+			{
+				let state = self.state.lock();
+				if !state.queue.is_empty() || state.closed {
+					return state.into_mut()?.queue.pop_front();
+				}
+
+				state.modified()?
+			}
+			.await;
+		}
+	}
+}
+
+// Reorders group completions into a contiguous sequence, filling gaps with drops.
+#[derive(Default)]
+pub(super) struct GroupSequencer {
+	// The watermark; every group below this has been reported.
+	next: Option<u64>,
+
+	// Completed groups waiting on an earlier group.
+	pending: BTreeMap<u64, GroupEvent>,
+
+	// Only set once the application asks for events.
+	events: Option<StateWeak<GroupEventsState>>,
+
+	// The events reported since the last call to take.
+	emitted: Vec<GroupEvent>,
+
+	// Set after resuming, so the next group drops every group before it instead of waiting.
+	resync: bool,
+}
+
+impl GroupSequencer {
+	pub fn events(&mut self) -> GroupEvents {
+		let state = State::default();
+		self.events = Some(state.downgrade());
+
+		GroupEvents { state }
+	}
+
+	pub fn delivered(&mut self, group_id: u64) {
+		self.insert(GroupEvent::Delivered(group_id));
+	}
+
+	pub fn reset(&mut self, group_id: u64, err: ServeError) {
+		self.insert(GroupEvent::Reset(group_id, err));
+	}
+
+	pub fn dropped(&mut self, group_id: u64) {
+		self.insert(GroupEvent::Dropped(group_id));
+	}
+
+	// The publisher skipped some groups, so report any gap before the next group as dropped.
+	pub fn resync(&mut self) {
+		self.resync = true;
+	}
+
+	// Returns every event reported since the last call, in group order.
+	pub fn take(&mut self) -> Vec<GroupEvent> {
+		std::mem::take(&mut self.emitted)
+	}
+
+	// Report everything still pending, since no more groups will arrive.
+	pub fn close(&mut self) {
+		while let Some((&last, _)) = self.pending.last_key_value() {
+			let next = self.next.unwrap_or(last);
+			let event = self.pending.remove(&next).unwrap_or(GroupEvent::Dropped(next));
+			self.next = Some(next + 1);
+			self.emit(event);
+		}
+
+		if let Some(state) = self.events.take().and_then(|events| events.upgrade()) {
+			if let Some(mut state) = state.lock_mut() {
+				state.closed = true;
+			}
+		}
+	}
+
+	fn insert(&mut self, event: GroupEvent) {
+		let next = *self.next.get_or_insert(event.group_id());
+		if event.group_id() < next {
+			// Already reported, most likely declared dropped before it finally arrived.
+			return;
+		}
+
+		// The first group after resuming, which every skipped group precedes.
+		let resync = std::mem::take(&mut self.resync).then_some(event.group_id());
+
+		self.pending.entry(event.group_id()).or_insert(event);
+
+		let mut next = next;
+
+		loop {
+			if let Some(event) = self.pending.remove(&next) {
+				self.emit(event);
+			} else if self.pending.len() > GAP_WINDOW || resync.is_some_and(|resync| next < resync) {
+				// Give up on the gap if too many groups are waiting behind it, or if it was skipped.
+				self.emit(GroupEvent::Dropped(next));
+			} else {
+				break;
+			}
+
+			next += 1;
+		}
+
+		self.next = Some(next);
+	}
+
+	fn emit(&mut self, event: GroupEvent) {
+		self.emitted.push(event.clone());
+
+		let state = match self.events.as_ref().and_then(|events| events.upgrade()) {
+			Some(state) => state,
+			None => {
+				// The application is no longer listening.
+				self.events = None;
+				return;
+			}
+		};
+
+		if let Some(mut state) = state.lock_mut() {
+			state.queue.push_back(event);
+		};
+	}
+}
+
+impl Drop for GroupSequencer {
+	fn drop(&mut self) {
+		self.close();
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn drain(events: &mut GroupEvents) -> Vec<GroupEvent> {
+		let mut res = Vec::new();
+		while !events.state.lock().queue.is_empty() {
+			res.push(futures::executor::block_on(events.next()).unwrap());
+		}
+		res
+	}
+
+	#[test]
+	fn gaps() {
+		let mut sequencer = GroupSequencer::default();
+		let mut events = sequencer.events();
+
+		sequencer.delivered(5);
+		sequencer.delivered(6);
+		sequencer.delivered(8);
+		assert_eq!(drain(&mut events), [GroupEvent::Delivered(5), GroupEvent::Delivered(6)]);
+
+		// The drop for 7 arrives after 8 was delivered.
+		sequencer.dropped(7);
+		assert_eq!(drain(&mut events), [GroupEvent::Dropped(7), GroupEvent::Delivered(8)]);
+
+		// Group 9 never arrives, so it's dropped once the window fills up.
+		for id in 10..=10 + GAP_WINDOW as u64 {
+			sequencer.delivered(id);
+		}
+
+		let res = drain(&mut events);
+		assert_eq!(res[0], GroupEvent::Dropped(9));
+		assert_eq!(res.len(), GAP_WINDOW + 2);
+
+		// A late arrival is ignored.
+		sequencer.delivered(9);
+		sequencer.delivered(20);
+		sequencer.close();
+		assert_eq!(drain(&mut events), [GroupEvent::Dropped(19), GroupEvent::Delivered(20)]);
+		assert_eq!(futures::executor::block_on(events.next()), None);
+	}
+
+	#[test]
+	fn resync() {
+		let mut sequencer = GroupSequencer::default();
+		let mut events = sequencer.events();
+
+		sequencer.delivered(1);
+		sequencer.resync();
+		sequencer.delivered(4);
+
+		assert_eq!(
+			drain(&mut events),
+			[
+				GroupEvent::Delivered(1),
+				GroupEvent::Dropped(2),
+				GroupEvent::Dropped(3),
+				GroupEvent::Delivered(4)
+			]
+		);
+	}
+}
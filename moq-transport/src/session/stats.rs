@@ -0,0 +1,189 @@
+use std::{
+	sync::{
+		atomic::{self, AtomicU64},
+		Arc,
+	},
+	time,
+};
+
+use crate::transport;
+
+use super::{SessionPath, SessionRtt, StreamDirection, SubscribeStats, Subscriber};
+
+/// A snapshot of a session's statistics, see [super::Session::stats].
+///
+/// Bytes are counted at the MoQ layer, including the control stream but not any QUIC or WebTransport overhead.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionStats {
+	/// The round trip time measured by the keepalive, if enabled; see [super::Session::set_keepalive].
+	pub rtt: Option<time::Duration>,
+
+	/// The network path, including the congestion window, if the transport exposes it.
+	pub path: Option<transport::Path>,
+
+	/// The bytes sent on streams and datagrams.
+	pub bytes_sent: u64,
+
+	/// The bytes received on streams and datagrams.
+	pub bytes_received: u64,
+
+	/// The bytes sent per second since the previous update, only set by [SessionStatsReader::watch].
+	pub send_bitrate: Option<u64>,
+
+	/// The bytes received per second since the previous update, only set by [SessionStatsReader::watch].
+	pub recv_bitrate: Option<u64>,
+
+	/// The number of data streams opened by us.
+	pub streams_sent: u64,
+
+	/// The number of data streams opened by the peer.
+	pub streams_received: u64,
+
+	/// The number of data streams currently open, in either direction.
+	pub streams_active: u64,
+
+	/// Each active subscription to the peer, ordered by ID.
+	pub subscriptions: Vec<SubscriptionStats>,
+}
+
+/// The statistics for a single subscription, see [SessionStats::subscriptions].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscriptionStats {
+	pub id: u64,
+	pub namespace: String,
+	pub name: String,
+	pub stats: SubscribeStats,
+
+	/// The payload bytes received per second since the previous update, only set by [SessionStatsReader::watch].
+	pub bitrate: Option<u64>,
+}
+
+/// Returns the statistics of a session, which remains valid while running.
+#[derive(Clone)]
+pub struct SessionStatsReader {
+	traffic: Traffic,
+	rtt: SessionRtt,
+	path: SessionPath,
+	subscriber: Option<Subscriber>,
+}
+
+impl SessionStatsReader {
+	pub(super) fn new(traffic: Traffic, rtt: SessionRtt, path: SessionPath, subscriber: Option<Subscriber>) -> Self {
+		Self {
+			traffic,
+			rtt,
+			path,
+			subscriber,
+		}
+	}
+
+	/// Returns a snapshot of the statistics.
+	pub fn get(&self) -> SessionStats {
+		let counter = |value: &AtomicU64| value.load(atomic::Ordering::Relaxed);
+		let traffic = &self.traffic.0;
+
+		let mut subscriptions = match &self.subscriber {
+			Some(subscriber) => subscriber.subscription_stats(),
+			None => Vec::new(),
+		};
+		subscriptions.sort_by_key(|subscription| subscription.id);
+
+		SessionStats {
+			rtt: self.rtt.get(),
+			path: self.path.get(),
+			bytes_sent: counter(&traffic.bytes_sent),
+			bytes_received: counter(&traffic.bytes_received),
+			send_bitrate: None,
+			recv_bitrate: None,
+			streams_sent: counter(&traffic.streams_sent),
+			streams_received: counter(&traffic.streams_received),
+			streams_active: counter(&traffic.streams_active),
+			subscriptions,
+		}
+	}
+
+	/// Returns a channel updated with a snapshot every interval, including the bitrates since the previous one.
+	///
+	/// The updates stop once every receiver is dropped.
+	pub fn watch(&self, interval: time::Duration) -> tokio::sync::watch::Receiver<SessionStats> {
+		let (sender, receiver) = tokio::sync::watch::channel(self.get());
+		let this = self.clone();
+
+		tokio::spawn(async move {
+			let mut previous = (time::Instant::now(), sender.borrow().clone());
+
+			loop {
+				tokio::select! {
+					_ = tokio::time::sleep(interval) => {},
+					_ = sender.closed() => return,
+				};
+
+				let now = time::Instant::now();
+				let mut stats = this.get();
+				stats.rates(&previous.1, now - previous.0);
+
+				sender.send_replace(stats.clone());
+				previous = (now, stats);
+			}
+		});
+
+		receiver
+	}
+}
+
+impl SessionStats {
+	// Fill in the bitrates based on the previous snapshot.
+	fn rates(&mut self, previous: &SessionStats, elapsed: time::Duration) {
+		let rate = |bytes: u64, prev: u64| (bytes.saturating_sub(prev) as f64 / elapsed.as_secs_f64()) as u64;
+
+		self.send_bitrate = Some(rate(self.bytes_sent, previous.bytes_sent));
+		self.recv_bitrate = Some(rate(self.bytes_received, previous.bytes_received));
+
+		for subscription in &mut self.subscriptions {
+			let prev = previous
+				.subscriptions
+				.iter()
+				.find(|prev| prev.id == subscription.id)
+				.map_or(0, |prev| prev.stats.bytes);
+
+			subscription.bitrate = Some(rate(subscription.stats.bytes, prev));
+		}
+	}
+}
+
+#[derive(Default)]
+struct TrafficCounters {
+	bytes_sent: AtomicU64,
+	bytes_received: AtomicU64,
+	streams_sent: AtomicU64,
+	streams_received: AtomicU64,
+	streams_active: AtomicU64,
+}
+
+// Counts the bytes and streams for a session, shared by every reader and writer.
+#[derive(Clone, Default)]
+pub(super) struct Traffic(Arc<TrafficCounters>);
+
+impl Traffic {
+	pub fn sent(&self, size: usize) {
+		self.0.bytes_sent.fetch_add(size as u64, atomic::Ordering::Relaxed);
+	}
+
+	pub fn received(&self, size: usize) {
+		self.0.bytes_received.fetch_add(size as u64, atomic::Ordering::Relaxed);
+	}
+
+	pub fn stream_opened(&self, direction: StreamDirection) {
+		let count = match direction {
+			StreamDirection::Send => &self.0.streams_sent,
+			StreamDirection::Recv => &self.0.streams_received,
+		};
+
+		count.fetch_add(1, atomic::Ordering::Relaxed);
+		self.0.streams_active.fetch_add(1, atomic::Ordering::Relaxed);
+	}
+
+	pub fn stream_closed(&self) {
+		self.0.streams_active.fetch_sub(1, atomic::Ordering::Relaxed);
+	}
+}
@@ -1,14 +1,32 @@
-use std::ops;
+use std::{
+	ops,
+	sync::{Arc, Mutex},
+};
 
 use crate::{
 	data,
 	message::{self, FilterType, SubscribeLocation, SubscribePair},
+	metrics::TrackMetrics,
 	serve::{self, ServeError, TrackWriter, TrackWriterMode},
 };
 
 use crate::watch::State;
 
-use super::Subscriber;
+use super::{GroupEvents, GroupSequencer, Subscriber};
+
+/// Where a subscription should start.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SubscribeStart {
+	/// Start from the latest group.
+	#[default]
+	Latest,
+
+	/// Start from the oldest group the publisher still has cached.
+	Earliest,
+
+	/// Start from this group, or the oldest cached group after it.
+	Group(u64),
+}
 
 #[derive(Debug, Clone)]
 pub struct SubscribeInfo {
@@ -16,9 +34,41 @@ pub struct SubscribeInfo {
 	pub name: String,
 }
 
+/// A snapshot of the progress of a subscription.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubscribeStats {
+	/// The largest group ID received so far.
+	pub latest_group: Option<u64>,
+
+	/// The number of groups received.
+	pub groups: u64,
+
+	/// The number of objects received.
+	pub objects: u64,
+
+	/// The number of payload bytes received.
+	pub bytes: u64,
+
+	/// The number of streams that were dropped before they were fully received.
+	pub drops: u64,
+
+	/// False once the subscription has been closed.
+	pub active: bool,
+}
+
+impl SubscribeStats {
+	pub(super) fn group(&mut self, group_id: u64) {
+		self.groups += 1;
+		self.latest_group = Some(self.latest_group.map_or(group_id, |latest| latest.max(group_id)));
+	}
+}
+
 struct SubscribeState {
 	ok: bool,
 	closed: Result<(), ServeError>,
+
+	// The first group the publisher will deliver, if it's starting from the cache.
+	start: Option<u64>,
 }
 
 impl Default for SubscribeState {
@@ -26,6 +76,7 @@ impl Default for SubscribeState {
 		Self {
 			ok: Default::default(),
 			closed: Ok(()),
+			start: None,
 		}
 	}
 }
@@ -35,14 +86,30 @@ impl Default for SubscribeState {
 pub struct Subscribe {
 	state: State<SubscribeState>,
 	subscriber: Subscriber,
-	id: u64,
+	msg: message::Subscribe,
+
+	// Updated by the subscriber as data arrives.
+	stats: Arc<Mutex<SubscribeStats>>,
+	sequencer: Arc<Mutex<GroupSequencer>>,
+
+	// The untouched track, returned on a retryable error so it can be subscribed again.
+	// NOTE: Not part of the state, since it's handed back after the receiver is dropped.
+	returned: Arc<Mutex<Option<TrackWriter>>>,
 
 	pub info: SubscribeInfo,
 }
 
 impl Subscribe {
-	pub(super) fn new(mut subscriber: Subscriber, id: u64, track: TrackWriter) -> (Subscribe, SubscribeRecv) {
-		subscriber.send_message(message::Subscribe {
+	// NOTE: The caller sends the SUBSCRIBE, so a subscription can be rejected without one.
+	pub(super) fn new(
+		subscriber: Subscriber,
+		id: u64,
+		track: TrackWriter,
+		start: SubscribeStart,
+		max_groups: Option<u64>,
+		retry: bool,
+	) -> (Subscribe, SubscribeRecv) {
+		let mut msg = message::Subscribe {
 			id,
 			track_alias: id,
 			track_namespace: track.namespace.clone(),
@@ -58,7 +125,32 @@ impl Subscribe {
 				object: SubscribeLocation::None,
 			}),
 			params: Default::default(),
-		});
+		};
+
+		let group = match start {
+			SubscribeStart::Latest => None,
+			// The publisher clamps to the oldest group it has cached.
+			SubscribeStart::Earliest => Some(0),
+			SubscribeStart::Group(group) => Some(group),
+		};
+
+		if let Some(group) = group {
+			msg.filter_type = FilterType::AbsoluteStart;
+			msg.start = Some(SubscribePair {
+				group: SubscribeLocation::Absolute(group),
+				object: SubscribeLocation::Absolute(0),
+			});
+			msg.end = None;
+		}
+
+		// Let the publisher stop early when the last group is known up front.
+		if let (Some(group), Some(count)) = (group, max_groups) {
+			msg.filter_type = FilterType::AbsoluteRange;
+			msg.end = Some(SubscribePair {
+				group: SubscribeLocation::Absolute(group + count.saturating_sub(1)),
+				object: SubscribeLocation::None,
+			});
+		}
 
 		let info = SubscribeInfo {
 			namespace: track.namespace.clone(),
@@ -66,22 +158,103 @@ impl Subscribe {
 		};
 
 		let (send, recv) = State::default().split();
+		let stats = Arc::new(Mutex::new(SubscribeStats::default()));
+		let sequencer = Arc::new(Mutex::new(GroupSequencer::default()));
+		let returned = Arc::new(Mutex::new(None));
 
 		let send = Subscribe {
 			state: send,
 			subscriber,
-			id,
+			msg,
+			stats: stats.clone(),
+			sequencer: sequencer.clone(),
+			returned: returned.clone(),
 			info,
 		};
 
 		let recv = SubscribeRecv {
 			state: recv,
 			writer: Some(track.into()),
+			retry,
+			returned,
+			metrics: TrackMetrics::new(&send.info.namespace, &send.info.name),
+			msg: send.msg.clone(),
+			stats,
+			sequencer,
+			remaining: max_groups,
+			finish: None,
 		};
 
 		(send, recv)
 	}
 
+	/// Change the priority of the subscription without tearing it down.
+	///
+	/// The publisher applies the new priority to subsequent groups; any in-flight group is not interrupted.
+	pub fn set_priority(&mut self, priority: u64) {
+		self.send_update(Some(priority), None);
+	}
+
+	/// Ask the publisher to stop opening new group streams, for example while the player is buffering.
+	///
+	/// Groups already in flight are still delivered. A live track skips any groups produced while paused,
+	/// reported as dropped by [Self::group_events] once resumed; other tracks continue where they left off.
+	pub fn pause(&mut self) {
+		self.send_update(None, Some(true));
+	}
+
+	/// Resume a subscription paused with [Self::pause].
+	pub fn resume(&mut self) {
+		// Groups skipped by the publisher will never arrive, so don't wait for them.
+		self.sequencer.lock().unwrap().resync();
+		self.send_update(None, Some(false));
+	}
+
+	fn send_update(&mut self, priority: Option<u64>, paused: Option<bool>) {
+		self.subscriber.send_message(message::SubscribeUpdate {
+			id: self.msg.id,
+			track_alias: self.msg.track_alias,
+			track_namespace: self.msg.track_namespace.clone(),
+			track_name: self.msg.track_name.clone(),
+			filter_type: self.msg.filter_type.clone(),
+			start: self.msg.start.clone(),
+			end: self.msg.end.clone(),
+			priority,
+			paused,
+			params: Default::default(),
+		});
+	}
+
+	// Take back the track after a retryable error or redirect, if no data was received.
+	pub(super) fn take_retry(&mut self) -> Option<TrackWriter> {
+		self.returned.lock().unwrap().take()
+	}
+
+	/// Returns the first group the publisher will deliver, once it has accepted a subscription from the cache.
+	///
+	/// This is always None unless both peers advertised [crate::message::SUBSCRIBE_START_PARAM] in their SETUP.
+	pub fn start(&self) -> Option<u64> {
+		self.state.lock().start
+	}
+
+	/// Returns a snapshot of the subscription's progress.
+	pub fn stats(&self) -> SubscribeStats {
+		let mut stats = self.stats.lock().unwrap().clone();
+
+		let state = self.state.lock();
+		stats.active = state.closed.is_ok() && state.modified().is_some();
+
+		stats
+	}
+
+	/// Returns an ordered stream of delivered and dropped groups, starting from the next group received.
+	///
+	/// A reset group is reported with the publisher's error, and a gap is reported as dropped once enough later groups arrive.
+	/// Only applies when the publisher uses a stream per group; any previous stream of events is closed.
+	pub fn group_events(&mut self) -> GroupEvents {
+		self.sequencer.lock().unwrap().events()
+	}
+
 	pub async fn closed(&self) -> Result<(), ServeError> {
 		loop {
 			{
@@ -96,11 +269,24 @@ impl Subscribe {
 			.await;
 		}
 	}
+
+	/// Unsubscribe immediately, closing the track with [ServeError::Cancel].
+	///
+	/// This is the same as dropping the handle, but makes the intent explicit.
+	pub fn unsubscribe(self) {
+		drop(self);
+	}
 }
 
 impl Drop for Subscribe {
 	fn drop(&mut self) {
-		self.subscriber.send_message(message::Unsubscribe { id: self.id });
+		// Close a track that was handed back but never taken, so readers don't wait forever.
+		if let Some(track) = self.take_retry() {
+			let err = self.state.lock().closed.clone().err().unwrap_or(ServeError::Cancel);
+			track.close(err).ok();
+		}
+
+		self.subscriber.send_message(message::Unsubscribe { id: self.msg.id });
 	}
 }
 
@@ -115,10 +301,116 @@ impl ops::Deref for Subscribe {
 pub(super) struct SubscribeRecv {
 	state: State<SubscribeState>,
 	writer: Option<TrackWriterMode>,
+
+	// Return the track instead of closing it on a retryable error.
+	retry: bool,
+	returned: Arc<Mutex<Option<TrackWriter>>>,
+
+	// The original SUBSCRIBE, used to resubscribe after a reconnect.
+	msg: message::Subscribe,
+
+	stats: Arc<Mutex<SubscribeStats>>,
+	sequencer: Arc<Mutex<GroupSequencer>>,
+	metrics: TrackMetrics,
+
+	// The number of groups left before the subscription completes.
+	remaining: Option<u64>,
+
+	// The final group announced by SUBSCRIBE_DONE, which we're still waiting to receive.
+	finish: Option<u64>,
 }
 
 impl SubscribeRecv {
-	pub fn ok(&mut self) -> Result<(), ServeError> {
+	pub fn msg(&self) -> &message::Subscribe {
+		&self.msg
+	}
+
+	pub fn stats(&self) -> Arc<Mutex<SubscribeStats>> {
+		self.stats.clone()
+	}
+
+	pub fn sequencer(&self) -> Arc<Mutex<GroupSequencer>> {
+		self.sequencer.clone()
+	}
+
+	pub fn metrics(&self) -> TrackMetrics {
+		self.metrics.clone()
+	}
+
+	// Returns true once the maximum number of groups, or the final group announced by SUBSCRIBE_DONE, has been received.
+	pub fn completed(&self) -> bool {
+		let finished = || {
+			self.finish
+				.is_some_and(|last| self.stats.lock().unwrap().latest_group >= Some(last))
+		};
+		self.remaining == Some(0) || finished()
+	}
+
+	// Returns true if the publisher already ended the subscription, so there's no need to unsubscribe.
+	pub fn finishing(&self) -> bool {
+		self.finish.is_some()
+	}
+
+	// Wait for the final group before closing cleanly, since SUBSCRIBE_DONE may overtake the streams before it.
+	// Returns false if the subscription should be closed immediately.
+	pub fn finish(&mut self, last: Option<(u64, u64)>, err: &ServeError) -> bool {
+		if *err != ServeError::Done
+			|| !matches!(
+				self.writer,
+				Some(TrackWriterMode::Track(_) | TrackWriterMode::Groups(_))
+			) {
+			return false;
+		}
+
+		let mut last = match last {
+			Some((group, _)) => group,
+			None => return false,
+		};
+
+		// The publisher reports its latest group, which may be past the end of our range.
+		if let (FilterType::AbsoluteRange, Some(end)) = (&self.msg.filter_type, self.msg.end.as_ref()) {
+			if let SubscribeLocation::Absolute(end) = end.group {
+				last = last.min(end);
+			}
+		}
+
+		self.finish = Some(last);
+		!self.completed()
+	}
+
+	// Returns the SUBSCRIBE to send on a new session, or None if the subscription can't be resumed.
+	pub fn resume(&mut self) -> Option<message::Subscribe> {
+		let mut msg = self.msg.clone();
+
+		match self.writer.as_ref()? {
+			// Resume after the last group received.
+			TrackWriterMode::Groups(groups) => {
+				msg.filter_type = FilterType::AbsoluteStart;
+				msg.start = Some(SubscribePair {
+					group: SubscribeLocation::Absolute(groups.next_group_id()),
+					object: SubscribeLocation::Absolute(0),
+				});
+				msg.end = None;
+			}
+			// A stream can't be continued, since the publisher would start a new one.
+			TrackWriterMode::Stream(_) => {
+				if let Some(writer) = self.writer.take() {
+					writer.close(ServeError::Cancel).ok();
+				}
+				return None;
+			}
+			_ => {}
+		}
+
+		// We expect a new SUBSCRIBE_OK from the new session.
+		if let Some(mut state) = self.state.lock_mut() {
+			state.ok = false;
+		}
+
+		Some(msg)
+	}
+
+	pub fn ok(&mut self, start: Option<u64>) -> Result<(), ServeError> {
 		let state = self.state.lock();
 		if state.ok {
 			return Err(ServeError::Duplicate);
@@ -126,16 +418,27 @@ impl SubscribeRecv {
 
 		if let Some(mut state) = state.into_mut() {
 			state.ok = true;
+			state.start = start;
 		}
 
 		Ok(())
 	}
 
 	pub fn error(mut self, err: ServeError) -> Result<(), ServeError> {
-		if let Some(writer) = self.writer.take() {
-			writer.close(err.clone())?;
+		match self.writer.take() {
+			// We can only retry if we haven't started writing to the track.
+			Some(TrackWriterMode::Track(track))
+				if self.retry && (err.is_retryable() || matches!(err, ServeError::Redirect(_))) =>
+			{
+				*self.returned.lock().unwrap() = Some(track)
+			}
+			Some(writer) => writer.close(err.clone())?,
+			None => {}
 		}
 
+		// No more groups will arrive, so report any gaps.
+		self.sequencer.lock().unwrap().close();
+
 		let state = self.state.lock();
 		state.closed.clone()?;
 
@@ -145,6 +448,16 @@ impl SubscribeRecv {
 		Ok(())
 	}
 
+	// Close the subscription with a redirect, keeping the untouched track if it will be subscribed elsewhere.
+	pub fn redirect(mut self, url: String, follow: bool) -> Result<(), ServeError> {
+		if follow {
+			// Avoid closing the track, since it's handed back via Subscribe::take_retry.
+			self.retry = true;
+		}
+
+		self.error(ServeError::Redirect(url))
+	}
+
 	pub fn track(&mut self, header: data::TrackHeader) -> Result<serve::StreamWriter, ServeError> {
 		let writer = self.writer.take().ok_or(ServeError::Done)?;
 
@@ -167,16 +480,36 @@ impl SubscribeRecv {
 			_ => return Err(ServeError::Mode),
 		};
 
-		let writer = groups.create(serve::Group {
+		let writer = match groups.create(serve::Group {
 			group_id: header.group_id,
 			priority: header.send_order,
-		})?;
+		}) {
+			Ok(writer) => writer,
+			Err(err) => {
+				// Keep the track, since a duplicate or late group doesn't end the subscription.
+				self.writer = Some(groups.into());
+				return Err(err);
+			}
+		};
+
+		self.stats.lock().unwrap().group(header.group_id);
+		self.remaining = self.remaining.map(|remaining| remaining.saturating_sub(1));
 
-		self.writer = Some(groups.into());
+		// Drop the track after the final group, so readers see a clean end instead of an error.
+		if !self.completed() {
+			self.writer = Some(groups.into());
+		}
 
 		Ok(writer)
 	}
 
+	// Forward dropped groups to the track's readers, if it's delivered as groups.
+	pub fn dropped(&mut self, dropped: serve::GroupsDropped) {
+		if let Some(TrackWriterMode::Groups(groups)) = self.writer.as_mut() {
+			groups.dropped(dropped).ok();
+		}
+	}
+
 	pub fn object(&mut self, header: data::ObjectHeader) -> Result<serve::ObjectWriter, ServeError> {
 		let writer = self.writer.take().ok_or(ServeError::Done)?;
 
@@ -197,6 +530,12 @@ impl SubscribeRecv {
 		Ok(writer)
 	}
 
+	// Returns true once the track is delivered as datagrams.
+	// NOTE: A large first datagram sent as a stream can't be told apart from an object.
+	pub fn is_datagrams(&self) -> bool {
+		matches!(self.writer, Some(TrackWriterMode::Datagrams(_)))
+	}
+
 	pub fn datagram(&mut self, datagram: data::Datagram) -> Result<(), ServeError> {
 		let writer = self.writer.take().ok_or(ServeError::Done)?;
 
@@ -214,6 +553,8 @@ impl SubscribeRecv {
 			payload: datagram.payload,
 		})?;
 
+		self.writer = Some(datagrams.into());
+
 		Ok(())
 	}
 }
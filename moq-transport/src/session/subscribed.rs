@@ -1,29 +1,37 @@
-use std::ops;
+use std::{cmp, collections::VecDeque, ops, sync::Arc, time};
 
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 
 use crate::coding::Encode;
-use crate::serve::{ServeError, TrackReaderMode};
+use crate::error::ErrorCode;
+use crate::message::{FilterType, SubscribeLocation};
+use crate::metrics::TrackMetrics;
+use crate::serve::{GroupOrder, ServeError, TrackReaderMode};
 use crate::watch::State;
 use crate::{data, message, serve};
 
-use super::{Publisher, SessionError, SubscribeInfo, Writer};
+use super::{
+	GroupCounter, GroupPriority, GroupProgress, Publisher, ServingGuard, SessionError, SessionEvent, StreamDirection,
+	SubscribeInfo, SubscribedProgress, Writer,
+};
 
 #[derive(Debug)]
 struct SubscribedState {
 	max: Option<(u64, u64)>,
+
+	// Overrides the priority of new streams, set by SUBSCRIBE_UPDATE.
+	priority: Option<u64>,
+
+	// Stops new group streams from being opened, set by SUBSCRIBE_UPDATE.
+	paused: bool,
+
 	closed: Result<(), ServeError>,
 }
 
 impl SubscribedState {
 	fn update_max(&mut self, group_id: u64, object_id: u64) -> Result<(), ServeError> {
-		if let Some((max_group, max_object)) = self.max {
-			if group_id >= max_group && object_id >= max_object {
-				self.max = Some((group_id, object_id));
-			}
-		}
-
+		self.max = cmp::max(self.max, Some((group_id, object_id)));
 		Ok(())
 	}
 }
@@ -32,21 +40,77 @@ impl Default for SubscribedState {
 	fn default() -> Self {
 		Self {
 			max: None,
+			priority: None,
+			paused: false,
 			closed: Ok(()),
 		}
 	}
 }
 
+/// A snapshot of a subscription being served, returned by [Publisher::active_subscriptions].
+#[derive(Debug, Clone)]
+pub struct SubscribedStatus {
+	pub id: u64,
+	pub info: SubscribeInfo,
+
+	/// The priority set by the subscriber, overriding the priority of each group.
+	pub priority: Option<u64>,
+
+	pub paused: bool,
+
+	/// The latest group and object sent.
+	pub latest: Option<(u64, u64)>,
+
+	/// The groups in flight and the most recently finished groups; see [SubscribedProgress::groups].
+	pub groups: Vec<GroupProgress>,
+}
+
+// A group being transmitted, which can be reset to make room for newer groups.
+struct GroupInflight {
+	group_id: u64,
+	priority: u64,
+	cancel: tokio::sync::oneshot::Sender<()>,
+}
+
+// Options applied to each group stream.
+#[derive(Clone)]
+struct GroupOptions {
+	concurrency: usize,
+	coalesce: usize,
+	metrics: TrackMetrics,
+}
+
 pub struct Subscribed {
 	publisher: Publisher,
 	state: State<SubscribedState>,
 	msg: message::Subscribe,
 	ok: bool,
 
+	// The maximum number of groups transmitted in parallel.
+	max_groups: usize,
+
+	// The maximum number of objects within a group buffered in parallel, relaxing their order.
+	object_concurrency: usize,
+
+	// Coalesce writes smaller than this many bytes, flushing at the end of each object.
+	coalesce: usize,
+
+	// Start the first live group from the next object rather than the beginning.
+	join_mid_group: bool,
+
+	progress: SubscribedProgress,
+	metrics: TrackMetrics,
+
 	pub info: SubscribeInfo,
+
+	// Held while serving, and declared last so it's released after SUBSCRIBE_DONE is queued on drop.
+	serving: Option<ServingGuard>,
 }
 
 impl Subscribed {
+	/// The default maximum number of groups transmitted in parallel.
+	pub const MAX_GROUPS: usize = 8;
+
 	pub(super) fn new(publisher: Publisher, msg: message::Subscribe) -> (Self, SubscribedRecv) {
 		let (send, recv) = State::default().split();
 		let info = SubscribeInfo {
@@ -54,21 +118,78 @@ impl Subscribed {
 			name: msg.track_name.clone(),
 		};
 
+		let progress = SubscribedProgress::default();
+		let metrics = TrackMetrics::new(&info.namespace, &info.name);
+
+		// Prevents updates after being closed
+		let recv = SubscribedRecv {
+			state: recv,
+			id: msg.id,
+			info: info.clone(),
+			progress: progress.clone(),
+		};
+
 		let send = Self {
 			publisher,
 			state: send,
 			msg,
 			info,
 			ok: false,
+			max_groups: Self::MAX_GROUPS,
+			object_concurrency: 1,
+			coalesce: 0,
+			join_mid_group: false,
+			progress,
+			metrics,
+			serving: None,
 		};
 
-		// Prevents updates after being closed
-		let recv = SubscribedRecv { state: recv };
-
 		(send, recv)
 	}
 
+	/// Set the maximum number of groups transmitted in parallel, each using a separate stream.
+	///
+	/// When the limit is reached, an ascending track waits for a group to finish.
+	/// A live (descending) track instead resets the oldest in-flight group with the lowest priority.
+	pub fn set_max_groups(&mut self, count: usize) {
+		self.max_groups = cmp::max(count, 1);
+	}
+
+	/// Buffer up to this many objects within a group in parallel, sending each one as soon as it's complete.
+	///
+	/// Each object is still sent contiguously, but objects may be sent out of order within the group,
+	/// so a small object isn't stuck behind a large one that's still being produced.
+	/// The default of 1 sends objects sequentially as they're produced.
+	pub fn set_object_concurrency(&mut self, count: usize) {
+		self.object_concurrency = cmp::max(count, 1);
+	}
+
+	/// Coalesce small writes into a buffer of this many bytes, sending it at the end of each object.
+	///
+	/// This reduces the number of stream writes for tracks with many small objects or chunks.
+	/// The default of 0 sends each write immediately.
+	pub fn set_coalesce(&mut self, size: usize) {
+		self.coalesce = size;
+	}
+
+	/// Start the first group from the next object produced, rather than the beginning, for a late joiner.
+	///
+	/// This avoids waiting for the next group when joining a large group that's already in progress.
+	/// Each object carries its ID, so the subscriber knows where the group starts.
+	/// It only applies to subscriptions at the live edge; the default delivers every group in full.
+	pub fn set_join_mid_group(&mut self, enabled: bool) {
+		self.join_mid_group = enabled;
+	}
+
+	/// Returns a handle reporting the progress of each group sent, which remains valid while serving.
+	pub fn progress(&self) -> SubscribedProgress {
+		self.progress.clone()
+	}
+
 	pub async fn serve(mut self, track: serve::TrackReader) -> Result<(), SessionError> {
+		let _active = self.metrics.subscribed();
+		self.serving = Some(self.publisher.serving());
+
 		let res = self.serve_inner(track).await;
 		if let Err(err) = &res {
 			self.close(err.clone().into())?;
@@ -81,15 +202,38 @@ impl Subscribed {
 		let latest = track.latest();
 		self.state.lock_mut().ok_or(ServeError::Cancel)?.max = latest;
 
+		// Only wait for the track mode before SUBSCRIBE_OK when we need to report where we're starting.
+		let (mode, start) = match self.start() {
+			Some(start) => {
+				let mut mode = track.mode().await?;
+				let start = match &mut mode {
+					TrackReaderMode::Groups(groups) => match self.end() {
+						Some(end) => groups.range(start..end.saturating_add(1)),
+						None => groups.start_at(start),
+					},
+					_ => None,
+				};
+
+				(Some(mode), start)
+			}
+			None => (None, None),
+		};
+
 		self.publisher.send_message(message::SubscribeOk {
 			id: self.msg.id,
 			expires: None,
 			latest,
+			start,
 		});
 
 		self.ok = true; // So we sent SubscribeDone on drop
 
-		match track.mode().await? {
+		let mode = match mode {
+			Some(mode) => mode,
+			None => track.mode().await?,
+		};
+
+		match mode {
 			// TODO cancel track/datagrams on closed
 			TrackReaderMode::Stream(stream) => self.serve_track(stream).await,
 			TrackReaderMode::Groups(groups) => self.serve_groups(groups).await,
@@ -98,6 +242,39 @@ impl Subscribed {
 		}
 	}
 
+	// The group requested by an absolute start, which may be older than the latest group.
+	fn start(&self) -> Option<u64> {
+		match self.msg.filter_type {
+			FilterType::AbsoluteStart | FilterType::AbsoluteRange => match self.msg.start.as_ref()?.group {
+				SubscribeLocation::Absolute(group) => Some(group),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+
+	// The last group requested by an absolute range.
+	fn end(&self) -> Option<u64> {
+		match self.msg.filter_type {
+			FilterType::AbsoluteRange => match self.msg.end.as_ref()?.group {
+				SubscribeLocation::Absolute(group) => Some(group),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+
+	/// Tell the subscriber to subscribe to the track at another origin, instead of serving it.
+	///
+	/// This must be called before serving; see [crate::session::Subscriber::follow_redirects].
+	pub fn redirect(self, url: String) -> Result<(), ServeError> {
+		if self.ok {
+			return Err(ServeError::Duplicate);
+		}
+
+		self.close(ServeError::Redirect(url))
+	}
+
 	pub fn close(self, err: ServeError) -> Result<(), ServeError> {
 		let state = self.state.lock();
 		state.closed.clone()?;
@@ -122,6 +299,24 @@ impl Subscribed {
 			.await;
 		}
 	}
+
+	// Wait until the subscriber pauses or resumes, returning the new value.
+	async fn paused_changed(&self, paused: bool) -> bool {
+		loop {
+			{
+				let state = self.state.lock();
+				if state.paused != paused || state.closed.is_err() {
+					return state.paused;
+				}
+
+				match state.modified() {
+					Some(notify) => notify,
+					None => return paused,
+				}
+			}
+			.await;
+		}
+	}
 }
 
 impl ops::Deref for Subscribed {
@@ -146,6 +341,8 @@ impl Drop for Subscribed {
 				code: err.code(),
 				reason: err.to_string(),
 			});
+		} else if let ServeError::Redirect(url) = err {
+			self.publisher.send_message(message::Redirect { id: self.msg.id, url });
 		} else {
 			self.publisher.send_message(message::SubscribeError {
 				id: self.msg.id,
@@ -159,12 +356,14 @@ impl Drop for Subscribed {
 
 impl Subscribed {
 	async fn serve_track(&mut self, mut track: serve::StreamReader) -> Result<(), SessionError> {
-		let mut stream = self.publisher.open_uni().await?;
+		let opening = time::Instant::now();
+		let (mut stream, _permit) = self.publisher.open_uni(self.msg.id, track.priority).await?;
+		self.metrics.stream_opened(opening.elapsed());
 
 		// TODO figure out u32 vs u64 priority
 		stream.set_priority(track.priority as i32);
 
-		let mut writer = Writer::new(stream);
+		let mut writer = self.publisher.writer(stream).with_coalesce(self.coalesce);
 
 		let header: data::Header = data::TrackHeader {
 			subscribe_id: self.msg.id,
@@ -174,10 +373,23 @@ impl Subscribed {
 		.into();
 
 		writer.encode(&header).await?;
+		let _observed = self.publisher.observer().stream(StreamDirection::Send, &header);
 
 		log::trace!("sent track header: {:?}", header);
 
-		while let Some(mut group) = track.next().await? {
+		loop {
+			// Stop between groups when draining, finishing the stream so its permit is released.
+			let mut group = tokio::select! {
+				res = track.next() => match res? {
+					Some(group) => group,
+					None => break,
+				},
+				_ = self.publisher.closing() => {
+					writer.flush().await?;
+					return Err(ServeError::GoingAway.into());
+				}
+			};
+
 			while let Some(mut object) = group.next().await? {
 				let header = data::TrackObject {
 					group_id: object.group_id,
@@ -196,14 +408,20 @@ impl Subscribed {
 				log::trace!("sent track object: {:?}", header);
 
 				while let Some(chunk) = object.read().await? {
-					writer.write(&chunk).await?;
-					log::trace!("sent track payload: {:?}", chunk.len());
+					let size = chunk.len();
+					writer.write_chunk(chunk).await?;
+					self.metrics.sent(size);
+					log::trace!("sent track payload: {:?}", size);
 				}
 
+				writer.flush().await?;
 				log::trace!("sent track done");
 			}
 		}
 
+		// Send the header even if there were no objects.
+		writer.flush().await?;
+
 		Ok(())
 	}
 
@@ -211,10 +429,42 @@ impl Subscribed {
 		let mut tasks = FuturesUnordered::new();
 		let mut done: Option<Result<(), ServeError>> = None;
 
+		// The groups currently being transmitted, in the order they were started.
+		let mut inflight = VecDeque::<GroupInflight>::new();
+
+		// Live tracks make room for new groups instead of waiting.
+		let live = groups.order == GroupOrder::Descending;
+
+		// Only the group in progress when subscribing at the live edge is joined part way.
+		let mut join_mid_group = self.join_mid_group && self.start().is_none();
+
+		// A paused live track skips groups so it resumes at the live edge, while other tracks stop reading.
+		let mut paused = self.state.lock().paused;
+
 		loop {
 			tokio::select! {
-				res = groups.next(), if done.is_none() => match res {
-					Ok(Some(group)) => {
+				res = groups.next(), if done.is_none() && (live || (!paused && inflight.len() < self.max_groups)) => match res {
+					Ok(Some(group)) if paused => log::debug!("skipping group while paused: {}", group.group_id),
+					Ok(Some(mut group)) => {
+						if std::mem::take(&mut join_mid_group) {
+							let skipped = group.skip_produced();
+							log::debug!("joining group mid-way: group={} skipped={}", group.group_id, skipped);
+						}
+
+						if inflight.len() >= self.max_groups {
+							// Reset the lowest priority group (largest value), preferring the oldest.
+							let index = inflight
+								.iter()
+								.enumerate()
+								.max_by_key(|(index, inflight)| (inflight.priority, cmp::Reverse(*index)))
+								.map(|(index, _)| index);
+
+							if let Some(reset) = index.and_then(|index| inflight.remove(index)) {
+								log::debug!("too many groups in flight, resetting group: {}", reset.group_id);
+								reset.cancel.send(()).ok();
+							}
+						}
+
 						let header = data::GroupHeader {
 							subscribe_id: self.msg.id,
 							track_alias: self.msg.track_alias,
@@ -222,52 +472,173 @@ impl Subscribed {
 							send_order: group.priority,
 						};
 
+						let (cancel, cancelled) = tokio::sync::oneshot::channel();
+						inflight.push_back(GroupInflight {
+							group_id: group.group_id,
+							priority: group.priority,
+							cancel,
+						});
+
 						let publisher = self.publisher.clone();
 						let state = self.state.clone();
 						let info = group.info.clone();
+						let counter = self.progress.start(group.group_id);
+						let options = GroupOptions {
+							concurrency: self.object_concurrency,
+							coalesce: self.coalesce,
+							metrics: self.metrics.clone(),
+						};
 
 						tasks.push(async move {
-							if let Err(err) = Self::serve_group(header, group, publisher, state).await {
+							if let Err(err) = Self::serve_group(header, group, publisher, state, options, counter, cancelled).await {
 								log::warn!("failed to serve group: {:?}, error: {}", info, err);
 							}
+
+							info.group_id
 						});
 					},
 					Ok(None) => done = Some(Ok(())),
-					Err(err) => done = Some(Err(err)),
+					Err(err) => {
+						// The track was closed with an error, so any groups in flight won't be completed.
+						Self::cancel_groups(&mut inflight);
+						done = Some(Err(err));
+					},
 				},
-				res = self.closed(), if done.is_none() => done = Some(res),
-				_ = tasks.next(), if !tasks.is_empty() => {},
+				res = self.closed(), if done.is_none() => {
+					// The subscription was closed, so stop sending any groups in flight.
+					if res.is_err() {
+						Self::cancel_groups(&mut inflight);
+					}
+
+					done = Some(res);
+				},
+				err = self.publisher.closed(), if done.is_none() || !tasks.is_empty() => {
+					// The streams can't be reset once the session is closed, so just stop.
+					return Err(err);
+				},
+				// Finish the groups in flight when draining, but don't start any more.
+				_ = self.publisher.closing(), if done.is_none() => done = Some(Err(ServeError::GoingAway)),
+				res = self.paused_changed(paused), if done.is_none() => paused = res,
+				Some(group_id) = tasks.next(), if !tasks.is_empty() => inflight.retain(|inflight| inflight.group_id != group_id),
 				else => return Ok(done.unwrap()?),
 			}
 		}
 	}
 
+	// Reset every group in flight, rather than waiting for them to finish.
+	fn cancel_groups(inflight: &mut VecDeque<GroupInflight>) {
+		for inflight in inflight.drain(..) {
+			log::debug!("cancelling group in flight: {}", inflight.group_id);
+			inflight.cancel.send(()).ok();
+		}
+	}
+
 	async fn serve_group(
 		header: data::GroupHeader,
-		mut group: serve::GroupReader,
+		group: serve::GroupReader,
 		mut publisher: Publisher,
 		state: State<SubscribedState>,
+		options: GroupOptions,
+		counter: Arc<GroupCounter>,
+		mut cancelled: tokio::sync::oneshot::Receiver<()>,
 	) -> Result<(), SessionError> {
-		let mut stream = publisher.open_uni().await?;
+		// Use the subscriber's priority if it has been updated.
+		let priority = state.lock().priority.unwrap_or(group.priority);
 
-		// TODO figure out u32 vs u64 priority
-		stream.set_priority(group.priority as i32);
+		let dropped = SessionEvent::GroupDropped {
+			subscribe_id: header.subscribe_id,
+			group_id: header.group_id,
+		};
+
+		let opening = time::Instant::now();
+
+		// The group may be cancelled while waiting for the scheduler or the transport.
+		let res = tokio::select! {
+			res = publisher.open_uni(header.subscribe_id, priority) => res.map(Some),
+			Ok(()) = &mut cancelled => Ok(None),
+		};
+
+		let (mut stream, _permit) = match res {
+			Ok(Some(res)) => res,
+			res => {
+				counter.finish(true);
+				options.metrics.group_finished(opening.elapsed(), true);
+				publisher.observer().emit(dropped);
+
+				// Nothing was sent if cancelled, so there's no stream to reset.
+				return res.map(|_| ());
+			}
+		};
+
+		let opened = time::Instant::now();
+		options.metrics.stream_opened(opened - opening);
+
+		let priority = publisher.prioritizer().priority(&GroupPriority {
+			priority,
+			order: group.order,
+			group_id: group.group_id,
+		});
+		stream.set_priority(priority);
+
+		let mut writer = publisher.writer(stream).with_coalesce(options.coalesce);
+
+		// Reported as closed once we return, after the stream is finished or reset.
+		let _observed = publisher
+			.observer()
+			.stream(StreamDirection::Send, &header.clone().into());
+
+		let (res, reset) = tokio::select! {
+			res = Self::serve_group_inner(&mut writer, header, group, state, &options, &counter) => match res {
+				Ok(()) => (Ok(()), false),
+				Err(err) => {
+					// Reset rather than finish the stream, so the subscriber knows the group is incomplete.
+					writer.reset(&err);
+					(Err(err), true)
+				}
+			},
+			Ok(()) = &mut cancelled => {
+				writer.reset(&ServeError::Cancel);
+				(Ok(()), true)
+			}
+		};
+
+		counter.finish(reset);
+		options.metrics.group_finished(opened.elapsed(), reset);
+
+		if reset {
+			publisher.observer().emit(dropped);
+		}
 
-		let mut writer = Writer::new(stream);
+		res
+	}
 
+	async fn serve_group_inner(
+		writer: &mut Writer,
+		header: data::GroupHeader,
+		mut group: serve::GroupReader,
+		state: State<SubscribedState>,
+		options: &GroupOptions,
+		counter: &GroupCounter,
+	) -> Result<(), SessionError> {
 		let header: data::Header = header.into();
 		writer.encode(&header).await?;
 
 		log::trace!("sent group: {:?}", header);
 
+		if options.concurrency > 1 {
+			return Self::serve_group_unordered(writer, group, state, options, counter).await;
+		}
+
 		while let Some(mut object) = group.next().await? {
 			let header = data::GroupObject {
 				object_id: object.object_id,
 				size: object.size,
 				status: object.status,
+				meta: object.meta.clone(),
 			};
 
 			writer.encode(&header).await?;
+			counter.produced(object.size);
 
 			state
 				.lock_mut()
@@ -277,16 +648,75 @@ impl Subscribed {
 			log::trace!("sent group object: {:?}", header);
 
 			while let Some(chunk) = object.read().await? {
-				writer.write(&chunk).await?;
-				log::trace!("sent group payload: {:?}", chunk.len());
+				let size = chunk.len();
+				writer.write_chunk(chunk).await?;
+				counter.sent(size);
+				options.metrics.sent(size);
+				log::trace!("sent group payload: {:?}", size);
 			}
 
+			writer.flush().await?;
 			log::trace!("sent group done");
 		}
 
+		// Send the header even if there were no objects.
+		writer.flush().await?;
+
 		Ok(())
 	}
 
+	// Buffer objects in parallel, writing each one in full as soon as it's complete.
+	async fn serve_group_unordered(
+		writer: &mut Writer,
+		mut group: serve::GroupReader,
+		state: State<SubscribedState>,
+		options: &GroupOptions,
+		counter: &GroupCounter,
+	) -> Result<(), SessionError> {
+		let mut pending = FuturesUnordered::new();
+		let mut done = false;
+
+		loop {
+			tokio::select! {
+				res = group.next(), if !done && pending.len() < options.concurrency => match res? {
+					Some(mut object) => {
+						counter.produced(object.size);
+						pending.push(async move {
+							let payload = object.read_all().await;
+							(object, payload)
+						});
+					},
+					None => done = true,
+				},
+				Some((object, payload)) = pending.next() => {
+					let payload = payload?;
+
+					let header = data::GroupObject {
+						object_id: object.object_id,
+						size: object.size,
+						status: object.status,
+						meta: object.meta.clone(),
+					};
+
+					writer.encode(&header).await?;
+					let size = payload.len();
+					writer.write_chunk(payload).await?;
+					writer.flush().await?;
+					counter.sent(size);
+					options.metrics.sent(size);
+
+					state
+						.lock_mut()
+						.ok_or(ServeError::Done)?
+						.update_max(group.group_id, object.object_id)?;
+
+					log::trace!("sent group object: {:?}", header);
+				},
+				else => return writer.flush().await,
+			}
+		}
+	}
+
 	pub async fn serve_objects(&mut self, mut objects: serve::ObjectsReader) -> Result<(), SessionError> {
 		let mut tasks = FuturesUnordered::new();
 		let mut done = None;
@@ -336,21 +766,26 @@ impl Subscribed {
 			.ok_or(ServeError::Done)?
 			.update_max(object.group_id, object.object_id)?;
 
-		let mut stream = publisher.open_uni().await?;
+		// Use the subscriber's priority if it has been updated.
+		let priority = state.lock().priority.unwrap_or(object.priority);
+
+		let (mut stream, _permit) = publisher.open_uni(header.subscribe_id, priority).await?;
 
 		// TODO figure out u32 vs u64 priority
-		stream.set_priority(object.priority as i32);
+		stream.set_priority(priority as i32);
 
-		let mut writer = Writer::new(stream);
+		let mut writer = publisher.writer(stream);
 
 		let header: data::Header = header.into();
 		writer.encode(&header).await?;
+		let _observed = publisher.observer().stream(StreamDirection::Send, &header);
 
 		log::trace!("sent object: {:?}", header);
 
 		while let Some(chunk) = object.read().await? {
-			writer.write(&chunk).await?;
-			log::trace!("sent object payload: {:?}", chunk.len());
+			let size = chunk.len();
+			writer.write_chunk(chunk).await?;
+			log::trace!("sent object payload: {:?}", size);
 		}
 
 		log::trace!("sent object done");
@@ -359,28 +794,80 @@ impl Subscribed {
 	}
 
 	async fn serve_datagrams(&mut self, mut datagrams: serve::DatagramsReader) -> Result<(), SessionError> {
-		while let Some(datagram) = datagrams.read().await? {
-			let datagram = data::Datagram {
-				subscribe_id: self.msg.id,
-				track_alias: self.msg.track_alias,
-				group_id: datagram.group_id,
-				object_id: datagram.object_id,
-				send_order: datagram.priority,
-				object_status: datagram.status,
-				payload: datagram.payload,
-			};
+		let mut tasks = FuturesUnordered::new();
+		let mut done = None;
+
+		loop {
+			tokio::select! {
+				res = datagrams.read(), if done.is_none() => match res {
+					Ok(Some(datagram)) => {
+						let datagram = data::Datagram {
+							subscribe_id: self.msg.id,
+							track_alias: self.msg.track_alias,
+							group_id: datagram.group_id,
+							object_id: datagram.object_id,
+							send_order: datagram.priority,
+							object_status: datagram.status,
+							payload: datagram.payload,
+						};
+
+						let (group_id, object_id) = (datagram.group_id, datagram.object_id);
+
+						let mut buffer = bytes::BytesMut::with_capacity(datagram.payload.len() + 100);
+						datagram.encode(&mut buffer)?;
+
+						if buffer.len() <= self.publisher.max_datagram_size().await {
+							self.publisher.send_datagram(buffer.into()).await?;
+							log::trace!("sent datagram: {:?}", datagram);
+						} else {
+							// Too large for a datagram, so fall back to a stream for this frame.
+							let publisher = self.publisher.clone();
+							tasks.push(async move {
+								if let Err(err) = Self::serve_datagram_stream(datagram, publisher).await {
+									log::warn!("failed to serve datagram stream: group={} object={}, error: {}", group_id, object_id, err);
+								}
+							});
+						}
+
+						self.state
+							.lock_mut()
+							.ok_or(ServeError::Done)?
+							.update_max(group_id, object_id)?;
+					},
+					Ok(None) => done = Some(Ok(())),
+					Err(err) => done = Some(Err(err)),
+				},
+				_ = tasks.next(), if !tasks.is_empty() => {},
+				res = self.closed(), if done.is_none() => done = Some(res),
+				else => return Ok(done.unwrap()?),
+			}
+		}
+	}
+
+	// Send a datagram as a single object stream, which the subscriber delivers as a datagram.
+	async fn serve_datagram_stream(datagram: data::Datagram, mut publisher: Publisher) -> Result<(), SessionError> {
+		let (mut stream, _permit) = publisher.open_uni(datagram.subscribe_id, datagram.send_order).await?;
 
-			let mut buffer = bytes::BytesMut::with_capacity(datagram.payload.len() + 100);
-			datagram.encode(&mut buffer)?;
+		// TODO figure out u32 vs u64 priority
+		stream.set_priority(datagram.send_order as i32);
 
-			self.publisher.send_datagram(buffer.into()).await?;
-			log::trace!("sent datagram: {:?}", datagram);
+		let mut writer = publisher.writer(stream);
 
-			self.state
-				.lock_mut()
-				.ok_or(ServeError::Done)?
-				.update_max(datagram.group_id, datagram.object_id)?;
+		let header: data::Header = data::ObjectHeader {
+			subscribe_id: datagram.subscribe_id,
+			track_alias: datagram.track_alias,
+			group_id: datagram.group_id,
+			object_id: datagram.object_id,
+			send_order: datagram.send_order,
+			object_status: datagram.object_status,
 		}
+		.into();
+
+		writer.encode(&header).await?;
+		let _observed = publisher.observer().stream(StreamDirection::Send, &header);
+		writer.write_chunk(datagram.payload).await?;
+
+		log::trace!("sent datagram stream: {:?}", header);
 
 		Ok(())
 	}
@@ -388,9 +875,27 @@ impl Subscribed {
 
 pub(super) struct SubscribedRecv {
 	state: State<SubscribedState>,
+
+	// Kept for diagnostics.
+	id: u64,
+	info: SubscribeInfo,
+	progress: SubscribedProgress,
 }
 
 impl SubscribedRecv {
+	pub fn status(&self) -> SubscribedStatus {
+		let state = self.state.lock();
+
+		SubscribedStatus {
+			id: self.id,
+			info: self.info.clone(),
+			priority: state.priority,
+			paused: state.paused,
+			latest: state.max,
+			groups: self.progress.groups(),
+		}
+	}
+
 	pub fn recv_unsubscribe(&mut self) -> Result<(), ServeError> {
 		let state = self.state.lock();
 		state.closed.clone()?;
@@ -401,4 +906,18 @@ impl SubscribedRecv {
 
 		Ok(())
 	}
+
+	pub fn recv_update(&mut self, msg: message::SubscribeUpdate) -> Result<(), ServeError> {
+		if let Some(priority) = msg.priority {
+			let mut state = self.state.lock_mut().ok_or(ServeError::Done)?;
+			state.priority = Some(priority);
+		}
+
+		if let Some(paused) = msg.paused {
+			let mut state = self.state.lock_mut().ok_or(ServeError::Done)?;
+			state.paused = paused;
+		}
+
+		Ok(())
+	}
 }
@@ -1,65 +1,581 @@
 use std::{
-	collections::{hash_map, HashMap},
+	cmp,
+	collections::{HashMap, VecDeque},
+	future::Future,
 	io,
 	sync::{atomic, Arc, Mutex},
+	time,
 };
 
 use crate::{
-	coding::Decode,
+	coding::{Decode, DecodeError},
 	data,
+	error::ErrorCode,
 	message::{self, Message},
 	serve::{self, ServeError},
-	setup,
+	setup, transport,
 };
 
 use crate::watch::Queue;
 
-use super::{Announced, AnnouncedRecv, Reader, Session, SessionError, Subscribe, SubscribeRecv};
+use super::{
+	Access, AnnounceInfo, Announced, AnnouncedEvent, AnnouncedRecv, Announcements, AuthRequest, Drain, GroupEvent,
+	Reader, Redirector, Session, SessionError, StreamDirection, Subscribe, SubscribeRecv, SubscribeStart,
+	SubscribeStats, SubscriptionStats, Writer,
+};
+
+// The queue for an Announcements handle, only receiving namespaces that start with the prefix.
+type PrefixQueue = (String, Queue<AnnouncedEvent<AnnounceInfo>>);
 
 // TODO remove Clone.
 #[derive(Clone)]
 pub struct Subscriber {
 	announced: Arc<Mutex<HashMap<String, AnnouncedRecv>>>,
-	announced_queue: Queue<Announced>,
+	announced_queue: Queue<AnnouncedEvent>,
+
+	// Withdrawals skipped by announced(), kept for announced_event() once it has been used.
+	withdrawn: Arc<Mutex<VecDeque<AnnounceInfo>>>,
+	withdrawn_wanted: Arc<atomic::AtomicBool>,
+
+	// Every Announcements handle with its prefix; dropped handles are removed on the next event.
+	announcements: Arc<Mutex<Vec<PrefixQueue>>>,
+
+	// The prefixes sent with SUBSCRIBE_NAMESPACE, counting the handles using each.
+	announce_prefixes: Arc<Mutex<HashMap<String, usize>>>,
 
 	subscribes: Arc<Mutex<HashMap<u64, SubscribeRecv>>>,
 	subscribe_next: Arc<atomic::AtomicU64>,
 
-	outgoing: Queue<Message>,
+	// Replaced when the session reconnects, so shared between all clones.
+	outgoing: Arc<Mutex<Queue<Message>>>,
+
+	// The maximum size of each chunk read from a stream.
+	max_chunk: Arc<atomic::AtomicUsize>,
+
+	// Used to open fetch streams, replaced when the session reconnects.
+	transport: Arc<Mutex<Option<transport::Session>>>,
+
+	// Set on shutdown; each stream task holds a receiver until it finishes.
+	shutdown: Arc<tokio::sync::watch::Sender<bool>>,
+
+	// Set after sending or receiving GOAWAY, replaced when the session reconnects.
+	drain: Arc<Mutex<Drain>>,
+
+	// Checks each ANNOUNCE from the peer, replaced when the session reconnects.
+	access: Arc<Mutex<Access>>,
+
+	// Follows a REDIRECT to another origin, if enabled.
+	pub(super) redirect: Arc<Mutex<Option<Arc<Redirector>>>>,
+
+	// Counts each reconnect, so announcements carried over can be withdrawn if the peer doesn't announce them again.
+	resumes: Arc<atomic::AtomicU64>,
 }
 
 impl Subscriber {
-	pub(super) fn new(outgoing: Queue<Message>) -> Self {
+	/// The largest datagram accepted when it's sent as a stream, since it's buffered until complete.
+	pub const MAX_DATAGRAM_STREAM: usize = 1024 * 1024;
+
+	/// How long to wait for the final group named by SUBSCRIBE_DONE, in case its stream was reset or lost.
+	pub const FINISH_TIMEOUT: time::Duration = time::Duration::from_secs(2);
+
+	pub(super) fn new(
+		outgoing: Queue<Message>,
+		transport: Option<transport::Session>,
+		drain: Drain,
+		access: Access,
+	) -> Self {
 		Self {
 			announced: Default::default(),
 			announced_queue: Default::default(),
+			withdrawn: Default::default(),
+			withdrawn_wanted: Default::default(),
+			announcements: Default::default(),
+			announce_prefixes: Default::default(),
 			subscribes: Default::default(),
 			subscribe_next: Default::default(),
-			outgoing,
+			outgoing: Arc::new(Mutex::new(outgoing)),
+			max_chunk: Arc::new(atomic::AtomicUsize::new(usize::MAX)),
+			transport: Arc::new(Mutex::new(transport)),
+			shutdown: Arc::new(tokio::sync::watch::channel(false).0),
+			drain: Arc::new(Mutex::new(drain)),
+			access: Arc::new(Mutex::new(access)),
+			redirect: Default::default(),
+			resumes: Default::default(),
+		}
+	}
+
+	/// Follow redirects to other origins, using the provided function to connect to each URL.
+	///
+	/// When a publisher responds with [message::Redirect], [Self::subscribe] and [Self::subscribe_retry]
+	/// transparently subscribe to the track at the new origin instead, so any reader is unaffected.
+	/// Each origin is visited at most once, and the track is closed with [ServeError::Redirect] after `max_hops` redirects.
+	/// Otherwise, a redirect closes the track with [ServeError::Redirect] so the application can handle it.
+	pub fn follow_redirects<F, Fut>(&mut self, max_hops: usize, connect: F)
+	where
+		F: Fn(String) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<transport::Session, SessionError>> + Send + 'static,
+	{
+		*self.redirect.lock().unwrap() = Some(Arc::new(Redirector::new(max_hops, connect)));
+	}
+
+	/// Cap the size of each chunk read from a stream, bounding allocations for large objects.
+	///
+	/// Objects are still delivered in full, just split into more chunks.
+	pub fn set_max_chunk(&self, size: usize) {
+		self.max_chunk.store(size, atomic::Ordering::Relaxed);
+	}
+
+	/// Stop the subscriber, shared by every clone, for a deterministic teardown.
+	///
+	/// Every subscription is closed with [ServeError::Cancel] and any stream still being received is stopped.
+	/// Returns once all stream tasks have finished; the session must still be running for them to do so.
+	/// Subscriptions made afterwards are cancelled immediately.
+	///
+	/// Announcements already queued are still returned by [Self::announced], which then returns None.
+	/// Any announcement arriving afterwards is rejected, rather than queued and never seen.
+	pub async fn shutdown(&mut self) {
+		self.shutdown.send_replace(true);
+		self.announced_queue.close();
+
+		let subscribes: Vec<_> = self.subscribes.lock().unwrap().drain().collect();
+		for (id, subscribe) in subscribes {
+			subscribe.error(ServeError::Cancel).ok();
+			self.send_message(message::Unsubscribe { id });
+		}
+
+		let announced: Vec<_> = self.announced.lock().unwrap().drain().collect();
+		for (namespace, announce) in announced {
+			announce.recv_unannounce().ok();
+			self.notify_announcements(AnnouncedEvent::Unannounced(AnnounceInfo { namespace }));
+		}
+
+		for (_, mut queue) in self.announcements.lock().unwrap().drain(..) {
+			queue.close();
+		}
+
+		self.shutdown.closed().await;
+	}
+
+	// Move to a new session after a reconnect, resubscribing to any active tracks.
+	pub(super) fn resume(
+		&mut self,
+		outgoing: Queue<Message>,
+		transport: transport::Session,
+		drain: Drain,
+		access: Access,
+	) {
+		*self.outgoing.lock().unwrap() = outgoing.clone();
+		*self.transport.lock().unwrap() = Some(transport);
+		*self.drain.lock().unwrap() = drain;
+		*self.access.lock().unwrap() = access;
+
+		// Announcements are scoped to the session, so keep them until the peer announces them again.
+		// Any still waiting from the previous reconnect weren't, so they're withdrawn now.
+		let resume = self.resumes.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+		self.withdraw_resumed(resume - 1);
+
+		for announce in self.announced.lock().unwrap().values_mut() {
+			announce.resumed = Some(resume);
+		}
+
+		let mut outgoing = outgoing;
+		for prefix in self.announce_prefixes.lock().unwrap().keys() {
+			let msg = message::SubscribeNamespace {
+				namespace_prefix: prefix.clone(),
+				params: Default::default(),
+			};
+			outgoing.push(message::Subscriber::from(msg).into()).ok();
+		}
+
+		self.subscribes
+			.lock()
+			.unwrap()
+			.retain(|_, subscribe| match subscribe.resume() {
+				Some(msg) => {
+					log::debug!("resubscribing: {:?}", msg);
+					outgoing.push(message::Subscriber::from(msg).into()).is_ok()
+				}
+				None => false,
+			});
+	}
+
+	// Withdraw the announcements carried over by the last reconnect if the peer doesn't announce them again in time.
+	pub(super) fn expire_resumed(&self, wait: time::Duration) {
+		let resume = self.resumes.load(atomic::Ordering::Relaxed);
+
+		let mut this = self.clone();
+		tokio::spawn(async move {
+			tokio::time::sleep(wait).await;
+			this.withdraw_resumed(resume);
+		});
+	}
+
+	// Report any announcements carried over by a reconnect, up to and including this one, as withdrawn.
+	fn withdraw_resumed(&mut self, resume: u64) {
+		let mut announced = self.announced.lock().unwrap();
+
+		let withdrawn: Vec<_> = announced
+			.iter()
+			.filter(|(_, announce)| announce.resumed.is_some_and(|resumed| resumed <= resume))
+			.map(|(namespace, _)| namespace.clone())
+			.collect();
+
+		for namespace in withdrawn {
+			if let Some(announce) = announced.remove(&namespace) {
+				announce.recv_unannounce().ok();
+			}
+
+			let info = AnnounceInfo { namespace };
+			self.notify_announcements(AnnouncedEvent::Unannounced(info.clone()));
+			self.announced_queue.push(AnnouncedEvent::Unannounced(info)).ok();
 		}
 	}
 
-	pub async fn accept(session: web_transport::Session) -> Result<(Session, Self), SessionError> {
+	pub async fn accept(session: impl Into<transport::Session>) -> Result<(Session, Self), SessionError> {
 		let (session, _, subscriber) = Session::accept_role(session, setup::Role::Subscriber).await?;
 		Ok((session, subscriber.unwrap()))
 	}
 
-	pub async fn connect(session: web_transport::Session) -> Result<(Session, Self), SessionError> {
+	pub async fn connect(session: impl Into<transport::Session>) -> Result<(Session, Self), SessionError> {
 		let (session, _, subscriber) = Session::connect_role(session, setup::Role::Subscriber).await?;
 		Ok((session, subscriber.unwrap()))
 	}
 
+	/// Wait for the next announced namespace, skipping any withdrawals.
 	pub async fn announced(&mut self) -> Option<Announced> {
+		loop {
+			match self.announced_queue.pop().await? {
+				AnnouncedEvent::Announced(announced) => return Some(announced),
+				AnnouncedEvent::Unannounced(info) => {
+					if self.withdrawn_wanted.load(atomic::Ordering::Relaxed) {
+						self.withdrawn.lock().unwrap().push_back(info);
+					}
+				}
+			}
+		}
+	}
+
+	/// Wait for the next announced namespace, failing with [ServeError::Timeout] if none arrives in time.
+	///
+	/// Returns None if the session is closed, just like [Self::announced].
+	pub async fn announced_timeout(&mut self, timeout: time::Duration) -> Result<Option<Announced>, ServeError> {
+		tokio::time::timeout(timeout, self.announced())
+			.await
+			.map_err(|_| ServeError::Timeout)
+	}
+
+	/// Returns the number of announce events that have not been consumed yet.
+	///
+	/// A relay can use this to reject new announcements when the backlog is too deep.
+	pub fn announced_backlog(&self) -> usize {
+		self.announced_queue.len()
+	}
+
+	/// Wait until every announcement queued before [Self::shutdown] has been consumed.
+	pub async fn announced_drained(&self) {
+		self.announced_queue.drained().await
+	}
+
+	/// Wait for the next announced or withdrawn namespace.
+	///
+	/// Each announced namespace is returned by either this or [Self::announced], whichever is called first.
+	/// Once this has been called, any withdrawal skipped by [Self::announced] is returned here instead.
+	/// Use [Self::announcements] to observe every announcement from several places.
+	pub async fn announced_event(&mut self) -> Option<AnnouncedEvent> {
+		self.withdrawn_wanted.store(true, atomic::Ordering::Relaxed);
+
+		// Any skipped withdrawals happened before everything still queued.
+		if let Some(info) = self.withdrawn.lock().unwrap().pop_front() {
+			return Some(AnnouncedEvent::Unannounced(info));
+		}
+
 		self.announced_queue.pop().await
 	}
 
+	/// Observe the namespaces announced by the peer that start with the prefix, independently of [Self::announced] and any other handle.
+	///
+	/// The handle starts with every matching namespace currently announced, followed by each one announced or withdrawn.
+	/// Use an empty prefix to observe every namespace, or [Self::announced_prefix] to also filter them at the peer.
+	pub fn announcements(&self, prefix: &str) -> Announcements {
+		Announcements::new(self.announcements_queue(prefix), None)
+	}
+
+	/// Observe the namespaces announced by the peer that start with the prefix, such as "room/123/".
+	///
+	/// The peer is asked to only announce matching namespaces, so others don't traverse the wire.
+	/// This applies to the whole session, so [Self::announced] also stops receiving other namespaces.
+	/// The filter is removed once every handle for the prefix is dropped, although namespaces already announced remain.
+	pub fn announced_prefix(&mut self, prefix: &str) -> Announcements {
+		let count = {
+			let mut prefixes = self.announce_prefixes.lock().unwrap();
+			let count = prefixes.entry(prefix.to_string()).or_default();
+			*count += 1;
+			*count
+		};
+
+		if count == 1 {
+			self.send_message(message::SubscribeNamespace {
+				namespace_prefix: prefix.to_string(),
+				params: Default::default(),
+			});
+		}
+
+		let queue = self.announcements_queue(prefix);
+		Announcements::new(queue, Some((self.clone(), prefix.to_string())))
+	}
+
+	pub(super) fn drop_announce_prefix(&mut self, prefix: &str) {
+		let mut prefixes = self.announce_prefixes.lock().unwrap();
+		let Some(count) = prefixes.get_mut(prefix) else {
+			return;
+		};
+
+		*count -= 1;
+		if *count == 0 {
+			prefixes.remove(prefix);
+			drop(prefixes);
+
+			self.send_message(message::UnsubscribeNamespace {
+				namespace_prefix: prefix.to_string(),
+			});
+		}
+	}
+
+	fn announcements_queue(&self, prefix: &str) -> Queue<AnnouncedEvent<AnnounceInfo>> {
+		// Hold the lock so no event is missed or repeated between the snapshot and registering.
+		let announced = self.announced.lock().unwrap();
+
+		let (mut send, recv) = Queue::default().split();
+		for namespace in announced.keys().filter(|namespace| namespace.starts_with(prefix)) {
+			let info = AnnounceInfo {
+				namespace: namespace.clone(),
+			};
+			send.push(AnnouncedEvent::Announced(info)).ok();
+		}
+
+		let mut announcements = self.announcements.lock().unwrap();
+		if *self.shutdown.borrow() {
+			send.close();
+		} else {
+			announcements.push((prefix.to_string(), send));
+		}
+
+		recv
+	}
+
+	// Send an event to every Announcements handle, forgetting any that were dropped.
+	// NOTE: Called while holding the announced lock, so events are in the same order as the map changes.
+	fn notify_announcements(&self, event: AnnouncedEvent<AnnounceInfo>) {
+		let namespace = match &event {
+			AnnouncedEvent::Announced(info) | AnnouncedEvent::Unannounced(info) => info.namespace.clone(),
+		};
+
+		self.announcements.lock().unwrap().retain_mut(|(prefix, queue)| {
+			if !namespace.starts_with(prefix.as_str()) {
+				return true;
+			}
+
+			// Skip a withdrawal if the handle hasn't seen the namespace yet, so the queue can't grow without bound.
+			if matches!(event, AnnouncedEvent::Unannounced(_))
+				&& queue
+					.remove(|queued| matches!(queued, AnnouncedEvent::Announced(info) if info.namespace == namespace))
+					.is_some()
+			{
+				return true;
+			}
+
+			queue.push(event.clone()).is_ok()
+		});
+	}
+
 	pub async fn subscribe(&mut self, track: serve::TrackWriter) -> Result<(), ServeError> {
+		let mut subscribe = self.subscribe_handle(track);
+		let res = subscribe.closed().await;
+
+		self.redirected(&mut subscribe, res).await
+	}
+
+	/// Subscribe to a track, returning a handle that can be used to modify the subscription.
+	/// The subscription is cancelled when the handle is dropped.
+	pub fn subscribe_handle(&mut self, track: serve::TrackWriter) -> Subscribe {
+		self.subscribe_inner(track, SubscribeStart::Latest, None, false)
+	}
+
+	/// Subscribe to a track starting from an older group, returning a handle like [Self::subscribe_handle].
+	///
+	/// The publisher clamps the start to the groups it still has cached; see [Subscribe::start].
+	pub fn subscribe_from(&mut self, track: serve::TrackWriter, start: SubscribeStart) -> Subscribe {
+		self.subscribe_inner(track, start, None, false)
+	}
+
+	/// Subscribe to a track and automatically unsubscribe after receiving `max_groups` groups.
+	///
+	/// The track is closed cleanly once the final group has been received, rather than with an error.
+	/// This only applies when the publisher uses a stream per group.
+	/// Zero groups closes the track immediately with [ServeError::Done], without subscribing.
+	pub fn subscribe_groups(&mut self, track: serve::TrackWriter, start: SubscribeStart, max_groups: u64) -> Subscribe {
+		self.subscribe_inner(track, start, Some(max_groups), false)
+	}
+
+	/// Subscribe to the groups from `start` to `end` inclusive, closing the track cleanly like [Self::subscribe_groups].
+	///
+	/// The publisher stops after serving `end`, and clamps `start` to the groups it still has cached.
+	/// The subscription is closed with [ServeError::OutOfOrder] if `end` is before `start`.
+	pub fn subscribe_range(&mut self, track: serve::TrackWriter, start: u64, end: u64) -> Subscribe {
+		match end.checked_sub(start) {
+			Some(count) => self.subscribe_inner(track, SubscribeStart::Group(start), Some(count + 1), false),
+			None => self.reject(track, ServeError::OutOfOrder),
+		}
+	}
+
+	/// Subscribe to a track, retrying with exponential backoff until the timeout on retryable errors.
+	///
+	/// This smooths over the race where a subscriber joins before the publisher is routing the track.
+	/// See [ServeError::is_retryable] for the errors that are retried.
+	pub async fn subscribe_retry(
+		&mut self,
+		mut track: serve::TrackWriter,
+		timeout: time::Duration,
+	) -> Result<(), ServeError> {
+		const BACKOFF_MIN: time::Duration = time::Duration::from_millis(10);
+		const BACKOFF_MAX: time::Duration = time::Duration::from_secs(1);
+
+		let deadline = tokio::time::Instant::now() + timeout;
+		let mut backoff = BACKOFF_MIN;
+
+		loop {
+			let mut subscribe = self.subscribe_inner(track, SubscribeStart::Latest, None, true);
+
+			let err = match subscribe.closed().await {
+				Ok(()) => return Ok(()),
+				Err(err) => err,
+			};
+
+			// We only get the track back if the error was retryable, or we're following a redirect.
+			track = match subscribe.take_retry() {
+				Some(track) => track,
+				None => return Err(err),
+			};
+
+			if let ServeError::Redirect(url) = err {
+				return self.follow_redirect(track, url).await;
+			}
+
+			if tokio::time::Instant::now() + backoff > deadline {
+				track.close(err.clone())?;
+				return Err(err);
+			}
+
+			log::debug!("retrying subscribe: {:?}, error: {}", subscribe.info, err);
+
+			tokio::time::sleep(backoff).await;
+			backoff = cmp::min(backoff * 2, BACKOFF_MAX);
+		}
+	}
+
+	/// Fetch a single cached group without subscribing to the track.
+	///
+	/// The whole group is downloaded before returning.
+	/// If the group isn't cached, for example because it expired, this fails with [ServeError::NotFound].
+	pub async fn fetch(
+		&mut self,
+		namespace: &str,
+		name: &str,
+		group_id: u64,
+	) -> Result<serve::GroupReader, SessionError> {
+		let transport = self.transport.lock().unwrap().clone().ok_or(ServeError::Cancel)?;
+		let (send, recv) = transport.open_bi().await?;
+
+		let header = data::FetchHeader {
+			namespace: namespace.to_string(),
+			name: name.to_string(),
+			group_id,
+		};
+
+		log::trace!("sending fetch: {:?}", header);
+
+		// NOTE: Dropping the writer finishes our side of the stream.
+		Writer::new(send).encode(&header).await?;
+
+		let max_chunk = self.max_chunk.load(atomic::Ordering::Relaxed);
+		let mut reader = Reader::new(recv).with_max_chunk(max_chunk);
+
+		let response: data::FetchResponse = reader.decode().await?;
+		if response.code != 0 {
+			return Err(ServeError::from_code(response.code).into());
+		}
+
+		let (writer, group) = serve::GroupInfo {
+			track: Arc::new(serve::Track::new(namespace.to_string(), name.to_string())),
+			group_id,
+			priority: response.send_order,
+			expires: None,
+		}
+		.produce();
+
+		// Fetches aren't tied to a subscription, so the stats are discarded.
+		let stats = Mutex::new(SubscribeStats::default());
+		Self::recv_group(writer, &mut reader, &stats).await?;
+
+		Ok(group)
+	}
+
+	// Follow the redirect if the subscription was redirected and we got the track back.
+	async fn redirected(&self, subscribe: &mut Subscribe, res: Result<(), ServeError>) -> Result<(), ServeError> {
+		if let Err(ServeError::Redirect(url)) = &res {
+			if let Some(track) = subscribe.take_retry() {
+				return self.follow_redirect(track, url.clone()).await;
+			}
+		}
+
+		res
+	}
+
+	async fn follow_redirect(&self, track: serve::TrackWriter, url: String) -> Result<(), ServeError> {
+		let redirect = self.redirect.lock().unwrap().clone();
+		match redirect {
+			Some(redirect) => redirect.follow(track, url).await,
+			None => {
+				let err = ServeError::Redirect(url);
+				track.close(err.clone())?;
+				Err(err)
+			}
+		}
+	}
+
+	pub(super) fn subscribe_inner(
+		&mut self,
+		track: serve::TrackWriter,
+		start: SubscribeStart,
+		max_groups: Option<u64>,
+		retry: bool,
+	) -> Subscribe {
+		if *self.shutdown.borrow() {
+			return self.reject(track, ServeError::Cancel);
+		}
+
+		// Otherwise the remaining count would saturate, delivering a group anyway.
+		if max_groups == Some(0) {
+			return self.reject(track, ServeError::Done);
+		}
+
 		let id = self.subscribe_next.fetch_add(1, atomic::Ordering::Relaxed);
 
-		let (send, recv) = Subscribe::new(self.clone(), id, track);
+		let (send, recv) = Subscribe::new(self.clone(), id, track, start, max_groups, retry);
+		self.send_message(recv.msg().clone());
 		self.subscribes.lock().unwrap().insert(id, recv);
 
-		send.closed().await
+		send
+	}
+
+	// Return a subscription that's already closed with the error, without sending SUBSCRIBE.
+	fn reject(&mut self, track: serve::TrackWriter, err: ServeError) -> Subscribe {
+		let id = self.subscribe_next.fetch_add(1, atomic::Ordering::Relaxed);
+
+		let (send, recv) = Subscribe::new(self.clone(), id, track, SubscribeStart::Latest, None, false);
+		recv.error(err).ok();
+
+		send
 	}
 
 	pub(super) fn send_message<M: Into<message::Subscriber>>(&mut self, msg: M) {
@@ -69,11 +585,12 @@ impl Subscriber {
 		match &msg {
 			message::Subscriber::AnnounceCancel(msg) => self.drop_announce(&msg.namespace),
 			message::Subscriber::AnnounceError(msg) => self.drop_announce(&msg.namespace),
+			message::Subscriber::Unsubscribe(msg) => self.drop_subscribe(msg.id),
 			_ => {}
 		}
 
 		// TODO report dropped messages?
-		let _ = self.outgoing.push(msg.into());
+		let _ = self.outgoing.lock().unwrap().push(msg.into());
 	}
 
 	pub(super) fn recv_message(&mut self, msg: message::Publisher) -> Result<(), SessionError> {
@@ -84,6 +601,7 @@ impl Subscriber {
 			message::Publisher::SubscribeError(msg) => self.recv_subscribe_error(msg),
 			message::Publisher::SubscribeDone(msg) => self.recv_subscribe_done(msg),
 			message::Publisher::TrackStatus(msg) => self.recv_track_status(msg),
+			message::Publisher::Redirect(msg) => self.recv_redirect(msg),
 		};
 
 		if let Err(SessionError::Serve(err)) = res {
@@ -94,28 +612,104 @@ impl Subscriber {
 		res
 	}
 
+	/// Block until the current session starts draining, after either side sent GOAWAY.
+	///
+	/// Existing subscriptions are still delivered, but the application should migrate to another session.
+	pub async fn draining(&self) {
+		let drain = self.drain.lock().unwrap().clone();
+		drain.draining().await
+	}
+
 	fn recv_announce(&mut self, msg: &message::Announce) -> Result<(), SessionError> {
-		let mut announces = self.announced.lock().unwrap();
+		// We sent GOAWAY, so refuse any new announcements.
+		if self.drain.lock().unwrap().is_closing() {
+			let err = ServeError::GoingAway;
+			self.send_message(message::AnnounceError {
+				namespace: msg.namespace.clone(),
+				code: err.code(),
+				reason: err.to_string(),
+			});
+			return Ok(());
+		}
 
-		let entry = match announces.entry(msg.namespace.clone()) {
-			hash_map::Entry::Occupied(_) => return Err(SessionError::Duplicate),
-			hash_map::Entry::Vacant(entry) => entry,
+		let request = AuthRequest::Announce {
+			namespace: &msg.namespace,
 		};
+		let res = self.access.lock().unwrap().check(request);
+		if let Err(err) = res {
+			self.send_message(message::AnnounceError {
+				namespace: msg.namespace.clone(),
+				code: err.code(),
+				reason: err.to_string(),
+			});
+			return Ok(());
+		}
+
+		let max = self.access.lock().unwrap().limits().config().max_announces;
+
+		let mut announces = self.announced.lock().unwrap();
+		if let Some(announce) = announces.get_mut(&msg.namespace) {
+			// The peer announced it again after a reconnect, so the application keeps using the same handle.
+			if announce.resumed.take().is_none() {
+				return Err(SessionError::Duplicate);
+			}
+
+			if announce.is_ok() {
+				drop(announces);
+				self.send_message(message::AnnounceOk {
+					namespace: msg.namespace.clone(),
+				});
+			}
+
+			return Ok(());
+		}
+
+		if max.is_some_and(|max| announces.len() >= max) {
+			// Sending the error removes the announcement from the map, so release the lock first.
+			drop(announces);
+
+			let err = ServeError::Quota;
+			self.send_message(message::AnnounceError {
+				namespace: msg.namespace.clone(),
+				code: err.code(),
+				reason: err.to_string(),
+			});
+			return Ok(());
+		}
 
 		let (announced, recv) = Announced::new(self.clone(), msg.namespace.to_string());
-		if let Err(announced) = self.announced_queue.push(announced) {
+		if let Err(AnnouncedEvent::Announced(announced)) =
+			self.announced_queue.push(AnnouncedEvent::Announced(announced))
+		{
+			// Rejecting the announcement removes it from the map, so release the lock first.
+			drop(announces);
 			announced.close(ServeError::Cancel)?;
 			return Ok(());
 		}
 
-		entry.insert(recv);
+		announces.insert(msg.namespace.clone(), recv);
+
+		let info = AnnounceInfo {
+			namespace: msg.namespace.clone(),
+		};
+		self.notify_announcements(AnnouncedEvent::Announced(info));
 
 		Ok(())
 	}
 
 	fn recv_unannounce(&mut self, msg: &message::Unannounce) -> Result<(), SessionError> {
-		if let Some(announce) = self.announced.lock().unwrap().remove(&msg.namespace) {
+		let mut announced = self.announced.lock().unwrap();
+
+		if let Some(announce) = announced.remove(&msg.namespace) {
 			announce.recv_unannounce()?;
+
+			let info = AnnounceInfo {
+				namespace: msg.namespace.clone(),
+			};
+			self.notify_announcements(AnnouncedEvent::Unannounced(info.clone()));
+
+			// NOTE: The application may not be listening for withdrawals, so ignore any errors.
+			self.announced_queue.push(AnnouncedEvent::Unannounced(info)).ok();
 		}
 
 		Ok(())
@@ -123,7 +717,7 @@ impl Subscriber {
 
 	fn recv_subscribe_ok(&mut self, msg: &message::SubscribeOk) -> Result<(), SessionError> {
 		if let Some(subscribe) = self.subscribes.lock().unwrap().get_mut(&msg.id) {
-			subscribe.ok()?;
+			subscribe.ok(msg.start)?;
 		}
 
 		Ok(())
@@ -131,20 +725,61 @@ impl Subscriber {
 
 	fn recv_subscribe_error(&mut self, msg: &message::SubscribeError) -> Result<(), SessionError> {
 		if let Some(subscribe) = self.subscribes.lock().unwrap().remove(&msg.id) {
-			subscribe.error(ServeError::Closed(msg.code))?;
+			subscribe.error(ServeError::from_code(msg.code))?;
 		}
 
 		Ok(())
 	}
 
-	fn recv_subscribe_done(&mut self, msg: &message::SubscribeDone) -> Result<(), SessionError> {
+	fn recv_redirect(&mut self, msg: &message::Redirect) -> Result<(), SessionError> {
 		if let Some(subscribe) = self.subscribes.lock().unwrap().remove(&msg.id) {
-			subscribe.error(ServeError::Closed(msg.code))?;
+			let follow = self.redirect.lock().unwrap().is_some();
+			subscribe.redirect(msg.url.clone(), follow)?;
 		}
 
 		Ok(())
 	}
 
+	fn recv_subscribe_done(&mut self, msg: &message::SubscribeDone) -> Result<(), SessionError> {
+		let err = ServeError::from_code(msg.code);
+		let mut subscribes = self.subscribes.lock().unwrap();
+
+		// Keep receiving until the final group arrives, closing the track once it does.
+		if let Some(subscribe) = subscribes.get_mut(&msg.id) {
+			if subscribe.finish(msg.last, &err) {
+				self.expire_finish(msg.id, err);
+				return Ok(());
+			}
+		}
+
+		if let Some(subscribe) = subscribes.remove(&msg.id) {
+			subscribe.error(err)?;
+		}
+
+		Ok(())
+	}
+
+	// Close the subscription anyway if the final group hasn't arrived in time.
+	fn expire_finish(&self, id: u64, err: ServeError) {
+		let mut this = self.clone();
+		tokio::spawn(async move {
+			tokio::time::sleep(Self::FINISH_TIMEOUT).await;
+			this.expire_finished(id, err);
+		});
+	}
+
+	fn expire_finished(&mut self, id: u64, err: ServeError) {
+		let mut subscribes = self.subscribes.lock().unwrap();
+		if !subscribes.get(&id).is_some_and(|subscribe| subscribe.finishing()) {
+			return;
+		}
+
+		if let Some(subscribe) = subscribes.remove(&id) {
+			log::debug!("final group never arrived: id={}", id);
+			subscribe.error(err).ok();
+		}
+	}
+
 	fn recv_track_status(&mut self, _msg: &message::TrackStatus) -> Result<(), SessionError> {
 		// TODO: Expose this somehow?
 		// TODO: Also add a way to sent a Track Status Request in the first place
@@ -152,17 +787,108 @@ impl Subscriber {
 		Ok(())
 	}
 
+	fn drop_subscribe(&mut self, id: u64) {
+		// Close the track now, rather than waiting for the publisher to acknowledge with SUBSCRIBE_DONE.
+		let subscribe = self.subscribes.lock().unwrap().remove(&id);
+		if let Some(subscribe) = subscribe {
+			subscribe.error(ServeError::Cancel).ok();
+		}
+	}
+
 	fn drop_announce(&mut self, namespace: &str) {
-		self.announced.lock().unwrap().remove(namespace);
+		let mut announced = self.announced.lock().unwrap();
+
+		if announced.remove(namespace).is_some() {
+			let info = AnnounceInfo {
+				namespace: namespace.to_string(),
+			};
+			self.notify_announcements(AnnouncedEvent::Unannounced(info));
+		}
+	}
+
+	// Returns the progress of each active subscription, for the session's statistics.
+	pub(super) fn subscription_stats(&self) -> Vec<SubscriptionStats> {
+		let subscribes = self.subscribes.lock().unwrap();
+		subscribes
+			.iter()
+			.map(|(id, subscribe)| SubscriptionStats {
+				id: *id,
+				namespace: subscribe.msg().track_namespace.clone(),
+				name: subscribe.msg().track_name.clone(),
+				stats: SubscribeStats {
+					active: true,
+					..subscribe.stats().lock().unwrap().clone()
+				},
+				bitrate: None,
+			})
+			.collect()
 	}
 
-	pub(super) async fn recv_stream(mut self, stream: web_transport::RecvStream) -> Result<(), SessionError> {
-		let mut reader = Reader::new(stream);
-		let header: data::Header = reader.decode().await?;
+	pub(super) async fn recv_stream(mut self, stream: transport::RecvStream) -> Result<(), SessionError> {
+		let max_chunk = self.max_chunk.load(atomic::Ordering::Relaxed);
+		let (limits, traffic) = {
+			let access = self.access.lock().unwrap();
+			(access.limits().clone(), access.observer().traffic().clone())
+		};
+
+		let mut reader = Reader::new(stream)
+			.with_max_chunk(max_chunk)
+			.with_limits(limits)
+			.with_traffic(traffic);
+
+		// Held until we return, so shutdown can wait for this stream.
+		let mut shutdown = self.shutdown.subscribe();
+		if *shutdown.borrow_and_update() {
+			reader.stop(&ServeError::Cancel);
+			return Err(ServeError::Cancel.into());
+		}
+
+		let res = tokio::select! {
+			res = self.recv_stream_header(&mut reader) => res,
+			Ok(_) = shutdown.wait_for(|shutdown| *shutdown) => Err(ServeError::Cancel.into()),
+		};
+
+		match &res {
+			Err(SessionError::Serve(ServeError::Cancel)) => reader.stop(&ServeError::Cancel),
+			// Abandon the rest of a stream we refused to buffer, such as an oversized datagram.
+			Err(SessionError::WrongSize) => reader.stop(&SessionError::WrongSize),
+			_ => {}
+		}
+
+		res
+	}
+
+	async fn recv_stream_header(&mut self, reader: &mut Reader) -> Result<(), SessionError> {
+		let header: data::Header = match reader.decode().await {
+			Ok(header) => header,
+			// Unlike control messages, each stream is self-contained so an unknown type can be skipped.
+			Err(SessionError::Decode(DecodeError::InvalidMessage(typ))) => {
+				log::warn!("ignoring stream with unknown type: {}", typ);
+				return Ok(());
+			}
+			Err(err) => return Err(err),
+		};
 
 		let id = header.subscribe_id();
 
+		// Reported as closed once we return.
+		let observed = self.access.lock().unwrap().observer().clone();
+		let _observed = observed.stream(StreamDirection::Recv, &header);
+
 		let res = self.recv_stream_inner(reader, header).await;
+
+		// A resumed subscription may receive a group we already have, so ignore it.
+		if let Err(SessionError::Serve(ServeError::Duplicate)) = &res {
+			log::debug!("ignoring duplicate stream: id={}", id);
+			return Ok(());
+		}
+
+		// A group that arrived too late to be cached doesn't affect the rest of the subscription.
+		if let Err(SessionError::Serve(ServeError::OutOfOrder)) = &res {
+			log::debug!("ignoring late stream: id={}", id);
+			return Ok(());
+		}
+
 		if let Err(SessionError::Serve(err)) = &res {
 			// The writer is closed, so we should teriminate.
 			// TODO it would be nice to do this immediately when the Writer is closed.
@@ -174,7 +900,7 @@ impl Subscriber {
 		res
 	}
 
-	async fn recv_stream_inner(&mut self, reader: Reader, header: data::Header) -> Result<(), SessionError> {
+	async fn recv_stream_inner(&mut self, reader: &mut Reader, header: data::Header) -> Result<(), SessionError> {
 		let id = header.subscribe_id();
 
 		// This is super silly, but I couldn't figure out a way to avoid the mutex guard across awaits.
@@ -182,29 +908,112 @@ impl Subscriber {
 			Track(serve::StreamWriter),
 			Group(serve::GroupWriter),
 			Object(serve::ObjectWriter),
+			Datagram(data::ObjectHeader),
 		}
 
-		let writer = {
+		let group_id = match &header {
+			data::Header::Group(group) => Some(group.group_id),
+			_ => None,
+		};
+
+		let (writer, stats, sequencer) = {
 			let mut subscribes = self.subscribes.lock().unwrap();
 			let subscribe = subscribes.get_mut(&id).ok_or(ServeError::NotFound)?;
 
-			match header {
+			let writer = match header {
 				data::Header::Track(track) => Writer::Track(subscribe.track(track)?),
 				data::Header::Group(group) => Writer::Group(subscribe.group(group)?),
+				// A datagram too large to send as one is sent as an object stream instead.
+				data::Header::Object(object) if subscribe.is_datagrams() => Writer::Datagram(object),
 				data::Header::Object(object) => Writer::Object(subscribe.object(object)?),
+			};
+
+			reader.set_metrics(subscribe.metrics());
+			let res = (writer, subscribe.stats(), subscribe.sequencer());
+
+			// We've received enough groups, so stop the subscription once this one is delivered.
+			if subscribe.completed() {
+				let finishing = subscribe.finishing();
+				subscribes.remove(&id);
+				drop(subscribes);
+
+				if !finishing {
+					self.send_message(message::Unsubscribe { id });
+				}
 			}
+
+			res
 		};
 
-		match writer {
-			Writer::Track(track) => Self::recv_track(track, reader).await?,
-			Writer::Group(group) => Self::recv_group(group, reader).await?,
-			Writer::Object(object) => Self::recv_object(object, reader).await?,
+		let res = match writer {
+			Writer::Track(track) => Self::recv_track(track, reader, &stats).await,
+			Writer::Group(group) => Self::recv_group(group, reader, &stats).await,
+			Writer::Object(object) => Self::recv_object(object, reader, &stats).await,
+			Writer::Datagram(header) => self.recv_datagram_stream(header, reader, &stats).await,
 		};
 
-		Ok(())
+		if res.is_err() {
+			stats.lock().unwrap().drops += 1;
+		}
+
+		if let Some(group_id) = group_id {
+			let events = {
+				let mut sequencer = sequencer.lock().unwrap();
+				match &res {
+					Ok(()) => sequencer.delivered(group_id),
+					Err(err) => match err.reset() {
+						Some(reset) => sequencer.reset(group_id, reset),
+						None => sequencer.dropped(group_id),
+					},
+				}
+
+				sequencer.take()
+			};
+
+			self.recv_dropped(id, events);
+		}
+
+		res
 	}
 
-	async fn recv_track(mut track: serve::StreamWriter, mut reader: Reader) -> Result<(), SessionError> {
+	// Notify the track's readers about groups that will never arrive, merging consecutive groups with the same error.
+	fn recv_dropped(&mut self, id: u64, events: Vec<GroupEvent>) {
+		let mut ranges: Vec<serve::GroupsDropped> = Vec::new();
+
+		for event in events {
+			let (group_id, error) = match event {
+				GroupEvent::Delivered(_) => continue,
+				GroupEvent::Reset(group_id, err) => (group_id, Some(err)),
+				GroupEvent::Dropped(group_id) => (group_id, None),
+			};
+
+			match ranges.last_mut() {
+				Some(last) if last.start + last.count == group_id && last.error == error => last.count += 1,
+				_ => ranges.push(serve::GroupsDropped {
+					start: group_id,
+					count: 1,
+					error,
+				}),
+			}
+		}
+
+		if ranges.is_empty() {
+			return;
+		}
+
+		let mut subscribes = self.subscribes.lock().unwrap();
+		if let Some(subscribe) = subscribes.get_mut(&id) {
+			for dropped in ranges {
+				subscribe.dropped(dropped);
+			}
+		}
+	}
+
+	async fn recv_track(
+		mut track: serve::StreamWriter,
+		reader: &mut Reader,
+		stats: &Mutex<SubscribeStats>,
+	) -> Result<(), SessionError> {
 		log::trace!("received track: {:?}", track.info);
 
 		let mut prev: Option<serve::StreamGroupWriter> = None;
@@ -214,10 +1023,15 @@ impl Subscriber {
 
 			let mut group = match prev {
 				Some(group) if group.group_id == chunk.group_id => group,
-				_ => track.create(chunk.group_id)?,
+				_ => {
+					let group = track.create(chunk.group_id)?;
+					stats.lock().unwrap().group(chunk.group_id);
+					group
+				}
 			};
 
 			let mut object = group.create(chunk.size)?;
+			stats.lock().unwrap().objects += 1;
 
 			let mut remain = chunk.size;
 			while remain > 0 {
@@ -225,6 +1039,7 @@ impl Subscriber {
 
 				log::trace!("received track payload: {:?}", chunk.len());
 				remain -= chunk.len();
+				stats.lock().unwrap().bytes += chunk.len() as u64;
 				object.write(chunk)?;
 			}
 
@@ -234,7 +1049,26 @@ impl Subscriber {
 		Ok(())
 	}
 
-	async fn recv_group(mut group: serve::GroupWriter, mut reader: Reader) -> Result<(), SessionError> {
+	async fn recv_group(
+		mut group: serve::GroupWriter,
+		reader: &mut Reader,
+		stats: &Mutex<SubscribeStats>,
+	) -> Result<(), SessionError> {
+		let res = Self::recv_group_inner(&mut group, reader, stats).await;
+
+		// Tell readers why the group is incomplete if the publisher reset the stream.
+		if let Some(err) = res.as_ref().err().and_then(|err| err.reset()) {
+			group.close(err).ok();
+		}
+
+		res
+	}
+
+	async fn recv_group_inner(
+		group: &mut serve::GroupWriter,
+		reader: &mut Reader,
+		stats: &Mutex<SubscribeStats>,
+	) -> Result<(), SessionError> {
 		log::trace!("received group: {:?}", group.info);
 
 		while !reader.done().await? {
@@ -242,12 +1076,15 @@ impl Subscriber {
 
 			log::trace!("received group object: {:?}", object);
 			let mut remain = object.size;
-			let mut object = group.create(object.size)?;
+			// NOTE: Objects may arrive out of order if the publisher relaxed ordering.
+			let mut object = group.create_at(object.object_id, object.size, object.meta)?;
+			stats.lock().unwrap().objects += 1;
 
 			while remain > 0 {
 				let data = reader.read_chunk(remain).await?.ok_or(SessionError::WrongSize)?;
 				log::trace!("received group payload: {:?}", data.len());
 				remain -= data.len();
+				stats.lock().unwrap().bytes += data.len() as u64;
 				object.write(data)?;
 			}
 		}
@@ -255,17 +1092,64 @@ impl Subscriber {
 		Ok(())
 	}
 
-	async fn recv_object(mut object: serve::ObjectWriter, mut reader: Reader) -> Result<(), SessionError> {
+	async fn recv_object(
+		mut object: serve::ObjectWriter,
+		reader: &mut Reader,
+		stats: &Mutex<SubscribeStats>,
+	) -> Result<(), SessionError> {
 		log::trace!("received object: {:?}", object.info);
+		stats.lock().unwrap().objects += 1;
 
 		while let Some(data) = reader.read_chunk(usize::MAX).await? {
 			log::trace!("received object payload: {:?}", data.len());
+			stats.lock().unwrap().bytes += data.len() as u64;
 			object.write(data)?;
 		}
 
 		Ok(())
 	}
 
+	// Read a datagram sent as an object stream, delivering it once it's complete.
+	async fn recv_datagram_stream(
+		&mut self,
+		header: data::ObjectHeader,
+		reader: &mut Reader,
+		stats: &Mutex<SubscribeStats>,
+	) -> Result<(), SessionError> {
+		log::trace!("received datagram stream: {:?}", header);
+
+		let mut payload = bytes::BytesMut::new();
+		while let Some(data) = reader.read_chunk(usize::MAX).await? {
+			if payload.len() + data.len() > Self::MAX_DATAGRAM_STREAM {
+				return Err(SessionError::WrongSize);
+			}
+
+			payload.extend_from_slice(&data);
+		}
+
+		{
+			let mut stats = stats.lock().unwrap();
+			stats.objects += 1;
+			stats.bytes += payload.len() as u64;
+		}
+
+		let datagram = data::Datagram {
+			subscribe_id: header.subscribe_id,
+			track_alias: header.track_alias,
+			group_id: header.group_id,
+			object_id: header.object_id,
+			send_order: header.send_order,
+			object_status: header.object_status,
+			payload: payload.freeze(),
+		};
+
+		if let Some(subscribe) = self.subscribes.lock().unwrap().get_mut(&datagram.subscribe_id) {
+			subscribe.datagram(datagram)?;
+		}
+
+		Ok(())
+	}
+
 	pub fn recv_datagram(&mut self, datagram: bytes::Bytes) -> Result<(), SessionError> {
 		let mut cursor = io::Cursor::new(datagram);
 		let datagram = data::Datagram::decode(&mut cursor)?;
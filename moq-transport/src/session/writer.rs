@@ -1,44 +1,107 @@
-use std::io;
+use crate::coding::Encode;
+use crate::error::ErrorCode;
+use crate::transport;
 
-use crate::coding::{Encode, EncodeError};
-
-use super::SessionError;
-use bytes::Buf;
+use super::{BufferPool, PooledBuffer, SessionError, Traffic};
+use bytes::Bytes;
 
 pub struct Writer {
-	stream: web_transport::SendStream,
-	buffer: bytes::BytesMut,
+	stream: transport::SendStream,
+	buffer: PooledBuffer,
+
+	// Small writes are buffered until this many bytes, or 0 to write immediately.
+	coalesce: usize,
+
+	// Counts the bytes sent for the session's statistics.
+	traffic: Option<Traffic>,
 }
 
 impl Writer {
-	pub fn new(stream: web_transport::SendStream) -> Self {
+	pub fn new(stream: transport::SendStream) -> Self {
 		Self {
 			stream,
 			buffer: Default::default(),
+			coalesce: 0,
+			traffic: None,
 		}
 	}
 
+	/// Take the encode buffer from the pool, returning it when the writer is dropped.
+	pub(super) fn with_pool(mut self, pool: &BufferPool) -> Self {
+		let mut buffer = pool.get();
+		buffer.extend_from_slice(&self.buffer);
+		self.buffer = buffer;
+		self
+	}
+
+	/// Buffer writes smaller than `size` until the buffer fills or [Self::flush] is called.
+	///
+	/// Any buffered data is lost if the writer is dropped without flushing.
+	pub fn with_coalesce(mut self, size: usize) -> Self {
+		self.coalesce = size;
+		self.buffer.reserve(size);
+		self
+	}
+
+	pub(super) fn with_traffic(mut self, traffic: Traffic) -> Self {
+		self.traffic = Some(traffic);
+		self
+	}
+
 	pub async fn encode<T: Encode>(&mut self, msg: &T) -> Result<(), SessionError> {
+		if self.coalesce > 0 {
+			msg.encode(&mut *self.buffer)?;
+			return self.flush_full().await;
+		}
+
 		self.buffer.clear();
-		msg.encode(&mut self.buffer)?;
+		msg.encode(&mut *self.buffer)?;
 
+		self.flush().await
+	}
+
+	/// Write a chunk without copying it, unless it's small enough to be coalesced.
+	pub async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), SessionError> {
+		if chunk.len() < self.coalesce {
+			self.buffer.extend_from_slice(&chunk);
+			return self.flush_full().await;
+		}
+
+		// Keep the data in order by sending anything buffered first.
+		self.flush().await?;
+		self.sent(chunk.len());
+		self.stream.write_chunk(chunk).await?;
+
+		Ok(())
+	}
+
+	/// Send any buffered data.
+	pub async fn flush(&mut self) -> Result<(), SessionError> {
 		while !self.buffer.is_empty() {
-			self.stream.write_buf(&mut self.buffer).await?;
+			let size = self.stream.write_buf(&mut *self.buffer).await?;
+			self.sent(size);
 		}
 
 		Ok(())
 	}
 
-	pub async fn write(&mut self, buf: &[u8]) -> Result<(), SessionError> {
-		let mut cursor = io::Cursor::new(buf);
+	fn sent(&self, size: usize) {
+		if let Some(traffic) = &self.traffic {
+			traffic.sent(size);
+		}
+	}
 
-		while cursor.has_remaining() {
-			let size = self.stream.write_buf(&mut cursor).await?;
-			if size == 0 {
-				return Err(EncodeError::More(cursor.remaining()).into());
-			}
+	// Send the buffer once it reaches the coalescing threshold.
+	async fn flush_full(&mut self) -> Result<(), SessionError> {
+		if self.buffer.len() >= self.coalesce {
+			self.flush().await?;
 		}
 
 		Ok(())
 	}
+
+	/// Abandon the stream, signalling the error to the peer.
+	pub fn reset<E: ErrorCode>(self, err: &E) {
+		self.stream.reset(err.reset_code());
+	}
 }
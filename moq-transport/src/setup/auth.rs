@@ -0,0 +1,24 @@
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+use super::Extension;
+
+/// A bearer token, such as a JWT, sent by the client to authenticate the session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthToken(pub String);
+
+impl Encode for AuthToken {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.0.encode(w)
+	}
+}
+
+impl Decode for AuthToken {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self(String::decode(r)?))
+	}
+}
+
+impl Extension for AuthToken {
+	// AUTHORIZATION_INFO
+	const ID: u64 = 2;
+}
@@ -4,7 +4,8 @@ use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params};
 /// Sent by the client to setup the session.
 // NOTE: This is not a message type, but rather the control stream header.
 // Proposal: https://github.com/moq-wg/moq-transport/issues/138
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Client {
 	/// The list of supported versions in preferred order.
 	pub versions: Versions,
@@ -0,0 +1,103 @@
+use std::io::Cursor;
+
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params};
+
+/// A typed setup parameter, sent in the [super::Client] or [super::Server] message.
+///
+/// Implement this for any application or vendor extension, then add it to [Extensions].
+pub trait Extension: Encode + Decode {
+	/// The parameter ID, which must not be 0 (ROLE) or 1 (PATH).
+	const ID: u64;
+}
+
+/// The setup parameters sent by one side of the session, accessed by [Extension] type.
+#[derive(Default, Debug, Clone)]
+pub struct Extensions {
+	params: Params,
+}
+
+impl Extensions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add an extension, replacing any previous value.
+	pub fn set<E: Extension>(&mut self, ext: E) -> Result<(), EncodeError> {
+		// The ROLE and PATH parameters are reserved.
+		if E::ID <= 1 {
+			return Err(EncodeError::InvalidValue);
+		}
+
+		self.params.set(E::ID, ext)
+	}
+
+	pub fn has<E: Extension>(&self) -> bool {
+		self.params.has(E::ID)
+	}
+
+	/// Returns the extension if it was sent, or an error if it couldn't be decoded.
+	pub fn get<E: Extension>(&self) -> Result<Option<E>, DecodeError> {
+		match self.params.0.get(&E::ID) {
+			Some(value) => Ok(Some(E::decode(&mut Cursor::new(value))?)),
+			None => Ok(None),
+		}
+	}
+}
+
+impl From<Params> for Extensions {
+	fn from(params: Params) -> Self {
+		Self { params }
+	}
+}
+
+impl From<Extensions> for Params {
+	fn from(extensions: Extensions) -> Self {
+		extensions.params
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::setup::{Client, Role, Version};
+	use bytes::BytesMut;
+
+	#[derive(Debug, PartialEq)]
+	struct Vendor(String);
+
+	impl Encode for Vendor {
+		fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+			self.0.encode(w)
+		}
+	}
+
+	impl Decode for Vendor {
+		fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+			Ok(Self(String::decode(r)?))
+		}
+	}
+
+	impl Extension for Vendor {
+		const ID: u64 = 0xff01;
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut extensions = Extensions::new();
+		extensions.set(Vendor("hello".to_string())).unwrap();
+
+		let client = Client {
+			versions: [Version::DRAFT_04].into(),
+			role: Role::Both,
+			params: extensions.into(),
+		};
+
+		let mut buf = BytesMut::new();
+		client.encode(&mut buf).unwrap();
+
+		let client = Client::decode(&mut buf).unwrap();
+		let extensions = Extensions::from(client.params);
+		assert!(extensions.has::<Vendor>());
+		assert_eq!(extensions.get::<Vendor>().unwrap(), Some(Vendor("hello".to_string())));
+	}
+}
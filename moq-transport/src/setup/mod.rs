@@ -2,14 +2,18 @@
 //!
 //! After establishing the WebTransport session, the client creates a bidirectional QUIC stream.
 //! The client sends the [Client] message and the server responds with the [Server] message.
-//! Both sides negotate the [Version] and [Role].
+//! Both sides negotate the [Version] and [Role], and may include any [Extensions].
 
+mod auth;
 mod client;
+mod extension;
 mod role;
 mod server;
 mod version;
 
+pub use auth::*;
 pub use client::*;
+pub use extension::*;
 pub use role::*;
 pub use server::*;
 pub use version::*;
@@ -2,6 +2,7 @@ use crate::coding::{Decode, DecodeError, Encode, EncodeError};
 
 /// Indicates the endpoint is a publisher, subscriber, or both.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Role {
 	Publisher,
 	Subscriber,
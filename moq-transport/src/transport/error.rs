@@ -0,0 +1,32 @@
+use crate::error::ErrorCode;
+
+/// An error returned by a [super::Transport] or its streams.
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum TransportError {
+	/// The peer closed the connection with a code and reason.
+	#[error("closed: code={code} reason={reason}")]
+	Closed { code: u32, reason: String },
+
+	/// The peer reset the stream we were reading.
+	#[error("reset: code={0}")]
+	Reset(u32),
+
+	/// The peer asked us to stop writing the stream.
+	#[error("stopped: code={0}")]
+	Stopped(u32),
+
+	/// The connection or stream failed for any other reason, such as a timeout or a local close.
+	#[error("{0}")]
+	Failed(String),
+}
+
+impl ErrorCode for TransportError {
+	fn code(&self) -> u64 {
+		match self {
+			Self::Closed { .. } => 503,
+			Self::Failed(_) => 503,
+			Self::Reset(_) => 500,
+			Self::Stopped(_) => 500,
+		}
+	}
+}
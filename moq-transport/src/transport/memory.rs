@@ -0,0 +1,878 @@
+use std::{
+	cmp,
+	collections::{BinaryHeap, HashMap, VecDeque},
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, FutureExt};
+use tokio::{
+	sync::{mpsc, watch, Notify},
+	time::Instant,
+};
+
+use super::{stream_id, RecvStream, RecvTransport, SendStream, SendTransport, Transport, TransportError};
+
+/// The network conditions for packets sent by one side of a [Memory] pair.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryConfig {
+	/// The one-way delay of each packet.
+	pub latency: Duration,
+
+	/// A random delay of up to this duration added to each packet.
+	pub jitter: Duration,
+
+	/// The probability that a packet is delayed by another latency, arriving after packets sent later.
+	///
+	/// Stream data is still delivered in order, so this only reorders different streams and datagrams.
+	pub reorder: f64,
+
+	/// The probability that a packet is lost, from 0 to 1.
+	///
+	/// Lost datagrams are gone, while lost stream data is retransmitted after three times the latency.
+	pub loss: f64,
+
+	/// The number of consecutive packets lost each time, simulating interference; zero is the same as one.
+	pub burst: u32,
+
+	/// The throughput in bytes per second, or unlimited if None.
+	pub bandwidth: Option<u64>,
+
+	/// Seeds the random number generator, so losses are reproducible.
+	pub seed: u64,
+}
+
+/// An in-process [Transport], connecting a pair of endpoints without sockets or certificates.
+///
+/// Streams are split into packets, which are sent in priority order at the configured bandwidth,
+/// then delayed and lost according to the [MemoryConfig].
+pub struct Memory {
+	shared: Arc<Shared>,
+	accept_uni: tokio::sync::Mutex<mpsc::UnboundedReceiver<RecvStream>>,
+	accept_bi: tokio::sync::Mutex<mpsc::UnboundedReceiver<(SendStream, RecvStream)>>,
+	datagrams: tokio::sync::Mutex<mpsc::UnboundedReceiver<Bytes>>,
+}
+
+impl Memory {
+	/// The maximum size of a packet, and therefore a datagram.
+	pub const MAX_DATAGRAM: usize = 1200;
+
+	/// The maximum bytes queued for each stream before writes block.
+	pub const MAX_BUFFERED: usize = 64 * 1024;
+
+	// The maximum datagrams queued before new ones are dropped.
+	const MAX_DATAGRAMS: usize = 64;
+
+	/// Return a (client, server) pair, using the same conditions in both directions.
+	pub fn pair(config: MemoryConfig) -> (Self, Self) {
+		let mut server = config.clone();
+		server.seed = server.seed.wrapping_add(1);
+
+		Self::pair_with(config, server)
+	}
+
+	/// Return a (client, server) pair, using the conditions for packets sent by the client and server respectively.
+	pub fn pair_with(client: MemoryConfig, server: MemoryConfig) -> (Self, Self) {
+		let (client_side, server_side) = (Self::new(false), Self::new(true));
+
+		tokio::spawn(run(client_side.shared.clone(), server_side.shared.clone(), client));
+		tokio::spawn(run(server_side.shared.clone(), client_side.shared.clone(), server));
+
+		(client_side, server_side)
+	}
+
+	fn new(server: bool) -> Self {
+		let accept_uni = mpsc::unbounded_channel();
+		let accept_bi = mpsc::unbounded_channel();
+		let datagrams = mpsc::unbounded_channel();
+
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State {
+				server,
+				next: [0, 0],
+				remote: [0, 0],
+				recv: HashMap::new(),
+				accept_uni: accept_uni.0,
+				accept_bi: accept_bi.0,
+				datagrams: datagrams.0,
+			}),
+			stopped: Default::default(),
+			outgoing: Default::default(),
+			wake: Notify::new(),
+			drained: Notify::new(),
+			closed: watch::channel(None).0,
+		});
+
+		Self {
+			shared,
+			accept_uni: tokio::sync::Mutex::new(accept_uni.1),
+			accept_bi: tokio::sync::Mutex::new(accept_bi.1),
+			datagrams: tokio::sync::Mutex::new(datagrams.1),
+		}
+	}
+}
+
+impl Drop for Memory {
+	fn drop(&mut self) {
+		// Like QUIC, the connection is closed once every handle is dropped.
+		Transport::close(self, 0, "");
+	}
+}
+
+impl Transport for Memory {
+	fn open_uni(&self) -> BoxFuture<'_, Result<SendStream, TransportError>> {
+		let res = self.shared.open(true).map(|(send, _)| send);
+		async move { res }.boxed()
+	}
+
+	fn open_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>> {
+		let res = self
+			.shared
+			.open(false)
+			.map(|(send, recv)| (send, recv.expect("missing recv")));
+		async move { res }.boxed()
+	}
+
+	fn accept_uni(&self) -> BoxFuture<'_, Result<RecvStream, TransportError>> {
+		async move {
+			match self.accept_uni.lock().await.recv().await {
+				Some(recv) => Ok(recv),
+				None => Err(self.shared.error()),
+			}
+		}
+		.boxed()
+	}
+
+	fn accept_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>> {
+		async move {
+			match self.accept_bi.lock().await.recv().await {
+				Some(streams) => Ok(streams),
+				None => Err(self.shared.error()),
+			}
+		}
+		.boxed()
+	}
+
+	fn send_datagram(&self, payload: Bytes) -> BoxFuture<'_, Result<(), TransportError>> {
+		let res = match self.shared.closed.borrow().clone() {
+			Some(err) => Err(err),
+			None if payload.len() > Self::MAX_DATAGRAM => Err(TransportError::Failed("datagram too large".to_string())),
+			None => {
+				let mut outgoing = self.shared.outgoing.lock().unwrap();
+
+				// Like QUIC, datagrams are dropped instead of queued forever.
+				if outgoing.datagrams.len() < Self::MAX_DATAGRAMS {
+					outgoing.datagrams.push_back(payload);
+					self.shared.wake.notify_one();
+				}
+
+				Ok(())
+			}
+		};
+
+		async move { res }.boxed()
+	}
+
+	fn recv_datagram(&self) -> BoxFuture<'_, Result<Bytes, TransportError>> {
+		async move {
+			match self.datagrams.lock().await.recv().await {
+				Some(datagram) => Ok(datagram),
+				None => Err(self.shared.error()),
+			}
+		}
+		.boxed()
+	}
+
+	fn max_datagram_size(&self) -> BoxFuture<'_, usize> {
+		async { Self::MAX_DATAGRAM }.boxed()
+	}
+
+	fn close(&self, code: u32, reason: &str) {
+		if self.shared.closed.borrow().is_some() {
+			return;
+		}
+
+		// Abandon anything queued, sending only the close.
+		let mut outgoing = self.shared.outgoing.lock().unwrap();
+		*outgoing = Outgoing::default();
+		outgoing.control.push_back(Frame::Close {
+			code,
+			reason: reason.to_string(),
+		});
+		drop(outgoing);
+
+		self.shared.wake.notify_one();
+		self.shared.close(TransportError::Failed("closed locally".to_string()));
+	}
+
+	fn closed(&self) -> BoxFuture<'_, TransportError> {
+		let mut closed = self.shared.closed.subscribe();
+		async move {
+			match closed.wait_for(Option::is_some).await {
+				Ok(err) => err.clone().unwrap(),
+				Err(_) => TransportError::Failed("memory dropped".to_string()),
+			}
+		}
+		.boxed()
+	}
+}
+
+struct Shared {
+	state: Mutex<State>,
+
+	// The active send streams, and the code if the peer asked us to stop sending.
+	// NOTE: This is separate from the state so streams can be dropped while it's locked.
+	stopped: Mutex<HashMap<u64, Option<u32>>>,
+
+	// Frames waiting to be sent over the link.
+	outgoing: Mutex<Outgoing>,
+
+	// Notified when there's something new to send.
+	wake: Notify,
+
+	// Notified when queued stream data is sent, so blocked writes can continue.
+	drained: Notify,
+
+	// Set once the connection is closed, by either side.
+	closed: watch::Sender<Option<TransportError>>,
+}
+
+impl Shared {
+	fn send(&self, frame: Frame) {
+		self.outgoing.lock().unwrap().control.push_back(frame);
+		self.wake.notify_one();
+	}
+
+	fn error(&self) -> TransportError {
+		self.closed
+			.borrow()
+			.clone()
+			.unwrap_or_else(|| TransportError::Failed("memory closed".to_string()))
+	}
+
+	// Open one of our streams, returning the receive half too if bidirectional.
+	fn open(self: &Arc<Self>, uni: bool) -> Result<(SendStream, Option<RecvStream>), TransportError> {
+		if let Some(err) = self.closed.borrow().clone() {
+			return Err(err);
+		}
+
+		let mut state = self.state.lock().unwrap();
+		let index = state.next[uni as usize];
+		state.next[uni as usize] += 1;
+
+		let id = stream_id(index, uni, state.server);
+		let send = state.send_stream(self, id);
+		let recv = (!uni).then(|| state.recv_stream(self, id));
+
+		Ok((send, recv))
+	}
+
+	// Mark the connection as closed, waking up any readers, writers and acceptors.
+	fn close(&self, err: TransportError) {
+		let first = self.closed.send_if_modified(|closed| match closed {
+			Some(_) => false,
+			None => {
+				*closed = Some(err);
+				true
+			}
+		});
+
+		if !first {
+			return;
+		}
+
+		let mut state = self.state.lock().unwrap();
+		state.recv.clear();
+		state.accept_uni = mpsc::unbounded_channel().0;
+		state.accept_bi = mpsc::unbounded_channel().0;
+		state.datagrams = mpsc::unbounded_channel().0;
+		drop(state);
+
+		self.drained.notify_waiters();
+	}
+
+	// Dispatch a frame from the peer.
+	fn recv(self: &Arc<Self>, frame: Frame) {
+		let mut state = self.state.lock().unwrap();
+
+		match frame {
+			Frame::Stream { id, data } => {
+				if let Some(events) = state.incoming(self, id) {
+					events.send(RecvEvent::Data(data)).ok();
+				}
+			}
+			Frame::Fin { id } => {
+				if let Some(events) = state.incoming(self, id) {
+					events.send(RecvEvent::Fin).ok();
+				}
+				state.recv.remove(&id);
+			}
+			Frame::Reset { id, code } => {
+				if let Some(events) = state.incoming(self, id) {
+					events.send(RecvEvent::Reset(code)).ok();
+				}
+				state.recv.remove(&id);
+			}
+			Frame::Stop { id, code } => {
+				if let Some(stopped) = self.stopped.lock().unwrap().get_mut(&id) {
+					*stopped = Some(code);
+				}
+			}
+			Frame::Datagram(data) => {
+				state.datagrams.send(data).ok();
+			}
+			Frame::Close { code, reason } => {
+				drop(state);
+				self.close(TransportError::Closed { code, reason });
+			}
+		}
+	}
+}
+
+struct State {
+	server: bool,
+
+	// The index of the next stream to open, by whether it's unidirectional.
+	next: [u64; 2],
+
+	// The index of the next stream the peer will open, by whether it's unidirectional.
+	remote: [u64; 2],
+
+	// The active receive streams, until they're finished or reset.
+	recv: HashMap<u64, mpsc::UnboundedSender<RecvEvent>>,
+
+	accept_uni: mpsc::UnboundedSender<RecvStream>,
+	accept_bi: mpsc::UnboundedSender<(SendStream, RecvStream)>,
+	datagrams: mpsc::UnboundedSender<Bytes>,
+}
+
+impl State {
+	fn send_stream(&mut self, shared: &Arc<Shared>, id: u64) -> SendStream {
+		shared.stopped.lock().unwrap().insert(id, None);
+		SendStream::new(MemorySend {
+			id,
+			shared: shared.clone(),
+			priority: 0,
+			done: false,
+		})
+	}
+
+	fn recv_stream(&mut self, shared: &Arc<Shared>, id: u64) -> RecvStream {
+		let (events, receiver) = mpsc::unbounded_channel();
+		self.recv.insert(id, events);
+
+		RecvStream::new(MemoryRecv {
+			id,
+			shared: shared.clone(),
+			events: receiver,
+			buffer: Bytes::new(),
+			done: false,
+		})
+	}
+
+	// Return the receive half of the stream, accepting any new streams opened by the peer.
+	fn incoming(&mut self, shared: &Arc<Shared>, id: u64) -> Option<&mpsc::UnboundedSender<RecvEvent>> {
+		let server = id & 1 == 1;
+		let uni = id & 2 == 2;
+
+		if server != self.server {
+			// Like QUIC, streams are opened in order, so opening one opens any lower streams too.
+			let index = id >> 2;
+			while self.remote[uni as usize] <= index {
+				let id = stream_id(self.remote[uni as usize], uni, server);
+				self.remote[uni as usize] += 1;
+
+				let recv = self.recv_stream(shared, id);
+				if uni {
+					self.accept_uni.send(recv).ok();
+				} else {
+					let send = self.send_stream(shared, id);
+					self.accept_bi.send((send, recv)).ok();
+				}
+			}
+		}
+
+		// None if the stream was already finished or abandoned.
+		self.recv.get(&id)
+	}
+}
+
+#[derive(Default)]
+struct Outgoing {
+	// Control frames, sent before anything else.
+	control: VecDeque<Frame>,
+
+	// Datagrams, sent before any stream data.
+	datagrams: VecDeque<Bytes>,
+
+	// The data queued for each stream.
+	streams: HashMap<u64, Queue>,
+
+	// Incremented each time a stream is sent, so streams with the same priority take turns.
+	turn: u64,
+}
+
+#[derive(Default)]
+struct Queue {
+	priority: i32,
+	data: VecDeque<Bytes>,
+	size: usize,
+	fin: bool,
+
+	// The last turn this stream was sent.
+	turn: u64,
+}
+
+impl Outgoing {
+	// Return the next packet to send, if any.
+	fn next(&mut self) -> Option<Frame> {
+		if let Some(frame) = self.control.pop_front() {
+			return Some(frame);
+		}
+
+		if let Some(datagram) = self.datagrams.pop_front() {
+			return Some(Frame::Datagram(datagram));
+		}
+
+		// Send the highest priority stream, or whichever was sent least recently on a tie.
+		let id = *self
+			.streams
+			.iter()
+			.filter(|(_, queue)| queue.size > 0 || queue.fin)
+			.max_by_key(|(id, queue)| (queue.priority, cmp::Reverse(queue.turn), cmp::Reverse(**id)))?
+			.0;
+
+		self.turn += 1;
+
+		let queue = self.streams.get_mut(&id).unwrap();
+		queue.turn = self.turn;
+
+		let Some(chunk) = queue.data.front_mut() else {
+			self.streams.remove(&id);
+			return Some(Frame::Fin { id });
+		};
+
+		let data = match chunk.len() > Memory::MAX_DATAGRAM {
+			true => chunk.split_to(Memory::MAX_DATAGRAM),
+			false => queue.data.pop_front().unwrap(),
+		};
+
+		queue.size -= data.len();
+
+		Some(Frame::Stream { id, data })
+	}
+}
+
+enum RecvEvent {
+	Data(Bytes),
+	Fin,
+	Reset(u32),
+}
+
+struct MemorySend {
+	id: u64,
+	shared: Arc<Shared>,
+	priority: i32,
+
+	// Set once the stream is reset.
+	done: bool,
+}
+
+impl MemorySend {
+	async fn send(&mut self, data: Bytes) -> Result<(), TransportError> {
+		loop {
+			let drained = self.shared.drained.notified();
+			tokio::pin!(drained);
+			drained.as_mut().enable();
+
+			if let Some(err) = self.shared.closed.borrow().clone() {
+				return Err(err);
+			}
+
+			if let Some(Some(code)) = self.shared.stopped.lock().unwrap().get(&self.id) {
+				return Err(TransportError::Stopped(*code));
+			}
+
+			let queued = {
+				let mut outgoing = self.shared.outgoing.lock().unwrap();
+				let queue = outgoing.streams.entry(self.id).or_default();
+
+				let room = queue.size < Memory::MAX_BUFFERED;
+				if room {
+					queue.priority = self.priority;
+					queue.size += data.len();
+					queue.data.push_back(data.clone());
+				}
+
+				room
+			};
+
+			if queued {
+				self.shared.wake.notify_one();
+				return Ok(());
+			}
+
+			drained.await;
+		}
+	}
+}
+
+impl SendTransport for MemorySend {
+	fn write<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<usize, TransportError>> {
+		async move {
+			let size = buf.len().min(Memory::MAX_BUFFERED);
+			self.send(Bytes::copy_from_slice(&buf[..size])).await?;
+			Ok(size)
+		}
+		.boxed()
+	}
+
+	fn write_chunk(&mut self, chunk: Bytes) -> BoxFuture<'_, Result<(), TransportError>> {
+		self.send(chunk).boxed()
+	}
+
+	fn set_priority(&mut self, order: i32) {
+		self.priority = order;
+
+		if let Some(queue) = self.shared.outgoing.lock().unwrap().streams.get_mut(&self.id) {
+			queue.priority = order;
+		}
+	}
+
+	fn reset(&mut self, code: u32) {
+		if !self.done {
+			self.done = true;
+
+			// Abandon any queued data too.
+			let mut outgoing = self.shared.outgoing.lock().unwrap();
+			outgoing.streams.remove(&self.id);
+			outgoing.control.push_back(Frame::Reset { id: self.id, code });
+			drop(outgoing);
+
+			self.shared.wake.notify_one();
+		}
+	}
+}
+
+impl Drop for MemorySend {
+	fn drop(&mut self) {
+		// Like QUIC, dropping the stream finishes it once the queued data is sent.
+		if !self.done {
+			self.shared
+				.outgoing
+				.lock()
+				.unwrap()
+				.streams
+				.entry(self.id)
+				.or_default()
+				.fin = true;
+			self.shared.wake.notify_one();
+		}
+
+		self.shared.stopped.lock().unwrap().remove(&self.id);
+	}
+}
+
+struct MemoryRecv {
+	id: u64,
+	shared: Arc<Shared>,
+	events: mpsc::UnboundedReceiver<RecvEvent>,
+
+	// Any data past the maximum size of the previous read.
+	buffer: Bytes,
+
+	// Set once the stream is finished or reset.
+	done: bool,
+}
+
+impl RecvTransport for MemoryRecv {
+	fn read_chunk(&mut self, max: usize) -> BoxFuture<'_, Result<Option<Bytes>, TransportError>> {
+		async move {
+			if self.buffer.is_empty() {
+				match self.events.recv().await {
+					Some(RecvEvent::Data(data)) => self.buffer = data,
+					Some(RecvEvent::Fin) => {
+						self.done = true;
+						return Ok(None);
+					}
+					Some(RecvEvent::Reset(code)) => {
+						self.done = true;
+						return Err(TransportError::Reset(code));
+					}
+					None if self.done => return Ok(None),
+					None => return Err(self.shared.error()),
+				}
+			}
+
+			let size = max.min(self.buffer.len());
+			Ok(Some(self.buffer.split_to(size)))
+		}
+		.boxed()
+	}
+
+	fn stop(&mut self, code: u32) {
+		if !self.done {
+			self.done = true;
+			self.shared.send(Frame::Stop { id: self.id, code });
+		}
+	}
+}
+
+impl Drop for MemoryRecv {
+	fn drop(&mut self) {
+		// Like QUIC, dropping an unfinished stream asks the peer to stop sending.
+		self.stop(0);
+	}
+}
+
+#[derive(Debug)]
+enum Frame {
+	Stream { id: u64, data: Bytes },
+	Fin { id: u64 },
+	Reset { id: u64, code: u32 },
+	Stop { id: u64, code: u32 },
+	Datagram(Bytes),
+	Close { code: u32, reason: String },
+}
+
+impl Frame {
+	// The stream that must be delivered in order, if any.
+	fn stream(&self) -> Option<u64> {
+		match self {
+			Self::Stream { id, .. } | Self::Fin { id } | Self::Reset { id, .. } => Some(*id),
+			_ => None,
+		}
+	}
+
+	// The size counted against the bandwidth, which ignores any headers.
+	fn size(&self) -> usize {
+		match self {
+			Self::Stream { data, .. } | Self::Datagram(data) => data.len(),
+			_ => 0,
+		}
+	}
+}
+
+// A packet in flight, ordered so the earliest is at the top of a max-heap.
+struct Delivery {
+	at: Instant,
+	seq: u64,
+	frame: Frame,
+}
+
+impl PartialEq for Delivery {
+	fn eq(&self, other: &Self) -> bool {
+		(self.at, self.seq) == (other.at, other.seq)
+	}
+}
+
+impl Eq for Delivery {}
+
+impl PartialOrd for Delivery {
+	fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Delivery {
+	fn cmp(&self, other: &Self) -> cmp::Ordering {
+		(other.at, other.seq).cmp(&(self.at, self.seq))
+	}
+}
+
+// A xorshift generator, which is plenty random for simulating loss.
+struct Random(u64);
+
+impl Random {
+	fn new(seed: u64) -> Self {
+		// Scramble the seed with splitmix64, since xorshift can't start at zero.
+		let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+		Self((z ^ (z >> 31)).max(1))
+	}
+
+	// Return true with the probability.
+	fn chance(&mut self, probability: f64) -> bool {
+		self.next() < probability
+	}
+
+	// Return a value in [0, 1).
+	fn next(&mut self) -> f64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		(self.0 >> 11) as f64 / (1u64 << 53) as f64
+	}
+}
+
+// Send packets from the local endpoint to the remote, until the remote is closed.
+async fn run(local: Arc<Shared>, remote: Arc<Shared>, config: MemoryConfig) {
+	let mut random = Random::new(config.seed);
+
+	// The number of packets left to lose in the current burst.
+	let mut burst = 0;
+	let mut lost = move |random: &mut Random| {
+		if burst > 0 {
+			burst -= 1;
+			true
+		} else if random.chance(config.loss) {
+			burst = config.burst.saturating_sub(1);
+			true
+		} else {
+			false
+		}
+	};
+	let mut closed = remote.closed.subscribe();
+
+	let mut inflight = BinaryHeap::new();
+	let mut seq = 0;
+
+	// The latest delivery for each stream, so they arrive in order despite retransmissions.
+	let mut ordered = HashMap::new();
+
+	// When the link is free to send the next packet.
+	let mut free = Instant::now();
+
+	loop {
+		let now = Instant::now();
+
+		while free <= now {
+			let Some(frame) = local.outgoing.lock().unwrap().next() else {
+				break;
+			};
+
+			local.drained.notify_waiters();
+
+			if let Some(bandwidth) = config.bandwidth {
+				free = free.max(now) + Duration::from_secs_f64(frame.size() as f64 / bandwidth.max(1) as f64);
+			}
+
+			let mut at = free.max(now) + config.latency + config.jitter.mul_f64(random.next());
+
+			if random.chance(config.reorder) {
+				at += config.latency;
+			}
+
+			if let Some(id) = frame.stream() {
+				// Like QUIC, stream data is retransmitted after a timeout until it arrives.
+				// NOTE: The retries are capped so a loss of 1 doesn't spin forever.
+				for _ in 0..16 {
+					if !lost(&mut random) {
+						break;
+					}
+
+					at += config.latency * 3;
+				}
+
+				let last = ordered.entry(id).or_insert(at);
+				at = at.max(*last);
+				*last = at;
+
+				if let Frame::Fin { .. } | Frame::Reset { .. } = frame {
+					ordered.remove(&id);
+				}
+			} else if let Frame::Datagram(_) = frame {
+				if lost(&mut random) {
+					continue;
+				}
+			}
+
+			seq += 1;
+			inflight.push(Delivery { at, seq, frame });
+		}
+
+		let next = inflight.peek().map(|delivery: &Delivery| delivery.at);
+
+		tokio::select! {
+			_ = tokio::time::sleep_until(next.unwrap_or(now)), if next.is_some() => {
+				let now = Instant::now();
+
+				while inflight.peek().is_some_and(|delivery| delivery.at <= now) {
+					let delivery = inflight.pop().unwrap();
+					remote.recv(delivery.frame);
+				}
+			},
+			_ = tokio::time::sleep_until(free), if free > now => {},
+			_ = local.wake.notified() => {},
+			_ = closed.wait_for(Option::is_some) => return,
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[tokio::test]
+	async fn latency() {
+		let config = MemoryConfig {
+			latency: Duration::from_millis(50),
+			..Default::default()
+		};
+
+		let (client, server) = Memory::pair(config);
+
+		let start = Instant::now();
+		client.send_datagram(Bytes::from_static(b"hello")).await.unwrap();
+		assert_eq!(server.recv_datagram().await.unwrap(), Bytes::from_static(b"hello"));
+		assert!(start.elapsed() >= Duration::from_millis(50));
+	}
+
+	#[tokio::test]
+	async fn loss() {
+		let config = MemoryConfig {
+			loss: 1.0,
+			..Default::default()
+		};
+
+		let (client, server) = Memory::pair(config);
+
+		// Datagrams are lost, while stream data is eventually retransmitted.
+		client.send_datagram(Bytes::from_static(b"lost")).await.unwrap();
+
+		let mut send = client.open_uni().await.unwrap();
+		send.write_chunk(Bytes::from_static(b"hello")).await.unwrap();
+		drop(send);
+
+		let mut recv = server.accept_uni().await.unwrap();
+		assert_eq!(recv.read_chunk(1024).await.unwrap(), Some(Bytes::from_static(b"hello")));
+		assert_eq!(recv.read_chunk(1024).await.unwrap(), None);
+
+		let datagram = tokio::time::timeout(Duration::from_millis(10), server.recv_datagram()).await;
+		assert!(datagram.is_err());
+	}
+
+	#[tokio::test]
+	async fn priority() {
+		let config = MemoryConfig {
+			bandwidth: Some(1_000_000),
+			..Default::default()
+		};
+
+		let (client, server) = Memory::pair(config);
+
+		let mut low = client.open_uni().await.unwrap();
+		low.write_chunk(Bytes::from(vec![0; 10_000])).await.unwrap();
+
+		let mut high = client.open_uni().await.unwrap();
+		high.set_priority(1);
+		high.write_chunk(Bytes::from(vec![1; 10_000])).await.unwrap();
+
+		drop(low);
+		drop(high);
+
+		let read = |mut recv: RecvStream| async move {
+			while recv.read_chunk(usize::MAX).await.unwrap().is_some() {}
+			Instant::now()
+		};
+
+		let low = server.accept_uni().await.unwrap();
+		let high = server.accept_uni().await.unwrap();
+
+		// The high priority stream finishes first, despite being queued later.
+		let (low, high) = tokio::join!(read(low), read(high));
+		assert!(high < low);
+	}
+}
@@ -0,0 +1,205 @@
+//! The connection underneath a [crate::session::Session], abstracted so it can run over different transports.
+//!
+//! Any type implementing [Transport] can be converted into a [Session]:
+//! - `web_transport_quinn::Session` for WebTransport over HTTP/3, or `web_transport::Session` in the browser.
+//! - [quinn::Connection] for raw QUIC using the [crate::setup::ALPN], which skips the HTTP/3 CONNECT handshake.
+//! - [WebSocket] as a fallback for networks that block UDP.
+//! - [Memory] for tests, simulating latency, loss and bandwidth without sockets.
+//!
+//! The traits return boxed futures so a session can hold any transport without being generic.
+mod error;
+mod memory;
+mod path;
+#[cfg(not(target_arch = "wasm32"))]
+mod quic;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+mod websocket;
+#[cfg(not(target_arch = "wasm32"))]
+mod webtransport;
+
+pub use error::*;
+pub use memory::*;
+pub use path::*;
+pub use websocket::*;
+
+use std::sync::Arc;
+
+use bytes::{Buf, BufMut, Bytes};
+use futures::future::BoxFuture;
+
+/// A connection able to open and accept streams, and to send and receive datagrams.
+pub trait Transport: Send + Sync {
+	/// Open a new unidirectional stream, which may block when there are too many concurrent streams.
+	fn open_uni(&self) -> BoxFuture<'_, Result<SendStream, TransportError>>;
+
+	/// Open a new bidirectional stream, which may block when there are too many concurrent streams.
+	fn open_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>>;
+
+	/// Block until the peer opens a new unidirectional stream.
+	fn accept_uni(&self) -> BoxFuture<'_, Result<RecvStream, TransportError>>;
+
+	/// Block until the peer opens a new bidirectional stream.
+	fn accept_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>>;
+
+	/// Send a datagram, which may be dropped for any reason.
+	fn send_datagram(&self, payload: Bytes) -> BoxFuture<'_, Result<(), TransportError>>;
+
+	/// Block until the peer sends a datagram.
+	fn recv_datagram(&self) -> BoxFuture<'_, Result<Bytes, TransportError>>;
+
+	/// The maximum size of a datagram that can be sent.
+	fn max_datagram_size(&self) -> BoxFuture<'_, usize>;
+
+	/// Close the connection immediately with a code and reason.
+	fn close(&self, code: u32, reason: &str);
+
+	/// Block until the connection is closed, by either side.
+	fn closed(&self) -> BoxFuture<'_, TransportError>;
+
+	/// Returns the current network path, or None if the transport doesn't expose it.
+	fn path(&self) -> Option<Path> {
+		None
+	}
+}
+
+/// An outgoing stream of bytes, returned by a [Transport].
+pub trait SendTransport: Send + Sync {
+	/// Write some of the buffer, returning the size written.
+	fn write<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<usize, TransportError>>;
+
+	/// Write the entire chunk, avoiding a copy if the transport supports it.
+	fn write_chunk(&mut self, chunk: Bytes) -> BoxFuture<'_, Result<(), TransportError>>;
+
+	/// Set the priority of the stream relative to others; higher is sent first.
+	fn set_priority(&mut self, order: i32);
+
+	/// Abandon the stream, sending the code to the peer.
+	fn reset(&mut self, code: u32);
+}
+
+/// An incoming stream of bytes, returned by a [Transport].
+pub trait RecvTransport: Send + Sync {
+	/// Read the next chunk of at most `max` bytes, or None when the stream is finished.
+	fn read_chunk(&mut self, max: usize) -> BoxFuture<'_, Result<Option<Bytes>, TransportError>>;
+
+	/// Ask the peer to stop sending, with the code.
+	fn stop(&mut self, code: u32);
+}
+
+/// A connection to the peer over any [Transport].
+///
+/// The session can be cloned to create multiple handles.
+#[derive(Clone)]
+pub struct Session(Arc<dyn Transport>);
+
+impl Session {
+	pub fn new<T: Transport + 'static>(transport: T) -> Self {
+		Self(Arc::new(transport))
+	}
+
+	pub async fn open_uni(&self) -> Result<SendStream, TransportError> {
+		self.0.open_uni().await
+	}
+
+	pub async fn open_bi(&self) -> Result<(SendStream, RecvStream), TransportError> {
+		self.0.open_bi().await
+	}
+
+	pub async fn accept_uni(&self) -> Result<RecvStream, TransportError> {
+		self.0.accept_uni().await
+	}
+
+	pub async fn accept_bi(&self) -> Result<(SendStream, RecvStream), TransportError> {
+		self.0.accept_bi().await
+	}
+
+	pub async fn send_datagram(&self, payload: Bytes) -> Result<(), TransportError> {
+		self.0.send_datagram(payload).await
+	}
+
+	pub async fn recv_datagram(&self) -> Result<Bytes, TransportError> {
+		self.0.recv_datagram().await
+	}
+
+	pub async fn max_datagram_size(&self) -> usize {
+		self.0.max_datagram_size().await
+	}
+
+	pub fn close(&self, code: u32, reason: &str) {
+		self.0.close(code, reason)
+	}
+
+	pub async fn closed(&self) -> TransportError {
+		self.0.closed().await
+	}
+
+	pub fn path(&self) -> Option<Path> {
+		self.0.path()
+	}
+}
+
+/// An outgoing stream of bytes to the peer.
+pub struct SendStream(Box<dyn SendTransport>);
+
+impl SendStream {
+	pub fn new<T: SendTransport + 'static>(stream: T) -> Self {
+		Self(Box::new(stream))
+	}
+
+	pub async fn write(&mut self, buf: &[u8]) -> Result<usize, TransportError> {
+		self.0.write(buf).await
+	}
+
+	/// Write some of the buffer, advancing it by the size written.
+	pub async fn write_buf<B: Buf>(&mut self, buf: &mut B) -> Result<usize, TransportError> {
+		let size = self.0.write(buf.chunk()).await?;
+		buf.advance(size);
+		Ok(size)
+	}
+
+	pub async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), TransportError> {
+		self.0.write_chunk(chunk).await
+	}
+
+	pub fn set_priority(&mut self, order: i32) {
+		self.0.set_priority(order)
+	}
+
+	pub fn reset(mut self, code: u32) {
+		self.0.reset(code)
+	}
+}
+
+/// An incoming stream of bytes from the peer.
+pub struct RecvStream(Box<dyn RecvTransport>);
+
+impl RecvStream {
+	pub fn new<T: RecvTransport + 'static>(stream: T) -> Self {
+		Self(Box::new(stream))
+	}
+
+	pub async fn read_chunk(&mut self, max: usize) -> Result<Option<Bytes>, TransportError> {
+		self.0.read_chunk(max).await
+	}
+
+	/// Append the next chunk to the buffer, returning false when the stream is finished.
+	pub async fn read_buf<B: BufMut>(&mut self, buf: &mut B) -> Result<bool, TransportError> {
+		match self.0.read_chunk(buf.remaining_mut()).await? {
+			Some(chunk) => {
+				buf.put(chunk);
+				Ok(true)
+			}
+			None => Ok(false),
+		}
+	}
+
+	pub fn stop(mut self, code: u32) {
+		self.0.stop(code)
+	}
+}
+
+// The lowest bit is set for streams opened by the server, and the next bit for unidirectional streams.
+fn stream_id(index: u64, uni: bool, server: bool) -> u64 {
+	(index << 2) | ((uni as u64) << 1) | (server as u64)
+}
@@ -0,0 +1,23 @@
+use std::{net, time};
+
+/// The network path used by a [super::Transport], as seen by its congestion controller.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+	/// The address of the peer, which changes when either side migrates to a new network.
+	pub remote: net::SocketAddr,
+
+	/// The smoothed round trip time.
+	pub rtt: time::Duration,
+
+	/// The congestion window in bytes.
+	pub cwnd: u64,
+}
+
+impl Path {
+	/// Estimate the bandwidth in bytes per second, as a congestion window per round trip.
+	///
+	/// The estimate starts over after a migration, since the congestion controller is reset for the new path.
+	pub fn bandwidth(&self) -> Option<u64> {
+		(!self.rtt.is_zero()).then(|| (self.cwnd as f64 / self.rtt.as_secs_f64()) as u64)
+	}
+}
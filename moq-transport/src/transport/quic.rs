@@ -0,0 +1,150 @@
+use bytes::Bytes;
+use futures::{future::BoxFuture, FutureExt};
+
+use super::{Path, RecvStream, RecvTransport, SendStream, SendTransport, Session, Transport, TransportError};
+
+// Raw QUIC, negotiated with the MoQ ALPN instead of WebTransport.
+// Streams and datagrams map directly to QUIC, and codes are sent without the HTTP/3 mapping.
+struct Quic(quinn::Connection);
+
+impl From<quinn::Connection> for Session {
+	fn from(conn: quinn::Connection) -> Self {
+		Session::new(Quic(conn))
+	}
+}
+
+impl Transport for Quic {
+	fn open_uni(&self) -> BoxFuture<'_, Result<SendStream, TransportError>> {
+		async move {
+			let send = self.0.open_uni().await.map_err(connection_error)?;
+			Ok(SendStream::new(QuicSend(send)))
+		}
+		.boxed()
+	}
+
+	fn open_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>> {
+		async move {
+			let (send, recv) = self.0.open_bi().await.map_err(connection_error)?;
+			Ok((SendStream::new(QuicSend(send)), RecvStream::new(QuicRecv(recv))))
+		}
+		.boxed()
+	}
+
+	fn accept_uni(&self) -> BoxFuture<'_, Result<RecvStream, TransportError>> {
+		async move {
+			let recv = self.0.accept_uni().await.map_err(connection_error)?;
+			Ok(RecvStream::new(QuicRecv(recv)))
+		}
+		.boxed()
+	}
+
+	fn accept_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>> {
+		async move {
+			let (send, recv) = self.0.accept_bi().await.map_err(connection_error)?;
+			Ok((SendStream::new(QuicSend(send)), RecvStream::new(QuicRecv(recv))))
+		}
+		.boxed()
+	}
+
+	fn send_datagram(&self, payload: Bytes) -> BoxFuture<'_, Result<(), TransportError>> {
+		let res = self.0.send_datagram(payload).map_err(|err| match err {
+			quinn::SendDatagramError::ConnectionLost(err) => connection_error(err),
+			err => TransportError::Failed(err.to_string()),
+		});
+
+		async move { res }.boxed()
+	}
+
+	fn recv_datagram(&self) -> BoxFuture<'_, Result<Bytes, TransportError>> {
+		async move { self.0.read_datagram().await.map_err(connection_error) }.boxed()
+	}
+
+	fn max_datagram_size(&self) -> BoxFuture<'_, usize> {
+		// None means the peer doesn't support datagrams.
+		let size = self.0.max_datagram_size().unwrap_or(0);
+		async move { size }.boxed()
+	}
+
+	fn close(&self, code: u32, reason: &str) {
+		self.0.close(code.into(), reason.as_bytes())
+	}
+
+	fn closed(&self) -> BoxFuture<'_, TransportError> {
+		async move { connection_error(self.0.closed().await) }.boxed()
+	}
+
+	fn path(&self) -> Option<Path> {
+		Some(Path {
+			remote: self.0.remote_address(),
+			rtt: self.0.rtt(),
+			cwnd: self.0.stats().path.cwnd,
+		})
+	}
+}
+
+struct QuicSend(quinn::SendStream);
+
+impl SendTransport for QuicSend {
+	fn write<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<usize, TransportError>> {
+		async move { self.0.write(buf).await.map_err(write_error) }.boxed()
+	}
+
+	fn write_chunk(&mut self, chunk: Bytes) -> BoxFuture<'_, Result<(), TransportError>> {
+		async move { self.0.write_chunk(chunk).await.map_err(write_error) }.boxed()
+	}
+
+	fn set_priority(&mut self, order: i32) {
+		self.0.set_priority(order).ok();
+	}
+
+	fn reset(&mut self, code: u32) {
+		self.0.reset(code.into()).ok();
+	}
+}
+
+struct QuicRecv(quinn::RecvStream);
+
+impl RecvTransport for QuicRecv {
+	fn read_chunk(&mut self, max: usize) -> BoxFuture<'_, Result<Option<Bytes>, TransportError>> {
+		async move {
+			let chunk = self.0.read_chunk(max, true).await.map_err(read_error)?;
+			Ok(chunk.map(|chunk| chunk.bytes))
+		}
+		.boxed()
+	}
+
+	fn stop(&mut self, code: u32) {
+		self.0.stop(code.into()).ok();
+	}
+}
+
+// Codes are u32 at the MoQ layer, so anything larger came from a misbehaving peer.
+fn code(code: quinn::VarInt) -> u32 {
+	code.into_inner().try_into().unwrap_or(u32::MAX)
+}
+
+fn connection_error(err: quinn::ConnectionError) -> TransportError {
+	match err {
+		quinn::ConnectionError::ApplicationClosed(close) => TransportError::Closed {
+			code: code(close.error_code),
+			reason: String::from_utf8_lossy(&close.reason).to_string(),
+		},
+		err => TransportError::Failed(err.to_string()),
+	}
+}
+
+fn write_error(err: quinn::WriteError) -> TransportError {
+	match err {
+		quinn::WriteError::Stopped(err) => TransportError::Stopped(code(err)),
+		quinn::WriteError::ConnectionLost(err) => connection_error(err),
+		err => TransportError::Failed(err.to_string()),
+	}
+}
+
+fn read_error(err: quinn::ReadError) -> TransportError {
+	match err {
+		quinn::ReadError::Reset(err) => TransportError::Reset(code(err)),
+		quinn::ReadError::ConnectionLost(err) => connection_error(err),
+		err => TransportError::Failed(err.to_string()),
+	}
+}
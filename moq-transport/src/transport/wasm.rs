@@ -0,0 +1,147 @@
+use bytes::Bytes;
+use futures::{future::BoxFuture, FutureExt};
+
+use super::{RecvStream, RecvTransport, SendStream, SendTransport, Session, Transport, TransportError};
+
+// WebTransport in the browser, which is the only transport available there.
+struct WebTransport(web_transport::Session);
+
+impl From<web_transport::Session> for Session {
+	fn from(session: web_transport::Session) -> Self {
+		Session::new(WebTransport(session))
+	}
+}
+
+impl Transport for WebTransport {
+	fn open_uni(&self) -> BoxFuture<'_, Result<SendStream, TransportError>> {
+		let mut session = self.0.clone();
+		async move {
+			let send = session.open_uni().await.map_err(session_error)?;
+			Ok(SendStream::new(WebSend(Some(send))))
+		}
+		.boxed()
+	}
+
+	fn open_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>> {
+		let mut session = self.0.clone();
+		async move {
+			let (send, recv) = session.open_bi().await.map_err(session_error)?;
+			Ok((
+				SendStream::new(WebSend(Some(send))),
+				RecvStream::new(WebRecv(Some(recv))),
+			))
+		}
+		.boxed()
+	}
+
+	fn accept_uni(&self) -> BoxFuture<'_, Result<RecvStream, TransportError>> {
+		let mut session = self.0.clone();
+		async move {
+			let recv = session.accept_uni().await.map_err(session_error)?;
+			Ok(RecvStream::new(WebRecv(Some(recv))))
+		}
+		.boxed()
+	}
+
+	fn accept_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>> {
+		let mut session = self.0.clone();
+		async move {
+			let (send, recv) = session.accept_bi().await.map_err(session_error)?;
+			Ok((
+				SendStream::new(WebSend(Some(send))),
+				RecvStream::new(WebRecv(Some(recv))),
+			))
+		}
+		.boxed()
+	}
+
+	fn send_datagram(&self, payload: Bytes) -> BoxFuture<'_, Result<(), TransportError>> {
+		let mut session = self.0.clone();
+		async move { session.send_datagram(payload).await.map_err(session_error) }.boxed()
+	}
+
+	fn recv_datagram(&self) -> BoxFuture<'_, Result<Bytes, TransportError>> {
+		let mut session = self.0.clone();
+		async move { session.recv_datagram().await.map_err(session_error) }.boxed()
+	}
+
+	fn max_datagram_size(&self) -> BoxFuture<'_, usize> {
+		self.0.max_datagram_size().boxed()
+	}
+
+	fn close(&self, code: u32, reason: &str) {
+		self.0.clone().close(code, reason)
+	}
+
+	fn closed(&self) -> BoxFuture<'_, TransportError> {
+		async move { session_error(self.0.closed().await) }.boxed()
+	}
+}
+
+// The stream is only taken when reset, since web_transport consumes it.
+struct WebSend(Option<web_transport::SendStream>);
+
+impl SendTransport for WebSend {
+	fn write<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<usize, TransportError>> {
+		async move {
+			let stream = self.0.as_mut().ok_or_else(reset_stream)?;
+			stream.write(buf).await.map_err(write_error)
+		}
+		.boxed()
+	}
+
+	fn write_chunk(&mut self, chunk: Bytes) -> BoxFuture<'_, Result<(), TransportError>> {
+		async move {
+			let stream = self.0.as_mut().ok_or_else(reset_stream)?;
+			stream.write_chunk(chunk).await.map_err(write_error)
+		}
+		.boxed()
+	}
+
+	fn set_priority(&mut self, order: i32) {
+		if let Some(stream) = &mut self.0 {
+			stream.set_priority(order);
+		}
+	}
+
+	fn reset(&mut self, code: u32) {
+		if let Some(stream) = self.0.take() {
+			stream.reset(code);
+		}
+	}
+}
+
+struct WebRecv(Option<web_transport::RecvStream>);
+
+impl RecvTransport for WebRecv {
+	fn read_chunk(&mut self, max: usize) -> BoxFuture<'_, Result<Option<Bytes>, TransportError>> {
+		async move {
+			let stream = self.0.as_mut().ok_or_else(reset_stream)?;
+			stream.read_chunk(max).await.map_err(read_error)
+		}
+		.boxed()
+	}
+
+	fn stop(&mut self, code: u32) {
+		if let Some(stream) = self.0.take() {
+			stream.stop(code);
+		}
+	}
+}
+
+fn reset_stream() -> TransportError {
+	TransportError::Failed("stream closed".to_string())
+}
+
+// TODO The browser doesn't expose the close reason or reset code via these errors.
+fn session_error(err: web_transport::SessionError) -> TransportError {
+	TransportError::Failed(err.to_string())
+}
+
+fn write_error(err: web_transport::WriteError) -> TransportError {
+	TransportError::Failed(err.to_string())
+}
+
+fn read_error(err: web_transport::ReadError) -> TransportError {
+	TransportError::Failed(err.to_string())
+}
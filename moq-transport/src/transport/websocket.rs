@@ -0,0 +1,617 @@
+use std::{
+	collections::HashMap,
+	fmt,
+	sync::{Arc, Mutex},
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{future::BoxFuture, FutureExt, Sink, SinkExt, Stream, StreamExt};
+use tokio::sync::{mpsc, watch, OwnedSemaphorePermit, Semaphore};
+
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+use super::{stream_id, RecvStream, RecvTransport, SendStream, SendTransport, Transport, TransportError};
+
+/// A fallback [Transport] for networks that block UDP, tunneling streams and datagrams over a WebSocket.
+///
+/// Each binary message carries a single frame for a stream, similar to QUIC's STREAM, RESET_STREAM and STOP_SENDING.
+/// The WebSocket is provided as a sink and stream of binary messages, so any implementation can be used.
+///
+/// Everything is sent over a single TCP connection, so streams suffer from head-of-line blocking,
+/// priorities are ignored, and datagrams are delivered reliably.
+pub struct WebSocket {
+	shared: Arc<Shared>,
+	accept_uni: tokio::sync::Mutex<mpsc::UnboundedReceiver<RecvStream>>,
+	accept_bi: tokio::sync::Mutex<mpsc::UnboundedReceiver<(SendStream, RecvStream)>>,
+	datagrams: tokio::sync::Mutex<mpsc::UnboundedReceiver<Bytes>>,
+}
+
+impl WebSocket {
+	/// The maximum size of a datagram, matching a typical QUIC path so publishers behave the same.
+	pub const MAX_DATAGRAM: usize = 1200;
+
+	/// The maximum bytes queued for the WebSocket before writes block.
+	pub const MAX_QUEUED: usize = 1024 * 1024;
+
+	/// Run the transport as the client, which opened the WebSocket.
+	pub fn client<T, R, E>(sink: T, stream: R) -> Self
+	where
+		T: Sink<Bytes> + Send + Unpin + 'static,
+		T::Error: fmt::Display,
+		R: Stream<Item = Result<Bytes, E>> + Send + Unpin + 'static,
+		E: fmt::Display + 'static,
+	{
+		Self::new(sink, stream, false)
+	}
+
+	/// Run the transport as the server, which accepted the WebSocket.
+	pub fn server<T, R, E>(sink: T, stream: R) -> Self
+	where
+		T: Sink<Bytes> + Send + Unpin + 'static,
+		T::Error: fmt::Display,
+		R: Stream<Item = Result<Bytes, E>> + Send + Unpin + 'static,
+		E: fmt::Display + 'static,
+	{
+		Self::new(sink, stream, true)
+	}
+
+	fn new<T, R, E>(sink: T, stream: R, server: bool) -> Self
+	where
+		T: Sink<Bytes> + Send + Unpin + 'static,
+		T::Error: fmt::Display,
+		R: Stream<Item = Result<Bytes, E>> + Send + Unpin + 'static,
+		E: fmt::Display + 'static,
+	{
+		let (outgoing, queue) = mpsc::unbounded_channel();
+		let accept_uni = mpsc::unbounded_channel();
+		let accept_bi = mpsc::unbounded_channel();
+		let datagrams = mpsc::unbounded_channel();
+
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State {
+				server,
+				next: [0, 0],
+				remote: [0, 0],
+				recv: HashMap::new(),
+				accept_uni: accept_uni.0,
+				accept_bi: accept_bi.0,
+				datagrams: datagrams.0,
+			}),
+			stopped: Default::default(),
+			outgoing,
+			queued: Arc::new(Semaphore::new(Self::MAX_QUEUED)),
+			closed: watch::channel(None).0,
+		});
+
+		tokio::spawn(run(shared.clone(), sink, stream, queue));
+
+		Self {
+			shared,
+			accept_uni: tokio::sync::Mutex::new(accept_uni.1),
+			accept_bi: tokio::sync::Mutex::new(accept_bi.1),
+			datagrams: tokio::sync::Mutex::new(datagrams.1),
+		}
+	}
+}
+
+impl Drop for WebSocket {
+	fn drop(&mut self) {
+		// Like QUIC, the connection is closed once every handle is dropped.
+		self.shared.send(Frame::Close {
+			code: 0,
+			reason: String::new(),
+		});
+	}
+}
+
+impl Transport for WebSocket {
+	fn open_uni(&self) -> BoxFuture<'_, Result<SendStream, TransportError>> {
+		let res = self.shared.open(true).map(|(send, _)| send);
+		async move { res }.boxed()
+	}
+
+	fn open_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>> {
+		let res = self
+			.shared
+			.open(false)
+			.map(|(send, recv)| (send, recv.expect("missing recv")));
+		async move { res }.boxed()
+	}
+
+	fn accept_uni(&self) -> BoxFuture<'_, Result<RecvStream, TransportError>> {
+		async move {
+			match self.accept_uni.lock().await.recv().await {
+				Some(recv) => Ok(recv),
+				None => Err(self.shared.error()),
+			}
+		}
+		.boxed()
+	}
+
+	fn accept_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>> {
+		async move {
+			match self.accept_bi.lock().await.recv().await {
+				Some(streams) => Ok(streams),
+				None => Err(self.shared.error()),
+			}
+		}
+		.boxed()
+	}
+
+	fn send_datagram(&self, payload: Bytes) -> BoxFuture<'_, Result<(), TransportError>> {
+		let res = match self.shared.closed.borrow().clone() {
+			Some(err) => Err(err),
+			None if payload.len() > Self::MAX_DATAGRAM => Err(TransportError::Failed("datagram too large".to_string())),
+			None => {
+				self.shared.send(Frame::Datagram(payload));
+				Ok(())
+			}
+		};
+
+		async move { res }.boxed()
+	}
+
+	fn recv_datagram(&self) -> BoxFuture<'_, Result<Bytes, TransportError>> {
+		async move {
+			match self.datagrams.lock().await.recv().await {
+				Some(datagram) => Ok(datagram),
+				None => Err(self.shared.error()),
+			}
+		}
+		.boxed()
+	}
+
+	fn max_datagram_size(&self) -> BoxFuture<'_, usize> {
+		async { Self::MAX_DATAGRAM }.boxed()
+	}
+
+	fn close(&self, code: u32, reason: &str) {
+		self.shared.send(Frame::Close {
+			code,
+			reason: reason.to_string(),
+		});
+	}
+
+	fn closed(&self) -> BoxFuture<'_, TransportError> {
+		let mut closed = self.shared.closed.subscribe();
+		async move {
+			match closed.wait_for(Option::is_some).await {
+				Ok(err) => err.clone().unwrap(),
+				Err(_) => TransportError::Failed("websocket dropped".to_string()),
+			}
+		}
+		.boxed()
+	}
+}
+
+struct Shared {
+	state: Mutex<State>,
+
+	// The active send streams, and the code if the peer asked us to stop sending.
+	// NOTE: This is separate from the state so streams can be dropped while it's locked.
+	stopped: Mutex<HashMap<u64, Option<u32>>>,
+
+	outgoing: mpsc::UnboundedSender<(Frame, Option<OwnedSemaphorePermit>)>,
+
+	// Limits the bytes queued for the WebSocket, so writes block instead of buffering forever.
+	queued: Arc<Semaphore>,
+
+	// Set once the WebSocket is closed, by either side.
+	closed: watch::Sender<Option<TransportError>>,
+}
+
+impl Shared {
+	fn send(&self, frame: Frame) {
+		self.outgoing.send((frame, None)).ok();
+	}
+
+	fn error(&self) -> TransportError {
+		self.closed
+			.borrow()
+			.clone()
+			.unwrap_or_else(|| TransportError::Failed("websocket closed".to_string()))
+	}
+
+	// Open one of our streams, returning the receive half too if bidirectional.
+	fn open(self: &Arc<Self>, uni: bool) -> Result<(SendStream, Option<RecvStream>), TransportError> {
+		if let Some(err) = self.closed.borrow().clone() {
+			return Err(err);
+		}
+
+		let mut state = self.state.lock().unwrap();
+		let index = state.next[uni as usize];
+		state.next[uni as usize] += 1;
+
+		let id = stream_id(index, uni, state.server);
+		let send = state.send_stream(self, id);
+		let recv = (!uni).then(|| state.recv_stream(self, id));
+
+		Ok((send, recv))
+	}
+}
+
+struct State {
+	server: bool,
+
+	// The index of the next stream to open, by whether it's unidirectional.
+	next: [u64; 2],
+
+	// The index of the next stream the peer will open, by whether it's unidirectional.
+	remote: [u64; 2],
+
+	// The active receive streams, until they're finished or reset.
+	recv: HashMap<u64, mpsc::UnboundedSender<RecvEvent>>,
+
+	accept_uni: mpsc::UnboundedSender<RecvStream>,
+	accept_bi: mpsc::UnboundedSender<(SendStream, RecvStream)>,
+	datagrams: mpsc::UnboundedSender<Bytes>,
+}
+
+impl State {
+	fn send_stream(&mut self, shared: &Arc<Shared>, id: u64) -> SendStream {
+		shared.stopped.lock().unwrap().insert(id, None);
+		SendStream::new(WebSocketSend {
+			id,
+			shared: shared.clone(),
+			done: false,
+		})
+	}
+
+	fn recv_stream(&mut self, shared: &Arc<Shared>, id: u64) -> RecvStream {
+		let (events, receiver) = mpsc::unbounded_channel();
+		self.recv.insert(id, events);
+
+		RecvStream::new(WebSocketRecv {
+			id,
+			shared: shared.clone(),
+			events: receiver,
+			buffer: Bytes::new(),
+			done: false,
+		})
+	}
+
+	// Return the receive half of the stream, accepting any new streams opened by the peer.
+	fn incoming(&mut self, shared: &Arc<Shared>, id: u64) -> Option<&mpsc::UnboundedSender<RecvEvent>> {
+		let server = id & 1 == 1;
+		let uni = id & 2 == 2;
+
+		if server != self.server {
+			// Like QUIC, streams are opened in order, so opening one opens any lower streams too.
+			let index = id >> 2;
+			while self.remote[uni as usize] <= index {
+				let id = stream_id(self.remote[uni as usize], uni, server);
+				self.remote[uni as usize] += 1;
+
+				let recv = self.recv_stream(shared, id);
+				if uni {
+					self.accept_uni.send(recv).ok();
+				} else {
+					let send = self.send_stream(shared, id);
+					self.accept_bi.send((send, recv)).ok();
+				}
+			}
+		}
+
+		// None if the stream was already finished or abandoned.
+		self.recv.get(&id)
+	}
+}
+
+enum RecvEvent {
+	Data(Bytes),
+	Fin,
+	Reset(u32),
+}
+
+struct WebSocketSend {
+	id: u64,
+	shared: Arc<Shared>,
+
+	// Set once the stream is finished or reset.
+	done: bool,
+}
+
+impl WebSocketSend {
+	async fn send(&mut self, data: Bytes) -> Result<(), TransportError> {
+		if let Some(err) = self.shared.closed.borrow().clone() {
+			return Err(err);
+		}
+
+		if let Some(Some(code)) = self.shared.stopped.lock().unwrap().get(&self.id) {
+			return Err(TransportError::Stopped(*code));
+		}
+
+		let size = data.len().clamp(1, WebSocket::MAX_QUEUED) as u32;
+		let permit = self
+			.shared
+			.queued
+			.clone()
+			.acquire_many_owned(size)
+			.await
+			.expect("semaphore closed");
+
+		let frame = Frame::Stream { id: self.id, data };
+		self.shared.outgoing.send((frame, Some(permit))).ok();
+
+		Ok(())
+	}
+}
+
+impl SendTransport for WebSocketSend {
+	fn write<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<usize, TransportError>> {
+		async move {
+			// Copy at most the queue size, so a huge write can't deadlock waiting for permits.
+			let size = buf.len().min(WebSocket::MAX_QUEUED);
+			self.send(Bytes::copy_from_slice(&buf[..size])).await?;
+			Ok(size)
+		}
+		.boxed()
+	}
+
+	fn write_chunk(&mut self, mut chunk: Bytes) -> BoxFuture<'_, Result<(), TransportError>> {
+		async move {
+			while !chunk.is_empty() {
+				let size = chunk.len().min(WebSocket::MAX_QUEUED);
+				self.send(chunk.split_to(size)).await?;
+			}
+
+			Ok(())
+		}
+		.boxed()
+	}
+
+	fn set_priority(&mut self, _order: i32) {
+		// Everything shares a TCP connection, so there's nothing to prioritize.
+	}
+
+	fn reset(&mut self, code: u32) {
+		if !self.done {
+			self.done = true;
+			self.shared.send(Frame::Reset { id: self.id, code });
+		}
+	}
+}
+
+impl Drop for WebSocketSend {
+	fn drop(&mut self) {
+		// Like QUIC, dropping the stream finishes it.
+		if !self.done {
+			self.shared.send(Frame::Fin { id: self.id });
+		}
+
+		self.shared.stopped.lock().unwrap().remove(&self.id);
+	}
+}
+
+struct WebSocketRecv {
+	id: u64,
+	shared: Arc<Shared>,
+	events: mpsc::UnboundedReceiver<RecvEvent>,
+
+	// Any data past the maximum size of the previous read.
+	buffer: Bytes,
+
+	// Set once the stream is finished or reset.
+	done: bool,
+}
+
+impl RecvTransport for WebSocketRecv {
+	fn read_chunk(&mut self, max: usize) -> BoxFuture<'_, Result<Option<Bytes>, TransportError>> {
+		async move {
+			if self.buffer.is_empty() {
+				match self.events.recv().await {
+					Some(RecvEvent::Data(data)) => self.buffer = data,
+					Some(RecvEvent::Fin) => {
+						self.done = true;
+						return Ok(None);
+					}
+					Some(RecvEvent::Reset(code)) => {
+						self.done = true;
+						return Err(TransportError::Reset(code));
+					}
+					None if self.done => return Ok(None),
+					None => return Err(self.shared.error()),
+				}
+			}
+
+			let size = max.min(self.buffer.len());
+			Ok(Some(self.buffer.split_to(size)))
+		}
+		.boxed()
+	}
+
+	fn stop(&mut self, code: u32) {
+		if !self.done {
+			self.done = true;
+			self.shared.send(Frame::Stop { id: self.id, code });
+		}
+	}
+}
+
+impl Drop for WebSocketRecv {
+	fn drop(&mut self) {
+		// Like QUIC, dropping an unfinished stream asks the peer to stop sending.
+		// The peer responds with a reset, which removes the stream from the state.
+		self.stop(0);
+	}
+}
+
+// Write queued frames and dispatch received frames until either side closes the WebSocket.
+async fn run<T, R, E>(
+	shared: Arc<Shared>,
+	mut sink: T,
+	mut stream: R,
+	mut queue: mpsc::UnboundedReceiver<(Frame, Option<OwnedSemaphorePermit>)>,
+) where
+	T: Sink<Bytes> + Unpin,
+	T::Error: fmt::Display,
+	R: Stream<Item = Result<Bytes, E>> + Unpin,
+	E: fmt::Display,
+{
+	let send = async {
+		// NOTE: The queue can't close since we hold a sender.
+		while let Some((frame, _permit)) = queue.recv().await {
+			let mut buf = BytesMut::new();
+			frame.encode(&mut buf).expect("failed to encode frame");
+
+			if let Err(err) = sink.send(buf.freeze()).await {
+				return TransportError::Failed(err.to_string());
+			}
+
+			if let Frame::Close { .. } = frame {
+				sink.close().await.ok();
+				return TransportError::Failed("closed locally".to_string());
+			}
+		}
+
+		TransportError::Failed("websocket dropped".to_string())
+	};
+
+	let recv = async {
+		loop {
+			let mut msg = match stream.next().await {
+				Some(Ok(msg)) => msg,
+				Some(Err(err)) => return TransportError::Failed(err.to_string()),
+				None => return TransportError::Failed("websocket closed".to_string()),
+			};
+
+			let frame = match Frame::decode(&mut msg) {
+				Ok(frame) => frame,
+				Err(err) => return TransportError::Failed(format!("invalid frame: {}", err)),
+			};
+
+			if let Some(err) = recv_frame(&shared, frame) {
+				return err;
+			}
+		}
+	};
+
+	let err = tokio::select! {
+		err = send => err,
+		err = recv => err,
+	};
+
+	shared.closed.send_replace(Some(err));
+
+	// Wake up any readers and acceptors.
+	let mut state = shared.state.lock().unwrap();
+	state.recv.clear();
+	state.accept_uni = mpsc::unbounded_channel().0;
+	state.accept_bi = mpsc::unbounded_channel().0;
+	state.datagrams = mpsc::unbounded_channel().0;
+}
+
+// Dispatch a frame from the peer, returning an error if it closed the connection.
+fn recv_frame(shared: &Arc<Shared>, frame: Frame) -> Option<TransportError> {
+	let mut state = shared.state.lock().unwrap();
+
+	match frame {
+		Frame::Stream { id, data } => {
+			if let Some(events) = state.incoming(shared, id) {
+				events.send(RecvEvent::Data(data)).ok();
+			}
+		}
+		Frame::Fin { id } => {
+			if let Some(events) = state.incoming(shared, id) {
+				events.send(RecvEvent::Fin).ok();
+			}
+			state.recv.remove(&id);
+		}
+		Frame::Reset { id, code } => {
+			if let Some(events) = state.incoming(shared, id) {
+				events.send(RecvEvent::Reset(code)).ok();
+			}
+			state.recv.remove(&id);
+		}
+		Frame::Stop { id, code } => {
+			if let Some(stopped) = shared.stopped.lock().unwrap().get_mut(&id) {
+				*stopped = Some(code);
+			}
+		}
+		Frame::Datagram(data) => {
+			state.datagrams.send(data).ok();
+		}
+		Frame::Close { code, reason } => return Some(TransportError::Closed { code, reason }),
+	}
+
+	None
+}
+
+// A frame sent in each WebSocket message.
+#[derive(Debug)]
+enum Frame {
+	Stream { id: u64, data: Bytes },
+	Fin { id: u64 },
+	Reset { id: u64, code: u32 },
+	Stop { id: u64, code: u32 },
+	Datagram(Bytes),
+	Close { code: u32, reason: String },
+}
+
+impl Encode for Frame {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		match self {
+			Self::Stream { id, data } => {
+				0u64.encode(w)?;
+				id.encode(w)?;
+				Self::encode_remaining(w, data.len())?;
+				w.put_slice(data);
+			}
+			Self::Fin { id } => {
+				1u64.encode(w)?;
+				id.encode(w)?;
+			}
+			Self::Reset { id, code } => {
+				2u64.encode(w)?;
+				id.encode(w)?;
+				(*code as u64).encode(w)?;
+			}
+			Self::Stop { id, code } => {
+				3u64.encode(w)?;
+				id.encode(w)?;
+				(*code as u64).encode(w)?;
+			}
+			Self::Datagram(data) => {
+				4u64.encode(w)?;
+				Self::encode_remaining(w, data.len())?;
+				w.put_slice(data);
+			}
+			Self::Close { code, reason } => {
+				5u64.encode(w)?;
+				(*code as u64).encode(w)?;
+				reason.encode(w)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+impl Decode for Frame {
+	// NOTE: The payload is the rest of the message, so this can't be used on a stream.
+	fn decode<B: bytes::Buf>(r: &mut B) -> Result<Self, DecodeError> {
+		let code = |r: &mut B| u32::try_from(u64::decode(r)?).map_err(|_| DecodeError::InvalidValue);
+
+		Ok(match u64::decode(r)? {
+			0 => Self::Stream {
+				id: u64::decode(r)?,
+				data: r.copy_to_bytes(r.remaining()),
+			},
+			1 => Self::Fin { id: u64::decode(r)? },
+			2 => Self::Reset {
+				id: u64::decode(r)?,
+				code: code(r)?,
+			},
+			3 => Self::Stop {
+				id: u64::decode(r)?,
+				code: code(r)?,
+			},
+			4 => Self::Datagram(r.copy_to_bytes(r.remaining())),
+			5 => Self::Close {
+				code: code(r)?,
+				reason: String::decode(r)?,
+			},
+			typ => return Err(DecodeError::InvalidMessage(typ)),
+		})
+	}
+}
@@ -0,0 +1,189 @@
+use std::io;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{future::BoxFuture, FutureExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::{RecvStream, RecvTransport, SendStream, SendTransport, Session, Transport, TransportError};
+
+// WebTransport over HTTP/3, as used by browsers.
+//
+// Streams are read and written through quinn's io errors, which carry the raw HTTP/3 code.
+// web-transport-quinn 0.3 decodes codes incorrectly, so we invert the mapping ourselves; see [code].
+struct WebTransport(web_transport_quinn::Session);
+
+impl From<web_transport_quinn::Session> for Session {
+	fn from(session: web_transport_quinn::Session) -> Self {
+		Session::new(WebTransport(session))
+	}
+}
+
+impl Transport for WebTransport {
+	fn open_uni(&self) -> BoxFuture<'_, Result<SendStream, TransportError>> {
+		async move {
+			let send = self.0.open_uni().await.map_err(session_error)?;
+			Ok(SendStream::new(WebSend(send)))
+		}
+		.boxed()
+	}
+
+	fn open_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>> {
+		async move {
+			let (send, recv) = self.0.open_bi().await.map_err(session_error)?;
+			Ok((SendStream::new(WebSend(send)), RecvStream::new(WebRecv(recv))))
+		}
+		.boxed()
+	}
+
+	fn accept_uni(&self) -> BoxFuture<'_, Result<RecvStream, TransportError>> {
+		async move {
+			let recv = self.0.accept_uni().await.map_err(session_error)?;
+			Ok(RecvStream::new(WebRecv(recv)))
+		}
+		.boxed()
+	}
+
+	fn accept_bi(&self) -> BoxFuture<'_, Result<(SendStream, RecvStream), TransportError>> {
+		async move {
+			let (send, recv) = self.0.accept_bi().await.map_err(session_error)?;
+			Ok((SendStream::new(WebSend(send)), RecvStream::new(WebRecv(recv))))
+		}
+		.boxed()
+	}
+
+	fn send_datagram(&self, payload: Bytes) -> BoxFuture<'_, Result<(), TransportError>> {
+		let res = self.0.send_datagram(payload).map_err(session_error);
+		async move { res }.boxed()
+	}
+
+	fn recv_datagram(&self) -> BoxFuture<'_, Result<Bytes, TransportError>> {
+		async move { self.0.read_datagram().await.map_err(session_error) }.boxed()
+	}
+
+	fn max_datagram_size(&self) -> BoxFuture<'_, usize> {
+		let size = self.0.max_datagram_size();
+		async move { size }.boxed()
+	}
+
+	fn close(&self, code: u32, reason: &str) {
+		self.0.close(code, reason.as_bytes())
+	}
+
+	fn closed(&self) -> BoxFuture<'_, TransportError> {
+		async move { session_error(self.0.closed().await) }.boxed()
+	}
+}
+
+struct WebSend(web_transport_quinn::SendStream);
+
+impl SendTransport for WebSend {
+	fn write<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<usize, TransportError>> {
+		async move { AsyncWriteExt::write(&mut self.0, buf).await.map_err(write_error) }.boxed()
+	}
+
+	fn write_chunk(&mut self, chunk: Bytes) -> BoxFuture<'_, Result<(), TransportError>> {
+		async move { AsyncWriteExt::write_all(&mut self.0, &chunk).await.map_err(write_error) }.boxed()
+	}
+
+	fn set_priority(&mut self, order: i32) {
+		self.0.set_priority(order).ok();
+	}
+
+	fn reset(&mut self, code: u32) {
+		self.0.reset(code).ok();
+	}
+}
+
+struct WebRecv(web_transport_quinn::RecvStream);
+
+impl WebRecv {
+	// Avoid allocating the maximum when the caller reads until the end of the stream.
+	const CHUNK: usize = 64 * 1024;
+}
+
+impl RecvTransport for WebRecv {
+	fn read_chunk(&mut self, max: usize) -> BoxFuture<'_, Result<Option<Bytes>, TransportError>> {
+		async move {
+			let max = max.min(Self::CHUNK);
+			let mut buf = BytesMut::with_capacity(max);
+
+			let mut limit = (&mut buf).limit(max);
+			let size = AsyncReadExt::read_buf(&mut self.0, &mut limit)
+				.await
+				.map_err(read_error)?;
+			Ok((size > 0 || max == 0).then(|| buf.freeze()))
+		}
+		.boxed()
+	}
+
+	fn stop(&mut self, code: u32) {
+		self.0.stop(code).ok();
+	}
+}
+
+// WebTransport maps application codes into the HTTP/3 error space, skipping the reserved codepoints.
+// web-transport-quinn 0.3 divides instead of subtracting them when decoding, so invert the mapping ourselves.
+fn code(code: quinn::VarInt) -> u32 {
+	const ERROR_FIRST: u64 = 0x52e4a40fa8db;
+	const ERROR_LAST: u64 = 0x52e5ac983162;
+
+	let code = code.into_inner();
+	if !(ERROR_FIRST..=ERROR_LAST).contains(&code) {
+		// Not mapped, such as the code sent by quinn when a stream is dropped.
+		return code.try_into().unwrap_or(u32::MAX);
+	}
+
+	let code = code - ERROR_FIRST;
+	(code - code / 0x1f).try_into().unwrap_or(u32::MAX)
+}
+
+fn connection_error(err: &quinn::ConnectionError) -> TransportError {
+	match err {
+		quinn::ConnectionError::ApplicationClosed(close) => TransportError::Closed {
+			code: code(close.error_code),
+			reason: String::from_utf8_lossy(&close.reason).to_string(),
+		},
+		err => TransportError::Failed(err.to_string()),
+	}
+}
+
+fn session_error(err: web_transport_quinn::SessionError) -> TransportError {
+	match err {
+		web_transport_quinn::SessionError::ConnectionError(err) => connection_error(&err),
+		err => TransportError::Failed(err.to_string()),
+	}
+}
+
+fn write_error(err: io::Error) -> TransportError {
+	match err.get_ref().and_then(|err| err.downcast_ref::<quinn::WriteError>()) {
+		Some(quinn::WriteError::Stopped(err)) => TransportError::Stopped(code(*err)),
+		Some(quinn::WriteError::ConnectionLost(err)) => connection_error(err),
+		_ => TransportError::Failed(err.to_string()),
+	}
+}
+
+fn read_error(err: io::Error) -> TransportError {
+	match err.get_ref().and_then(|err| err.downcast_ref::<quinn::ReadError>()) {
+		Some(quinn::ReadError::Reset(err)) => TransportError::Reset(code(*err)),
+		Some(quinn::ReadError::ConnectionLost(err)) => connection_error(err),
+		_ => TransportError::Failed(err.to_string()),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn http3_codes() {
+		// The encoding used by web-transport-quinn when resetting a stream, which skips a codepoint every 30.
+		let encode = |code: u32| quinn::VarInt::from_u64(0x52e4a40fa8db + code as u64 + code as u64 / 0x1e).unwrap();
+
+		for value in [0, 1, 29, 30, 31, 500, 0xffff, u32::MAX] {
+			assert_eq!(code(encode(value)), value);
+		}
+
+		// Codes outside the range are passed through, like raw QUIC.
+		assert_eq!(code(quinn::VarInt::from_u32(0)), 0);
+	}
+}
@@ -2,38 +2,107 @@ use std::collections::VecDeque;
 
 use super::State;
 
+struct QueueState<T> {
+	items: VecDeque<T>,
+
+	// Set by close, preventing new entries while the remaining ones are popped.
+	closed: bool,
+}
+
+impl<T> Default for QueueState<T> {
+	fn default() -> Self {
+		Self {
+			items: Default::default(),
+			closed: false,
+		}
+	}
+}
+
 pub struct Queue<T> {
-	state: State<VecDeque<T>>,
+	state: State<QueueState<T>>,
 }
 
 impl<T> Queue<T> {
 	pub fn push(&mut self, item: T) -> Result<(), T> {
 		match self.state.lock_mut() {
-			Some(mut state) => state.push_back(item),
+			Some(state) if state.closed => return Err(item),
+			Some(mut state) => state.items.push_back(item),
 			None => return Err(item),
 		};
 
 		Ok(())
 	}
 
+	/// Returns the next item, or None once the queue is closed and empty.
 	pub async fn pop(&mut self) -> Option<T> {
 		loop {
 			{
 				let queue = self.state.lock();
-				if !queue.is_empty() {
-					return queue.into_mut()?.pop_front();
+				if !queue.items.is_empty() {
+					return queue.into_mut()?.items.pop_front();
+				}
+
+				if queue.closed {
+					return None;
 				}
+
 				queue.modified()?
 			}
 			.await;
 		}
 	}
 
+	/// Remove the first item waiting to be popped that matches, returning it.
+	pub fn remove(&mut self, f: impl Fn(&T) -> bool) -> Option<T> {
+		let mut state = self.state.lock_mut()?;
+		let index = state.items.iter().position(f)?;
+		state.items.remove(index)
+	}
+
+	/// Returns the number of items waiting to be popped.
+	pub fn len(&self) -> usize {
+		self.state.lock().items.len()
+	}
+
+	/// Returns true if there are no items waiting to be popped.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Reject any new items, while still allowing the remaining items to be popped.
+	///
+	/// This applies to every clone of the queue.
+	pub fn close(&mut self) {
+		if let Some(mut state) = self.state.lock_mut() {
+			state.closed = true;
+		}
+	}
+
+	/// Wait until the queue is closed and every remaining item has been popped.
+	///
+	/// Also returns if the other half of a split queue is dropped.
+	pub async fn drained(&self) {
+		loop {
+			{
+				let queue = self.state.lock();
+				if queue.closed && queue.items.is_empty() {
+					return;
+				}
+
+				match queue.modified() {
+					Some(notify) => notify,
+					None => return,
+				}
+			}
+			.await;
+		}
+	}
+
 	// Drop the state
-	pub fn close(self) -> Vec<T> {
+	pub fn drain(self) -> Vec<T> {
 		// Drain the queue of any remaining entries
 		let res = match self.state.lock_mut() {
-			Some(mut queue) => queue.drain(..).collect(),
+			Some(mut queue) => queue.items.drain(..).collect(),
 			_ => Vec::new(),
 		};
 
@@ -64,3 +133,28 @@ impl<T> Default for Queue<T> {
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[tokio::test]
+	async fn close() {
+		let mut queue = Queue::default();
+		queue.push(1).unwrap();
+		queue.push(2).unwrap();
+
+		queue.close();
+		assert_eq!(queue.push(3), Err(3));
+
+		// The consumer is still able to drain the remaining items.
+		let mut consumer = queue.clone();
+		let drained = tokio::spawn(async move { queue.drained().await });
+
+		assert_eq!(consumer.pop().await, Some(1));
+		assert_eq!(consumer.pop().await, Some(2));
+		assert_eq!(consumer.pop().await, None);
+
+		drained.await.unwrap();
+	}
+}
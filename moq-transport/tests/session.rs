@@ -0,0 +1,1756 @@
+use std::{
+	io,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{future::BoxFuture, FutureExt};
+use moq_transport::{
+	coding::{Decode, DecodeError, Encode, EncodeError, Params},
+	data,
+	error::ErrorCode,
+	harness, message,
+	serve::{self, ServeError, TrackReaderMode},
+	session::{
+		AnnouncedEvent, AuthRequest, GroupEvent, Identity, Publisher, Reconnect, ReconnectConfig, ReconnectStatus,
+		Relay, Session, SessionConfig, SessionError, SessionLimits, SubscribeStart, Subscriber, KEEPALIVE_PARAM,
+	},
+	setup, transport,
+};
+
+// Connect a publisher to a subscriber over the transport, running both sessions in the background.
+async fn connect((client, server): (transport::Session, transport::Session)) -> (Publisher, Subscriber) {
+	let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+	let (publish, publisher) = publish.unwrap();
+	let (subscribe, subscriber) = subscribe.unwrap();
+
+	tokio::spawn(publish.run());
+	tokio::spawn(subscribe.run());
+
+	(publisher, subscriber)
+}
+
+// Connect a publisher to a subscriber in memory.
+async fn connected_pair() -> (Publisher, Subscriber) {
+	connect(harness::pair()).await
+}
+
+// Announce a broadcast in the background, returning the writer that keeps it announced.
+fn announce(publisher: &Publisher, namespace: &str) -> serve::TracksWriter {
+	let (writer, _, reader) = serve::Tracks::new(namespace.to_string()).produce();
+
+	let mut publisher = publisher.clone();
+	tokio::spawn(async move { publisher.announce(reader).await });
+
+	writer
+}
+
+// Append a group with a single object, which is never finished so its stream stays open.
+fn open_group(groups: &mut serve::GroupsWriter, payload: &'static [u8]) -> serve::GroupWriter {
+	let mut group = groups.append(0).unwrap();
+	group.write(Bytes::from_static(payload)).unwrap();
+	group
+}
+
+// Wait for a subscribed track to be delivered as groups.
+async fn expect_groups(track: &serve::TrackReader) -> serve::GroupsReader {
+	match track.mode().await.unwrap() {
+		TrackReaderMode::Groups(groups) => groups,
+		_ => panic!("wrong mode"),
+	}
+}
+
+#[tokio::test]
+async fn subscribe_group() {
+	let (client, server) = harness::pair();
+
+	let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+	let (publish, publisher) = publish.unwrap();
+	let (subscribe, mut subscriber) = subscribe.unwrap();
+
+	assert_eq!(publish.role(), setup::Role::Publisher);
+	assert_eq!(subscribe.role(), setup::Role::Subscriber);
+
+	tokio::spawn(publish.run());
+	tokio::spawn(subscribe.run());
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+
+	let mut group = groups.append(0).unwrap();
+	group.write(Bytes::from_static(b"hello")).unwrap();
+	group.write(Bytes::from_static(b"world")).unwrap();
+	drop(group);
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut group = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(group.group_id, 0);
+	assert_eq!(group.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+	assert_eq!(group.read_next().await.unwrap(), Some(Bytes::from_static(b"world")));
+	assert_eq!(group.read_next().await.unwrap(), None);
+
+	let active = publisher.active_subscriptions();
+	assert_eq!(active.len(), 1);
+	assert_eq!(active[0].info.name, "video");
+
+	// Keep the track alive until the end of the test.
+	drop(groups);
+}
+
+#[tokio::test]
+async fn object_meta() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+
+	let meta = data::ObjectMeta {
+		timestamp: 90_000,
+		flags: data::ObjectFlags {
+			keyframe: true,
+			discardable: false,
+		},
+		..Default::default()
+	};
+
+	let mut group = groups.append(0).unwrap();
+	group.write_with_meta(Bytes::from_static(b"key"), meta.clone()).unwrap();
+	group.write(Bytes::from_static(b"delta")).unwrap();
+	drop(group);
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	// The metadata is only attached to the objects written with it.
+	let mut group = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(group.next().await.unwrap().unwrap().meta, Some(meta));
+	assert_eq!(group.next().await.unwrap().unwrap().meta, None);
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn reset_group() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	let group = open_group(&mut groups, b"hello");
+
+	let (track, mut track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let mut subscribe = subscriber.subscribe_handle(track);
+	let mut events = subscribe.group_events();
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	// The publisher resets the stream with the same error.
+	group.close(ServeError::Timeout).unwrap();
+
+	assert_eq!(reader.read_next().await, Err(ServeError::Timeout));
+	assert_eq!(events.next().await, Some(GroupEvent::Reset(0, ServeError::Timeout)));
+
+	// Readers of the track are told too, without holding the subscription.
+	let dropped = serve::GroupsDropped {
+		start: 0,
+		count: 1,
+		error: Some(ServeError::Timeout),
+	};
+	assert_eq!(track_reader.dropped().await, Some(dropped));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn webtransport() {
+	let (publisher, mut subscriber) = connect(harness::pair_webtransport().await.unwrap()).await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	let group = open_group(&mut groups, b"hello");
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	// Reset codes survive the HTTP/3 mapping.
+	group.close(ServeError::Timeout).unwrap();
+	assert_eq!(reader.read_next().await, Err(ServeError::Timeout));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn raw_quic() {
+	let (publisher, mut subscriber) = connect(harness::pair_quic().await.unwrap()).await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	let group = open_group(&mut groups, b"hello");
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	// Reset codes are sent without the HTTP/3 mapping, and still arrive intact.
+	group.close(ServeError::Timeout).unwrap();
+	assert_eq!(reader.read_next().await, Err(ServeError::Timeout));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn websocket() {
+	let (publisher, mut subscriber) = connect(harness::pair_websocket()).await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+
+	let mut group = groups.append(0).unwrap();
+	group.write(Bytes::from_static(b"hello")).unwrap();
+	group.write(Bytes::from(vec![7u8; 100_000])).unwrap();
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+	assert_eq!(reader.read_next().await.unwrap().unwrap().len(), 100_000);
+
+	// Resets are tunneled too.
+	group.close(ServeError::Timeout).unwrap();
+	assert_eq!(reader.read_next().await, Err(ServeError::Timeout));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn memory() {
+	let (publisher, mut subscriber) = connect(harness::pair_memory(transport::MemoryConfig {
+		latency: Duration::from_millis(10),
+		loss: 0.05,
+		bandwidth: Some(10_000_000),
+		seed: 1,
+		..Default::default()
+	}))
+	.await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+
+	let mut group = groups.append(0).unwrap();
+	group.write(Bytes::from_static(b"hello")).unwrap();
+	group.write(Bytes::from(vec![7u8; 100_000])).unwrap();
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	// Lost packets are retransmitted, so everything still arrives.
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+	assert_eq!(reader.read_next().await.unwrap().unwrap().len(), 100_000);
+
+	group.close(ServeError::Timeout).unwrap();
+	assert_eq!(reader.read_next().await, Err(ServeError::Timeout));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn netsim_lossy() {
+	let link = transport::MemoryConfig {
+		latency: Duration::from_millis(20),
+		jitter: Duration::from_millis(10),
+		reorder: 0.05,
+		loss: 0.05,
+		burst: 3,
+		seed: 7,
+		..Default::default()
+	};
+
+	let report = harness::Netsim {
+		forward: link.clone(),
+		reverse: link,
+		groups: 30,
+		interval: Duration::from_millis(10),
+		size: 5_000,
+		linger: Duration::from_millis(500),
+		..Default::default()
+	}
+	.run()
+	.await
+	.unwrap();
+
+	// Lost packets are retransmitted, so only groups overtaken by a newer group are skipped.
+	report.assert_delivered(0.5);
+	report.assert_latest_delivered();
+}
+
+#[tokio::test]
+async fn netsim_congested() {
+	let report = harness::Netsim {
+		forward: transport::MemoryConfig {
+			latency: Duration::from_millis(10),
+			bandwidth: Some(100_000),
+			..Default::default()
+		},
+		groups: 20,
+		interval: Duration::from_millis(10),
+		size: 10_000,
+		linger: Duration::from_millis(300),
+		..Default::default()
+	}
+	.run()
+	.await
+	.unwrap();
+
+	// The link only fits a fraction of the groups, so the newest groups are sent first.
+	report.assert_latest_delivered();
+	assert!(!report.dropped.is_empty());
+}
+
+#[tokio::test]
+async fn shutdown() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	let group = open_group(&mut groups, b"hello");
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	tokio::time::timeout(Duration::from_secs(1), subscriber.shutdown())
+		.await
+		.expect("shutdown timed out");
+
+	assert_eq!(subscribe.closed().await, Err(ServeError::Cancel));
+
+	// New subscriptions are cancelled immediately.
+	let (track, _track_reader) = serve::Track::new("test".to_string(), "audio".to_string()).produce();
+	let late = subscriber.subscribe_handle(track);
+	assert_eq!(late.closed().await, Err(ServeError::Cancel));
+
+	drop(group);
+	drop(groups);
+}
+
+#[tokio::test]
+async fn unsubscribe() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	let group = open_group(&mut groups, b"hello");
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	subscribe.unsubscribe();
+
+	// The track is closed locally, without waiting for the publisher.
+	assert_eq!(groups_reader.next().await.err(), Some(ServeError::Cancel));
+
+	// The publisher resets the group in flight.
+	let res = tokio::time::timeout(Duration::from_secs(1), reader.read_next())
+		.await
+		.expect("group wasn't reset");
+	assert!(res.is_err());
+
+	drop(group);
+	drop(groups);
+}
+
+#[tokio::test]
+async fn unsubscribe_waiting() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	// Only one stream at a time, so the second track waits for the first group to finish.
+	publisher.set_max_streams(1);
+
+	let mut writer = announce(&publisher, "test");
+	let mut video = writer.create("video").unwrap().groups().unwrap();
+	let mut audio = writer.create("audio").unwrap().groups().unwrap();
+
+	// The video group holds the only permit.
+	let group = open_group(&mut video, b"hello");
+	let _audio = audio.append(0).unwrap();
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _video = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	let active = |count| {
+		let publisher = publisher.clone();
+		async move {
+			while publisher.active_subscriptions().len() != count {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		}
+	};
+
+	let (track, _track_reader) = serve::Track::new("test".to_string(), "audio".to_string()).produce();
+	let audio_subscribe = subscriber.subscribe_handle(track);
+	active(2).await;
+
+	// The audio group is still waiting for a stream, but unsubscribing doesn't wait for it.
+	audio_subscribe.unsubscribe();
+	tokio::time::timeout(Duration::from_secs(1), active(1))
+		.await
+		.expect("subscription wasn't cancelled");
+
+	drop(group);
+}
+
+#[tokio::test]
+async fn track_error() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	let group = open_group(&mut groups, b"hello");
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	groups.close(ServeError::Timeout).unwrap();
+
+	// The publisher resets the group in flight instead of waiting for it to finish.
+	let res = tokio::time::timeout(Duration::from_secs(1), reader.read_next())
+		.await
+		.expect("group wasn't reset");
+	assert!(res.is_err());
+
+	assert_eq!(subscribe.closed().await, Err(ServeError::Timeout));
+
+	drop(group);
+}
+
+// Write a message directly to a stream, for peers that don't follow the rules.
+async fn send<T: Encode>(stream: &mut transport::SendStream, msg: &T) {
+	let mut buf = BytesMut::new();
+	msg.encode(&mut buf).unwrap();
+	stream.write(&buf).await.unwrap();
+}
+
+async fn recv<T: Decode>(stream: &mut transport::RecvStream, buf: &mut BytesMut) -> T {
+	loop {
+		let mut cursor = io::Cursor::new(&buf[..]);
+		match T::decode(&mut cursor) {
+			Ok(msg) => {
+				buf.advance(cursor.position() as usize);
+				return msg;
+			}
+			Err(DecodeError::More(_)) => assert!(stream.read_buf(buf).await.unwrap(), "stream closed"),
+			Err(err) => panic!("decode error: {}", err),
+		}
+	}
+}
+
+#[tokio::test]
+async fn keepalive() {
+	let (client, server) = harness::pair();
+
+	let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+	let (mut publish, _publisher) = publish.unwrap();
+	let (subscribe, _subscriber) = subscribe.unwrap();
+
+	let interval = std::time::Duration::from_millis(10);
+	publish.set_keepalive(interval, Duration::from_secs(1));
+	let rtt = publish.rtt();
+
+	tokio::spawn(publish.run());
+	tokio::spawn(subscribe.run());
+
+	tokio::time::timeout(Duration::from_secs(1), async {
+		while rtt.get().is_none() {
+			tokio::time::sleep(interval).await;
+		}
+	})
+	.await
+	.expect("no PONG received");
+}
+
+#[tokio::test]
+async fn keepalive_timeout() {
+	let (client, server) = harness::pair();
+
+	// Advertise PING support by hand, but never answer.
+	let setup = async {
+		let (mut control, mut recver) = client.open_bi().await.unwrap();
+		let mut buf = BytesMut::new();
+
+		let mut params = Params::default();
+		params.set(KEEPALIVE_PARAM, 1u64).unwrap();
+
+		let setup = setup::Client {
+			role: setup::Role::Subscriber,
+			versions: [setup::Version::DRAFT_04].into(),
+			params,
+		};
+
+		send(&mut control, &setup).await;
+		let _: setup::Server = recv(&mut recver, &mut buf).await;
+
+		(control, recver)
+	};
+
+	let (publish, _control) = tokio::join!(Publisher::accept(server), setup);
+	let (mut publish, _publisher) = publish.unwrap();
+
+	publish.set_keepalive(
+		std::time::Duration::from_millis(10),
+		std::time::Duration::from_millis(50),
+	);
+
+	let res = tokio::time::timeout(Duration::from_secs(1), publish.run())
+		.await
+		.expect("dead peer wasn't detected");
+	assert!(matches!(res, Err(SessionError::Timeout)));
+}
+
+#[tokio::test]
+async fn negotiate_version() {
+	let custom = setup::Version(0xff0000aa);
+
+	let mut client = SessionConfig::new(setup::Role::Subscriber);
+	client.codecs = message::Codecs::new();
+	client.codecs.register(custom, message::Draft04);
+	client.codecs.register(setup::Version::DRAFT_04, message::Draft04);
+
+	let mut server = SessionConfig::new(setup::Role::Publisher);
+	server.codecs.register(custom, message::Draft04);
+
+	let (client_session, server_session) = harness::pair();
+	let (publish, subscribe) = tokio::join!(
+		Session::accept_with(server_session, server.clone()),
+		Session::connect_with(client_session, client.clone())
+	);
+
+	// The client's preferred version wins, even though the server prefers draft-04.
+	assert_eq!(publish.unwrap().0.version(), custom);
+	assert_eq!(subscribe.unwrap().0.version(), custom);
+
+	// Fail if there's no version in common.
+	client.codecs = message::Codecs::new();
+	client.codecs.register(custom, message::Draft04);
+	server.codecs = message::Codecs::default();
+
+	let (client_session, server_session) = harness::pair();
+	let (publish, _subscribe) = tokio::join!(
+		Session::accept_with(server_session, server),
+		tokio::time::timeout(
+			std::time::Duration::from_millis(100),
+			Session::connect_with(client_session, client)
+		)
+	);
+	assert!(matches!(publish, Err(SessionError::Version(..))));
+}
+
+#[tokio::test]
+async fn fetch() {
+	let (client, server) = harness::pair();
+	let raw = server.clone();
+
+	let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+	let (publish, publisher) = publish.unwrap();
+	let (subscribe, mut subscriber) = subscribe.unwrap();
+
+	tokio::spawn(publish.run());
+	let subscribe = tokio::spawn(subscribe.run());
+
+	let mut writer = announce(&publisher, "test");
+	let track = serve::Track::new("test".to_string(), "video".to_string()).cache(4);
+	let mut groups = writer.insert(track).unwrap().groups().unwrap();
+
+	let mut group = groups.append(0).unwrap();
+	group.write(Bytes::from_static(b"cached")).unwrap();
+	drop(group);
+	groups.append(0).unwrap();
+
+	let mut group = subscriber.fetch("test", "video", 0).await.unwrap();
+	assert_eq!(group.read_next().await.unwrap(), Some(Bytes::from_static(b"cached")));
+
+	let err = subscriber.fetch("test", "video", 9).await.err();
+	assert!(matches!(err, Some(SessionError::Serve(ServeError::NotFound))));
+
+	// The subscriber refuses a fetch from the publisher, without closing the session.
+	let (mut send, _recv) = raw.open_bi().await.unwrap();
+	send.write(b"fetch").await.unwrap();
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	assert!(!subscribe.is_finished());
+
+	let mut group = subscriber.fetch("test", "video", 0).await.unwrap();
+	assert_eq!(group.read_next().await.unwrap(), Some(Bytes::from_static(b"cached")));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn early() {
+	let (client, server) = harness::pair();
+
+	// The client's SETUP and SUBSCRIBE are sent before the server even accepts.
+	let config = SessionConfig::new(setup::Role::Subscriber).with_early();
+	let (subscribe, _, subscriber) = Session::connect_with(client, config).await.unwrap();
+	let mut subscriber = subscriber.unwrap();
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+	tokio::spawn(subscribe.run());
+
+	let (publish, mut publisher) = Publisher::accept(server).await.unwrap();
+	tokio::spawn(publish.run());
+
+	let (mut writer, _, reader) = serve::Tracks::new("test".to_string()).produce();
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	groups.append(0).unwrap().write(Bytes::from_static(b"early")).unwrap();
+
+	// The SUBSCRIBE arrived before any ANNOUNCE, so it's served from the unknown queue.
+	let subscribed = publisher.subscribed().await.unwrap();
+	tokio::spawn(Publisher::serve_subscribe(subscribed, reader));
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut group = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(group.read_next().await.unwrap(), Some(Bytes::from_static(b"early")));
+
+	// Fail when running if the server picks a different version.
+	let (client, server) = harness::pair();
+	let config = SessionConfig::new(setup::Role::Subscriber).with_early();
+	let (subscribe, _, _subscriber) = Session::connect_with(client, config).await.unwrap();
+
+	let (mut control, mut recver) = server.accept_bi().await.unwrap();
+	let mut buf = BytesMut::new();
+	let setup: setup::Client = recv(&mut recver, &mut buf).await;
+	assert_eq!(setup.versions.len(), 1);
+
+	let reply = setup::Server {
+		role: setup::Role::Publisher,
+		version: setup::Version(0xff0000aa),
+		params: Default::default(),
+	};
+	send(&mut control, &reply).await;
+
+	let res = tokio::time::timeout(Duration::from_secs(1), subscribe.run())
+		.await
+		.unwrap();
+	assert!(matches!(res, Err(SessionError::Version(..))));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn pending() {
+	let (mut publisher, mut subscriber) = connected_pair().await;
+	publisher.set_pending(Duration::from_millis(100));
+
+	// Subscribe before the broadcast goes live.
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+	tokio::time::sleep(Duration::from_millis(20)).await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	groups.append(0).unwrap().write(Bytes::from_static(b"live")).unwrap();
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut group = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(group.read_next().await.unwrap(), Some(Bytes::from_static(b"live")));
+
+	// Without an announce, the subscription is treated as unknown after the timeout.
+	let (track, _track_reader) = serve::Track::new("other".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let subscribed = tokio::time::timeout(Duration::from_millis(50), publisher.subscribed()).await;
+	assert!(subscribed.is_err(), "subscription wasn't held");
+
+	let subscribed = tokio::time::timeout(Duration::from_secs(1), publisher.subscribed()).await;
+	assert_eq!(subscribed.unwrap().unwrap().namespace, "other");
+
+	drop(groups);
+}
+
+#[derive(Debug, PartialEq)]
+struct Vendor(u64);
+
+impl Encode for Vendor {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.0.encode(w)
+	}
+}
+
+impl Decode for Vendor {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self(u64::decode(r)?))
+	}
+}
+
+impl setup::Extension for Vendor {
+	const ID: u64 = 0xff02;
+}
+
+#[tokio::test]
+async fn extensions() {
+	let mut client = SessionConfig::new(setup::Role::Subscriber);
+	client.extensions.set(Vendor(1)).unwrap();
+
+	let mut server = SessionConfig::new(setup::Role::Publisher);
+	server.extensions.set(Vendor(2)).unwrap();
+
+	let (client_session, server_session) = harness::pair();
+	let (publish, subscribe) = tokio::join!(
+		Session::accept_with(server_session, server),
+		Session::connect_with(client_session, client)
+	);
+
+	// Each side sees the peer's extensions.
+	let (publish, _, _) = publish.unwrap();
+	let (subscribe, _, _) = subscribe.unwrap();
+	assert_eq!(publish.extensions().get::<Vendor>().unwrap(), Some(Vendor(1)));
+	assert_eq!(subscribe.extensions().get::<Vendor>().unwrap(), Some(Vendor(2)));
+}
+
+#[tokio::test]
+async fn auth() {
+	let server = SessionConfig::new(setup::Role::Publisher).with_auth(|token| match token {
+		Some("secret") => Ok(Identity::new("alice")),
+		_ => Err(SessionError::Unauthorized),
+	});
+
+	let client = SessionConfig::new(setup::Role::Subscriber)
+		.with_token("secret")
+		.unwrap();
+	let (client_session, server_session) = harness::pair();
+	let (publish, subscribe) = tokio::join!(
+		Session::accept_with(server_session, server.clone()),
+		Session::connect_with(client_session, client)
+	);
+	assert_eq!(publish.unwrap().0.identity(), Some(&Identity::new("alice")));
+	assert!(subscribe.unwrap().0.identity().is_none());
+
+	// The client learns why it was rejected.
+	let client = SessionConfig::new(setup::Role::Subscriber).with_token("wrong").unwrap();
+	let (client_session, server_session) = harness::pair();
+	let (publish, subscribe) = tokio::join!(
+		Session::accept_with(server_session, server),
+		Session::connect_with(client_session, client)
+	);
+	assert!(matches!(publish, Err(SessionError::Unauthorized)));
+	assert_eq!(subscribe.err().unwrap().close().unwrap().code, 401);
+}
+
+#[tokio::test]
+async fn authorize() {
+	// Each user may only publish and subscribe to their own broadcast.
+	let server = SessionConfig::new(setup::Role::Both)
+		.with_auth(|token| Ok(Identity::new(token.unwrap_or_default())))
+		.with_authorizer(|identity, request| {
+			let namespace = match request {
+				AuthRequest::Announce { namespace } => namespace,
+				AuthRequest::Subscribe { namespace, .. } => namespace,
+			};
+
+			match identity {
+				Some(identity) if identity.name == *namespace => Ok(()),
+				_ => Err(ServeError::Unauthorized),
+			}
+		});
+	let client = SessionConfig::new(setup::Role::Both).with_token("alice").unwrap();
+
+	let (client_session, server_session) = harness::pair();
+	let (server_session, client_session) = tokio::join!(
+		Session::accept_with(server_session, server),
+		Session::connect_with(client_session, client)
+	);
+	let (server_session, publisher, _) = server_session.unwrap();
+	let (client_session, client_publisher, subscriber) = client_session.unwrap();
+	let (publisher, mut client_publisher, mut subscriber) =
+		(publisher.unwrap(), client_publisher.unwrap(), subscriber.unwrap());
+
+	tokio::spawn(server_session.run());
+	tokio::spawn(client_session.run());
+
+	// Keep the writers alive, otherwise the broadcasts are unannounced.
+	let mut writers = Vec::new();
+	for namespace in ["alice", "bob"] {
+		let mut writer = announce(&publisher, namespace);
+		let mut groups = writer.create("video").unwrap().groups().unwrap();
+		groups.append(0).unwrap().write(Bytes::from_static(b"hello")).unwrap();
+		writers.push((writer, groups));
+	}
+
+	let (track, alice) = serve::Track::new("alice".to_string(), "video".to_string()).produce();
+	let _alice = subscriber.subscribe_handle(track);
+	assert!(matches!(alice.mode().await.unwrap(), TrackReaderMode::Groups(_)));
+
+	let (track, bob) = serve::Track::new("bob".to_string(), "video".to_string()).produce();
+	let _bob = subscriber.subscribe_handle(track);
+	assert!(matches!(bob.mode().await, Err(ServeError::Unauthorized)));
+
+	// The server also rejects announcements for someone else's broadcast.
+	let (_writer, _, reader) = serve::Tracks::new("bob".to_string()).produce();
+	let err = client_publisher.announce(reader).await.unwrap_err();
+	assert!(matches!(err, SessionError::Serve(ServeError::Unauthorized)));
+}
+
+#[tokio::test]
+async fn limits() {
+	let server = SessionConfig::new(setup::Role::Both).with_limits(SessionLimits {
+		max_subscriptions: Some(1),
+		max_announces: Some(1),
+		..Default::default()
+	});
+
+	let (client_session, server_session) = harness::pair();
+	let (server_session, client_session) = tokio::join!(
+		Session::accept_with(server_session, server),
+		Session::connect_with(client_session, SessionConfig::default())
+	);
+	let (server_session, publisher, server_subscriber) = server_session.unwrap();
+	let (client_session, client_publisher, subscriber) = client_session.unwrap();
+	let (publisher, mut server_subscriber) = (publisher.unwrap(), server_subscriber.unwrap());
+	let (mut client_publisher, mut subscriber) = (client_publisher.unwrap(), subscriber.unwrap());
+
+	tokio::spawn(server_session.run());
+	tokio::spawn(client_session.run());
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	groups.append(0).unwrap().write(Bytes::from_static(b"hello")).unwrap();
+	let _audio = writer.create("audio").unwrap().groups().unwrap();
+
+	let (track, video) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _video = subscriber.subscribe_handle(track);
+	assert!(matches!(video.mode().await.unwrap(), TrackReaderMode::Groups(_)));
+
+	// Only one subscription is served at a time.
+	let (track, _audio_reader) = serve::Track::new("test".to_string(), "audio".to_string()).produce();
+	let audio = subscriber.subscribe_handle(track);
+	assert_eq!(audio.closed().await, Err(ServeError::Quota));
+
+	// Only one namespace may be announced at a time.
+	let (_first, _, reader) = serve::Tracks::new("first".to_string()).produce();
+	let mut announcer = client_publisher.clone();
+	tokio::spawn(async move { announcer.announce(reader).await });
+	let _first = server_subscriber.announced().await.unwrap();
+
+	let (_second, _, reader) = serve::Tracks::new("second".to_string()).produce();
+	let err = client_publisher.announce(reader).await.unwrap_err();
+	assert!(matches!(err, SessionError::Serve(ServeError::Quota)));
+}
+
+#[tokio::test]
+async fn stats() {
+	let (client, server) = harness::pair();
+
+	let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+	let (publish, publisher) = publish.unwrap();
+	let (subscribe, mut subscriber) = subscribe.unwrap();
+
+	let (publish_stats, subscribe_stats) = (publish.stats(), subscribe.stats());
+	tokio::spawn(publish.run());
+	tokio::spawn(subscribe.run());
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	let _group = open_group(&mut groups, b"hello");
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+	let mut group = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(group.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	let stats = subscribe_stats.get();
+	assert!(stats.bytes_sent > 0);
+	assert!(stats.bytes_received > 0);
+	assert_eq!(stats.streams_received, 1);
+	assert_eq!(stats.streams_sent, 0);
+	assert_eq!(stats.subscriptions.len(), 1);
+	assert_eq!(stats.subscriptions[0].name, "video");
+	assert_eq!(stats.subscriptions[0].stats.bytes, 5);
+	assert!(stats.subscriptions[0].bitrate.is_none());
+
+	let stats = publish_stats.get();
+	assert_eq!(stats.streams_sent, 1);
+	assert!(stats.bytes_sent > 0);
+	assert!(stats.subscriptions.is_empty());
+
+	// The watch channel includes the bitrates.
+	let mut watch = subscribe_stats.watch(std::time::Duration::from_millis(10));
+	watch.changed().await.unwrap();
+	assert_eq!(watch.borrow().subscriptions[0].bitrate, Some(0));
+}
+
+#[tokio::test]
+async fn go_away() {
+	let (client, server) = harness::pair();
+
+	let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+	let (publish, publisher) = publish.unwrap();
+	let (subscribe, mut subscriber) = subscribe.unwrap();
+
+	let closer = publish.closer();
+	let publish = tokio::spawn(publish.run());
+	let subscribe = tokio::spawn(subscribe.run());
+
+	let mut writer = announce(&publisher, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	writer.create("audio").unwrap().groups().unwrap();
+
+	// The session can't drain until the open group is dropped.
+	let mut group = open_group(&mut groups, b"hello");
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	let close = tokio::spawn(closer.close_gracefully(std::time::Duration::from_secs(10)));
+
+	let timeout = Duration::from_secs(1);
+	tokio::time::timeout(timeout, subscriber.draining())
+		.await
+		.expect("no GOAWAY");
+	tokio::time::timeout(timeout, publisher.draining())
+		.await
+		.expect("not draining");
+
+	// New subscriptions are refused, while the existing one is still served.
+	let (track, _track_reader) = serve::Track::new("test".to_string(), "audio".to_string()).produce();
+	let late = subscriber.subscribe_handle(track);
+	assert_eq!(late.closed().await, Err(ServeError::GoingAway));
+
+	group.write(Bytes::from_static(b"world")).unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"world")));
+
+	// The session closes once the group is finished.
+	drop(group);
+	tokio::time::timeout(timeout, close).await.unwrap().unwrap().unwrap();
+
+	// Our session stops cleanly, while the peer sees the session closed.
+	tokio::time::timeout(timeout, publish).await.unwrap().unwrap().unwrap();
+
+	let err = tokio::time::timeout(timeout, subscribe)
+		.await
+		.unwrap()
+		.unwrap()
+		.unwrap_err();
+	assert_eq!(err.close().map(|close| close.code), Some(0));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn go_away_live() {
+	let (client, server) = harness::pair();
+
+	let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+	let (publish, publisher) = publish.unwrap();
+	let (subscribe, mut subscriber) = subscribe.unwrap();
+
+	let closer = publish.closer();
+	let publish = tokio::spawn(publish.run());
+	let subscribe = tokio::spawn(subscribe.run());
+
+	// A live track sent over a single stream, which stays open for the whole subscription.
+	let mut writer = announce(&publisher, "test");
+	let mut stream = writer.create("video").unwrap().stream(0).unwrap();
+	stream.append().unwrap().write(Bytes::from_static(b"hello")).unwrap();
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let subscription = subscriber.subscribe_handle(track);
+
+	// Wait until the track stream has been opened.
+	let timeout = Duration::from_secs(1);
+	tokio::time::timeout(timeout, track_reader.mode())
+		.await
+		.expect("no stream")
+		.unwrap();
+
+	let close = tokio::spawn(closer.close_gracefully(Duration::from_secs(10)));
+
+	// The subscription ends at the group boundary, so the session drains well before the timeout.
+	tokio::time::timeout(timeout, close).await.unwrap().unwrap().unwrap();
+	tokio::time::timeout(timeout, publish).await.unwrap().unwrap().unwrap();
+	tokio::time::timeout(timeout, subscribe).await.unwrap().unwrap().ok();
+
+	drop(subscription);
+	drop(stream);
+}
+
+#[tokio::test]
+async fn duplicate_subscribe() {
+	let (client, server) = harness::pair();
+
+	// Act as a subscriber by hand, so we can reuse a subscribe ID.
+	let setup = async {
+		let (mut control, mut recver) = client.open_bi().await.unwrap();
+		let mut buf = BytesMut::new();
+
+		let versions: setup::Versions = [setup::Version::DRAFT_04].into();
+		let setup = setup::Client {
+			role: setup::Role::Subscriber,
+			versions,
+			params: Default::default(),
+		};
+
+		send(&mut control, &setup).await;
+		let _: setup::Server = recv(&mut recver, &mut buf).await;
+
+		(control, recver, buf)
+	};
+
+	let (publish, (mut control, mut recver, mut buf)) = tokio::join!(Publisher::accept(server), setup);
+	let (publish, mut publisher) = publish.unwrap();
+	tokio::spawn(publish.run());
+
+	let subscribe = message::Message::from(message::Subscribe {
+		id: 7,
+		track_alias: 7,
+		track_namespace: "test".to_string(),
+		track_name: "video".to_string(),
+		filter_type: message::FilterType::LatestGroup,
+		start: None,
+		end: None,
+		params: Default::default(),
+	});
+
+	send(&mut control, &subscribe).await;
+	let subscribed = publisher.subscribed().await.unwrap();
+
+	// The same ID is refused.
+	send(&mut control, &subscribe).await;
+	match recv(&mut recver, &mut buf).await {
+		message::Message::SubscribeError(err) => {
+			assert_eq!(err.id, 7);
+			assert_eq!(err.code, ServeError::Duplicate.code());
+		}
+		msg => panic!("unexpected message: {:?}", msg),
+	}
+
+	// The original subscription is still served.
+	assert_eq!(publisher.active_subscriptions().len(), 1);
+
+	let (writer, reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let _groups = writer.groups().unwrap();
+	tokio::spawn(subscribed.serve(reader));
+
+	match recv(&mut recver, &mut buf).await {
+		message::Message::SubscribeOk(ok) => assert_eq!(ok.id, 7),
+		msg => panic!("unexpected message: {:?}", msg),
+	}
+}
+
+#[tokio::test]
+async fn relay() {
+	let (origin, upstream) = connect(harness::pair()).await;
+	let (downstream, mut subscriber) = connect(harness::pair()).await;
+
+	tokio::spawn(Relay::new(upstream, downstream).run());
+
+	let mut writer = announce(&origin, "test");
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+
+	let mut group = groups.append(0).unwrap();
+	group.write(Bytes::from_static(b"hello")).unwrap();
+	drop(group);
+
+	let announced = subscriber.announced().await.unwrap();
+	assert_eq!(announced.namespace, "test");
+
+	// Both subscriptions are served by a single subscription to the origin.
+	let mut subscribes = Vec::new();
+	for _ in 0..2 {
+		let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+		let subscribe = subscriber.subscribe_handle(track);
+
+		let mut groups_reader = expect_groups(&track_reader).await;
+
+		let mut group = groups_reader.next().await.unwrap().unwrap();
+		assert_eq!(group.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+		subscribes.push((subscribe, track_reader, groups_reader));
+	}
+
+	assert_eq!(origin.active_subscriptions().len(), 1);
+
+	// The origin is unsubscribed once every downstream subscriber leaves.
+	drop(subscribes);
+
+	tokio::time::timeout(std::time::Duration::from_secs(5), async {
+		while !origin.active_subscriptions().is_empty() {
+			tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("origin still subscribed");
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn subscribe_range() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	let mut writer = announce(&publisher, "test");
+	let track = serve::Track::new("test".to_string(), "video".to_string()).cache(4);
+	let mut groups = writer.insert(track).unwrap().groups().unwrap();
+
+	for _ in 0..4 {
+		let mut group = groups.append(0).unwrap();
+		group.write(Bytes::from_static(b"hello")).unwrap();
+	}
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string())
+		.cache(4)
+		.order(serve::GroupOrder::Ascending)
+		.produce();
+	let _subscribe = subscriber.subscribe_range(track, 1, 2);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	assert_eq!(groups_reader.next().await.unwrap().unwrap().group_id, 1);
+	assert_eq!(groups_reader.next().await.unwrap().unwrap().group_id, 2);
+	assert!(groups_reader.next().await.unwrap().is_none());
+
+	// The publisher finishes after the final group, without waiting for another group to be produced.
+	tokio::time::timeout(std::time::Duration::from_secs(5), async {
+		while !publisher.active_subscriptions().is_empty() {
+			tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("publisher still serving");
+
+	// An empty range is rejected, rather than requesting a single group.
+	let (track, _track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let rejected = subscriber.subscribe_range(track, 2, 1);
+	assert_eq!(rejected.closed().await, Err(ServeError::OutOfOrder));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn subscribe_groups() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	let mut writer = announce(&publisher, "test");
+	let track = serve::Track::new("test".to_string(), "video".to_string()).cache(4);
+	let mut groups = writer.insert(track).unwrap().groups().unwrap();
+
+	for _ in 0..4 {
+		let mut group = groups.append(0).unwrap();
+		group.write(Bytes::from_static(b"hello")).unwrap();
+	}
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string())
+		.cache(4)
+		.order(serve::GroupOrder::Ascending)
+		.produce();
+	let _subscribe = subscriber.subscribe_groups(track, SubscribeStart::Group(1), 2);
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	assert_eq!(groups_reader.next().await.unwrap().unwrap().group_id, 1);
+	assert_eq!(groups_reader.next().await.unwrap().unwrap().group_id, 2);
+	assert!(groups_reader.next().await.unwrap().is_none());
+
+	// Zero groups delivers nothing, rather than a single group.
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let empty = subscriber.subscribe_groups(track, SubscribeStart::Group(1), 0);
+	assert_eq!(empty.closed().await, Err(ServeError::Done));
+	assert!(track_reader.mode().await.is_err());
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn subscribe_done_lost() {
+	let (client, server) = harness::pair();
+
+	// Act as a publisher by hand, so the final group never arrives.
+	let setup = async {
+		let (mut control, mut recver) = server.accept_bi().await.unwrap();
+		let mut buf = BytesMut::new();
+
+		let _: setup::Client = recv(&mut recver, &mut buf).await;
+		let reply = setup::Server {
+			role: setup::Role::Publisher,
+			version: setup::Version::DRAFT_04,
+			params: Default::default(),
+		};
+		send(&mut control, &reply).await;
+
+		(control, recver, buf)
+	};
+
+	let (subscribe, (mut control, mut recver, mut buf)) = tokio::join!(Subscriber::connect(client), setup);
+	let (subscribe, mut subscriber) = subscribe.unwrap();
+	tokio::spawn(subscribe.run());
+
+	let (track, _track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let subscription = subscriber.subscribe_handle(track);
+
+	let id = match recv(&mut recver, &mut buf).await {
+		message::Message::Subscribe(msg) => msg.id,
+		msg => panic!("unexpected message: {:?}", msg),
+	};
+
+	let ok = message::Message::from(message::SubscribeOk {
+		id,
+		expires: None,
+		latest: None,
+		start: None,
+	});
+	send(&mut control, &ok).await;
+
+	let done = message::Message::from(message::SubscribeDone {
+		id,
+		code: ServeError::Done.code(),
+		reason: "done".to_string(),
+		last: Some((3, 0)),
+	});
+	send(&mut control, &done).await;
+
+	// The subscription waits for the final group, but gives up after the timeout.
+	let timeout = Subscriber::FINISH_TIMEOUT + Duration::from_secs(1);
+	let closed = tokio::time::timeout(timeout, subscription.closed()).await;
+	assert_eq!(closed.expect("never finished"), Err(ServeError::Done));
+}
+
+#[tokio::test]
+async fn announcements() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	let mut early = subscriber.announcements("");
+	let mut other = subscriber.announcements("other/");
+
+	// Not read until the namespace has been withdrawn.
+	let mut idle = subscriber.announcements("");
+
+	let (_writer, _, reader) = serve::Tracks::new("test".to_string()).produce();
+	let mut session = publisher.clone();
+	let announce = tokio::spawn(async move { session.announce(reader).await });
+
+	let mut announced = subscriber.announced().await.unwrap();
+	announced.ok().unwrap();
+
+	// A late handle starts with the namespaces already announced.
+	let mut late = subscriber.announcements("");
+
+	for announcements in [&mut early, &mut late] {
+		match announcements.next().await.unwrap() {
+			AnnouncedEvent::Announced(info) => assert_eq!(info.namespace, "test"),
+			event => panic!("unexpected event: {:?}", event),
+		}
+	}
+
+	// Withdrawing the namespace is seen by every handle.
+	announce.abort();
+
+	for announcements in [&mut early, &mut late] {
+		match announcements.next().await.unwrap() {
+			AnnouncedEvent::Unannounced(info) => assert_eq!(info.namespace, "test"),
+			event => panic!("unexpected event: {:?}", event),
+		}
+	}
+
+	// The prefix skipped the first namespace, while the idle handle never saw it at all.
+	let _other = self::announce(&publisher, "other/1");
+
+	for announcements in [&mut other, &mut idle] {
+		match announcements.next().await.unwrap() {
+			AnnouncedEvent::Announced(info) => assert_eq!(info.namespace, "other/1"),
+			event => panic!("unexpected event: {:?}", event),
+		}
+	}
+}
+
+#[tokio::test]
+async fn announced_prefix() {
+	let (client, server) = harness::pair();
+
+	let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+	let (publish, publisher) = publish.unwrap();
+	let (mut subscribe, mut subscriber) = subscribe.unwrap();
+
+	// Send the filter before anything is announced.
+	let mut room1 = subscriber.announced_prefix("room/1/");
+
+	// The PONG is only sent after the publisher processed the filter.
+	let interval = std::time::Duration::from_millis(10);
+	subscribe.set_keepalive(interval, Duration::from_secs(1));
+	let rtt = subscribe.rtt();
+
+	tokio::spawn(publish.run());
+	tokio::spawn(subscribe.run());
+
+	while rtt.get().is_none() {
+		tokio::time::sleep(interval).await;
+	}
+
+	let _writers: Vec<_> = ["room/1/alice", "room/2/bob"]
+		.into_iter()
+		.map(|namespace| announce(&publisher, namespace))
+		.collect();
+
+	match room1.next().await.unwrap() {
+		AnnouncedEvent::Announced(info) => assert_eq!(info.namespace, "room/1/alice"),
+		event => panic!("unexpected event: {:?}", event),
+	}
+
+	// The other room was never sent.
+	let announced = subscriber.announced().await.unwrap();
+	assert_eq!(announced.namespace, "room/1/alice");
+
+	let timeout = std::time::Duration::from_millis(100);
+	assert!(matches!(
+		subscriber.announced_timeout(timeout).await,
+		Err(ServeError::Timeout)
+	));
+
+	// Adding a prefix announces the namespaces that were held back.
+	let mut room2 = subscriber.announced_prefix("room/2/");
+	match room2.next().await.unwrap() {
+		AnnouncedEvent::Announced(info) => assert_eq!(info.namespace, "room/2/bob"),
+		event => panic!("unexpected event: {:?}", event),
+	}
+}
+
+#[tokio::test]
+async fn withdraw() {
+	let (mut publisher, mut subscriber) = connected_pair().await;
+
+	let mut announcements = subscriber.announcements("");
+
+	let (writer, _, reader) = serve::Tracks::new("test".to_string()).produce();
+	let announce = tokio::spawn(async move { publisher.announce(reader).await });
+
+	let mut announced = subscriber.announced().await.unwrap();
+	announced.ok().unwrap();
+	assert!(matches!(announcements.next().await, Some(AnnouncedEvent::Announced(_))));
+
+	// Closing the broadcast stops serving it and unannounces the namespace.
+	writer.close(ServeError::Done).unwrap();
+	assert!(matches!(
+		announce.await.unwrap(),
+		Err(SessionError::Serve(ServeError::Done))
+	));
+
+	assert!(matches!(
+		announcements.next().await,
+		Some(AnnouncedEvent::Unannounced(_))
+	));
+	assert!(announced.closed().await.is_err());
+}
+
+#[tokio::test]
+async fn announced_mixed() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	let one = announce(&publisher, "one");
+	let mut first = match subscriber.announced_event().await.unwrap() {
+		AnnouncedEvent::Announced(announced) => announced,
+		AnnouncedEvent::Unannounced(info) => panic!("unexpected withdrawal: {}", info.namespace),
+	};
+	assert_eq!(first.info.namespace, "one");
+	first.ok().unwrap();
+
+	// The withdrawal skipped by announced() is still returned by announced_event().
+	one.close(ServeError::Done).unwrap();
+	assert!(first.closed().await.is_err());
+	let _two = announce(&publisher, "two");
+
+	let mut announced = subscriber.announced().await.unwrap();
+	assert_eq!(announced.info.namespace, "two");
+	announced.ok().unwrap();
+
+	match subscriber.announced_event().await.unwrap() {
+		AnnouncedEvent::Unannounced(info) => assert_eq!(info.namespace, "one"),
+		AnnouncedEvent::Announced(announced) => panic!("unexpected announce: {}", announced.info.namespace),
+	}
+}
+
+#[tokio::test]
+async fn large_datagram() {
+	let (publisher, mut subscriber) = connected_pair().await;
+
+	let mut writer = announce(&publisher, "test");
+	let mut datagrams = writer.create("audio").unwrap().datagrams().unwrap();
+
+	let datagram = |object_id, payload: Bytes| serve::Datagram {
+		group_id: 0,
+		object_id,
+		priority: 0,
+		status: data::ObjectStatus::Object,
+		payload,
+	};
+
+	datagrams.write(datagram(0, Bytes::from_static(b"small"))).unwrap();
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "audio".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut datagrams_reader = match track_reader.mode().await.unwrap() {
+		TrackReaderMode::Datagrams(datagrams) => datagrams,
+		_ => panic!("wrong mode"),
+	};
+
+	let small = datagrams_reader.read().await.unwrap().unwrap();
+	assert_eq!(small.payload, Bytes::from_static(b"small"));
+
+	// Larger than any datagram, so it's sent on a stream and still delivered as a datagram.
+	let payload = Bytes::from(vec![7u8; 4096]);
+	datagrams.write(datagram(1, payload.clone())).unwrap();
+
+	let large = datagrams_reader.read().await.unwrap().unwrap();
+	assert_eq!(large.object_id, 1);
+	assert_eq!(large.payload, payload);
+
+	// Too large to buffer, so the stream is stopped without failing the subscription.
+	let oversized = Bytes::from(vec![7u8; Subscriber::MAX_DATAGRAM_STREAM + 1]);
+	datagrams.write(datagram(2, oversized)).unwrap();
+
+	let res = tokio::time::timeout(Duration::from_millis(100), datagrams_reader.read()).await;
+	assert!(res.is_err(), "oversized datagram was delivered");
+
+	datagrams.write(datagram(3, Bytes::from_static(b"after"))).unwrap();
+	let after = datagrams_reader.read().await.unwrap().unwrap();
+	assert_eq!(after.object_id, 3);
+}
+
+#[tokio::test]
+async fn subscribe_retry() {
+	let (mut publisher, mut subscriber) = connected_pair().await;
+
+	let (mut writer, _, reader) = serve::Tracks::new("test".to_string()).produce();
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	let _group = open_group(&mut groups, b"retried");
+
+	// Reject the first subscribe as if the track wasn't routed yet, then serve the retry.
+	tokio::spawn(async move {
+		let subscribed = publisher.subscribed().await.unwrap();
+		subscribed.close(ServeError::NotFound).unwrap();
+
+		let subscribed = publisher.subscribed().await.unwrap();
+		Publisher::serve_subscribe(subscribed, reader).await
+	});
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	tokio::spawn(async move { subscriber.subscribe_retry(track, Duration::from_secs(1)).await });
+
+	let mut groups_reader = tokio::time::timeout(Duration::from_secs(1), expect_groups(&track_reader))
+		.await
+		.unwrap();
+
+	let mut group = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(group.read_next().await.unwrap(), Some(Bytes::from_static(b"retried")));
+}
+
+#[tokio::test]
+async fn redirect() {
+	let (mut publisher, mut subscriber) = connected_pair().await;
+
+	// The relay redirects the video track to the origin, and anything else to a relay that redirects to itself.
+	tokio::spawn(async move {
+		while let Some(subscribed) = publisher.subscribed().await {
+			let url = match subscribed.name.as_str() {
+				"video" => "https://origin",
+				_ => "https://loop",
+			};
+			subscribed.redirect(url.to_string()).unwrap();
+		}
+	});
+
+	let (mut writer, _, reader) = serve::Tracks::new("test".to_string()).produce();
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+	let _group = open_group(&mut groups, b"hello");
+
+	subscriber.follow_redirects(4, move |url| {
+		let reader = reader.clone();
+
+		async move {
+			let (client, server) = harness::pair();
+
+			tokio::spawn(async move {
+				let (session, mut publisher) = Publisher::accept(server).await.unwrap();
+				tokio::spawn(session.run());
+
+				match url.as_str() {
+					"https://origin" => publisher.announce(reader).await.ok(),
+					_ => {
+						while let Some(subscribed) = publisher.subscribed().await {
+							subscribed.redirect(url.clone()).unwrap();
+						}
+						None
+					}
+				}
+			});
+
+			Ok(client)
+		}
+	});
+
+	let (track, track_reader) = serve::Track::new("test".to_string(), "video".to_string()).produce();
+	let mut video = subscriber.clone();
+	tokio::spawn(async move { video.subscribe(track).await });
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut group = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(group.read_next().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	// Each origin is only visited once, so a redirect loop fails.
+	let (track, _track_reader) = serve::Track::new("test".to_string(), "audio".to_string()).produce();
+	let err = subscriber.subscribe(track).await.unwrap_err();
+	assert_eq!(err, ServeError::Redirect("https://loop".to_string()));
+
+	drop(groups);
+}
+
+#[tokio::test]
+async fn reconnect() {
+	let (mut writer, _, reader) = serve::Tracks::new("server".to_string()).produce();
+	let mut groups = writer.create("video").unwrap().groups().unwrap();
+
+	// Each connection is accepted by a new server session, which reports the client's announcements.
+	let (accept, mut accepted) = tokio::sync::mpsc::unbounded_channel::<transport::Memory>();
+	let (announced, mut announces) = tokio::sync::mpsc::unbounded_channel();
+
+	tokio::spawn(async move {
+		while let Some(server) = accepted.recv().await {
+			let (session, publisher, subscriber) = Session::accept(transport::Session::new(server)).await.unwrap();
+			let (mut publisher, mut subscriber) = (publisher.unwrap(), subscriber.unwrap());
+
+			let reader = reader.clone();
+			let announced = announced.clone();
+
+			tokio::spawn(session.run());
+			tokio::spawn(async move { publisher.announce(reader).await });
+			tokio::spawn(async move {
+				while let Some(mut announce) = subscriber.announced().await {
+					announce.ok().unwrap();
+					announced.send(announce.namespace.clone()).ok();
+				}
+			});
+		}
+	});
+
+	let (reconnect, mut subscriber) = Reconnect::new(ReconnectConfig {
+		backoff_min: Duration::from_millis(10),
+		announce_wait: Duration::from_millis(100),
+		..Default::default()
+	});
+	let status = reconnect.status();
+
+	let (_client_writer, _, client_reader) = serve::Tracks::new("client".to_string()).produce();
+	reconnect.publisher().announce(client_reader).unwrap();
+
+	let (connected, mut connection) = tokio::sync::mpsc::unbounded_channel();
+	tokio::spawn(reconnect.run(move || {
+		let (client, server) = transport::Memory::pair(Default::default());
+		let client = transport::Session::new(client);
+
+		accept.send(server).ok();
+		connected.send(client.clone()).ok();
+
+		async move { Ok(client) }
+	}));
+
+	let (track, track_reader) = serve::Track::new("server".to_string(), "video".to_string()).produce();
+	let _subscribe = subscriber.subscribe_handle(track);
+
+	let mut group = groups.append(0).unwrap();
+	group.write(Bytes::from_static(b"one")).unwrap();
+
+	let mut groups_reader = expect_groups(&track_reader).await;
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"one")));
+	assert_eq!(announces.recv().await.unwrap(), "client");
+
+	let mut server = subscriber.announced().await.unwrap();
+	assert_eq!(server.namespace, "server");
+	server.ok().unwrap();
+
+	// Kill the connection, which isn't a clean close.
+	connection.recv().await.unwrap().close(1, "network changed");
+
+	assert!(matches!(status.changed().await, ReconnectStatus::Reconnecting { .. }));
+	assert_eq!(status.changed().await, ReconnectStatus::Connected);
+
+	// The announcement is replayed on the new session.
+	assert_eq!(announces.recv().await.unwrap(), "client");
+
+	// The server announces again, so we keep the same handle instead of reporting it as withdrawn.
+	assert!(matches!(
+		subscriber.announced_timeout(Duration::from_millis(200)).await,
+		Err(ServeError::Timeout)
+	));
+	assert!(tokio::time::timeout(Duration::from_millis(10), server.closed())
+		.await
+		.is_err());
+
+	// The same track reader continues with the next group.
+	let mut group = groups.append(0).unwrap();
+	group.write(Bytes::from_static(b"two")).unwrap();
+
+	let mut reader = groups_reader.next().await.unwrap().unwrap();
+	assert_eq!(reader.group_id, 1);
+	assert_eq!(reader.read_next().await.unwrap(), Some(Bytes::from_static(b"two")));
+}
+
+// Wraps a transport, reporting a path that the test changes as if the client moved to a new network.
+struct Migrating {
+	inner: transport::Session,
+	path: Arc<Mutex<transport::Path>>,
+}
+
+impl transport::Transport for Migrating {
+	fn open_uni(&self) -> BoxFuture<'_, Result<transport::SendStream, transport::TransportError>> {
+		self.inner.open_uni().boxed()
+	}
+
+	fn open_bi(
+		&self,
+	) -> BoxFuture<'_, Result<(transport::SendStream, transport::RecvStream), transport::TransportError>> {
+		self.inner.open_bi().boxed()
+	}
+
+	fn accept_uni(&self) -> BoxFuture<'_, Result<transport::RecvStream, transport::TransportError>> {
+		self.inner.accept_uni().boxed()
+	}
+
+	fn accept_bi(
+		&self,
+	) -> BoxFuture<'_, Result<(transport::SendStream, transport::RecvStream), transport::TransportError>> {
+		self.inner.accept_bi().boxed()
+	}
+
+	fn send_datagram(&self, payload: Bytes) -> BoxFuture<'_, Result<(), transport::TransportError>> {
+		self.inner.send_datagram(payload).boxed()
+	}
+
+	fn recv_datagram(&self) -> BoxFuture<'_, Result<Bytes, transport::TransportError>> {
+		self.inner.recv_datagram().boxed()
+	}
+
+	fn max_datagram_size(&self) -> BoxFuture<'_, usize> {
+		self.inner.max_datagram_size().boxed()
+	}
+
+	fn close(&self, code: u32, reason: &str) {
+		self.inner.close(code, reason)
+	}
+
+	fn closed(&self) -> BoxFuture<'_, transport::TransportError> {
+		self.inner.closed().boxed()
+	}
+
+	fn path(&self) -> Option<transport::Path> {
+		Some(self.path.lock().unwrap().clone())
+	}
+}
+
+#[tokio::test]
+async fn migrate() {
+	let (client, server) = harness::pair_memory(Default::default());
+
+	let path = Arc::new(Mutex::new(transport::Path {
+		remote: "192.0.2.1:4443".parse().unwrap(),
+		rtt: Duration::from_millis(100),
+		cwnd: 125_000,
+	}));
+	let server = transport::Session::new(Migrating {
+		inner: server,
+		path: path.clone(),
+	});
+
+	let (publish, subscribe) = tokio::join!(Publisher::accept(server), Subscriber::connect(client));
+	let (mut publish, _publisher) = publish.unwrap();
+	let (subscribe, _subscriber) = subscribe.unwrap();
+
+	// Only PING after a migration.
+	publish.set_keepalive(Duration::from_secs(3600), Duration::from_secs(1));
+	let rtt = publish.rtt();
+	let session_path = publish.path();
+	let stats = publish.stats();
+
+	tokio::spawn(publish.run());
+	tokio::spawn(subscribe.run());
+
+	assert_eq!(stats.get().path.unwrap().bandwidth(), Some(1_250_000));
+
+	// The client moved to a new network, which the congestion controller starts over with.
+	let changed = tokio::spawn(async move { session_path.changed().await });
+	tokio::task::yield_now().await;
+
+	*path.lock().unwrap() = transport::Path {
+		remote: "198.51.100.7:50000".parse().unwrap(),
+		rtt: Duration::from_millis(40),
+		cwnd: 12_000,
+	};
+
+	let migrated = tokio::time::timeout(Duration::from_secs(2), changed)
+		.await
+		.expect("migration not reported")
+		.unwrap()
+		.unwrap();
+	assert_eq!(migrated.remote, "198.51.100.7:50000".parse().unwrap());
+	assert_eq!(migrated.bandwidth(), Some(300_000));
+
+	// The round trip is measured again immediately.
+	tokio::time::timeout(Duration::from_secs(1), async {
+		while rtt.get().is_none() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("no PONG received after migrating");
+}